@@ -1,7 +1,7 @@
 pub use ipc_channel::ipc::IpcSender;
 use ipc_channel::ipc::{self, IpcReceiver};
 
-use crate::widget::network_statistic_large::LogoPreset;
+use crate::widget::{WidgetTheme, network_statistic_large::LogoPreset};
 
 #[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
 pub struct StatisticMessage {
@@ -11,11 +11,23 @@ pub struct StatisticMessage {
     pub upload_speed: u64,
 }
 
+/// Downsampled speed history for sparklines, `None` marks a gap
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct StatisticHistoryMessage {
+    pub resolution_secs: u64,
+    pub download: Vec<Option<u64>>,
+    pub upload: Vec<Option<u64>>,
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub enum Message {
     Stop,
     UpdateStatistic(StatisticMessage),
+    /// Optional extended frame, widgets may ignore it
+    UpdateStatisticHistory(StatisticHistoryMessage),
     UpdateLogo(LogoPreset),
+    SetAlwaysOnTop(bool),
+    SetTheme(WidgetTheme),
 }
 
 pub struct IPCServer {
@@ -1,7 +1,9 @@
 pub mod network_statistic_large;
 pub mod network_statistic_small;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use eframe::egui::{self, ViewportCommand, WindowLevel};
 
 pub use network_statistic_large::NyanpasuNetworkStatisticLargeWidget;
 pub use network_statistic_small::NyanpasuNetworkStatisticSmallWidget;
@@ -25,6 +27,110 @@ fn get_window_state_path() -> std::io::Result<PathBuf> {
     Ok(path)
 }
 
+/// Preferences toggled from the widget itself, stored next to the window state
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WidgetPreferences {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub always_on_top: Option<bool>,
+}
+
+impl WidgetPreferences {
+    /// The preferences file of the given window state file
+    pub fn path_of(window_state_path: &Path) -> PathBuf {
+        window_state_path.with_extension("prefs.json")
+    }
+
+    pub fn load(path: &Path) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn get_preferences_path() -> std::io::Result<PathBuf> {
+    get_window_state_path().map(|path| WidgetPreferences::path_of(&path))
+}
+
+/// Resolve the always-on-top flag, `NYANPASU_EGUI_ALWAYS_ON_TOP` takes precedence over the saved preference
+fn resolve_always_on_top() -> bool {
+    if let Ok(value) = std::env::var("NYANPASU_EGUI_ALWAYS_ON_TOP") {
+        return matches!(value.trim(), "1" | "true");
+    }
+    get_preferences_path()
+        .ok()
+        .and_then(|path| WidgetPreferences::load(&path).always_on_top)
+        .unwrap_or(true)
+}
+
+fn window_level(always_on_top: bool) -> WindowLevel {
+    if always_on_top {
+        WindowLevel::AlwaysOnTop
+    } else {
+        WindowLevel::Normal
+    }
+}
+
+/// Apply the flag to the viewport and remember it for the next launch
+fn set_always_on_top(ctx: &egui::Context, always_on_top: bool) {
+    ctx.send_viewport_cmd(ViewportCommand::WindowLevel(window_level(always_on_top)));
+    let saved = get_preferences_path()
+        .map_err(anyhow::Error::from)
+        .and_then(|path| {
+            let mut prefs = WidgetPreferences::load(&path);
+            prefs.always_on_top = Some(always_on_top);
+            prefs.save(&path)
+        });
+    if let Err(e) = saved {
+        eprintln!("Failed to save widget preferences: {e}");
+    }
+}
+
+/// Color scheme pushed from the main app
+#[derive(
+    Debug, Default, serde::Serialize, serde::Deserialize, specta::Type, Copy, Clone, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WidgetTheme {
+    Light,
+    #[default]
+    Dark,
+    System,
+}
+
+impl From<WidgetTheme> for egui::ThemePreference {
+    fn from(theme: WidgetTheme) -> Self {
+        match theme {
+            WidgetTheme::Light => egui::ThemePreference::Light,
+            WidgetTheme::Dark => egui::ThemePreference::Dark,
+            WidgetTheme::System => egui::ThemePreference::System,
+        }
+    }
+}
+
+fn set_theme(ctx: &egui::Context, theme: WidgetTheme) {
+    ctx.set_theme(egui::ThemePreference::from(theme));
+    ctx.request_repaint();
+}
+
+/// Right-click menu shared by all widgets
+fn show_context_menu(response: &egui::Response, always_on_top: &mut bool) {
+    response.context_menu(|ui| {
+        if ui.checkbox(always_on_top, "Always on Top").changed() {
+            set_always_on_top(ui.ctx(), *always_on_top);
+            ui.close();
+        }
+    });
+}
+
 // Platform-specific activation policy moved to tauri/src/utils/platform.rs
 
 // pub fn launch_widget<'app, T: Send + Sync + Sized, A: EframeAppCreator<'app, T>>(
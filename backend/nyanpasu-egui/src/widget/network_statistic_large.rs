@@ -33,6 +33,10 @@ const DARK_MODE_STATUS_SHEET_COLOR: Color32 =
 const STATUS_ICON_CONTAINER_COLOR: Color32 = Color32::from_rgb(79, 55, 139);
 static LOGO_CONTAINER_COLOR: LazyLock<Color32> =
     LazyLock::new(|| Color32::from_rgba_unmultiplied(79, 55, 139, GLOBAL_ALPHA));
+static DOWNLOAD_SPARKLINE_COLOR: LazyLock<Color32> =
+    LazyLock::new(|| Color32::from_rgba_unmultiplied(157, 218, 169, 96));
+static UPLOAD_SPARKLINE_COLOR: LazyLock<Color32> =
+    LazyLock::new(|| Color32::from_rgba_unmultiplied(202, 135, 227, 96));
 
 // Icons
 const DOWNLOAD_ICON: &[u8] = include_bytes!("../../assets/download.svg");
@@ -101,6 +105,44 @@ fn use_dark_purple_accent(style: &mut Style) {
     };
 }
 
+/// Map a speed history onto polylines inside `rect`, `None` entries break the line
+fn sparkline_segments(history: &[Option<u64>], max: u64, rect: egui::Rect) -> Vec<Vec<egui::Pos2>> {
+    if history.len() < 2 || max == 0 {
+        return Vec::new();
+    }
+    let step = rect.width() / (history.len() - 1) as f32;
+    let mut segments = Vec::new();
+    let mut current = Vec::new();
+    for (i, value) in history.iter().enumerate() {
+        match value {
+            Some(value) => {
+                let y = rect.bottom() - rect.height() * (*value as f32 / max as f32);
+                current.push(egui::pos2(rect.left() + step * i as f32, y));
+            }
+            None if !current.is_empty() => segments.push(std::mem::take(&mut current)),
+            None => {}
+        }
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    segments
+}
+
+fn paint_sparkline(
+    painter: &egui::Painter,
+    rect: egui::Rect,
+    history: &[Option<u64>],
+    max: u64,
+    color: Color32,
+) {
+    for points in sparkline_segments(history, max, rect) {
+        if points.len() > 1 {
+            painter.add(egui::Shape::line(points, Stroke::new(1.0, color)));
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum LogoPreset {
@@ -118,6 +160,9 @@ pub struct NyanpasuNetworkStatisticLargeWidgetInner {
     upload_total: u64,
     download_speed: u64,
     upload_speed: u64,
+    download_history: Vec<Option<u64>>,
+    upload_history: Vec<Option<u64>>,
+    always_on_top: bool,
 
     // eframe ctx
     egui_ctx: egui::Context,
@@ -156,6 +201,9 @@ impl NyanpasuNetworkStatisticLargeWidget {
                 upload_total: 0,
                 download_speed: 0,
                 upload_speed: 0,
+                download_history: Vec::new(),
+                upload_history: Vec::new(),
+                always_on_top: super::resolve_always_on_top(),
             })),
         };
         let this = widget.clone();
@@ -197,7 +245,7 @@ impl NyanpasuNetworkStatisticLargeWidget {
                 .with_inner_size([206.0, 60.0])
                 .with_decorations(false)
                 .with_transparent(true)
-                .with_always_on_top()
+                .with_window_level(super::window_level(super::resolve_always_on_top()))
                 .with_drag_and_drop(true)
                 .with_resizable(false)
                 .with_taskbar(false),
@@ -225,10 +273,22 @@ impl NyanpasuNetworkStatisticLargeWidget {
                 this.upload_speed = statistic.upload_speed;
                 this.request_repaint();
             }
+            Message::UpdateStatisticHistory(history) => {
+                this.download_history = history.download;
+                this.upload_history = history.upload;
+                this.request_repaint();
+            }
             Message::UpdateLogo(logo_preset) => {
                 this.logo_preset = logo_preset;
                 this.request_repaint();
             }
+            Message::SetAlwaysOnTop(always_on_top) => {
+                this.always_on_top = always_on_top;
+                super::set_always_on_top(&this.egui_ctx, always_on_top);
+            }
+            Message::SetTheme(theme) => {
+                super::set_theme(&this.egui_ctx, theme);
+            }
             Message::Stop => {
                 std::thread::spawn(move || {
                     // wait for 5 seconds to ensure the widget is closed, or the app will be terminated
@@ -249,6 +309,7 @@ impl eframe::App for NyanpasuNetworkStatisticLargeWidget {
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let this = self.inner.read();
+        let mut always_on_top = this.always_on_top;
         let visuals = &ctx.style().visuals;
 
         egui::CentralPanel::default()
@@ -259,9 +320,24 @@ impl eframe::App for NyanpasuNetworkStatisticLargeWidget {
                     .inner_margin(Margin::symmetric(9, 6)),
             )
             .show(ctx, |ui| {
-                if ui.interact(ui.max_rect(), Id::new("window-drag"), Sense::drag()).dragged() {
+                let response = ui.interact(ui.max_rect(), Id::new("window-drag"), Sense::click_and_drag());
+                if response.dragged() {
                     ctx.send_viewport_cmd(ViewportCommand::StartDrag);
                 }
+                super::show_context_menu(&response, &mut always_on_top);
+
+                // Speed history sparklines behind the statistics, both on the same scale
+                let max = this
+                    .download_history
+                    .iter()
+                    .chain(this.upload_history.iter())
+                    .flatten()
+                    .copied()
+                    .max()
+                    .unwrap_or(0);
+                let chart = ui.max_rect();
+                paint_sparkline(ui.painter(), chart, &this.download_history, max, *DOWNLOAD_SPARKLINE_COLOR);
+                paint_sparkline(ui.painter(), chart, &this.upload_history, max, *UPLOAD_SPARKLINE_COLOR);
 
                 let available_height = ui.available_height();
                 ui.horizontal_centered(|ui| {
@@ -433,5 +509,10 @@ impl eframe::App for NyanpasuNetworkStatisticLargeWidget {
                     });
                 });
             });
+
+        if always_on_top != this.always_on_top {
+            drop(this);
+            self.inner.write().always_on_top = always_on_top;
+        }
     }
 }
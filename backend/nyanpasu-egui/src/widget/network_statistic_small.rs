@@ -92,6 +92,7 @@ struct NyanpasuNetworkStatisticSmallWidgetState {
     // upload_total: u64,
     download_speed: u64,
     upload_speed: u64,
+    always_on_top: bool,
 
     // eframe ctx
     egui_ctx: egui::Context,
@@ -121,6 +122,7 @@ impl NyanpasuNetworkStatisticSmallWidget {
                 egui_ctx: cc.egui_ctx.clone(),
                 download_speed: 0,
                 upload_speed: 0,
+                always_on_top: super::resolve_always_on_top(),
             })),
         };
         let this = widget.clone();
@@ -162,7 +164,7 @@ impl NyanpasuNetworkStatisticSmallWidget {
                 .with_inner_size([80.0, 32.0])
                 .with_decorations(false)
                 .with_transparent(true)
-                .with_always_on_top()
+                .with_window_level(super::window_level(super::resolve_always_on_top()))
                 .with_drag_and_drop(true)
                 .with_resizable(false)
                 .with_taskbar(false),
@@ -190,6 +192,13 @@ impl NyanpasuNetworkStatisticSmallWidget {
                 this.upload_speed = statistic.upload_speed;
                 this.request_repaint();
             }
+            Message::SetAlwaysOnTop(always_on_top) => {
+                this.always_on_top = always_on_top;
+                super::set_always_on_top(&this.egui_ctx, always_on_top);
+            }
+            Message::SetTheme(theme) => {
+                super::set_theme(&this.egui_ctx, theme);
+            }
             Message::Stop => {
                 std::thread::spawn(move || {
                     // wait for 5 seconds to ensure the widget is closed, or the app will be terminated
@@ -198,6 +207,8 @@ impl NyanpasuNetworkStatisticSmallWidget {
                 });
                 this.egui_ctx.send_viewport_cmd(ViewportCommand::Close);
             }
+            // The small widget has no room for the sparkline
+            Message::UpdateStatisticHistory(_) => {}
             _ => {
                 eprintln!("Unsupported message: {msg:?}");
             }
@@ -214,6 +225,7 @@ impl eframe::App for NyanpasuNetworkStatisticSmallWidget {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let visuals = &ctx.style().visuals;
         let this = self.state.read();
+        let mut always_on_top = this.always_on_top;
 
         egui::CentralPanel::default()
             .frame(
@@ -227,12 +239,15 @@ impl eframe::App for NyanpasuNetworkStatisticSmallWidget {
                     .inner_margin(Margin::same(4)),
             )
             .show(ctx, |ui| {
-                if ui
-                    .interact(ui.max_rect(), Id::new("window-drag"), Sense::drag())
-                    .dragged()
-                {
+                let response = ui.interact(
+                    ui.max_rect(),
+                    Id::new("window-drag"),
+                    Sense::click_and_drag(),
+                );
+                if response.dragged() {
                     ctx.send_viewport_cmd(ViewportCommand::StartDrag);
                 }
+                super::show_context_menu(&response, &mut always_on_top);
                 ui.horizontal(|ui| {
                     ui.allocate_ui(Vec2::new(24.0, 24.0), |ui| {
                         egui::Frame::NONE
@@ -295,5 +310,10 @@ impl eframe::App for NyanpasuNetworkStatisticSmallWidget {
                     });
                 })
             });
+
+        if always_on_top != this.always_on_top {
+            drop(this);
+            self.state.write().always_on_top = always_on_top;
+        }
     }
 }
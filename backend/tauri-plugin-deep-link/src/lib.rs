@@ -12,6 +12,10 @@ mod platform_impl;
 #[path = "macos.rs"]
 mod platform_impl;
 
+mod shared;
+
+pub use shared::verify_scheme_registered;
+
 static ID: OnceCell<String> = OnceCell::new();
 
 /// This function is meant for use-cases where the default [`prepare()`] function can't be used.
@@ -23,15 +27,40 @@ pub fn set_identifier(identifier: &str) -> Result<()> {
         .map_err(|_| ErrorKind::AlreadyExists.into())
 }
 
-// Consider adding a function to register without starting the listener.
-
 /// Registers a handler for the given scheme.
 ///
 /// ## Platform-specific:
 ///
 /// - **macOS**: On macOS schemes must be defined in an Info.plist file, therefore this function only calls [`listen()`] without registering the scheme. This function can only be called once on macOS.
 pub fn register<F: FnMut(String) + Send + 'static>(scheme: &[&str], handler: F) -> Result<()> {
-    platform_impl::register(scheme, handler)
+    platform_impl::register(scheme, handler)?;
+    shared::warn_if_unregistered(scheme);
+    Ok(())
+}
+
+/// Registers the given schemes without starting the listener.
+///
+/// ## Platform-specific:
+///
+/// - **macOS**: Schemes are defined in the Info.plist file, this function has no effect.
+pub fn register_scheme(scheme: &[&str]) -> Result<()> {
+    platform_impl::register_scheme(scheme)?;
+    shared::warn_if_unregistered(scheme);
+    Ok(())
+}
+
+/// Checks if all given schemes are registered to the current executable.
+///
+/// ## Platform-specific:
+///
+/// - **macOS**: Always returns `true`.
+pub fn is_registered(scheme: &[&str]) -> Result<bool> {
+    platform_impl::is_registered(scheme)
+}
+
+/// Checks if the identifier was set by [`prepare()`] or [`set_identifier()`].
+pub fn id_already_set() -> bool {
+    ID.get().is_some()
 }
 
 /// Starts the event listener without registering any schemes.
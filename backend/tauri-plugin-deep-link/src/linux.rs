@@ -11,7 +11,20 @@ use crate::ID;
 
 pub fn register<F: FnMut(String) + Send + 'static>(schemes: &[&str], handler: F) -> Result<()> {
     listen(handler)?;
+    register_scheme(schemes)
+}
 
+fn handler_file_name() -> Result<String> {
+    Ok(format!(
+        "{}-handler.desktop",
+        tauri_utils::platform::current_exe()?
+            .file_name()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Executable name not found"))?
+            .to_string_lossy()
+    ))
+}
+
+pub fn register_scheme(schemes: &[&str]) -> Result<()> {
     let mut target = data_dir()
         .ok_or_else(|| Error::new(ErrorKind::NotFound, "Data directory not found"))?
         .join("applications");
@@ -20,12 +33,7 @@ pub fn register<F: FnMut(String) + Send + 'static>(schemes: &[&str], handler: F)
 
     let exe = tauri_utils::platform::current_exe()?;
 
-    let file_name = format!(
-        "{}-handler.desktop",
-        exe.file_name()
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Executable name not found"))?
-            .to_string_lossy()
-    );
+    let file_name = handler_file_name()?;
 
     target.push(&file_name);
 
@@ -74,6 +82,19 @@ pub fn register<F: FnMut(String) + Send + 'static>(schemes: &[&str], handler: F)
     Ok(())
 }
 
+pub fn is_registered(schemes: &[&str]) -> Result<bool> {
+    let file_name = handler_file_name()?;
+    for scheme in schemes {
+        let output = Command::new("xdg-mime")
+            .args(["query", "default", &format!("x-scheme-handler/{}", scheme)])
+            .output()?;
+        if String::from_utf8_lossy(&output.stdout).trim() != file_name {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
 pub fn unregister(_schemes: &[&str]) -> Result<()> {
     let mut target =
         data_dir().ok_or_else(|| Error::new(ErrorKind::NotFound, "data directory not found."))?;
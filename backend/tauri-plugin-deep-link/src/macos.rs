@@ -26,10 +26,38 @@ pub fn register<F: FnMut(String) + Send + 'static>(_scheme: &[&str], handler: F)
     Ok(())
 }
 
+pub fn register_scheme(_scheme: &[&str]) -> Result<()> {
+    Ok(())
+}
+
+pub fn is_registered(_scheme: &[&str]) -> Result<bool> {
+    Ok(true)
+}
+
 pub fn unregister(_scheme: &[&str]) -> Result<()> {
     Ok(())
 }
 
+/// Schemes can only be declared in the bundle's `Info.plist`, so check that the running bundle declares it.
+pub fn plist_declares_scheme(scheme: &str) -> Result<bool> {
+    let exe = tauri_utils::platform::current_exe()?;
+    // Foo.app/Contents/MacOS/foo -> Foo.app/Contents/Info.plist
+    let Some(contents) = exe.parent().and_then(|dir| dir.parent()) else {
+        return Ok(false);
+    };
+    let plist = match std::fs::read_to_string(contents.join("Info.plist")) {
+        Ok(plist) => plist,
+        // not running from a bundle
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let Some((_, schemes)) = plist.split_once("<key>CFBundleURLSchemes</key>") else {
+        return Ok(false);
+    };
+    let schemes = schemes.split("</array>").next().unwrap_or_default();
+    Ok(schemes.contains(&format!("<string>{scheme}</string>")))
+}
+
 // kInternetEventClass
 const EVENT_CLASS: u32 = 0x4755524c;
 // kAEGetURL
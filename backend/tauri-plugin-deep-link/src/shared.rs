@@ -0,0 +1,32 @@
+use std::io::Result;
+
+use crate::platform_impl;
+
+/// Asks the OS whether `scheme` currently resolves to this executable.
+///
+/// ## Platform-specific:
+///
+/// - **Linux**: Compares `xdg-mime query default x-scheme-handler/{scheme}` with the generated `.desktop` file name. Returns an error if `xdg-mime` is not installed.
+/// - **Windows**: Compares the registry `shell\open\command` value with the current executable.
+/// - **macOS**: Checks that the `Info.plist` of the running bundle declares the scheme in `CFBundleURLSchemes`.
+pub fn verify_scheme_registered(scheme: &str) -> Result<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        platform_impl::plist_declares_scheme(scheme)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        platform_impl::is_registered(&[scheme])
+    }
+}
+
+/// Logs a warning for every scheme the OS did not pick up after registration.
+pub(crate) fn warn_if_unregistered(schemes: &[&str]) {
+    for scheme in schemes {
+        match verify_scheme_registered(scheme) {
+            Ok(true) => {}
+            Ok(false) => log::warn!("URL scheme `{scheme}` is not registered to this app"),
+            Err(e) => log::warn!("Failed to verify URL scheme `{scheme}`: {e}"),
+        }
+    }
+}
@@ -27,7 +27,18 @@ use crate::ID;
 
 pub fn register<F: FnMut(String) + Send + 'static>(schemes: &[&str], handler: F) -> Result<()> {
     listen(handler)?;
+    register_scheme(schemes)
+}
+
+fn open_command() -> Result<String> {
+    let exe = tauri_utils::platform::current_exe()?
+        .display()
+        .to_string()
+        .replace("\\\\?\\", "");
+    Ok(format!("\"{}\" \"%1\"", &exe))
+}
 
+pub fn register_scheme(schemes: &[&str]) -> Result<()> {
     for scheme in schemes {
         let hkcu = RegKey::predef(HKEY_CURRENT_USER);
         let base = Path::new("Software").join("Classes").join(scheme);
@@ -52,12 +63,34 @@ pub fn register<F: FnMut(String) + Send + 'static>(schemes: &[&str], handler: F)
 
         let (cmd, _) = hkcu.create_subkey(base.join("shell").join("open").join("command"))?;
 
-        cmd.set_value("", &format!("\"{}\" \"%1\"", &exe))?;
+        cmd.set_value("", &open_command()?)?;
     }
 
     Ok(())
 }
 
+pub fn is_registered(schemes: &[&str]) -> Result<bool> {
+    let expected = open_command()?;
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    for scheme in schemes {
+        let path = Path::new("Software")
+            .join("Classes")
+            .join(scheme)
+            .join("shell")
+            .join("open")
+            .join("command");
+        let command = match hkcu.open_subkey(path) {
+            Ok(key) => key.get_value::<String, _>("")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if !command.eq_ignore_ascii_case(&expected) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
 pub fn unregister(schemes: &[&str]) -> Result<()> {
     for scheme in schemes {
         let hkcu = RegKey::predef(HKEY_CURRENT_USER);
@@ -5,9 +5,12 @@ use anyhow::Ok;
 use clap::{Parser, Subcommand};
 use migrate::MigrateOpts;
 use nyanpasu_egui::widget::StatisticWidgetVariant;
+use service::ServiceOpts;
 use tauri::utils::platform::current_exe;
 
 mod migrate;
+mod paths;
+mod service;
 
 #[derive(Parser, Debug)]
 #[command(name = "clash-nyanpasu", version, about, long_about = None, disable_version_flag = true)]
@@ -16,6 +19,18 @@ pub struct Cli {
     /// Print the version
     #[clap(short = 'v', long, default_value = "false")]
     version: bool,
+    /// Keep all data in the `data` folder next to the executable
+    #[clap(long, default_value = "false")]
+    portable: bool,
+    /// Launch without starting the core, the service or the privilege system
+    #[clap(long, default_value = "false")]
+    safe_mode: bool,
+    /// Print the resolved directories and the service executable path, then exit
+    #[clap(long, default_value = "false")]
+    print_paths: bool,
+    /// Print `--print-paths` output as JSON
+    #[clap(long, default_value = "false", requires = "print_paths")]
+    json: bool,
     #[command(subcommand)]
     command: Option<Commands>,
     #[arg(raw = true)]
@@ -40,6 +55,8 @@ enum Commands {
     PanicDialog { message: String },
     /// Launch the Widget with the specified name.
     StatisticWidget { variant: StatisticWidgetVariant },
+    /// Manage the Nyanpasu service.
+    Service(ServiceOpts),
 }
 
 struct DelayedExitGuard;
@@ -56,9 +73,17 @@ impl Drop for DelayedExitGuard {
 
 pub fn parse() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    crate::consts::set_safe_mode(cli.safe_mode);
     if cli.version {
         print_version_info();
     }
+    // exit immediately with a meaningful code so that scripts can rely on it
+    if cli.print_paths {
+        std::process::exit(paths::print(cli.json));
+    }
+    if let Some(Commands::Service(opts)) = &cli.command {
+        std::process::exit(service::parse(opts));
+    }
     if let Some(commands) = &cli.command {
         let guard = DelayedExitGuard::new();
         match commands {
@@ -98,6 +123,7 @@ pub fn parse() -> anyhow::Result<()> {
                 nyanpasu_egui::widget::start_statistic_widget(*variant)
                     .expect("Failed to start statistic widget");
             }
+            Commands::Service(_) => unreachable!(),
         }
         drop(guard);
         std::process::exit(0);
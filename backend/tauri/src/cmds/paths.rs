@@ -0,0 +1,120 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::{core::service, utils::dirs};
+
+#[derive(Debug, Serialize)]
+struct ResolvedPath {
+    name: &'static str,
+    path: PathBuf,
+    exists: bool,
+    /// Where the path comes from, only set for the service executable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+struct RejectedPath {
+    path: PathBuf,
+    source: &'static str,
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ResolvedPaths {
+    portable: bool,
+    paths: Vec<ResolvedPath>,
+    /// Service executables skipped because they can't run on this machine
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rejected: Vec<RejectedPath>,
+}
+
+fn entry(name: &'static str, path: PathBuf, source: Option<&'static str>) -> ResolvedPath {
+    ResolvedPath {
+        name,
+        exists: path.exists(),
+        path,
+        source,
+    }
+}
+
+fn resolve() -> anyhow::Result<ResolvedPaths> {
+    let dirs = dirs::runtime_paths()?;
+    let report = service::service_path_report()?;
+    let (service_path, source) = report.selected_or_fallback()?;
+    let rejected = report
+        .rejected()
+        .map(|c| RejectedPath {
+            path: c.path.clone(),
+            source: c.source,
+            reason: c.rejection.clone().unwrap_or_default(),
+        })
+        .collect();
+    Ok(ResolvedPaths {
+        portable: dirs.portable,
+        paths: vec![
+            entry("config", dirs.config_dir, None),
+            entry("data", dirs.data_dir, None),
+            entry("install", dirs.install_dir, None),
+            entry("logs", dirs.logs_dir, None),
+            entry("profiles", dirs.profiles_dir, None),
+            entry("cache", dirs.cache_dir, None),
+            entry("service", service_path, Some(source)),
+        ],
+        rejected,
+    })
+}
+
+fn print_text(resolved: &ResolvedPaths) {
+    println!("{:>10}: {}", "portable", resolved.portable);
+    for path in &resolved.paths {
+        let exists = if path.exists {
+            "exists".green()
+        } else {
+            "missing".red()
+        };
+        match path.source {
+            Some(source) => println!(
+                "{:>10}: {} [{exists}] ({source})",
+                path.name,
+                path.path.display()
+            ),
+            None => println!("{:>10}: {} [{exists}]", path.name, path.path.display()),
+        }
+    }
+    for rejected in &resolved.rejected {
+        println!(
+            "{:>10}: {} [{}] ({}: {})",
+            "skipped",
+            rejected.path.display(),
+            "rejected".yellow(),
+            rejected.source,
+            rejected.reason
+        );
+    }
+}
+
+/// Print the resolved directories and the service executable path.
+/// Returns the process exit code.
+pub fn print(json: bool) -> i32 {
+    let resolved = match resolve() {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("{} {e:#}", "error:".red());
+            return 1;
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&resolved) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{} {e}", "error:".red());
+                return 1;
+            }
+        }
+    } else {
+        print_text(&resolved);
+    }
+    0
+}
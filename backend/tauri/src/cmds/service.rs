@@ -0,0 +1,90 @@
+use clap::{Args, Subcommand};
+use colored::Colorize;
+use nyanpasu_ipc::types::StatusInfo;
+
+use crate::{
+    config::{Config, IVerge},
+    core::service::control,
+};
+
+#[derive(Debug, Args)]
+pub struct ServiceOpts {
+    #[command(subcommand)]
+    action: ServiceAction,
+    /// Print the service status as JSON, in the same shape as the `service_status` command
+    #[arg(long, global = true, default_value = "false")]
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+enum ServiceAction {
+    /// Show the service status.
+    Status,
+    /// Install the service.
+    Install,
+    /// Start the service.
+    Start,
+    /// Stop the service.
+    Stop,
+    /// Restart the service.
+    Restart,
+    /// Uninstall the service.
+    Uninstall,
+}
+
+async fn run_action(action: ServiceAction) -> anyhow::Result<()> {
+    match action {
+        ServiceAction::Status => Ok(()),
+        ServiceAction::Install => control::install_service().await,
+        ServiceAction::Start => control::start_service().await,
+        ServiceAction::Stop => control::stop_service().await,
+        ServiceAction::Restart => control::restart_service().await,
+        ServiceAction::Uninstall => {
+            control::uninstall_service().await?;
+            // 与界面中卸载服务一致，同时关闭服务模式
+            Config::verge().data().patch_config(IVerge {
+                enable_service_mode: Some(false),
+                ..IVerge::default()
+            });
+            Config::verge().data().save_file()
+        }
+    }
+}
+
+fn print_status(status: &StatusInfo<'_>) {
+    println!("{:>10}: {:?}", "Status", status.status);
+    if !status.name.is_empty() {
+        println!("{:>10}: {}", "Name", status.name);
+    }
+    if !status.version.is_empty() {
+        println!("{:>10}: {}", "Version", status.version);
+    }
+    if let Some(server) = &status.server {
+        println!("{:>10}: {}", "Server", server.version);
+    }
+}
+
+/// Run the action, then print the resulting status.
+/// Returns the process exit code.
+pub fn parse(opts: &ServiceOpts) -> i32 {
+    nyanpasu_utils::runtime::block_on(async {
+        let result = run_action(opts.action).await;
+        if let Err(e) = &result {
+            eprintln!("{} {e:#}", "error:".red());
+        } else if !matches!(opts.action, ServiceAction::Status) && !opts.json {
+            println!("{}", format!("{:?} succeeded", opts.action).green());
+        }
+        match control::status().await {
+            Ok(status) if opts.json => match serde_json::to_string_pretty(&status) {
+                Ok(json) => println!("{json}"),
+                Err(e) => eprintln!("{} {e}", "error:".red()),
+            },
+            Ok(status) => print_status(&status),
+            Err(e) => {
+                eprintln!("{} failed to query service status: {e:#}", "error:".red());
+                return 1;
+            }
+        }
+        i32::from(result.is_err())
+    })
+}
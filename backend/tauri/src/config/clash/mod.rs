@@ -2,15 +2,17 @@ use crate::utils::{
     dirs,
     help::{self, get_clash_external_port},
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use log::warn;
 use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::Path,
     str::FromStr,
 };
 use tracing_attributes::instrument;
+use url::Url;
 
 use super::Config;
 
@@ -85,15 +87,19 @@ impl IClashTemp {
         ClashInfo {
             port: Self::guard_mixed_port(config),
             server: Self::guard_client_ctrl(config),
-            secret: config.get("secret").and_then(|value| match value {
-                Value::String(val_str) => Some(val_str.clone()),
-                Value::Bool(val_bool) => Some(val_bool.to_string()),
-                Value::Number(val_num) => Some(val_num.to_string()),
-                _ => None,
-            }),
+            secret: Self::guard_secret(config),
         }
     }
 
+    pub fn guard_secret(config: &Mapping) -> Option<String> {
+        config.get("secret").and_then(|value| match value {
+            Value::String(val_str) => Some(val_str.clone()),
+            Value::Bool(val_bool) => Some(val_bool.to_string()),
+            Value::Number(val_num) => Some(val_num.to_string()),
+            _ => None,
+        })
+    }
+
     #[allow(dead_code)]
     pub fn get_external_controller_port(&self) -> u16 {
         let server = self.get_client_info().server;
@@ -201,6 +207,144 @@ pub struct ClashInfo {
     pub secret: Option<String>,
 }
 
+/// 连接核心 API 使用的地址
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, specta::Type)]
+#[serde(tag = "type", content = "address", rename_all = "snake_case")]
+pub enum ControllerEndpoint {
+    /// `ip:port`，监听全部地址时使用回环地址
+    Tcp(String),
+    /// `external-controller-unix` 的 socket 路径
+    Unix(String),
+}
+
+impl ControllerEndpoint {
+    /// 由核心的配置得出，设置了 `external-controller-unix` 时优先使用 unix socket
+    /// `external-controller` 总会由应用写入，可通过核心参数 `-ext-ctl-unix` 设置 socket
+    /// 相对的 socket 路径基于核心工作目录
+    pub fn from_config(config: &Mapping, home: &Path) -> Self {
+        let get = |key: &str| {
+            config
+                .get(key)
+                .and_then(|value| value.as_str())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        match get("external-controller-unix") {
+            Some(path) if cfg!(unix) => Self::Unix(home.join(path).display().to_string()),
+            _ => Self::Tcp(IClashTemp::guard_client_ctrl(config)),
+        }
+    }
+
+    pub fn current() -> Self {
+        ClashController::current().endpoint
+    }
+
+    /// 用于 HTTP 与 WebSocket 请求的 host，unix socket 不使用 host
+    pub fn authority(&self) -> &str {
+        match self {
+            Self::Tcp(addr) => addr,
+            Self::Unix(_) => "localhost",
+        }
+    }
+}
+
+/// 核心 API 的地址与 secret，`api`、`ws` 等模块统一从这里获取
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClashController {
+    pub endpoint: ControllerEndpoint,
+    pub secret: Option<String>,
+}
+
+impl ClashController {
+    pub fn from_config(config: &Mapping, home: &Path) -> Self {
+        Self {
+            endpoint: ControllerEndpoint::from_config(config, home),
+            secret: IClashTemp::guard_secret(config),
+        }
+    }
+
+    /// 优先使用运行时配置，配置文件中对 `external-controller` 与 `secret` 的覆盖同样生效
+    pub fn current() -> Self {
+        let home = dirs::app_data_dir().unwrap_or_default();
+        if let Some(config) = Config::runtime().latest().config.as_ref() {
+            return Self::from_config(config, &home);
+        }
+        Self::from_config(&Config::clash().data().0, &home)
+    }
+
+    /// HTTP 请求的根地址，WebSocket 替换 scheme 即可
+    pub fn url(&self) -> Result<Url> {
+        let authority = self.endpoint.authority();
+        Url::parse(&format!("http://{authority}/"))
+            .with_context(|| format!("invalid external-controller `{authority}`"))
+    }
+}
+
+/// 当前生效的核心 API 地址
+pub fn clash_controller_url() -> Result<Url> {
+    ClashController::current().url()
+}
+
+#[test]
+fn test_controller_endpoint() {
+    fn endpoint(config: &str) -> ControllerEndpoint {
+        let config = serde_yaml::from_str::<Mapping>(config).unwrap();
+        ControllerEndpoint::from_config(&config, Path::new("/data"))
+    }
+
+    assert_eq!(
+        endpoint("external-controller: 0.0.0.0:9090"),
+        ControllerEndpoint::Tcp("127.0.0.1:9090".into())
+    );
+    assert_eq!(
+        endpoint("external-controller: '[::]:9090'"),
+        ControllerEndpoint::Tcp("127.0.0.1:9090".into())
+    );
+    assert_eq!(
+        endpoint("external-controller: 192.168.1.2:9090"),
+        ControllerEndpoint::Tcp("192.168.1.2:9090".into())
+    );
+    assert_eq!(
+        endpoint("external-controller: 127.0.0.1:9090\nexternal-controller-unix: ''"),
+        ControllerEndpoint::Tcp("127.0.0.1:9090".into())
+    );
+    if cfg!(unix) {
+        assert_eq!(
+            endpoint("external-controller: 127.0.0.1:9090\nexternal-controller-unix: mihomo.sock"),
+            ControllerEndpoint::Unix("/data/mihomo.sock".into())
+        );
+        assert_eq!(
+            endpoint("external-controller-unix: /run/mihomo.sock"),
+            ControllerEndpoint::Unix("/run/mihomo.sock".into())
+        );
+    }
+    assert_eq!(endpoint("mode: rule").authority(), "127.0.0.1:9090");
+}
+
+#[test]
+fn test_clash_controller() {
+    let config =
+        serde_yaml::from_str::<Mapping>("external-controller: 0.0.0.0:19090\nsecret: 123456")
+            .unwrap();
+    let controller = ClashController::from_config(&config, Path::new("/data"));
+    assert_eq!(
+        controller.url().unwrap().as_str(),
+        "http://127.0.0.1:19090/"
+    );
+    assert_eq!(controller.secret.as_deref(), Some("123456"));
+
+    let config = serde_yaml::from_str::<Mapping>("external-controller: '[::1]:9090'").unwrap();
+    let controller = ClashController::from_config(&config, Path::new("/data"));
+    assert_eq!(controller.url().unwrap().as_str(), "http://[::1]:9090/");
+    assert_eq!(controller.secret, None);
+
+    let controller = ClashController {
+        endpoint: ControllerEndpoint::Tcp("bad host:9090".into()),
+        secret: None,
+    };
+    assert!(controller.url().is_err());
+}
+
 #[test]
 fn test_clash_info() {
     fn get_case<T: Into<Value>, D: Into<Value>>(mp: T, ec: D) -> ClashInfo {
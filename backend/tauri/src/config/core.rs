@@ -1,16 +1,19 @@
-use super::{Draft, IClashTemp, IRuntime, IVerge, Profiles};
+use super::{Draft, IClashTemp, IRuntime, IVerge, Profiles, nyanpasu::ClashCore};
 use crate::{
     core::state::ManagedState,
     enhance,
     utils::{dirs, help},
 };
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use nyanpasu_utils::runtime::block_on;
 use once_cell::sync::OnceCell;
 use std::{env::temp_dir, path::PathBuf};
 
 pub const RUNTIME_CONFIG: &str = "clash-config.yaml";
 pub const CHECK_CONFIG: &str = "clash-config-check.yaml";
+pub const MINIMAL_STARTUP_CONFIG: &str = "clash-config-startup.yaml";
+const PROXY_VALIDATION_FAILED_EVENT: &str = "proxy-validation-failed";
+const COMPATIBILITY_WARNINGS_EVENT: &str = "core-compatibility-warnings";
 
 pub struct Config {
     clash_config: Draft<IClashTemp>,
@@ -50,7 +53,7 @@ impl Config {
     /// 初始化配置
     pub fn init_config() -> Result<()> {
         crate::log_err!(block_on(Self::generate()));
-        if let Err(err) = Self::generate_file(ConfigType::Run) {
+        if let Err(err) = block_on(Self::generate_file_async(ConfigType::Run)) {
             log::error!(target: "app", "{err:?}");
 
             let runtime_path = dirs::app_config_dir()?.join(RUNTIME_CONFIG);
@@ -71,6 +74,7 @@ impl Config {
         let path = match typ {
             ConfigType::Run => dirs::app_config_dir()?.join(RUNTIME_CONFIG),
             ConfigType::Check => temp_dir().join(CHECK_CONFIG),
+            ConfigType::MinimalStartup => dirs::app_config_dir()?.join(MINIMAL_STARTUP_CONFIG),
         };
 
         let runtime = Config::runtime();
@@ -80,19 +84,69 @@ impl Config {
             .as_ref()
             .ok_or(anyhow!("failed to get runtime config"))?;
 
-        help::save_yaml(&path, &config, Some("# Generated by Clash Nyanpasu"))?;
+        if matches!(typ, ConfigType::MinimalStartup) {
+            let config = super::generate_minimal_service_config(config);
+            help::save_yaml(&path, &config, Some("# Generated by Clash Nyanpasu"))?;
+        } else {
+            help::save_yaml(&path, &config, Some("# Generated by Clash Nyanpasu"))?;
+        }
         Ok(path)
     }
 
+    /// 在阻塞线程中写入配置，避免大配置的序列化阻塞异步运行时
+    pub async fn generate_file_async(typ: ConfigType) -> Result<PathBuf> {
+        tokio::task::spawn_blocking(move || Self::generate_file(typ)).await?
+    }
+
+    /// 当前生效的核心，用于生成配置与启动核心
+    pub fn clash_core() -> ClashCore {
+        let profile_core = Config::profiles().latest().current_clash_core();
+        ClashCore::resolve(profile_core, Config::verge().latest().clash_core)
+    }
+
     /// 生成配置存好
     pub async fn generate() -> Result<()> {
         let (config, exists_keys, postprocessing_outputs) = enhance::enhance().await;
 
+        // 核心无法加载的节点定义直接阻止应用，警告只记录在后处理输出中
+        let errors = postprocessing_outputs
+            .proxy_validation
+            .iter()
+            .filter(|e| e.severity == enhance::ValidationSeverity::Error)
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            crate::log_err!(crate::core::handle::Handle::emit(
+                PROXY_VALIDATION_FAILED_EVENT,
+                &errors
+            ));
+            let details = errors
+                .iter()
+                .map(|e| match &e.field {
+                    Some(field) => format!("{}: `{field}` {}", e.proxy_name, e.error),
+                    None => format!("{}: {}", e.proxy_name, e.error),
+                })
+                .collect::<Vec<_>>();
+            bail!("invalid proxy definitions: {}", details.join("; "));
+        }
+
+        // 为适配 clash-rs 改写或移除的字段，切换配置时已先确认，其他途径在此提示
+        if !postprocessing_outputs.compatibility_warnings.is_empty() {
+            crate::log_err!(crate::core::handle::Handle::emit(
+                COMPATIBILITY_WARNINGS_EVENT,
+                &postprocessing_outputs.compatibility_warnings
+            ));
+        }
+
+        // 切换配置后 geodata 与规则集合的路径可能变化
+        crate::log_err!(crate::core::asset_watcher::rebuild_asset_watcher(&config));
+
         *Config::runtime().draft() = IRuntime {
             config: Some(config),
             exists_keys,
             postprocessing_output: postprocessing_outputs,
         };
+        crate::utils::redact::LogRedactor::global()
+            .set_secrets(super::ClashController::current().secret);
 
         Ok(())
     }
@@ -102,4 +156,6 @@ impl Config {
 pub enum ConfigType {
     Run,
     Check,
+    /// 服务启动核心时使用的精简配置
+    MinimalStartup,
 }
@@ -0,0 +1,101 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::net::IpAddr;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(tag = "type", content = "ip", rename_all = "snake_case")]
+pub enum LanBindAddress {
+    /// 只监听指定的网卡地址
+    Specific(IpAddr),
+    #[default]
+    All,
+}
+
+impl LanBindAddress {
+    /// 对应核心的 `bind-address`
+    pub fn as_bind_address(&self) -> String {
+        match self {
+            LanBindAddress::Specific(ip) => ip.to_string(),
+            LanBindAddress::All => "*".to_string(),
+        }
+    }
+}
+
+/// 局域网共享，设置后由 nyanpasu 接管 `allow-lan`、`bind-address` 和 `authentication`
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(default)]
+pub struct LanShareConfig {
+    pub enabled: bool,
+    pub bind_address: LanBindAddress,
+    /// 允许连接的地址段，为空时不限制，仅 Mihomo 支持
+    pub allowed_cidrs: Vec<String>,
+    pub require_auth: bool,
+    pub username: String,
+    pub password: String,
+}
+
+impl LanShareConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(cidr) = self.allowed_cidrs.iter().find(|cidr| !is_valid_cidr(cidr)) {
+            bail!("invalid cidr: {cidr}");
+        }
+        if self.require_auth {
+            if self.username.is_empty() || self.password.is_empty() {
+                bail!("username and password are required when authentication is enabled");
+            }
+            // 核心以 `user:pass` 的格式解析认证信息
+            if self.username.contains(':') {
+                bail!("username must not contain `:`");
+            }
+        }
+        Ok(())
+    }
+
+    /// 开启认证时的 `user:pass`
+    pub fn credentials(&self) -> Option<String> {
+        self.require_auth
+            .then(|| format!("{}:{}", self.username, self.password))
+    }
+}
+
+pub fn is_valid_cidr(cidr: &str) -> bool {
+    let Some((ip, prefix)) = cidr.trim().split_once('/') else {
+        return false;
+    };
+    let (Ok(ip), Ok(prefix)) = (ip.parse::<IpAddr>(), prefix.parse::<u8>()) else {
+        return false;
+    };
+    match ip {
+        IpAddr::V4(_) => prefix <= 32,
+        IpAddr::V6(_) => prefix <= 128,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_lan_share() {
+        let mut config = LanShareConfig {
+            enabled: true,
+            allowed_cidrs: vec!["192.168.1.0/24".into(), "fd00::/8".into()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.credentials(), None);
+
+        config.require_auth = true;
+        assert!(config.validate().is_err());
+        config.username = "phone".into();
+        config.password = "p:ss".into();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.credentials().as_deref(), Some("phone:p:ss"));
+
+        for cidr in ["192.168.1.0", "192.168.1.0/33", "fd00::/129", "lan/24"] {
+            config.allowed_cidrs = vec![cidr.into()];
+            assert!(config.validate().is_err(), "{cidr}");
+        }
+    }
+}
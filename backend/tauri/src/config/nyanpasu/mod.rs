@@ -2,9 +2,11 @@ use crate::utils::{dirs, help};
 use anyhow::Result;
 // use log::LevelFilter;
 use enumflags2::bitflags;
+use indexmap::IndexMap;
 use nyanpasu_macro::VergePatch;
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use std::path::PathBuf;
 
 /// Validates if a string is a valid hex color code
 pub fn is_hex_color(color: &str) -> bool {
@@ -16,10 +18,12 @@ pub fn is_hex_color(color: &str) -> bool {
 }
 
 mod clash_strategy;
+mod lan_share;
 pub mod logging;
 mod widget;
 
 pub use self::clash_strategy::{ClashStrategy, ExternalControllerPortStrategy};
+pub use lan_share::{LanBindAddress, LanShareConfig};
 pub use logging::LoggingLevel;
 pub use widget::NetworkStatisticWidgetConfig;
 
@@ -45,6 +49,13 @@ impl Default for ClashCore {
     }
 }
 
+impl ClashCore {
+    /// 配置中指定的核心优先于设置中的核心
+    pub fn resolve(profile: Option<ClashCore>, global: Option<ClashCore>) -> ClashCore {
+        profile.or(global).unwrap_or_default()
+    }
+}
+
 impl From<ClashCore> for String {
     fn from(core: ClashCore) -> Self {
         match core {
@@ -125,6 +136,17 @@ impl AsRef<str> for TunStack {
     }
 }
 
+/// 系统代理的设置方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemProxyMode {
+    /// 写入 host:port
+    #[default]
+    Static,
+    /// 写入内置 PAC 服务的地址
+    Pac,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default, Type)]
 #[serde(rename_all = "snake_case")]
 pub enum BreakWhenProxyChange {
@@ -134,11 +156,32 @@ pub enum BreakWhenProxyChange {
     All,
 }
 
+/// 切换配置前的连接检查阈值，满足任一条件的连接视为会受影响的长连接
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Type)]
+pub struct ProfileSwitchGuard {
+    /// 已传输的字节数（上传 + 下载）
+    pub min_bytes: u64,
+    /// 连接已建立的秒数
+    pub min_age_secs: u64,
+}
+
+impl Default for ProfileSwitchGuard {
+    fn default() -> Self {
+        Self {
+            min_bytes: 10 * 1024 * 1024,
+            min_age_secs: 300,
+        }
+    }
+}
+
 /// ### `verge.yaml` schema
 #[derive(Default, Debug, Clone, Deserialize, Serialize, VergePatch, specta::Type)]
 #[verge(patch_fn = "patch_config")]
 // TODO: use new managedState and builder pattern instead
 pub struct IVerge {
+    /// 配置文件 schema 版本，缺失视为 0
+    pub config_version: Option<u32>,
+
     /// app listening port for app singleton
     pub app_singleton_port: Option<u16>,
 
@@ -168,6 +211,9 @@ pub struct IVerge {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enable_service_mode: Option<bool>,
 
+    /// 服务 IPC socket 的路径，`/run/` 不可写时使用，默认为 `/run/nyanpasu_ipc.sock`
+    pub ipc_socket_path: Option<PathBuf>,
+
     /// can the app auto startup
     pub enable_auto_launch: Option<bool>,
 
@@ -181,12 +227,35 @@ pub struct IVerge {
     pub enable_proxy_guard: Option<bool>,
 
     /// set system proxy bypass
-    pub system_proxy_bypass: Option<String>,
+    /// 旧版本保存为分隔符拼接的字符串，读取时兼容
+    #[serde(
+        default,
+        deserialize_with = "crate::utils::config::deserialize_bypass_list"
+    )]
+    pub system_proxy_bypass: Option<Vec<String>>,
+
+    /// 同时从系统代理与核心规则中排除的域名，规则中按 `DOMAIN-SUFFIX` 直连
+    pub global_exclude_list: Option<Vec<String>>,
 
     /// proxy guard interval
     #[serde(alias = "proxy_guard_duration")]
     pub proxy_guard_interval: Option<u64>,
 
+    /// 系统代理的设置方式，默认为静态代理
+    pub system_proxy_mode: Option<SystemProxyMode>,
+
+    /// PAC 服务监听的端口，未设置时使用随机端口
+    pub pac_server_port: Option<u16>,
+
+    /// 启动时自动结束上次崩溃遗留的核心进程，默认开启；关闭时只发送通知
+    pub kill_orphaned_cores: Option<bool>,
+
+    /// 将关闭的连接记录到数据目录，默认关闭
+    pub enable_connection_log: Option<bool>,
+
+    /// 匿名遥测，默认关闭
+    pub telemetry: Option<crate::utils::telemetry::TelemetrySettings>,
+
     /// theme setting
     pub theme_color: Option<String>,
 
@@ -197,6 +266,14 @@ pub struct IVerge {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub clash_core: Option<ClashCore>,
 
+    /// 启动核心时附加的参数，启动前会经过校验
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clash_core_extra_args: Option<Vec<String>>,
+
+    /// 允许附加参数将外部控制器监听在非回环地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_remote_api: Option<bool>,
+
     /// hotkey map
     /// format: {func},{key}
     pub hotkeys: Option<Vec<String>>,
@@ -221,9 +298,57 @@ pub struct IVerge {
     /// false: 不中断连接
     pub break_when_mode_change: Option<bool>,
 
+    /// 切换配置前检查是否有长连接会被中断，未设置时不检查
+    pub profile_switch_guard: Option<ProfileSwitchGuard>,
+
+    /// 切换配置需要更换正在运行的核心时先询问用户
+    pub core_switch_confirm: Option<bool>,
+
+    /// 跳过首次运行及更新后对核心文件的 SHA-256 校验
+    pub skip_core_verification: Option<bool>,
+
+    /// 记录配置激活各阶段的耗时，默认开启
+    pub enable_activation_report: Option<bool>,
+
+    /// 局域网共享，未设置时沿用 clash 配置中的 `allow-lan`
+    pub lan_share: Option<LanShareConfig>,
+
+    /// 开启 TUN 时阻止流量经其他网卡的路由绕过代理
+    pub strict_routing: Option<bool>,
+
+    /// 同步工具（Syncthing、Resilio 等）的目录，其中的配置文件变化时提示导入
+    pub profile_sync_dir: Option<PathBuf>,
+
     /// 默认的延迟测试连接
     pub default_latency_test: Option<String>,
 
+    /// 在 TUN 与 DNS 配置中启用 IPv6，默认关闭
+    pub enable_ipv6: Option<bool>,
+
+    /// 未开启 TUN 时也使用 fake-ip DNS，默认关闭
+    pub enable_dns_enhance: Option<bool>,
+
+    /// DNS 上游，支持 `tls://`、`https://`、`quic://` 等，未设置时使用内置的默认值
+    pub dns_nameservers: Option<Vec<String>>,
+
+    /// 窗口不可见时降低轮询频率的阈值
+    pub polling_thresholds: Option<crate::core::activity::PollingThresholds>,
+
+    /// 增强步骤的顺序与开关
+    pub enhance_chain: Option<crate::enhance::EnhanceChain>,
+
+    /// 配置文件超过该大小（MB）时记录警告，默认为 20
+    pub large_profile_warn_mb: Option<u64>,
+
+    /// 定时更新订阅时，超过该大小（MB）的配置不计算变化，默认为 10
+    pub profile_diff_max_mb: Option<u64>,
+
+    /// 额外信任的 CA 证书（PEM），用于订阅与更新下载，例如企业代理的根证书
+    pub custom_ca_certs: Option<Vec<PathBuf>>,
+
+    /// 插入到订阅规则前后的用户规则
+    pub rule_injection: Option<crate::enhance::RuleInjection>,
+
     /// 支持关闭字段过滤，避免meta的新字段都被过滤掉，默认为真
     pub enable_clash_fields: Option<bool>,
 
@@ -277,6 +402,33 @@ pub struct IVerge {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pac_url: Option<String>,
 
+    /// 主网络接口变化时自动重启核心
+    pub auto_restart_on_network_change: Option<bool>,
+
+    /// 系统从休眠唤醒后重启核心
+    pub restart_core_on_wake: Option<bool>,
+
+    /// 服务模式下先用精简配置启动核心，就绪后再加载完整配置
+    pub use_minimal_startup_config: Option<bool>,
+
+    /// 对核心 API 请求签名，外部 UI 代理拒绝未签名的跨站请求
+    pub use_api_request_signing: Option<bool>,
+
+    /// 测速下载地址列表，为空时使用内置地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speedtest_endpoints: Option<Vec<String>>,
+
+    /// 单次测速的流量上限（字节），默认 100MB
+    pub speedtest_byte_cap: Option<u64>,
+
+    /// 自定义节点名称解析规则，优先于内置规则
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_metadata_patterns: Option<crate::core::clash::node_meta::NodeMetadataPatterns>,
+
+    /// 代理组智能选择策略，key 为 profile uid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_policies: Option<IndexMap<String, crate::core::clash::policy::GroupPolicies>>,
+
     /// enable tray text display on Linux systems
     /// When enabled, shows proxy and TUN mode status as text next to the tray icon
     /// When disabled, only shows status via icon changes (prevents text display issues on Wayland)
@@ -295,7 +447,7 @@ pub struct WindowState {
 
 impl IVerge {
     pub fn new() -> Self {
-        match dirs::nyanpasu_config_path().and_then(|path| help::read_yaml::<IVerge, _>(&path)) {
+        match dirs::nyanpasu_config_path().and_then(|path| Self::load_and_migrate(&path)) {
             Ok(mut config) => {
                 // Validate and fix theme_color if it's invalid
                 if let Some(ref theme_color) = config.theme_color {
@@ -314,6 +466,21 @@ impl IVerge {
         }
     }
 
+    /// 读取配置文件，并在反序列化前升级旧版本的 schema
+    fn load_and_migrate(path: &std::path::Path) -> Result<Self> {
+        use crate::utils::config::{ConfigVersion, migrate_verge_config};
+
+        let raw = help::read_yaml::<serde_yaml::Value, _>(path)?;
+        let from_version = ConfigVersion::of(&raw)?;
+        if from_version == ConfigVersion::CURRENT {
+            return Ok(serde_yaml::from_value(raw)?);
+        }
+        log::info!(target: "app", "migrating verge config from v{from_version} to v{}", ConfigVersion::CURRENT);
+        let config: Self = serde_yaml::from_value(migrate_verge_config(raw, from_version)?)?;
+        crate::log_err!(config.save_file());
+        Ok(config)
+    }
+
     fn merge_with_template(mut config: IVerge) -> Self {
         let template = Self::template();
 
@@ -369,6 +536,7 @@ impl IVerge {
 
     pub fn template() -> Self {
         Self {
+            config_version: Some(crate::utils::config::ConfigVersion::CURRENT),
             clash_core: Some(ClashCore::default()),
             language: {
                 let locale = crate::utils::help::get_system_locale();
@@ -400,6 +568,10 @@ impl IVerge {
             enable_service_mode: Some(false),
             always_on_top: Some(false),
             enable_tray_text: Some(false),
+            auto_restart_on_network_change: Some(false),
+            restart_core_on_wake: Some(false),
+            use_minimal_startup_config: Some(false),
+            speedtest_byte_cap: Some(crate::core::speedtest::DEFAULT_SPEEDTEST_BYTE_CAP),
             ..Self::default()
         }
     }
@@ -0,0 +1,293 @@
+//! 配置的文件夹分组，文件夹内的顺序即 `items` 中的相对顺序
+use super::{
+    item::{Profile, ProfileMetaGetter},
+    item_type::ProfileUid,
+    profiles::Profiles,
+};
+use anyhow::{Result, bail};
+use serde::Serialize;
+use specta::Type;
+
+pub const MAX_FOLDER_NAME_LEN: usize = 32;
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProfileFolder {
+    pub name: String,
+    pub profiles: Vec<Profile>,
+}
+
+/// 侧边栏使用的分组结构
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProfileTree {
+    pub folders: Vec<ProfileFolder>,
+    /// 不属于任何文件夹的配置
+    pub root: Vec<Profile>,
+}
+
+impl Profiles {
+    fn validate_folder_name(&self, name: &str) -> Result<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("folder name should not be empty");
+        }
+        if name.chars().count() > MAX_FOLDER_NAME_LEN {
+            bail!("folder name should not exceed {MAX_FOLDER_NAME_LEN} characters");
+        }
+        if self.folders.iter().any(|folder| folder == name) {
+            bail!("folder `{name}` already exists");
+        }
+        Ok(name.to_string())
+    }
+
+    fn ensure_folder(&self, name: &str) -> Result<()> {
+        if !self.folders.iter().any(|folder| folder == name) {
+            bail!("folder `{name}` does not exist");
+        }
+        Ok(())
+    }
+
+    fn insert_folder(&mut self, name: &str) -> Result<()> {
+        let name = self.validate_folder_name(name)?;
+        self.folders.push(name);
+        Ok(())
+    }
+
+    /// 移动后排在目标文件夹的末尾
+    fn move_item_to_folder(&mut self, uid: &str, folder: Option<String>) -> Result<()> {
+        if let Some(folder) = &folder {
+            self.ensure_folder(folder)?;
+        }
+        let Some(index) = self.items.iter().position(|item| item.uid() == uid) else {
+            bail!("failed to find the profile item \"uid:{uid}\"");
+        };
+        let mut item = self.items.remove(index);
+        item.set_folder(folder);
+        self.items.push(item);
+        Ok(())
+    }
+
+    /// 同时改写所有成员，一次写入
+    fn rename_folder_inner(&mut self, old: &str, new: &str) -> Result<()> {
+        self.ensure_folder(old)?;
+        let new = self.validate_folder_name(new)?;
+        for folder in self
+            .folders
+            .iter_mut()
+            .filter(|folder| folder.as_str() == old)
+        {
+            *folder = new.clone();
+        }
+        for item in self
+            .items
+            .iter_mut()
+            .filter(|item| item.folder() == Some(old))
+        {
+            item.set_folder(Some(new.clone()));
+        }
+        Ok(())
+    }
+
+    /// 有成员时需要指定 `move_to_root`，否则拒绝删除
+    fn remove_folder(&mut self, name: &str, move_to_root: bool) -> Result<()> {
+        self.ensure_folder(name)?;
+        let members = self
+            .items
+            .iter_mut()
+            .filter(|item| item.folder() == Some(name))
+            .collect::<Vec<_>>();
+        if !members.is_empty() && !move_to_root {
+            bail!("folder `{name}` is not empty");
+        }
+        for item in members {
+            item.set_folder(None);
+        }
+        self.folders.retain(|folder| folder != name);
+        Ok(())
+    }
+
+    /// `uids` 需要恰好是该文件夹的全部成员，成员在 `items` 中占据的位置不变
+    fn reorder_folder(&mut self, folder: Option<&str>, uids: &[ProfileUid]) -> Result<()> {
+        if let Some(folder) = folder {
+            self.ensure_folder(folder)?;
+        }
+        let slots = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.folder() == folder)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let mut members = slots
+            .iter()
+            .map(|index| Some(self.items[*index].clone()))
+            .collect::<Vec<_>>();
+        if uids.len() != slots.len() {
+            bail!("the order list should contain all profiles in the folder");
+        }
+        let ordered = uids
+            .iter()
+            .map(|uid| {
+                members
+                    .iter_mut()
+                    .find(|item| item.as_ref().is_some_and(|item| item.uid() == uid))
+                    .and_then(Option::take)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "profile \"uid:{uid}\" is not in the folder or is duplicated"
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (slot, item) in slots.into_iter().zip(ordered) {
+            self.items[slot] = item;
+        }
+        Ok(())
+    }
+
+    pub fn create_folder(&mut self, name: &str) -> Result<()> {
+        self.insert_folder(name)?;
+        self.save_file()
+    }
+
+    pub fn move_to_folder(&mut self, uid: &str, folder: Option<String>) -> Result<()> {
+        self.move_item_to_folder(uid, folder)?;
+        self.save_file()
+    }
+
+    pub fn rename_folder(&mut self, old: &str, new: &str) -> Result<()> {
+        self.rename_folder_inner(old, new)?;
+        self.save_file()
+    }
+
+    pub fn delete_folder(&mut self, name: &str, move_to_root: bool) -> Result<()> {
+        self.remove_folder(name, move_to_root)?;
+        self.save_file()
+    }
+
+    pub fn set_folder_order(&mut self, folder: Option<&str>, uids: &[ProfileUid]) -> Result<()> {
+        self.reorder_folder(folder, uids)?;
+        self.save_file()
+    }
+
+    /// 属于未记录文件夹的配置（如手动编辑过 `profiles.yaml`）会补上对应的文件夹
+    pub fn grouped(&self) -> ProfileTree {
+        let mut folders = self
+            .folders
+            .iter()
+            .map(|name| ProfileFolder {
+                name: name.clone(),
+                profiles: vec![],
+            })
+            .collect::<Vec<_>>();
+        let mut root = vec![];
+        for item in &self.items {
+            let Some(name) = item.folder() else {
+                root.push(item.clone());
+                continue;
+            };
+            match folders.iter_mut().find(|folder| folder.name == name) {
+                Some(folder) => folder.profiles.push(item.clone()),
+                None => folders.push(ProfileFolder {
+                    name: name.to_string(),
+                    profiles: vec![item.clone()],
+                }),
+            }
+        }
+        ProfileTree { folders, root }
+    }
+
+    /// 文件夹内的配置，`None` 表示全部
+    pub fn uids_in_folder(&self, folder: Option<&str>) -> Vec<ProfileUid> {
+        self.items
+            .iter()
+            .filter(|item| folder.is_none() || item.folder() == folder)
+            .map(|item| item.uid().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::profile::item::{LocalProfile, ProfileShared};
+
+    fn local(uid: &str, folder: Option<&str>) -> Profile {
+        Profile::Local(LocalProfile {
+            shared: ProfileShared {
+                uid: uid.to_string(),
+                name: uid.to_string(),
+                file: format!("{uid}.yaml"),
+                desc: None,
+                updated: 0,
+                folder: folder.map(String::from),
+            },
+            symlinks: None,
+            chain: vec![],
+        })
+    }
+
+    fn uids(profiles: &[Profile]) -> Vec<&str> {
+        profiles.iter().map(|item| item.uid()).collect()
+    }
+
+    #[test]
+    fn test_folder_operations() {
+        let mut profiles = Profiles {
+            items: vec![
+                local("a", Some("work")),
+                local("b", None),
+                local("c", Some("work")),
+                local("d", Some("manual")),
+            ],
+            folders: vec!["work".into()],
+            ..Default::default()
+        };
+        assert!(profiles.insert_folder(" work ").is_err());
+        assert!(profiles.insert_folder("  ").is_err());
+        assert!(profiles.insert_folder(&"x".repeat(33)).is_err());
+        profiles.insert_folder("home").unwrap();
+
+        let tree = profiles.grouped();
+        assert_eq!(
+            tree.folders
+                .iter()
+                .map(|folder| folder.name.as_str())
+                .collect::<Vec<_>>(),
+            ["work", "home", "manual"]
+        );
+        assert_eq!(uids(&tree.folders[0].profiles), ["a", "c"]);
+        assert_eq!(uids(&tree.root), ["b"]);
+
+        profiles
+            .reorder_folder(Some("work"), &["c".into(), "a".into()])
+            .unwrap();
+        assert_eq!(uids(&profiles.items), ["c", "b", "a", "d"]);
+        assert!(
+            profiles
+                .reorder_folder(Some("work"), &["c".into()])
+                .is_err()
+        );
+        assert!(
+            profiles
+                .reorder_folder(Some("work"), &["c".into(), "c".into()])
+                .is_err()
+        );
+
+        profiles
+            .move_item_to_folder("b", Some("home".into()))
+            .unwrap();
+        assert!(
+            profiles
+                .move_item_to_folder("b", Some("missing".into()))
+                .is_err()
+        );
+        profiles.rename_folder_inner("work", "office").unwrap();
+        assert_eq!(profiles.uids_in_folder(Some("office")), ["c", "a"]);
+        assert!(profiles.rename_folder_inner("office", "home").is_err());
+
+        assert!(profiles.remove_folder("office", false).is_err());
+        profiles.remove_folder("office", true).unwrap();
+        assert_eq!(profiles.folders, ["home"]);
+        assert_eq!(uids(&profiles.grouped().root), ["c", "a"]);
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(clippy::crate_in_macro_def, dead_code)]
 use super::item_type::ProfileItemType;
-use crate::utils::dirs;
+use crate::{config::nyanpasu::ClashCore, utils::dirs};
 use ambassador::{Delegate, delegatable_trait};
 use anyhow::{Context, Result, bail};
 use nyanpasu_macro::EnumWrapperCombined;
@@ -42,6 +42,8 @@ pub trait ProfileMetaGetter {
     fn uid(&self) -> &str;
     fn updated(&self) -> usize;
     fn file(&self) -> &str;
+    fn folder(&self) -> Option<&str>;
+    fn clash_core(&self) -> Option<ClashCore>;
 }
 
 #[delegatable_trait]
@@ -119,6 +121,24 @@ impl Profile {
         }
     }
 
+    pub fn set_folder(&mut self, folder: Option<String>) {
+        match self {
+            Profile::Remote(profile) => profile.shared.folder = folder,
+            Profile::Local(profile) => profile.shared.folder = folder,
+            Profile::Merge(profile) => profile.shared.folder = folder,
+            Profile::Script(profile) => profile.shared.folder = folder,
+        }
+    }
+
+    pub fn set_clash_core(&mut self, clash_core: Option<ClashCore>) {
+        match self {
+            Profile::Remote(profile) => profile.shared.clash_core = clash_core,
+            Profile::Local(profile) => profile.shared.clash_core = clash_core,
+            Profile::Merge(profile) => profile.shared.clash_core = clash_core,
+            Profile::Script(profile) => profile.shared.clash_core = clash_core,
+        }
+    }
+
     /// get the file data
     pub fn read_file(&self) -> Result<String> {
         let file = self.file();
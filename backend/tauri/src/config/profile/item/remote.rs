@@ -5,10 +5,11 @@ use super::{
 };
 use crate::{
     config::{
-        Config, ProfileKindGetter,
+        ProfileKindGetter,
         profile::item_type::{ProfileItemType, ProfileUid},
     },
-    utils::{config::NyanpasuReqwestProxyExt, dirs::APP_VERSION, help},
+    enhance::convert::{self, ConversionTemplate},
+    utils::{config::NyanpasuReqwestProxyExt, dirs::APP_VERSION, help, tls::CustomCaExt},
 };
 use ambassador::Delegate;
 use backon::Retryable;
@@ -67,6 +68,11 @@ pub struct RemoteProfile {
     #[serde(alias = "chains", default)]
     #[builder_field_attr(serde(alias = "chains", default))]
     pub chain: Vec<ProfileUid>,
+    /// 订阅为节点链接列表时，使用内置转换生成配置
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder_field_attr(serde(default))]
+    pub conversion: Option<ConversionTemplate>,
 }
 
 impl RemoteProfile {
@@ -96,7 +102,7 @@ impl RemoteProfileSubscription for RemoteProfile {
         if let Some(partial) = partial {
             opts.apply(partial);
         }
-        let subscription = subscribe_url(&self.url, &opts).await?;
+        let subscription = subscribe_url(&self.url, &opts, self.conversion.as_ref()).await?;
         self.extra = subscription.info;
 
         let content = serde_yaml::to_string(&subscription.data)?;
@@ -120,12 +126,15 @@ struct Subscription {
 async fn subscribe_url(
     url: &Url,
     options: &RemoteProfileOptions,
+    conversion: Option<&ConversionTemplate>,
 ) -> Result<Subscription, SubscribeError> {
     let options = options.apply_default();
     let base_builder = || {
         reqwest::ClientBuilder::new()
             .use_rustls_tls()
             .no_proxy()
+            .with_custom_ca()
+            .tls_info(options.pin_sha256.is_some())
             .timeout(Duration::from_secs(30))
     };
 
@@ -134,10 +143,7 @@ async fn subscribe_url(
         // FIXME: 解耦此部分代理地址读取
         if options.self_proxy.unwrap_or_default() && !cfg!(test) {
             // 使用软件自己的代理
-            let port = Config::verge()
-                .latest()
-                .verge_mixed_port
-                .unwrap_or(Config::clash().data().get_mixed_port());
+            let port = crate::core::sysopt::proxy_port();
             Some(format!("http://127.0.0.1:{port}"))
         } else if options.with_proxy.unwrap() {
             // 使用系统代理
@@ -285,6 +291,14 @@ async fn subscribe_url(
         }
     };
 
+    // 在读取响应前校验证书指纹，不匹配时不使用任何响应内容
+    if let Some(pin) = options.pin_sha256.as_deref() {
+        crate::utils::tls::verify_pin(&resp, pin).map_err(|e| SubscribeError::CertificatePin {
+            url: url.to_string(),
+            reason: format!("{e:#}"),
+        })?;
+    }
+
     let header = resp.headers();
     tracing::debug!("headers: {:#?}", header);
 
@@ -330,6 +344,11 @@ async fn subscribe_url(
         None => None,
     };
 
+    let content_type = header
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
     let data = resp
         .text_with_charset("utf-8")
         .await
@@ -341,11 +360,35 @@ async fn subscribe_url(
     // process the charset "UTF-8 with BOM"
     let data = data.trim_start_matches('\u{feff}');
 
-    // check the data whether the valid yaml format
-    let yaml = serde_yaml::from_str::<Mapping>(data).map_err(|e| SubscribeError::Parse {
-        url: url.to_string(),
-        source: e,
-    })?;
+    // 认证页面或服务商的错误页面不会覆盖原有的配置文件
+    if let Err((detected, snippet)) = utils::check_content(content_type.as_deref(), data) {
+        return Err(SubscribeError::InvalidSubscriptionContent {
+            url: url.to_string(),
+            detected,
+            snippet,
+        });
+    }
+
+    let yaml = match conversion {
+        // convert the node list into a full config
+        Some(template) => {
+            let conversion = convert::convert_subscription(data, template).map_err(|e| {
+                SubscribeError::ValidationFailed {
+                    url: url.to_string(),
+                    reason: format!("{e:#}"),
+                }
+            })?;
+            for warning in &conversion.warnings {
+                tracing::warn!("skipped a node while converting {url}: {warning}");
+            }
+            conversion.config
+        }
+        // check the data whether the valid yaml format
+        None => serde_yaml::from_str::<Mapping>(data).map_err(|e| SubscribeError::Parse {
+            url: url.to_string(),
+            source: e,
+        })?,
+    };
 
     if !yaml.contains_key("proxies") && !yaml.contains_key("proxy-providers") {
         return Err(SubscribeError::ValidationFailed {
@@ -375,7 +418,7 @@ async fn subscribe_urls(
             reason: "urls should not be empty".to_string(),
         });
     }
-    let futures = urls.iter().map(|url| subscribe_url(url, options));
+    let futures = urls.iter().map(|url| subscribe_url(url, options, None));
     let results = futures::future::join_all(futures).await;
     let (successes, errors): (Vec<_>, Vec<_>) = results.into_iter().partition_map(|r| match r {
         Ok(val) => itertools::Either::Left(val),
@@ -428,10 +471,30 @@ pub enum SubscribeError {
     #[error("invalid profile at {url}: {reason}")]
     ValidationFailed { url: String, reason: String },
 
+    #[error("certificate pin check failed at {url}: {reason}")]
+    CertificatePin { url: String, reason: String },
+
+    #[error("unexpected {detected:?} content at {url}: {snippet}")]
+    InvalidSubscriptionContent {
+        url: String,
+        detected: DetectedContent,
+        /// 截断并转义后的响应开头
+        snippet: String,
+    },
+
     #[error("multiple errors occurred: {0:?}")]
     MultipleErrors(Vec<SubscribeError>),
 }
 
+/// 订阅响应不是配置或节点列表时推测的内容类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedContent {
+    /// 认证页面或错误页面
+    Html,
+    Empty,
+    Unknown,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum RemoteProfileBuilderError {
     #[error("validation error: {0}")]
@@ -467,11 +530,12 @@ impl RemoteProfileBuilder {
                 .uid(super::utils::generate_uid(&ProfileItemType::Remote));
         }
         let url = self.url.take().unwrap();
+        let conversion = self.conversion.take().unwrap_or_default();
         let options = self
             .option
             .build()
             .map_err(|e| RemoteProfileBuilderError::Validation(e.to_string()))?;
-        let mut subscription = subscribe_url(&url, &options).await?;
+        let mut subscription = subscribe_url(&url, &options, conversion.as_ref()).await?;
         let extra = subscription.info;
 
         if self.shared.get_name().is_none()
@@ -493,6 +557,7 @@ impl RemoteProfileBuilder {
             extra,
             option: self.option.build().unwrap(),
             chain: self.chain.take().unwrap_or_default(),
+            conversion,
         };
         // write the profile to the file
         profile
@@ -543,6 +608,11 @@ pub struct RemoteProfileOptions {
     /// subscription update interval
     #[builder(default = "120")]
     pub update_interval: u64,
+
+    /// 订阅服务器叶子证书的 SHA-256 指纹（hex），不匹配时拒绝使用响应
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub pin_sha256: Option<String>,
 }
 
 impl Default for RemoteProfileOptions {
@@ -552,6 +622,7 @@ impl Default for RemoteProfileOptions {
             with_proxy: None,
             self_proxy: Some(true),
             update_interval: 120, // 2 hours
+            pin_sha256: None,
         }
     }
 }
@@ -573,8 +644,82 @@ impl RemoteProfileOptions {
 }
 
 mod utils {
+    use super::DetectedContent;
+    use crate::enhance::convert;
     use base64::{Engine, engine::general_purpose};
     use reqwest::header::{self, HeaderMap};
+    use serde_yaml::Mapping;
+
+    /// 配置中至少包含其中一个键
+    const EXPECTED_KEYS: [&str; 4] = ["proxies", "proxy-providers", "rules", "port"];
+    const SNIPPET_MAX_CHARS: usize = 120;
+
+    /// 响应必须是包含常见键的 YAML，或者可以转换的节点列表
+    pub fn check_content(
+        content_type: Option<&str>,
+        body: &str,
+    ) -> Result<(), (DetectedContent, String)> {
+        let body = body.trim();
+        if body.is_empty() {
+            return Err((DetectedContent::Empty, String::new()));
+        }
+        // 部分服务商的 Content-Type 不准确，只在内容无法识别时参考
+        let is_config = serde_yaml::from_str::<Mapping>(body)
+            .is_ok_and(|config| EXPECTED_KEYS.iter().any(|key| config.contains_key(*key)));
+        if is_config || !convert::parse_node_list(body).0.is_empty() {
+            return Ok(());
+        }
+        let is_html = content_type.is_some_and(|ty| ty.to_ascii_lowercase().contains("text/html"))
+            || looks_like_html(body);
+        let detected = if is_html {
+            DetectedContent::Html
+        } else {
+            DetectedContent::Unknown
+        };
+        Err((detected, snippet(body)))
+    }
+
+    fn looks_like_html(body: &str) -> bool {
+        let head = body
+            .chars()
+            .take(256)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        [
+            "<!doctype html",
+            "<html",
+            "<head",
+            "<body",
+            "<meta",
+            "<title",
+        ]
+        .iter()
+        .any(|tag| head.starts_with(tag) || head.contains(&format!("\n{tag}")))
+    }
+
+    /// 合并空白后截断，并转义 HTML 特殊字符，避免界面把内容当作标记渲染
+    pub fn snippet(body: &str) -> String {
+        let collapsed = body.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut snippet = collapsed
+            .chars()
+            .take(SNIPPET_MAX_CHARS)
+            .collect::<String>();
+        if collapsed.chars().count() > SNIPPET_MAX_CHARS {
+            snippet.push('…');
+        }
+        let mut escaped = String::with_capacity(snippet.len());
+        for c in snippet.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
 
     /// parse profile title from headers
     pub fn parse_profile_title_header(headers: &HeaderMap) -> Option<String> {
@@ -625,3 +770,47 @@ mod utils {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DetectedContent, utils::check_content};
+    use base64::{Engine, engine::general_purpose};
+
+    #[test]
+    fn test_check_content() {
+        let html = "<!DOCTYPE html>\n<html><head><title>Hotel Wi-Fi Login</title></head>\n<body>Please sign in</body></html>";
+        let (detected, snippet) =
+            check_content(Some("text/html; charset=utf-8"), html).unwrap_err();
+        assert_eq!(detected, DetectedContent::Html);
+        assert!(
+            snippet.starts_with("&lt;!DOCTYPE html&gt; &lt;html&gt;"),
+            "{snippet}"
+        );
+        assert!(!snippet.contains('<'));
+        // 没有 Content-Type 时按内容识别
+        assert_eq!(
+            check_content(None, html).unwrap_err().0,
+            DetectedContent::Html
+        );
+
+        assert_eq!(
+            check_content(Some("text/plain"), " \n ").unwrap_err(),
+            (DetectedContent::Empty, String::new())
+        );
+
+        let (detected, snippet) = check_content(
+            Some("text/plain"),
+            &format!("mode: rule\nlog-level: info\nname: {}", "a".repeat(200)),
+        )
+        .unwrap_err();
+        assert_eq!(detected, DetectedContent::Unknown);
+        assert!(snippet.ends_with('…'));
+        assert_eq!(snippet.chars().count(), 121);
+
+        let list = general_purpose::STANDARD.encode(
+            "trojan://password@example.com:443?sni=example.com#node-1\nss://YWVzLTEyOC1nY206cGFzcw@example.com:8388#node-2\n",
+        );
+        assert!(check_content(Some("text/html"), &list).is_ok());
+        assert!(check_content(Some("text/html"), "port: 7890\nproxies: []\n").is_ok());
+    }
+}
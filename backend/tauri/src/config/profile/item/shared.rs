@@ -6,7 +6,9 @@ use nyanpasu_macro::BuilderUpdate;
 use serde::{Deserialize, Serialize, de::Visitor};
 
 use crate::{
-    config::profile::item_type::ProfileItemType, enhance::ScriptType, utils::dirs::app_profiles_dir,
+    config::{nyanpasu::ClashCore, profile::item_type::ProfileItemType},
+    enhance::ScriptType,
+    utils::dirs::app_profiles_dir,
 };
 
 use super::{ProfileMetaGetter, ProfileMetaSetter};
@@ -42,6 +44,16 @@ pub struct ProfileShared {
     #[builder(default = "chrono::Local::now().timestamp() as usize")]
     /// update time
     pub updated: usize,
+
+    /// 所在的文件夹，为空时位于根目录
+    #[builder(default, setter(strip_option))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+
+    /// 使用该配置时的核心，为空时使用设置中的核心
+    #[builder(default, setter(strip_option))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clash_core: Option<ClashCore>,
 }
 
 impl ProfileShared {
@@ -138,6 +150,8 @@ impl ProfileSharedBuilder {
             updated: builder
                 .updated
                 .unwrap_or_else(|| chrono::Local::now().timestamp() as usize),
+            folder: builder.folder.clone().unwrap_or_default(),
+            clash_core: builder.clash_core.unwrap_or_default(),
         })
     }
 }
@@ -168,6 +182,14 @@ impl ProfileMetaGetter for ProfileShared {
     fn file(&self) -> &str {
         &self.file
     }
+
+    fn folder(&self) -> Option<&str> {
+        self.folder.as_deref()
+    }
+
+    fn clash_core(&self) -> Option<ClashCore> {
+        self.clash_core
+    }
 }
 
 impl ProfileMetaSetter for ProfileShared {
@@ -1,4 +1,5 @@
 pub mod builder;
+pub mod folder;
 pub mod item;
 pub mod item_type;
 pub mod profiles;
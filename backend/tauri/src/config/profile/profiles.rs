@@ -3,7 +3,10 @@ use super::{
     item::{Profile, prelude::*},
     item_type::ProfileUid,
 };
-use crate::utils::{dirs, help};
+use crate::{
+    config::nyanpasu::ClashCore,
+    utils::{dirs, help},
+};
 use anyhow::{Result, bail};
 use derive_builder::Builder;
 use indexmap::IndexMap;
@@ -11,7 +14,7 @@ use nyanpasu_macro::BuilderUpdate;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
-use std::borrow::Borrow;
+use std::{borrow::Borrow, path::PathBuf};
 use tracing_attributes::instrument;
 
 /// Define the `profiles.yaml` schema
@@ -32,6 +35,9 @@ pub struct Profiles {
     #[serde(default)]
     /// profile list
     pub items: Vec<Profile>,
+    #[serde(default)]
+    /// 文件夹列表，空文件夹也会保留
+    pub folders: Vec<String>,
 }
 
 impl Default for Profiles {
@@ -45,6 +51,7 @@ impl Default for Profiles {
                 "tcp-concurrent".into(),
             ],
             items: vec![],
+            folders: vec![],
         }
     }
 }
@@ -202,21 +209,50 @@ impl Profiles {
         Ok(is_current)
     }
 
-    /// 获取current指向的配置内容
-    pub fn current_mappings(&self) -> Result<IndexMap<&str, Mapping>> {
-        let current = self
+    /// 当前配置中第一个指定了核心的配置
+    pub fn current_clash_core(&self) -> Option<ClashCore> {
+        self.current_clash_core_source().map(|(_, core)| core)
+    }
+
+    /// 指定了核心的配置及其核心
+    pub fn current_clash_core_source(&self) -> Option<(&ProfileUid, ClashCore)> {
+        self.current
+            .iter()
+            .filter_map(|uid| Some((uid, self.get_item(uid).ok()?.clash_core()?)))
+            .next()
+    }
+
+    /// 获取current指向的配置文件
+    pub fn current_files(&self) -> Result<Vec<(ProfileUid, PathBuf)>> {
+        let profiles_dir = dirs::app_profiles_dir()?;
+        Ok(self
             .items
             .iter()
             .filter(|e| self.current.iter().any(|uid| uid == e.uid()))
-            .collect::<Vec<_>>();
-        let (successes, failures): (Vec<(&str, Mapping)>, Vec<anyhow::Error>) = current
-            .par_iter()
-            .map(|item| {
-                let file_path = dirs::app_profiles_dir()?.join(item.file());
+            .map(|item| (item.uid().to_string(), profiles_dir.join(item.file())))
+            .collect())
+    }
+
+    /// 解析配置文件，大配置耗时较长，需在阻塞线程中调用
+    /// 超过 warn_size 字节的文件会记录警告
+    pub fn read_mappings(
+        files: Vec<(ProfileUid, PathBuf)>,
+        warn_size: u64,
+    ) -> Result<IndexMap<ProfileUid, Mapping>> {
+        let (successes, failures): (Vec<(ProfileUid, Mapping)>, Vec<anyhow::Error>) = files
+            .into_par_iter()
+            .map(|(uid, file_path)| {
                 if !file_path.exists() {
                     return Err(anyhow::anyhow!("failed to find the file: {:?}", file_path));
                 }
-                help::read_merge_mapping(&file_path).map(|mapping| (item.uid(), mapping))
+                let size = std::fs::metadata(&file_path)?.len();
+                if size > warn_size {
+                    tracing::warn!(
+                        "profile {uid} is {:.1} MB, switching to it may be slow",
+                        size as f64 / 1024.0 / 1024.0
+                    );
+                }
+                help::read_merge_mapping(&file_path).map(|mapping| (uid, mapping))
             })
             .partition_map(|item| match item {
                 Ok(item) => itertools::Either::Left(item),
@@ -229,3 +265,55 @@ impl Profiles {
         Ok(map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    /// 生成含 n 条规则的配置
+    fn large_profile(rules: usize) -> String {
+        let mut profile = String::from(
+            "proxies:\n  - { name: node, type: socks5, server: 127.0.0.1, port: 1080 }\nrules:\n",
+        );
+        for i in 0..rules {
+            writeln!(profile, "  - DOMAIN-SUFFIX,host-{i}.example.com,node").unwrap();
+        }
+        profile.push_str("  - MATCH,DIRECT\n");
+        profile
+    }
+
+    #[tokio::test]
+    async fn test_large_profile_round_trip() {
+        const RULES: usize = 100_000;
+        let dir = tempfile::tempdir().unwrap();
+        let profile_path = dir.path().join("large.yaml");
+        std::fs::write(&profile_path, large_profile(RULES)).unwrap();
+
+        let files = vec![("large".to_string(), profile_path)];
+        let mappings = tokio::task::spawn_blocking(move || Profiles::read_mappings(files, 1024))
+            .await
+            .unwrap()
+            .unwrap();
+        let rules = mappings["large"]["rules"].as_sequence().unwrap();
+        assert_eq!(rules.len(), RULES + 1);
+        assert_eq!(
+            rules[0].as_str(),
+            Some("DOMAIN-SUFFIX,host-0.example.com,node")
+        );
+        assert_eq!(rules[RULES].as_str(), Some("MATCH,DIRECT"));
+
+        // 写出后重新读取，规则的数量与顺序不变
+        let output = dir.path().join("output.yaml");
+        let expected = mappings["large"].clone();
+        let output_path = output.clone();
+        tokio::task::spawn_blocking(move || {
+            help::save_yaml(&output_path, &mappings["large"], Some("# test"))
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let written: Mapping = help::read_yaml(&output).unwrap();
+        assert_eq!(written, expected);
+    }
+}
@@ -106,11 +106,14 @@ fn test_tagged_enum_serialization() {
             file: "remote-1.yaml".to_string(),
             desc: Some("A remote profile".to_string()),
             updated: 1234567890,
+            folder: None,
+            clash_core: None,
         },
         url: Url::parse("https://example.com/config.yaml").unwrap(),
         extra: SubscriptionInfo::default(),
         option: RemoteProfileOptions::default(),
         chain: vec![],
+        conversion: None,
     });
 
     let local_profile = Profile::Local(LocalProfile {
@@ -120,6 +123,8 @@ fn test_tagged_enum_serialization() {
             file: "local-1.yaml".to_string(),
             desc: None,
             updated: 1234567890,
+            folder: None,
+            clash_core: None,
         },
         symlinks: None,
         chain: vec![],
@@ -132,6 +137,8 @@ fn test_tagged_enum_serialization() {
             file: "merge-1.yaml".to_string(),
             desc: Some("Merge multiple profiles".to_string()),
             updated: 1234567890,
+            folder: None,
+            clash_core: None,
         },
     });
 
@@ -142,6 +149,8 @@ fn test_tagged_enum_serialization() {
             file: "script-1.js".to_string(),
             desc: None,
             updated: 1234567890,
+            folder: None,
+            clash_core: None,
         },
         script_type: ScriptType::JavaScript,
     });
@@ -200,6 +209,7 @@ fn test_profile_kind_getter() {
         extra: SubscriptionInfo::default(),
         option: RemoteProfileOptions::default(),
         chain: vec![],
+        conversion: None,
     };
     assert_eq!(remote.kind(), ProfileItemType::Remote);
 
@@ -305,6 +315,8 @@ fn test_large_numbers() {
                 file: format!("test-{}.yaml", desc),
                 desc: None,
                 updated: value,
+                folder: None,
+                clash_core: None,
             },
             symlinks: None,
             chain: vec![],
@@ -320,3 +332,64 @@ fn test_large_numbers() {
         }
     }
 }
+
+/// 配置中指定的核心优先于设置中的核心
+#[test]
+fn test_profile_clash_core_override() {
+    use crate::config::{ProfileMetaGetter, Profiles, nyanpasu::ClashCore};
+
+    let profile = |uid: &str, clash_core: Option<ClashCore>| {
+        Profile::Local(LocalProfile {
+            shared: crate::config::profile::item::ProfileShared {
+                uid: uid.to_string(),
+                name: uid.to_string(),
+                file: format!("{uid}.yaml"),
+                desc: None,
+                updated: 0,
+                folder: None,
+                clash_core,
+            },
+            symlinks: None,
+            chain: vec![],
+        })
+    };
+    let mut profiles = Profiles {
+        items: vec![
+            profile("plain", None),
+            profile("meta", Some(ClashCore::Mihomo)),
+            profile("alpha", Some(ClashCore::MihomoAlpha)),
+        ],
+        ..Profiles::default()
+    };
+
+    profiles.current = vec!["plain".into()];
+    assert_eq!(profiles.current_clash_core(), None);
+    assert_eq!(
+        ClashCore::resolve(profiles.current_clash_core(), Some(ClashCore::ClashPremium)),
+        ClashCore::ClashPremium
+    );
+    assert_eq!(
+        ClashCore::resolve(profiles.current_clash_core(), None),
+        ClashCore::default()
+    );
+
+    // 多个配置同时启用时使用第一个指定了核心的配置
+    profiles.current = vec!["plain".into(), "meta".into(), "alpha".into()];
+    assert_eq!(profiles.current_clash_core(), Some(ClashCore::Mihomo));
+    assert_eq!(
+        profiles.current_clash_core_source(),
+        Some((&"meta".to_string(), ClashCore::Mihomo))
+    );
+    assert_eq!(
+        ClashCore::resolve(profiles.current_clash_core(), Some(ClashCore::ClashPremium)),
+        ClashCore::Mihomo
+    );
+
+    // 旧的配置文件没有该字段
+    let parsed: Profile =
+        serde_yaml::from_str("uid: old\ntype: local\nname: old\nfile: old.yaml\nupdated: 0")
+            .unwrap();
+    assert_eq!(parsed.clash_core(), None);
+    let yaml = serde_yaml::to_string(&profile("alpha", Some(ClashCore::MihomoAlpha))).unwrap();
+    assert!(yaml.contains("clash_core: mihomo-alpha"), "{yaml}");
+}
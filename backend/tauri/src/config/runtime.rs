@@ -86,3 +86,128 @@ impl IRuntime {
         }
     }
 }
+
+/// 精简配置中保留的字段，`external-controller` 与 `secret` 供客户端访问 API
+const MINIMAL_CONFIG_KEYS: &[&str] = &[
+    "port",
+    "socks-port",
+    "mixed-port",
+    "tun",
+    "dns",
+    "proxies",
+    "proxy-groups",
+    "external-controller",
+    "secret",
+];
+
+/// 代理组中引用外部资源的字段
+const GROUP_EXTERNAL_KEYS: &[&str] = &[
+    "use",
+    "include-all",
+    "include-all-proxies",
+    "include-all-providers",
+];
+
+/// 生成服务启动核心时使用的精简配置
+/// 去掉规则集、规则、脚本等外部资源，只保留启动所需字段，完整配置在核心就绪后再加载
+pub fn generate_minimal_service_config(full_config: &Mapping) -> Mapping {
+    let mut config: Mapping = MINIMAL_CONFIG_KEYS
+        .iter()
+        .filter_map(|key| {
+            full_config
+                .get(*key)
+                .map(|value| (Value::from(*key), value.clone()))
+        })
+        .collect();
+
+    if let Some(Value::Sequence(groups)) = config.get_mut("proxy-groups") {
+        for group in groups.iter_mut().filter_map(Value::as_mapping_mut) {
+            for key in GROUP_EXTERNAL_KEYS {
+                group.remove(*key);
+            }
+            // 去掉 provider 后组可能为空，核心会拒绝空组
+            let is_empty = group
+                .get("proxies")
+                .and_then(Value::as_sequence)
+                .is_none_or(|proxies| proxies.is_empty());
+            if is_empty {
+                group.insert("proxies".into(), Value::Sequence(vec!["DIRECT".into()]));
+            }
+        }
+    }
+
+    // nameserver-policy 中引用的规则集与 geosite 需要下载外部资源
+    if let Some(Value::Mapping(policy)) = config
+        .get_mut("dns")
+        .and_then(Value::as_mapping_mut)
+        .and_then(|dns| dns.get_mut("nameserver-policy"))
+    {
+        policy.retain(|key, _| {
+            !key.as_str()
+                .is_some_and(|key| key.contains("rule-set:") || key.contains("geosite:"))
+        });
+    }
+
+    config.insert("rules".into(), Value::Sequence(vec!["MATCH,DIRECT".into()]));
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_minimal_service_config() {
+        let full: Mapping = serde_yaml::from_str(
+            r#"
+mixed-port: 7890
+external-controller: 127.0.0.1:9872
+secret: foo
+mode: rule
+script:
+  code: "def main(ctx, md): return 'DIRECT'"
+rule-providers:
+  ads:
+    type: http
+    url: https://example.com/ads.yaml
+rules:
+  - RULE-SET,ads,REJECT
+  - MATCH,Proxy
+dns:
+  enable: true
+  nameserver-policy:
+    "rule-set:ads": 1.1.1.1
+    "geosite:cn": 223.5.5.5
+    "+.example.com": 8.8.8.8
+proxies:
+  - name: a
+    type: ss
+proxy-groups:
+  - name: Proxy
+    type: select
+    proxies: [a]
+  - name: Provider
+    type: select
+    use: [sub]
+"#,
+        )
+        .unwrap();
+        let minimal = generate_minimal_service_config(&full);
+        for key in ["rule-providers", "script", "mode"] {
+            assert!(minimal.get(key).is_none(), "{key} should be stripped");
+        }
+        assert_eq!(minimal.get("secret"), full.get("secret"));
+        assert_eq!(
+            minimal.get("rules"),
+            Some(&Value::Sequence(vec!["MATCH,DIRECT".into()]))
+        );
+        let policy = minimal["dns"]["nameserver-policy"].as_mapping().unwrap();
+        assert_eq!(policy.len(), 1);
+        let provider_group = &minimal["proxy-groups"][1];
+        assert!(provider_group.get("use").is_none());
+        assert_eq!(
+            provider_group["proxies"],
+            Value::Sequence(vec!["DIRECT".into()])
+        );
+    }
+}
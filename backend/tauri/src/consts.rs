@@ -32,17 +32,25 @@ pub static BUILD_INFO: Lazy<BuildInfo> = Lazy::new(|| BuildInfo {
 
 pub static IS_APPIMAGE: Lazy<bool> = Lazy::new(|| std::env::var("APPIMAGE").is_ok());
 
-#[cfg(target_os = "windows")]
-pub static IS_PORTABLE: Lazy<bool> = Lazy::new(|| {
-    if cfg!(windows) {
-        let dir = crate::utils::dirs::app_install_dir().unwrap();
-        let portable_file = dir.join(".config/PORTABLE");
-        portable_file.exists()
-    } else {
-        false
-    }
+pub static PORTABLE_MODE: Lazy<crate::utils::dirs::PortableMode> = Lazy::new(|| {
+    use crate::utils::dirs::{PortableMode, app_install_dir, detect_portable_mode};
+    app_install_dir()
+        .map(|dir| detect_portable_mode(&dir, std::env::args_os()))
+        .unwrap_or(PortableMode::Disabled)
 });
 
+/// 安全模式：不自动启动核心、服务、系统代理与定时任务，用于配置错误导致启动即崩溃时修复设置
+/// 由 `cmds::parse` 按命令行参数 `--safe-mode` 设置
+static SAFE_MODE: OnceCell<bool> = OnceCell::new();
+
+pub fn set_safe_mode(enabled: bool) {
+    let _ = SAFE_MODE.set(enabled);
+}
+
+pub fn is_safe_mode() -> bool {
+    SAFE_MODE.get().copied().unwrap_or(false)
+}
+
 /// A Tauri AppHandle copy for access from global context,
 /// maybe only access it from panic handler
 static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
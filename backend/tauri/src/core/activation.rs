@@ -0,0 +1,180 @@
+//! 配置激活流程（下载、解析、处理链、校验、写入、核心重载）各阶段的耗时与错误
+//! 阶段记录保存在 task-local 中，不在 `track` 范围内时不计时
+use crate::{config::Config, core::handle, log_err};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{cell::RefCell, collections::VecDeque, fmt::Display, future::Future, time::Instant};
+
+/// 保留最近的报告数量
+const MAX_REPORTS: usize = 10;
+
+tokio::task_local! {
+    static STAGES: RefCell<Vec<ActivationStage>>;
+}
+
+static REPORTS: Lazy<Mutex<VecDeque<ActivationReport>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(MAX_REPORTS)));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    Ok,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ActivationStage {
+    pub name: String,
+    pub duration_ms: u64,
+    pub status: StageStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ActivationReport {
+    /// 触发激活的操作，如 `switch`、`update`
+    pub trigger: String,
+    /// 失败时只包含到失败阶段为止的记录
+    pub stages: Vec<ActivationStage>,
+    pub total_ms: u64,
+    pub success: bool,
+    pub finished_at: i64,
+}
+
+fn enabled() -> bool {
+    Config::verge()
+        .latest()
+        .enable_activation_report
+        .unwrap_or(true)
+}
+
+fn in_scope() -> bool {
+    STAGES.try_with(|_| ()).is_ok()
+}
+
+/// 汇总 `fut` 中记录的阶段，完成后保存并发送 `activation-report` 事件
+/// 嵌套调用时并入外层的报告
+pub async fn track<T>(trigger: &str, fut: impl Future<Output = Result<T>>) -> Result<T> {
+    if in_scope() || !enabled() {
+        return fut.await;
+    }
+    let started = Instant::now();
+    let (result, stages) = STAGES
+        .scope(RefCell::new(Vec::new()), async {
+            let result = fut.await;
+            (result, STAGES.with(|stages| stages.take()))
+        })
+        .await;
+    let report = ActivationReport {
+        trigger: trigger.to_string(),
+        stages,
+        total_ms: started.elapsed().as_millis() as u64,
+        success: result.is_ok(),
+        finished_at: chrono::Local::now().timestamp(),
+    };
+    tracing::debug!("activation `{trigger}` finished in {}ms", report.total_ms);
+    {
+        let mut reports = REPORTS.lock();
+        if reports.len() == MAX_REPORTS {
+            reports.pop_front();
+        }
+        reports.push_back(report.clone());
+    }
+    log_err!(handle::Handle::emit("activation-report", report));
+    result
+}
+
+pub fn last_report() -> Option<ActivationReport> {
+    REPORTS.lock().back().cloned()
+}
+
+/// 使用单调时钟计时的阶段，不在 `track` 范围内时为空操作
+pub struct StageTimer(Option<Instant>);
+
+impl StageTimer {
+    pub fn start() -> Self {
+        Self(in_scope().then(Instant::now))
+    }
+
+    pub fn finish<E: Display>(self, name: impl Into<String>, error: Option<E>) {
+        let Some(started) = self.0 else {
+            return;
+        };
+        let stage = ActivationStage {
+            name: name.into(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            status: match error {
+                Some(_) => StageStatus::Failed,
+                None => StageStatus::Ok,
+            },
+            error: error.map(|e| e.to_string()),
+        };
+        let _ = STAGES.try_with(|stages| stages.borrow_mut().push(stage));
+    }
+}
+
+/// 记录一个异步阶段
+pub async fn stage<T, E: Display>(
+    name: &str,
+    fut: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let timer = StageTimer::start();
+    let result = fut.await;
+    timer.finish(name, result.as_ref().err());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn run_stages(fail_at: Option<&str>) -> (Result<()>, Vec<ActivationStage>) {
+        STAGES
+            .scope(RefCell::new(Vec::new()), async {
+                let result = async {
+                    for name in ["parse", "validation", "write"] {
+                        stage(name, async {
+                            match fail_at {
+                                Some(fail) if fail == name => anyhow::bail!("{name} failed"),
+                                _ => Ok(()),
+                            }
+                        })
+                        .await?;
+                    }
+                    Ok::<_, anyhow::Error>(())
+                }
+                .await;
+                (result, STAGES.with(|stages| stages.take()))
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_stages_are_recorded_in_order() {
+        let (result, stages) = run_stages(None).await;
+        assert!(result.is_ok());
+        let names = stages.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["parse", "validation", "write"]);
+        assert!(stages.iter().all(|s| s.status == StageStatus::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_failure_produces_partial_report() {
+        let (result, stages) = run_stages(Some("validation")).await;
+        assert!(result.is_err());
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[1].status, StageStatus::Failed);
+        assert_eq!(stages[1].error.as_deref(), Some("validation failed"));
+    }
+
+    #[tokio::test]
+    async fn test_timer_is_noop_outside_scope() {
+        assert!(StageTimer::start().0.is_none());
+        stage("outside", async { Ok::<_, anyhow::Error>(()) })
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+//! 根据主窗口是否可见调整后台轮询的频率
+//! 窗口关闭到托盘后降低频率，长时间不可见时暂停只供界面使用的推送
+//! 托盘与小组件依赖的流量统计不受影响，核心也不受影响
+use crate::{config::Config, core::handle::Handle, feat, log_err};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tauri::Manager;
+
+const TICK_INTERVAL: Duration = Duration::from_secs(10);
+const PROFILE_CHANGED_EVENT: &str = "polling-profile-changed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PollingProfile {
+    /// 窗口可见，全速轮询
+    Active,
+    /// 窗口不可见，降低频率
+    Background,
+    /// 长时间不可见，暂停连接分组推送
+    Suspended,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct PollingThresholds {
+    /// 窗口不可见多久后暂停（秒）
+    pub suspend_after_secs: u64,
+    /// 后台时连接分组的推送间隔（秒）
+    pub background_frame_secs: u64,
+}
+
+impl Default for PollingThresholds {
+    fn default() -> Self {
+        Self {
+            suspend_after_secs: 30 * 60,
+            background_frame_secs: 5,
+        }
+    }
+}
+
+impl PollingThresholds {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.background_frame_secs == 0 {
+            anyhow::bail!("background frame interval should be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+impl PollingProfile {
+    /// 连接分组的最小推送间隔，`None` 表示每帧都推送
+    pub fn connections_interval(self, thresholds: &PollingThresholds) -> Option<Duration> {
+        match self {
+            Self::Active => None,
+            Self::Background | Self::Suspended => {
+                Some(Duration::from_secs(thresholds.background_frame_secs))
+            }
+        }
+    }
+
+    /// 代理列表缓存的有效期
+    pub fn proxies_ttl(self) -> Duration {
+        match self {
+            Self::Active => Duration::from_secs(3),
+            Self::Background | Self::Suspended => Duration::from_secs(30),
+        }
+    }
+
+    /// 托盘代理列表的轮询间隔，`None` 表示不轮询
+    pub fn proxies_poll_interval(self) -> Option<Duration> {
+        match self {
+            Self::Active => Some(Duration::from_secs(10)),
+            Self::Background => Some(Duration::from_secs(60)),
+            Self::Suspended => None,
+        }
+    }
+
+    /// 是否执行代理组策略的定时测速
+    pub fn delay_watchdog(self) -> bool {
+        self == Self::Active
+    }
+}
+
+/// 窗口可见性，时间为 unix 秒
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ActivityTracker {
+    visible: bool,
+    hidden_since: u64,
+}
+
+impl ActivityTracker {
+    fn set_visible(&mut self, visible: bool, now: u64) {
+        if self.visible && !visible {
+            self.hidden_since = now;
+        }
+        self.visible = visible;
+    }
+
+    fn profile(&self, now: u64, thresholds: &PollingThresholds) -> PollingProfile {
+        if self.visible {
+            PollingProfile::Active
+        } else if now.saturating_sub(self.hidden_since) >= thresholds.suspend_after_secs {
+            PollingProfile::Suspended
+        } else {
+            PollingProfile::Background
+        }
+    }
+}
+
+pub struct ActivityManager {
+    running: AtomicBool,
+    tracker: Mutex<ActivityTracker>,
+    profile: Mutex<PollingProfile>,
+    thresholds: Mutex<PollingThresholds>,
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
+impl ActivityManager {
+    pub fn global() -> &'static ActivityManager {
+        // 启动时窗口尚未创建，静默启动时直接进入后台
+        static MANAGER: Lazy<ActivityManager> = Lazy::new(|| ActivityManager {
+            running: AtomicBool::new(false),
+            tracker: Mutex::new(ActivityTracker {
+                visible: false,
+                hidden_since: now(),
+            }),
+            profile: Mutex::new(PollingProfile::Background),
+            thresholds: Mutex::new(PollingThresholds::default()),
+        });
+        &MANAGER
+    }
+
+    pub fn profile(&self) -> PollingProfile {
+        *self.profile.lock()
+    }
+
+    /// 当前配置下连接分组的最小推送间隔
+    pub fn connections_interval(&self) -> Option<Duration> {
+        self.profile().connections_interval(&self.thresholds.lock())
+    }
+
+    /// 窗口显示、获得焦点或销毁时调用，立即重新计算
+    pub fn set_visible(&self, visible: bool) {
+        self.tracker.lock().set_visible(visible, now());
+        self.tick();
+    }
+
+    /// 按主窗口的实际状态更新可见性，隐藏或最小化视为不可见
+    pub fn sync_window<R: tauri::Runtime>(&self, app_handle: &tauri::AppHandle<R>) {
+        let visible = app_handle.get_webview_window("main").is_some_and(|window| {
+            window.is_visible().unwrap_or(false) && !window.is_minimized().unwrap_or(false)
+        });
+        self.set_visible(visible);
+    }
+
+    pub fn start(&'static self) {
+        if self.running.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(TICK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                self.tick();
+            }
+        });
+    }
+
+    fn tick(&self) {
+        let thresholds = Config::verge()
+            .latest()
+            .polling_thresholds
+            .unwrap_or_default();
+        *self.thresholds.lock() = thresholds;
+        let next = self.tracker.lock().profile(now(), &thresholds);
+        let previous = std::mem::replace(&mut *self.profile.lock(), next);
+        if previous != next {
+            tracing::info!("polling profile changed: {previous:?} -> {next:?}");
+            on_profile_changed(next);
+        }
+    }
+}
+
+fn on_profile_changed(next: PollingProfile) {
+    if next == PollingProfile::Active {
+        // 让界面立即拿到最新的数据
+        feat::update_proxies_buff(None);
+    }
+    log_err!(Handle::emit(PROFILE_CHANGED_EVENT, next));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_transitions() {
+        let thresholds = PollingThresholds {
+            suspend_after_secs: 60,
+            background_frame_secs: 5,
+        };
+        let mut tracker = ActivityTracker {
+            visible: false,
+            hidden_since: 1_000,
+        };
+        assert_eq!(
+            tracker.profile(1_000, &thresholds),
+            PollingProfile::Background
+        );
+        assert_eq!(
+            tracker.profile(1_060, &thresholds),
+            PollingProfile::Suspended
+        );
+
+        tracker.set_visible(true, 1_070);
+        assert_eq!(tracker.profile(1_070, &thresholds), PollingProfile::Active);
+        // 可见时不会因为时间推移而暂停
+        assert_eq!(tracker.profile(9_999, &thresholds), PollingProfile::Active);
+
+        // 从可见变为不可见时重新计时，重复的隐藏事件不会重置
+        tracker.set_visible(false, 2_000);
+        tracker.set_visible(false, 2_050);
+        assert_eq!(
+            tracker.profile(2_059, &thresholds),
+            PollingProfile::Background
+        );
+        assert_eq!(
+            tracker.profile(2_060, &thresholds),
+            PollingProfile::Suspended
+        );
+    }
+
+    #[test]
+    fn test_profile_rates() {
+        let thresholds = PollingThresholds::default();
+        assert_eq!(
+            PollingProfile::Active.connections_interval(&thresholds),
+            None
+        );
+        assert_eq!(
+            PollingProfile::Background.connections_interval(&thresholds),
+            Some(Duration::from_secs(5))
+        );
+        assert!(PollingProfile::Active.delay_watchdog());
+        assert!(!PollingProfile::Background.delay_watchdog());
+        assert_eq!(PollingProfile::Suspended.proxies_poll_interval(), None);
+    }
+}
@@ -0,0 +1,371 @@
+//! 监听 geodata 与本地规则集合文件，变化后刷新对应的规则集合或重载核心
+//! 监听的路径由生成的运行时配置得出，切换配置后重建
+//! 更新器先写临时文件再重命名，连续的写入由 debouncer 合并为一次处理
+use crate::{
+    core::{
+        clash::api,
+        handle, profile_activation,
+        profile_switch::{self, SwitchApplied},
+    },
+    log_err,
+    utils::dirs,
+};
+use anyhow::{Result, bail};
+use notify_debouncer_full::{
+    DebounceEventResult, Debouncer, RecommendedCache, new_debouncer,
+    notify::{EventKind, RecommendedWatcher, RecursiveMode},
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_yaml::Mapping;
+use specta::Type;
+use std::{
+    collections::BTreeSet,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+const ASSET_DEBOUNCE: Duration = Duration::from_millis(800);
+
+/// 核心工作目录中的 geodata 文件
+const GEODATA_FILES: [&str; 6] = [
+    "Country.mmdb",
+    "geoip.metadb",
+    "geoip.dat",
+    "geosite.dat",
+    "GeoLite2-ASN.mmdb",
+    "ASN.mmdb",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetKind {
+    Geodata,
+    /// 本地（`type: file`）规则集合，值为名称
+    RuleProvider(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchedAsset {
+    pub path: PathBuf,
+    pub kind: AssetKind,
+}
+
+/// 从运行时配置中得出需要监听的文件，相对路径基于核心工作目录
+pub fn watched_assets(config: &Mapping, home: &Path) -> Vec<WatchedAsset> {
+    let mut assets = GEODATA_FILES
+        .iter()
+        .map(|file| WatchedAsset {
+            path: home.join(file),
+            kind: AssetKind::Geodata,
+        })
+        .collect::<Vec<_>>();
+    let providers = config
+        .get("rule-providers")
+        .and_then(|providers| providers.as_mapping());
+    for (name, provider) in providers.into_iter().flatten() {
+        let (Some(name), Some(provider)) = (name.as_str(), provider.as_mapping()) else {
+            continue;
+        };
+        if provider.get("type").and_then(|t| t.as_str()) != Some("file") {
+            continue;
+        }
+        let Some(path) = provider.get("path").and_then(|p| p.as_str()) else {
+            continue;
+        };
+        assets.push(WatchedAsset {
+            path: home.join(path),
+            kind: AssetKind::RuleProvider(name.to_string()),
+        });
+    }
+    assets
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReloadPlan {
+    pub providers: Vec<String>,
+    /// geodata 变化后需要重载核心
+    pub reload_core: bool,
+}
+
+impl ReloadPlan {
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty() && !self.reload_core
+    }
+}
+
+pub fn plan_reload(changed: &[PathBuf], assets: &[WatchedAsset]) -> ReloadPlan {
+    let mut providers = BTreeSet::new();
+    let mut reload_core = false;
+    for asset in assets.iter().filter(|asset| changed.contains(&asset.path)) {
+        match &asset.kind {
+            AssetKind::Geodata => reload_core = true,
+            AssetKind::RuleProvider(name) => {
+                providers.insert(name.clone());
+            }
+        }
+    }
+    ReloadPlan {
+        // 重载核心时规则集合会一并重新读取
+        providers: if reload_core {
+            Vec::new()
+        } else {
+            providers.into_iter().collect()
+        },
+        reload_core,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CoreAssetsReloaded {
+    pub paths: Vec<String>,
+    pub providers: Vec<String>,
+    /// 重载核心的方式，只刷新规则集合时为 None
+    pub core: Option<SwitchApplied>,
+}
+
+pub trait AssetReloader: Send + Sync + 'static {
+    fn refresh_rule_provider(&self, name: &str) -> impl Future<Output = Result<()>> + Send;
+    /// 重启核心，geodata 只在核心启动时加载
+    fn reload_core(&self) -> impl Future<Output = Result<SwitchApplied>> + Send;
+}
+
+pub struct CoreAssetReloader;
+
+impl AssetReloader for CoreAssetReloader {
+    async fn refresh_rule_provider(&self, name: &str) -> Result<()> {
+        let result = api::refresh_rule_provider(name.to_string()).await?;
+        if !result.success {
+            bail!("{}", result.message.unwrap_or_default());
+        }
+        Ok(())
+    }
+
+    async fn reload_core(&self) -> Result<SwitchApplied> {
+        let _exclusive = profile_activation::global().exclusive().await;
+        // PUT /configs 不会重新读取 GeoIP 与 GeoSite 文件
+        profile_switch::restart_core().await
+    }
+}
+
+struct WatcherState {
+    assets: Arc<Vec<WatchedAsset>>,
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+}
+
+pub struct AssetWatcher<R> {
+    reloader: Arc<R>,
+    state: Mutex<Option<WatcherState>>,
+}
+
+impl AssetWatcher<CoreAssetReloader> {
+    pub fn global() -> &'static Self {
+        static WATCHER: OnceLock<AssetWatcher<CoreAssetReloader>> = OnceLock::new();
+        WATCHER.get_or_init(|| AssetWatcher::new(CoreAssetReloader))
+    }
+}
+
+impl<R: AssetReloader> AssetWatcher<R> {
+    pub fn new(reloader: R) -> Self {
+        Self {
+            reloader: Arc::new(reloader),
+            state: Mutex::new(None),
+        }
+    }
+
+    /// 按新的文件列表重新监听，列表未变化时不做处理
+    pub fn rebuild(&self, config: &Mapping, home: &Path) -> Result<()> {
+        let assets = watched_assets(config, home);
+        let mut state = self.state.lock();
+        if state.as_ref().is_some_and(|state| *state.assets == assets) {
+            return Ok(());
+        }
+        state.take();
+
+        let assets = Arc::new(assets);
+        let reloader = self.reloader.clone();
+        let watched = assets.clone();
+        let mut debouncer =
+            new_debouncer(ASSET_DEBOUNCE, None, move |result: DebounceEventResult| {
+                let events = match result {
+                    Ok(events) => events,
+                    Err(errors) => {
+                        tracing::warn!("asset watcher error: {errors:?}");
+                        return;
+                    }
+                };
+                let mut changed = events
+                    .iter()
+                    .filter(|event| {
+                        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    })
+                    .flat_map(|event| event.paths.iter())
+                    .filter(|path| path.is_file())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                changed.sort();
+                changed.dedup();
+                let plan = plan_reload(&changed, &watched);
+                if plan.is_empty() {
+                    return;
+                }
+                let reloader = reloader.clone();
+                tauri::async_runtime::spawn(async move {
+                    execute_plan(reloader.as_ref(), plan, changed).await;
+                });
+            })?;
+        let dirs = assets
+            .iter()
+            .filter_map(|asset| asset.path.parent())
+            .filter(|dir| dir.is_dir())
+            .collect::<BTreeSet<_>>();
+        for dir in dirs {
+            debouncer.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        tracing::debug!("watching {} core asset files", assets.len());
+        *state = Some(WatcherState {
+            assets,
+            _debouncer: debouncer,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        self.state.lock().take();
+    }
+}
+
+async fn execute_plan<R: AssetReloader>(reloader: &R, plan: ReloadPlan, changed: Vec<PathBuf>) {
+    tracing::info!("core assets changed: {changed:?}");
+    let core = if plan.reload_core {
+        match reloader.reload_core().await {
+            Ok(applied) => Some(applied),
+            Err(e) => {
+                tracing::error!("failed to reload core after assets changed: {e:?}");
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    let mut providers = Vec::with_capacity(plan.providers.len());
+    for name in plan.providers {
+        match reloader.refresh_rule_provider(&name).await {
+            Ok(()) => providers.push(name),
+            Err(e) => tracing::error!("failed to refresh rule provider `{name}`: {e:?}"),
+        }
+    }
+    log_err!(handle::Handle::emit(
+        "core-assets-reloaded",
+        CoreAssetsReloaded {
+            paths: changed
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+            providers,
+            core,
+        }
+    ));
+}
+
+/// 运行时配置生成后调用
+pub fn rebuild_asset_watcher(config: &Mapping) -> Result<()> {
+    AssetWatcher::global().rebuild(config, &dirs::app_data_dir()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config() -> Mapping {
+        serde_yaml::from_str(
+            r#"
+rule-providers:
+  local:
+    type: file
+    behavior: domain
+    path: ./rules/local.yaml
+  remote:
+    type: http
+    behavior: domain
+    url: https://example.com/remote.yaml
+    path: ./rules/remote.yaml
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_watched_assets_and_plan() {
+        let home = Path::new("/data");
+        let assets = watched_assets(&config(), home);
+        assert_eq!(assets.len(), GEODATA_FILES.len() + 1);
+        assert_eq!(
+            assets.last().unwrap().kind,
+            AssetKind::RuleProvider("local".into())
+        );
+
+        let plan = plan_reload(&[PathBuf::from("/data/rules/local.yaml")], &assets);
+        assert_eq!(plan.providers, ["local"]);
+        assert!(!plan.reload_core);
+
+        let plan = plan_reload(
+            &[
+                PathBuf::from("/data/rules/local.yaml"),
+                PathBuf::from("/data/Country.mmdb"),
+            ],
+            &assets,
+        );
+        assert!(plan.reload_core && plan.providers.is_empty());
+        assert!(plan_reload(&[PathBuf::from("/data/rules/remote.yaml")], &assets).is_empty());
+    }
+
+    #[derive(Default)]
+    struct MockReloader {
+        refreshed: Arc<Mutex<Vec<String>>>,
+        reloads: Arc<AtomicUsize>,
+    }
+
+    impl AssetReloader for MockReloader {
+        async fn refresh_rule_provider(&self, name: &str) -> Result<()> {
+            self.refreshed.lock().push(name.to_string());
+            Ok(())
+        }
+
+        async fn reload_core(&self) -> Result<SwitchApplied> {
+            self.reloads.fetch_add(1, Ordering::SeqCst);
+            Ok(SwitchApplied::Restarted)
+        }
+    }
+
+    #[test]
+    fn test_rapid_writes_collapse_to_one_reload() {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::create_dir(home.path().join("rules")).unwrap();
+        let provider = home.path().join("rules/local.yaml");
+        std::fs::write(&provider, "payload: []").unwrap();
+
+        let reloader = MockReloader::default();
+        let (refreshed, reloads) = (reloader.refreshed.clone(), reloader.reloads.clone());
+        let watcher = AssetWatcher::new(reloader);
+        watcher.rebuild(&config(), home.path()).unwrap();
+
+        for i in 0..5 {
+            std::fs::write(&provider, format!("payload: ['{i}.example.com']")).unwrap();
+        }
+        std::thread::sleep(ASSET_DEBOUNCE * 3);
+        assert_eq!(*refreshed.lock(), ["local"]);
+        assert_eq!(reloads.load(Ordering::SeqCst), 0);
+
+        // 先写临时文件再重命名
+        let temp = home.path().join("Country.mmdb.tmp");
+        std::fs::write(&temp, "mmdb").unwrap();
+        std::fs::rename(&temp, home.path().join("Country.mmdb")).unwrap();
+        std::thread::sleep(ASSET_DEBOUNCE * 3);
+        assert_eq!(reloads.load(Ordering::SeqCst), 1);
+        assert_eq!(refreshed.lock().len(), 1);
+        watcher.stop();
+    }
+}
@@ -1,13 +1,18 @@
-use crate::config::Config;
+use super::signing;
+use crate::config::{ClashController, ControllerEndpoint};
 use anyhow::{Context, Result};
 use indexmap::IndexMap;
-use reqwest::{Method, StatusCode, header::HeaderMap};
+use reqwest::{
+    Method, StatusCode,
+    header::{HeaderMap, HeaderName},
+};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
 use specta::Type;
 use std::{
     collections::HashMap,
     fmt::{self, Display, Formatter},
+    time::{Duration, Instant},
 };
 use tracing_attributes::instrument;
 use url::Url;
@@ -34,6 +39,61 @@ pub async fn patch_configs(config: &Mapping) -> Result<()> {
     Ok(())
 }
 
+/// GET /configs
+/// 获取核心当前的运行配置
+#[instrument]
+pub async fn get_configs() -> Result<Mapping> {
+    let path = "/configs";
+    let resp: Mapping = perform_request((Method::GET, path)).await?.json().await?;
+    Ok(resp)
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+pub struct VersionRes {
+    pub version: String,
+    /// Mihomo Only
+    #[serde(default)]
+    pub meta: bool,
+    /// Premium Only
+    #[serde(default)]
+    pub premium: bool,
+}
+
+/// GET /version
+#[instrument]
+pub async fn get_version() -> Result<VersionRes> {
+    let path = "/version";
+    let resp: VersionRes = perform_request((Method::GET, path)).await?.json().await?;
+    Ok(resp)
+}
+
+const CONTROLLER_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 测量请求 `/version` 的耗时，API 不可达或未就绪时返回 `None`
+/// 失败只记录调试日志，可以用于轮询
+pub async fn controller_ping() -> Option<Duration> {
+    let ping = async {
+        let (endpoint, base_url, mut headers) = clash_client_info()?;
+        let url = base_url.join("/version")?;
+        signing::sign_headers(&mut headers, Method::GET.as_str(), url.path());
+        let client = clash_http_client(&endpoint)?;
+        let started = Instant::now();
+        client
+            .get(url)
+            .headers(headers)
+            .timeout(CONTROLLER_PING_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<VersionRes>()
+            .await?;
+        Ok::<_, anyhow::Error>(started.elapsed())
+    };
+    ping.await
+        .inspect_err(|e| tracing::debug!("controller ping failed: {e:?}"))
+        .ok()
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxiesRes {
@@ -67,7 +127,10 @@ pub struct ProxyItem {
     pub icon: Option<String>, // Mihomo Only
     #[serde(default)]
     pub hidden: bool, // Mihomo Only
-                             // extra: {}, // Mihomo Only
+    /// 用户置顶，由后端填充
+    #[serde(default)]
+    pub pinned: bool,
+    // extra: {}, // Mihomo Only
 }
 
 impl From<ProxyProviderItem> for ProxyItem {
@@ -79,6 +142,7 @@ impl From<ProxyProviderItem> for ProxyItem {
             vehicle_type: _,
             test_url: _,
             expected_status: _,
+            updated_at: _,
         } = item;
 
         let now = proxies
@@ -102,6 +166,7 @@ impl From<ProxyProviderItem> for ProxyItem {
             tfo: None,
             icon: None,
             hidden: false,
+            pinned: false,
         }
     }
 }
@@ -183,6 +248,8 @@ pub struct ProxyProviderItem {
     pub test_url: Option<String>, // Mihomo Only
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expected_status: Option<String>, // Mihomo Only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
@@ -201,10 +268,51 @@ pub async fn get_providers_proxies() -> Result<ProvidersProxiesRes> {
     Ok(resp)
 }
 
+/// 代理集合概要信息，不包含节点详情
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderInfo {
+    pub name: String,
+    pub vehicle_type: VehicleType,
+    pub node_count: usize,
+    /// 核心返回的最后更新时间 (RFC 3339)
+    pub updated_at: Option<String>,
+    /// 配置中的刷新间隔（秒），仅 http 集合
+    #[serde(default)]
+    pub interval: Option<u64>,
+    /// 最后一次主动刷新的时间（unix 秒）
+    #[serde(default)]
+    pub last_refreshed_at: Option<i64>,
+}
+
+impl From<&ProxyProviderItem> for ProviderInfo {
+    fn from(item: &ProxyProviderItem) -> Self {
+        Self {
+            name: item.name.clone(),
+            vehicle_type: item.vehicle_type.clone(),
+            node_count: item.proxies.len(),
+            updated_at: item.updated_at.clone(),
+            interval: None,
+            last_refreshed_at: None,
+        }
+    }
+}
+
+/// 获取所有订阅/文件类型的代理集合概要
+#[instrument]
+pub async fn get_proxy_providers() -> Result<Vec<ProviderInfo>> {
+    let resp = get_providers_proxies().await?;
+    Ok(resp
+        .providers
+        .values()
+        .filter(|item| matches!(item.vehicle_type, VehicleType::Http | VehicleType::File))
+        .map(ProviderInfo::from)
+        .collect())
+}
+
 /// GET /providers/proxies/:name
 /// 获取单个代理集合的所有代理信息
 /// group: 代理集合名称
-#[allow(dead_code)]
 #[instrument]
 pub async fn get_providers_proxies_group(group: String) -> Result<ProxyProviderItem> {
     let path = format!("/providers/proxies/{group}");
@@ -239,9 +347,167 @@ pub async fn get_providers_proxies_healthcheck(name: String) -> Result<Mapping>
     Ok(resp)
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleProviderItem {
+    pub name: String,
+    #[serde(default)]
+    pub behavior: String,
+    #[serde(default)]
+    pub rule_count: u64,
+    pub vehicle_type: VehicleType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleProvidersRes {
+    #[serde(default)]
+    pub providers: IndexMap<String, RuleProviderItem>,
+}
+
+/// 规则集合刷新结果
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleProviderRefreshResult {
+    pub name: String,
+    pub success: bool,
+    /// 失败原因
+    pub message: Option<String>,
+    /// 核心不支持单独刷新，没有刷新该集合
+    pub skipped: bool,
+    /// 跳过后已通过重载核心配置刷新
+    pub reloaded: bool,
+}
+
+impl RuleProviderRefreshResult {
+    fn refreshed(name: String) -> Self {
+        Self {
+            name,
+            success: true,
+            message: None,
+            skipped: false,
+            reloaded: false,
+        }
+    }
+
+    fn failed(name: String, err: &anyhow::Error) -> Self {
+        Self {
+            name,
+            success: false,
+            message: Some(format!("{err:#}")),
+            skipped: false,
+            reloaded: false,
+        }
+    }
+
+    /// 跳过的集合记录重载的结果
+    fn skipped(name: String, reload: &Result<()>) -> Self {
+        Self {
+            name,
+            success: false,
+            message: reload.as_ref().err().map(|e| format!("{e:#}")),
+            skipped: true,
+            reloaded: reload.is_ok(),
+        }
+    }
+}
+
+/// GET /providers/rules
+/// 获取所有规则集合
+#[instrument]
+pub async fn get_providers_rules() -> Result<RuleProvidersRes> {
+    let path = "/providers/rules";
+    let resp: RuleProvidersRes = perform_request((Method::GET, path)).await?.json().await?;
+    Ok(resp)
+}
+
+/// PUT /providers/rules/:name
+/// 更新规则集合
+/// name: 规则集合名称
+#[instrument]
+pub async fn update_providers_rules_group(name: &str) -> Result<()> {
+    let path = format!("/providers/rules/{name}");
+    let _ = perform_request((Method::PUT, path.as_str())).await?;
+    Ok(())
+}
+
+/// 判断错误是否因为核心不支持该接口
+fn is_unsupported_endpoint(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|s| s == StatusCode::NOT_FOUND || s == StatusCode::METHOD_NOT_ALLOWED)
+}
+
+/// 核心不支持刷新规则集合时，重载核心配置来刷新
+async fn reload_core_for_rule_providers() -> Result<()> {
+    tracing::info!("core does not support refreshing rule providers, reloading config instead");
+    crate::core::CoreManager::global().update_config().await
+}
+
+/// 刷新单个规则集合，不支持时回退为重载核心
+/// 先确认集合存在，避免把未知集合的 404 当作核心不支持
+#[instrument]
+pub async fn refresh_rule_provider(name: String) -> Result<RuleProviderRefreshResult> {
+    match get_providers_rules().await {
+        Ok(res) if !res.providers.contains_key(&name) => {
+            let err = anyhow::anyhow!("rule provider `{name}` not found");
+            return Ok(RuleProviderRefreshResult::failed(name, &err));
+        }
+        Ok(_) => {}
+        Err(e) if is_unsupported_endpoint(&e) => {
+            let reload = reload_core_for_rule_providers().await;
+            return Ok(RuleProviderRefreshResult::skipped(name, &reload));
+        }
+        Err(e) => return Ok(RuleProviderRefreshResult::failed(name, &e)),
+    }
+    Ok(match update_providers_rules_group(&name).await {
+        Ok(_) => RuleProviderRefreshResult::refreshed(name),
+        Err(e) if is_unsupported_endpoint(&e) => {
+            let reload = reload_core_for_rule_providers().await;
+            RuleProviderRefreshResult::skipped(name, &reload)
+        }
+        Err(e) => RuleProviderRefreshResult::failed(name, &e),
+    })
+}
+
+/// 刷新所有规则集合，返回每个集合的刷新结果
+#[instrument]
+pub async fn refresh_all_rule_providers() -> Result<Vec<RuleProviderRefreshResult>> {
+    let providers = match get_providers_rules().await {
+        Ok(res) => res.providers,
+        Err(e) if is_unsupported_endpoint(&e) => {
+            reload_core_for_rule_providers().await?;
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut results = Vec::with_capacity(providers.len());
+    let mut unsupported = Vec::new();
+    for name in providers.into_keys() {
+        match update_providers_rules_group(&name).await {
+            Ok(_) => results.push(RuleProviderRefreshResult::refreshed(name)),
+            Err(e) if is_unsupported_endpoint(&e) => unsupported.push(name),
+            Err(e) => results.push(RuleProviderRefreshResult::failed(name, &e)),
+        }
+    }
+    // 重载完成后才能确定跳过的集合是否已刷新
+    if !unsupported.is_empty() {
+        let reload = reload_core_for_rule_providers().await;
+        results.extend(
+            unsupported
+                .into_iter()
+                .map(|name| RuleProviderRefreshResult::skipped(name, &reload)),
+        );
+    }
+    Ok(results)
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize, Type)]
 pub struct DelayRes {
-    delay: u64,
+    pub delay: u64,
 }
 
 /// GET /proxies/{name}/delay
@@ -262,22 +528,75 @@ pub async fn get_proxy_delay(name: String, test_url: Option<String>) -> Result<D
     Ok(resp)
 }
 
-/// 根据clash info获取clash服务地址和请求头
+/// 根据生效的配置获取clash服务地址和请求头
 #[instrument]
-fn clash_client_info() -> Result<(String, HeaderMap)> {
-    let client = { Config::clash().data().get_client_info() };
-
-    let server = format!("http://{}", client.server);
+fn clash_client_info() -> Result<(ControllerEndpoint, Url, HeaderMap)> {
+    let controller = ClashController::current();
+    let url = controller.url()?;
 
     let mut headers = HeaderMap::new();
     headers.insert("Content-Type", "application/json".parse()?);
 
-    if let Some(secret) = client.secret {
+    if let Some(secret) = controller.secret {
         let secret = format!("Bearer {secret}").parse()?;
         headers.insert("Authorization", secret);
     }
 
-    Ok((server, headers))
+    Ok((controller.endpoint, url, headers))
+}
+
+/// 按 API 地址选择 TCP 或 unix socket 连接
+pub(super) fn clash_http_client(endpoint: &ControllerEndpoint) -> Result<reqwest::Client> {
+    let builder = reqwest::ClientBuilder::new().no_proxy();
+    let builder = match endpoint {
+        ControllerEndpoint::Tcp(_) => builder,
+        #[cfg(unix)]
+        ControllerEndpoint::Unix(path) => builder.unix_socket(path.as_str()),
+        #[cfg(not(unix))]
+        ControllerEndpoint::Unix(path) => anyhow::bail!("unix socket `{path}` is not supported"),
+    };
+    Ok(builder.build()?)
+}
+
+/// 连接核心 API 的传输层
+pub trait ClashStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send {}
+
+impl<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send> ClashStream for T {}
+
+pub type ClashWebSocket = tokio_tungstenite::WebSocketStream<Box<dyn ClashStream>>;
+
+/// 连接核心 API 的 WebSocket，path 含查询参数
+pub async fn connect_clash_websocket(path_and_query: &str) -> Result<ClashWebSocket> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let (endpoint, mut url, mut headers) =
+        clash_client_info().context("failed to get clash client info")?;
+    url.set_scheme("ws")
+        .map_err(|_| anyhow::anyhow!("failed to set websocket scheme"))?;
+    let url = url.join(path_and_query).context("failed to parse path")?;
+    signing::sign_headers(&mut headers, Method::GET.as_str(), url.path());
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .context("failed to create client request")?;
+    for name in [
+        reqwest::header::AUTHORIZATION,
+        HeaderName::from_static(signing::SIGNATURE_HEADER),
+        HeaderName::from_static(signing::TIMESTAMP_HEADER),
+    ] {
+        if let Some(value) = headers.remove(&name) {
+            request.headers_mut().insert(name, value);
+        }
+    }
+    let stream: Box<dyn ClashStream> = match &endpoint {
+        ControllerEndpoint::Tcp(addr) => Box::new(tokio::net::TcpStream::connect(addr).await?),
+        #[cfg(unix)]
+        ControllerEndpoint::Unix(path) => Box::new(tokio::net::UnixStream::connect(path).await?),
+        #[cfg(not(unix))]
+        ControllerEndpoint::Unix(path) => anyhow::bail!("unix socket `{path}` is not supported"),
+    };
+    let (stream, _) = tokio_tungstenite::client_async(request, stream).await?;
+    Ok(stream)
 }
 
 /// The Request Parameters
@@ -365,10 +684,11 @@ where
         data,
         query,
     } = param.into();
-    let (host, headers) = clash_client_info().context("failed to get clash client info")?;
-    let base_url = Url::parse(&host).context("failed to parse host")?;
+    let (endpoint, base_url, mut headers) =
+        clash_client_info().context("failed to get clash client info")?;
     let opts = url::Url::options().base_url(Some(&base_url));
     let url = opts.parse(&path).context("failed to parse path")?;
+    signing::sign_headers(&mut headers, method.as_str(), url.path());
 
     let span = tracing::Span::current();
     span.record("method", tracing::field::display(&method));
@@ -377,7 +697,7 @@ where
     span.record("data", tracing::field::debug(&data));
 
     async {
-        let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+        let client = clash_http_client(&endpoint)?;
         let mut builder = client.request(method.clone(), url.clone()).headers(headers);
 
         if let Some(query) = &query {
@@ -458,6 +778,69 @@ pub fn parse_check_output(log: String) -> String {
     log
 }
 
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionMetadata {
+    #[serde(default)]
+    pub network: String,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default, rename = "destinationIP")]
+    pub destination_ip: String,
+    #[serde(default)]
+    pub destination_port: String,
+    #[serde(default)]
+    pub process: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+pub struct ConnectionItem {
+    pub id: String,
+    #[serde(default)]
+    pub metadata: ConnectionMetadata,
+    #[serde(default)]
+    pub upload: u64,
+    #[serde(default)]
+    pub download: u64,
+    /// RFC 3339 格式的建立时间
+    pub start: String,
+    #[serde(default)]
+    pub chains: Vec<String>,
+    #[serde(default)]
+    pub rule: String,
+    #[serde(default, rename = "rulePayload")]
+    pub rule_payload: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionsRes {
+    #[serde(default)]
+    pub download_total: u64,
+    #[serde(default)]
+    pub upload_total: u64,
+    /// 没有连接时核心返回 null
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub connections: Vec<ConnectionItem>,
+}
+
+fn deserialize_null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// GET /connections
+/// 获取当前连接的快照
+#[instrument]
+pub async fn get_connections() -> Result<ConnectionsRes> {
+    let path = "/connections";
+    let resp: ConnectionsRes = perform_request((Method::GET, path)).await?.json().await?;
+    Ok(resp)
+}
+
 /// DELETE /connections
 /// Close all connections or a specific connection by ID
 #[instrument]
@@ -471,6 +854,334 @@ pub async fn delete_connections(id: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Type)]
+pub struct DnsAnswer {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub record_type: u16,
+    #[serde(default)]
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Type)]
+pub struct DnsQueryRes {
+    #[serde(default, rename = "Answer")]
+    pub answer: Vec<DnsAnswer>,
+}
+
+/// GET /dns/query
+/// 使用核心的 DNS 解析域名，query_type 为 `A`、`AAAA` 等
+#[instrument]
+pub async fn dns_query(name: &str, query_type: &str) -> Result<DnsQueryRes> {
+    let path = "/dns/query";
+    let query = Query([("name", name), ("type", query_type)]);
+    let resp: DnsQueryRes = perform_request((Method::GET, path, query))
+        .await?
+        .json()
+        .await?;
+    Ok(resp)
+}
+
+/// 外部 UI 反向代理
+/// 将 external-ui 及其 API 请求转发到 clash，并自动附加鉴权头
+/// 代理只接受携带本次会话令牌的请求，防止其他网页借助它访问控制器
+#[derive(Debug, Clone)]
+pub struct ClashExtUIProxy {
+    port: u16,
+    token: String,
+}
+
+/// 注入到外部 UI 响应中的 CSP，只允许应用自身内嵌
+const EXT_UI_CONTENT_SECURITY_POLICY: &str = "default-src 'self' 'unsafe-inline' data: blob: http: https: ws: wss:; frame-ancestors 'self' tauri://localhost http://tauri.localhost https://tauri.localhost http://localhost:3000";
+
+/// 转发请求体的大小上限
+const EXT_UI_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// 会话令牌的查询参数与 cookie 名
+const EXT_UI_TOKEN_PARAM: &str = "token";
+const EXT_UI_TOKEN_COOKIE: &str = "nyanpasu_ext_ui_token";
+
+/// 不应被转发的逐跳请求头
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+impl ClashExtUIProxy {
+    /// 在系统分配的端口上启动反向代理
+    pub fn spawn() -> Result<Self> {
+        // 直接绑定 0 端口再读取实际端口，避免选端口与绑定之间被抢占
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))
+            .context("failed to bind external ui proxy")?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let token = format!("{:032x}", rand::random::<u128>());
+        let proxy = Self { port, token };
+        let state = proxy.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = state.run(listener).await {
+                tracing::error!("external ui proxy exited: {e:?}");
+            }
+        });
+        Ok(proxy)
+    }
+
+    /// 代理后的 dashboard 地址，带有本次会话的令牌
+    pub fn dashboard_url(&self) -> String {
+        format!(
+            "http://127.0.0.1:{}/ui/?{EXT_UI_TOKEN_PARAM}={}",
+            self.port, self.token
+        )
+    }
+
+    async fn run(self, listener: std::net::TcpListener) -> Result<()> {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        tracing::debug!("external ui proxy listening on {}", listener.local_addr()?);
+        let app = axum::Router::new().fallback(move |req| ext_ui_proxy_handler(req, self.clone()));
+        axum::serve(listener, app).await?;
+        Ok(())
+    }
+
+    /// 只接受 `127.0.0.1:<port>` 的 Host，防止 DNS rebinding
+    fn check_host(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(reqwest::header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|host| host == format!("127.0.0.1:{}", self.port))
+    }
+
+    /// 从查询参数或 cookie 中取出令牌并校验
+    /// 返回值表示令牌是否来自查询参数，此时需要下发 cookie
+    fn check_token(&self, headers: &HeaderMap, query: Option<&str>) -> Option<bool> {
+        let from_query = query.and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == EXT_UI_TOKEN_PARAM)
+                .map(|(_, value)| value.into_owned())
+        });
+        if let Some(token) = from_query {
+            return token_eq(&token, &self.token).then_some(true);
+        }
+        headers
+            .get_all(reqwest::header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .any(|(name, value)| name == EXT_UI_TOKEN_COOKIE && token_eq(value, &self.token))
+            .then_some(false)
+    }
+
+    fn token_cookie(&self) -> String {
+        format!(
+            "{EXT_UI_TOKEN_COOKIE}={}; Path=/; HttpOnly; SameSite=Strict",
+            self.token
+        )
+    }
+}
+
+/// 长度相同时逐字节比较全部内容，避免按时间猜测令牌
+fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// 去掉令牌参数，不转发给 clash
+fn strip_token_param(path: &str, query: Option<&str>) -> String {
+    let Some(query) = query else {
+        return path.to_string();
+    };
+    let rest =
+        url::form_urlencoded::parse(query.as_bytes()).filter(|(key, _)| key != EXT_UI_TOKEN_PARAM);
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(rest)
+        .finish();
+    if query.is_empty() {
+        path.to_string()
+    } else {
+        format!("{path}?{query}")
+    }
+}
+
+/// 开启请求签名时，跨站发出的 API 请求必须带有有效签名
+fn check_ext_ui_signature(parts: &axum::http::request::Parts, port: u16) -> Result<()> {
+    let path = parts.uri.path();
+    if path.starts_with("/ui/") || !signing::enabled() {
+        return Ok(());
+    }
+    if !signing::is_cross_site(&parts.headers, port) {
+        return Ok(());
+    }
+    let secret = ClashController::current().secret.unwrap_or_default();
+    let now = chrono::Utc::now().timestamp();
+    if secret.is_empty()
+        || !signing::verify(&secret, parts.method.as_str(), path, &parts.headers, now)
+    {
+        anyhow::bail!("missing or invalid request signature");
+    }
+    Ok(())
+}
+
+async fn ext_ui_proxy_handler(
+    req: axum::extract::Request,
+    proxy: ClashExtUIProxy,
+) -> axum::response::Response {
+    use axum::{extract::FromRequestParts, response::IntoResponse};
+
+    if !proxy.check_host(req.headers()) {
+        tracing::warn!("rejected external ui request with unexpected host");
+        return (StatusCode::FORBIDDEN, "unexpected host").into_response();
+    }
+    let Some(set_cookie) = proxy.check_token(req.headers(), req.uri().query()) else {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+    };
+    let port = proxy.port;
+
+    let is_websocket = req
+        .headers()
+        .get(reqwest::header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let (mut parts, body) = req.into_parts();
+    let path_and_query = strip_token_param(parts.uri.path(), parts.uri.query());
+    if let Err(e) = check_ext_ui_signature(&parts, port) {
+        tracing::warn!("rejected external ui request to {path_and_query}: {e}");
+        return (StatusCode::FORBIDDEN, e.to_string()).into_response();
+    }
+
+    let result = if is_websocket {
+        match axum::extract::ws::WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+            Ok(ws) => Ok(ws.on_upgrade(move |socket| async move {
+                if let Err(e) = forward_ext_ui_websocket(socket, &path_and_query).await {
+                    tracing::warn!("external ui websocket forwarding failed: {e:?}");
+                }
+            })),
+            Err(rejection) => return rejection.into_response(),
+        }
+    } else {
+        forward_ext_ui_request(parts, body, &path_and_query).await
+    };
+
+    match result {
+        Ok(mut response) => {
+            let headers = response.headers_mut();
+            headers.remove(reqwest::header::X_FRAME_OPTIONS);
+            headers.insert(
+                reqwest::header::CONTENT_SECURITY_POLICY,
+                reqwest::header::HeaderValue::from_static(EXT_UI_CONTENT_SECURITY_POLICY),
+            );
+            if set_cookie && let Ok(cookie) = proxy.token_cookie().parse() {
+                headers.append(reqwest::header::SET_COOKIE, cookie);
+            }
+            response
+        }
+        Err(e) => {
+            tracing::error!("failed to proxy external ui request: {e:?}");
+            (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+        }
+    }
+}
+
+async fn forward_ext_ui_request(
+    parts: axum::http::request::Parts,
+    body: axum::body::Body,
+    path_and_query: &str,
+) -> Result<axum::response::Response> {
+    let (endpoint, base_url, auth_headers) =
+        clash_client_info().context("failed to get clash client info")?;
+    let url = base_url.join(path_and_query)?;
+
+    let mut headers = parts.headers;
+    headers.remove(reqwest::header::HOST);
+    headers.remove(reqwest::header::COOKIE);
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+    if let Some(auth) = auth_headers.get(reqwest::header::AUTHORIZATION) {
+        headers.insert(reqwest::header::AUTHORIZATION, auth.clone());
+    }
+    signing::sign_headers(&mut headers, parts.method.as_str(), url.path());
+    let body = axum::body::to_bytes(body, EXT_UI_MAX_BODY_SIZE)
+        .await
+        .context("failed to read request body")?;
+
+    let client = clash_http_client(&endpoint)?;
+    let resp = client
+        .request(parts.method, url)
+        .headers(headers)
+        .body(body)
+        .send()
+        .await?;
+
+    let mut builder = axum::http::Response::builder().status(resp.status());
+    for (name, value) in resp.headers() {
+        if !HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+            builder = builder.header(name, value);
+        }
+    }
+    Ok(builder.body(axum::body::Body::from_stream(resp.bytes_stream()))?)
+}
+
+async fn forward_ext_ui_websocket(
+    socket: axum::extract::ws::WebSocket,
+    path_and_query: &str,
+) -> Result<()> {
+    use axum::extract::ws::Message as AxumMessage;
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let upstream = connect_clash_websocket(path_and_query).await?;
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+    let (mut client_tx, mut client_rx) = socket.split();
+
+    let client_to_upstream = async {
+        while let Some(Ok(msg)) = client_rx.next().await {
+            let msg = match msg {
+                AxumMessage::Text(text) => Message::text(text.as_str()),
+                AxumMessage::Binary(bin) => Message::binary(bin),
+                AxumMessage::Ping(data) => Message::Ping(data),
+                AxumMessage::Pong(data) => Message::Pong(data),
+                AxumMessage::Close(_) => break,
+            };
+            if upstream_tx.send(msg).await.is_err() {
+                break;
+            }
+        }
+        let _ = upstream_tx.close().await;
+    };
+    let upstream_to_client = async {
+        while let Some(Ok(msg)) = upstream_rx.next().await {
+            let msg = match msg {
+                Message::Text(text) => AxumMessage::text(text.as_str()),
+                Message::Binary(bin) => AxumMessage::binary(bin),
+                Message::Ping(data) => AxumMessage::Ping(data),
+                Message::Pong(data) => AxumMessage::Pong(data),
+                Message::Close(_) => break,
+                Message::Frame(_) => continue,
+            };
+            if client_tx.send(msg).await.is_err() {
+                break;
+            }
+        }
+        let _ = client_tx.close().await;
+    };
+    // 任意一端断开即结束转发
+    tokio::select! {
+        _ = client_to_upstream => {},
+        _ = upstream_to_client => {},
+    }
+    Ok(())
+}
+
 #[test]
 fn test_parse_check_output() {
     let str1 = r#"xxxx\n time="2022-11-18T20:42:58+08:00" level=error msg="proxy 0: 'alpn' expected type 'string', got unconvertible type '[]interface {}'""#;
@@ -512,3 +1223,61 @@ fn test_path() {
         .unwrap();
     assert_eq!(url.to_string(), "http://127.0.0.1:9090/configs");
 }
+
+#[test]
+fn test_ext_ui_proxy_rejects_foreign_host() {
+    let proxy = ClashExtUIProxy {
+        port: 12345,
+        token: "secret-token".into(),
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(reqwest::header::HOST, "127.0.0.1:12345".parse().unwrap());
+    assert!(proxy.check_host(&headers));
+    // DNS rebinding 时 Host 为攻击者的域名
+    headers.insert(reqwest::header::HOST, "evil.example:12345".parse().unwrap());
+    assert!(!proxy.check_host(&headers));
+    headers.insert(reqwest::header::HOST, "127.0.0.1:9090".parse().unwrap());
+    assert!(!proxy.check_host(&headers));
+    assert!(!proxy.check_host(&HeaderMap::new()));
+}
+
+#[test]
+fn test_ext_ui_proxy_token() {
+    let proxy = ClashExtUIProxy {
+        port: 12345,
+        token: "secret-token".into(),
+    };
+    let empty = HeaderMap::new();
+    assert_eq!(proxy.check_token(&empty, None), None);
+    assert_eq!(
+        proxy.check_token(&empty, Some("token=secret-token")),
+        Some(true)
+    );
+    assert_eq!(proxy.check_token(&empty, Some("token=wrong")), None);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        reqwest::header::COOKIE,
+        "other=1; nyanpasu_ext_ui_token=secret-token"
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(proxy.check_token(&headers, None), Some(false));
+    // 查询参数中的错误令牌不能被 cookie 覆盖
+    assert_eq!(proxy.check_token(&headers, Some("token=wrong")), None);
+    headers.insert(
+        reqwest::header::COOKIE,
+        "nyanpasu_ext_ui_token=secret-tokeX".parse().unwrap(),
+    );
+    assert_eq!(proxy.check_token(&headers, None), None);
+}
+
+#[test]
+fn test_strip_token_param() {
+    assert_eq!(strip_token_param("/ui/", Some("token=abc")), "/ui/");
+    assert_eq!(
+        strip_token_param("/logs", Some("token=abc&level=info")),
+        "/logs?level=info"
+    );
+    assert_eq!(strip_token_param("/version", None), "/version");
+}
@@ -0,0 +1,195 @@
+//! 并发测量单个节点的延迟，返回分位数与吞吐
+//! 核心的 SOCKS5 入站无法按请求指定节点，因此通过 `/proxies/{name}/delay` 由核心经指定节点发起请求，
+//! 既不会切换当前选择，也不受规则影响
+use super::api;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_BENCHMARK_URLS: &[&str] = &[
+    "https://www.gstatic.com/generate_204",
+    "https://cp.cloudflare.com/generate_204",
+];
+pub const DEFAULT_CONCURRENCY: u32 = 4;
+const MAX_CONCURRENCY: u32 = 32;
+const MAX_DURATION_SECS: u32 = 60;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct BenchmarkResult {
+    pub proxy: String,
+    pub requests_sent: u64,
+    pub successful: u64,
+    pub failed: u64,
+    /// 毫秒，只统计成功的请求
+    pub latency_p50: u32,
+    pub latency_p95: u32,
+    pub latency_p99: u32,
+    /// 每秒成功的请求数
+    pub throughput_rps: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClashBenchmarkSuite {
+    pub test_urls: Vec<String>,
+    pub concurrency: u32,
+    pub duration: Duration,
+}
+
+impl Default for ClashBenchmarkSuite {
+    fn default() -> Self {
+        Self {
+            test_urls: DEFAULT_BENCHMARK_URLS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            concurrency: DEFAULT_CONCURRENCY,
+            duration: Duration::from_secs(10),
+        }
+    }
+}
+
+/// 最近秩法计算分位数，`sorted` 需要升序
+fn percentile(sorted: &[u32], p: f64) -> u32 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl ClashBenchmarkSuite {
+    /// 在测试时长内由多个 worker 轮流请求各测试地址，`probe` 返回单次请求的延迟（毫秒）
+    async fn run_with<F, Fut>(&self, proxy: &str, probe: F) -> BenchmarkResult
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = anyhow::Result<u32>>,
+    {
+        let urls = match self.test_urls.is_empty() {
+            true => DEFAULT_BENCHMARK_URLS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            false => self.test_urls.clone(),
+        };
+        let concurrency = self.concurrency.clamp(1, MAX_CONCURRENCY);
+        let duration = self
+            .duration
+            .min(Duration::from_secs(MAX_DURATION_SECS as u64));
+        let next = AtomicU64::new(0);
+        let started = Instant::now();
+        let deadline = started + duration;
+
+        let workers = (0..concurrency).map(|_| async {
+            let mut latencies = Vec::new();
+            let mut failed = 0u64;
+            while Instant::now() < deadline {
+                let index = next.fetch_add(1, Ordering::Relaxed) as usize;
+                let url = urls[index % urls.len()].clone();
+                match probe(url).await {
+                    Ok(latency) => latencies.push(latency),
+                    Err(e) => {
+                        tracing::debug!("benchmark request via `{proxy}` failed: {e:#}");
+                        failed += 1;
+                    }
+                }
+            }
+            (latencies, failed)
+        });
+        let results = join_all(workers).await;
+        let elapsed = started.elapsed().as_secs_f64().max(f64::EPSILON);
+
+        let mut latencies = Vec::new();
+        let mut failed = 0;
+        for (worker_latencies, worker_failed) in results {
+            latencies.extend(worker_latencies);
+            failed += worker_failed;
+        }
+        latencies.sort_unstable();
+        let successful = latencies.len() as u64;
+        BenchmarkResult {
+            proxy: proxy.to_string(),
+            requests_sent: successful + failed,
+            successful,
+            failed,
+            latency_p50: percentile(&latencies, 50.0),
+            latency_p95: percentile(&latencies, 95.0),
+            latency_p99: percentile(&latencies, 99.0),
+            throughput_rps: successful as f64 / elapsed,
+        }
+    }
+
+    pub async fn run(&self, proxy: &str) -> BenchmarkResult {
+        self.run_with(proxy, |url| async move {
+            let res = api::get_proxy_delay(proxy.to_string(), Some(url)).await?;
+            Ok(res.delay.min(u32::MAX as u64) as u32)
+        })
+        .await
+    }
+}
+
+pub async fn benchmark_proxy(
+    proxy_name: String,
+    test_urls: Vec<String>,
+    concurrency: u32,
+    duration_secs: u32,
+) -> BenchmarkResult {
+    let suite = ClashBenchmarkSuite {
+        test_urls,
+        concurrency,
+        duration: Duration::from_secs(duration_secs as u64),
+    };
+    suite.run(&proxy_name).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    #[test]
+    fn test_percentile() {
+        let sorted = (1..=100).collect::<Vec<u32>>();
+        assert_eq!(percentile(&sorted, 50.0), 50);
+        assert_eq!(percentile(&sorted, 95.0), 95);
+        assert_eq!(percentile(&sorted, 99.0), 99);
+        assert_eq!(percentile(&[7], 99.0), 7);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_benchmark() {
+        let suite = ClashBenchmarkSuite {
+            test_urls: vec!["https://a.example".into(), "https://b.example".into()],
+            concurrency: 3,
+            duration: Duration::from_millis(300),
+        };
+        let seen = Mutex::new(Vec::new());
+        let result = suite
+            .run_with("HK 01", |url| {
+                seen.lock().push(url.clone());
+                async move {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    match url.as_str() {
+                        "https://a.example" => Ok(42),
+                        _ => anyhow::bail!("timeout"),
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(result.proxy, "HK 01");
+        assert!(result.requests_sent > 3, "{result:?}");
+        assert_eq!(result.requests_sent, seen.lock().len() as u64);
+        assert_eq!(result.successful + result.failed, result.requests_sent);
+        // 两个地址轮流请求
+        assert!(result.successful.abs_diff(result.failed) <= 1, "{result:?}");
+        assert_eq!(result.latency_p50, 42);
+        assert_eq!(result.latency_p99, 42);
+        assert!(result.throughput_rps > 0.0);
+    }
+}
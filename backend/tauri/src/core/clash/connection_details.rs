@@ -0,0 +1,259 @@
+//! 单条连接的详情：完整的规则、经过的策略组，以及 fake-ip 对应的真实地址
+//! 连接关闭后很快从核心中消失，最近关闭的连接在本地保留一段时间供查询
+use super::{
+    api::{self, ConnectionItem},
+    traffic_policy::rule_of,
+};
+use crate::config::Config;
+use anyhow::{Result, bail};
+use serde::Serialize;
+use serde_yaml::Mapping;
+use specta::Type;
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+const RECENTLY_CLOSED_CAPACITY: usize = 200;
+const RECENTLY_CLOSED_TTL: Duration = Duration::from_secs(60);
+
+/// 核心的默认 fake-ip 网段
+const DEFAULT_FAKE_IP_RANGE: &str = "198.18.0.1/16";
+
+/// 最近关闭的连接，超出容量时丢弃最早关闭的
+#[derive(Debug, Default)]
+pub struct RecentlyClosed {
+    entries: VecDeque<(Instant, ConnectionItem)>,
+}
+
+impl RecentlyClosed {
+    pub fn insert(&mut self, conn: ConnectionItem, now: Instant) {
+        self.prune(now);
+        if self.entries.len() == RECENTLY_CLOSED_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((now, conn));
+    }
+
+    pub fn get(&self, id: &str, now: Instant) -> Option<&ConnectionItem> {
+        self.entries
+            .iter()
+            .rev()
+            .take_while(|(closed_at, _)| now.duration_since(*closed_at) < RECENTLY_CLOSED_TTL)
+            .find(|(_, conn)| conn.id == id)
+            .map(|(_, conn)| conn)
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self
+            .entries
+            .front()
+            .is_some_and(|(closed_at, _)| now.duration_since(*closed_at) >= RECENTLY_CLOSED_TTL)
+        {
+            self.entries.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct DnsResolution {
+    /// 目标地址为 fake-ip
+    pub fake_ip: bool,
+    /// 域名实际解析到的地址，仅在 fake-ip 时查询
+    pub resolved: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ConnectionDetails {
+    pub connection: ConnectionItem,
+    /// 连接已关闭，数据来自本地保留的记录
+    pub closed: bool,
+    /// 命中的规则及其匹配内容，如 `DomainSuffix(google.com)`
+    pub rule: String,
+    /// 从规则指向的策略开始依次经过的策略组，最后一项为出站节点
+    pub chain: Vec<String>,
+    pub dns: DnsResolution,
+}
+
+impl ConnectionDetails {
+    fn new(connection: ConnectionItem, closed: bool, dns: DnsResolution) -> Self {
+        Self {
+            rule: rule_of(&connection),
+            // 核心返回的链从出站节点开始
+            chain: connection.chains.iter().rev().cloned().collect(),
+            connection,
+            closed,
+            dns,
+        }
+    }
+}
+
+/// 开启 fake-ip 时返回使用的网段
+fn fake_ip_ranges(config: Option<&Mapping>) -> Option<Vec<String>> {
+    let dns = config?.get("dns")?.as_mapping()?;
+    if dns.get("enhanced-mode").and_then(|v| v.as_str()) != Some("fake-ip") {
+        return None;
+    }
+    let mut ranges = vec![
+        dns.get("fake-ip-range")
+            .and_then(|v| v.as_str())
+            .unwrap_or(DEFAULT_FAKE_IP_RANGE)
+            .to_string(),
+    ];
+    ranges.extend(
+        dns.get("fake-ip-range6")
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+    );
+    Some(ranges)
+}
+
+fn in_range(ip: &str, range: &str) -> bool {
+    let (Ok(ip), Some((network, prefix))) = (ip.parse::<IpAddr>(), range.split_once('/')) else {
+        return false;
+    };
+    let (Ok(network), Ok(prefix)) = (network.parse::<IpAddr>(), prefix.parse::<u32>()) else {
+        return false;
+    };
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// 通过核心的 DNS 查询 fake-ip 对应域名的真实地址
+async fn resolve_fake_ip(conn: &ConnectionItem) -> DnsResolution {
+    let ranges = fake_ip_ranges(Config::runtime().latest().config.as_ref()).unwrap_or_default();
+    let metadata = &conn.metadata;
+    let fake_ip = ranges
+        .iter()
+        .any(|range| in_range(&metadata.destination_ip, range));
+    if !fake_ip || metadata.host.is_empty() {
+        return DnsResolution {
+            fake_ip,
+            resolved: Vec::new(),
+        };
+    }
+    let query_type = if metadata.destination_ip.contains(':') {
+        "AAAA"
+    } else {
+        "A"
+    };
+    let resolved = match api::dns_query(&metadata.host, query_type).await {
+        Ok(res) => res
+            .answer
+            .into_iter()
+            .filter(|answer| answer.data.parse::<IpAddr>().is_ok())
+            .map(|answer| answer.data)
+            .collect(),
+        Err(e) => {
+            tracing::warn!("failed to resolve `{}`: {e:?}", metadata.host);
+            Vec::new()
+        }
+    };
+    DnsResolution { fake_ip, resolved }
+}
+
+/// cached 为 connections ws 中缓存的记录，连接仍活跃时使用核心返回的最新数据
+pub async fn connection_details(
+    id: &str,
+    cached: Option<(ConnectionItem, bool)>,
+) -> Result<ConnectionDetails> {
+    let (conn, closed) = match cached {
+        Some((conn, true)) => (conn, true),
+        cached => {
+            let fresh = match api::get_connections().await {
+                Ok(res) => res.connections.into_iter().find(|conn| conn.id == id),
+                Err(e) => {
+                    tracing::warn!("failed to fetch connections: {e:?}");
+                    None
+                }
+            };
+            match (fresh, cached) {
+                (Some(conn), _) => (conn, false),
+                // 缓存之后连接已关闭
+                (None, Some((conn, _))) => (conn, true),
+                (None, None) => bail!("connection `{id}` not found"),
+            }
+        }
+    };
+    let dns = resolve_fake_ip(&conn).await;
+    Ok(ConnectionDetails::new(conn, closed, dns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::test_utils::connection;
+
+    fn conn(id: &str) -> ConnectionItem {
+        connection(
+            id,
+            serde_json::json!({
+                "metadata": { "destinationIP": "198.18.0.42" },
+                "chains": ["HK 01", "Auto", "Proxy"],
+                "rule": "DomainSuffix",
+                "rulePayload": "example.com",
+            }),
+        )
+    }
+
+    #[test]
+    fn test_recently_closed_retention() {
+        let start = Instant::now();
+        let mut closed = RecentlyClosed::default();
+        closed.insert(conn("old"), start);
+        for i in 0..RECENTLY_CLOSED_CAPACITY {
+            closed.insert(conn(&i.to_string()), start + Duration::from_secs(30));
+        }
+        // 超出容量时丢弃最早关闭的连接
+        assert!(closed.get("old", start + Duration::from_secs(30)).is_none());
+        assert!(closed.get("0", start + Duration::from_secs(30)).is_some());
+        assert!(closed.get("0", start + Duration::from_secs(89)).is_some());
+        assert!(closed.get("0", start + Duration::from_secs(90)).is_none());
+
+        closed.insert(conn("new"), start + Duration::from_secs(90));
+        assert_eq!(closed.entries.len(), 1);
+        assert!(closed.get("new", start + Duration::from_secs(90)).is_some());
+    }
+
+    #[test]
+    fn test_fake_ip_range() {
+        let config: Mapping =
+            serde_yaml::from_str("dns:\n  enhanced-mode: fake-ip\n  fake-ip-range6: 2001:db8::/32")
+                .unwrap();
+        let ranges = fake_ip_ranges(Some(&config)).unwrap();
+        assert_eq!(ranges, [DEFAULT_FAKE_IP_RANGE, "2001:db8::/32"]);
+        assert!(in_range("198.18.0.42", &ranges[0]));
+        assert!(!in_range("198.19.0.1", &ranges[0]));
+        assert!(in_range("2001:db8::1", &ranges[1]));
+        assert!(!in_range("2001:db9::1", &ranges[1]));
+        assert!(!in_range("198.18.0.42", &ranges[1]));
+
+        let config: Mapping = serde_yaml::from_str("dns:\n  enhanced-mode: redir-host").unwrap();
+        assert!(fake_ip_ranges(Some(&config)).is_none());
+    }
+
+    #[test]
+    fn test_details_chain_and_rule() {
+        let details = ConnectionDetails::new(
+            conn("1"),
+            true,
+            DnsResolution {
+                fake_ip: true,
+                resolved: vec!["93.184.216.34".into()],
+            },
+        );
+        assert_eq!(details.rule, "DomainSuffix(example.com)");
+        assert_eq!(details.chain, ["Proxy", "Auto", "HK 01"]);
+        assert!(details.closed);
+    }
+}
@@ -0,0 +1,589 @@
+//! 连接日志：开启后将关闭的连接追加到 `{data_dir}/connection_history`，默认关闭
+//! 连接面板的更新路径只把记录放入队列，由后台线程写入；单个文件超出大小后轮转，只保留固定数量的文件
+//! 导出与清理按行流式处理，不会一次读入全部记录；导出只在打开文件时持有锁，不阻塞写入
+use super::{api::ConnectionItem, traffic_policy::rule_of};
+use crate::utils::dirs;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{
+    fs,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::sync::mpsc;
+
+const HISTORY_DIR: &str = "connection_history";
+const FILE_PREFIX: &str = "connections";
+/// 单个文件的大小上限
+const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+/// 包括正在写入的文件在内最多保留的文件数
+const MAX_FILES: usize = 5;
+/// 写入线程来不及处理时丢弃新的记录，不阻塞连接面板
+const QUEUE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ConnectionRecord {
+    /// RFC 3339 格式的建立时间
+    pub start: String,
+    pub end: DateTime<Utc>,
+    pub network: String,
+    pub host: String,
+    pub destination: String,
+    pub rule: String,
+    /// 从规则指向的策略到出站节点
+    pub chains: Vec<String>,
+    pub upload: u64,
+    pub download: u64,
+    pub process: String,
+}
+
+impl ConnectionRecord {
+    pub fn new(conn: &ConnectionItem, end: DateTime<Utc>) -> Self {
+        Self {
+            start: conn.start.clone(),
+            end,
+            network: conn.metadata.network.clone(),
+            host: conn.metadata.host.clone(),
+            destination: format!(
+                "{}:{}",
+                conn.metadata.destination_ip, conn.metadata.destination_port
+            ),
+            rule: rule_of(conn),
+            chains: conn.chains.iter().rev().cloned().collect(),
+            upload: conn.upload,
+            download: conn.download,
+            process: conn.metadata.process.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// 按连接关闭时间筛选，两端均包含
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct TimeRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    fn contains(&self, time: &DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| *time >= from) && self.to.is_none_or(|to| *time <= to)
+    }
+}
+
+/// 按大小轮转的日志文件，`connections.jsonl` 为当前文件，`connections.N.jsonl` 越大越旧
+#[derive(Debug)]
+pub struct RotatingLog {
+    dir: PathBuf,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingLog {
+    pub fn new(dir: PathBuf, max_size: u64, max_files: usize) -> Self {
+        Self {
+            dir,
+            max_size,
+            max_files: max_files.max(1),
+        }
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(format!("{FILE_PREFIX}.jsonl")),
+            n => self.dir.join(format!("{FILE_PREFIX}.{n}.jsonl")),
+        }
+    }
+
+    /// 从旧到新排列的现有文件
+    fn files(&self) -> Vec<PathBuf> {
+        (0..self.max_files)
+            .rev()
+            .map(|index| self.path(index))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    fn rotate(&self) -> Result<()> {
+        let oldest = self.path(self.max_files - 1);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for index in (0..self.max_files - 1).rev() {
+            let path = self.path(index);
+            if path.exists() {
+                fs::rename(&path, self.path(index + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn append(&self, records: &[ConnectionRecord]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let current = self.path(0);
+        if fs::metadata(&current).is_ok_and(|meta| meta.len() >= self.max_size) {
+            self.rotate()?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&current)
+            .with_context(|| format!("failed to open {}", current.display()))?;
+        let mut writer = BufWriter::new(file);
+        for record in records {
+            serde_json::to_writer(&mut writer, record)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// 打开现有的文件并记录当前长度，之后读取不再需要持有锁
+    /// 之后追加的内容不会被读到，轮转或清理替换文件后仍读取打开时的内容
+    pub fn snapshot(&self) -> Result<LogSnapshot> {
+        let files = self
+            .files()
+            .into_iter()
+            .map(|path| {
+                let file = fs::File::open(&path)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                let len = file.metadata()?.len();
+                Ok((file, len))
+            })
+            .collect::<Result<_>>()?;
+        Ok(LogSnapshot { files })
+    }
+
+    /// 将符合条件的记录写入 `writer`，返回写入的条数
+    pub fn export(
+        &self,
+        range: &TimeRange,
+        format: ExportFormat,
+        writer: &mut impl Write,
+    ) -> Result<u64> {
+        self.snapshot()?.export(range, format, writer)
+    }
+
+    /// 删除关闭时间早于 `before` 的记录，返回删除的条数
+    pub fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+        let mut removed = 0;
+        for path in self.files() {
+            let tmp = path.with_extension("jsonl.tmp");
+            let mut kept = 0;
+            {
+                let reader = BufReader::new(fs::File::open(&path)?);
+                let mut writer = BufWriter::new(fs::File::create(&tmp)?);
+                for line in reader.lines() {
+                    let line = line?;
+                    match serde_json::from_str::<ConnectionRecord>(&line) {
+                        Ok(record) if record.end >= before => {
+                            writer.write_all(line.as_bytes())?;
+                            writer.write_all(b"\n")?;
+                            kept += 1;
+                        }
+                        _ => removed += 1,
+                    }
+                }
+                writer.flush()?;
+            }
+            if kept == 0 {
+                fs::remove_file(&tmp)?;
+                fs::remove_file(&path)?;
+            } else {
+                fs::rename(&tmp, &path)?;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// 导出时读取的文件，从旧到新排列
+pub struct LogSnapshot {
+    files: Vec<(fs::File, u64)>,
+}
+
+impl LogSnapshot {
+    /// 从旧到新依次读取记录，损坏的行会被跳过
+    fn for_each(self, mut f: impl FnMut(ConnectionRecord) -> Result<()>) -> Result<()> {
+        for (file, len) in self.files {
+            let reader = BufReader::new(file.take(len));
+            for line in reader.lines() {
+                if let Ok(record) = serde_json::from_str::<ConnectionRecord>(&line?) {
+                    f(record)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 将符合条件的记录写入 `writer`，返回写入的条数
+    pub fn export(
+        self,
+        range: &TimeRange,
+        format: ExportFormat,
+        writer: &mut impl Write,
+    ) -> Result<u64> {
+        let mut count = 0;
+        match format {
+            ExportFormat::Csv => writer.write_all(CSV_HEADER.as_bytes())?,
+            ExportFormat::Json => writer.write_all(b"[")?,
+        }
+        self.for_each(|record| {
+            if !range.contains(&record.end) {
+                return Ok(());
+            }
+            match format {
+                ExportFormat::Csv => writer.write_all(csv_row(&record).as_bytes())?,
+                ExportFormat::Json => {
+                    if count > 0 {
+                        writer.write_all(b",")?;
+                    }
+                    writer.write_all(b"\n")?;
+                    serde_json::to_writer(&mut *writer, &record)?;
+                }
+            }
+            count += 1;
+            Ok(())
+        })?;
+        if format == ExportFormat::Json {
+            writer.write_all(b"\n]\n")?;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+}
+
+const CSV_HEADER: &str =
+    "start,end,network,host,destination,rule,chains,upload,download,process\r\n";
+
+/// 含有逗号、引号或换行的字段加上引号，引号本身写两次
+/// 以 `=+-@` 开头的字段在表格软件中会被当作公式，前面加上 `'`
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// 导出目标必须是绝对路径、扩展名与格式一致，且不能位于应用的配置与数据目录中
+fn check_dest(dest: &Path, format: ExportFormat, protected: &[PathBuf]) -> Result<PathBuf> {
+    if !dest.is_absolute() {
+        bail!("export path must be absolute: {}", dest.display());
+    }
+    if dest.extension().and_then(|ext| ext.to_str()) != Some(format.extension()) {
+        bail!("export path must end with `.{}`", format.extension());
+    }
+    let (Some(parent), Some(name)) = (dest.parent(), dest.file_name()) else {
+        bail!("invalid export path: {}", dest.display());
+    };
+    // 解析符号链接与 `..` 后再比较
+    let dest = parent
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", parent.display()))?
+        .join(name);
+    if fs::symlink_metadata(&dest).is_ok_and(|meta| !meta.is_file()) {
+        bail!("export path is not a regular file: {}", dest.display());
+    }
+    if protected
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| dest.starts_with(dir))
+    {
+        bail!("can not export into the app directory: {}", dest.display());
+    }
+    Ok(dest)
+}
+
+fn csv_row(record: &ConnectionRecord) -> String {
+    let fields = [
+        csv_field(&record.start),
+        record.end.to_rfc3339(),
+        csv_field(&record.network),
+        csv_field(&record.host),
+        csv_field(&record.destination),
+        csv_field(&record.rule),
+        csv_field(&record.chains.join(" -> ")),
+        record.upload.to_string(),
+        record.download.to_string(),
+        csv_field(&record.process),
+    ];
+    format!("{}\r\n", fields.join(","))
+}
+
+pub struct ConnectionLogger {
+    enabled: AtomicBool,
+    log: Arc<Mutex<Option<RotatingLog>>>,
+    sender: Mutex<Option<mpsc::Sender<Vec<ConnectionRecord>>>>,
+}
+
+impl ConnectionLogger {
+    pub fn global() -> &'static ConnectionLogger {
+        static LOGGER: Lazy<ConnectionLogger> = Lazy::new(|| ConnectionLogger {
+            enabled: AtomicBool::new(false),
+            log: Arc::new(Mutex::new(None)),
+            sender: Mutex::new(None),
+        });
+        &LOGGER
+    }
+
+    /// 启动时以及设置变化后调用
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Release);
+        if enabled && self.sender.lock().is_none() {
+            self.spawn_writer();
+        }
+    }
+
+    fn spawn_writer(&self) {
+        let (tx, mut rx) = mpsc::channel::<Vec<ConnectionRecord>>(QUEUE_CAPACITY);
+        let log = self.log.clone();
+        std::thread::Builder::new()
+            .name("connection-log".into())
+            .spawn(move || {
+                while let Some(records) = rx.blocking_recv() {
+                    let result = with_log(&log, |log| log.append(&records));
+                    if let Err(e) = result {
+                        log::error!(target: "app", "failed to write connection log: {e:?}");
+                    }
+                }
+            })
+            .map(|_| *self.sender.lock() = Some(tx))
+            .unwrap_or_else(
+                |e| log::error!(target: "app", "failed to spawn connection log writer: {e:?}"),
+            );
+    }
+
+    /// 在连接关闭时调用，未开启时直接返回
+    pub fn log_closed<'a>(&self, connections: impl IntoIterator<Item = &'a ConnectionItem>) {
+        if !self.enabled.load(Ordering::Acquire) {
+            return;
+        }
+        let end = Utc::now();
+        let records = connections
+            .into_iter()
+            .map(|conn| ConnectionRecord::new(conn, end))
+            .collect::<Vec<_>>();
+        if records.is_empty() {
+            return;
+        }
+        if let Some(sender) = self.sender.lock().as_ref()
+            && sender.try_send(records).is_err()
+        {
+            log::warn!(target: "app", "connection log queue is full, drop records");
+        }
+    }
+
+    pub fn export(&self, range: &TimeRange, format: ExportFormat, dest: &Path) -> Result<u64> {
+        let protected = [dirs::app_config_dir_path()?, dirs::app_data_dir_path()?];
+        let dest = check_dest(dest, format, &protected)?;
+        // 只在打开文件时持有锁，写入线程不会因导出而丢弃记录
+        let snapshot = with_log(&self.log, |log| log.snapshot())?;
+        let file = fs::File::create(&dest)
+            .with_context(|| format!("failed to create {}", dest.display()))?;
+        let mut writer = BufWriter::new(file);
+        snapshot.export(range, format, &mut writer)
+    }
+
+    pub fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+        with_log(&self.log, |log| log.purge(before))
+    }
+}
+
+/// 写入、导出与清理共用一把锁，避免轮转时文件被同时改写
+fn with_log<T>(
+    log: &Mutex<Option<RotatingLog>>,
+    f: impl FnOnce(&RotatingLog) -> Result<T>,
+) -> Result<T> {
+    let mut log = log.lock();
+    if log.is_none() {
+        *log = Some(RotatingLog::new(
+            dirs::app_data_dir()?.join(HISTORY_DIR),
+            MAX_FILE_SIZE,
+            MAX_FILES,
+        ));
+    }
+    f(log.as_ref().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(host: &str, end: &str) -> ConnectionRecord {
+        ConnectionRecord {
+            start: "2026-10-16T00:00:00Z".into(),
+            end: DateTime::parse_from_rfc3339(end).unwrap().to_utc(),
+            network: "tcp".into(),
+            host: host.into(),
+            destination: "1.1.1.1:443".into(),
+            rule: "DomainSuffix(example.com)".into(),
+            chains: vec!["Proxy".into(), "HK 01".into()],
+            upload: 1,
+            download: 2,
+            process: "curl".into(),
+        }
+    }
+
+    fn export(log: &RotatingLog, range: &TimeRange, format: ExportFormat) -> (u64, String) {
+        let mut out = Vec::new();
+        let count = log.export(range, format, &mut out).unwrap();
+        (count, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = RotatingLog::new(dir.path().to_path_buf(), 1, 3);
+        for i in 0..5 {
+            log.append(&[record(&format!("host{i}"), "2026-10-16T01:00:00Z")])
+                .unwrap();
+        }
+        // 每次写入前都超出大小，只保留最新的三个文件
+        assert_eq!(log.files().len(), 3);
+        assert!(!dir.path().join("connections.3.jsonl").exists());
+        let (count, csv) = export(&log, &TimeRange::default(), ExportFormat::Csv);
+        assert_eq!(count, 3);
+        let hosts = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(3).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(hosts, ["host2", "host3", "host4"]);
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        assert_eq!(csv_field("example.com"), "example.com");
+        assert_eq!(csv_field("a,b.com"), "\"a,b.com\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+
+        let row = csv_row(&record("evil,\"host\".com", "2026-10-16T01:00:00Z"));
+        assert!(
+            row.starts_with(
+                "2026-10-16T00:00:00Z,2026-10-16T01:00:00+00:00,tcp,\"evil,\"\"host\"\".com\",1.1.1.1:443,"
+            ),
+            "{row}"
+        );
+        assert!(row.ends_with(",Proxy -> HK 01,1,2,curl\r\n"), "{row}");
+    }
+
+    #[test]
+    fn test_csv_formula_injection() {
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-cmd"), "'-cmd");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("a=b"), "a=b");
+    }
+
+    #[test]
+    fn test_check_dest() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = dir.path().join("app");
+        fs::create_dir(&app_dir).unwrap();
+        let protected = [app_dir.clone()];
+        let dest = dir.path().join("out.csv");
+        assert_eq!(
+            check_dest(&dest, ExportFormat::Csv, &protected).unwrap(),
+            dir.path().canonicalize().unwrap().join("out.csv")
+        );
+        assert!(check_dest(&dest, ExportFormat::Json, &protected).is_err());
+        assert!(check_dest(Path::new("out.csv"), ExportFormat::Csv, &protected).is_err());
+        assert!(check_dest(&app_dir.join("out.csv"), ExportFormat::Csv, &protected).is_err());
+        // `..` 解析后仍在应用目录中
+        let sneaky = app_dir.join("sub/../out.json");
+        fs::create_dir(app_dir.join("sub")).unwrap();
+        assert!(check_dest(&sneaky, ExportFormat::Json, &protected).is_err());
+        assert!(
+            check_dest(
+                &dir.path().join("missing/out.csv"),
+                ExportFormat::Csv,
+                &protected
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_export_json_and_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = RotatingLog::new(dir.path().to_path_buf(), MAX_FILE_SIZE, MAX_FILES);
+        let (count, json) = export(&log, &TimeRange::default(), ExportFormat::Json);
+        assert_eq!(count, 0);
+        assert!(
+            serde_json::from_str::<Vec<ConnectionRecord>>(&json)
+                .unwrap()
+                .is_empty()
+        );
+
+        log.append(&[
+            record("a.com", "2026-10-16T01:00:00Z"),
+            record("b.com", "2026-10-16T02:00:00Z"),
+            record("c.com", "2026-10-16T03:00:00Z"),
+        ])
+        .unwrap();
+        let range = TimeRange {
+            from: Some("2026-10-16T02:00:00Z".parse().unwrap()),
+            to: None,
+        };
+        let (count, json) = export(&log, &range, ExportFormat::Json);
+        assert_eq!(count, 2);
+        let records = serde_json::from_str::<Vec<ConnectionRecord>>(&json).unwrap();
+        assert_eq!(records[0].host, "b.com");
+        assert_eq!(records[1].host, "c.com");
+    }
+
+    #[test]
+    fn test_purge_boundary() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = RotatingLog::new(dir.path().to_path_buf(), MAX_FILE_SIZE, MAX_FILES);
+        log.append(&[
+            record("old.com", "2026-10-16T01:59:59Z"),
+            record("edge.com", "2026-10-16T02:00:00Z"),
+            record("new.com", "2026-10-16T03:00:00Z"),
+        ])
+        .unwrap();
+        // 正好等于 `before` 的记录保留
+        let removed = log.purge("2026-10-16T02:00:00Z".parse().unwrap()).unwrap();
+        assert_eq!(removed, 1);
+        let (count, csv) = export(&log, &TimeRange::default(), ExportFormat::Csv);
+        assert_eq!(count, 2);
+        assert!(!csv.contains("old.com") && csv.contains("edge.com"));
+
+        assert_eq!(
+            log.purge("2026-10-17T00:00:00Z".parse().unwrap()).unwrap(),
+            2
+        );
+        assert!(log.files().is_empty());
+    }
+}
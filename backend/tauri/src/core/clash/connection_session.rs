@@ -0,0 +1,168 @@
+//! 保存上一次运行时的活跃连接，重启后连接面板可以先展示这些连接
+//! 连接断开或退出时写入 `{data_dir}/last_connections.bincode`，启动时读取一次
+use super::api::ConnectionItem;
+use crate::utils::dirs;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const SESSION_FILE: &str = "last_connections.bincode";
+
+/// 最多保存的连接数，按建立时间保留最新的
+pub const MAX_SESSION_CONNECTIONS: usize = 5000;
+
+pub type ConnectionSnapshot = ConnectionItem;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ConnectionSession {
+    pub connections: Vec<ConnectionSnapshot>,
+    pub saved_at: DateTime<Utc>,
+    pub total_upload: u64,
+    pub total_download: u64,
+}
+
+impl ConnectionSession {
+    pub fn new(
+        mut connections: Vec<ConnectionSnapshot>,
+        total_upload: u64,
+        total_download: u64,
+    ) -> Self {
+        if connections.len() > MAX_SESSION_CONNECTIONS {
+            // RFC 3339 的时间可以直接按字符串比较
+            connections.sort_by(|a, b| b.start.cmp(&a.start));
+            connections.truncate(MAX_SESSION_CONNECTIONS);
+        }
+        Self {
+            connections,
+            saved_at: Utc::now(),
+            total_upload,
+            total_download,
+        }
+    }
+}
+
+pub fn session_path() -> Result<PathBuf> {
+    Ok(dirs::app_data_dir()?.join(SESSION_FILE))
+}
+
+/// 先写入临时文件再替换，避免退出时写到一半留下损坏的文件
+pub fn save_connection_session(path: &Path, session: &ConnectionSession) -> Result<()> {
+    let bytes = bincode::serde::encode_to_vec(session, bincode::config::standard())?;
+    let tmp = path.with_extension("bincode.tmp");
+    fs::write(&tmp, bytes).with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// 文件不存在时返回 `None`，文件损坏时返回错误
+pub fn restore_connection_session(path: &Path) -> Result<Option<ConnectionSession>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let (session, _): (ConnectionSession, _) =
+        bincode::serde::decode_from_slice(&bytes, bincode::config::standard())
+            .with_context(|| format!("failed to decode {}", path.display()))?;
+    Ok(Some(session))
+}
+
+static LAST_SESSION: OnceCell<Option<ConnectionSession>> = OnceCell::new();
+
+/// 启动时读取上一次的连接，需要在连接面板开始推送之前调用
+pub fn load_last_session() {
+    LAST_SESSION.get_or_init(|| {
+        match session_path().and_then(|path| restore_connection_session(&path)) {
+            Ok(session) => session,
+            Err(e) => {
+                log::warn!(target: "app", "failed to restore last connection session: {e:?}");
+                None
+            }
+        }
+    });
+}
+
+pub fn last_session() -> Option<ConnectionSession> {
+    LAST_SESSION.get().cloned().flatten()
+}
+
+/// 断开重连与退出可能同时保存，共用同一个临时文件，需要串行写入
+static SAVE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// 保存当前的活跃连接，没有活跃连接时不覆盖上一次的记录
+/// 例如重连失败时会再次断开，此时活跃连接已经清空
+pub async fn persist_session(
+    connections: Vec<ConnectionSnapshot>,
+    total_upload: u64,
+    total_download: u64,
+) -> Result<()> {
+    if connections.is_empty() {
+        return Ok(());
+    }
+    tokio::task::spawn_blocking(move || {
+        let session = ConnectionSession::new(connections, total_upload, total_download);
+        let _guard = SAVE_LOCK.lock();
+        save_connection_session(&session_path()?, &session)
+    })
+    .await?
+    .context("failed to save connection session")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::test_utils::connection;
+
+    fn conn(id: usize) -> ConnectionSnapshot {
+        let start = DateTime::parse_from_rfc3339("2026-10-16T00:00:00Z").unwrap();
+        connection(
+            &id.to_string(),
+            serde_json::json!({
+                "upload": id,
+                "download": id * 2,
+                "start": (start + chrono::Duration::seconds(id as i64))
+                    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_save_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_FILE);
+        assert!(restore_connection_session(&path).unwrap().is_none());
+
+        let session = ConnectionSession::new((0..3).map(conn).collect(), 100, 200);
+        save_connection_session(&path, &session).unwrap();
+        let restored = restore_connection_session(&path).unwrap().unwrap();
+        assert_eq!(restored.connections.len(), 3);
+        assert_eq!(restored.connections[2].download, 4);
+        assert_eq!(restored.connections[0].metadata.host, "example.com");
+        assert_eq!(restored.saved_at, session.saved_at);
+        assert_eq!((restored.total_upload, restored.total_download), (100, 200));
+
+        fs::write(&path, b"broken").unwrap();
+        assert!(restore_connection_session(&path).is_err());
+    }
+
+    #[test]
+    fn test_session_cap() {
+        let session =
+            ConnectionSession::new((0..MAX_SESSION_CONNECTIONS + 10).map(conn).collect(), 0, 0);
+        assert_eq!(session.connections.len(), MAX_SESSION_CONNECTIONS);
+        // 保留最新建立的连接
+        assert!(
+            session
+                .connections
+                .iter()
+                .all(|c| c.id.parse::<usize>().unwrap() >= 10)
+        );
+    }
+}
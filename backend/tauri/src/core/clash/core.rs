@@ -1,7 +1,7 @@
 use super::api;
 use crate::{
     config::{Config, ConfigType, nyanpasu::ClashCore},
-    core::{handle::Handle, logger::Logger},
+    core::{activation, handle::Handle, logger::Logger},
     log_err,
     utils::dirs,
 };
@@ -18,7 +18,7 @@ use nyanpasu_utils::{
         CommandEvent,
         instance::{CoreInstance, CoreInstanceBuilder},
     },
-    runtime::{block_on, spawn},
+    runtime::spawn,
 };
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
@@ -36,23 +36,37 @@ use std::{
 use tokio::time::sleep;
 use tracing_attributes::instrument;
 
+/// 服务 IPC 客户端名称，跟随用户设置的 socket 路径
+fn service_ipc_name() -> String {
+    crate::core::service::control::ipc_client_name()
+}
+
+/// 服务模式下是否先用精简配置启动核心
+fn use_minimal_startup_config() -> bool {
+    Config::verge()
+        .latest()
+        .use_minimal_startup_config
+        .unwrap_or(false)
+}
+
 async fn wait_for_clash_api_ready(max_attempts: usize, delay: Duration) -> Result<()> {
-    let client_info = { Config::clash().latest().get_client_info() };
-    let url = format!("http://{}/version", client_info.server);
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let crate::config::ClashController { endpoint, secret } =
+        crate::config::ClashController::current();
+    let url = crate::config::clash_controller_url()?.join("/version")?;
+    let client = api::clash_http_client(&endpoint)?;
 
     for attempt in 0..max_attempts {
-        let mut request = client.get(&url);
-        if let Some(secret) = &client_info.secret {
+        let mut request = client.get(url.clone());
+        if let Some(secret) = &secret {
             request = request.bearer_auth(secret);
         }
 
         match request.send().await {
             Ok(response) if response.status().is_success() => {
                 tracing::info!(
-                    "clash api became ready after {} checks at {}",
+                    "clash api became ready after {} checks at {:?}",
                     attempt + 1,
-                    client_info.server
+                    endpoint
                 );
                 return Ok(());
             }
@@ -65,9 +79,9 @@ async fn wait_for_clash_api_ready(max_attempts: usize, delay: Duration) -> Resul
             }
             Err(error) => {
                 tracing::debug!(
-                    "clash api readiness check {} failed for {}: {}",
+                    "clash api readiness check {} failed for {:?}: {}",
                     attempt + 1,
-                    client_info.server,
+                    endpoint,
                     error
                 );
             }
@@ -76,10 +90,7 @@ async fn wait_for_clash_api_ready(max_attempts: usize, delay: Duration) -> Resul
         tokio::time::sleep(delay).await;
     }
 
-    anyhow::bail!(
-        "clash api did not become ready in time at {}",
-        client_info.server
-    )
+    anyhow::bail!("clash api did not become ready in time at {:?}", endpoint)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Type)]
@@ -127,25 +138,29 @@ enum Instance {
 }
 
 impl Instance {
-    pub fn try_new(run_type: RunType) -> Result<Self> {
-        let core_type: nyanpasu_utils::core::CoreType = {
-            (Config::verge()
-                .latest()
-                .clash_core
-                .as_ref()
-                .unwrap_or(&ClashCore::ClashPremium))
-            .into()
-        };
+    pub async fn try_new(run_type: RunType) -> Result<Self> {
+        let core_type: nyanpasu_utils::core::CoreType = (&Config::clash_core()).into();
         let data_dir = camino::Utf8PathBuf::from_path_buf(dirs::app_data_dir()?)
             .map_err(|e| anyhow::anyhow!("failed to convert data dir to utf8 path: {:?}", e))?;
         let binary = camino::Utf8PathBuf::from_path_buf(find_binary_path(&core_type)?)
             .map_err(|e| anyhow::anyhow!("failed to convert binary path to utf8 path: {:?}", e))?;
-        let config_path = camino::Utf8PathBuf::from_path_buf(Config::generate_file(
-            ConfigType::Run,
-        )?)
-        .map_err(|e| anyhow::anyhow!("failed to convert config path to utf8 path: {:?}", e))?;
+        let config_path =
+            camino::Utf8PathBuf::from_path_buf(Config::generate_file_async(ConfigType::Run).await?)
+                .map_err(|e| {
+                    anyhow::anyhow!("failed to convert config path to utf8 path: {:?}", e)
+                })?;
         let pid_path = camino::Utf8PathBuf::from_path_buf(dirs::clash_pid_path()?)
             .map_err(|e| anyhow::anyhow!("failed to convert pid path to utf8 path: {:?}", e))?;
+        // 附加参数在生成配置时已写入对应的字段，这里拒绝启动未通过校验的参数
+        let extra_args = Config::verge()
+            .latest()
+            .clash_core_extra_args
+            .clone()
+            .unwrap_or_default();
+        if !extra_args.is_empty() {
+            let extra_args = super::core_args::validate_core_args(&extra_args)?;
+            tracing::info!("core extra args: {extra_args:?}");
+        }
         match run_type {
             RunType::Normal => {
                 let instance = Arc::new(
@@ -163,10 +178,17 @@ impl Instance {
                     stated_changed_at: Arc::new(AtomicI64::new(get_current_ts())),
                 })
             }
-            RunType::Service => Ok(Instance::Service {
-                config_path: config_path.into(),
-                core_type,
-            }),
+            RunType::Service => {
+                let config_path = if use_minimal_startup_config() {
+                    Config::generate_file_async(ConfigType::MinimalStartup).await?
+                } else {
+                    config_path.into()
+                };
+                Ok(Instance::Service {
+                    config_path,
+                    core_type,
+                })
+            }
             RunType::Elevated => {
                 // TODO: Implement elevated mode when needed
                 // For now, fallback to normal mode for safety
@@ -266,29 +288,19 @@ impl Instance {
                                             );
                                             stated_changed_at
                                                 .store(get_current_ts(), Ordering::Relaxed);
-                                            if status.code != Some(0)
-                                                || !matches!(status.signal, Some(9) | Some(15))
+                                            let err = anyhow::anyhow!(format!(
+                                                "core terminated with status: {:?}\n{}",
+                                                status,
+                                                err_buf.join("\n")
+                                            ));
+                                            tracing::error!("{}\n{}", err, err_buf.join("\n"));
+                                            // 启动完成后 rx 已被丢弃，发送失败说明核心运行中退出
+                                            if tx.send(Err(err)).await.is_err()
+                                                && !kill_flag.load(Ordering::Acquire)
                                             {
-                                                let err = anyhow::anyhow!(format!(
-                                                    "core terminated with status: {:?}\n{}",
-                                                    status,
-                                                    err_buf.join("\n")
+                                                super::watchdog::on_unexpected_exit(format!(
+                                                    "{status:?}"
                                                 ));
-                                                tracing::error!("{}\n{}", err, err_buf.join("\n"));
-                                                if tx.send(Err(err)).await.is_err()
-                                                    && !kill_flag.load(Ordering::Acquire)
-                                                {
-                                                    std::thread::spawn(move || {
-                                                        block_on(async {
-                                                            tracing::info!(
-                                                                "Trying to recover core."
-                                                            );
-                                                            let _ = CoreManager::global()
-                                                                .recover_core()
-                                                                .await;
-                                                        });
-                                                    });
-                                                }
                                             }
                                             break;
                                         }
@@ -320,9 +332,10 @@ impl Instance {
                     config_file: Cow::Borrowed(config_path),
                     core_type: Cow::Borrowed(core_type),
                 };
-                let start_result = nyanpasu_ipc::client::shortcuts::Client::service_default()
-                    .start_core(&payload)
-                    .await;
+                let start_result =
+                    nyanpasu_ipc::client::shortcuts::Client::new(&service_ipc_name())
+                        .start_core(&payload)
+                        .await;
 
                 match start_result {
                     Ok(()) => {}
@@ -344,7 +357,7 @@ impl Instance {
                 // is still unavailable, causing dashboard/proxies pages to show
                 // empty data until another recovery path kicks in.
                 for attempt in 0..20 {
-                    let status = nyanpasu_ipc::client::shortcuts::Client::service_default()
+                    let status = nyanpasu_ipc::client::shortcuts::Client::new(&service_ipc_name())
                         .status()
                         .await;
 
@@ -387,11 +400,11 @@ impl Instance {
                 stated_changed_at.store(get_current_ts(), Ordering::Relaxed);
                 Ok(())
             }
-            Instance::Service { .. } => {
-                Ok(nyanpasu_ipc::client::shortcuts::Client::service_default()
-                    .stop_core()
-                    .await?)
-            }
+            Instance::Service { .. } => Ok(nyanpasu_ipc::client::shortcuts::Client::new(
+                &service_ipc_name(),
+            )
+            .stop_core()
+            .await?),
         }
     }
 
@@ -418,7 +431,7 @@ impl Instance {
                 })
             }
             Instance::Service { .. } => {
-                let status = nyanpasu_ipc::client::shortcuts::Client::service_default()
+                let status = nyanpasu_ipc::client::shortcuts::Client::new(&service_ipc_name())
                     .status()
                     .await
                     .map(|info| match info.core_infos.state {
@@ -455,7 +468,7 @@ impl Instance {
                 )
             }
             Instance::Service { .. } => {
-                let status = nyanpasu_ipc::client::shortcuts::Client::service_default()
+                let status = nyanpasu_ipc::client::shortcuts::Client::new(&service_ipc_name())
                     .status()
                     .await;
                 match status {
@@ -478,6 +491,8 @@ impl Instance {
 #[derive(Debug)]
 pub struct CoreManager {
     instance: Mutex<Option<Arc<Instance>>>,
+    /// 最近一次启动时使用的核心
+    running_core: Mutex<Option<ClashCore>>,
     #[cfg(target_os = "macos")]
     previous_dns: tokio::sync::Mutex<Option<Vec<std::net::IpAddr>>>,
 }
@@ -487,6 +502,7 @@ impl CoreManager {
         static CORE_MANAGER: OnceCell<CoreManager> = OnceCell::new();
         CORE_MANAGER.get_or_init(|| CoreManager {
             instance: Mutex::new(None),
+            running_core: Mutex::new(None),
             #[cfg(target_os = "macos")]
             previous_dns: tokio::sync::Mutex::new(None),
         })
@@ -510,7 +526,26 @@ impl CoreManager {
     }
 
     pub fn init(&self) -> Result<()> {
+        if crate::consts::is_safe_mode() {
+            log::info!(target: "app", "safe mode, skip launching the core");
+            return Ok(());
+        }
         tauri::async_runtime::spawn(async {
+            // 先清理上次崩溃遗留的核心，避免端口与 TUN 网卡被占用
+            let _ = tokio::task::spawn_blocking(super::orphan::handle_orphaned_cores).await;
+            // 遗留核心清理后再检查端口占用，服务启动的核心可能仍在运行并占用端口，此时不替换
+            let (skip, port) = {
+                let verge = Config::verge().latest();
+                (
+                    verge.enable_random_port.unwrap_or(false)
+                        || verge.enable_service_mode.unwrap_or(false),
+                    verge.verge_mixed_port,
+                )
+            };
+            if !skip {
+                let port = port.unwrap_or(Config::clash().latest().get_mixed_port());
+                crate::utils::net::ClashMixedPortRangeScanner::default().substitute(port);
+            }
             // 启动clash
             log_err!(Self::global().run_core().await);
         });
@@ -521,13 +556,11 @@ impl CoreManager {
     /// 检查配置是否正确
     pub async fn check_config(&self) -> Result<()> {
         use nyanpasu_utils::core::instance::CoreInstance;
-        let config_path = Config::generate_file(ConfigType::Check)?;
+        let config_path = Config::generate_file_async(ConfigType::Check).await?;
         let config_path = Utf8PathBuf::from_path_buf(config_path)
             .map_err(|_| anyhow::anyhow!("failed to convert config path to utf8 path"))?;
 
-        let clash_core = { Config::verge().latest().clash_core };
-        let clash_core = clash_core.unwrap_or(ClashCore::ClashPremium);
-        let clash_core: nyanpasu_utils::core::CoreType = (&clash_core).into();
+        let clash_core: nyanpasu_utils::core::CoreType = (&Config::clash_core()).into();
 
         let app_dir = dirs::app_data_dir()?;
         let app_dir = Utf8PathBuf::from_path_buf(app_dir)
@@ -544,8 +577,14 @@ impl CoreManager {
         Ok(())
     }
 
-    /// 启动核心
+    /// 启动核心，同时清除看门狗的主动停止标记与放弃重启的状态
     pub async fn run_core(&self) -> Result<()> {
+        super::watchdog::on_manual_start();
+        self.start_core().await
+    }
+
+    /// 启动核心，不改变看门狗的状态，看门狗重启核心时使用
+    async fn start_core(&self) -> Result<()> {
         let run_type = RunType::default();
 
         {
@@ -579,6 +618,7 @@ impl CoreManager {
 
         // Regenerate runtime config with the reloaded settings
         Config::generate().await?;
+        super::provider_prefetch::prefetch_missing_providers(false).await;
 
         // 检查端口是否可用
         if !matches!(run_type, RunType::Service) {
@@ -590,7 +630,8 @@ impl CoreManager {
                 "service mode active; keep the existing external-controller managed by the service"
             );
         }
-        let instance = Arc::new(Instance::try_new(run_type)?);
+        let core = Config::clash_core();
+        let instance = Arc::new(Instance::try_new(run_type).await?);
 
         #[cfg(target_os = "macos")]
         {
@@ -605,32 +646,40 @@ impl CoreManager {
             let mut this = self.instance.lock();
             *this = Some(instance.clone());
         }
+        *self.running_core.lock() = Some(core);
         instance.start().await?;
+        if matches!(instance.run_type(), RunType::Normal) {
+            log_err!(
+                super::orphan::save_core_record(),
+                "failed to record core process"
+            );
+        }
         wait_for_clash_api_ready(20, Duration::from_millis(250)).await?;
+        if matches!(run_type, RunType::Service) && use_minimal_startup_config() {
+            // 核心已用精简配置启动，加载完整配置
+            let path = Config::generate_file_async(ConfigType::Run).await?;
+            api::put_configs(dirs::path_to_str(&path)?).await?;
+        }
         Handle::refresh_clash();
+        // 部分核心不会保存代理组选择，重启后手动恢复
+        spawn(async {
+            log_err!(
+                super::proxies::restore_group_selections().await,
+                "failed to restore proxy group selections"
+            );
+        });
+        // 核心更新或切换后刷新功能探测结果
+        spawn(async move {
+            log_err!(
+                super::core_info::CoreInfo::detect(&core).await,
+                "failed to detect core info"
+            );
+        });
         Ok(())
     }
 
-    /// 重启内核
-    ///
-    /// 注意：此函数包含重试逻辑，但有最大重试次数限制以防止无限递归
-    pub async fn recover_core(&'static self) -> Result<()> {
-        self.recover_core_with_retry(0).await
-    }
-
-    /// 带重试计数的内核恢复实现
-    async fn recover_core_with_retry(&'static self, retry_count: u32) -> Result<()> {
-        const MAX_RETRIES: u32 = 10; // 最多重试 10 次
-
-        if retry_count >= MAX_RETRIES {
-            let err = anyhow::anyhow!(
-                "Failed to recover clash core after {} attempts, giving up to prevent resource exhaustion",
-                MAX_RETRIES
-            );
-            log::error!(target: "app", "{}", err);
-            return Err(err);
-        }
-
+    /// 重启内核，重试与退避由看门狗负责
+    pub async fn recover_core(&self) -> Result<()> {
         // 清除原来的实例
         {
             let instance = {
@@ -645,37 +694,13 @@ impl CoreManager {
             }
         }
 
-        if let Err(err) = self.run_core().await {
-            log::error!(
-                target: "app",
-                "Failed to recover clash core (attempt {}/{}): {:?}",
-                retry_count + 1,
-                MAX_RETRIES,
-                err
-            );
-
-            // 使用指数退避策略：5s, 10s, 20s, ...，最多 60s
-            let delay_secs = std::cmp::min(5 * 2u64.pow(retry_count), 60);
-            tokio::time::sleep(Duration::from_secs(delay_secs)).await;
-
-            // 在后台线程中重试，因为 recover_core_with_retry 可能不是 Send
-            let retry_count = retry_count + 1;
-            std::thread::spawn(move || {
-                nyanpasu_utils::runtime::block_on(async {
-                    let _ = CoreManager::global()
-                        .recover_core_with_retry(retry_count)
-                        .await;
-                })
-            });
-        } else {
-            log::info!(target: "app", "Core recovered successfully after {} attempts", retry_count);
-        }
-
-        Ok(())
+        self.start_core().await
     }
 
     /// 停止核心运行
     pub async fn stop_core(&self) -> Result<()> {
+        // 主动停止，核心退出后看门狗不会重启
+        super::watchdog::mark_intentional_stop();
         #[cfg(target_os = "macos")]
         let _ = self
             .change_default_network_dns(false)
@@ -688,17 +713,29 @@ impl CoreManager {
         if let Some(instance) = instance.as_ref() {
             instance.stop().await?;
         }
+        log_err!(super::orphan::clear_core_record());
+        *self.running_core.lock() = None;
         Ok(())
     }
 
-    /// 切换核心
-    #[instrument(skip(self))]
-    pub async fn change_core(&self, clash_core: Option<ClashCore>) -> Result<()> {
-        let clash_core = clash_core.ok_or(anyhow::anyhow!("clash core is null"))?;
+    /// 当前核心实例的运行方式，尚未启动过核心时返回 None
+    pub(super) fn run_type(&self) -> Option<RunType> {
+        self.instance
+            .lock()
+            .as_ref()
+            .map(|instance| instance.run_type())
+    }
 
-        log::debug!(target: "app", "change core to `{clash_core}`");
+    /// 正在运行的核心，可能与设置中的核心不同
+    pub fn running_core(&self) -> Option<ClashCore> {
+        *self.running_core.lock()
+    }
 
-        Config::verge().draft().clash_core = Some(clash_core);
+    /// 用 verge draft 中的核心重新启动，由 `feat::patch_verge` 在持有修改锁时调用
+    /// verge 的修改由调用方应用或丢弃
+    #[instrument(skip(self))]
+    pub async fn change_core(&self) -> Result<()> {
+        log::debug!(target: "app", "change core to `{}`", Config::clash_core());
 
         // 更新配置
         Config::generate().await?;
@@ -711,16 +748,12 @@ impl CoreManager {
         match self.run_core().await {
             Ok(_) => {
                 tracing::info!("change core success");
-                Config::verge().apply();
                 Config::runtime().apply();
-                log_err!(Config::verge().latest().save_file());
                 Ok(())
             }
             Err(err) => {
                 tracing::error!("failed to change core: {err:?}");
-                Config::verge().discard();
                 Config::runtime().discard();
-                self.run_core().await?;
                 Err(err)
             }
         }
@@ -733,30 +766,33 @@ impl CoreManager {
 
         // 更新配置
         Config::generate().await?;
+        super::provider_prefetch::prefetch_missing_providers(true).await;
 
         // 检查配置是否正常
-        self.check_config().await?;
+        activation::stage("validation", self.check_config()).await?;
 
         // 更新运行时配置
-        let path = Config::generate_file(ConfigType::Run)?;
+        let path = activation::stage("write", Config::generate_file_async(ConfigType::Run)).await?;
         let path = dirs::path_to_str(&path)?;
 
         // 发送请求 发送5次
-        for i in 0..5 {
-            match api::put_configs(path).await {
-                Ok(_) => break,
-                Err(err) => {
-                    if i < 4 {
-                        log::info!(target: "app", "{err:?}");
-                    } else {
-                        bail!(err);
+        activation::stage("core reload", async {
+            for i in 0..5 {
+                match api::put_configs(path).await {
+                    Ok(_) => break,
+                    Err(err) => {
+                        if i < 4 {
+                            log::info!(target: "app", "{err:?}");
+                        } else {
+                            bail!(err);
+                        }
                     }
                 }
+                sleep(Duration::from_millis(250)).await;
             }
-            sleep(Duration::from_millis(250)).await;
-        }
-
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
     #[cfg(target_os = "macos")]
@@ -797,7 +833,7 @@ impl CoreManager {
             log::debug!(target: "app", "set new dns: {:?}", new_dns);
             let result = match run_type {
                 RunType::Service => {
-                    nyanpasu_ipc::client::shortcuts::Client::service_default()
+                    nyanpasu_ipc::client::shortcuts::Client::new(&service_ipc_name())
                         .set_dns(&NetworkSetDnsReq {
                             // FIXME: improve this type notation
                             dns_servers: new_dns
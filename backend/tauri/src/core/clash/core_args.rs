@@ -0,0 +1,307 @@
+//! 校验用户为核心附加的启动参数（`clash_core_extra_args`）
+//! 拒绝指向数据目录与配置目录之外的路径、非回环的监听地址，以及与托管字段冲突的参数
+//! 核心由 nyanpasu-utils 或服务启动，二者都不接受额外的命令行参数，
+//! 因此参数按核心的语义写入生成的配置中对应的字段，普通模式与服务模式效果一致
+use crate::{config::Config, utils::dirs};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type, thiserror::Error)]
+#[error("`{arg}`: {reason}")]
+pub struct SanitizationError {
+    pub arg: String,
+    pub reason: String,
+}
+
+/// 由应用管理的参数：工作目录、配置文件、密钥，以及会让核心直接退出的参数
+const MANAGED_FLAGS: &[&str] = &["d", "f", "config", "secret", "t", "v", "h"];
+
+/// 设置监听地址的参数
+const LISTEN_FLAGS: &[&str] = &["ext-ctl", "external-controller"];
+
+/// 支持的参数与对应的配置字段
+const VALUE_FLAGS: &[(&str, &str)] = &[
+    ("ext-ctl", "external-controller"),
+    ("external-controller", "external-controller"),
+    ("ext-ctl-unix", "external-controller-unix"),
+    ("ext-ctl-pipe", "external-controller-pipe"),
+    ("ext-ui", "external-ui"),
+];
+
+/// 值为路径的参数
+const PATH_FLAGS: &[&str] = &["ext-ctl-unix", "ext-ui"];
+
+/// 不带值的开关参数与对应的配置字段
+const SWITCH_FLAGS: &[(&str, &str)] = &[("m", "geodata-mode")];
+
+struct SanitizePolicy {
+    allowed_dirs: Vec<PathBuf>,
+    /// 核心的工作目录，相对路径以此为基准
+    base_dir: PathBuf,
+    allow_remote_api: bool,
+}
+
+impl SanitizePolicy {
+    fn current() -> Self {
+        let allowed_dirs = [dirs::app_data_dir(), dirs::app_config_dir()]
+            .into_iter()
+            .filter_map(|dir| dir.ok())
+            .collect::<Vec<_>>();
+        let allow_remote_api = Config::verge().latest().allow_remote_api.unwrap_or(false);
+        Self {
+            base_dir: dirs::app_data_dir().unwrap_or_default(),
+            allowed_dirs,
+            allow_remote_api,
+        }
+    }
+
+    /// 相对路径按核心的工作目录解析，再按字面消去 `..`，不解析符号链接
+    fn is_allowed_path(&self, path: &Path) -> bool {
+        let mut resolved = PathBuf::new();
+        for component in self.base_dir.join(path).components() {
+            match component {
+                Component::ParentDir => {
+                    if !resolved.pop() {
+                        return false;
+                    }
+                }
+                Component::CurDir => {}
+                component => resolved.push(component),
+            }
+        }
+        self.allowed_dirs
+            .iter()
+            .any(|dir| resolved.starts_with(dir))
+    }
+}
+
+/// 拆分为 `(flag, value, 原始参数)`
+/// 兼容 `-flag value`、`--flag value` 与 `-flag=value` 三种写法
+fn parse_args(args: &[String]) -> Vec<(Option<&str>, Option<&str>, &String)> {
+    let mut parsed = Vec::new();
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        let (flag, value) = match arg.strip_prefix("--").or_else(|| arg.strip_prefix('-')) {
+            Some(flag) => match flag.split_once('=') {
+                Some((flag, value)) => (Some(flag), Some(value)),
+                None if SWITCH_FLAGS.iter().any(|(name, _)| *name == flag) => (Some(flag), None),
+                None => (
+                    Some(flag),
+                    iter.next_if(|next| !next.starts_with('-'))
+                        .map(String::as_str),
+                ),
+            },
+            None => (None, Some(arg.as_str())),
+        };
+        parsed.push((flag, value, arg));
+    }
+    parsed
+}
+
+/// 校验通过时返回原样的参数，否则返回全部错误
+pub fn sanitize_core_args(args: &[String]) -> Result<Vec<String>, Vec<SanitizationError>> {
+    sanitize_with(args, &SanitizePolicy::current())
+}
+
+/// 合并全部错误，用于启动核心与修改设置时报错
+pub fn validate_core_args(args: &[String]) -> anyhow::Result<Vec<String>> {
+    sanitize_core_args(args).map_err(|errors| {
+        let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+        anyhow::anyhow!("invalid core args: {}", errors.join("; "))
+    })
+}
+
+fn sanitize_with(
+    args: &[String],
+    policy: &SanitizePolicy,
+) -> Result<Vec<String>, Vec<SanitizationError>> {
+    let mut errors = Vec::new();
+    for (flag, value, arg) in parse_args(args) {
+        let reject = |reason: &str| SanitizationError {
+            arg: arg.clone(),
+            reason: reason.to_string(),
+        };
+
+        if let Some(flag) = flag {
+            if MANAGED_FLAGS.contains(&flag) {
+                errors.push(reject("conflicts with a field managed by the app"));
+                continue;
+            }
+            let switch = SWITCH_FLAGS.iter().any(|(name, _)| *name == flag);
+            if !switch && !VALUE_FLAGS.iter().any(|(name, _)| *name == flag) {
+                errors.push(reject("unsupported core argument"));
+                continue;
+            }
+            if !switch && value.is_none() {
+                errors.push(reject("missing value"));
+                continue;
+            }
+            if LISTEN_FLAGS.contains(&flag)
+                && !policy.allow_remote_api
+                && value.is_some_and(|value| !is_loopback_listen(value))
+            {
+                errors.push(reject(
+                    "listening on a non-loopback address requires `allow_remote_api`",
+                ));
+                continue;
+            }
+        }
+        let Some(flag) = flag else {
+            errors.push(reject("expected a flag"));
+            continue;
+        };
+        if let Some(value) = value
+            && PATH_FLAGS.contains(&flag)
+            && !policy.is_allowed_path(Path::new(value))
+        {
+            errors.push(reject(
+                "path is outside of the app data and config directories",
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(args.to_vec())
+    } else {
+        Err(errors)
+    }
+}
+
+/// 将校验过的参数写入配置中对应的字段，命令行参数同样覆盖配置文件
+pub fn apply_core_args(config: &mut Mapping, args: &[String]) {
+    for (flag, value, _) in parse_args(args) {
+        let Some(flag) = flag else {
+            continue;
+        };
+        if let Some((_, key)) = SWITCH_FLAGS.iter().find(|(name, _)| *name == flag) {
+            config.insert((*key).into(), Value::Bool(true));
+        } else if let Some((_, key)) = VALUE_FLAGS.iter().find(|(name, _)| *name == flag)
+            && let Some(value) = value
+        {
+            config.insert((*key).into(), value.into());
+        }
+    }
+}
+
+/// 端口可省略，如 `:9090` 与 `0.0.0.0:9090` 分别监听全部地址
+fn is_loopback_listen(value: &str) -> bool {
+    let host = match value.parse::<SocketAddr>() {
+        Ok(addr) => return addr.ip().is_loopback(),
+        Err(_) => match value.rsplit_once(':') {
+            Some((host, _)) => host,
+            None => value,
+        },
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => host.eq_ignore_ascii_case("localhost"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow_remote_api: bool) -> SanitizePolicy {
+        SanitizePolicy {
+            allowed_dirs: vec![PathBuf::from("/home/user/.local/share/clash-nyanpasu")],
+            base_dir: PathBuf::from("/home/user/.local/share/clash-nyanpasu"),
+            allow_remote_api,
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn rejected(args: &[String], policy: &SanitizePolicy) -> Vec<String> {
+        sanitize_with(args, policy)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.arg)
+            .collect()
+    }
+
+    #[test]
+    fn test_allowed_args() {
+        let allowed = args(&[
+            "-ext-ctl",
+            "127.0.0.1:9090",
+            "--ext-ui=/home/user/.local/share/clash-nyanpasu/ui",
+            "-m",
+        ]);
+        assert_eq!(sanitize_with(&allowed, &policy(false)), Ok(allowed.clone()));
+        let remote = args(&["-ext-ctl", "0.0.0.0:9090"]);
+        assert!(sanitize_with(&remote, &policy(true)).is_ok());
+    }
+
+    #[test]
+    fn test_rejected_args() {
+        let policy = policy(false);
+        assert_eq!(
+            rejected(&args(&["--config", "/etc/shadow"]), &policy),
+            ["--config"]
+        );
+        assert_eq!(
+            rejected(
+                &args(&[
+                    "-ext-ctl",
+                    "0.0.0.0:9090",
+                    "--ext-ctl=[::]:9090",
+                    "-ext-ctl",
+                    ":9090"
+                ]),
+                &policy
+            ),
+            ["-ext-ctl", "--ext-ctl=[::]:9090", "-ext-ctl"]
+        );
+        assert_eq!(
+            rejected(
+                &args(&[
+                    "-ext-ui",
+                    "/home/user/.local/share/clash-nyanpasu/../../.ssh",
+                    "/etc/passwd",
+                ]),
+                &policy
+            ),
+            ["-ext-ui", "/etc/passwd"]
+        );
+        assert_eq!(
+            rejected(&args(&["-secret=abc", "-d"]), &policy),
+            ["-secret=abc", "-d"]
+        );
+        // 相对路径按工作目录解析
+        assert_eq!(
+            rejected(
+                &args(&["-ext-ui", "../x", "-ext-ui=ui/../../../y"]),
+                &policy
+            ),
+            ["-ext-ui", "-ext-ui=ui/../../../y"]
+        );
+        assert!(sanitize_with(&args(&["-ext-ui", "ui/./dashboard"]), &policy).is_ok());
+        assert_eq!(
+            rejected(&args(&["-unknown", "x", "-ext-ui"]), &policy),
+            ["-unknown", "-ext-ui"]
+        );
+    }
+
+    #[test]
+    fn test_apply_core_args() {
+        let mut config = Mapping::new();
+        apply_core_args(
+            &mut config,
+            &args(&["-m", "-ext-ui", "ui", "--ext-ctl=127.0.0.1:9091"]),
+        );
+        assert_eq!(config.get("geodata-mode"), Some(&Value::Bool(true)));
+        assert_eq!(config.get("external-ui"), Some(&Value::from("ui")));
+        assert_eq!(
+            config.get("external-controller"),
+            Some(&Value::from("127.0.0.1:9091"))
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! 探测核心的实际类型、版本与支持的功能
+//! 配置中的 `ClashCore` 只表示二进制名称，实际类型以核心自身的输出为准
+use super::api;
+use crate::config::nyanpasu::ClashCore;
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+const API_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreFlavor {
+    Meta,
+    Premium,
+    ClashRs,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreFeature {
+    Tun,
+    TunSystemStack,
+    TunGvisorStack,
+    TunMixedStack,
+    ScriptMode,
+    Providers,
+    /// GET /group/{name}/delay
+    GroupDelay,
+    /// GET /dns/query
+    DnsQuery,
+    /// POST /configs/geo
+    GeoUpdate,
+    /// POST /restart
+    Restart,
+    /// PUT /configs 热重载时保留已有连接
+    HotReload,
+}
+
+impl CoreFlavor {
+    pub fn features(self) -> Vec<CoreFeature> {
+        use CoreFeature::*;
+        match self {
+            CoreFlavor::Meta => vec![
+                Tun,
+                TunSystemStack,
+                TunGvisorStack,
+                TunMixedStack,
+                Providers,
+                GroupDelay,
+                DnsQuery,
+                GeoUpdate,
+                Restart,
+                HotReload,
+            ],
+            CoreFlavor::Premium => vec![
+                Tun,
+                TunSystemStack,
+                TunGvisorStack,
+                ScriptMode,
+                Providers,
+                HotReload,
+            ],
+            CoreFlavor::ClashRs => vec![Tun, Providers],
+            CoreFlavor::Unknown => vec![],
+        }
+    }
+
+    /// DNS 上游支持的协议，`udp` 表示不带协议的地址
+    pub fn dns_schemes(self) -> &'static [&'static str] {
+        match self {
+            CoreFlavor::Meta | CoreFlavor::Unknown => {
+                &["udp", "tcp", "tls", "https", "quic", "dhcp", "system"]
+            }
+            CoreFlavor::Premium => &["udp", "tcp", "tls", "https", "dhcp"],
+            CoreFlavor::ClashRs => &["udp", "tcp", "tls", "https"],
+        }
+    }
+
+    /// 优先使用运行中核心的探测结果，其次是二进制 `-v` 的输出，都没有时按配置的核心推断
+    pub fn of(core: &ClashCore) -> Self {
+        CoreInfo::cached(core)
+            .map(|info| info.flavor)
+            .or_else(|| CoreVersionInfo::cached_flavor(*core))
+            .unwrap_or(match core {
+                ClashCore::ClashPremium => CoreFlavor::Premium,
+                _ => CoreFlavor::Meta,
+            })
+    }
+
+    /// 核心未运行时执行二进制确认类型，结果按二进制缓存，生成配置前调用
+    pub async fn detect(core: &ClashCore) -> Self {
+        if CoreInfo::cached(core).is_none()
+            && let Err(e) = CoreVersionInfo::probe(*core).await
+        {
+            tracing::warn!("failed to probe {core} binary: {e:?}");
+        }
+        Self::of(core)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct CoreInfo {
+    /// 配置中的核心名称
+    pub name: String,
+    pub flavor: CoreFlavor,
+    pub version: String,
+    pub features: Vec<CoreFeature>,
+}
+
+static CACHE: Lazy<RwLock<Option<(ClashCore, CoreInfo)>>> = Lazy::new(|| RwLock::new(None));
+
+impl CoreInfo {
+    pub fn supports(&self, feature: CoreFeature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// 最近一次探测的结果，核心已切换时返回 None
+    pub fn cached(core: &ClashCore) -> Option<CoreInfo> {
+        CACHE
+            .read()
+            .as_ref()
+            .filter(|(cached, _)| cached == core)
+            .map(|(_, info)| info.clone())
+    }
+
+    /// 优先查询核心 API，核心未运行或无法区分类型时探测二进制，结果与版本信息共用缓存
+    pub async fn detect(core: &ClashCore) -> Result<CoreInfo> {
+        let from_api = match tokio::time::timeout(API_TIMEOUT, api::get_version()).await {
+            Ok(Ok(res)) => Some(res),
+            Ok(Err(e)) => {
+                tracing::debug!("failed to query core version from api: {e:?}");
+                None
+            }
+            Err(_) => None,
+        };
+        let (flavor, version) = match from_api {
+            Some(res) if res.meta => (CoreFlavor::Meta, res.version),
+            Some(res) if res.premium => (CoreFlavor::Premium, res.version),
+            _ => {
+                let probed = CoreVersionInfo::probe(*core).await?;
+                (probed.flavor(), probed.version)
+            }
+        };
+        let info = CoreInfo {
+            name: core.to_string(),
+            flavor,
+            version,
+            features: flavor.features(),
+        };
+        *CACHE.write() = Some((*core, info.clone()));
+        Ok(info)
+    }
+}
+
+/// 解析 `-v` 输出，例如：
+/// - `Mihomo Meta v1.18.1 linux amd64 with go1.21.5`
+/// - `Clash 2023.08.17 linux amd64 with go1.20.7`
+/// - `clash-rs 0.7.1`
+fn parse_version_output(output: &str) -> (CoreFlavor, Option<String>) {
+    let lower = output.to_ascii_lowercase();
+    let flavor = if lower.contains("mihomo") || lower.contains("meta") {
+        CoreFlavor::Meta
+    } else if lower.contains("clash-rs") || lower.contains("clash_rs") {
+        CoreFlavor::ClashRs
+    } else if lower.starts_with("clash") {
+        CoreFlavor::Premium
+    } else {
+        CoreFlavor::Unknown
+    };
+    let version = output
+        .split_whitespace()
+        .find(|token| {
+            let digits = token.strip_prefix('v').unwrap_or(token);
+            digits.starts_with(|c: char| c.is_ascii_digit())
+        })
+        .map(str::to_string);
+    (flavor, version)
+}
+
+/// 核心二进制文件的版本与构建信息
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct CoreVersionInfo {
+    pub core: ClashCore,
+    /// 无法解析时为原始输出的第一行
+    pub version: String,
+    pub build_time: Option<String>,
+    /// 构建所用的 Go 或 Rust 版本
+    pub go_or_rust_version: Option<String>,
+    /// 版本命令的原始输出
+    pub raw: String,
+    pub binary_path: PathBuf,
+    pub binary_sha256: String,
+}
+
+/// 二进制路径对应的修改时间、大小与探测结果
+type VersionCache = HashMap<PathBuf, (SystemTime, u64, CoreVersionInfo)>;
+
+/// 按二进制路径缓存，文件的修改时间或大小变化后重新探测
+static VERSION_CACHE: Lazy<Mutex<VersionCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl CoreVersionInfo {
+    /// 会执行核心二进制，不要在异步运行时中直接调用
+    pub fn probe_blocking(core: ClashCore) -> Result<Self> {
+        let core_type: nyanpasu_utils::core::CoreType = (&core).into();
+        let binary_path = super::core::find_binary_path(&core_type)?;
+        let metadata = std::fs::metadata(&binary_path)?;
+        let (mtime, size) = (metadata.modified()?, metadata.len());
+        if let Some((cached_mtime, cached_size, info)) = VERSION_CACHE.lock().get(&binary_path)
+            && *cached_mtime == mtime
+            && *cached_size == size
+            && info.core == core
+        {
+            return Ok(info.clone());
+        }
+
+        let raw = run_version_flags(&binary_path)?;
+        let parsed = parse_build_info(&raw);
+        let info = CoreVersionInfo {
+            core,
+            version: parsed
+                .version
+                .unwrap_or_else(|| raw.lines().next().unwrap_or_default().to_string()),
+            build_time: parsed.build_time,
+            go_or_rust_version: parsed.toolchain,
+            binary_sha256: hex::encode(Sha256::digest(std::fs::read(&binary_path)?)),
+            binary_path: binary_path.clone(),
+            raw,
+        };
+        VERSION_CACHE
+            .lock()
+            .insert(binary_path, (mtime, size, info.clone()));
+        Ok(info)
+    }
+
+    pub async fn probe(core: ClashCore) -> Result<Self> {
+        tokio::task::spawn_blocking(move || Self::probe_blocking(core)).await?
+    }
+
+    /// 按版本输出的第一行判断核心类型
+    fn flavor(&self) -> CoreFlavor {
+        parse_version_output(self.raw.lines().next().unwrap_or_default()).0
+    }
+
+    /// 最近一次探测该核心二进制得到的类型
+    fn cached_flavor(core: ClashCore) -> Option<CoreFlavor> {
+        flavor_in_cache(&VERSION_CACHE.lock(), core)
+    }
+}
+
+fn flavor_in_cache(cache: &VersionCache, core: ClashCore) -> Option<CoreFlavor> {
+    cache
+        .values()
+        .filter(|(.., info)| info.core == core)
+        .map(|(.., info)| info.flavor())
+        .find(|flavor| *flavor != CoreFlavor::Unknown)
+}
+
+/// 不同核心的版本参数不同，依次尝试直到有输出
+fn run_version_flags(binary: &Path) -> Result<String> {
+    for args in [["-v"], ["-V"], ["version"]] {
+        let mut command = std::process::Command::new(binary);
+        command.args(args);
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        }
+        let output = command.output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !stdout.is_empty() {
+            return Ok(stdout);
+        }
+    }
+    anyhow::bail!("`{}` did not report a version", binary.display())
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BuildInfo {
+    version: Option<String>,
+    build_time: Option<String>,
+    toolchain: Option<String>,
+}
+
+/// 解析版本命令的输出，例如：
+/// - `Mihomo Meta v1.18.1 linux amd64 with go1.21.5 Sun Dec 17 04:07:18 UTC 2023`
+/// - `Clash 2023.08.17 linux amd64 with go1.20.7 Thu Aug 17 04:28:49 UTC 2023`
+/// - `clash-rs 0.7.1` 以及可能附带的 `rustc 1.80.0` 等信息
+fn parse_build_info(output: &str) -> BuildInfo {
+    let first_line = output.lines().next().unwrap_or_default();
+    let (_, version) = parse_version_output(first_line);
+    let tokens = first_line.split_whitespace().collect::<Vec<_>>();
+    let go = tokens.iter().position(|token| {
+        token.starts_with("go") && token[2..].starts_with(|c: char| c.is_ascii_digit())
+    });
+    let (toolchain, build_time) = match go {
+        Some(index) => {
+            let rest = tokens[index + 1..].join(" ");
+            (
+                Some(tokens[index].to_string()),
+                (!rest.is_empty()).then_some(rest),
+            )
+        }
+        None => {
+            let rustc = output
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .windows(2)
+                .find(|pair| pair[0].eq_ignore_ascii_case("rustc"))
+                .map(|pair| format!("rustc {}", pair[1]));
+            (rustc, None)
+        }
+    };
+    BuildInfo {
+        version,
+        build_time,
+        toolchain,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version_output() {
+        assert_eq!(
+            parse_version_output("Mihomo Meta v1.18.1 linux amd64 with go1.21.5"),
+            (CoreFlavor::Meta, Some("v1.18.1".into()))
+        );
+        assert_eq!(
+            parse_version_output("Clash 2023.08.17 linux amd64 with go1.20.7"),
+            (CoreFlavor::Premium, Some("2023.08.17".into()))
+        );
+        assert_eq!(
+            parse_version_output("clash-rs 0.7.1"),
+            (CoreFlavor::ClashRs, Some("0.7.1".into()))
+        );
+        assert_eq!(parse_version_output("unknown"), (CoreFlavor::Unknown, None));
+    }
+
+    #[test]
+    fn test_parse_build_info() {
+        assert_eq!(
+            parse_build_info(
+                "Mihomo Meta v1.18.1 linux amd64 with go1.21.5 Sun Dec 17 04:07:18 UTC 2023\nUse tags: with_gvisor"
+            ),
+            BuildInfo {
+                version: Some("v1.18.1".into()),
+                build_time: Some("Sun Dec 17 04:07:18 UTC 2023".into()),
+                toolchain: Some("go1.21.5".into()),
+            }
+        );
+        assert_eq!(
+            parse_build_info("Clash 2023.08.17 linux amd64 with go1.20.7"),
+            BuildInfo {
+                version: Some("2023.08.17".into()),
+                build_time: None,
+                toolchain: Some("go1.20.7".into()),
+            }
+        );
+        assert_eq!(
+            parse_build_info("clash-rs 0.7.1\nbuilt with rustc 1.80.0"),
+            BuildInfo {
+                version: Some("0.7.1".into()),
+                build_time: None,
+                toolchain: Some("rustc 1.80.0".into()),
+            }
+        );
+        assert_eq!(parse_build_info("something new"), BuildInfo::default());
+    }
+
+    #[test]
+    fn test_flavor_from_probed_binary() {
+        // mihomo-alpha 的二进制被替换为 clash-rs
+        let info = CoreVersionInfo {
+            core: ClashCore::MihomoAlpha,
+            version: "0.7.1".into(),
+            build_time: None,
+            go_or_rust_version: None,
+            raw: "clash-rs 0.7.1".into(),
+            binary_path: PathBuf::from("/tmp/mihomo-alpha"),
+            binary_sha256: String::new(),
+        };
+        let cache =
+            VersionCache::from([(info.binary_path.clone(), (SystemTime::UNIX_EPOCH, 0, info))]);
+        assert_eq!(
+            flavor_in_cache(&cache, ClashCore::MihomoAlpha),
+            Some(CoreFlavor::ClashRs)
+        );
+        assert_eq!(flavor_in_cache(&cache, ClashCore::ClashPremium), None);
+    }
+}
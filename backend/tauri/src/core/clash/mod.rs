@@ -3,8 +3,26 @@ use once_cell::sync::Lazy;
 use tauri::Emitter;
 
 pub mod api;
+pub mod bench;
+pub mod connection_details;
+pub mod connection_log;
+pub mod connection_session;
 pub mod core;
+pub mod core_args;
+pub mod core_info;
+pub mod node_meta;
+pub mod orphan;
+pub mod policy;
+pub mod provider_prefetch;
+pub mod provider_refresh;
 pub mod proxies;
+pub mod proxy_search;
+pub mod signing;
+pub mod statistics;
+#[cfg(test)]
+mod test_utils;
+pub mod traffic_policy;
+pub mod watchdog;
 pub mod ws;
 
 pub static CLASH_API_DEFAULT_BACKOFF_STRATEGY: Lazy<ExponentialBuilder> = Lazy::new(|| {
@@ -15,8 +33,27 @@ pub static CLASH_API_DEFAULT_BACKOFF_STRATEGY: Lazy<ExponentialBuilder> = Lazy::
 });
 
 pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(manager: &M) -> anyhow::Result<()> {
+    // 需要在连接断开时覆盖记录之前读取
+    connection_session::load_last_session();
+    connection_log::ConnectionLogger::global().set_enabled(
+        crate::config::Config::verge()
+            .latest()
+            .enable_connection_log
+            .unwrap_or(false),
+    );
     let ws_connector = ws::ClashConnectionsConnector::new();
     manager.manage(ws_connector.clone());
+    // 代理启动失败只影响外部 UI，不影响其他功能
+    match api::ClashExtUIProxy::spawn() {
+        Ok(proxy) => {
+            manager.manage(proxy);
+        }
+        Err(e) => tracing::error!("failed to start external ui proxy: {e:?}"),
+    }
+    if crate::consts::is_safe_mode() {
+        // 核心没有启动，无需连接
+        return Ok(());
+    }
     let app_handle = manager.app_handle().clone();
 
     tauri::async_runtime::spawn(async move {
@@ -31,13 +68,13 @@ pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(manager: &M) -> anyhow::Re
             tokio::time::sleep(std::time::Duration::from_secs(1)).await;
 
             let (state, _, _) = super::CoreManager::global().status().await;
+            // Core 已运行后，以 API 实际可以响应作为就绪的标志
             if matches!(
                 state.as_ref(),
                 nyanpasu_ipc::api::status::CoreState::Running
-            ) {
-                // Core 已运行，再等待 2 秒确保 API 端点完全就绪
-                tracing::info!("Clash core is running, waiting 2 seconds for API to be ready...");
-                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            ) && let Some(latency) = api::controller_ping().await
+            {
+                tracing::info!("Clash API is ready, latency: {latency:?}");
                 break;
             }
 
@@ -0,0 +1,340 @@
+//! 从节点名称中解析地区、倍率与标签，供前端展示
+//! 解析结果按节点名称缓存，规则表变化时需调用 `reparse` 刷新
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use regex::{Regex, RegexBuilder};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeMetadata {
+    /// ISO 3166-1 alpha-2 地区代码，无法识别时为 None
+    pub region_code: Option<String>,
+    /// 地区旗帜 emoji
+    pub emoji: Option<String>,
+    pub multiplier: Option<f64>,
+    pub tags: Vec<String>,
+}
+
+/// 用户自定义的解析规则，优先于内置规则
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct NodeMetadataPatterns {
+    /// 地区代码 => 正则
+    #[serde(default)]
+    pub regions: IndexMap<String, String>,
+    /// 倍率正则，需包含一个捕获组
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplier: Option<String>,
+    /// 标签 => 正则
+    #[serde(default)]
+    pub tags: IndexMap<String, String>,
+}
+
+/// 内置地区规则表，地区代码区分大小写以免误匹配普通单词
+const REGION_PATTERNS: &[(&str, &str)] = &[
+    ("HK", r"香港|\bHKG?\b|(?i)hong\s*kong"),
+    ("TW", r"台湾|台灣|\bTW\b|(?i)taiwan"),
+    ("MO", r"澳门|澳門|\bMO\b|(?i)maca[ou]"),
+    ("JP", r"日本|东京|大阪|\bJP\b|(?i)japan|tokyo|osaka"),
+    ("KR", r"韩国|韓國|首尔|\bKR\b|(?i)korea|seoul"),
+    ("SG", r"新加坡|狮城|\bSG\b|(?i)singapore"),
+    (
+        "US",
+        r"美国|美國|洛杉矶|圣何塞|硅谷|\bUSA?\b|(?i)united\s*states|los\s*angeles",
+    ),
+    (
+        "GB",
+        r"英国|英國|伦敦|\bUK\b|\bGB\b|(?i)united\s*kingdom|london",
+    ),
+    ("DE", r"德国|德國|法兰克福|\bDE\b|(?i)germany|frankfurt"),
+    ("FR", r"法国|法國|巴黎|\bFR\b|(?i)france|paris"),
+    ("NL", r"荷兰|荷蘭|\bNL\b|(?i)netherlands|amsterdam"),
+    ("CA", r"加拿大|\bCA\b|(?i)canada"),
+    ("AU", r"澳大利亚|澳洲|\bAU\b|(?i)australia|sydney"),
+    ("RU", r"俄罗斯|俄羅斯|\bRU\b|(?i)russia|moscow"),
+    ("IN", r"印度|(?i)\bindia\b"),
+    ("TR", r"土耳其|\bTR\b|(?i)turkey|türkiye"),
+    ("AR", r"阿根廷|\bAR\b|(?i)argentina"),
+];
+
+/// 内置标签规则表
+const TAG_PATTERNS: &[(&str, &str)] = &[
+    ("IPLC", r"(?i)\bIPLC\b"),
+    ("IEPL", r"(?i)\bIEPL\b"),
+    ("BGP", r"(?i)\bBGP\b"),
+    ("CN2", r"(?i)\bCN2\b"),
+    ("Home", r"家宽|家庭|(?i)\bhome\b|residential"),
+    ("Game", r"游戏|(?i)\bgame\b|gaming"),
+    ("Streaming", r"流媒体|解锁|\bNF\b|(?i)\bnetflix\b|disney"),
+];
+
+/// 内置倍率规则，按顺序尝试："倍率:2"、"x2"/"×2"、"2x"/"2倍"
+const MULTIPLIER_PATTERNS: &[&str] = &[
+    r"倍率\s*[:：]?\s*(\d+(?:\.\d+)?)",
+    r"(?:\b[xX]|×)\s*(\d+(?:\.\d+)?)\b",
+    r"(\d+(?:\.\d+)?)\s*(?:[xX]\b|×|倍)",
+];
+
+fn build_regex(pattern: &str) -> Option<Regex> {
+    match RegexBuilder::new(pattern).unicode(true).build() {
+        Ok(re) => Some(re),
+        Err(e) => {
+            tracing::warn!("invalid node metadata pattern `{pattern}`: {e}");
+            None
+        }
+    }
+}
+
+fn build_table<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<(String, Regex)> {
+    entries
+        .into_iter()
+        .filter_map(|(key, pattern)| build_regex(pattern).map(|re| (key.to_string(), re)))
+        .collect()
+}
+
+/// 编译后的规则表，自定义规则排在内置规则之前
+struct PatternTable {
+    custom_regions: Vec<(String, Regex)>,
+    regions: Vec<(String, Regex)>,
+    tags: Vec<(String, Regex)>,
+    multipliers: Vec<Regex>,
+}
+
+impl PatternTable {
+    fn new(custom: &NodeMetadataPatterns) -> Self {
+        let custom_regions = custom
+            .regions
+            .iter()
+            .map(|(code, pattern)| (code.as_str(), pattern.as_str()));
+        let tags = custom
+            .tags
+            .iter()
+            .map(|(tag, pattern)| (tag.as_str(), pattern.as_str()))
+            .chain(TAG_PATTERNS.iter().copied());
+        let multipliers = custom
+            .multiplier
+            .as_deref()
+            .into_iter()
+            .chain(MULTIPLIER_PATTERNS.iter().copied())
+            .filter_map(build_regex)
+            .collect();
+        Self {
+            custom_regions: build_table(custom_regions),
+            regions: build_table(REGION_PATTERNS.iter().copied()),
+            tags: build_table(tags),
+            multipliers,
+        }
+    }
+
+    fn region_in(table: &[(String, Regex)], name: &str) -> Option<String> {
+        table
+            .iter()
+            .find(|(_, re)| re.is_match(name))
+            .map(|(code, _)| code.to_ascii_uppercase())
+    }
+
+    fn multiplier_of(&self, name: &str) -> Option<f64> {
+        self.multipliers.iter().find_map(|re| {
+            re.captures(name)?
+                .iter()
+                .skip(1)
+                .flatten()
+                .find_map(|m| m.as_str().parse().ok())
+        })
+    }
+
+    fn parse(&self, name: &str) -> NodeMetadata {
+        // 自定义规则优先，其次是名称里的旗帜，最后才是内置文字规则
+        let region_code = Self::region_in(&self.custom_regions, name)
+            .or_else(|| flag_region(name))
+            .or_else(|| Self::region_in(&self.regions, name));
+        let emoji = region_code.as_deref().and_then(region_flag);
+        let mut tags = Vec::new();
+        for (tag, re) in &self.tags {
+            if re.is_match(name) && !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        NodeMetadata {
+            region_code,
+            emoji,
+            multiplier: self.multiplier_of(name),
+            tags,
+        }
+    }
+}
+
+const REGIONAL_INDICATOR_A: u32 = 0x1F1E6;
+const REGIONAL_INDICATOR_Z: u32 = 0x1F1FF;
+
+fn regional_indicator_letter(c: char) -> Option<char> {
+    let c = c as u32;
+    (REGIONAL_INDICATOR_A..=REGIONAL_INDICATOR_Z)
+        .contains(&c)
+        .then(|| char::from(b'A' + (c - REGIONAL_INDICATOR_A) as u8))
+}
+
+/// 解析名称中的第一个旗帜 emoji（两个连续的 regional indicator）
+fn flag_region(name: &str) -> Option<String> {
+    let letters = name
+        .chars()
+        .map(regional_indicator_letter)
+        .collect::<Vec<_>>();
+    letters.windows(2).find_map(|pair| match pair {
+        [Some(a), Some(b)] => Some(format!("{a}{b}")),
+        _ => None,
+    })
+}
+
+/// 地区代码转旗帜 emoji
+fn region_flag(code: &str) -> Option<String> {
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    code.chars()
+        .map(|c| char::from_u32(REGIONAL_INDICATOR_A + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+/// 缓存的条目上限，超出时清空重新解析
+const MAX_ENTRIES: usize = 10_000;
+
+pub struct NodeMetadataCache {
+    table: RwLock<Arc<PatternTable>>,
+    entries: RwLock<FxHashMap<String, NodeMetadata>>,
+}
+
+impl NodeMetadataCache {
+    pub fn global() -> &'static NodeMetadataCache {
+        static CACHE: Lazy<NodeMetadataCache> =
+            Lazy::new(|| NodeMetadataCache::new(PatternTable::new(&custom_patterns())));
+        &CACHE
+    }
+
+    fn new(table: PatternTable) -> Self {
+        Self {
+            table: RwLock::new(Arc::new(table)),
+            entries: RwLock::new(FxHashMap::default()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> NodeMetadata {
+        if let Some(meta) = self.entries.read().get(name) {
+            return meta.clone();
+        }
+        let meta = self.table.read().parse(name);
+        let mut entries = self.entries.write();
+        if entries.len() >= MAX_ENTRIES {
+            entries.clear();
+        }
+        entries.insert(name.to_string(), meta.clone());
+        meta
+    }
+
+    /// 批量获取，用于生成代理快照；不在快照中的节点从缓存中移除
+    pub fn collect<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> IndexMap<String, NodeMetadata> {
+        let collected = names
+            .into_iter()
+            .map(|name| (name.to_string(), self.get(name)))
+            .collect::<IndexMap<_, _>>();
+        self.entries
+            .write()
+            .retain(|name, _| collected.contains_key(name));
+        collected
+    }
+
+    /// 重新读取自定义规则并清空缓存
+    pub fn reparse(&self) {
+        *self.table.write() = Arc::new(PatternTable::new(&custom_patterns()));
+        self.entries.write().clear();
+    }
+}
+
+/// 规则表变化后重新解析，并刷新代理快照以推送给前端
+pub async fn reparse_node_metadata() -> anyhow::Result<()> {
+    use super::proxies::{ProxiesGuard, ProxiesGuardExt};
+    NodeMetadataCache::global().reparse();
+    ProxiesGuard::global().update().await
+}
+
+fn custom_patterns() -> NodeMetadataPatterns {
+    crate::config::Config::verge()
+        .latest()
+        .node_metadata_patterns
+        .clone()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> NodeMetadata {
+        PatternTable::new(&NodeMetadataPatterns::default()).parse(name)
+    }
+
+    #[test]
+    fn test_parse_flag_and_multiplier() {
+        let meta = parse("🇺🇸 US-01 | x2");
+        assert_eq!(meta.region_code.as_deref(), Some("US"));
+        assert_eq!(meta.emoji.as_deref(), Some("🇺🇸"));
+        assert_eq!(meta.multiplier, Some(2.0));
+
+        let meta = parse("🇯🇵 Tokyo IPLC 0.5x");
+        assert_eq!(meta.region_code.as_deref(), Some("JP"));
+        assert_eq!(meta.multiplier, Some(0.5));
+        assert_eq!(meta.tags, vec!["IPLC".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_chinese_names() {
+        let meta = parse("香港 02 倍率:1.5");
+        assert_eq!(meta.region_code.as_deref(), Some("HK"));
+        assert_eq!(meta.emoji.as_deref(), Some("🇭🇰"));
+        assert_eq!(meta.multiplier, Some(1.5));
+        assert_eq!(parse("新加坡 3倍").multiplier, Some(3.0));
+    }
+
+    #[test]
+    fn test_parse_without_region_info() {
+        let meta = parse("Premium Node 01");
+        assert_eq!(meta, NodeMetadata::default());
+        // 单词内部的 x 不应被当作倍率
+        assert_eq!(parse("Max2 Relay").multiplier, None);
+    }
+
+    #[test]
+    fn test_custom_patterns_take_precedence() {
+        let custom = NodeMetadataPatterns {
+            regions: [("SG".to_string(), "狮城|Lion".to_string())].into(),
+            multiplier: Some(r"rate=(\d+)".into()),
+            ..Default::default()
+        };
+        let meta = PatternTable::new(&custom).parse("🇭🇰 Lion rate=3 x2");
+        assert_eq!(meta.region_code.as_deref(), Some("SG"));
+        assert_eq!(meta.multiplier, Some(3.0));
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let cache = NodeMetadataCache::new(PatternTable::new(&NodeMetadataPatterns::default()));
+        cache.collect(["🇭🇰 HK 01", "🇯🇵 JP 01"]);
+        let collected = cache.collect(["🇯🇵 JP 01", "🇺🇸 US 01"]);
+        assert_eq!(collected["🇺🇸 US 01"].region_code.as_deref(), Some("US"));
+        // 已不在快照中的节点被移除
+        assert_eq!(cache.entries.read().len(), 2);
+        assert!(!cache.entries.read().contains_key("🇭🇰 HK 01"));
+
+        for i in 0..MAX_ENTRIES + 1 {
+            cache.get(&format!("node {i}"));
+        }
+        assert!(cache.entries.read().len() <= MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,308 @@
+//! 查找上次崩溃后遗留的核心进程
+//! 可执行文件与受管理的核心一致、且不是本应用或服务启动的进程视为遗留进程；
+//! 核心启动时记录 pid 与启动时间，可执行文件路径读取不到时据此判断，启动时间不同说明 pid 已被复用
+use super::core::find_binary_path;
+use crate::{
+    config::{Config, nyanpasu::ClashCore},
+    utils::dirs,
+};
+use anyhow::{Context, Result};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+
+const RECORD_FILE: &str = "core_process.json";
+const SERVICE_NAME: &str = "nyanpasu-service";
+
+/// 核心启动时写入的记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreProcessRecord {
+    pub pid: u32,
+    /// 进程启动的 unix 时间戳（秒）
+    pub start_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub parent: Option<u32>,
+    pub start_time: u64,
+    /// 没有权限时读取不到
+    pub exe: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Type)]
+pub struct OrphanedCore {
+    pub pid: u32,
+    pub exe: Option<String>,
+    pub start_time: u64,
+}
+
+/// 进程表，测试时可以替换
+pub trait ProcessTable {
+    fn processes(&self) -> Vec<ProcessEntry>;
+    fn kill(&self, pid: u32) -> bool;
+}
+
+pub struct SystemProcessTable;
+
+impl ProcessTable for SystemProcessTable {
+    fn processes(&self) -> Vec<ProcessEntry> {
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::All, true);
+        system
+            .processes()
+            .values()
+            .map(|process| ProcessEntry {
+                pid: process.pid().as_u32(),
+                parent: process.parent().map(|pid| pid.as_u32()),
+                start_time: process.start_time(),
+                exe: process.exe().map(Path::to_path_buf),
+            })
+            .collect()
+    }
+
+    fn kill(&self, pid: u32) -> bool {
+        let pid = Pid::from_u32(pid);
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+        system.process(pid).is_some_and(|process| process.kill())
+    }
+}
+
+fn record_path() -> Result<PathBuf> {
+    Ok(dirs::app_data_dir()?.join(RECORD_FILE))
+}
+
+/// 核心以子进程启动后调用，pid 取自核心写入的 pid 文件
+pub fn save_core_record() -> Result<()> {
+    let pid = fs::read_to_string(dirs::clash_pid_path()?)
+        .context("failed to read core pid file")?
+        .trim()
+        .parse::<u32>()
+        .context("invalid core pid file")?;
+    let start_time = SystemProcessTable
+        .processes()
+        .into_iter()
+        .find(|entry| entry.pid == pid)
+        .map(|entry| entry.start_time)
+        .with_context(|| format!("core process {pid} is not running"))?;
+    let record = CoreProcessRecord { pid, start_time };
+    fs::write(record_path()?, serde_json::to_vec(&record)?)?;
+    Ok(())
+}
+
+pub fn clear_core_record() -> Result<()> {
+    match fs::remove_file(record_path()?) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn load_core_record() -> Option<CoreProcessRecord> {
+    let bytes = fs::read(record_path().ok()?).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// 所有核心可能使用的可执行文件
+fn managed_binaries() -> Vec<PathBuf> {
+    [
+        ClashCore::ClashPremium,
+        ClashCore::Mihomo,
+        ClashCore::MihomoAlpha,
+    ]
+    .iter()
+    .filter_map(|core| find_binary_path(&core.into()).ok())
+    .map(|path| fs::canonicalize(&path).unwrap_or(path))
+    .collect()
+}
+
+/// Linux 下运行中的可执行文件被替换后，路径会带上 ` (deleted)` 后缀
+fn normalize_exe(exe: &Path) -> PathBuf {
+    let exe = exe.to_string_lossy();
+    PathBuf::from(exe.strip_suffix(" (deleted)").unwrap_or(&exe))
+}
+
+fn is_service(entry: &ProcessEntry) -> bool {
+    entry
+        .exe
+        .as_deref()
+        .and_then(Path::file_stem)
+        .is_some_and(|stem| stem.to_string_lossy().starts_with(SERVICE_NAME))
+}
+
+pub fn find_orphans(
+    processes: &[ProcessEntry],
+    binaries: &[PathBuf],
+    record: Option<&CoreProcessRecord>,
+    current_pid: u32,
+) -> Vec<OrphanedCore> {
+    let managed_parent = |parent: Option<u32>| {
+        parent.is_some_and(|parent| {
+            parent == current_pid
+                || processes
+                    .iter()
+                    .any(|entry| entry.pid == parent && is_service(entry))
+        })
+    };
+    processes
+        .iter()
+        .filter(|entry| entry.pid != current_pid && !managed_parent(entry.parent))
+        .filter(|entry| match &entry.exe {
+            Some(exe) => binaries.contains(&normalize_exe(exe)),
+            None => record.is_some_and(|record| {
+                record.pid == entry.pid && record.start_time == entry.start_time
+            }),
+        })
+        .map(|entry| OrphanedCore {
+            pid: entry.pid,
+            exe: entry.exe.as_ref().map(|exe| exe.display().to_string()),
+            start_time: entry.start_time,
+        })
+        .collect()
+}
+
+pub fn orphaned_cores_with(table: &impl ProcessTable) -> Vec<OrphanedCore> {
+    find_orphans(
+        &table.processes(),
+        &managed_binaries(),
+        load_core_record().as_ref(),
+        std::process::id(),
+    )
+}
+
+pub fn orphaned_cores() -> Vec<OrphanedCore> {
+    orphaned_cores_with(&SystemProcessTable)
+}
+
+/// 结束遗留的核心进程，返回未能结束的进程
+pub fn kill_orphaned_cores(
+    table: &impl ProcessTable,
+    orphans: Vec<OrphanedCore>,
+) -> Vec<OrphanedCore> {
+    orphans
+        .into_iter()
+        .filter(|orphan| {
+            let killed = table.kill(orphan.pid);
+            if killed {
+                log::info!(target: "app", "killed orphaned core process {}", orphan.pid);
+            } else {
+                log::warn!(target: "app", "failed to kill orphaned core process {}", orphan.pid);
+            }
+            !killed
+        })
+        .collect()
+}
+
+/// 启动核心前调用：按设置自动结束遗留进程，否则通知用户
+pub fn handle_orphaned_cores() {
+    let orphans = orphaned_cores();
+    if orphans.is_empty() {
+        return;
+    }
+    log::warn!(target: "app", "found orphaned core processes: {orphans:?}");
+    let auto_kill = Config::verge().latest().kill_orphaned_cores.unwrap_or(true);
+    let remaining = match auto_kill {
+        true => kill_orphaned_cores(&SystemProcessTable, orphans),
+        false => orphans,
+    };
+    if !remaining.is_empty() {
+        notify(remaining.len());
+    }
+}
+
+fn notify(count: usize) {
+    use tauri_plugin_notification::NotificationExt;
+    crate::log_err!(
+        crate::consts::app_handle()
+            .notification()
+            .builder()
+            .title(t!("notification.orphaned_cores.title"))
+            .body(t!("notification.orphaned_cores.body", count = count))
+            .show()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURRENT: u32 = 100;
+
+    fn entry(pid: u32, parent: u32, start_time: u64, exe: Option<&str>) -> ProcessEntry {
+        ProcessEntry {
+            pid,
+            parent: Some(parent),
+            start_time,
+            exe: exe.map(PathBuf::from),
+        }
+    }
+
+    struct FakeTable(Vec<ProcessEntry>);
+
+    impl ProcessTable for FakeTable {
+        fn processes(&self) -> Vec<ProcessEntry> {
+            self.0.clone()
+        }
+
+        fn kill(&self, _pid: u32) -> bool {
+            false
+        }
+    }
+
+    fn pids(table: &FakeTable, record: Option<&CoreProcessRecord>) -> Vec<u32> {
+        let binaries = vec![PathBuf::from("/data/mihomo")];
+        find_orphans(&table.processes(), &binaries, record, CURRENT)
+            .into_iter()
+            .map(|orphan| orphan.pid)
+            .collect()
+    }
+
+    #[test]
+    fn test_find_orphans() {
+        let table = FakeTable(vec![
+            entry(CURRENT, 1, 10, Some("/app/clash-nyanpasu")),
+            // 本应用启动的核心
+            entry(200, CURRENT, 20, Some("/data/mihomo")),
+            // 服务启动的核心
+            entry(300, 1, 5, Some("/usr/bin/nyanpasu-service")),
+            entry(301, 300, 30, Some("/data/mihomo")),
+            // 上次崩溃遗留，核心文件已被更新替换
+            entry(400, 1, 8, Some("/data/mihomo")),
+            entry(401, 1, 9, Some("/data/mihomo (deleted)")),
+            // 其他程序
+            entry(500, 1, 8, Some("/usr/bin/mihomo")),
+        ]);
+        assert_eq!(pids(&table, None), vec![400, 401]);
+
+        let orphans = find_orphans(
+            &table.processes(),
+            &[PathBuf::from("/data/mihomo")],
+            None,
+            CURRENT,
+        );
+        assert_eq!(kill_orphaned_cores(&table, orphans).len(), 2);
+    }
+
+    #[test]
+    fn test_pid_reuse() {
+        let record = CoreProcessRecord {
+            pid: 400,
+            start_time: 8,
+        };
+        // 读取不到可执行文件时依据启动记录判断
+        let table = FakeTable(vec![entry(400, 1, 8, None)]);
+        assert_eq!(pids(&table, Some(&record)), vec![400]);
+        // pid 被其他进程复用
+        let table = FakeTable(vec![entry(400, 1, 42, None)]);
+        assert!(pids(&table, Some(&record)).is_empty());
+        let table = FakeTable(vec![entry(400, 1, 42, Some("/usr/bin/sshd"))]);
+        assert!(pids(&table, Some(&record)).is_empty());
+        assert!(pids(&FakeTable(vec![entry(400, 1, 8, None)]), None).is_empty());
+    }
+}
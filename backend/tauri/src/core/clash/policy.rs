@@ -0,0 +1,418 @@
+//! 代理组智能选择策略
+//! 在匹配正则的节点中按延迟或抖动选出最优节点，超过阈值时自动切换
+use super::{
+    api,
+    proxies::{Proxies, ProxiesGuard, ProxiesGuardExt},
+};
+use crate::{config::Config, core::handle::Handle, feat, log_err};
+use anyhow::{Context, Result, bail};
+use futures::StreamExt;
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tracing_attributes::instrument;
+
+/// 策略评估间隔
+const EVALUATE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// 批量测速的并发上限
+const DELAY_TEST_CONCURRENCY: usize = 8;
+/// 抖动采样次数
+const JITTER_SAMPLES: usize = 3;
+const DECISION_CAPACITY: usize = 50;
+
+/// 每个 profile 的策略，key 为代理组名称
+pub type GroupPolicies = IndexMap<String, GroupPolicy>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyMetric {
+    /// 延迟
+    #[default]
+    Delay,
+    /// 连续测速之间的平均波动
+    Jitter,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct GroupPolicy {
+    /// 仅在匹配的节点中选择，为空时匹配全部
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_regex: Option<String>,
+    /// 排除匹配的节点
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_regex: Option<String>,
+    #[serde(default)]
+    pub metric: PolicyMetric,
+    /// 新节点需比当前节点至少好这么多毫秒才会切换
+    #[serde(default)]
+    pub switch_threshold_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+struct NodeFilter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+fn compile_regex(field: &str, pattern: &Option<String>) -> Result<Option<Regex>> {
+    pattern
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            // 节点名常带 emoji 国旗，按 Unicode 字符匹配
+            RegexBuilder::new(p)
+                .unicode(true)
+                .build()
+                .with_context(|| format!("invalid {field}: `{p}`"))
+        })
+        .transpose()
+}
+
+impl GroupPolicy {
+    fn filter(&self) -> Result<NodeFilter> {
+        Ok(NodeFilter {
+            include: compile_regex("include_regex", &self.include_regex)?,
+            exclude: compile_regex("exclude_regex", &self.exclude_regex)?,
+        })
+    }
+}
+
+impl NodeFilter {
+    fn matches(&self, name: &str) -> bool {
+        self.include.as_ref().is_none_or(|re| re.is_match(name))
+            && !self.exclude.as_ref().is_some_and(|re| re.is_match(name))
+    }
+}
+
+/// 一次自动切换的记录
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyDecision {
+    /// unix 时间戳（秒）
+    pub timestamp: i64,
+    pub group: String,
+    pub from: Option<String>,
+    pub to: String,
+    pub metric: PolicyMetric,
+    pub from_score: Option<u64>,
+    pub to_score: u64,
+}
+
+/// 从测速结果中选出应切换到的节点，无需切换时返回 None
+fn choose_candidate(
+    current: Option<&str>,
+    candidates: &[String],
+    scores: &IndexMap<String, u64>,
+    threshold_ms: u64,
+) -> Option<(String, u64)> {
+    let (best, best_score) = candidates
+        .iter()
+        .filter_map(|name| scores.get(name).map(|score| (name, *score)))
+        .min_by_key(|(_, score)| *score)?;
+    let Some(current) = current else {
+        return Some((best.clone(), best_score));
+    };
+    if current == best {
+        return None;
+    }
+    // 当前节点不在候选中或已不可用时直接切换
+    if !candidates.iter().any(|name| name == current) {
+        return Some((best.clone(), best_score));
+    }
+    match scores.get(current) {
+        Some(current_score) if current_score.saturating_sub(best_score) <= threshold_ms => None,
+        _ => Some((best.clone(), best_score)),
+    }
+}
+
+async fn measure(name: String, metric: PolicyMetric) -> Option<u64> {
+    match metric {
+        PolicyMetric::Delay => api::get_proxy_delay(name, None)
+            .await
+            .ok()
+            .map(|res| res.delay),
+        PolicyMetric::Jitter => {
+            let mut samples = Vec::with_capacity(JITTER_SAMPLES);
+            for _ in 0..JITTER_SAMPLES {
+                samples.push(api::get_proxy_delay(name.clone(), None).await.ok()?.delay);
+            }
+            let diffs = samples.windows(2).map(|w| w[0].abs_diff(w[1]));
+            Some(diffs.sum::<u64>() / (JITTER_SAMPLES as u64 - 1))
+        }
+    }
+}
+
+/// 批量测速，限制并发
+async fn measure_all(names: Vec<String>, metric: PolicyMetric) -> IndexMap<String, u64> {
+    futures::stream::iter(names)
+        .map(|name| async move {
+            let score = measure(name.clone(), metric).await;
+            (name, score)
+        })
+        .buffer_unordered(DELAY_TEST_CONCURRENCY)
+        .filter_map(|(name, score)| async move { score.map(|score| (name, score)) })
+        .collect()
+        .await
+}
+
+/// 当前 profile 的 key，策略按 profile 分开保存
+fn current_profile_key() -> Option<String> {
+    Config::profiles().latest().get_current().first().cloned()
+}
+
+fn load_policies() -> GroupPolicies {
+    let Some(profile) = current_profile_key() else {
+        return GroupPolicies::default();
+    };
+    Config::verge()
+        .latest()
+        .group_policies
+        .as_ref()
+        .and_then(|policies| policies.get(&profile).cloned())
+        .unwrap_or_default()
+}
+
+async fn save_policies(policies: GroupPolicies) -> Result<()> {
+    let profile = current_profile_key().context("no profile is selected")?;
+    let mut all = Config::verge()
+        .latest()
+        .group_policies
+        .clone()
+        .unwrap_or_default();
+    if policies.is_empty() {
+        all.shift_remove(&profile);
+    } else {
+        all.insert(profile, policies);
+    }
+    feat::patch_verge(crate::config::nyanpasu::IVerge {
+        group_policies: Some(all),
+        ..Default::default()
+    })
+    .await
+}
+
+fn validate_group(proxies: &Proxies, group: &str) -> Result<()> {
+    let item = proxies
+        .records
+        .get(group)
+        .with_context(|| format!("proxy group `{group}` not found"))?;
+    if !item.r#type.eq_ignore_ascii_case("selector") {
+        bail!(
+            "proxy group `{group}` is a `{}` group, only selector groups can be switched",
+            item.r#type
+        );
+    }
+    Ok(())
+}
+
+pub struct GroupPolicyManager {
+    running: AtomicBool,
+    decisions: Mutex<VecDeque<PolicyDecision>>,
+}
+
+impl GroupPolicyManager {
+    pub fn global() -> &'static GroupPolicyManager {
+        static MANAGER: Lazy<GroupPolicyManager> = Lazy::new(|| GroupPolicyManager {
+            running: AtomicBool::new(false),
+            decisions: Mutex::new(VecDeque::with_capacity(DECISION_CAPACITY)),
+        });
+        &MANAGER
+    }
+
+    /// 启动定时评估
+    pub fn start(&'static self) {
+        if self.running.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(EVALUATE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // 跳过首次立即触发，等待核心就绪
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                // 窗口不可见时不做定时测速
+                if !crate::core::activity::ActivityManager::global()
+                    .profile()
+                    .delay_watchdog()
+                {
+                    continue;
+                }
+                log_err!(
+                    self.evaluate_all().await,
+                    "failed to evaluate group policies"
+                );
+            }
+        });
+    }
+
+    pub fn list(&self) -> GroupPolicies {
+        load_policies()
+    }
+
+    pub fn decisions(&self) -> Vec<PolicyDecision> {
+        self.decisions.lock().iter().cloned().collect()
+    }
+
+    /// 校验并保存策略
+    pub async fn set(&self, group: String, policy: GroupPolicy) -> Result<()> {
+        policy.filter()?;
+        validate_group(&Proxies::fetch().await?, &group)?;
+        let mut policies = load_policies();
+        policies.insert(group, policy);
+        save_policies(policies).await
+    }
+
+    pub async fn remove(&self, group: &str) -> Result<()> {
+        let mut policies = load_policies();
+        if policies.shift_remove(group).is_none() {
+            bail!("proxy group `{group}` has no policy");
+        }
+        save_policies(policies).await
+    }
+
+    #[instrument(skip(self))]
+    async fn evaluate_all(&self) -> Result<()> {
+        let policies = load_policies();
+        if policies.is_empty() {
+            return Ok(());
+        }
+        // 核心重启期间暂停
+        let (state, ..) = crate::core::CoreManager::global().status().await;
+        if !matches!(
+            state.as_ref(),
+            nyanpasu_ipc::api::status::CoreState::Running
+        ) {
+            tracing::debug!("core is not running, skip group policies");
+            return Ok(());
+        }
+        let proxies = Proxies::fetch().await?;
+        for (group, policy) in policies {
+            if let Err(e) = self.evaluate(&proxies, &group, &policy).await {
+                tracing::warn!("failed to evaluate policy of group {group}: {e:#}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn evaluate(&self, proxies: &Proxies, group: &str, policy: &GroupPolicy) -> Result<()> {
+        validate_group(proxies, group)?;
+        let item = &proxies.records[group];
+        let filter = policy.filter()?;
+        let candidates = item
+            .all
+            .iter()
+            .flatten()
+            .filter(|name| filter.matches(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            tracing::debug!("no node of group {group} matches its policy");
+            return Ok(());
+        }
+        let current = item.now.clone();
+        let mut names = candidates.clone();
+        if let Some(current) = current.as_ref()
+            && !names.contains(current)
+        {
+            names.push(current.clone());
+        }
+        let scores = measure_all(names, policy.metric).await;
+        let Some((to, to_score)) = choose_candidate(
+            current.as_deref(),
+            &candidates,
+            &scores,
+            policy.switch_threshold_ms,
+        ) else {
+            return Ok(());
+        };
+
+        ProxiesGuard::global().select_proxy(group, &to).await?;
+        let decision = PolicyDecision {
+            timestamp: chrono::Utc::now().timestamp(),
+            group: group.to_string(),
+            from_score: current.as_ref().and_then(|c| scores.get(c).copied()),
+            from: current,
+            to,
+            metric: policy.metric,
+            to_score,
+        };
+        tracing::info!(
+            "group policy switched {} from {:?} to {}",
+            decision.group,
+            decision.from,
+            decision.to
+        );
+        {
+            let mut decisions = self.decisions.lock();
+            if decisions.len() >= DECISION_CAPACITY {
+                decisions.pop_front();
+            }
+            decisions.push_back(decision.clone());
+        }
+        log_err!(Handle::emit("group-policy-switched", decision));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_filter_matches_emoji_flags() {
+        let policy = GroupPolicy {
+            include_regex: Some("🇺🇸|(?i)\\bUS\\b".into()),
+            exclude_regex: Some("0\\.\\d+x".into()),
+            ..Default::default()
+        };
+        let filter = policy.filter().unwrap();
+        assert!(filter.matches("🇺🇸 Los Angeles 01"));
+        assert!(filter.matches("us-west"));
+        assert!(!filter.matches("🇺🇸 Seattle 0.5x"));
+        assert!(!filter.matches("🇯🇵 Tokyo"));
+    }
+
+    #[test]
+    fn test_invalid_regex_is_rejected() {
+        let policy = GroupPolicy {
+            include_regex: Some("(".into()),
+            ..Default::default()
+        };
+        assert!(policy.filter().is_err());
+    }
+
+    #[test]
+    fn test_choose_candidate_respects_threshold() {
+        let candidates = names(&["a", "b"]);
+        let scores: IndexMap<String, u64> = [("a".to_string(), 100), ("b".to_string(), 80)].into();
+        assert_eq!(choose_candidate(Some("a"), &candidates, &scores, 30), None);
+        assert_eq!(
+            choose_candidate(Some("a"), &candidates, &scores, 10),
+            Some(("b".to_string(), 80))
+        );
+        // 当前节点不在候选中时无视阈值
+        assert_eq!(
+            choose_candidate(Some("c"), &candidates, &scores, 1000),
+            Some(("b".to_string(), 80))
+        );
+        // 当前节点测速失败
+        let scores: IndexMap<String, u64> = [("b".to_string(), 80)].into();
+        assert_eq!(
+            choose_candidate(Some("a"), &candidates, &scores, 1000),
+            Some(("b".to_string(), 80))
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! 核心启动前下载缺失的代理集合与规则集合
+//! 首次启动时代理尚未就绪，被墙的集合地址核心无法下载，导致配置不可用
+//! 这里只补全本地缓存（`path`）不存在的集合，之后的更新仍由核心负责
+use crate::{
+    config::Config,
+    core::{activation, handle::Handle, sysopt},
+    log_err,
+    utils::{dirs, tls::CustomCaExt},
+};
+use anyhow::{Context, Result, bail};
+use backon::Retryable;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use specta::Type;
+use std::{
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use sysproxy::Sysproxy;
+
+const PROGRESS_EVENT: &str = "provider-prefetch-progress";
+/// 单次请求的超时，超时后换下一条线路
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// 整个预下载的时间上限，超时的集合交给核心启动后自行下载
+const BUDGET: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    Proxy,
+    Rule,
+}
+
+impl ProviderKind {
+    fn key(self) -> &'static str {
+        match self {
+            ProviderKind::Proxy => "proxy-providers",
+            ProviderKind::Rule => "rule-providers",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderFile {
+    pub kind: ProviderKind,
+    pub name: String,
+    pub url: String,
+    /// 已拼接核心工作目录的绝对路径
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(tag = "status", content = "message", rename_all = "snake_case")]
+pub enum PrefetchStatus {
+    Downloading,
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct PrefetchProgress {
+    pub kind: ProviderKind,
+    pub name: String,
+    pub status: PrefetchStatus,
+}
+
+/// 核心只允许工作目录内的路径，绝对路径与 `..` 一律跳过
+fn resolve_path(home: &Path, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        .then(|| home.join(path))
+}
+
+/// 配置中本地缓存不存在的 `type: http` 集合
+/// 未设置 `path` 的集合由核心决定缓存位置，无法预先下载
+pub fn missing_providers(config: &Mapping, home: &Path) -> Vec<ProviderFile> {
+    let mut missing = Vec::new();
+    for kind in [ProviderKind::Proxy, ProviderKind::Rule] {
+        let Some(providers) = config.get(kind.key()).and_then(|v| v.as_mapping()) else {
+            continue;
+        };
+        for (name, provider) in providers {
+            let (Some(name), Some(provider)) = (name.as_str(), provider.as_mapping()) else {
+                continue;
+            };
+            let field = |key: &str| provider.get(key).and_then(|v| v.as_str());
+            if field("type") != Some("http") {
+                continue;
+            }
+            let (Some(url), Some(path)) = (field("url"), field("path")) else {
+                continue;
+            };
+            let Some(path) = resolve_path(home, path) else {
+                tracing::warn!("skip provider `{name}` with path outside the core directory");
+                continue;
+            };
+            if !path.exists() {
+                missing.push(ProviderFile {
+                    kind,
+                    name: name.to_string(),
+                    url: url.to_string(),
+                    path,
+                });
+            }
+        }
+    }
+    missing
+}
+
+/// 依次尝试的代理，`None` 表示直连
+fn routes(core_running: bool) -> Vec<Option<String>> {
+    let mut routes = Vec::new();
+    if core_running {
+        // 核心仍在使用上一个可用的配置
+        routes.push(Some(format!("http://127.0.0.1:{}", sysopt::proxy_port())));
+    }
+    if let Ok(p @ Sysproxy { enable: true, .. }) = Sysproxy::get_system_proxy() {
+        let proxy = Some(format!("http://{}:{}", p.host, p.port));
+        if !routes.contains(&proxy) {
+            routes.push(proxy);
+        }
+    }
+    routes.push(None);
+    routes
+}
+
+fn build_client(proxy: Option<&str>) -> Result<reqwest::Client> {
+    let mut builder = reqwest::ClientBuilder::new()
+        .use_rustls_tls()
+        .no_proxy()
+        .with_custom_ca()
+        .timeout(TIMEOUT)
+        .user_agent(format!("clash-nyanpasu/v{}", dirs::APP_VERSION));
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    Ok(builder.build()?)
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> reqwest::Result<bytes::Bytes> {
+    let perform = || async { client.get(url).send().await?.error_for_status() };
+    perform
+        .retry(
+            backon::ExponentialBuilder::default()
+                .with_min_delay(Duration::from_millis(200))
+                .with_max_times(2),
+        )
+        // 4xx 重试也不会成功
+        .when(|e| !e.status().is_some_and(|status| status.is_client_error()))
+        .await?
+        .bytes()
+        .await
+}
+
+async fn download(routes: &[Option<String>], provider: &ProviderFile) -> Result<()> {
+    let mut last_err = None;
+    for proxy in routes {
+        let content =
+            async { anyhow::Ok(fetch(&build_client(proxy.as_deref())?, &provider.url).await?) }
+                .await;
+        let content = match content {
+            Ok(content) if !content.is_empty() => content,
+            Ok(_) => {
+                last_err = Some(anyhow::anyhow!("empty response"));
+                continue;
+            }
+            Err(e) => {
+                tracing::debug!(
+                    "failed to fetch provider `{}` via {}: {e:?}",
+                    provider.name,
+                    proxy.as_deref().unwrap_or("direct")
+                );
+                last_err = Some(e);
+                continue;
+            }
+        };
+        if let Some(parent) = provider.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // 先写入临时文件，避免核心读到不完整的内容
+        let tmp = provider.path.with_extension("prefetch");
+        tokio::fs::write(&tmp, &content)
+            .await
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &provider.path).await?;
+        return Ok(());
+    }
+    match last_err {
+        Some(e) => Err(e),
+        None => bail!("no route available"),
+    }
+}
+
+/// 在 `budget` 内并发下载所有缺失的集合，返回下载失败或超时的集合名称
+pub async fn prefetch(
+    providers: &[ProviderFile],
+    routes: &[Option<String>],
+    budget: Duration,
+    on_progress: impl Fn(PrefetchProgress),
+) -> Vec<String> {
+    let deadline = tokio::time::Instant::now() + budget;
+    let progress = |provider: &ProviderFile, status| {
+        on_progress(PrefetchProgress {
+            kind: provider.kind,
+            name: provider.name.clone(),
+            status,
+        })
+    };
+    let results = futures::future::join_all(providers.iter().map(|provider| async {
+        progress(provider, PrefetchStatus::Downloading);
+        let result = tokio::time::timeout_at(deadline, download(routes, provider))
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {budget:?}")));
+        match &result {
+            Ok(()) => progress(provider, PrefetchStatus::Done),
+            Err(e) => {
+                tracing::warn!("failed to prefetch provider `{}`: {e:?}", provider.name);
+                progress(provider, PrefetchStatus::Failed(format!("{e:#}")));
+            }
+        }
+        result.is_ok()
+    }))
+    .await;
+    providers
+        .iter()
+        .zip(results)
+        .filter(|(_, ok)| !ok)
+        .map(|(provider, _)| provider.name.clone())
+        .collect()
+}
+
+/// 在激活流程中调用，失败不影响核心启动，只在激活报告中记录缺失的集合
+pub async fn prefetch_missing_providers(core_running: bool) {
+    let providers = {
+        let Some(config) = Config::runtime().latest().config.clone() else {
+            return;
+        };
+        match dirs::app_data_dir() {
+            Ok(home) => missing_providers(&config, &home),
+            Err(e) => {
+                log::error!(target: "app", "failed to get core directory: {e:?}");
+                return;
+            }
+        }
+    };
+    if providers.is_empty() {
+        return;
+    }
+    let timer = activation::StageTimer::start();
+    let failed = prefetch(&providers, &routes(core_running), BUDGET, |progress| {
+        log_err!(Handle::emit(PROGRESS_EVENT, progress));
+    })
+    .await;
+    timer.finish(
+        "provider prefetch",
+        (!failed.is_empty()).then(|| format!("missing providers: {}", failed.join(", "))),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::StatusCode, routing::get};
+    use parking_lot::Mutex;
+
+    async fn serve() -> String {
+        let app = Router::new()
+            .route("/ok.yaml", get(|| async { "proxies: []\n" }))
+            .route("/blocked.yaml", get(|| async { StatusCode::FORBIDDEN }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    fn config(base: &str) -> Mapping {
+        serde_yaml::from_str(&format!(
+            r#"
+proxy-providers:
+  ok:
+    type: http
+    url: {base}/ok.yaml
+    path: ./proxies/ok.yaml
+  local:
+    type: file
+    path: ./proxies/local.yaml
+rule-providers:
+  blocked:
+    type: http
+    url: {base}/blocked.yaml
+    path: ./rules/blocked.yaml
+  escape:
+    type: http
+    url: {base}/ok.yaml
+    path: ../escape.yaml
+"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let home = Path::new("/home");
+        assert_eq!(
+            resolve_path(home, "./rules/a.yaml"),
+            Some(home.join("./rules/a.yaml"))
+        );
+        assert!(resolve_path(home, "../a.yaml").is_none());
+        assert!(resolve_path(home, "/etc/a.yaml").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_degrades_on_failure() {
+        let base = serve().await;
+        let home = tempfile::tempdir().unwrap();
+        let providers = missing_providers(&config(&base), home.path());
+        let names = providers
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["ok", "blocked"]);
+
+        let events = Mutex::new(Vec::new());
+        let failed = prefetch(&providers, &[None], BUDGET, |progress| {
+            events.lock().push(progress);
+        })
+        .await;
+        assert_eq!(failed, ["blocked"]);
+        assert_eq!(
+            std::fs::read_to_string(home.path().join("proxies/ok.yaml")).unwrap(),
+            "proxies: []\n"
+        );
+        assert!(!home.path().join("rules/blocked.yaml").exists());
+
+        let events = events.into_inner();
+        assert_eq!(events.len(), 4);
+        assert!(
+            events
+                .iter()
+                .any(|e| e.name == "ok" && e.status == PrefetchStatus::Done)
+        );
+        assert!(events.iter().any(|e| e.name == "blocked"
+            && e.kind == ProviderKind::Rule
+            && matches!(e.status, PrefetchStatus::Failed(_))));
+
+        // 已下载的集合不再重复下载
+        let providers = missing_providers(&config(&base), home.path());
+        assert_eq!(providers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_respects_budget() {
+        // 接受连接但不响应
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut conns = Vec::new();
+            while let Ok((conn, _)) = listener.accept().await {
+                conns.push(conn);
+            }
+        });
+        let home = tempfile::tempdir().unwrap();
+        let providers = vec![ProviderFile {
+            kind: ProviderKind::Proxy,
+            name: "stalled".into(),
+            url: format!("http://{addr}/stalled.yaml"),
+            path: home.path().join("stalled.yaml"),
+        }];
+        let started = std::time::Instant::now();
+        let failed = prefetch(&providers, &[None], Duration::from_millis(300), |_| {}).await;
+        assert_eq!(failed, ["stalled"]);
+        assert!(started.elapsed() < TIMEOUT);
+    }
+}
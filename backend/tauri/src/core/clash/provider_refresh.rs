@@ -0,0 +1,228 @@
+//! 按 `interval` 主动刷新 `type: http` 的代理集合
+//! 部分核心不能可靠地按间隔自动更新，每分钟检查一次，到期后调用 `PUT /providers/proxies/{name}`
+use super::api::{self, ProviderInfo};
+use crate::{config::Config, core::handle::Handle, log_err, utils::dirs};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use specta::Type;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+const REFRESHED_EVENT: &str = "proxy-provider-refreshed";
+
+/// 同一时间只执行一轮刷新
+static REFRESHING: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RefreshState {
+    /// 代理集合名称到最后刷新时间（unix 秒）
+    #[serde(default)]
+    last_refreshed: HashMap<String, i64>,
+    /// 加载后有修改，需要写回文件
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl RefreshState {
+    fn load() -> Self {
+        dirs::provider_refresh_state_path()
+            .ok()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn mark_refreshed(&mut self, name: String, now: i64) {
+        self.last_refreshed.insert(name, now);
+        self.dirty = true;
+    }
+
+    /// 没有修改时不写文件
+    fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        std::fs::write(
+            dirs::provider_refresh_state_path()?,
+            serde_json::to_vec(self)?,
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProxyProviderRefreshed {
+    pub name: String,
+    pub proxy_count: usize,
+}
+
+/// 运行时配置中设置了 `interval` 的 http 代理集合及其间隔（秒）
+fn http_provider_intervals(config: &Mapping) -> HashMap<String, u64> {
+    config
+        .get("proxy-providers")
+        .and_then(|providers| providers.as_mapping())
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, provider)| {
+            let provider = provider.as_mapping()?;
+            if provider.get("type").and_then(|t| t.as_str()) != Some("http") {
+                return None;
+            }
+            let interval = provider.get("interval")?.as_u64().filter(|i| *i > 0)?;
+            Some((name.as_str()?.to_string(), interval))
+        })
+        .collect()
+}
+
+/// 返回到期的代理集合，首次出现的集合从当前时间开始计时
+fn due_providers(
+    intervals: &HashMap<String, u64>,
+    state: &mut RefreshState,
+    now: i64,
+) -> Vec<String> {
+    let len = state.last_refreshed.len();
+    state
+        .last_refreshed
+        .retain(|name, _| intervals.contains_key(name));
+    state.dirty |= state.last_refreshed.len() != len;
+    let mut due = intervals
+        .iter()
+        .filter(|(name, interval)| {
+            let last = match state.last_refreshed.get(name.as_str()) {
+                Some(last) => *last,
+                None => {
+                    state.mark_refreshed(name.to_string(), now);
+                    now
+                }
+            };
+            now - last >= **interval as i64
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    due.sort();
+    due
+}
+
+fn runtime_intervals() -> HashMap<String, u64> {
+    Config::runtime()
+        .latest()
+        .config
+        .as_ref()
+        .map(http_provider_intervals)
+        .unwrap_or_default()
+}
+
+async fn refresh(name: &str) -> Result<usize> {
+    api::update_providers_proxies_group(name).await?;
+    let proxy_count = api::get_providers_proxies_group(name.to_string())
+        .await?
+        .proxies
+        .len();
+    log_err!(Handle::emit(
+        REFRESHED_EVENT,
+        ProxyProviderRefreshed {
+            name: name.to_string(),
+            proxy_count,
+        }
+    ));
+    Ok(proxy_count)
+}
+
+/// 由定时任务每分钟调用
+pub async fn refresh_due_providers() -> Result<()> {
+    let Ok(_guard) = REFRESHING.try_lock() else {
+        return Ok(());
+    };
+    let intervals = runtime_intervals();
+    let mut state = RefreshState::load();
+    let now = chrono::Utc::now().timestamp();
+    let due = due_providers(&intervals, &mut state, now);
+    for name in due {
+        match refresh(&name).await {
+            Ok(count) => {
+                tracing::info!("refreshed proxy provider `{name}`, {count} proxies");
+                state.mark_refreshed(name, now);
+            }
+            Err(e) => tracing::warn!("failed to refresh proxy provider `{name}`: {e:?}"),
+        }
+    }
+    state.save()
+}
+
+/// 立即刷新，并重新开始计时
+pub async fn force_refresh(name: &str) -> Result<usize> {
+    let proxy_count = refresh(name).await?;
+    let _guard = REFRESHING.lock().await;
+    let mut state = RefreshState::load();
+    state.mark_refreshed(name.to_string(), chrono::Utc::now().timestamp());
+    state.save()?;
+    Ok(proxy_count)
+}
+
+/// 核心返回的代理集合，附带配置的间隔与最后一次主动刷新的时间
+pub async fn list_proxy_providers() -> Result<Vec<ProviderInfo>> {
+    let intervals = runtime_intervals();
+    let state = RefreshState::load();
+    Ok(api::get_proxy_providers()
+        .await?
+        .into_iter()
+        .map(|mut info| {
+            info.interval = intervals.get(&info.name).copied();
+            info.last_refreshed_at = state.last_refreshed.get(&info.name).copied();
+            info
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due_providers() {
+        let config: Mapping = serde_yaml::from_str(
+            r#"
+proxy-providers:
+  hourly:
+    type: http
+    url: https://example.com/a.yaml
+    interval: 3600
+  daily:
+    type: http
+    url: https://example.com/b.yaml
+    interval: 86400
+  local:
+    type: file
+    path: ./c.yaml
+    interval: 60
+  manual:
+    type: http
+    url: https://example.com/d.yaml
+"#,
+        )
+        .unwrap();
+        let intervals = http_provider_intervals(&config);
+        assert_eq!(intervals.len(), 2);
+
+        let mut state = RefreshState::default();
+        state.last_refreshed.insert("removed".into(), 0);
+        // 首次出现时只记录时间
+        assert!(due_providers(&intervals, &mut state, 1_000).is_empty());
+        assert!(!state.last_refreshed.contains_key("removed"));
+        assert!(state.dirty);
+        // 没有到期也没有新增的集合时不需要写回
+        state.dirty = false;
+        assert!(due_providers(&intervals, &mut state, 1_060).is_empty());
+        assert!(!state.dirty);
+        assert_eq!(
+            due_providers(&intervals, &mut state, 1_000 + 3_600),
+            ["hourly"]
+        );
+        assert_eq!(
+            due_providers(&intervals, &mut state, 1_000 + 86_400),
+            ["daily", "hourly"]
+        );
+    }
+}
@@ -1,7 +1,11 @@
 /// This module is used to manage the proxies for the Tauri application.
 /// It is used to provide the unite interface between tray and frontend.
 /// TODO: add a diff algorithm to reduce the data transfer, and the rerendering of the tray menu.
-use super::{CLASH_API_DEFAULT_BACKOFF_STRATEGY, api};
+use super::{
+    CLASH_API_DEFAULT_BACKOFF_STRATEGY, api,
+    node_meta::{NodeMetadata, NodeMetadataCache},
+    proxy_search::{self, PinnedProxy},
+};
 use adler::adler32;
 use anyhow::Result;
 use backon::Retryable;
@@ -10,7 +14,11 @@ use log::warn;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use std::sync::{Arc, OnceLock};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, OnceLock},
+};
+use tauri::Manager;
 use tokio::{sync::broadcast, try_join};
 use tracing_attributes::instrument;
 
@@ -64,6 +72,15 @@ pub struct Proxies {
     pub groups: Vec<ProxyGroupItem>,
     pub records: IndexMap<String, api::ProxyItem>,
     pub proxies: Vec<api::ProxyItem>,
+    /// 从节点名称解析出的地区、倍率等信息，key 为节点名称
+    #[serde(default)]
+    pub metadata: IndexMap<String, NodeMetadata>,
+    /// 合并了用户标签的节点列表
+    #[serde(default)]
+    pub extended_proxies: Vec<ExtendedProxyInfo>,
+    /// 当前配置文件置顶的节点，已不存在的节点标记为 missing
+    #[serde(default)]
+    pub pinned: Vec<PinnedProxy>,
 }
 
 async fn fetch_proxies() -> Result<(api::ProxiesRes, api::ProvidersProxiesRes)> {
@@ -183,16 +200,61 @@ impl Proxies {
             item
         });
 
+        // 6. parse the node metadata from names
+        let metadata = NodeMetadataCache::global().collect(
+            inner_proxies
+                .keys()
+                .chain(provider_map.keys())
+                .map(String::as_str),
+        );
+
+        // 7. mark the pinned proxies and surface them first
+        let pinned = proxy_search::pinned_proxies().unwrap_or_else(|e| {
+            warn!(target: "clash::proxies", "failed to load pinned proxies: {e:?}");
+            Vec::new()
+        });
+        let mut global = global.unwrap_or_default();
+        let mut groups = groups;
+        let mut proxies = proxies;
+        for group in groups.iter_mut().chain(std::iter::once(&mut global)) {
+            mark_pinned(&mut group.all, &pinned);
+        }
+        mark_pinned(&mut proxies, &pinned);
+        let pinned = pinned
+            .into_iter()
+            .map(|name| PinnedProxy {
+                missing: !metadata.contains_key(&name),
+                name,
+            })
+            .collect();
+
+        // 8. merge the user defined tags
+        let extended_proxies = ProxyTagSystem::global().extend(&proxies);
+
         Ok(Proxies {
-            global: global.unwrap_or_default(),
+            global,
             direct,
             groups,
             records: inner_proxies,
             proxies,
+            metadata,
+            extended_proxies,
+            pinned,
         })
     }
 }
 
+/// 标记置顶节点并稳定地排到最前
+fn mark_pinned(items: &mut [api::ProxyItem], pinned: &[String]) {
+    if pinned.is_empty() {
+        return;
+    }
+    for item in items.iter_mut() {
+        item.pinned = pinned.contains(&item.name);
+    }
+    items.sort_by_key(|item| !item.pinned);
+}
+
 pub struct ProxiesGuard {
     inner: Proxies,
     checksum: Option<u32>,
@@ -246,15 +308,114 @@ impl ProxiesGuard {
         self.updated_at
     }
 
+    /// 缓存的有效期随轮询频率变化
     pub fn is_updated(&self) -> bool {
         let now = chrono::Utc::now().timestamp() as u64;
-        now - self.updated_at <= 3
+        let ttl = crate::core::activity::ActivityManager::global()
+            .profile()
+            .proxies_ttl();
+        now - self.updated_at <= ttl.as_secs()
+    }
+}
+
+/// 持久化的代理组选择，在核心重启后恢复
+const GROUP_SELECTIONS_STORAGE_KEY: &str = "proxy_group_selections";
+
+type GroupSelections = IndexMap<String, String>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GroupSelectionError {
+    #[error("proxy group `{0}` not found")]
+    GroupNotFound(String),
+    #[error("proxy `{proxy}` is not a member of group `{group}`")]
+    NotAMember { group: String, proxy: String },
+    /// 只有 select 类型的代理组可以手动切换
+    #[error("proxy group `{group}` is a `{kind}` group and can not be selected manually")]
+    NotSelectable { group: String, kind: String },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl Proxies {
+    /// 校验 group 是可以手动切换的代理组，且 proxy 属于 group
+    pub fn validate_selection(&self, group: &str, proxy: &str) -> Result<(), GroupSelectionError> {
+        let item = self
+            .records
+            .get(group)
+            .filter(|item| item.all.is_some())
+            .ok_or_else(|| GroupSelectionError::GroupNotFound(group.to_string()))?;
+        if !item.r#type.eq_ignore_ascii_case("Selector") {
+            return Err(GroupSelectionError::NotSelectable {
+                group: group.to_string(),
+                kind: item.r#type.clone(),
+            });
+        }
+        let members = item.all.as_deref().unwrap_or_default();
+        if !members.iter().any(|name| name == proxy) {
+            return Err(GroupSelectionError::NotAMember {
+                group: group.to_string(),
+                proxy: proxy.to_string(),
+            });
+        }
+        Ok(())
     }
 }
 
+fn load_group_selections() -> Result<GroupSelections> {
+    use crate::core::storage::{Storage, WebStorage};
+    let storage = crate::consts::app_handle().state::<Storage>();
+    Ok(storage
+        .get_item::<GroupSelections>(GROUP_SELECTIONS_STORAGE_KEY)?
+        .unwrap_or_default())
+}
+
+fn persist_group_selection(group: &str, proxy: &str) -> Result<()> {
+    use crate::core::storage::{Storage, WebStorage};
+    let mut selections = load_group_selections()?;
+    selections.insert(group.to_string(), proxy.to_string());
+    let storage = crate::consts::app_handle().state::<Storage>();
+    storage.set_item(GROUP_SELECTIONS_STORAGE_KEY, &selections)?;
+    Ok(())
+}
+
+/// 核心重启后恢复已持久化的代理组选择
+#[instrument]
+pub async fn restore_group_selections() -> Result<()> {
+    let selections = load_group_selections()?;
+    if selections.is_empty() {
+        return Ok(());
+    }
+    let proxies = Proxies::fetch().await?;
+    for (group, proxy) in selections.iter() {
+        if proxies.validate_selection(group, proxy).is_err() {
+            tracing::debug!("skip restoring stale selection {group} -> {proxy}");
+            continue;
+        }
+        let current = proxies
+            .records
+            .get(group)
+            .and_then(|item| item.now.as_ref());
+        if current.is_some_and(|now| now == proxy) {
+            continue;
+        }
+        if let Err(e) = api::update_proxy(group, proxy).await {
+            warn!(target: "clash::proxies", "failed to restore selection {group} -> {proxy}: {e:?}");
+        }
+    }
+    ProxiesGuard::global().update().await?;
+    Ok(())
+}
+
 pub trait ProxiesGuardExt {
     async fn update(&self) -> Result<()>;
     async fn select_proxy(&self, group: &str, name: &str) -> Result<()>;
+    /// 校验并切换代理组的选中节点，可选持久化
+    async fn set_group_selection(
+        &self,
+        group: &str,
+        proxy: &str,
+        persist: bool,
+    ) -> Result<(), GroupSelectionError>;
 }
 
 type ProxiesGuardSingleton = &'static Arc<RwLock<ProxiesGuard>>;
@@ -279,4 +440,236 @@ impl ProxiesGuardExt for ProxiesGuardSingleton {
         self.update().await?;
         Ok(())
     }
+
+    async fn set_group_selection(
+        &self,
+        group: &str,
+        proxy: &str,
+        persist: bool,
+    ) -> Result<(), GroupSelectionError> {
+        if !self.read().is_updated() {
+            self.update().await?;
+        }
+        self.read().inner().validate_selection(group, proxy)?;
+        self.select_proxy(group, proxy).await?;
+        if persist {
+            persist_group_selection(group, proxy)?;
+        }
+        Ok(())
+    }
+}
+
+/// 用户为节点添加的标签与备注
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, Type)]
+pub struct ProxyTag {
+    pub proxy_name: String,
+    pub tags: Vec<String>,
+    pub color: Option<String>,
+    pub note: Option<String>,
+}
+
+impl ProxyTag {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.color.is_none() && self.note.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+pub struct ExtendedProxyInfo {
+    #[serde(flatten)]
+    pub proxy: api::ProxyItem,
+    pub tags: Vec<String>,
+    pub color: Option<String>,
+    pub note: Option<String>,
+}
+
+/// 节点标签，持久化到 `{data_dir}/proxy_tags.json`
+#[derive(Default)]
+pub struct ProxyTagSystem {
+    tags: RwLock<HashMap<String, ProxyTag>>,
+}
+
+impl ProxyTagSystem {
+    pub fn global() -> &'static ProxyTagSystem {
+        static TAGS: OnceLock<ProxyTagSystem> = OnceLock::new();
+        TAGS.get_or_init(|| {
+            let tags = Self::load().unwrap_or_else(|e| {
+                warn!(target: "clash::proxies", "failed to load proxy tags: {e:?}");
+                HashMap::new()
+            });
+            ProxyTagSystem {
+                tags: RwLock::new(tags),
+            }
+        })
+    }
+
+    fn load() -> Result<HashMap<String, ProxyTag>> {
+        let path = crate::utils::dirs::proxy_tags_path()?;
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    fn save(&self) -> Result<()> {
+        let buf = serde_json::to_vec_pretty(&*self.tags.read())?;
+        std::fs::write(crate::utils::dirs::proxy_tags_path()?, buf)?;
+        Ok(())
+    }
+
+    /// 修改节点的标签信息，清空后移除该条目
+    fn update(&self, proxy_name: &str, f: impl FnOnce(&mut ProxyTag)) {
+        let mut tags = self.tags.write();
+        let entry = tags
+            .entry(proxy_name.to_string())
+            .or_insert_with(|| ProxyTag {
+                proxy_name: proxy_name.to_string(),
+                ..Default::default()
+            });
+        f(entry);
+        if entry.is_empty() {
+            tags.remove(proxy_name);
+        }
+    }
+
+    pub fn tag(&self, proxy_name: &str, tag: &str) -> Result<()> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            anyhow::bail!("tag must not be empty");
+        }
+        self.update(proxy_name, |entry| {
+            if !entry.tags.iter().any(|t| t == tag) {
+                entry.tags.push(tag.to_string());
+            }
+        });
+        self.save()
+    }
+
+    pub fn untag(&self, proxy_name: &str, tag: &str) -> Result<()> {
+        self.update(proxy_name, |entry| entry.tags.retain(|t| t != tag));
+        self.save()
+    }
+
+    /// 设置备注，空字符串表示清除
+    pub fn set_note(&self, proxy_name: &str, note: &str) -> Result<()> {
+        let note = note.trim();
+        self.update(proxy_name, |entry| {
+            entry.note = (!note.is_empty()).then(|| note.to_string());
+        });
+        self.save()
+    }
+
+    pub fn proxies_by_tag(&self, tag: &str) -> Vec<String> {
+        let mut names = self
+            .tags
+            .read()
+            .values()
+            .filter(|entry| entry.tags.iter().any(|t| t == tag))
+            .map(|entry| entry.proxy_name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn all_tags(&self) -> Vec<String> {
+        self.tags
+            .read()
+            .values()
+            .flat_map(|entry| entry.tags.iter().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn extend(&self, proxies: &[api::ProxyItem]) -> Vec<ExtendedProxyInfo> {
+        let tags = self.tags.read();
+        proxies
+            .iter()
+            .map(|proxy| {
+                let entry = tags.get(&proxy.name);
+                ExtendedProxyInfo {
+                    proxy: proxy.clone(),
+                    tags: entry.map(|e| e.tags.clone()).unwrap_or_default(),
+                    color: entry.and_then(|e| e.color.clone()),
+                    note: entry.and_then(|e| e.note.clone()),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_tag_entries_are_cleaned_up() {
+        let system = ProxyTagSystem::default();
+        system.update("HK 01", |e| e.tags.push("fast".into()));
+        system.update("JP 01", |e| e.tags.push("fast".into()));
+        system.update("JP 01", |e| e.note = Some("gaming".into()));
+        assert_eq!(system.proxies_by_tag("fast"), vec!["HK 01", "JP 01"]);
+
+        system.update("HK 01", |e| e.tags.retain(|t| t != "fast"));
+        assert!(!system.tags.read().contains_key("HK 01"));
+        system.update("JP 01", |e| e.tags.clear());
+        assert!(system.tags.read().contains_key("JP 01"));
+        assert!(system.all_tags().is_empty());
+    }
+
+    #[test]
+    fn test_pinned_proxies_are_surfaced_first() {
+        let mut items = ["HK 01", "JP 01", "US 01", "SG 01"]
+            .into_iter()
+            .map(|name| api::ProxyItem {
+                name: name.into(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        mark_pinned(&mut items, &["US 01".into(), "HK 01".into()]);
+        let names = items.iter().map(|i| i.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["HK 01", "US 01", "JP 01", "SG 01"]);
+        assert!(items[0].pinned && items[1].pinned && !items[2].pinned);
+    }
+
+    #[test]
+    fn test_validate_selection() {
+        let group = |name: &str, kind: &str| api::ProxyItem {
+            name: name.into(),
+            r#type: kind.into(),
+            all: Some(vec!["HK 01".into(), "JP 01".into()]),
+            ..Default::default()
+        };
+        let mut proxies = Proxies::default();
+        for item in [
+            group("Proxy", "Selector"),
+            group("Auto", "URLTest"),
+            api::ProxyItem {
+                name: "HK 01".into(),
+                r#type: "Shadowsocks".into(),
+                ..Default::default()
+            },
+        ] {
+            proxies.records.insert(item.name.clone(), item);
+        }
+
+        assert!(proxies.validate_selection("Proxy", "JP 01").is_ok());
+        assert!(matches!(
+            proxies.validate_selection("Proxy", "US 01"),
+            Err(GroupSelectionError::NotAMember { .. })
+        ));
+        assert!(matches!(
+            proxies.validate_selection("Auto", "JP 01"),
+            Err(GroupSelectionError::NotSelectable { kind, .. }) if kind == "URLTest"
+        ));
+        // 节点本身不是代理组
+        assert!(matches!(
+            proxies.validate_selection("HK 01", "JP 01"),
+            Err(GroupSelectionError::GroupNotFound(_))
+        ));
+        assert!(matches!(
+            proxies.validate_selection("Missing", "JP 01"),
+            Err(GroupSelectionError::GroupNotFound(_))
+        ));
+    }
 }
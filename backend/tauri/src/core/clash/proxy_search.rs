@@ -0,0 +1,454 @@
+//! 后端的节点搜索与置顶
+//! 搜索基于代理快照建立索引，快照更新后重建；置顶列表按配置文件持久化到 Storage
+use super::{
+    node_meta::NodeMetadata,
+    proxies::{Proxies, ProxiesGuard},
+};
+use anyhow::Result;
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::sync::Arc;
+use tauri::Manager;
+
+/// 置顶的节点，key 为配置文件 uid
+const PINNED_PROXIES_STORAGE_KEY: &str = "pinned_proxies";
+
+/// 未选中配置文件时使用的 key
+const DEFAULT_PIN_SCOPE: &str = "default";
+
+type PinnedProxies = IndexMap<String, Vec<String>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct PinnedProxy {
+    pub name: String,
+    /// 订阅更新后节点已不存在，仍保留在置顶列表中
+    pub missing: bool,
+}
+
+fn pin_scope() -> String {
+    crate::config::Config::profiles()
+        .latest()
+        .get_current()
+        .first()
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_PIN_SCOPE.to_string())
+}
+
+fn load_pinned_proxies() -> Result<PinnedProxies> {
+    use crate::core::storage::{Storage, WebStorage};
+    let storage = crate::consts::app_handle().state::<Storage>();
+    Ok(storage
+        .get_item::<PinnedProxies>(PINNED_PROXIES_STORAGE_KEY)?
+        .unwrap_or_default())
+}
+
+fn update_pinned_proxies(f: impl FnOnce(&mut Vec<String>)) -> Result<()> {
+    use crate::core::storage::{Storage, WebStorage};
+    let mut pinned = load_pinned_proxies()?;
+    let scope = pin_scope();
+    let names = pinned.entry(scope.clone()).or_default();
+    f(names);
+    if names.is_empty() {
+        pinned.shift_remove(&scope);
+    }
+    let storage = crate::consts::app_handle().state::<Storage>();
+    storage.set_item(PINNED_PROXIES_STORAGE_KEY, &pinned)?;
+    Ok(())
+}
+
+/// 当前配置文件的置顶节点，按置顶顺序排列
+pub fn pinned_proxies() -> Result<Vec<String>> {
+    Ok(load_pinned_proxies()?
+        .shift_remove(&pin_scope())
+        .unwrap_or_default())
+}
+
+pub fn pin_proxy(name: &str) -> Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("proxy name must not be empty");
+    }
+    update_pinned_proxies(|names| {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    })
+}
+
+pub fn unpin_proxy(name: &str) -> Result<()> {
+    update_pinned_proxies(|names| names.retain(|n| n != name))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ProxySearchMatch {
+    pub name: String,
+    pub score: u32,
+    pub pinned: bool,
+    /// 包含该节点的代理组
+    pub groups: Vec<String>,
+    pub metadata: NodeMetadata,
+}
+
+struct IndexEntry {
+    name: String,
+    lowercase: String,
+    initials: String,
+    /// 地区代码与标签，均为小写
+    keywords: Vec<String>,
+    groups: Vec<String>,
+    metadata: NodeMetadata,
+    pinned: bool,
+}
+
+/// 按快照预先计算小写名称与拼音首字母，避免每次搜索重复处理
+#[derive(Default)]
+pub struct ProxySearchIndex {
+    entries: Vec<IndexEntry>,
+}
+
+impl ProxySearchIndex {
+    pub fn build(proxies: &Proxies) -> Self {
+        let mut groups: FxHashMap<&str, Vec<String>> = FxHashMap::default();
+        for group in proxies.groups.iter() {
+            for item in group.all.iter() {
+                groups
+                    .entry(item.name.as_str())
+                    .or_default()
+                    .push(group.name.clone());
+            }
+        }
+        let pinned = proxies
+            .pinned
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        let entries = proxies
+            .metadata
+            .iter()
+            .filter(|(name, _)| {
+                // 代理组本身不参与搜索
+                !proxies
+                    .records
+                    .get(name.as_str())
+                    .is_some_and(|item| item.all.is_some())
+            })
+            .map(|(name, metadata)| {
+                let mut keywords = metadata
+                    .tags
+                    .iter()
+                    .map(|tag| tag.to_lowercase())
+                    .collect::<Vec<_>>();
+                if let Some(code) = &metadata.region_code {
+                    keywords.push(code.to_lowercase());
+                }
+                IndexEntry {
+                    name: name.clone(),
+                    lowercase: name.to_lowercase(),
+                    initials: pinyin_initials(name),
+                    keywords,
+                    groups: groups.remove(name.as_str()).unwrap_or_default(),
+                    metadata: metadata.clone(),
+                    pinned: pinned.contains(&name.as_str()),
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// 按得分降序返回，得分相同时置顶节点优先，其余保持快照顺序
+    pub fn search(&self, query: &str, limit: usize) -> Vec<ProxySearchMatch> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+        let mut matches = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| score(entry, &query).map(|score| (score, idx)))
+            .collect::<Vec<_>>();
+        matches.sort_by(|(a_score, a_idx), (b_score, b_idx)| {
+            let a_pinned = self.entries[*a_idx].pinned;
+            let b_pinned = self.entries[*b_idx].pinned;
+            b_score
+                .cmp(a_score)
+                .then(b_pinned.cmp(&a_pinned))
+                .then(a_idx.cmp(b_idx))
+        });
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(score, idx)| {
+                let entry = &self.entries[idx];
+                ProxySearchMatch {
+                    name: entry.name.clone(),
+                    score,
+                    pinned: entry.pinned,
+                    groups: entry.groups.clone(),
+                    metadata: entry.metadata.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+fn score(entry: &IndexEntry, query: &str) -> Option<u32> {
+    if entry.lowercase == query {
+        return Some(1000);
+    }
+    if entry.lowercase.starts_with(query) {
+        return Some(900);
+    }
+    if let Some(pos) = entry.lowercase.find(query) {
+        // 越靠前的匹配得分越高
+        return Some(800 - pos.min(100) as u32);
+    }
+    if entry.keywords.iter().any(|keyword| keyword == query) {
+        return Some(600);
+    }
+    if let Some(pos) = entry.initials.find(query) {
+        return Some(500 - pos.min(100) as u32);
+    }
+    fuzzy_score(&entry.lowercase, query)
+}
+
+/// 子序列匹配，连续命中的字符越多得分越高
+fn fuzzy_score(haystack: &str, query: &str) -> Option<u32> {
+    let mut haystack = haystack.chars();
+    let mut score = 100u32;
+    let mut consecutive = 0u32;
+    for q in query.chars() {
+        let mut skipped = false;
+        loop {
+            match haystack.next() {
+                Some(c) if c == q => break,
+                Some(_) => skipped = true,
+                None => return None,
+            }
+        }
+        consecutive = if skipped { 0 } else { consecutive + 1 };
+        score += consecutive * 5;
+    }
+    Some(score.min(399))
+}
+
+/// 节点名称中常见汉字的拼音首字母，未收录的汉字忽略
+const PINYIN_INITIALS: &[(char, char)] = &[
+    ('澳', 'a'),
+    ('阪', 'b'),
+    ('倍', 'b'),
+    ('本', 'b'),
+    ('北', 'b'),
+    ('巴', 'b'),
+    ('城', 'c'),
+    ('大', 'd'),
+    ('德', 'd'),
+    ('东', 'd'),
+    ('度', 'd'),
+    ('电', 'd'),
+    ('俄', 'e'),
+    ('尔', 'e'),
+    ('耳', 'e'),
+    ('法', 'f'),
+    ('福', 'f'),
+    ('港', 'g'),
+    ('国', 'g'),
+    ('國', 'g'),
+    ('广', 'g'),
+    ('硅', 'g'),
+    ('韩', 'h'),
+    ('韓', 'h'),
+    ('荷', 'h'),
+    ('何', 'h'),
+    ('沪', 'h'),
+    ('加', 'j'),
+    ('家', 'j'),
+    ('京', 'j'),
+    ('矶', 'j'),
+    ('解', 'j'),
+    ('克', 'k'),
+    ('宽', 'k'),
+    ('拉', 'l'),
+    ('兰', 'l'),
+    ('蘭', 'l'),
+    ('伦', 'l'),
+    ('罗', 'l'),
+    ('羅', 'l'),
+    ('洛', 'l'),
+    ('率', 'l'),
+    ('流', 'l'),
+    ('利', 'l'),
+    ('黎', 'l'),
+    ('联', 'l'),
+    ('美', 'm'),
+    ('门', 'm'),
+    ('門', 'm'),
+    ('媒', 'm'),
+    ('拿', 'n'),
+    ('坡', 'p'),
+    ('其', 'q'),
+    ('日', 'r'),
+    ('圣', 's'),
+    ('首', 's'),
+    ('斯', 's'),
+    ('杉', 's'),
+    ('狮', 's'),
+    ('上', 's'),
+    ('深', 's'),
+    ('锁', 's'),
+    ('台', 't'),
+    ('臺', 't'),
+    ('土', 't'),
+    ('体', 't'),
+    ('湾', 'w'),
+    ('灣', 'w'),
+    ('香', 'x'),
+    ('新', 'x'),
+    ('线', 'x'),
+    ('西', 'x'),
+    ('戏', 'x'),
+    ('英', 'y'),
+    ('印', 'y'),
+    ('亚', 'y'),
+    ('游', 'y'),
+    ('移', 'y'),
+    ('洲', 'z'),
+    ('中', 'z'),
+    ('专', 'z'),
+    ('转', 'z'),
+];
+
+static PINYIN_TABLE: Lazy<FxHashMap<char, char>> =
+    Lazy::new(|| PINYIN_INITIALS.iter().copied().collect());
+
+/// 汉字转为拼音首字母，字母与数字保留并转为小写
+fn pinyin_initials(name: &str) -> String {
+    name.chars()
+        .filter_map(|c| {
+            if c.is_ascii_alphanumeric() {
+                Some(c.to_ascii_lowercase())
+            } else {
+                PINYIN_TABLE.get(&c).copied()
+            }
+        })
+        .collect()
+}
+
+static SEARCH_INDEX: Lazy<RwLock<(u64, Arc<ProxySearchIndex>)>> =
+    Lazy::new(|| RwLock::new((0, Arc::new(ProxySearchIndex::default()))));
+
+/// 获取当前快照的索引，快照更新后重建
+pub fn search_index() -> Arc<ProxySearchIndex> {
+    let guard = ProxiesGuard::global().read();
+    let updated_at = guard.updated_at();
+    {
+        let index = SEARCH_INDEX.read();
+        if index.0 == updated_at {
+            return index.1.clone();
+        }
+    }
+    let index = Arc::new(ProxySearchIndex::build(guard.inner()));
+    *SEARCH_INDEX.write() = (updated_at, index.clone());
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::{api::ProxyItem, proxies::ProxyGroupItem};
+
+    fn proxies(names: &[&str], pinned: &[&str]) -> Proxies {
+        let items = names
+            .iter()
+            .map(|name| ProxyItem {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let metadata = names
+            .iter()
+            .map(|name| (name.to_string(), NodeMetadata::default()))
+            .collect();
+        Proxies {
+            groups: vec![ProxyGroupItem {
+                name: "Proxy".into(),
+                all: items.clone(),
+                ..Default::default()
+            }],
+            records: items
+                .iter()
+                .map(|item| (item.name.clone(), item.clone()))
+                .collect(),
+            metadata,
+            pinned: pinned
+                .iter()
+                .map(|name| PinnedProxy {
+                    name: name.to_string(),
+                    missing: false,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn matched_names(matches: &[ProxySearchMatch]) -> Vec<&str> {
+        matches.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let mut snapshot = proxies(
+            &["香港 01", "HK IPLC 02", "美国 Hong 03", "日本 04"],
+            &["日本 04"],
+        );
+        snapshot.metadata["香港 01"].region_code = Some("HK".into());
+        let index = ProxySearchIndex::build(&snapshot);
+        // 名称匹配优先于地区代码
+        assert_eq!(
+            matched_names(&index.search("hk", 10)),
+            ["HK IPLC 02", "香港 01"]
+        );
+        // 拼音首字母
+        assert_eq!(matched_names(&index.search("xg", 10)), ["香港 01"]);
+        assert_eq!(matched_names(&index.search("rb", 10)), ["日本 04"]);
+        // 子序列
+        assert_eq!(matched_names(&index.search("hng", 10)), ["美国 Hong 03"]);
+        let matches = index.search("iplc", 10);
+        assert_eq!(matches[0].groups, ["Proxy"]);
+        assert!(index.search("日本", 1)[0].pinned);
+        assert!(index.search("  ", 10).is_empty());
+    }
+
+    #[test]
+    fn test_search_5000_names() {
+        let regions = ["香港", "HK", "日本", "JP", "美国", "US", "新加坡", "SG"];
+        let names = (0..5000)
+            .map(|i| {
+                format!(
+                    "{} {:04} IPLC x{}",
+                    regions[i % regions.len()],
+                    i,
+                    i % 3 + 1
+                )
+            })
+            .collect::<Vec<_>>();
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+        let index = ProxySearchIndex::build(&proxies(&names, &[]));
+
+        let started = std::time::Instant::now();
+        for query in ["xg", "jp 01", "4999", "iplc", "hkx"] {
+            std::hint::black_box(index.search(query, 50));
+        }
+        let elapsed = started.elapsed() / 5;
+        // 调试构建下留出余量
+        assert!(
+            elapsed < std::time::Duration::from_millis(if cfg!(debug_assertions) { 50 } else { 5 }),
+            "search took {elapsed:?}"
+        );
+        assert_eq!(matched_names(&index.search("4999", 1)), ["SG 4999 IPLC x2"]);
+    }
+}
@@ -0,0 +1,206 @@
+//! 核心 API 请求签名，是 nyanpasu 在 `Bearer` 鉴权之外的扩展
+//! 签名为 `hex(HMAC-SHA256(secret, "{METHOD}:{path}:{timestamp}"))`，path 不含查询参数，
+//! timestamp 为 unix 秒，分别放在 `X-Nyanpasu-Sig` 与 `X-Nyanpasu-Timestamp` 中
+//! 核心本身不校验签名，由外部 UI 代理拒绝未签名的跨站请求，防止本机网页借代理调用 API
+use crate::config::{ClashController, Config};
+use anyhow::{Result, bail};
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::Serialize;
+use sha2::Sha256;
+use specta::Type;
+
+pub const SIGNATURE_HEADER: &str = "x-nyanpasu-sig";
+pub const TIMESTAMP_HEADER: &str = "x-nyanpasu-timestamp";
+
+/// 允许的时间偏差（秒）
+const MAX_CLOCK_SKEW_SECS: i64 = 30;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(secret: &str, method: &str, path: &str, timestamp: i64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}:{path}:{timestamp}", method.to_ascii_uppercase()).as_bytes());
+    mac
+}
+
+pub fn sign(secret: &str, method: &str, path: &str, timestamp: i64) -> String {
+    hex::encode(mac(secret, method, path, timestamp).finalize().into_bytes())
+}
+
+/// 检查请求头中的签名与时间戳
+pub fn verify(secret: &str, method: &str, path: &str, headers: &HeaderMap, now: i64) -> bool {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let Some(timestamp) = header(TIMESTAMP_HEADER).and_then(|t| t.parse::<i64>().ok()) else {
+        return false;
+    };
+    if (now - timestamp).abs() > MAX_CLOCK_SKEW_SECS {
+        return false;
+    }
+    let Some(signature) = header(SIGNATURE_HEADER).and_then(|s| hex::decode(s).ok()) else {
+        return false;
+    };
+    mac(secret, method, path, timestamp)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+pub fn enabled() -> bool {
+    Config::verge()
+        .latest()
+        .use_api_request_signing
+        .unwrap_or_default()
+}
+
+/// 开启签名且设置了 secret 时附加签名头
+pub fn sign_headers(headers: &mut HeaderMap, method: &str, path: &str) {
+    if !enabled() {
+        return;
+    }
+    let Some(secret) = ClashController::current().secret else {
+        return;
+    };
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign(&secret, method, path, timestamp);
+    headers.insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+    headers.insert(
+        SIGNATURE_HEADER,
+        HeaderValue::from_str(&signature).expect("hex is a valid header value"),
+    );
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct RequestSignature {
+    /// 放在 `X-Nyanpasu-Sig` 中
+    pub signature: String,
+    /// 签名所用的 unix 秒，原样放在 `X-Nyanpasu-Timestamp` 中
+    pub timestamp: i64,
+}
+
+/// 使用当前的 secret 与时间签名，供前端直接请求核心时使用
+pub fn current_signature(method: &str, path: &str) -> Result<RequestSignature> {
+    let Some(secret) = ClashController::current().secret else {
+        bail!("clash secret is not set");
+    };
+    let timestamp = chrono::Utc::now().timestamp();
+    Ok(RequestSignature {
+        signature: sign(&secret, method, path, timestamp),
+        timestamp,
+    })
+}
+
+/// 本机访问外部 UI 代理时可能使用的 Host
+fn loopback_hosts(port: u16) -> [String; 3] {
+    [
+        format!("127.0.0.1:{port}"),
+        format!("localhost:{port}"),
+        format!("[::1]:{port}"),
+    ]
+}
+
+/// 浏览器发出的跨站请求，非浏览器客户端不受 CSRF 影响
+/// Host 不是本机的代理地址时（例如 DNS 重绑定）一律视为跨站，
+/// 没有 Origin 的请求只有在 Host 为本机代理地址时才视为同站
+pub fn is_cross_site(headers: &HeaderMap, port: u16) -> bool {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v: &HeaderValue| v.to_str().ok())
+    };
+    let hosts = loopback_hosts(port);
+    if !header("host").is_some_and(|host| hosts.iter().any(|h| h.eq_ignore_ascii_case(host))) {
+        return true;
+    }
+    if let Some(site) = header("sec-fetch-site")
+        && !matches!(site, "same-origin" | "none")
+    {
+        return true;
+    }
+    header("origin").is_some_and(|origin| {
+        !hosts
+            .iter()
+            .any(|host| origin.eq_ignore_ascii_case(&format!("http://{host}")))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test case 2
+        let mut mac = HmacSha256::new_from_slice(b"Jefe").unwrap();
+        mac.update(b"what do ya want for nothing?");
+        assert_eq!(
+            hex::encode(mac.finalize().into_bytes()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let signature = sign("secret", "put", "/configs", 1_000);
+        assert_eq!(signature, sign("secret", "PUT", "/configs", 1_000));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from(1_000));
+        headers.insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        assert!(verify("secret", "PUT", "/configs", &headers, 1_010));
+        assert!(!verify("secret", "PUT", "/configs", &headers, 1_031));
+        assert!(!verify("secret", "PATCH", "/configs", &headers, 1_000));
+        assert!(!verify("secret", "PUT", "/proxies", &headers, 1_000));
+        assert!(!verify("other", "PUT", "/configs", &headers, 1_000));
+
+        headers.remove(TIMESTAMP_HEADER);
+        assert!(!verify("secret", "PUT", "/configs", &headers, 1_000));
+    }
+
+    #[test]
+    fn test_is_cross_site() {
+        let port = 3000;
+        let host = "127.0.0.1:3000";
+        let origin = "http://127.0.0.1:3000";
+        let headers = |pairs: &[(&'static str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+                .collect::<HeaderMap>()
+        };
+        assert!(!is_cross_site(&headers(&[("host", host)]), port));
+        assert!(!is_cross_site(
+            &headers(&[
+                ("host", "localhost:3000"),
+                ("origin", "http://localhost:3000")
+            ]),
+            port
+        ));
+        assert!(!is_cross_site(
+            &headers(&[("host", host), ("origin", origin)]),
+            port
+        ));
+        assert!(!is_cross_site(
+            &headers(&[("host", host), ("sec-fetch-site", "same-origin")]),
+            port
+        ));
+        assert!(is_cross_site(
+            &headers(&[("host", host), ("origin", "https://evil.example")]),
+            port
+        ));
+        assert!(is_cross_site(
+            &headers(&[
+                ("host", host),
+                ("sec-fetch-site", "cross-site"),
+                ("origin", origin)
+            ]),
+            port
+        ));
+        // 缺少 Origin 时依赖 Host，DNS 重绑定的请求 Host 为攻击者的域名
+        assert!(is_cross_site(&headers(&[]), port));
+        assert!(is_cross_site(
+            &headers(&[("host", "evil.example:3000"), ("sec-fetch-site", "none")]),
+            port
+        ));
+    }
+}
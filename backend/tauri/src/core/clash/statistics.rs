@@ -0,0 +1,318 @@
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// 原始 1 秒采样保留 5 分钟
+const RAW_RETENTION_SECS: u64 = 5 * 60;
+/// 降采样 bucket 的跨度
+const BUCKET_SECS: u64 = 10;
+/// 降采样 bucket 保留 1 小时
+const BUCKET_RETENTION_SECS: u64 = 60 * 60;
+/// 两次采样间隔超过该值（乘以采样间隔）时视为断档
+const GAP_THRESHOLD_SECS: u64 = 3;
+/// 数量上限，避免时间戳异常时内存无限增长
+const RAW_CAPACITY: usize = (RAW_RETENTION_SECS * 2) as usize;
+const BUCKET_CAPACITY: usize = (BUCKET_RETENTION_SECS / BUCKET_SECS * 2) as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficResolution {
+    /// 原始 1 秒采样，最多 5 分钟
+    Second,
+    /// 10 秒降采样，最多 1 小时
+    TenSeconds,
+}
+
+impl TrafficResolution {
+    pub fn secs(self) -> u64 {
+        match self {
+            TrafficResolution::Second => 1,
+            TrafficResolution::TenSeconds => BUCKET_SECS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficPoint {
+    /// unix 时间戳（秒），降采样时为 bucket 起点
+    pub timestamp: u64,
+    pub download_avg: u64,
+    pub download_max: u64,
+    pub upload_avg: u64,
+    pub upload_max: u64,
+    /// 此处没有数据（如核心重启），不应插值
+    pub gap: bool,
+}
+
+impl TrafficPoint {
+    fn sample(timestamp: u64, download: u64, upload: u64) -> Self {
+        Self {
+            timestamp,
+            download_avg: download,
+            download_max: download,
+            upload_avg: upload,
+            upload_max: upload,
+            gap: false,
+        }
+    }
+
+    fn gap(timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            gap: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficSeries {
+    pub resolution_secs: u64,
+    pub points: Vec<TrafficPoint>,
+}
+
+/// 正在累积的 bucket
+#[derive(Debug, Clone, Copy)]
+struct PendingBucket {
+    start: u64,
+    count: u64,
+    download_sum: u64,
+    upload_sum: u64,
+    download_max: u64,
+    upload_max: u64,
+}
+
+impl PendingBucket {
+    fn new(start: u64) -> Self {
+        Self {
+            start,
+            count: 0,
+            download_sum: 0,
+            upload_sum: 0,
+            download_max: 0,
+            upload_max: 0,
+        }
+    }
+
+    fn add(&mut self, download: u64, upload: u64) {
+        self.count += 1;
+        self.download_sum = self.download_sum.saturating_add(download);
+        self.upload_sum = self.upload_sum.saturating_add(upload);
+        self.download_max = self.download_max.max(download);
+        self.upload_max = self.upload_max.max(upload);
+    }
+
+    fn to_point(self) -> TrafficPoint {
+        let count = self.count.max(1);
+        TrafficPoint {
+            timestamp: self.start,
+            download_avg: self.download_sum / count,
+            download_max: self.download_max,
+            upload_avg: self.upload_sum / count,
+            upload_max: self.upload_max,
+            gap: false,
+        }
+    }
+}
+
+/// 流量历史环形缓冲
+/// 原始采样与降采样 bucket 均增量维护，内存有上限
+#[derive(Debug, Default)]
+pub struct TrafficHistory {
+    raw: VecDeque<TrafficPoint>,
+    buckets: VecDeque<TrafficPoint>,
+    pending: Option<PendingBucket>,
+    last_sample: Option<u64>,
+}
+
+impl TrafficHistory {
+    pub fn global() -> &'static Mutex<TrafficHistory> {
+        static HISTORY: Lazy<Mutex<TrafficHistory>> =
+            Lazy::new(|| Mutex::new(TrafficHistory::default()));
+        &HISTORY
+    }
+
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+
+    /// 记录一次采样（速度，单位 B/s）
+    pub fn push(&mut self, timestamp: u64, download: u64, upload: u64) {
+        if let Some(last) = self.last_sample {
+            if timestamp < last {
+                return;
+            }
+            if timestamp - last > GAP_THRESHOLD_SECS {
+                self.mark_gap(last + 1);
+            }
+        }
+        self.last_sample = Some(timestamp);
+        self.raw
+            .push_back(TrafficPoint::sample(timestamp, download, upload));
+
+        let start = timestamp - timestamp % BUCKET_SECS;
+        match self.pending.as_mut() {
+            Some(pending) if pending.start == start => pending.add(download, upload),
+            _ => {
+                self.flush_pending();
+                let mut pending = PendingBucket::new(start);
+                pending.add(download, upload);
+                self.pending = Some(pending);
+            }
+        }
+        self.evict(timestamp);
+    }
+
+    /// 标记断档（如核心重启、连接断开），断档前后的数据不会被连在一起
+    pub fn mark_gap(&mut self, timestamp: u64) {
+        self.flush_pending();
+        self.last_sample = None;
+        if !self.raw.back().is_some_and(|p| p.gap) {
+            self.raw.push_back(TrafficPoint::gap(timestamp));
+        }
+        if !self.buckets.back().is_some_and(|p| p.gap) {
+            // 断档落在下一个 bucket 上，避免与已落盘的 bucket 时间戳重叠
+            let start = timestamp.div_ceil(BUCKET_SECS) * BUCKET_SECS;
+            self.buckets.push_back(TrafficPoint::gap(start));
+        }
+        self.evict(timestamp);
+    }
+
+    /// 获取最近 `window_secs` 秒的数据
+    pub fn recent(
+        &self,
+        now: u64,
+        window_secs: u64,
+        resolution: TrafficResolution,
+    ) -> TrafficSeries {
+        let since = now.saturating_sub(window_secs);
+        let points = match resolution {
+            TrafficResolution::Second => self
+                .raw
+                .iter()
+                .filter(|p| p.timestamp >= since)
+                .copied()
+                .collect(),
+            TrafficResolution::TenSeconds => {
+                let since = since - since % BUCKET_SECS;
+                self.buckets
+                    .iter()
+                    .copied()
+                    .chain(self.pending.map(PendingBucket::to_point))
+                    .filter(|p| p.timestamp >= since)
+                    .collect()
+            }
+        };
+        TrafficSeries {
+            resolution_secs: resolution.secs(),
+            points,
+        }
+    }
+
+    fn flush_pending(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.buckets.push_back(pending.to_point());
+        }
+    }
+
+    fn evict(&mut self, now: u64) {
+        while self
+            .raw
+            .front()
+            .is_some_and(|p| p.timestamp + RAW_RETENTION_SECS <= now)
+            || self.raw.len() > RAW_CAPACITY
+        {
+            self.raw.pop_front();
+        }
+        while self
+            .buckets
+            .front()
+            .is_some_and(|p| p.timestamp + BUCKET_RETENTION_SECS <= now)
+            || self.buckets.len() > BUCKET_CAPACITY
+        {
+            self.buckets.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_avg_and_max() {
+        let mut history = TrafficHistory::default();
+        for (i, speed) in [10, 20, 30, 40].into_iter().enumerate() {
+            history.push(100 + i as u64, speed, speed * 2);
+        }
+        // 110 开始新的 bucket，100 的 bucket 被落盘
+        history.push(110, 5, 5);
+        let series = history.recent(110, 60, TrafficResolution::TenSeconds);
+        assert_eq!(series.resolution_secs, 10);
+        assert_eq!(series.points.len(), 2);
+        let first = series.points[0];
+        assert_eq!(first.timestamp, 100);
+        assert_eq!(first.download_avg, 25);
+        assert_eq!(first.download_max, 40);
+        assert_eq!(first.upload_avg, 50);
+        assert_eq!(first.upload_max, 80);
+        // 未完成的 bucket 也会返回
+        assert_eq!(series.points[1].timestamp, 110);
+        assert_eq!(series.points[1].download_max, 5);
+    }
+
+    #[test]
+    fn test_gap_on_missing_samples() {
+        let mut history = TrafficHistory::default();
+        history.push(100, 1, 1);
+        history.push(101, 1, 1);
+        history.push(130, 2, 2);
+        let raw = history.recent(130, 60, TrafficResolution::Second).points;
+        assert_eq!(raw.len(), 4);
+        assert!(raw[2].gap);
+        assert_eq!(raw[2].timestamp, 102);
+        let buckets = history
+            .recent(130, 60, TrafficResolution::TenSeconds)
+            .points;
+        assert_eq!(
+            buckets.iter().map(|p| p.gap).collect::<Vec<_>>(),
+            vec![false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_explicit_gap_is_not_duplicated() {
+        let mut history = TrafficHistory::default();
+        history.push(100, 1, 1);
+        history.mark_gap(101);
+        history.mark_gap(102);
+        // 断档后的首个采样不应再产生新的断档
+        history.push(140, 1, 1);
+        let raw = history.recent(140, 60, TrafficResolution::Second).points;
+        assert_eq!(raw.iter().filter(|p| p.gap).count(), 1);
+    }
+
+    #[test]
+    fn test_retention_is_bounded() {
+        let mut history = TrafficHistory::default();
+        for ts in 0..(2 * BUCKET_RETENTION_SECS) {
+            history.push(ts, ts, ts);
+        }
+        assert!(history.raw.len() as u64 <= RAW_RETENTION_SECS);
+        assert!(history.buckets.len() as u64 <= BUCKET_RETENTION_SECS / BUCKET_SECS);
+        let now = 2 * BUCKET_RETENTION_SECS - 1;
+        let raw = history.recent(now, 60, TrafficResolution::Second).points;
+        assert_eq!(raw.len(), 61);
+    }
+}
@@ -0,0 +1,36 @@
+//! 测试共用的连接数据
+
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+/// 按核心 `/connections` 的格式构造连接，`fields` 覆盖默认字段，`metadata` 按字段合并
+pub fn connection<T: DeserializeOwned>(id: &str, fields: Value) -> T {
+    let mut value = json!({
+        "id": id,
+        "metadata": { "host": "example.com", "destinationIP": "1.1.1.1" },
+        "upload": 0,
+        "download": 0,
+        "start": "2026-10-16T12:00:00Z",
+        "chains": ["DIRECT"],
+        "rule": "Match",
+        "rulePayload": "",
+    });
+    merge(&mut value, fields);
+    serde_json::from_value(value).unwrap()
+}
+
+fn merge(base: &mut Value, patch: Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
@@ -0,0 +1,299 @@
+//! 根据连接历史统计各策略与规则的使用情况
+//! 连接历史由 connections ws 更新，关闭的连接最多保留 `MAX_CONNECTION_HISTORY` 条
+use super::{
+    api::ConnectionItem, connection_details::RecentlyClosed, connection_log::ConnectionLogger,
+};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use specta::Type;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Instant,
+};
+
+const MAX_CONNECTION_HISTORY: usize = 10_000;
+
+/// 样本过少时不给出优化建议
+const MIN_CONNECTIONS_FOR_SUGGESTIONS: usize = 200;
+
+/// 无法按连接记录判断是否命中的规则类型
+const UNTRACKED_RULE_TYPES: &[&str] = &["match", "final", "and", "or", "not", "subrule"];
+
+#[derive(Debug, Default)]
+pub struct ConnectionHistory {
+    active: HashMap<String, ConnectionItem>,
+    closed: VecDeque<ConnectionItem>,
+    /// 刚关闭的连接，供查询详情
+    recently_closed: RecentlyClosed,
+}
+
+impl ConnectionHistory {
+    /// 使用 ws 推送的活跃连接更新，不再出现的连接移入历史
+    pub fn update(&mut self, connections: Vec<ConnectionItem>) {
+        let mut active = connections
+            .into_iter()
+            .map(|conn| (conn.id.clone(), conn))
+            .collect::<HashMap<_, _>>();
+        std::mem::swap(&mut self.active, &mut active);
+        active.retain(|id, _| !self.active.contains_key(id));
+        self.close(active);
+    }
+
+    /// 与核心断开时，活跃连接全部视为已关闭
+    pub fn flush(&mut self) {
+        let active = std::mem::take(&mut self.active);
+        self.close(active);
+    }
+
+    /// 按建立时间移入历史
+    fn close(&mut self, connections: HashMap<String, ConnectionItem>) {
+        let mut connections = connections.into_values().collect::<Vec<_>>();
+        connections.sort_by(|a, b| a.start.cmp(&b.start));
+        ConnectionLogger::global().log_closed(&connections);
+        let now = Instant::now();
+        for conn in connections {
+            if self.closed.len() == MAX_CONNECTION_HISTORY {
+                self.closed.pop_front();
+            }
+            self.recently_closed.insert(conn.clone(), now);
+            self.closed.push_back(conn);
+        }
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &ConnectionItem> {
+        self.active.values()
+    }
+
+    /// 按 id 查找活跃或刚关闭的连接，第二项表示是否已关闭
+    pub fn find(&self, id: &str) -> Option<(ConnectionItem, bool)> {
+        if let Some(conn) = self.active.get(id) {
+            return Some((conn.clone(), false));
+        }
+        self.recently_closed
+            .get(id, Instant::now())
+            .map(|conn| (conn.clone(), true))
+    }
+
+    /// 最近的 n 条连接，活跃连接在前
+    pub fn recent(&self, n: usize) -> Vec<ConnectionItem> {
+        let mut active = self.active.values().cloned().collect::<Vec<_>>();
+        active.sort_by(|a, b| b.start.cmp(&a.start));
+        active
+            .into_iter()
+            .chain(self.closed.iter().rev().cloned())
+            .take(n)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct PolicyAnalysis {
+    /// 策略（规则指向的代理组或节点）及连接数，按连接数降序
+    pub top_policies: Vec<(String, u32)>,
+    /// 规则及命中次数，按次数降序
+    pub top_rules: Vec<(String, u32)>,
+    /// 各策略的上传与下载总量
+    pub traffic_by_policy: HashMap<String, u64>,
+}
+
+/// 连接链的最后一项为规则指向的策略
+fn policy_of(conn: &ConnectionItem) -> &str {
+    conn.chains.last().map(String::as_str).unwrap_or("DIRECT")
+}
+
+pub(super) fn rule_of(conn: &ConnectionItem) -> String {
+    if conn.rule_payload.is_empty() {
+        conn.rule.clone()
+    } else {
+        format!("{}({})", conn.rule, conn.rule_payload)
+    }
+}
+
+fn sorted_counts(counts: HashMap<String, u32>) -> Vec<(String, u32)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// 统计前 n 条连接
+pub fn analyze_traffic_policies(connections: &[ConnectionItem], n: usize) -> PolicyAnalysis {
+    let mut policies = HashMap::new();
+    let mut rules = HashMap::new();
+    let mut traffic_by_policy = HashMap::new();
+    for conn in connections.iter().take(n) {
+        let policy = policy_of(conn);
+        *policies.entry(policy.to_string()).or_default() += 1;
+        *traffic_by_policy.entry(policy.to_string()).or_default() += conn.upload + conn.download;
+        if !conn.rule.is_empty() {
+            *rules.entry(rule_of(conn)).or_default() += 1;
+        }
+    }
+    PolicyAnalysis {
+        top_policies: sorted_counts(policies),
+        top_rules: sorted_counts(rules),
+        traffic_by_policy,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct RuleOptimizationSuggestion {
+    /// 规则在配置中的位置
+    pub index: usize,
+    pub rule: String,
+    pub reason: String,
+}
+
+/// 忽略大小写与分隔符，使 `DOMAIN-SUFFIX` 与核心返回的 `DomainSuffix` 一致
+fn normalize_rule_type(rule_type: &str) -> String {
+    rule_type
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// 找出连接历史中从未命中的规则，建议删除
+pub fn suggest_rule_optimizations(
+    rules: &[String],
+    connections: &[ConnectionItem],
+) -> Vec<RuleOptimizationSuggestion> {
+    if connections.len() < MIN_CONNECTIONS_FOR_SUGGESTIONS {
+        return Vec::new();
+    }
+    let matched = connections
+        .iter()
+        .map(|conn| {
+            (
+                normalize_rule_type(&conn.rule),
+                conn.rule_payload.to_lowercase(),
+            )
+        })
+        .collect::<HashSet<_>>();
+    rules
+        .iter()
+        .enumerate()
+        .filter_map(|(index, rule)| {
+            let mut parts = rule.split(',').map(str::trim);
+            let rule_type = normalize_rule_type(parts.next()?);
+            if UNTRACKED_RULE_TYPES.contains(&rule_type.as_str()) {
+                return None;
+            }
+            let payload = parts.next().unwrap_or_default().to_lowercase();
+            (!matched.contains(&(rule_type, payload))).then(|| RuleOptimizationSuggestion {
+                index,
+                rule: rule.clone(),
+                reason: format!(
+                    "never matched in the last {} connections, consider removing it",
+                    connections.len()
+                ),
+            })
+        })
+        .collect()
+}
+
+/// 运行时配置中的规则
+pub fn runtime_rules(config: Option<&Mapping>) -> Vec<String> {
+    config
+        .and_then(|config| config.get("rules"))
+        .and_then(|rules| rules.as_sequence())
+        .map(|rules| {
+            rules
+                .iter()
+                .filter_map(|rule| rule.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::test_utils::connection;
+
+    fn conn(id: &str, rule: &str, payload: &str, chains: &[&str], bytes: u64) -> ConnectionItem {
+        connection(
+            id,
+            serde_json::json!({
+                "upload": bytes,
+                "download": bytes,
+                "start": format!("2026-10-16T12:00:{:02}Z", id.len()),
+                "chains": chains,
+                "rule": rule,
+                "rulePayload": payload,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_analyze_traffic_policies() {
+        let connections = [
+            conn("1", "DomainSuffix", "google.com", &["HK 01", "Proxy"], 10),
+            conn("2", "DomainSuffix", "google.com", &["JP 01", "Proxy"], 20),
+            conn("3", "Match", "", &["DIRECT"], 5),
+            conn("4", "GeoIP", "CN", &["DIRECT"], 1),
+            conn("5", "GeoIP", "CN", &["DIRECT"], 1),
+        ];
+        let analysis = analyze_traffic_policies(&connections, 10);
+        assert_eq!(
+            analysis.top_policies,
+            [("DIRECT".to_string(), 3), ("Proxy".to_string(), 2)]
+        );
+        assert_eq!(
+            analysis.top_rules[0],
+            ("DomainSuffix(google.com)".into(), 2)
+        );
+        assert_eq!(analysis.traffic_by_policy["Proxy"], 60);
+        assert_eq!(analysis.traffic_by_policy["DIRECT"], 14);
+
+        let analysis = analyze_traffic_policies(&connections, 1);
+        assert_eq!(analysis.top_policies, [("Proxy".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_suggest_rule_optimizations() {
+        let rules = [
+            "DOMAIN-SUFFIX,google.com,Proxy",
+            "DOMAIN-SUFFIX,unused.example,Proxy",
+            "GEOIP,CN,DIRECT",
+            "AND,((NETWORK,UDP),(DST-PORT,443)),REJECT",
+            "MATCH,Proxy",
+        ]
+        .map(String::from);
+        let mut connections = vec![conn("1", "DomainSuffix", "google.com", &["Proxy"], 1)];
+        assert!(suggest_rule_optimizations(&rules, &connections).is_empty());
+
+        connections.extend(
+            (0..MIN_CONNECTIONS_FOR_SUGGESTIONS).map(|_| conn("2", "GeoIP", "CN", &["DIRECT"], 1)),
+        );
+        let suggestions = suggest_rule_optimizations(&rules, &connections);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].index, 1);
+    }
+
+    #[test]
+    fn test_connection_history() {
+        let mut history = ConnectionHistory::default();
+        history.update(vec![
+            conn("a", "Match", "", &["DIRECT"], 1),
+            conn("bb", "Match", "", &["DIRECT"], 1),
+        ]);
+        history.update(vec![conn("bb", "Match", "", &["DIRECT"], 2)]);
+        assert_eq!(history.closed.len(), 1);
+        let ids = history
+            .recent(10)
+            .into_iter()
+            .map(|conn| conn.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["bb", "a"]);
+
+        assert_eq!(history.find("bb").map(|(_, closed)| closed), Some(false));
+        assert_eq!(history.find("a").map(|(_, closed)| closed), Some(true));
+
+        history.flush();
+        assert!(history.active.is_empty());
+        assert_eq!(history.closed.len(), 2);
+        assert_eq!(history.recent(1)[0].id, "bb");
+        assert_eq!(history.find("bb").map(|(_, closed)| closed), Some(true));
+        assert!(history.find("ccc").is_none());
+    }
+}
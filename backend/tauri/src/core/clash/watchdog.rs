@@ -0,0 +1,254 @@
+//! 核心看门狗：核心意外退出时按退避策略有限次重启
+//! 用户停止、退出应用等主动停止不会触发重启
+use super::core::{CoreManager, RunType};
+use crate::core::handle::Handle;
+use nyanpasu_ipc::api::status::CoreState;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use specta::Type;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+const CORE_RESTARTED_EVENT: &str = "core-restarted";
+const CORE_RESTART_FAILED_EVENT: &str = "core-restart-failed";
+
+/// 连续崩溃时的最大重启次数
+const MAX_RESTARTS: u32 = 3;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// 重启后稳定运行超过该时长，再次崩溃时重新计数
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// 主动停止核心时置位，启动核心时清除
+static INTENTIONAL_STOP: AtomicBool = AtomicBool::new(false);
+/// 同一时间只运行一个重启流程
+static RESTARTING: AtomicBool = AtomicBool::new(false);
+static POLICY: Lazy<Mutex<RestartPolicy>> = Lazy::new(|| Mutex::new(RestartPolicy::default()));
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CoreRestarted {
+    /// 本轮崩溃后的第几次重启
+    pub attempt: u32,
+    /// 核心退出的原因
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CoreRestartFailed {
+    pub attempts: u32,
+    pub reason: String,
+    /// 最后一次重启失败的错误
+    pub error: Option<String>,
+}
+
+/// 用户、退出流程或更新等主动停止核心前调用
+pub fn mark_intentional_stop() {
+    INTENTIONAL_STOP.store(true, Ordering::Release);
+}
+
+/// 用户或设置变更启动核心时调用，看门狗自身的重启不调用
+pub(super) fn on_manual_start() {
+    INTENTIONAL_STOP.store(false, Ordering::Release);
+    POLICY.lock().reset();
+}
+
+fn is_intentional_stop() -> bool {
+    INTENTIONAL_STOP.load(Ordering::Acquire)
+}
+
+#[derive(Debug, Default)]
+struct RestartPolicy {
+    /// 本轮崩溃已尝试的重启次数
+    attempts: u32,
+    /// 最近一次成功重启的时间
+    last_restart: Option<Instant>,
+    /// 达到次数上限后不再重启，直到用户重新启动核心
+    gave_up: bool,
+}
+
+impl RestartPolicy {
+    /// 核心崩溃时调用，返回是否需要重启
+    /// 只有重启成功后稳定运行足够久才重新计数
+    fn on_crash(&mut self, now: Instant) -> bool {
+        if self.gave_up {
+            return false;
+        }
+        if self
+            .last_restart
+            .is_some_and(|at| now.duration_since(at) >= STABLE_UPTIME)
+        {
+            self.attempts = 0;
+            self.last_restart = None;
+        }
+        true
+    }
+
+    /// 下一次重启的序号与等待时间，超过次数上限返回 None
+    fn next_attempt(&mut self) -> Option<(u32, Duration)> {
+        if self.attempts >= MAX_RESTARTS {
+            self.gave_up = true;
+            return None;
+        }
+        let delay = BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(self.attempts))
+            .min(MAX_DELAY);
+        self.attempts += 1;
+        Some((self.attempts, delay))
+    }
+
+    fn on_restarted(&mut self, now: Instant) {
+        self.last_restart = Some(now);
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// 核心进程退出且不是由 `Instance::stop` 停止时调用
+pub(super) fn on_unexpected_exit(reason: String) {
+    if is_intentional_stop() {
+        tracing::info!("core exited after an intentional stop, skip restarting: {reason}");
+        return;
+    }
+    if RESTARTING.swap(true, Ordering::AcqRel) {
+        tracing::debug!("core restart already in progress, ignore exit: {reason}");
+        return;
+    }
+    if !POLICY.lock().on_crash(Instant::now()) {
+        tracing::debug!("core restart was given up, wait for a manual start: {reason}");
+        RESTARTING.store(false, Ordering::Release);
+        return;
+    }
+    // run_core 的 future 不是 Send，在单独的线程中执行
+    std::thread::spawn(move || {
+        nyanpasu_utils::runtime::block_on(restart_loop(reason));
+        RESTARTING.store(false, Ordering::Release);
+    });
+}
+
+/// 服务模式下核心由服务托管，不会产生子进程退出事件，由服务健康检查调用
+pub(crate) async fn check_service_core() {
+    if is_intentional_stop() || RESTARTING.load(Ordering::Acquire) || POLICY.lock().gave_up {
+        return;
+    }
+    if CoreManager::global().run_type() != Some(RunType::Service) {
+        return;
+    }
+    let (state, ..) = CoreManager::global().status().await;
+    if matches!(state.as_ref(), CoreState::Stopped(_)) {
+        on_unexpected_exit("core stopped in service".to_string());
+    }
+}
+
+async fn restart_loop(reason: String) {
+    let mut last_error = None;
+    loop {
+        let next = POLICY.lock().next_attempt();
+        let Some((attempt, delay)) = next else {
+            break;
+        };
+        tracing::warn!(
+            "core exited unexpectedly ({reason}), restarting in {delay:?} (attempt {attempt}/{MAX_RESTARTS})"
+        );
+        tokio::time::sleep(delay).await;
+        // 等待期间用户停止或重新启动了核心，不再干预
+        if is_intentional_stop() {
+            tracing::info!("core was stopped intentionally, abort restarting");
+            return;
+        }
+        let (state, ..) = CoreManager::global().status().await;
+        if matches!(state.as_ref(), CoreState::Running) {
+            tracing::info!("core is already running, abort restarting");
+            return;
+        }
+        let result = CoreManager::global().recover_core().await;
+        // 重启期间用户停止了核心，启动完成后再停一次
+        if is_intentional_stop() {
+            tracing::info!("core was stopped intentionally while restarting, stop it again");
+            crate::log_err!(CoreManager::global().stop_core().await);
+            return;
+        }
+        match result {
+            Ok(()) => {
+                POLICY.lock().on_restarted(Instant::now());
+                tracing::info!("core restarted after unexpected exit (attempt {attempt})");
+                crate::log_err!(Handle::emit(
+                    CORE_RESTARTED_EVENT,
+                    CoreRestarted { attempt, reason }
+                ));
+                return;
+            }
+            Err(e) => {
+                tracing::error!("failed to restart core (attempt {attempt}): {e:?}");
+                last_error = Some(format!("{e:#}"));
+            }
+        }
+    }
+    let attempts = MAX_RESTARTS;
+    tracing::error!("giving up restarting core after {attempts} attempts");
+    crate::log_err!(Handle::emit(
+        CORE_RESTART_FAILED_EVENT,
+        CoreRestartFailed {
+            attempts,
+            reason,
+            error: last_error,
+        }
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let mut policy = RestartPolicy::default();
+        policy.on_crash(Instant::now());
+        assert_eq!(policy.next_attempt(), Some((1, Duration::from_secs(1))));
+        assert_eq!(policy.next_attempt(), Some((2, Duration::from_secs(2))));
+        assert_eq!(policy.next_attempt(), Some((3, Duration::from_secs(4))));
+        assert_eq!(policy.next_attempt(), None);
+    }
+
+    #[test]
+    fn test_crash_loop_keeps_counting() {
+        let now = Instant::now();
+        let mut policy = RestartPolicy::default();
+        policy.on_crash(now);
+        policy.next_attempt();
+        policy.on_restarted(now);
+        // 重启后很快又崩溃，继续累计次数
+        policy.on_crash(now + Duration::from_secs(5));
+        assert_eq!(policy.next_attempt().map(|(n, _)| n), Some(2));
+    }
+
+    #[test]
+    fn test_stable_run_resets_attempts() {
+        let now = Instant::now();
+        let mut policy = RestartPolicy::default();
+        policy.on_crash(now);
+        policy.next_attempt();
+        policy.next_attempt();
+        policy.on_restarted(now);
+        assert!(policy.on_crash(now + STABLE_UPTIME));
+        assert_eq!(policy.next_attempt(), Some((1, BASE_DELAY)));
+    }
+
+    #[test]
+    fn test_gave_up_until_manual_start() {
+        let now = Instant::now();
+        let mut policy = RestartPolicy::default();
+        assert!(policy.on_crash(now));
+        while policy.next_attempt().is_some() {}
+        // 所有重启都失败后，服务健康检查再次发现核心停止
+        assert!(!policy.on_crash(now + STABLE_UPTIME * 2));
+        assert_eq!(policy.next_attempt(), None);
+        policy.reset();
+        assert!(policy.on_crash(now + STABLE_UPTIME * 3));
+        assert_eq!(policy.next_attempt(), Some((1, BASE_DELAY)));
+    }
+}
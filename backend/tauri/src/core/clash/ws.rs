@@ -1,7 +1,9 @@
 use std::{
+    collections::HashMap,
     future::Future,
     ops::Deref,
     sync::{Arc, atomic::Ordering},
+    time::Instant,
 };
 
 use anyhow::Context;
@@ -12,20 +14,26 @@ use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tokio::{sync::mpsc::Receiver, task::JoinHandle};
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{client::IntoClientRequest, handshake::client::Request, protocol::Message},
-};
+use tokio_tungstenite::tungstenite::protocol::Message;
 
-use crate::log_err;
+use super::{
+    api::{self, ConnectionItem},
+    connection_session,
+    statistics::TrafficHistory,
+    traffic_policy::{ConnectionHistory, rule_of},
+};
+use crate::{
+    core::activity::{ActivityManager, PollingProfile},
+    log_err,
+};
 
 #[tracing::instrument]
 async fn connect_clash_server<T: serde::de::DeserializeOwned + Send + Sync + 'static>(
-    endpoint: Request,
+    path: &str,
 ) -> anyhow::Result<Receiver<T>> {
     // 添加连接超时：30 秒
-    let connect_fut = connect_async(endpoint);
-    let (stream, _) = tokio::time::timeout(std::time::Duration::from_secs(30), connect_fut)
+    let connect_fut = api::connect_clash_websocket(path);
+    let stream = tokio::time::timeout(std::time::Duration::from_secs(30), connect_fut)
         .await
         .context("WebSocket connection timeout after 30 seconds")??;
 
@@ -70,9 +78,28 @@ async fn connect_clash_server<T: serde::de::DeserializeOwned + Send + Sync + 'st
 struct ClashConnectionsMessage {
     download_total: u64,
     upload_total: u64,
+    /// 没有连接时核心返回 null
+    #[serde(default, deserialize_with = "deserialize_connections")]
+    connections: Vec<ConnectionItem>,
     // other fields are omitted
 }
 
+/// 跳过无法解析的连接，避免一条异常的连接导致整帧被丢弃
+fn deserialize_connections<'de, D>(deserializer: D) -> Result<Vec<ConnectionItem>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let items = Option::<Vec<serde_json::Value>>::deserialize(deserializer)?.unwrap_or_default();
+    Ok(items
+        .into_iter()
+        .filter_map(|item| {
+            serde_json::from_value(item)
+                .inspect_err(|e| tracing::warn!("skip malformed connection item: {e}"))
+                .ok()
+        })
+        .collect())
+}
+
 #[derive(Debug, Clone, Default, Copy, Type, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClashConnectionsInfo {
@@ -82,6 +109,72 @@ pub struct ClashConnectionsInfo {
     pub upload_speed: u64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupingKey {
+    ByDestinationHost,
+    /// 最终出站的节点
+    ByProxy,
+    ByRule,
+    BySourceProcess,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Type, Serialize, Deserialize)]
+pub struct ConnectionGroup {
+    pub key: String,
+    pub connection_count: u32,
+    pub total_upload: u64,
+    pub total_download: u64,
+    /// 连接 id
+    pub members: Vec<String>,
+}
+
+fn grouping_key_of(conn: &ConnectionItem, by: GroupingKey) -> String {
+    let metadata = &conn.metadata;
+    let key = match by {
+        GroupingKey::ByDestinationHost if metadata.host.is_empty() => {
+            metadata.destination_ip.clone()
+        }
+        GroupingKey::ByDestinationHost => metadata.host.clone(),
+        GroupingKey::ByProxy => conn.chains.first().cloned().unwrap_or_default(),
+        GroupingKey::ByRule => rule_of(conn),
+        GroupingKey::BySourceProcess => metadata.process.clone(),
+    };
+    if key.is_empty() {
+        "unknown".to_string()
+    } else {
+        key
+    }
+}
+
+/// 按目标、节点、规则或进程汇总连接，按总流量降序
+pub fn group_connections<'a>(
+    connections: impl IntoIterator<Item = &'a ConnectionItem>,
+    by: GroupingKey,
+) -> Vec<ConnectionGroup> {
+    let mut groups = HashMap::<String, ConnectionGroup>::new();
+    for conn in connections {
+        let key = grouping_key_of(conn, by);
+        let group = groups
+            .entry(key.clone())
+            .or_insert_with(|| ConnectionGroup {
+                key,
+                ..Default::default()
+            });
+        group.connection_count += 1;
+        group.total_upload += conn.upload;
+        group.total_download += conn.download;
+        group.members.push(conn.id.clone());
+    }
+    let mut groups = groups.into_values().collect::<Vec<_>>();
+    groups.sort_by(|a, b| {
+        (b.total_upload + b.total_download)
+            .cmp(&(a.total_upload + a.total_download))
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    groups
+}
+
 #[derive(Debug, Clone, Type, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "kind", content = "data")]
@@ -104,6 +197,13 @@ pub struct ClashConnectionsConnectorInner {
     connection_handler: Mutex<Option<JoinHandle<()>>>,
     broadcast_tx: tokio::sync::broadcast::Sender<ClashConnectionsConnectorEvent>,
     info: Mutex<ClashConnectionsInfo>,
+    history: Mutex<ConnectionHistory>,
+    /// 连接面板当前使用的分组方式，设置后每次更新时推送分组结果
+    grouping: Mutex<Option<GroupingKey>>,
+    /// 上一次处理的帧，用于按实际间隔计算速度
+    last_frame: Mutex<Option<Instant>>,
+    /// 上一次推送分组结果的时间，窗口不可见时据此降低推送频率
+    last_groups_frame: Mutex<Option<Instant>>,
 }
 
 // TODO:
@@ -127,35 +227,17 @@ impl ClashConnectionsConnector {
         }
     }
 
-    pub fn endpoint() -> anyhow::Result<Request> {
-        let (server, secret) = {
-            let info = crate::Config::clash().data().get_client_info();
-            (info.server, info.secret)
-        };
-        let url = format!("ws://{server}/connections");
-        let mut request = url
-            .into_client_request()
-            .context("failed to create client request")?;
-        if let Some(secret) = secret {
-            request.headers_mut().insert(
-                "Authorization",
-                format!("Bearer {secret}")
-                    .parse()
-                    .context("failed to create header value")?,
-            );
-        }
-        Ok(request)
-    }
-
     #[allow(clippy::manual_async_fn)]
     // FIXME: move to async fn while rust new solver got merged
     // ref: https://github.com/rust-lang/rust/issues/123072
     fn start_internal(&self) -> impl Future<Output = anyhow::Result<()>> + Send + use<'_> {
         async {
             self.dispatch_state_changed(ClashConnectionsConnectorState::Connecting);
-            let endpoint = Self::endpoint().context("failed to create endpoint")?;
-            log::debug!("connecting to clash connections ws server: {endpoint:?}");
-            let mut rx = connect_clash_server::<ClashConnectionsMessage>(endpoint).await?;
+            log::debug!(
+                "connecting to clash connections ws server: {:?}",
+                crate::config::ClashController::current().endpoint
+            );
+            let mut rx = connect_clash_server::<ClashConnectionsMessage>("/connections").await?;
             self.dispatch_state_changed(ClashConnectionsConnectorState::Connected);
             let this = self.clone();
             let mut connection_handler = self.connection_handler.lock();
@@ -212,6 +294,10 @@ impl ClashConnectionsConnectorInner {
             connection_handler: Mutex::new(None),
             broadcast_tx: tokio::sync::broadcast::channel(5).0,
             info: Mutex::new(ClashConnectionsInfo::default()),
+            history: Mutex::new(ConnectionHistory::default()),
+            grouping: Mutex::new(None),
+            last_frame: Mutex::new(None),
+            last_groups_frame: Mutex::new(None),
         }
     }
 
@@ -221,6 +307,20 @@ impl ClashConnectionsConnectorInner {
 
     fn dispatch_state_changed(&self, state: ClashConnectionsConnectorState) {
         self.state.store(state, Ordering::Release);
+        if state == ClashConnectionsConnectorState::Disconnected {
+            TrafficHistory::global()
+                .lock()
+                .mark_gap(TrafficHistory::now());
+            let (connections, upload, download) = self.session_snapshot();
+            self.history.lock().flush();
+            // 连接面板重新查询分组后才恢复推送
+            *self.grouping.lock() = None;
+            *self.last_groups_frame.lock() = None;
+            // 写入文件不占用连接记录的锁
+            tauri::async_runtime::spawn(async move {
+                log_err!(connection_session::persist_session(connections, upload, download).await);
+            });
+        }
         // SAFETY: the failures only there no active receivers,
         // so that the message will be dropped directly
         let _ = self
@@ -228,12 +328,72 @@ impl ClashConnectionsConnectorInner {
             .send(ClashConnectionsConnectorEvent::StateChanged(state));
     }
 
+    fn session_snapshot(&self) -> (Vec<ConnectionItem>, u64, u64) {
+        let connections = self.history.lock().active().cloned().collect();
+        let info = *self.info.lock();
+        (connections, info.upload_total, info.download_total)
+    }
+
+    /// 保存当前的活跃连接，退出时调用
+    pub async fn save_session(&self) -> anyhow::Result<()> {
+        let (connections, upload, download) = self.session_snapshot();
+        connection_session::persist_session(connections, upload, download).await
+    }
+
     /// Subscribe to the ClashConnectionsConnectorEvent
     pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<ClashConnectionsConnectorEvent> {
         self.broadcast_tx.subscribe()
     }
 
+    /// 最近的 n 条连接，活跃连接在前
+    pub fn recent_connections(&self, n: usize) -> Vec<ConnectionItem> {
+        self.history.lock().recent(n)
+    }
+
+    /// 按 id 查找活跃或刚关闭的连接，第二项表示是否已关闭
+    pub fn find_connection(&self, id: &str) -> Option<(ConnectionItem, bool)> {
+        self.history.lock().find(id)
+    }
+
+    /// 按指定方式汇总活跃连接，之后的更新会推送 `connections-groups-updated` 事件
+    pub fn connection_groups(&self, by: GroupingKey) -> Vec<ConnectionGroup> {
+        *self.grouping.lock() = Some(by);
+        group_connections(self.history.lock().active(), by)
+    }
+
+    /// 分组结果只供连接面板使用，窗口不可见时降低推送频率，暂停时不推送
+    fn should_emit_groups(&self, now: Instant) -> bool {
+        let activity = ActivityManager::global();
+        if activity.profile() == PollingProfile::Suspended {
+            return false;
+        }
+        let mut last = self.last_groups_frame.lock();
+        if let (Some(interval), Some(last)) = (activity.connections_interval(), *last)
+            && now.duration_since(last) < interval
+        {
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+
     fn update(&self, msg: ClashConnectionsMessage) {
+        let now = Instant::now();
+        let elapsed = self
+            .last_frame
+            .lock()
+            .replace(now)
+            .map(|last| now.duration_since(last));
+        // 按实际间隔折算速度
+        let elapsed_secs = elapsed.map_or(1, |elapsed| elapsed.as_secs().max(1));
+
+        if let Some(by) = *self.grouping.lock()
+            && self.should_emit_groups(now)
+        {
+            let groups = group_connections(&msg.connections, by);
+            let _ = crate::core::handle::Handle::emit("connections-groups-updated", groups);
+        }
+        self.history.lock().update(msg.connections);
         let mut info = self.info.lock();
         let previous_download_total =
             std::mem::replace(&mut info.download_total, msg.download_total);
@@ -241,11 +401,21 @@ impl ClashConnectionsConnectorInner {
         info.download_speed = msg
             .download_total
             .checked_sub(previous_download_total)
-            .unwrap_or_default();
+            .unwrap_or_default()
+            / elapsed_secs;
         info.upload_speed = msg
             .upload_total
             .checked_sub(previous_upload_total)
-            .unwrap_or_default();
+            .unwrap_or_default()
+            / elapsed_secs;
+        {
+            let mut history = TrafficHistory::global().lock();
+            history.push(
+                TrafficHistory::now(),
+                info.download_speed,
+                info.upload_speed,
+            );
+        }
 
         // SAFETY: the failures only there no active receivers,
         // so that the message will be dropped directly
@@ -280,3 +450,71 @@ impl Drop for ClashConnectionsConnectorInner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::test_utils::connection;
+
+    fn conn(id: &str, host: &str, process: &str, chains: &[&str], bytes: u64) -> ConnectionItem {
+        connection(
+            id,
+            serde_json::json!({
+                "metadata": { "host": host, "process": process },
+                "upload": bytes,
+                "download": bytes,
+                "chains": chains,
+                "rule": "DomainSuffix",
+                "rulePayload": host,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_group_connections() {
+        let connections = [
+            conn("1", "netflix.com", "chrome", &["HK 01", "Proxy"], 100),
+            conn("2", "netflix.com", "firefox", &["JP 01", "Proxy"], 50),
+            conn("3", "", "", &["DIRECT"], 10),
+        ];
+
+        let groups = group_connections(&connections, GroupingKey::ByDestinationHost);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, "netflix.com");
+        assert_eq!(groups[0].connection_count, 2);
+        assert_eq!(groups[0].total_upload, 150);
+        assert_eq!(groups[0].members, ["1", "2"]);
+        assert_eq!(groups[1].key, "1.1.1.1");
+
+        let groups = group_connections(&connections, GroupingKey::ByProxy);
+        let keys = groups.iter().map(|g| g.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, ["HK 01", "JP 01", "DIRECT"]);
+
+        let groups = group_connections(&connections, GroupingKey::BySourceProcess);
+        assert_eq!(groups.last().unwrap().key, "unknown");
+
+        let groups = group_connections(&connections, GroupingKey::ByRule);
+        assert_eq!(groups[0].key, "DomainSuffix(netflix.com)");
+    }
+
+    #[test]
+    fn test_skip_malformed_connections() {
+        let good = serde_json::to_value(conn("1", "a.com", "", &["DIRECT"], 1)).unwrap();
+        let msg: ClashConnectionsMessage = serde_json::from_value(serde_json::json!({
+            "downloadTotal": 1,
+            "uploadTotal": 2,
+            "connections": [good, { "id": 2, "metadata": null }],
+        }))
+        .unwrap();
+        assert_eq!(msg.connections.len(), 1);
+        assert_eq!(msg.connections[0].id, "1");
+
+        let msg: ClashConnectionsMessage = serde_json::from_value(serde_json::json!({
+            "downloadTotal": 0,
+            "uploadTotal": 0,
+            "connections": null,
+        }))
+        .unwrap();
+        assert!(msg.connections.is_empty());
+    }
+}
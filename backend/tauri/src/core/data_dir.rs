@@ -0,0 +1,667 @@
+//! 数据目录维护：统计并清理孤立的配置文件、过期的临时文件与多余的备份
+//! 扫描不跟随符号链接，单个条目的读取错误只记录不中断
+//! 数据目录同时是核心的工作目录，其中的资源文件无法判断归属，只清理临时文件
+use crate::{
+    config::{Config, MINIMAL_STARTUP_CONFIG, RUNTIME_CONFIG},
+    core::profile_activation,
+    utils::dirs,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// 保留最近的备份数量
+const BACKUP_RETENTION: usize = 5;
+/// 临时文件与孤立配置超过该时间未修改才视为残留，避免删除正在进行的下载与尚未登记的配置
+const TEMP_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+const TEMP_SUFFIXES: &[&str] = &[".part", ".tmp", ".prefetch"];
+const PROFILE_EXTENSIONS: &[&str] = &["yaml", "yml", "js", "lua"];
+/// 备份目录名末尾的时间，见 `feat::backup_settings`
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupCategory {
+    /// 配置列表中引用的配置文件，不会被清理
+    ActiveProfile,
+    /// 配置目录中未被任何配置项引用、超过一天未修改的配置文件
+    OrphanedProfile,
+    /// 超过一天的临时文件与未完成的下载
+    Temp,
+    /// 超出保留数量的旧备份
+    ExpiredBackup,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct DataDirEntry {
+    pub path: PathBuf,
+    pub category: CleanupCategory,
+    /// 目录为其中所有文件的大小之和
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct DataDirError {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CategorySummary {
+    pub category: CleanupCategory,
+    pub count: usize,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct DataDirReport {
+    pub entries: Vec<DataDirEntry>,
+    pub summary: Vec<CategorySummary>,
+    /// 无法读取的条目，不影响其他条目的扫描
+    pub errors: Vec<DataDirError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Type)]
+#[serde(tag = "status", content = "message", rename_all = "snake_case")]
+pub enum CleanupStatus {
+    Removed,
+    /// 预览模式下将被删除
+    WouldRemove,
+    /// 被当前配置引用，拒绝删除
+    Protected,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CleanupResult {
+    pub path: PathBuf,
+    pub category: CleanupCategory,
+    pub size: u64,
+    pub status: CleanupStatus,
+}
+
+/// 需要扫描的目录
+#[derive(Debug, Clone)]
+struct Layout {
+    config_dir: PathBuf,
+    data_dir: PathBuf,
+    profiles_dir: PathBuf,
+    backups_dir: PathBuf,
+    cache_dir: PathBuf,
+}
+
+impl Layout {
+    fn current() -> Result<Self> {
+        Ok(Layout {
+            config_dir: dirs::app_config_dir()?,
+            data_dir: dirs::app_data_dir()?,
+            profiles_dir: dirs::app_profiles_dir()?,
+            backups_dir: dirs::settings_backup_dir()?,
+            cache_dir: dirs::app_data_dir()?.join("cache"),
+        })
+    }
+
+    /// 配置目录中由应用管理的文件与目录
+    fn known_config_entries(&self) -> HashSet<PathBuf> {
+        [
+            dirs::NYANPASU_CONFIG,
+            dirs::PROFILE_YAML,
+            dirs::CLASH_CFG_GUARD_OVERRIDES,
+            dirs::MERGE_OVERLAY,
+            dirs::PORTABLE_FLAG,
+            RUNTIME_CONFIG,
+            MINIMAL_STARTUP_CONFIG,
+            ".window-state.json",
+            "migration.lock",
+        ]
+        .into_iter()
+        .map(|name| self.config_dir.join(name))
+        .chain([self.profiles_dir.clone(), self.backups_dir.clone()])
+        .collect()
+    }
+}
+
+/// 当前配置引用的文件，清理时无论分类都不会删除
+fn referenced_paths(layout: &Layout) -> HashSet<PathBuf> {
+    let profiles = Config::profiles().latest();
+    profiles
+        .get_items()
+        .iter()
+        .map(|item| layout.profiles_dir.join(item.file()))
+        .chain(layout.known_config_entries())
+        .collect()
+}
+
+struct Scanner<'a> {
+    layout: &'a Layout,
+    referenced: &'a HashSet<PathBuf>,
+    now: SystemTime,
+    report: DataDirReport,
+}
+
+impl Scanner<'_> {
+    fn error(&mut self, path: &Path, error: impl std::fmt::Display) {
+        self.report.errors.push(DataDirError {
+            path: path.to_path_buf(),
+            error: error.to_string(),
+        });
+    }
+
+    /// 读取目录，返回条目及其元数据（不跟随符号链接）
+    fn read_dir(&mut self, dir: &Path) -> Vec<(PathBuf, fs::Metadata)> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                self.error(dir, e);
+                return Vec::new();
+            }
+        };
+        let mut result = Vec::new();
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    self.error(dir, e);
+                    continue;
+                }
+            };
+            match fs::symlink_metadata(&path) {
+                Ok(meta) => result.push((path, meta)),
+                Err(e) => self.error(&path, e),
+            }
+        }
+        result.sort_by(|a, b| a.0.cmp(&b.0));
+        result
+    }
+
+    /// 目录的大小，不进入符号链接指向的目录
+    fn size_of(&mut self, path: &Path, meta: &fs::Metadata) -> u64 {
+        if !meta.is_dir() {
+            return meta.len();
+        }
+        self.read_dir(path)
+            .into_iter()
+            .map(|(path, meta)| self.size_of(&path, &meta))
+            .sum()
+    }
+
+    fn push(&mut self, path: PathBuf, meta: &fs::Metadata, category: CleanupCategory) {
+        let size = self.size_of(&path, meta);
+        self.report.entries.push(DataDirEntry {
+            path,
+            category,
+            size,
+            is_dir: meta.is_dir(),
+        });
+    }
+
+    fn is_stale(&self, meta: &fs::Metadata) -> bool {
+        meta.modified().is_ok_and(|modified| {
+            self.now
+                .duration_since(modified)
+                .is_ok_and(|age| age > TEMP_MAX_AGE)
+        })
+    }
+
+    fn is_stale_temp(&self, path: &Path, meta: &fs::Metadata) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let is_temp = TEMP_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+            || (name.starts_with("widget_") && name.ends_with(".state"));
+        is_temp && self.is_stale(meta)
+    }
+
+    fn scan_profiles(&mut self) {
+        let profiles_dir = self.layout.profiles_dir.clone();
+        for (path, meta) in self.read_dir(&profiles_dir) {
+            let category = if self.referenced.contains(&path) {
+                CleanupCategory::ActiveProfile
+            } else if self.is_stale_temp(&path, &meta) {
+                CleanupCategory::Temp
+            } else if meta.is_file()
+                && path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| PROFILE_EXTENSIONS.contains(&ext))
+            {
+                // 导入配置时先写文件再登记，刚写入的文件不列出
+                if !self.is_stale(&meta) {
+                    continue;
+                }
+                CleanupCategory::OrphanedProfile
+            } else {
+                CleanupCategory::Unknown
+            };
+            self.push(path, &meta, category);
+        }
+    }
+
+    /// 按目录名中的时间排序，无法解析时使用修改时间
+    fn scan_backups(&mut self) {
+        let backups_dir = self.layout.backups_dir.clone();
+        let mut backups = self.read_dir(&backups_dir);
+        backups.sort_by_cached_key(|(path, meta)| (backup_time(path, meta), path.clone()));
+        backups.reverse();
+        for (i, (path, meta)) in backups.into_iter().enumerate() {
+            if i >= BACKUP_RETENTION {
+                self.push(path, &meta, CleanupCategory::ExpiredBackup);
+            }
+        }
+    }
+
+    /// 配置目录中的其他文件可能由旧版本或插件写入，无法判断能否删除，只清理临时文件
+    fn scan_config_dir(&mut self) {
+        let config_dir = self.layout.config_dir.clone();
+        self.scan_temp(&config_dir);
+    }
+
+    /// 数据目录中的核心资源无法判断归属，只统计临时文件
+    fn scan_temp(&mut self, dir: &Path) {
+        for (path, meta) in self.read_dir(dir) {
+            if !self.referenced.contains(&path) && self.is_stale_temp(&path, &meta) {
+                self.push(path, &meta, CleanupCategory::Temp);
+            }
+        }
+    }
+
+    fn finish(mut self) -> DataDirReport {
+        let mut summary = Vec::<CategorySummary>::new();
+        for entry in &self.report.entries {
+            match summary.iter_mut().find(|s| s.category == entry.category) {
+                Some(s) => {
+                    s.count += 1;
+                    s.size += entry.size;
+                }
+                None => summary.push(CategorySummary {
+                    category: entry.category,
+                    count: 1,
+                    size: entry.size,
+                }),
+            }
+        }
+        self.report.summary = summary;
+        self.report
+    }
+}
+
+/// 备份的创建时间，优先取目录名末尾的时间
+fn backup_time(path: &Path, meta: &fs::Metadata) -> Option<chrono::NaiveDateTime> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let mut parts = name.rsplitn(3, '-');
+    parts
+        .next()
+        .zip(parts.next())
+        .and_then(|(time, date)| {
+            chrono::NaiveDateTime::parse_from_str(&format!("{date}-{time}"), BACKUP_TIME_FORMAT)
+                .ok()
+        })
+        .or_else(|| {
+            meta.modified()
+                .ok()
+                .map(|modified| chrono::DateTime::<chrono::Local>::from(modified).naive_local())
+        })
+}
+
+fn scan(layout: &Layout, referenced: &HashSet<PathBuf>, now: SystemTime) -> DataDirReport {
+    let mut scanner = Scanner {
+        layout,
+        referenced,
+        now,
+        report: DataDirReport {
+            entries: Vec::new(),
+            summary: Vec::new(),
+            errors: Vec::new(),
+        },
+    };
+    scanner.scan_profiles();
+    scanner.scan_backups();
+    scanner.scan_config_dir();
+    if layout.data_dir != layout.config_dir {
+        scanner.scan_temp(&layout.data_dir);
+    }
+    scanner.scan_temp(&layout.cache_dir);
+    scanner.scan_temp(&layout.cache_dir.join("updates"));
+    scanner.finish()
+}
+
+fn remove(entry: &DataDirEntry) -> std::io::Result<()> {
+    // 符号链接只删除链接本身
+    if entry.is_dir {
+        fs::remove_dir_all(&entry.path)
+    } else {
+        fs::remove_file(&entry.path)
+    }
+}
+
+fn cleanup(
+    report: &DataDirReport,
+    categories: &[CleanupCategory],
+    referenced: &HashSet<PathBuf>,
+    dry_run: bool,
+) -> Vec<CleanupResult> {
+    report
+        .entries
+        .iter()
+        .filter(|entry| categories.contains(&entry.category))
+        .map(|entry| {
+            let status = if entry.category == CleanupCategory::ActiveProfile
+                || referenced.contains(&entry.path)
+            {
+                CleanupStatus::Protected
+            } else if dry_run {
+                CleanupStatus::WouldRemove
+            } else {
+                match remove(entry) {
+                    Ok(()) => CleanupStatus::Removed,
+                    Err(e) => CleanupStatus::Failed(e.to_string()),
+                }
+            };
+            CleanupResult {
+                path: entry.path.clone(),
+                category: entry.category,
+                size: entry.size,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// 扫描配置与数据目录并分类
+pub fn data_dir_report() -> Result<DataDirReport> {
+    let layout = Layout::current()?;
+    let referenced = referenced_paths(&layout);
+    Ok(scan(&layout, &referenced, SystemTime::now()))
+}
+
+/// 删除所选分类的条目，删除前重新扫描，不信任调用方提供的路径
+/// 清理期间持有激活锁，避免配置项在扫描后登记
+pub async fn data_dir_cleanup(
+    categories: Vec<CleanupCategory>,
+    dry_run: bool,
+) -> Result<Vec<CleanupResult>> {
+    let _exclusive = profile_activation::global().exclusive().await;
+    let layout = Layout::current()?;
+    let referenced = referenced_paths(&layout);
+    let results = tokio::task::spawn_blocking(move || {
+        let report = scan(&layout, &referenced, SystemTime::now());
+        cleanup(&report, &categories, &referenced, dry_run)
+    })
+    .await?;
+    if !dry_run {
+        let removed = results
+            .iter()
+            .filter(|r| r.status == CleanupStatus::Removed)
+            .count();
+        tracing::info!("data dir cleanup removed {removed} entries");
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: &[CleanupCategory] = &[
+        CleanupCategory::ActiveProfile,
+        CleanupCategory::OrphanedProfile,
+        CleanupCategory::Temp,
+        CleanupCategory::ExpiredBackup,
+        CleanupCategory::Unknown,
+    ];
+
+    fn layout(root: &Path) -> Layout {
+        Layout {
+            config_dir: root.join("config"),
+            data_dir: root.join("data"),
+            profiles_dir: root.join("config/profiles"),
+            backups_dir: root.join("config/backups"),
+            cache_dir: root.join("data/cache"),
+        }
+    }
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn age(path: &Path, age: Duration) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    /// 无法读取的目录，以 root 运行时权限不生效
+    fn locked_dir(layout: &Layout) -> PathBuf {
+        layout.profiles_dir.join("locked")
+    }
+
+    fn is_unreadable(path: &Path) -> bool {
+        fs::read_dir(path).is_err()
+    }
+
+    #[cfg(unix)]
+    fn set_mode(path: &Path, mode: u32) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    /// 恢复权限，临时目录才能被删除
+    #[cfg(unix)]
+    fn unlock(layout: &Layout) {
+        let locked = locked_dir(layout);
+        if locked.exists() {
+            set_mode(&locked, 0o755);
+        }
+    }
+
+    /// 每个分类至少一个条目的数据目录
+    /// unix 下另有指向数据目录之外的符号链接与无法读取的目录
+    fn fixture(root: &Path) -> (Layout, HashSet<PathBuf>) {
+        let layout = layout(root);
+        let day = Duration::from_secs(2 * 24 * 60 * 60);
+        write(&layout.profiles_dir.join("active.yaml"), "proxies: []");
+        let orphan = layout.profiles_dir.join("orphan.yaml");
+        write(&orphan, "proxies: []");
+        age(&orphan, day);
+        // 刚写入、尚未登记的配置不清理
+        write(&layout.profiles_dir.join("importing.yaml"), "proxies: []");
+        write(&layout.profiles_dir.join("notes.txt"), "?");
+        #[cfg(unix)]
+        {
+            let outside = root.join("outside/secret.yaml");
+            write(&outside, "do not touch");
+            std::os::unix::fs::symlink(&outside, layout.profiles_dir.join("linked.yaml")).unwrap();
+            let locked = locked_dir(&layout);
+            write(&locked.join("a.yaml"), "proxies: []");
+            set_mode(&locked, 0o000);
+        }
+        write(&layout.config_dir.join(dirs::NYANPASU_CONFIG), "");
+        write(&layout.config_dir.join("leftover.log"), "x");
+        let part = layout.cache_dir.join("updates/installer.exe.part");
+        write(&part, "partial");
+        age(&part, day);
+        // 刚开始的下载不清理
+        write(&layout.data_dir.join("Country.mmdb.tmp"), "fresh");
+        let state = layout.data_dir.join("widget_large.state");
+        write(&state, "{}");
+        age(&state, day);
+        write(&layout.data_dir.join("Country.mmdb"), "geoip");
+        for i in 0..BACKUP_RETENTION + 2 {
+            write(
+                &layout
+                    .backups_dir
+                    .join(format!("reset-20240101-00000{i}"))
+                    .join(dirs::NYANPASU_CONFIG),
+                "backup",
+            );
+        }
+
+        let referenced = [layout.profiles_dir.join("active.yaml")]
+            .into_iter()
+            .chain(layout.known_config_entries())
+            .collect();
+        (layout, referenced)
+    }
+
+    fn names(report: &DataDirReport, category: CleanupCategory) -> Vec<String> {
+        report
+            .entries
+            .iter()
+            .filter(|e| e.category == category)
+            .map(|e| e.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_categories() {
+        let root = tempfile::tempdir().unwrap();
+        let (layout, referenced) = fixture(root.path());
+        let report = scan(&layout, &referenced, SystemTime::now());
+
+        // 无法读取的目录只记录错误，其他条目照常扫描
+        let locked = locked_dir(&layout);
+        let errors = report
+            .errors
+            .iter()
+            .map(|e| e.path.clone())
+            .collect::<Vec<_>>();
+        if cfg!(unix) && is_unreadable(&locked) {
+            assert_eq!(errors, [locked]);
+        } else {
+            assert!(errors.is_empty(), "{errors:?}");
+        }
+        assert_eq!(
+            names(&report, CleanupCategory::ActiveProfile),
+            ["active.yaml"]
+        );
+        assert_eq!(
+            names(&report, CleanupCategory::OrphanedProfile),
+            ["orphan.yaml"]
+        );
+        // 配置目录中的未知文件不会被列出，符号链接不视为配置文件
+        let unknown: &[&str] = if cfg!(unix) {
+            &["linked.yaml", "locked", "notes.txt"]
+        } else {
+            &["notes.txt"]
+        };
+        assert_eq!(names(&report, CleanupCategory::Unknown), unknown);
+        assert_eq!(
+            names(&report, CleanupCategory::Temp),
+            ["widget_large.state", "installer.exe.part"]
+        );
+        assert_eq!(
+            names(&report, CleanupCategory::ExpiredBackup),
+            ["reset-20240101-000001", "reset-20240101-000000"]
+        );
+        let backups = report
+            .summary
+            .iter()
+            .find(|s| s.category == CleanupCategory::ExpiredBackup)
+            .unwrap();
+        assert_eq!((backups.count, backups.size), (2, 12));
+        #[cfg(unix)]
+        unlock(&layout);
+    }
+
+    #[test]
+    fn test_cleanup_dry_run_matches_real_run() {
+        let root = tempfile::tempdir().unwrap();
+        let (layout, referenced) = fixture(root.path());
+        let report = scan(&layout, &referenced, SystemTime::now());
+
+        let preview = cleanup(&report, ALL, &referenced, true);
+        // 预览不修改文件
+        let rescanned = scan(&layout, &referenced, SystemTime::now());
+        assert_eq!(rescanned.entries.len(), report.entries.len());
+
+        let locked = locked_dir(&layout);
+        let unreadable = cfg!(unix) && is_unreadable(&locked);
+        let results = cleanup(&report, ALL, &referenced, false);
+        assert_eq!(preview.len(), results.len());
+        for (preview, result) in preview.iter().zip(&results) {
+            assert_eq!(preview.path, result.path);
+            match preview.status {
+                // 删除失败只影响该条目
+                CleanupStatus::WouldRemove if unreadable && result.path == locked => {
+                    assert!(matches!(result.status, CleanupStatus::Failed(_)));
+                }
+                CleanupStatus::WouldRemove => {
+                    assert_eq!(result.status, CleanupStatus::Removed);
+                    assert!(!result.path.exists());
+                }
+                CleanupStatus::Protected => {
+                    assert_eq!(result.status, CleanupStatus::Protected);
+                    assert!(result.path.exists());
+                }
+                ref status => panic!("unexpected status {status:?}"),
+            }
+        }
+        assert!(layout.profiles_dir.join("active.yaml").exists());
+        assert!(layout.profiles_dir.join("importing.yaml").exists());
+        assert!(layout.data_dir.join("Country.mmdb").exists());
+        assert!(layout.data_dir.join("Country.mmdb.tmp").exists());
+        assert!(layout.config_dir.join("leftover.log").exists());
+        // 只删除符号链接本身
+        assert!(root.path().join("outside/secret.yaml").exists());
+
+        let report = scan(&layout, &referenced, SystemTime::now());
+        assert_eq!(report.entries.len(), 1 + usize::from(unreadable));
+        #[cfg(unix)]
+        unlock(&layout);
+    }
+
+    #[test]
+    fn test_backups_sorted_by_time() {
+        let root = tempfile::tempdir().unwrap();
+        let layout = layout(root.path());
+        // 按名称排序时 `manual-` 会排在所有 `reset-` 之前
+        let mut backups = vec!["manual-20250101-000000".to_string()];
+        for i in 1..=BACKUP_RETENTION {
+            backups.push(format!("reset-2024010{i}-000000"));
+        }
+        for name in &backups {
+            write(&layout.backups_dir.join(name).join("a"), "backup");
+        }
+        // 名称中没有时间时使用修改时间
+        let untimed = layout.backups_dir.join("imported");
+        write(&untimed, "backup");
+        age(&untimed, Duration::from_secs(10 * 365 * 24 * 60 * 60));
+
+        let report = scan(&layout, &HashSet::new(), SystemTime::now());
+        assert_eq!(
+            names(&report, CleanupCategory::ExpiredBackup),
+            ["reset-20240101-000000", "imported"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_does_not_follow_symlinks() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        write(&outside.path().join("secret.yaml"), "do not touch");
+        let layout = layout(root.path());
+        fs::create_dir_all(&layout.profiles_dir).unwrap();
+        std::os::unix::fs::symlink(outside.path(), layout.profiles_dir.join("link")).unwrap();
+
+        let referenced = HashSet::new();
+        let report = scan(&layout, &referenced, SystemTime::now());
+        let link = &report.entries[0];
+        assert_eq!(link.category, CleanupCategory::Unknown);
+        assert!(!link.is_dir);
+
+        cleanup(&report, ALL, &referenced, false);
+        assert!(!layout.profiles_dir.join("link").exists());
+        assert!(outside.path().join("secret.yaml").exists());
+    }
+}
@@ -0,0 +1,268 @@
+//! 导出诊断包：日志、服务与核心状态、脱敏后的配置以及系统信息，打包为 zip
+//! 控制器 secret、代理凭据与订阅链接中的 token 在写入前统一脱敏
+use crate::{
+    config::{Config, Profiles, nyanpasu},
+    core::{
+        CoreManager, RunType,
+        clash::api,
+        service::{self, ipc::IpcMetrics},
+    },
+    utils::{collect, dirs, redact::Redactor},
+};
+use anyhow::{Context, Result};
+use nyanpasu_ipc::api::status::CoreState;
+use serde::Serialize;
+use std::{
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+/// 单个日志文件最多保留末尾的字节数
+const MAX_LOG_BYTES: u64 = 4 * 1024 * 1024;
+
+/// 最多打包的日志文件数，按修改时间取最新的
+const MAX_LOG_FILES: usize = 10;
+
+#[derive(Serialize)]
+struct ServiceDiagnostics {
+    status: Option<nyanpasu_ipc::types::StatusInfo<'static>>,
+    status_error: Option<String>,
+    ipc_state: service::ipc::IpcState,
+    ipc_metrics: IpcMetrics,
+}
+
+#[derive(Serialize)]
+struct RuntimeHealth {
+    core: nyanpasu::ClashCore,
+    core_state: CoreState,
+    state_changed_at: i64,
+    run_type: RunType,
+    controller_ping_ms: Option<u128>,
+    enable_service_mode: bool,
+}
+
+async fn service_diagnostics(metrics: IpcMetrics) -> ServiceDiagnostics {
+    let (status, status_error) = match service::control::status().await {
+        Ok(status) => (Some(status), None),
+        Err(e) => (None, Some(format!("{e:?}"))),
+    };
+    ServiceDiagnostics {
+        status,
+        status_error,
+        ipc_state: service::ipc::get_ipc_state(),
+        ipc_metrics: metrics,
+    }
+}
+
+async fn runtime_health() -> RuntimeHealth {
+    let core = Config::clash_core();
+    let enable_service_mode = Config::verge()
+        .latest()
+        .enable_service_mode
+        .unwrap_or_default();
+    let (state, state_changed_at, run_type) = CoreManager::global().status().await;
+    RuntimeHealth {
+        core,
+        core_state: state.into_owned(),
+        state_changed_at,
+        run_type,
+        controller_ping_ms: api::controller_ping().await.map(|d| d.as_millis()),
+        enable_service_mode,
+    }
+}
+
+/// 服务的日志目录，目前仅 Windows 服务的位置是固定的
+fn service_logs_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        std::env::var_os("PROGRAMDATA")
+            .map(|p| PathBuf::from(p).join("nyanpasu-service").join("logs"))
+            .filter(|p| p.is_dir())
+    }
+    #[cfg(not(windows))]
+    {
+        None
+    }
+}
+
+/// 目录下最新的若干个日志文件
+fn recent_logs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            meta.is_file().then(|| (meta.modified().ok(), e.path()))
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+    files
+        .into_iter()
+        .take(MAX_LOG_FILES)
+        .map(|(_, path)| path)
+        .collect()
+}
+
+/// 读取日志末尾，超出上限时从行首开始截取
+fn read_log_tail(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    if len > MAX_LOG_BYTES {
+        file.seek(SeekFrom::Start(len - MAX_LOG_BYTES))?;
+    }
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    let text = String::from_utf8_lossy(&buf);
+    Ok(match text.find('\n') {
+        Some(pos) if len > MAX_LOG_BYTES => text[pos + 1..].to_string(),
+        _ => text.into_owned(),
+    })
+}
+
+struct Bundle<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    redactor: Redactor,
+    /// 收集失败的部分，写入 errors.txt
+    errors: Vec<String>,
+}
+
+impl<W: Write + Seek> Bundle<W> {
+    fn write(&mut self, name: &str, content: &str) -> Result<()> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        self.zip.start_file(name, options)?;
+        self.zip.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    fn write_redacted(&mut self, name: &str, content: &str) -> Result<()> {
+        let content = self.redactor.redact_text(content);
+        self.write(name, &content)
+    }
+
+    fn write_json(&mut self, name: &str, value: &impl Serialize) -> Result<()> {
+        let mut value = serde_yaml::to_value(value)?;
+        self.redactor.redact_value(&mut value);
+        self.write(name, &serde_json::to_string_pretty(&value)?)
+    }
+
+    fn write_yaml(&mut self, name: &str, value: &impl Serialize) -> Result<()> {
+        let mut value = serde_yaml::to_value(value)?;
+        self.redactor.redact_value(&mut value);
+        self.write(name, &serde_yaml::to_string(&value)?)
+    }
+
+    fn write_logs(&mut self, prefix: &str, dir: &Path) -> Result<()> {
+        for path in recent_logs(dir) {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            match read_log_tail(&path) {
+                Ok(content) => self.write_redacted(&format!("{prefix}/{name}"), &content)?,
+                Err(e) => self.errors.push(format!("{}: {e:?}", path.display())),
+            }
+        }
+        Ok(())
+    }
+
+    /// 记录收集失败的部分，不中断打包
+    fn collect<T>(&mut self, section: &str, result: Result<T>) -> Option<T> {
+        result
+            .inspect_err(|e| self.errors.push(format!("{section}: {e:?}")))
+            .ok()
+    }
+}
+
+/// 只打包应用与服务的日志，同样经过脱敏
+pub fn export_logs(dest: &Path) -> Result<()> {
+    let file = fs::File::create(dest)
+        .with_context(|| format!("failed to create log archive {}", dest.display()))?;
+    let mut bundle = Bundle {
+        zip: ZipWriter::new(file),
+        redactor: Redactor::current(),
+        errors: Vec::new(),
+    };
+    bundle.write_logs("logs/app", &dirs::app_logs_dir()?)?;
+    if let Some(dir) = service_logs_dir() {
+        bundle.write_logs("logs/service", &dir)?;
+    }
+    if !bundle.errors.is_empty() {
+        let errors = bundle.errors.join("\n");
+        bundle.write_redacted("errors.txt", &errors)?;
+    }
+    bundle.zip.finish()?;
+    Ok(())
+}
+
+/// 将诊断信息打包到 `dest`，单个部分收集失败时记录到 `errors.txt` 中
+pub async fn export_diagnostics(dest: &Path, metrics: IpcMetrics) -> Result<()> {
+    let service = service_diagnostics(metrics).await;
+    let health = runtime_health().await;
+    let envs = tokio::task::spawn_blocking(|| collect::collect_envs().map_err(anyhow::Error::from))
+        .await?;
+    let sections = Sections {
+        service,
+        health,
+        envs,
+        runtime: Config::runtime().latest().config.clone(),
+        verge: Config::verge().latest().clone(),
+        profiles: Config::profiles().latest().clone(),
+    };
+    let redactor = Redactor::current();
+    // 读取日志与压缩都是阻塞 IO
+    let dest = dest.to_path_buf();
+    tokio::task::spawn_blocking(move || write_diagnostics(&dest, redactor, sections)).await?
+}
+
+/// 在异步上下文中收集的部分，打包时写入
+struct Sections {
+    service: ServiceDiagnostics,
+    health: RuntimeHealth,
+    envs: Result<collect::EnvInfo<'static>>,
+    runtime: Option<serde_yaml::Mapping>,
+    verge: nyanpasu::IVerge,
+    profiles: Profiles,
+}
+
+fn write_diagnostics(dest: &Path, redactor: Redactor, sections: Sections) -> Result<()> {
+    let file = fs::File::create(dest)
+        .with_context(|| format!("failed to create diagnostics bundle {}", dest.display()))?;
+    let mut bundle = Bundle {
+        zip: ZipWriter::new(file),
+        redactor,
+        errors: Vec::new(),
+    };
+
+    if let Some(dir) = bundle.collect("app logs", dirs::app_logs_dir()) {
+        bundle.write_logs("logs/app", &dir)?;
+    }
+    match service_logs_dir() {
+        Some(dir) => bundle.write_logs("logs/service", &dir)?,
+        None => bundle
+            .errors
+            .push("service logs: log directory not found".to_string()),
+    }
+    bundle.write_json("service.json", &sections.service)?;
+    bundle.write_json("health.json", &sections.health)?;
+    match sections.runtime {
+        Some(config) => bundle.write_yaml("config/runtime.yaml", &config)?,
+        None => bundle
+            .errors
+            .push("runtime config: not generated yet".to_string()),
+    }
+    bundle.write_yaml("config/verge.yaml", &sections.verge)?;
+    bundle.write_yaml("config/profiles.yaml", &sections.profiles)?;
+    if let Some(envs) = bundle.collect("env", sections.envs) {
+        bundle.write_json("env.json", &envs)?;
+    }
+
+    if !bundle.errors.is_empty() {
+        let errors = bundle.errors.join("\n");
+        bundle.write_redacted("errors.txt", &errors)?;
+    }
+    bundle.zip.finish()?;
+    Ok(())
+}
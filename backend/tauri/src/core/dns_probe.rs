@@ -0,0 +1,217 @@
+//! 不经过核心，直接向 DNS 上游查询一个已知域名，检查上游是否可用
+//! DoQ 需要额外的 QUIC 客户端，目前不支持检查
+use crate::{
+    config::Config,
+    enhance::{DEFAULT_NAMESERVERS, DnsUpstream, validate_nameservers},
+};
+use anyhow::{Result, bail};
+use futures::future::join_all;
+use rustls::pki_types::ServerName;
+use serde::Serialize;
+use specta::Type;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+};
+use tokio_rustls::TlsConnector;
+
+const PROBE_DOMAIN: &str = "www.gstatic.com";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// DoH 要求查询 id 为 0，其余协议沿用
+const QUERY_ID: u16 = 0;
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct DnsProbeResult {
+    pub upstream: String,
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// 查询 `name` 的 A 记录
+fn build_query(name: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(name.len() + 18);
+    buf.extend(QUERY_ID.to_be_bytes());
+    // 期望递归查询
+    buf.extend([0x01, 0x00]);
+    buf.extend([0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        buf.push(label.len() as u8);
+        buf.extend(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend([0, 1, 0, 1]);
+    buf
+}
+
+fn check_response(res: &[u8]) -> Result<()> {
+    if res.len() < 12 {
+        bail!("truncated response");
+    }
+    if res[..2] != QUERY_ID.to_be_bytes() || res[2] & 0x80 == 0 {
+        bail!("unexpected response");
+    }
+    let rcode = res[3] & 0x0f;
+    if rcode != 0 {
+        bail!("server returned rcode {rcode}");
+    }
+    if u16::from_be_bytes([res[6], res[7]]) == 0 {
+        bail!("empty answer");
+    }
+    Ok(())
+}
+
+async fn query_udp(upstream: &DnsUpstream, query: &[u8]) -> Result<()> {
+    let target = tokio::net::lookup_host((upstream.host.as_str(), upstream.port))
+        .await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("failed to resolve `{}`", upstream.host))?;
+    let bind = if target.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(target).await?;
+    socket.send(query).await?;
+    let mut buf = [0u8; 1500];
+    let len = socket.recv(&mut buf).await?;
+    check_response(&buf[..len])
+}
+
+/// TCP 与 DoT 共用的带两字节长度前缀的查询
+async fn query_stream<S>(stream: &mut S, query: &[u8]) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream
+        .write_all(&(query.len() as u16).to_be_bytes())
+        .await?;
+    stream.write_all(query).await?;
+    stream.flush().await?;
+    let len = stream.read_u16().await?;
+    let mut buf = vec![0u8; len as usize];
+    stream.read_exact(&mut buf).await?;
+    check_response(&buf)
+}
+
+async fn query_tcp(upstream: &DnsUpstream, query: &[u8]) -> Result<()> {
+    let mut stream = TcpStream::connect((upstream.host.as_str(), upstream.port)).await?;
+    query_stream(&mut stream, query).await
+}
+
+/// 完成 TLS 握手并校验证书后再查询
+async fn query_tls(upstream: &DnsUpstream, query: &[u8]) -> Result<()> {
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let server_name = ServerName::try_from(upstream.host.clone())?;
+    let tcp = TcpStream::connect((upstream.host.as_str(), upstream.port)).await?;
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, tcp)
+        .await?;
+    query_stream(&mut stream, query).await
+}
+
+async fn query_https(upstream: &DnsUpstream, query: Vec<u8>) -> Result<()> {
+    let Some(url) = upstream.url.clone() else {
+        bail!("missing doh url");
+    };
+    let res = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(PROBE_TIMEOUT)
+        .build()?
+        .post(url)
+        .header("content-type", "application/dns-message")
+        .header("accept", "application/dns-message")
+        .body(query)
+        .send()
+        .await?
+        .error_for_status()?;
+    check_response(&res.bytes().await?)
+}
+
+async fn probe(upstream: &DnsUpstream) -> Result<()> {
+    let query = build_query(PROBE_DOMAIN);
+    match upstream.scheme.as_str() {
+        "udp" => query_udp(upstream, &query).await,
+        "tcp" => query_tcp(upstream, &query).await,
+        "https" => query_https(upstream, query).await,
+        "tls" => query_tls(upstream, &query).await,
+        scheme => bail!("probing `{scheme}` upstreams is not supported"),
+    }
+}
+
+async fn probe_upstream(server: String) -> DnsProbeResult {
+    let started = Instant::now();
+    let result = match DnsUpstream::parse(&server) {
+        Ok(upstream) => tokio::time::timeout(PROBE_TIMEOUT, probe(&upstream))
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out"))),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => DnsProbeResult {
+            upstream: server,
+            reachable: true,
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            error: None,
+        },
+        Err(e) => DnsProbeResult {
+            upstream: server,
+            reachable: false,
+            latency_ms: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// 并发检查设置中的上游，未设置时检查默认上游
+pub async fn probe_dns_upstreams() -> Result<Vec<DnsProbeResult>> {
+    let servers = Config::verge()
+        .latest()
+        .dns_nameservers
+        .clone()
+        .unwrap_or_else(|| DEFAULT_NAMESERVERS.iter().map(|s| s.to_string()).collect());
+    let core = Config::clash_core();
+    validate_nameservers(&servers, &core)?;
+    Ok(join_all(servers.into_iter().map(probe_upstream)).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_query_and_check_response() {
+        let query = build_query("example.com");
+        assert_eq!(query.len(), 12 + 13 + 4);
+        assert_eq!(&query[12..20], b"\x07example");
+
+        let mut res = query.clone();
+        res[2] |= 0x80;
+        assert!(check_response(&res).is_err());
+        res[7] = 1;
+        assert!(check_response(&res).is_ok());
+        res[3] = 0x03;
+        assert!(check_response(&res).is_err());
+        assert!(check_response(&res[..4]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_quic_reported_unsupported() {
+        let res = probe_upstream("quic://dns.adguard.com".to_string()).await;
+        assert!(!res.reachable);
+        assert!(res.error.unwrap().contains("not supported"));
+    }
+}
@@ -0,0 +1,310 @@
+//! 紧急恢复：一键撤销所有流量接管，恢复系统原始网络
+//! 整个流程不等待核心 API，核心与服务均卡死时也能完成
+use crate::{
+    config::{Config, IVerge},
+    core::{
+        CoreManager,
+        handle::Handle,
+        service::{control, ipc::get_ipc_state},
+        sysopt::Sysopt,
+    },
+    log_err,
+    utils::dirs,
+};
+use anyhow::{Context, Result, anyhow};
+use rust_i18n::t;
+use serde::Serialize;
+use serde_yaml::Value;
+use specta::Type;
+use std::time::Duration;
+use tauri_plugin_notification::NotificationExt;
+
+/// 单个步骤的超时时间
+const STEP_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, Serialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum EmergencyResetStepKind {
+    /// 关闭系统代理及代理守卫、TUN 开关
+    DisableInterception,
+    SystemProxy,
+    Tun,
+    FlushDns,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum EmergencyResetStepStatus {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct EmergencyResetStep {
+    pub kind: EmergencyResetStepKind,
+    pub status: EmergencyResetStepStatus,
+    pub message: Option<String>,
+}
+
+impl EmergencyResetStep {
+    fn from_result(kind: EmergencyResetStepKind, result: Result<Option<String>>) -> Self {
+        let (status, message) = match result {
+            Ok(message) => (EmergencyResetStepStatus::Succeeded, message),
+            Err(e) => (EmergencyResetStepStatus::Failed, Some(format!("{e:#}"))),
+        };
+        tracing::info!("emergency reset step {kind:?}: {status:?} {message:?}");
+        Self {
+            kind,
+            status,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct EmergencyResetReport {
+    pub steps: Vec<EmergencyResetStep>,
+    /// 所有步骤均未失败
+    pub success: bool,
+}
+
+/// 执行紧急恢复，每个步骤独立执行，失败不影响后续步骤
+pub async fn emergency_reset() -> EmergencyResetReport {
+    tracing::warn!("emergency reset triggered");
+    let tun_enabled = disable_interception_flags();
+    let mut steps = vec![EmergencyResetStep::from_result(
+        EmergencyResetStepKind::DisableInterception,
+        persist_verge().map(|_| None),
+    )];
+
+    steps.push(EmergencyResetStep::from_result(
+        EmergencyResetStepKind::SystemProxy,
+        blocking_with_timeout(|| Sysopt::global().disable_sysproxy_now())
+            .await
+            .map(|_| None),
+    ));
+
+    steps.push(if tun_enabled {
+        EmergencyResetStep::from_result(EmergencyResetStepKind::Tun, teardown_tun().await)
+    } else {
+        EmergencyResetStep {
+            kind: EmergencyResetStepKind::Tun,
+            status: EmergencyResetStepStatus::Skipped,
+            message: Some("tun is not enabled".into()),
+        }
+    });
+
+    steps.push(EmergencyResetStep::from_result(
+        EmergencyResetStepKind::FlushDns,
+        flush_dns_cache().await,
+    ));
+
+    let report = EmergencyResetReport {
+        success: steps
+            .iter()
+            .all(|step| !matches!(step.status, EmergencyResetStepStatus::Failed)),
+        steps,
+    };
+    Handle::refresh_verge();
+    log_err!(Handle::update_systray_part());
+    log_err!(Handle::emit("emergency-reset", report.clone()));
+    notify(&report);
+    report
+}
+
+/// 关闭接管相关开关，用户需手动重新开启
+/// 同时修改 data 与 draft，正在进行的修改不会把开关再打开
+/// 返回重置前 TUN 是否开启
+fn disable_interception_flags() -> bool {
+    let verge = Config::verge();
+    let tun_enabled = {
+        let mut data = verge.data();
+        let enabled = data.enable_tun_mode.unwrap_or(false);
+        disable_flags(&mut data);
+        enabled
+    };
+    let tun_enabled = {
+        let mut latest = verge.latest();
+        let enabled = latest.enable_tun_mode.unwrap_or(false);
+        disable_flags(&mut latest);
+        tun_enabled || enabled
+    };
+    tun_enabled || runtime_tun_enabled()
+}
+
+fn disable_flags(config: &mut IVerge) {
+    config.enable_system_proxy = Some(false);
+    config.enable_proxy_guard = Some(false);
+    config.enable_tun_mode = Some(false);
+}
+
+fn persist_verge() -> Result<()> {
+    Config::verge().data().save_file()
+}
+
+/// 运行时配置中的 TUN 状态，用于兜底配置与实际不一致的情况
+fn runtime_tun_enabled() -> bool {
+    Config::runtime()
+        .latest()
+        .config
+        .as_ref()
+        .and_then(|config| config.get("tun"))
+        .and_then(|tun| tun.get("enable"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+fn runtime_tun_device() -> Option<String> {
+    Config::runtime()
+        .latest()
+        .config
+        .as_ref()
+        .and_then(|config| config.get("tun"))
+        .and_then(|tun| tun.get("device"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+async fn blocking_with_timeout<F>(f: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    tokio::time::timeout(STEP_TIMEOUT, tokio::task::spawn_blocking(f))
+        .await
+        .map_err(|_| anyhow!("timed out after {STEP_TIMEOUT:?}"))??
+}
+
+/// 停止核心以拆除 TUN 网卡与路由
+/// 服务模式下先让服务停止核心，超时或失败时直接结束核心进程
+async fn teardown_tun() -> Result<Option<String>> {
+    match tokio::time::timeout(STEP_TIMEOUT, CoreManager::global().stop_core()).await {
+        Ok(Ok(())) => return Ok(Some("core stopped".into())),
+        Ok(Err(e)) => tracing::warn!("failed to stop core gracefully: {e:#}"),
+        Err(_) => tracing::warn!("stopping core timed out, service may be hung"),
+    }
+    kill_core_process().await?;
+    // 进程退出后系统会回收 TUN 网卡，Linux 下仍残留时尝试直接删除
+    #[cfg(target_os = "linux")]
+    if let Some(device) = runtime_tun_device() {
+        remove_linux_tun_device(&device).await?;
+    }
+    Ok(Some("core process killed".into()))
+}
+
+/// 强制停止核心，服务模式下核心由服务持有，没有权限直接结束进程
+/// 此时先请求服务停止核心，仍失败时重启服务，服务退出时会结束其启动的核心
+pub(crate) async fn kill_core_process() -> Result<()> {
+    crate::core::clash::watchdog::mark_intentional_stop();
+    if !get_ipc_state().is_connected() {
+        return kill_local_core_process().context("failed to kill the core process");
+    }
+    let stop =
+        nyanpasu_ipc::client::shortcuts::Client::new(&control::ipc_client_name()).stop_core();
+    match tokio::time::timeout(STEP_TIMEOUT, stop).await {
+        Ok(Ok(_)) => return Ok(()),
+        Ok(Err(e)) => tracing::warn!("service failed to stop the core: {e:#}"),
+        Err(_) => tracing::warn!("asking the service to stop the core timed out"),
+    }
+    control::restart_service()
+        .await
+        .context("failed to restart the service owning the core")
+}
+
+fn kill_local_core_process() -> Result<()> {
+    use sysinfo::{Pid, ProcessesToUpdate, System};
+
+    let pid = std::fs::read_to_string(dirs::clash_pid_path()?)
+        .context("failed to read core pid file")?
+        .trim()
+        .parse::<u32>()
+        .context("invalid core pid file")?;
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    let process = system
+        .process(pid)
+        .ok_or_else(|| anyhow!("core process {pid} is not running"))?;
+    if !process.kill() {
+        anyhow::bail!("permission denied to kill core process {pid}");
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn remove_linux_tun_device(device: &str) -> Result<()> {
+    if !std::path::Path::new("/sys/class/net").join(device).exists() {
+        return Ok(());
+    }
+    run_command("ip", &["link", "delete", "dev", device])
+        .await
+        .with_context(|| format!("failed to remove tun device `{device}`"))
+}
+
+async fn run_command(program: &str, args: &[&str]) -> Result<()> {
+    let mut command = tokio::process::Command::new(program);
+    command.args(args).kill_on_drop(true);
+    #[cfg(windows)]
+    command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    let output = tokio::time::timeout(STEP_TIMEOUT, command.output())
+        .await
+        .map_err(|_| anyhow!("`{program}` timed out"))??;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{program}` exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// 刷新系统 DNS 缓存，清除 fake-ip 等残留解析结果
+async fn flush_dns_cache() -> Result<Option<String>> {
+    #[cfg(target_os = "windows")]
+    {
+        run_command("ipconfig", &["/flushdns"]).await?;
+        Ok(None)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        run_command("dscacheutil", &["-flushcache"]).await?;
+        // mDNSResponder 需要管理员权限，失败时仅记录
+        Ok(run_command("killall", &["-HUP", "mDNSResponder"])
+            .await
+            .err()
+            .map(|e| format!("mDNSResponder not reloaded: {e:#}")))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if run_command("resolvectl", &["flush-caches"]).await.is_ok() {
+            return Ok(None);
+        }
+        run_command("systemd-resolve", &["--flush-caches"])
+            .await
+            .map(|_| None)
+            .or_else(|_| Ok(Some("systemd-resolved is not available".into())))
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        Ok(Some("not supported on this platform".into()))
+    }
+}
+
+/// 写入系统通知中心，提醒用户需手动重新开启代理
+fn notify(report: &EmergencyResetReport) {
+    let body = if report.success {
+        t!("notification.emergency_reset.success")
+    } else {
+        t!("notification.emergency_reset.partial")
+    };
+    log_err!(
+        crate::consts::app_handle()
+            .notification()
+            .builder()
+            .title(t!("notification.emergency_reset.title"))
+            .body(body)
+            .show()
+    );
+}
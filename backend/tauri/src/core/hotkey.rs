@@ -1,4 +1,4 @@
-use crate::{config::Config, feat, log_err};
+use crate::{config::Config, feat, log_err, utils::config::ClashMode};
 use anyhow::{Result, bail};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
@@ -85,10 +85,10 @@ impl Hotkey {
 
         let f = match func.trim() {
             "open_or_close_dashboard" => feat::toggle_dashboard,
-            "clash_mode_rule" => || feat::change_clash_mode("rule".into()),
-            "clash_mode_global" => || feat::change_clash_mode("global".into()),
-            "clash_mode_direct" => || feat::change_clash_mode("direct".into()),
-            "clash_mode_script" => || feat::change_clash_mode("script".into()),
+            "clash_mode_rule" => || feat::change_clash_mode(ClashMode::Rule),
+            "clash_mode_global" => || feat::change_clash_mode(ClashMode::Global),
+            "clash_mode_direct" => || feat::change_clash_mode(ClashMode::Direct),
+            "clash_mode_script" => || feat::change_clash_mode(ClashMode::Script),
             "toggle_tun_mode" => feat::toggle_tun_mode,
             "enable_tun_mode" => feat::enable_tun_mode,
             "disable_tun_mode" => feat::disable_tun_mode,
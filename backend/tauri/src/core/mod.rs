@@ -1,12 +1,28 @@
+pub mod activation;
+pub mod activity;
+pub mod asset_watcher;
 pub mod clash;
 pub mod connection_interruption;
+pub mod data_dir;
+pub mod diagnostics;
+pub mod dns_probe;
+pub mod emergency;
 pub mod handle;
 pub mod hotkey;
+pub mod lan_share;
 pub mod logger;
 pub mod manager;
 pub mod migration;
+pub mod pac;
 pub mod privilege;
+pub mod profile_activation;
+pub mod profile_diff;
+pub mod profile_switch;
+pub mod profile_sync;
+pub mod proxy_recovery;
 pub mod service;
+pub mod shutdown;
+pub mod speedtest;
 pub mod state;
 pub mod state_v2;
 pub mod storage;
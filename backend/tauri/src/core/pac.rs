@@ -0,0 +1,448 @@
+//! PAC 模式的系统代理：由内置的本地 HTTP 服务提供 PAC 脚本，系统中只写入脚本地址
+//! 每次请求都返回最新的脚本，端口或绕过列表变化后重新生成内容即可生效
+use anyhow::{Context, Result};
+use axum::{Router, http::header, routing::get};
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, TcpListener},
+    sync::Arc,
+};
+use tokio::sync::oneshot;
+
+pub const PAC_PATH: &str = "/proxy.pac";
+const PAC_MIME: &str = "application/x-ns-proxy-autoconfig";
+
+fn js_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+/// 将一条绕过规则转为 PAC 中的判断条件，`host` 已转为小写并去掉 IPv6 的方括号
+fn bypass_condition(entry: &str) -> Option<String> {
+    let entry = entry.trim().to_ascii_lowercase();
+    if entry.is_empty() {
+        return None;
+    }
+    if entry == "<local>" {
+        return Some("isPlainHostName(host)".to_string());
+    }
+    if entry.contains('*') {
+        return Some(format!("shExpMatch(host, {})", js_string(&entry)));
+    }
+    if let Some((addr, prefix)) = entry.split_once('/') {
+        let prefix = prefix.parse::<u8>().ok()?;
+        if let Ok(addr) = addr.parse::<Ipv4Addr>() {
+            if prefix > 32 {
+                return None;
+            }
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            // isInNet 遇到域名会触发 DNS 查询，只对 IP 字面量判断
+            return Some(format!(
+                "(isIpv4 && isInNet(host, {}, {}))",
+                js_string(&addr.to_string()),
+                js_string(&Ipv4Addr::from(mask).to_string())
+            ));
+        }
+        let addr = addr.parse::<Ipv6Addr>().ok()?;
+        if prefix > 128 {
+            return None;
+        }
+        // isInNetEx 是 IPv6 扩展，不支持的环境直接跳过
+        return Some(format!(
+            "(isIpv6 && typeof isInNetEx === \"function\" && isInNetEx(host, {}))",
+            js_string(&format!("{addr}/{prefix}"))
+        ));
+    }
+    if let Ok(addr) = entry.parse::<Ipv6Addr>() {
+        return Some(format!("host === {}", js_string(&addr.to_string())));
+    }
+    Some(format!("host === {}", js_string(&entry)))
+}
+
+/// 生成 PAC 脚本，命中绕过列表的地址直连，其余走 mixed 端口
+pub fn generate_pac(host: &str, port: u16, bypass: &[String]) -> String {
+    let conditions = bypass
+        .iter()
+        .filter_map(|entry| bypass_condition(entry))
+        .collect::<Vec<_>>();
+    let mut script = String::from(
+        "function FindProxyForURL(url, host) {\n  \
+         host = host.toLowerCase().replace(/^\\[|\\]$/g, \"\");\n  \
+         var isIpv4 = /^\\d{1,3}(\\.\\d{1,3}){3}$/.test(host);\n  \
+         var isIpv6 = host.indexOf(\":\") !== -1;\n",
+    );
+    if !conditions.is_empty() {
+        script.push_str(&format!(
+            "  if (\n    {}\n  ) {{\n    return \"DIRECT\";\n  }}\n",
+            conditions.join(" ||\n    ")
+        ));
+    }
+    script.push_str(&format!(
+        "  return \"PROXY {host}:{port}; SOCKS5 {host}:{port}; DIRECT\";\n}}\n"
+    ));
+    script
+}
+
+struct RunningServer {
+    port: u16,
+    shutdown: oneshot::Sender<()>,
+}
+
+/// 只监听回环地址的 PAC 服务
+pub struct PacServer {
+    content: Arc<RwLock<String>>,
+    running: Mutex<Option<RunningServer>>,
+}
+
+static PAC_SERVER: Lazy<PacServer> = Lazy::new(PacServer::new);
+
+impl PacServer {
+    fn new() -> Self {
+        Self {
+            content: Default::default(),
+            running: Mutex::new(None),
+        }
+    }
+
+    pub fn global() -> &'static PacServer {
+        &PAC_SERVER
+    }
+
+    pub fn set_content(&self, content: String) {
+        *self.content.write() = content;
+    }
+
+    /// 服务运行时的 PAC 地址
+    pub fn url(&self) -> Option<String> {
+        self.running.lock().as_ref().map(|s| pac_url(s.port))
+    }
+
+    /// 启动服务并返回 PAC 地址，`port` 为空时使用随机端口
+    /// 已在运行且端口符合要求时不会重启
+    pub fn start(&self, port: Option<u16>) -> Result<String> {
+        let mut running = self.running.lock();
+        if let Some(server) = running.as_ref()
+            && port.is_none_or(|port| port == server.port)
+        {
+            return Ok(pac_url(server.port));
+        }
+        if let Some(server) = running.take() {
+            let _ = server.shutdown.send(());
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port.unwrap_or(0)))
+            .with_context(|| format!("failed to bind pac server on port {port:?}"))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let (shutdown, rx) = oneshot::channel();
+        let content = self.content.clone();
+        tauri::async_runtime::spawn(async move {
+            let app = Router::new().route(
+                PAC_PATH,
+                get(move || {
+                    let content = content.read().clone();
+                    async move { ([(header::CONTENT_TYPE, PAC_MIME)], content) }
+                }),
+            );
+            let result = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => {
+                    axum::serve(listener, app)
+                        .with_graceful_shutdown(async {
+                            let _ = rx.await;
+                        })
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => tracing::debug!("pac server on port {port} stopped"),
+                Err(e) => tracing::error!("pac server on port {port} failed: {e:?}"),
+            }
+        });
+        tracing::info!("pac server listening on 127.0.0.1:{port}");
+        *running = Some(RunningServer { port, shutdown });
+        Ok(pac_url(port))
+    }
+
+    /// 停止服务，返回服务之前是否在运行
+    pub fn stop(&self) -> bool {
+        match self.running.lock().take() {
+            Some(server) => {
+                let _ = server.shutdown.send(());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn pac_url(port: u16) -> String {
+    format!("http://127.0.0.1:{port}{PAC_PATH}")
+}
+
+#[cfg(target_os = "windows")]
+const INTERNET_SETTINGS: &str = r"Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+
+/// 通知 WinINet 重新加载设置，已运行的程序才会使用新的 PAC 地址
+#[cfg(target_os = "windows")]
+fn refresh_wininet() -> Result<()> {
+    use windows_sys::Win32::Networking::WinInet::{
+        INTERNET_OPTION_REFRESH, INTERNET_OPTION_SETTINGS_CHANGED, InternetSetOptionW,
+    };
+
+    for option in [INTERNET_OPTION_SETTINGS_CHANGED, INTERNET_OPTION_REFRESH] {
+        let ok = unsafe { InternetSetOptionW(std::ptr::null(), option, std::ptr::null(), 0) };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+/// 写入系统的 PAC 地址
+#[cfg(target_os = "windows")]
+pub fn set_auto_config_url(url: &str) -> Result<()> {
+    use winreg::{RegKey, enums::HKEY_CURRENT_USER};
+
+    let hcu = RegKey::predef(HKEY_CURRENT_USER);
+    let (key, _) = hcu.create_subkey(INTERNET_SETTINGS)?;
+    key.set_value("AutoConfigURL", &url)?;
+    refresh_wininet()
+}
+
+/// 系统的 PAC 地址是 `owned` 之一时清除，用户或公司配置的地址保持不变
+/// 返回是否清除了地址
+#[cfg(target_os = "windows")]
+pub fn clear_auto_config_url(owned: &[String]) -> Result<bool> {
+    use winreg::{RegKey, enums::HKEY_CURRENT_USER};
+
+    let hcu = RegKey::predef(HKEY_CURRENT_USER);
+    let (key, _) = hcu.create_subkey(INTERNET_SETTINGS)?;
+    let current: String = match key.get_value("AutoConfigURL") {
+        Ok(current) => current,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    if !owned.contains(&current) {
+        return Ok(false);
+    }
+    key.delete_value("AutoConfigURL")?;
+    refresh_wininet()?;
+    Ok(true)
+}
+
+#[cfg(target_os = "macos")]
+fn networksetup(args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("networksetup")
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "networksetup {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 已启用的网络服务，第一行是说明文字，`*` 开头的服务已停用
+#[cfg(target_os = "macos")]
+fn network_services() -> Result<Vec<String>> {
+    let services = networksetup(&["-listallnetworkservices"])?;
+    Ok(services
+        .lines()
+        .skip(1)
+        .filter(|s| !s.is_empty() && !s.starts_with('*'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// 解析 `networksetup -getautoproxyurl` 的输出，未启用时返回 None
+#[cfg(any(target_os = "macos", test))]
+fn parse_autoproxy_url(output: &str) -> Option<&str> {
+    let mut url = None;
+    let mut enabled = false;
+    for line in output.lines() {
+        if let Some(value) = line.strip_prefix("URL:") {
+            url = Some(value.trim());
+        } else if let Some(value) = line.strip_prefix("Enabled:") {
+            enabled = value.trim() == "Yes";
+        }
+    }
+    url.filter(|_| enabled)
+}
+
+/// 写入系统的 PAC 地址
+#[cfg(target_os = "macos")]
+pub fn set_auto_config_url(url: &str) -> Result<()> {
+    for service in network_services()? {
+        networksetup(&["-setautoproxyurl", &service, url])?;
+    }
+    Ok(())
+}
+
+/// 只关闭 PAC 地址是 `owned` 之一的网络服务的自动代理，返回是否清除了地址
+#[cfg(target_os = "macos")]
+pub fn clear_auto_config_url(owned: &[String]) -> Result<bool> {
+    let mut cleared = false;
+    for service in network_services()? {
+        let output = networksetup(&["-getautoproxyurl", &service])?;
+        if parse_autoproxy_url(&output).is_some_and(|url| owned.iter().any(|o| o == url)) {
+            networksetup(&["-setautoproxystate", &service, "off"])?;
+            cleared = true;
+        }
+    }
+    Ok(cleared)
+}
+
+#[cfg(target_os = "linux")]
+const GNOME_PROXY_SCHEMA: &str = "org.gnome.system.proxy";
+
+#[cfg(target_os = "linux")]
+fn gsettings(args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("gsettings")
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "gsettings {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// 写入系统的 PAC 地址，目前只支持 GNOME 的 gsettings
+#[cfg(target_os = "linux")]
+pub fn set_auto_config_url(url: &str) -> Result<()> {
+    gsettings(&["set", GNOME_PROXY_SCHEMA, "autoconfig-url", url])?;
+    gsettings(&["set", GNOME_PROXY_SCHEMA, "mode", "auto"])?;
+    Ok(())
+}
+
+/// 系统的 PAC 地址是 `owned` 之一时清除，返回是否清除了地址
+#[cfg(target_os = "linux")]
+pub fn clear_auto_config_url(owned: &[String]) -> Result<bool> {
+    let current = gsettings(&["get", GNOME_PROXY_SCHEMA, "autoconfig-url"])?;
+    let current = current.trim_matches('\'');
+    if !owned.iter().any(|o| o == current) {
+        return Ok(false);
+    }
+    // 用户已改为其他模式时不覆盖
+    if gsettings(&["get", GNOME_PROXY_SCHEMA, "mode"])? == "'auto'" {
+        gsettings(&["set", GNOME_PROXY_SCHEMA, "mode", "none"])?;
+    }
+    gsettings(&["set", GNOME_PROXY_SCHEMA, "autoconfig-url", ""])?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bypass(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_bypass_wildcards() {
+        assert_eq!(
+            bypass_condition("*.Example.com").as_deref(),
+            Some(r#"shExpMatch(host, "*.example.com")"#)
+        );
+        assert_eq!(
+            bypass_condition("192.168.*").as_deref(),
+            Some(r#"shExpMatch(host, "192.168.*")"#)
+        );
+        assert_eq!(
+            bypass_condition("<local>").as_deref(),
+            Some("isPlainHostName(host)")
+        );
+        assert_eq!(
+            bypass_condition("localhost").as_deref(),
+            Some(r#"host === "localhost""#)
+        );
+        assert_eq!(
+            bypass_condition("172.16.0.0/12").as_deref(),
+            Some(r#"(isIpv4 && isInNet(host, "172.16.0.0", "255.240.0.0"))"#)
+        );
+        assert_eq!(
+            bypass_condition("0.0.0.0/0").as_deref(),
+            Some(r#"(isIpv4 && isInNet(host, "0.0.0.0", "0.0.0.0"))"#)
+        );
+        assert_eq!(bypass_condition("10.0.0.0/33"), None);
+        assert_eq!(bypass_condition("  "), None);
+    }
+
+    #[test]
+    fn test_bypass_ipv6_literals() {
+        assert_eq!(
+            bypass_condition("::1").as_deref(),
+            Some(r#"host === "::1""#)
+        );
+        assert_eq!(
+            bypass_condition("FE80:0:0:0:0:0:0:1").as_deref(),
+            Some(r#"host === "fe80::1""#)
+        );
+        assert_eq!(
+            bypass_condition("fc00::/7").as_deref(),
+            Some(r#"(isIpv6 && typeof isInNetEx === "function" && isInNetEx(host, "fc00::/7"))"#)
+        );
+        assert_eq!(bypass_condition("fc00::/129"), None);
+    }
+
+    #[test]
+    fn test_parse_autoproxy_url() {
+        let output = "URL: http://127.0.0.1:33331/proxy.pac\nEnabled: Yes\n";
+        assert_eq!(
+            parse_autoproxy_url(output),
+            Some("http://127.0.0.1:33331/proxy.pac")
+        );
+        let output = "URL: http://127.0.0.1:33331/proxy.pac\nEnabled: No\n";
+        assert_eq!(parse_autoproxy_url(output), None);
+        assert_eq!(parse_autoproxy_url("URL: (null)\nEnabled: No\n"), None);
+    }
+
+    #[test]
+    fn test_generate_pac() {
+        let pac = generate_pac(
+            "127.0.0.1",
+            7890,
+            &bypass(&["<local>", "*.lan", "10.0.0.0/8", "::1"]),
+        );
+        assert!(pac.starts_with("function FindProxyForURL(url, host) {"));
+        assert!(pac.contains(
+            "    isPlainHostName(host) ||\n    shExpMatch(host, \"*.lan\") ||\n    \
+             (isIpv4 && isInNet(host, \"10.0.0.0\", \"255.0.0.0\")) ||\n    \
+             host === \"::1\"\n  ) {\n    return \"DIRECT\";"
+        ));
+        assert!(pac.contains(r#"return "PROXY 127.0.0.1:7890; SOCKS5 127.0.0.1:7890; DIRECT";"#));
+
+        let pac = generate_pac("127.0.0.1", 1080, &[]);
+        assert!(!pac.contains("DIRECT\";\n  }"));
+        assert!(pac.contains("PROXY 127.0.0.1:1080"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_pac() {
+        let server = PacServer::new();
+        server.set_content(generate_pac("127.0.0.1", 7890, &[]));
+        let url = server.start(None).unwrap();
+        assert_eq!(server.url().as_deref(), Some(url.as_str()));
+        // 未指定端口时不会重启
+        assert_eq!(server.start(None).unwrap(), url);
+
+        let fetch = || async {
+            let resp = reqwest::get(&url).await.unwrap();
+            assert_eq!(resp.headers()[header::CONTENT_TYPE], PAC_MIME);
+            resp.text().await.unwrap()
+        };
+        assert!(fetch().await.contains("127.0.0.1:7890"));
+        server.set_content(generate_pac("127.0.0.1", 7891, &[]));
+        assert!(fetch().await.contains("127.0.0.1:7891"));
+
+        assert!(server.stop());
+        assert!(!server.stop());
+        assert!(server.url().is_none());
+    }
+}
@@ -4,8 +4,11 @@ use specta::Type;
 use tauri::command;
 
 use super::{
-    PrivilegeMode, PrivilegeStatus, PrivilegedOperation, PrivilegedOperationHandler,
-    PrivilegedOperationResult, manager::PrivilegeManager, operations,
+    BatchOperationResult, PrivilegeMode, PrivilegeStatus, PrivilegedOperation,
+    PrivilegedOperationHandler, PrivilegedOperationResult,
+    manager::PrivilegeManager,
+    operations,
+    validation::{self, PrivilegeCommandError},
 };
 
 /// 获取权限管理状态
@@ -27,20 +30,31 @@ pub async fn get_current_privilege_mode() -> Result<PrivilegeMode, String> {
 #[specta::specta]
 pub async fn execute_privilege_operation(
     operation: PrivilegedOperation,
-) -> Result<PrivilegedOperationResult, String> {
-    PrivilegeManager::global()
+) -> Result<PrivilegedOperationResult, PrivilegeCommandError> {
+    validation::guard("execute_privilege_operation", Some(&operation))?;
+    Ok(PrivilegeManager::global()
         .execute_operation(operation)
-        .await
-        .map_err(|e| e.to_string())
+        .await?)
+}
+
+/// 批量执行权限操作，任一失败时回滚已完成的操作
+#[command]
+#[specta::specta]
+pub async fn batch_privilege_operation(
+    operations: Vec<PrivilegedOperation>,
+) -> Result<BatchOperationResult, PrivilegeCommandError> {
+    validation::guard_batch("batch_privilege_operation", &operations)?;
+    Ok(PrivilegeManager::global().batch_execute(operations).await?)
 }
 
 /// 预检权限操作
 #[command]
 #[specta::specta]
-pub async fn precheck_privilege_operation(operation: PrivilegedOperation) -> Result<bool, String> {
-    operations::precheck_privilege_operation(&operation)
-        .await
-        .map_err(|e| e.to_string())
+pub async fn precheck_privilege_operation(
+    operation: PrivilegedOperation,
+) -> Result<bool, PrivilegeCommandError> {
+    validation::guard("precheck_privilege_operation", Some(&operation))?;
+    Ok(operations::precheck_privilege_operation(&operation).await?)
 }
 
 /// 获取权限操作建议
@@ -56,6 +70,7 @@ pub async fn get_privilege_recommendations() -> Result<Vec<String>, String> {
 #[command]
 #[specta::specta]
 pub async fn auto_setup_service_mode() -> Result<String, String> {
+    validation::guard("auto_setup_service_mode", None).map_err(|e| e.to_string())?;
     let privilege_manager = PrivilegeManager::global();
     let status = privilege_manager.get_privilege_status().await;
 
@@ -1,16 +1,26 @@
 use anyhow::Result;
 use once_cell::sync::OnceCell;
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
+use tokio::sync::Mutex;
 use tracing::{error, info, warn};
 
 use super::{
-    PrivilegeMode, PrivilegeStatus, PrivilegedOperation, PrivilegedOperationHandler,
-    PrivilegedOperationResult, service_handler::ServicePrivilegeHandler,
+    BatchOperationResult, PrivilegeMode, PrivilegeStatus, PrivilegedOperation,
+    PrivilegedOperationHandler, PrivilegedOperationResult,
+    service_handler::ServicePrivilegeHandler,
+    validation::{self, ValidationContext},
 };
 
+tokio::task_local! {
+    /// 当前任务已持有调用队列，其中嵌套的特权操作不再排队
+    static QUEUE_HELD: ();
+}
+
 /// 全局权限管理器（纯服务模式）
 pub struct PrivilegeManager {
     pub(crate) service_handler: Option<Arc<ServicePrivilegeHandler>>,
+    /// 服务与提权命令的调用队列，同一时间只执行一个操作或一个批量操作
+    queue: Mutex<()>,
 }
 
 static PRIVILEGE_MANAGER: OnceCell<Arc<PrivilegeManager>> = OnceCell::new();
@@ -20,6 +30,7 @@ impl PrivilegeManager {
     pub fn new() -> Self {
         Self {
             service_handler: Some(Arc::new(ServicePrivilegeHandler::new())),
+            queue: Mutex::new(()),
         }
     }
 
@@ -35,17 +46,80 @@ impl PrivilegeManager {
     ) -> Result<PrivilegedOperationResult> {
         info!("执行权限操作: {:?}", operation);
 
+        // 后端内部发起的操作（如配置变化同步规则）同样需要校验
+        validation::validate_operation(&operation, &ValidationContext::current())?;
         // 所有操作都通过服务处理
         self.execute_service_operation(operation).await
     }
 
-    /// 执行服务操作（仅检查状态，不自动管理服务）
+    /// 作为一个事务依次执行多个操作，任一操作失败时按相反顺序执行补偿操作
+    pub async fn batch_execute(
+        &self,
+        operations: Vec<PrivilegedOperation>,
+    ) -> Result<BatchOperationResult> {
+        info!("批量执行权限操作: {:?}", operations);
+
+        let ctx = ValidationContext::current();
+        for operation in &operations {
+            validation::validate_operation(operation, &ctx)?;
+        }
+        let tun_enabled = crate::config::Config::verge()
+            .latest()
+            .enable_tun_mode
+            .unwrap_or(false);
+        self.run_batch_queued(operations, tun_enabled, |operation| {
+            self.execute_service_operation(operation)
+        })
+        .await
+    }
+
+    /// 整个批量操作（包括回滚）只进入一次调用队列，其他操作不会插入到步骤之间
+    async fn run_batch_queued<F, Fut>(
+        &self,
+        operations: Vec<PrivilegedOperation>,
+        tun_enabled: bool,
+        execute: F,
+    ) -> Result<BatchOperationResult>
+    where
+        F: FnMut(PrivilegedOperation) -> Fut,
+        Fut: Future<Output = Result<PrivilegedOperationResult>>,
+    {
+        self.with_queue(run_batch(operations, tun_enabled, execute))
+            .await
+    }
+
+    /// 持有调用队列执行 `f`，当前任务已持有时直接执行
+    /// TUN 开关通过修改配置生效，配置变化会同步严格路由等特权操作，这些嵌套的操作不再排队，
+    /// 修改配置同样先进入队列，保证队列总是在配置修改锁之前获取
+    pub async fn with_queue<F: Future>(&self, f: F) -> F::Output {
+        if QUEUE_HELD.try_with(|_| ()).is_ok() {
+            return f.await;
+        }
+        let _queue = self.queue.lock().await;
+        QUEUE_HELD.scope((), f).await
+    }
+
+    /// 在调用队列中执行服务操作
     async fn execute_service_operation(
         &self,
         operation: PrivilegedOperation,
+    ) -> Result<PrivilegedOperationResult> {
+        self.with_queue(self.dispatch_service_operation(operation))
+            .await
+    }
+
+    /// 执行服务操作（仅检查状态，不自动管理服务）
+    async fn dispatch_service_operation(
+        &self,
+        operation: PrivilegedOperation,
     ) -> Result<PrivilegedOperationResult> {
         info!("执行服务操作: {:?}", operation);
 
+        // 服务没有提供路由规则接口，直接请求提权执行
+        if let Some(result) = execute_elevated_operation(&operation).await {
+            return Ok(result);
+        }
+
         // 检查是否为关闭操作
         let is_disable_operation = match &operation {
             PrivilegedOperation::SetTunMode { enable } => !enable,
@@ -183,6 +257,14 @@ impl PrivilegeManager {
 
     /// 获取权限状态（纯服务模式）
     pub async fn get_privilege_status(&self) -> PrivilegeStatus {
+        if let Err(e) = crate::utils::dirs::ensure_not_portable("service mode") {
+            return PrivilegeStatus {
+                service_available: false,
+                service_connected: false,
+                current_mode: PrivilegeMode::Disabled,
+                unsupported_reason: Some(e.to_string()),
+            };
+        }
         let service_available = self.service_handler.is_some();
         let service_connected = if let Some(handler) = &self.service_handler {
             handler.is_available().await
@@ -194,6 +276,7 @@ impl PrivilegeManager {
             service_available,
             service_connected,
             current_mode: PrivilegeMode::Service,
+            unsupported_reason: None,
         }
     }
 
@@ -226,3 +309,278 @@ impl PrivilegeManager {
         Ok(())
     }
 }
+
+/// 服务不支持、需要在本进程中提权执行的操作，其他操作返回 `None`
+/// 每次执行都会弹出系统的授权提示
+async fn execute_elevated_operation(
+    operation: &PrivilegedOperation,
+) -> Option<PrivilegedOperationResult> {
+    use crate::utils::platform::strict_routing;
+
+    let result = match operation.clone() {
+        PrivilegedOperation::ApplyStrictRouting { tun_device } => {
+            tokio::task::spawn_blocking(move || strict_routing::apply_rules(&tun_device)).await
+        }
+        PrivilegedOperation::RemoveStrictRouting { tun_device } => {
+            tokio::task::spawn_blocking(move || strict_routing::remove_rules(&tun_device)).await
+        }
+        _ => return None,
+    };
+    let result = result
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+    Some(match result {
+        Ok(()) => PrivilegedOperationResult {
+            success: true,
+            message: None,
+            handler_used: ELEVATED_HANDLER.to_string(),
+        },
+        Err(e) => {
+            error!("提权执行操作失败: {}", e);
+            PrivilegedOperationResult {
+                success: false,
+                message: Some(format!("操作失败: {}", e)),
+                handler_used: ELEVATED_HANDLER.to_string(),
+            }
+        }
+    })
+}
+
+const ELEVATED_HANDLER: &str = "elevated_command";
+
+/// 撤销一个已成功的操作所需的补偿操作，无法撤销的操作返回 `None`
+fn compensation(operation: &PrivilegedOperation, tun_enabled: bool) -> Option<PrivilegedOperation> {
+    match operation {
+        // 只撤销本次批量操作中打开的 TUN
+        PrivilegedOperation::SetTunMode { enable: true } if !tun_enabled => {
+            Some(PrivilegedOperation::SetTunMode { enable: false })
+        }
+        PrivilegedOperation::ApplyStrictRouting { tun_device } => {
+            Some(PrivilegedOperation::RemoveStrictRouting {
+                tun_device: tun_device.clone(),
+            })
+        }
+        _ => None,
+    }
+}
+
+async fn run_batch<F, Fut>(
+    operations: Vec<PrivilegedOperation>,
+    mut tun_enabled: bool,
+    mut execute: F,
+) -> Result<BatchOperationResult>
+where
+    F: FnMut(PrivilegedOperation) -> Fut,
+    Fut: Future<Output = Result<PrivilegedOperationResult>>,
+{
+    let mut results = Vec::with_capacity(operations.len());
+    let mut compensations = Vec::new();
+    let mut batch_success = true;
+
+    let failed = |e: anyhow::Error| PrivilegedOperationResult {
+        success: false,
+        message: Some(format!("操作失败: {}", e)),
+        handler_used: "batch".to_string(),
+    };
+    for operation in operations {
+        let undo = compensation(&operation, tun_enabled);
+        let result = execute(operation.clone()).await.unwrap_or_else(failed);
+        let success = result.success;
+        results.push(result);
+        if !success {
+            error!("批量操作中的 {:?} 失败，开始回滚", operation);
+            batch_success = false;
+            break;
+        }
+        if let PrivilegedOperation::SetTunMode { enable } = operation {
+            tun_enabled = enable;
+        }
+        compensations.extend(undo);
+    }
+
+    let mut rollback = Vec::new();
+    if !batch_success {
+        for operation in compensations.into_iter().rev() {
+            let result = execute(operation.clone()).await.unwrap_or_else(failed);
+            if !result.success {
+                warn!("补偿操作 {:?} 失败: {:?}", operation, result.message);
+            }
+            rollback.push(result);
+        }
+    }
+
+    Ok(BatchOperationResult {
+        results,
+        batch_success,
+        rollback,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    fn operation_result(success: bool) -> PrivilegedOperationResult {
+        PrivilegedOperationResult {
+            success,
+            message: None,
+            handler_used: "test".to_string(),
+        }
+    }
+
+    async fn batch(
+        operations: Vec<PrivilegedOperation>,
+        tun_enabled: bool,
+        fail: impl Fn(&PrivilegedOperation) -> bool,
+    ) -> (BatchOperationResult, Vec<String>) {
+        let executed = Mutex::new(Vec::new());
+        let result = run_batch(operations, tun_enabled, |operation| {
+            executed.lock().push(format!("{operation:?}"));
+            let success = !fail(&operation);
+            async move { Ok(operation_result(success)) }
+        })
+        .await
+        .unwrap();
+        (result, executed.into_inner())
+    }
+
+    #[tokio::test]
+    async fn test_batch_rolls_back_enabled_tun() {
+        let operations = vec![
+            PrivilegedOperation::SetTunMode { enable: true },
+            PrivilegedOperation::ApplyStrictRouting {
+                tun_device: "Mihomo".into(),
+            },
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: "core".into(),
+            },
+            PrivilegedOperation::ModifyNetworkSettings { dns: None },
+        ];
+        let (result, executed) = batch(operations, false, |operation| {
+            matches!(operation, PrivilegedOperation::UpdateCorePermissions { .. })
+        })
+        .await;
+
+        assert!(!result.batch_success);
+        // 失败后的操作不再执行
+        assert_eq!(result.results.len(), 3);
+        assert!(!result.results[2].success);
+        assert_eq!(result.rollback.len(), 2);
+        assert_eq!(
+            &executed[3..],
+            [
+                "RemoveStrictRouting { tun_device: \"Mihomo\" }",
+                "SetTunMode { enable: false }"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_keeps_tun_enabled_before() {
+        let operations = vec![
+            PrivilegedOperation::SetTunMode { enable: true },
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: "core".into(),
+            },
+        ];
+        let (result, executed) = batch(operations.clone(), true, |operation| {
+            matches!(operation, PrivilegedOperation::UpdateCorePermissions { .. })
+        })
+        .await;
+        assert!(!result.batch_success);
+        assert!(result.rollback.is_empty());
+        assert_eq!(executed.len(), 2);
+
+        let (result, _) = batch(operations, false, |_| false).await;
+        assert!(result.batch_success);
+        assert_eq!(result.results.len(), 2);
+        assert!(result.rollback.is_empty());
+    }
+
+    fn test_manager() -> PrivilegeManager {
+        PrivilegeManager {
+            service_handler: None,
+            queue: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tun_compensation_does_not_deadlock() {
+        let manager = &test_manager();
+        let operations = vec![
+            PrivilegedOperation::SetTunMode { enable: true },
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: "core".into(),
+            },
+        ];
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            manager.run_batch_queued(operations, false, |operation| {
+                manager.with_queue(async move {
+                    // 服务不可用时关闭 TUN 会修改配置，开启严格路由时在同一任务中再次执行特权操作
+                    if let PrivilegedOperation::SetTunMode { enable: false } = operation {
+                        manager.with_queue(async {}).await;
+                    }
+                    Ok(operation_result(!matches!(
+                        operation,
+                        PrivilegedOperation::UpdateCorePermissions { .. }
+                    )))
+                })
+            }),
+        )
+        .await
+        .expect("compensation deadlocked")
+        .unwrap();
+
+        assert!(!result.batch_success);
+        assert_eq!(result.rollback.len(), 1);
+        assert!(result.rollback[0].success);
+    }
+
+    #[tokio::test]
+    async fn test_batch_holds_queue_between_steps() {
+        let manager = &test_manager();
+        let log = &Mutex::new(Vec::new());
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (gate_tx, gate_rx) = tokio::sync::oneshot::channel();
+        let first = &Mutex::new(Some((started_tx, gate_rx)));
+        let operations = vec![
+            PrivilegedOperation::ApplyStrictRouting {
+                tun_device: "Mihomo".into(),
+            },
+            PrivilegedOperation::ModifyNetworkSettings { dns: None },
+        ];
+        let batch = manager.run_batch_queued(operations, false, |operation| {
+            manager.with_queue(async move {
+                let step = first.lock().take();
+                if let Some((started, gate)) = step {
+                    started.send(()).unwrap();
+                    gate.await.unwrap();
+                }
+                log.lock().push(format!("{operation:?}"));
+                Ok(operation_result(true))
+            })
+        });
+        let other = async {
+            started_rx.await.unwrap();
+            let other = manager.with_queue(async { log.lock().push("other".to_string()) });
+            tokio::pin!(other);
+            // 第一步执行时开始排队
+            assert!(futures::poll!(&mut other).is_pending());
+            gate_tx.send(()).unwrap();
+            other.await;
+        };
+        let (result, ()) = tokio::join!(batch, other);
+
+        assert!(result.unwrap().batch_success);
+        assert_eq!(
+            log.lock().as_slice(),
+            [
+                "ApplyStrictRouting { tun_device: \"Mihomo\" }",
+                "ModifyNetworkSettings { dns: None }",
+                "other"
+            ]
+        );
+    }
+}
@@ -9,6 +9,7 @@ pub mod operations;
 pub mod service_handler;
 pub mod service_utils;
 pub mod simple_service;
+pub mod validation;
 
 /// 需要特权的操作类型
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -20,6 +21,10 @@ pub enum PrivilegedOperation {
     ModifyNetworkSettings { dns: Option<Vec<String>> },
     /// 更新核心权限
     UpdateCorePermissions { core_path: PathBuf },
+    /// 写入严格路由规则，使所有流量经过 TUN
+    ApplyStrictRouting { tun_device: String },
+    /// 移除严格路由规则
+    RemoveStrictRouting { tun_device: String },
 }
 
 /// 特权操作处理器接口
@@ -51,12 +56,24 @@ pub struct PrivilegedOperationResult {
     pub handler_used: String,
 }
 
+/// 批量权限操作结果
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct BatchOperationResult {
+    /// 已执行操作的结果，失败后的操作不再执行
+    pub results: Vec<PrivilegedOperationResult>,
+    pub batch_success: bool,
+    /// 失败后按相反顺序执行的补偿操作结果
+    pub rollback: Vec<PrivilegedOperationResult>,
+}
+
 /// 权限状态（纯服务模式）
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct PrivilegeStatus {
     pub service_available: bool,
     pub service_connected: bool,
     pub current_mode: PrivilegeMode,
+    /// 当前环境不支持服务模式的原因，如便携模式
+    pub unsupported_reason: Option<String>,
 }
 
 /// 权限模式（已简化为纯服务模式）
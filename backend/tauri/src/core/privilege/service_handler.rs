@@ -1,7 +1,7 @@
 use anyhow::Result;
 use tracing::info;
 
-use super::{PrivilegedOperation, PrivilegedOperationHandler, service_utils};
+use super::{PrivilegedOperation, PrivilegedOperationHandler, service_utils, validation};
 use crate::core::service::{control, ipc};
 
 /// 服务模式权限处理器
@@ -30,6 +30,10 @@ impl ServicePrivilegeHandler {
             PrivilegedOperation::ModifyNetworkSettings { dns } => {
                 self.modify_network_settings_via_service(dns.clone()).await
             }
+            PrivilegedOperation::ApplyStrictRouting { .. }
+            | PrivilegedOperation::RemoveStrictRouting { .. } => {
+                anyhow::bail!("服务不支持此操作: {:?}", operation)
+            }
         }
     }
 
@@ -47,10 +51,14 @@ impl ServicePrivilegeHandler {
 
     async fn update_core_permissions_via_service(
         &self,
-        _core_path: std::path::PathBuf,
+        core_path: std::path::PathBuf,
     ) -> Result<()> {
         info!("通过服务更新核心权限");
 
+        // 校验与执行之间文件可能被替换，执行前再次确认仍是应用管理的核心
+        let operation = PrivilegedOperation::UpdateCorePermissions { core_path };
+        validation::validate_operation(&operation, &validation::ValidationContext::current())?;
+
         // 服务模式下，核心由服务管理，权限由服务处理
         // 这里可以发送重新安装或更新核心权限的请求
 
@@ -81,6 +89,9 @@ impl ServicePrivilegeHandler {
     fn supports_operation(&self, operation: &PrivilegedOperation) -> bool {
         match operation {
             PrivilegedOperation::SetTunMode { .. } => true,
+            // 服务没有路由规则接口，由 PrivilegeManager 直接请求提权执行
+            PrivilegedOperation::ApplyStrictRouting { .. }
+            | PrivilegedOperation::RemoveStrictRouting { .. } => false,
             PrivilegedOperation::UpdateCorePermissions { .. }
             | PrivilegedOperation::ModifyNetworkSettings { .. } => {
                 // 这些操作需要更多的服务端支持
@@ -63,7 +63,10 @@ pub async fn ensure_service_running() -> Result<()> {
                 Ok(())
             } else if matches!(status.status, ServiceStatus::Stopped) {
                 info!("服务已安装但未运行，尝试启动");
-                control::start_service().await
+                control::start_service().await?;
+                // 服务由本次运行临时启动，退出时一并停止
+                control::mark_started_transiently();
+                Ok(())
             } else {
                 anyhow::bail!("服务未安装，无法启动");
             }
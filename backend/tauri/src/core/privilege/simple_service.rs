@@ -61,12 +61,23 @@ pub struct SimpleServiceStatus {
     pub version: Option<String>,
     /// 状态描述消息
     pub message: String,
+    /// 便携模式下不支持服务
+    pub supported: bool,
 }
 
 /// 获取简化的服务状态
 #[command]
 #[specta::specta]
 pub async fn service_status_summary() -> Result<SimpleServiceStatus, String> {
+    if let Err(e) = crate::utils::dirs::ensure_not_portable("service mode") {
+        return Ok(SimpleServiceStatus {
+            installed: false,
+            status: ServiceStatus::NotInstalled,
+            version: None,
+            message: e.to_string(),
+            supported: false,
+        });
+    }
     match control::status().await {
         Ok(status_info) => {
             let message = service_utils::get_service_status_message().await;
@@ -76,6 +87,7 @@ pub async fn service_status_summary() -> Result<SimpleServiceStatus, String> {
                 status: status_info.status,
                 version: status_info.server.map(|s| s.version.to_string()),
                 message,
+                supported: true,
             })
         }
         Err(e) => {
@@ -85,6 +97,7 @@ pub async fn service_status_summary() -> Result<SimpleServiceStatus, String> {
                 status: ServiceStatus::NotInstalled,
                 version: None,
                 message: format!("无法获取服务状态: {}", e),
+                supported: true,
             })
         }
     }
@@ -0,0 +1,364 @@
+//! 来自前端的特权操作在交给 `PrivilegeManager` 之前的校验与限流
+//! 渲染进程可能被注入脚本，路径、DNS、网卡名称等参数都不可信
+use super::PrivilegedOperation;
+use crate::{config::nyanpasu::ClashCore, core::clash::core::find_binary_path, utils::dirs};
+use parking_lot::Mutex;
+use serde::Serialize;
+use specta::Type;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const MAX_DNS_SERVERS: usize = 16;
+/// Linux 的 `IFNAMSIZ` 为 16（含结尾的 0），Windows 的名称更长
+const MAX_DEVICE_NAME_LEN: usize = 64;
+const MAX_BATCH_OPERATIONS: usize = 16;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+const RATE_LIMIT_MAX_REQUESTS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Type)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum PrivilegeCommandError {
+    #[error("path is not a managed core binary: {0}")]
+    NotManagedCore(String),
+    #[error("invalid path: {0}")]
+    InvalidPath(String),
+    #[error("invalid dns server: {0}")]
+    InvalidDns(String),
+    #[error("too many entries: {0}")]
+    TooManyEntries(String),
+    #[error("invalid name: {0}")]
+    InvalidName(String),
+    #[error("privileged features are disabled: {0}")]
+    PrivilegeDisabled(String),
+    /// 需要等待的秒数
+    #[error("too many privileged requests, retry in {0}s")]
+    RateLimited(u64),
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<anyhow::Error> for PrivilegeCommandError {
+    fn from(e: anyhow::Error) -> Self {
+        PrivilegeCommandError::Failed(e.to_string())
+    }
+}
+
+/// 校验所需的环境信息
+#[derive(Debug, Clone)]
+pub struct ValidationContext {
+    /// 应用管理的核心可执行文件，需已规范化
+    pub managed_cores: Vec<PathBuf>,
+    /// 特权功能不可用的原因，如便携模式
+    pub disabled_reason: Option<String>,
+}
+
+impl ValidationContext {
+    pub fn current() -> Self {
+        let managed_cores = [
+            ClashCore::ClashPremium,
+            ClashCore::Mihomo,
+            ClashCore::MihomoAlpha,
+        ]
+        .iter()
+        .filter_map(|core| find_binary_path(&core.into()).ok())
+        .filter_map(|path| dunce::canonicalize(path).ok())
+        .collect();
+        Self {
+            managed_cores,
+            disabled_reason: dirs::ensure_not_portable("privileged operations")
+                .err()
+                .map(|e| e.to_string()),
+        }
+    }
+}
+
+/// 只接受应用管理的核心可执行文件，数据目录中其他用户可写的文件不能获得权限
+fn validate_core_path(path: &Path, ctx: &ValidationContext) -> Result<(), PrivilegeCommandError> {
+    let display = path.display().to_string();
+    if path.as_os_str().is_empty() || path.to_string_lossy().contains('\0') {
+        return Err(PrivilegeCommandError::InvalidPath(display));
+    }
+    // 规范化后再比较，排除 `..` 与符号链接
+    let canonical = dunce::canonicalize(path)
+        .map_err(|e| PrivilegeCommandError::InvalidPath(format!("{display}: {e}")))?;
+    if ctx.managed_cores.contains(&canonical) {
+        Ok(())
+    } else {
+        Err(PrivilegeCommandError::NotManagedCore(display))
+    }
+}
+
+/// 支持 IP 与 DoH 地址
+fn validate_dns_server(server: &str) -> Result<(), PrivilegeCommandError> {
+    let invalid = || PrivilegeCommandError::InvalidDns(server.to_string());
+    if server.parse::<IpAddr>().is_ok() {
+        return Ok(());
+    }
+    let url = url::Url::parse(server).map_err(|_| invalid())?;
+    if url.scheme() == "https" && url.host_str().is_some() && !server.contains('\0') {
+        Ok(())
+    } else {
+        Err(invalid())
+    }
+}
+
+fn validate_name(name: &str, max_len: usize) -> Result<(), PrivilegeCommandError> {
+    if name.is_empty() || name.len() > max_len || name.chars().any(char::is_control) {
+        return Err(PrivilegeCommandError::InvalidName(
+            name.escape_debug().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 网卡名称会传给防火墙与路由命令，只允许常见字符
+pub(crate) fn validate_device_name(name: &str) -> Result<(), PrivilegeCommandError> {
+    validate_name(name, MAX_DEVICE_NAME_LEN)?;
+    if name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        Ok(())
+    } else {
+        Err(PrivilegeCommandError::InvalidName(
+            name.escape_debug().to_string(),
+        ))
+    }
+}
+
+fn check_count(what: &str, count: usize, max: usize) -> Result<(), PrivilegeCommandError> {
+    if count > max {
+        return Err(PrivilegeCommandError::TooManyEntries(format!(
+            "{count} {what}, at most {max} allowed"
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_operation(
+    operation: &PrivilegedOperation,
+    ctx: &ValidationContext,
+) -> Result<(), PrivilegeCommandError> {
+    match operation {
+        PrivilegedOperation::SetTunMode { enable: true } => {
+            if let Some(reason) = &ctx.disabled_reason {
+                return Err(PrivilegeCommandError::PrivilegeDisabled(reason.clone()));
+            }
+        }
+        // 关闭 TUN 总是允许
+        PrivilegedOperation::SetTunMode { enable: false } => {}
+        PrivilegedOperation::UpdateCorePermissions { core_path } => {
+            validate_core_path(core_path, ctx)?;
+        }
+        PrivilegedOperation::ModifyNetworkSettings { dns } => {
+            let dns = dns.as_deref().unwrap_or_default();
+            check_count("dns servers", dns.len(), MAX_DNS_SERVERS)?;
+            dns.iter()
+                .try_for_each(|server| validate_dns_server(server))?;
+        }
+        PrivilegedOperation::ApplyStrictRouting { tun_device }
+        | PrivilegedOperation::RemoveStrictRouting { tun_device } => {
+            validate_device_name(tun_device)?;
+        }
+    }
+    Ok(())
+}
+
+/// 按命令统计固定窗口内的请求次数
+pub struct RateLimiter {
+    window: Duration,
+    max_requests: usize,
+    requests: Mutex<HashMap<&'static str, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(window: Duration, max_requests: usize) -> Self {
+        Self {
+            window,
+            max_requests,
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn global() -> &'static RateLimiter {
+        static LIMITER: once_cell::sync::Lazy<RateLimiter> = once_cell::sync::Lazy::new(|| {
+            RateLimiter::new(RATE_LIMIT_WINDOW, RATE_LIMIT_MAX_REQUESTS)
+        });
+        &LIMITER
+    }
+
+    pub fn check(&self, command: &'static str, now: Instant) -> Result<(), PrivilegeCommandError> {
+        let mut requests = self.requests.lock();
+        let requests = requests.entry(command).or_default();
+        while requests
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= self.window)
+        {
+            requests.pop_front();
+        }
+        if requests.len() >= self.max_requests {
+            let retry_after = self.window - now.duration_since(requests[0]);
+            return Err(PrivilegeCommandError::RateLimited(
+                retry_after.as_secs().max(1),
+            ));
+        }
+        requests.push_back(now);
+        Ok(())
+    }
+}
+
+/// 前端发起的特权命令在执行前调用
+pub fn guard(
+    command: &'static str,
+    operation: Option<&PrivilegedOperation>,
+) -> Result<(), PrivilegeCommandError> {
+    if let Some(operation) = operation {
+        validate_operation(operation, &ValidationContext::current())?;
+    }
+    RateLimiter::global().check(command, Instant::now())
+}
+
+/// 批量操作整体只计一次限流
+pub fn guard_batch(
+    command: &'static str,
+    operations: &[PrivilegedOperation],
+) -> Result<(), PrivilegeCommandError> {
+    if operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(PrivilegeCommandError::TooManyEntries(format!(
+            "{} operations, at most {MAX_BATCH_OPERATIONS}",
+            operations.len()
+        )));
+    }
+    let ctx = ValidationContext::current();
+    for operation in operations {
+        validate_operation(operation, &ctx)?;
+    }
+    RateLimiter::global().check(command, Instant::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(core: &Path) -> ValidationContext {
+        ValidationContext {
+            managed_cores: vec![dunce::canonicalize(core).unwrap()],
+            disabled_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_rejects_malicious_payloads() {
+        let root = tempfile::tempdir().unwrap();
+        let core = root.path().join("mihomo");
+        std::fs::write(&core, b"").unwrap();
+        // 同一目录下放入的其他文件
+        let dropped = root.path().join("dropped");
+        std::fs::write(&dropped, b"").unwrap();
+        let ctx = context(&core);
+
+        let malicious = [
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: "/usr/bin/sudo".into(),
+            },
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: root.path().join("../../../usr/bin/sudo"),
+            },
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: root.path().join("mihomo\0/../../sudo"),
+            },
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: PathBuf::new(),
+            },
+            PrivilegedOperation::UpdateCorePermissions { core_path: dropped },
+            PrivilegedOperation::UpdateCorePermissions {
+                core_path: root.path().to_path_buf(),
+            },
+            PrivilegedOperation::ModifyNetworkSettings {
+                dns: Some(vec!["1.1.1.1".into(); MAX_DNS_SERVERS + 1]),
+            },
+            PrivilegedOperation::ModifyNetworkSettings {
+                dns: Some(vec!["1.1.1.1\0".into()]),
+            },
+            PrivilegedOperation::ModifyNetworkSettings {
+                dns: Some(vec!["http://dns.example/dns-query".into()]),
+            },
+            PrivilegedOperation::ModifyNetworkSettings {
+                dns: Some(vec!["8.8.8.8; rm -rf /".into()]),
+            },
+            PrivilegedOperation::ApplyStrictRouting {
+                tun_device: "Meta'; reboot; echo '".into(),
+            },
+            PrivilegedOperation::RemoveStrictRouting {
+                tun_device: "../../etc".into(),
+            },
+            PrivilegedOperation::RemoveStrictRouting {
+                tun_device: "x".repeat(MAX_DEVICE_NAME_LEN + 1),
+            },
+        ];
+        for operation in malicious {
+            assert!(
+                validate_operation(&operation, &ctx).is_err(),
+                "{operation:?}"
+            );
+        }
+
+        let valid = [
+            PrivilegedOperation::SetTunMode { enable: true },
+            PrivilegedOperation::UpdateCorePermissions { core_path: core },
+            PrivilegedOperation::ModifyNetworkSettings {
+                dns: Some(vec![
+                    "223.5.5.5".into(),
+                    "2606:4700:4700::1111".into(),
+                    "https://dns.alidns.com/dns-query".into(),
+                ]),
+            },
+            PrivilegedOperation::ApplyStrictRouting {
+                tun_device: "utun".into(),
+            },
+        ];
+        for operation in valid {
+            assert_eq!(
+                validate_operation(&operation, &ctx),
+                Ok(()),
+                "{operation:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tun_requires_privileged_features() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = ValidationContext {
+            disabled_reason: Some("portable mode".into()),
+            ..context(root.path())
+        };
+        assert!(matches!(
+            validate_operation(&PrivilegedOperation::SetTunMode { enable: true }, &ctx),
+            Err(PrivilegeCommandError::PrivilegeDisabled(_))
+        ));
+        assert!(
+            validate_operation(&PrivilegedOperation::SetTunMode { enable: false }, &ctx).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limiter = RateLimiter::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        assert!(limiter.check("a", start).is_ok());
+        assert!(limiter.check("a", start).is_ok());
+        assert_eq!(
+            limiter.check("a", start + Duration::from_secs(15)),
+            Err(PrivilegeCommandError::RateLimited(45))
+        );
+        // 各命令分别计数
+        assert!(limiter.check("b", start).is_ok());
+        assert!(limiter.check("a", start + Duration::from_secs(60)).is_ok());
+    }
+}
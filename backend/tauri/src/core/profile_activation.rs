@@ -0,0 +1,389 @@
+//! 串行执行配置激活，避免两次激活同时写入运行时配置、重启核心
+//! 同一时间只有一个请求在执行、一个请求在排队：目标相同的请求合并为同一个 ticket，
+//! 新请求取代排队中的请求；执行中的请求在应用到核心之前发现已被取代时取消
+use crate::{
+    config::{Config, Profiles, ProfilesBuilder, profile::item_type::ProfileUid},
+    core::{activation, connection_interruption, handle::Handle, profile_switch},
+    log_err,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::sync::Arc;
+
+const ACTIVATION_EVENT: &str = "profile-activation";
+
+pub type ActivationTicket = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivationPhase {
+    Queued,
+    Preparing,
+    /// 正在应用到核心，不再取消
+    Applying,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum ActivationStatus {
+    Queued,
+    Preparing,
+    Applying,
+    Finished,
+    Failed(String),
+    /// 排队时被新的请求取代
+    Superseded,
+    /// 执行中被新的请求取代，已丢弃写入的 draft
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct ActivationProgress {
+    pub ticket: ActivationTicket,
+    pub uid: ProfileUid,
+    pub status: ActivationStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct ActivationEntry {
+    pub ticket: ActivationTicket,
+    pub uid: ProfileUid,
+    pub phase: ActivationPhase,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct CurrentActivation {
+    pub running: Option<ActivationEntry>,
+    pub queued: Option<ActivationEntry>,
+}
+
+#[async_trait]
+pub trait ProfileActivator: Send + Sync + 'static {
+    /// 把目标配置写入 draft，此后仍可以丢弃
+    async fn prepare(&self, uid: &ProfileUid) -> Result<()>;
+    /// 应用到核心并保存
+    async fn commit(&self, uid: &ProfileUid) -> Result<()>;
+    /// 丢弃 `prepare` 写入的 draft
+    fn discard(&self);
+
+    fn report(&self, progress: ActivationProgress) {
+        log_err!(Handle::emit(ACTIVATION_EVENT, progress));
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    last_ticket: ActivationTicket,
+    running: Option<ActivationEntry>,
+    queued: Option<ActivationEntry>,
+    worker: bool,
+}
+
+pub struct ActivationQueue<A> {
+    activator: Arc<A>,
+    state: Arc<Mutex<QueueState>>,
+    /// 其他修改当前配置的操作同样需要持有，保证不会与激活交错
+    exclusive: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl<A> Clone for ActivationQueue<A> {
+    fn clone(&self) -> Self {
+        Self {
+            activator: self.activator.clone(),
+            state: self.state.clone(),
+            exclusive: self.exclusive.clone(),
+        }
+    }
+}
+
+impl<A: ProfileActivator> ActivationQueue<A> {
+    pub fn new(activator: A) -> Self {
+        Self {
+            activator: Arc::new(activator),
+            state: Default::default(),
+            exclusive: Default::default(),
+        }
+    }
+
+    fn report(&self, entry: &ActivationEntry, status: ActivationStatus) {
+        self.activator.report(ActivationProgress {
+            ticket: entry.ticket,
+            uid: entry.uid.clone(),
+            status,
+        });
+    }
+
+    /// 提交激活请求，返回用于追踪进度的 ticket
+    pub fn activate(&self, uid: ProfileUid) -> ActivationTicket {
+        let mut state = self.state.lock();
+        if let Some(queued) = &state.queued
+            && queued.uid == uid
+        {
+            return queued.ticket;
+        }
+        if state.queued.is_none()
+            && let Some(running) = &state.running
+            && running.uid == uid
+        {
+            return running.ticket;
+        }
+        state.last_ticket += 1;
+        let entry = ActivationEntry {
+            ticket: state.last_ticket,
+            uid,
+            phase: ActivationPhase::Queued,
+        };
+        if let Some(superseded) = state.queued.replace(entry.clone()) {
+            self.report(&superseded, ActivationStatus::Superseded);
+        }
+        self.report(&entry, ActivationStatus::Queued);
+        if !state.worker {
+            state.worker = true;
+            tauri::async_runtime::spawn(self.clone().run());
+        }
+        entry.ticket
+    }
+
+    pub fn current(&self) -> CurrentActivation {
+        let state = self.state.lock();
+        CurrentActivation {
+            running: state.running.clone(),
+            queued: state.queued.clone(),
+        }
+    }
+
+    /// 等待执行中的激活完成，并阻止新的激活开始
+    pub async fn exclusive(&self) -> tokio::sync::OwnedMutexGuard<()> {
+        self.exclusive.clone().lock_owned().await
+    }
+
+    fn set_phase(&self, phase: ActivationPhase) {
+        if let Some(running) = &mut self.state.lock().running {
+            running.phase = phase;
+        }
+    }
+
+    async fn run(self) {
+        loop {
+            let entry = {
+                let mut state = self.state.lock();
+                match state.queued.take() {
+                    Some(entry) => {
+                        state.running = Some(entry.clone());
+                        entry
+                    }
+                    None => {
+                        state.running = None;
+                        state.worker = false;
+                        return;
+                    }
+                }
+            };
+            let _guard = self.exclusive.lock().await;
+            self.set_phase(ActivationPhase::Preparing);
+            self.report(&entry, ActivationStatus::Preparing);
+            if let Err(e) = self.activator.prepare(&entry.uid).await {
+                self.activator.discard();
+                self.report(&entry, ActivationStatus::Failed(format!("{e:#}")));
+                continue;
+            }
+            // 应用到核心之前是唯一的取消点
+            let superseded = {
+                let mut state = self.state.lock();
+                let superseded = state.queued.is_some();
+                if !superseded && let Some(running) = &mut state.running {
+                    running.phase = ActivationPhase::Applying;
+                }
+                superseded
+            };
+            if superseded {
+                self.activator.discard();
+                self.report(&entry, ActivationStatus::Cancelled);
+                continue;
+            }
+            self.report(&entry, ActivationStatus::Applying);
+            match self.activator.commit(&entry.uid).await {
+                Ok(()) => self.report(&entry, ActivationStatus::Finished),
+                Err(e) => {
+                    self.activator.discard();
+                    tracing::error!("failed to activate profile `{}`: {e:?}", entry.uid);
+                    self.report(&entry, ActivationStatus::Failed(format!("{e:#}")));
+                }
+            }
+        }
+    }
+}
+
+/// 将配置设为唯一的当前配置并应用到核心
+pub struct CoreProfileActivator;
+
+#[async_trait]
+impl ProfileActivator for CoreProfileActivator {
+    async fn prepare(&self, uid: &ProfileUid) -> Result<()> {
+        let profiles = Config::profiles();
+        let mut draft = profiles.draft();
+        draft.get_item(uid)?;
+        let mut builder = ProfilesBuilder::default();
+        builder.current(vec![uid.clone()]);
+        draft.apply(builder);
+        Ok(())
+    }
+
+    async fn commit(&self, _uid: &ProfileUid) -> Result<()> {
+        let applied = activation::track("switch", profile_switch::apply_switch(false)).await?;
+        Handle::refresh_clash();
+        Handle::refresh_profiles();
+        Config::profiles().apply();
+        Config::profiles().data().save_file()?;
+        if applied != profile_switch::SwitchApplied::Drained {
+            let _ =
+                connection_interruption::ConnectionInterruptionService::on_profile_change().await;
+        }
+        Ok(())
+    }
+
+    fn discard(&self) {
+        Config::profiles().discard();
+    }
+}
+
+/// 与配置激活互斥地修改配置列表，修改在返回前提交
+pub async fn modify_profiles<T>(f: impl FnOnce(&mut Profiles) -> Result<T>) -> Result<T> {
+    let _exclusive = global().exclusive().await;
+    let committer = Config::profiles().auto_commit();
+    let mut draft = committer.draft();
+    f(&mut draft)
+}
+
+static QUEUE: Lazy<ActivationQueue<CoreProfileActivator>> =
+    Lazy::new(|| ActivationQueue::new(CoreProfileActivator));
+
+pub fn global() -> &'static ActivationQueue<CoreProfileActivator> {
+    &QUEUE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::{Notify, Semaphore, mpsc};
+
+    /// 模拟核心：prepare 写入 draft，commit 将 draft 设为当前配置
+    /// prepare 开始时通知测试，由测试放行后才完成，执行顺序不依赖计时
+    struct MockCore {
+        draft: Mutex<Option<ProfileUid>>,
+        active: Mutex<Option<ProfileUid>>,
+        commits: Mutex<Vec<ProfileUid>>,
+        progress: Mutex<Vec<ActivationProgress>>,
+        entered: mpsc::UnboundedSender<ProfileUid>,
+        release: Semaphore,
+        reported: Notify,
+    }
+
+    impl MockCore {
+        fn new() -> (Arc<Self>, mpsc::UnboundedReceiver<ProfileUid>) {
+            let (entered, rx) = mpsc::unbounded_channel();
+            let core = Self {
+                draft: Default::default(),
+                active: Default::default(),
+                commits: Default::default(),
+                progress: Default::default(),
+                entered,
+                release: Semaphore::new(0),
+                reported: Notify::new(),
+            };
+            (Arc::new(core), rx)
+        }
+    }
+
+    #[async_trait]
+    impl ProfileActivator for Arc<MockCore> {
+        async fn prepare(&self, uid: &ProfileUid) -> Result<()> {
+            self.entered.send(uid.clone()).unwrap();
+            self.release.acquire().await.unwrap().forget();
+            *self.draft.lock() = Some(uid.clone());
+            Ok(())
+        }
+
+        async fn commit(&self, uid: &ProfileUid) -> Result<()> {
+            let draft = self.draft.lock().take();
+            assert_eq!(draft.as_ref(), Some(uid));
+            *self.active.lock() = draft;
+            self.commits.lock().push(uid.clone());
+            Ok(())
+        }
+
+        fn discard(&self) {
+            self.draft.lock().take();
+        }
+
+        fn report(&self, progress: ActivationProgress) {
+            self.progress.lock().push(progress);
+            self.reported.notify_waiters();
+        }
+    }
+
+    fn final_status(core: &MockCore, ticket: ActivationTicket) -> Option<ActivationStatus> {
+        core.progress
+            .lock()
+            .iter()
+            .rev()
+            .find(|p| p.ticket == ticket)
+            .map(|p| p.status.clone())
+    }
+
+    async fn wait_finished(core: &MockCore, ticket: ActivationTicket) {
+        loop {
+            let reported = core.reported.notified();
+            if final_status(core, ticket) == Some(ActivationStatus::Finished) {
+                return;
+            }
+            reported.await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_competing_activations() {
+        let (core, mut entered) = MockCore::new();
+        let queue = ActivationQueue::new(core.clone());
+
+        let a = queue.activate("a".into());
+        assert_eq!(entered.recv().await.as_deref(), Some("a"));
+        let b = queue.activate("b".into());
+        let c = queue.activate("c".into());
+        assert_ne!(b, c);
+        core.release.add_permits(1);
+        assert_eq!(entered.recv().await.as_deref(), Some("c"));
+        core.release.add_permits(1);
+        wait_finished(&core, c).await;
+
+        assert_eq!(core.active.lock().as_deref(), Some("c"));
+        assert_eq!(*core.commits.lock(), ["c"]);
+        assert!(core.draft.lock().is_none());
+        assert_eq!(final_status(&core, a), Some(ActivationStatus::Cancelled));
+        assert_eq!(final_status(&core, b), Some(ActivationStatus::Superseded));
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_same_profile() {
+        let (core, mut entered) = MockCore::new();
+        let queue = ActivationQueue::new(core.clone());
+
+        let a = queue.activate("a".into());
+        assert_eq!(entered.recv().await.as_deref(), Some("a"));
+        assert_eq!(queue.activate("a".into()), a);
+        let b = queue.activate("b".into());
+        assert_eq!(queue.activate("b".into()), b);
+        assert_eq!(queue.current().queued.map(|entry| entry.ticket), Some(b));
+        core.release.add_permits(1);
+        assert_eq!(entered.recv().await.as_deref(), Some("b"));
+        core.release.add_permits(1);
+        wait_finished(&core, b).await;
+
+        assert_eq!(core.active.lock().as_deref(), Some("b"));
+        assert_eq!(*core.commits.lock(), ["b"]);
+    }
+}
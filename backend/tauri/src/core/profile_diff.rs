@@ -0,0 +1,398 @@
+//! 定时更新订阅后对比前后两份配置，告诉用户节点、代理组与规则的变化
+//! 名称变化的节点按 `server:port` 匹配，名称相似度不足时仍视为删除与新增
+use crate::{
+    config::{Config, ProfileMetaGetter},
+    core::{handle::Handle, profile_sync::ClashConfigDiff},
+    log_err,
+    utils::dirs,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const PROFILE_UPDATED_EVENT: &str = "profile-updated";
+/// 超过该大小（MB）的配置不计算差异，默认为 10
+const DEFAULT_DIFF_MAX_MB: u64 = 10;
+/// 名称的相似度低于该值时不认为是改名
+const RENAME_SIMILARITY: f64 = 0.55;
+
+/// 同一时间只读写一次记录文件
+static STATE: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct NodeRename {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct ProfileDiff {
+    pub added_nodes: Vec<String>,
+    pub removed_nodes: Vec<String>,
+    pub renamed_nodes: Vec<NodeRename>,
+    pub added_groups: Vec<String>,
+    pub removed_groups: Vec<String>,
+    pub added_rules: u32,
+    pub removed_rules: u32,
+    /// 每种规则类型数量的变化，不含没有变化的类型
+    pub rule_delta: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ProfileUpdated {
+    pub uid: String,
+    /// 更新时间（unix 秒）
+    pub updated_at: i64,
+    pub diff: Option<ProfileDiff>,
+    /// 配置超过大小限制，没有计算差异
+    pub diff_skipped: bool,
+}
+
+struct Node {
+    name: String,
+    endpoint: Option<String>,
+}
+
+fn nodes(config: &Mapping) -> Vec<Node> {
+    config
+        .get("proxies")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_mapping)
+        .filter_map(|proxy| {
+            let name = proxy.get("name")?.as_str()?.to_string();
+            let server = proxy.get("server").and_then(Value::as_str);
+            let port = proxy.get("port").and_then(|port| match port {
+                Value::Number(n) => n.as_u64().map(|n| n.to_string()),
+                Value::String(s) => Some(s.trim().to_string()),
+                _ => None,
+            });
+            let endpoint = server
+                .zip(port)
+                .map(|(server, port)| format!("{server}:{port}"));
+            Some(Node { name, endpoint })
+        })
+        .collect()
+}
+
+fn group_names(config: &Mapping) -> Vec<String> {
+    config
+        .get("proxy-groups")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(|group| group.get("name")?.as_str().map(str::to_string))
+        .collect()
+}
+
+fn rule_types(config: &Mapping) -> HashMap<String, i64> {
+    let mut types = HashMap::new();
+    for rule in config
+        .get("rules")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        let ty = rule.split(',').next().unwrap_or_default().trim();
+        *types.entry(ty.to_ascii_uppercase()).or_insert(0) += 1;
+    }
+    types
+}
+
+/// 字符二元组的 Dice 系数，适用于包含中文与 emoji 的节点名称
+fn similarity(a: &str, b: &str) -> f64 {
+    fn bigrams(s: &str) -> Vec<(char, char)> {
+        let chars = s.to_lowercase().chars().collect::<Vec<_>>();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    }
+    if a == b {
+        return 1.0;
+    }
+    let (a, mut b) = (bigrams(a), bigrams(b));
+    let total = a.len() + b.len();
+    if total == 0 {
+        return 0.0;
+    }
+    let mut common = 0;
+    for pair in a {
+        if let Some(i) = b.iter().position(|other| *other == pair) {
+            b.swap_remove(i);
+            common += 1;
+        }
+    }
+    (2 * common) as f64 / total as f64
+}
+
+/// 从删除与新增的节点中找出改名的节点，每个节点只匹配一次
+fn match_renames(removed: &mut Vec<&Node>, added: &mut Vec<&Node>) -> Vec<NodeRename> {
+    let mut candidates = Vec::new();
+    for (i, old) in removed.iter().enumerate() {
+        for (j, new) in added.iter().enumerate() {
+            if old.endpoint.is_some() && old.endpoint == new.endpoint {
+                let score = similarity(&old.name, &new.name);
+                if score >= RENAME_SIMILARITY {
+                    candidates.push((score, i, j));
+                }
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let (mut used_old, mut used_new) = (HashSet::new(), HashSet::new());
+    let mut renames = Vec::new();
+    for (_, i, j) in candidates {
+        if used_old.contains(&i) || used_new.contains(&j) {
+            continue;
+        }
+        used_old.insert(i);
+        used_new.insert(j);
+        renames.push(NodeRename {
+            from: removed[i].name.clone(),
+            to: added[j].name.clone(),
+        });
+    }
+    let mut index = 0;
+    removed.retain(|_| {
+        index += 1;
+        !used_old.contains(&(index - 1))
+    });
+    let mut index = 0;
+    added.retain(|_| {
+        index += 1;
+        !used_new.contains(&(index - 1))
+    });
+    renames
+}
+
+impl ProfileDiff {
+    pub fn compute(old: &Mapping, new: &Mapping) -> Self {
+        let (old_nodes, new_nodes) = (nodes(old), nodes(new));
+        let old_names = old_nodes
+            .iter()
+            .map(|n| n.name.as_str())
+            .collect::<HashSet<_>>();
+        let new_names = new_nodes
+            .iter()
+            .map(|n| n.name.as_str())
+            .collect::<HashSet<_>>();
+        let mut removed = old_nodes
+            .iter()
+            .filter(|n| !new_names.contains(n.name.as_str()))
+            .collect::<Vec<_>>();
+        let mut added = new_nodes
+            .iter()
+            .filter(|n| !old_names.contains(n.name.as_str()))
+            .collect::<Vec<_>>();
+        let renamed_nodes = match_renames(&mut removed, &mut added);
+
+        let (old_groups, new_groups) = (group_names(old), group_names(new));
+        let added_groups = new_groups
+            .iter()
+            .filter(|g| !old_groups.contains(g))
+            .cloned()
+            .collect();
+        let removed_groups = old_groups
+            .iter()
+            .filter(|g| !new_groups.contains(g))
+            .cloned()
+            .collect();
+
+        let mut rule_delta = BTreeMap::new();
+        for (ty, n) in rule_types(new) {
+            *rule_delta.entry(ty).or_insert(0) += n;
+        }
+        for (ty, n) in rule_types(old) {
+            *rule_delta.entry(ty).or_insert(0) -= n;
+        }
+        rule_delta.retain(|_, n| *n != 0);
+        let rules = ClashConfigDiff::compute(old, new);
+
+        Self {
+            added_nodes: added.into_iter().map(|n| n.name.clone()).collect(),
+            removed_nodes: removed.into_iter().map(|n| n.name.clone()).collect(),
+            renamed_nodes,
+            added_groups,
+            removed_groups,
+            added_rules: rules.added_rules,
+            removed_rules: rules.removed_rules,
+            rule_delta,
+        }
+    }
+}
+
+fn load_state() -> HashMap<String, ProfileUpdated> {
+    dirs::profile_diff_state_path()
+        .ok()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_update(update: &ProfileUpdated) -> Result<()> {
+    let _guard = STATE.lock();
+    let mut state = load_state();
+    // 已删除的配置不再保留记录
+    {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        state.retain(|uid, _| profiles.get_item(uid).is_ok());
+    }
+    state.insert(update.uid.clone(), update.clone());
+    std::fs::write(
+        dirs::profile_diff_state_path()?,
+        serde_json::to_vec(&state)?,
+    )?;
+    Ok(())
+}
+
+/// 读取到的配置内容，超过大小限制的配置不读入内存
+pub enum ProfileSnapshot {
+    Content(String),
+    TooLarge,
+}
+
+fn max_diff_bytes() -> u64 {
+    Config::verge()
+        .latest()
+        .profile_diff_max_mb
+        .unwrap_or(DEFAULT_DIFF_MAX_MB)
+        * 1024
+        * 1024
+}
+
+/// 更新前读取配置内容，供 [`record_update`] 对比
+pub async fn snapshot(uid: &str) -> Option<ProfileSnapshot> {
+    let path = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let file = profiles.get_item(uid).ok()?.file().to_string();
+        dirs::app_profiles_dir().ok()?.join(file)
+    };
+    let size = tokio::fs::metadata(&path).await.ok()?.len();
+    if size > max_diff_bytes() {
+        return Some(ProfileSnapshot::TooLarge);
+    }
+    tokio::fs::read_to_string(&path)
+        .await
+        .ok()
+        .map(ProfileSnapshot::Content)
+}
+
+/// 对比更新前后的配置，保存结果并发送 `profile-updated` 事件
+pub async fn record_update(uid: &str, previous: Option<ProfileSnapshot>) -> Result<()> {
+    let contents = match (previous, snapshot(uid).await) {
+        (Some(ProfileSnapshot::TooLarge), _) | (_, Some(ProfileSnapshot::TooLarge)) => None,
+        (previous, current) => {
+            let content = |snapshot: Option<ProfileSnapshot>| match snapshot {
+                Some(ProfileSnapshot::Content(content)) => content,
+                _ => String::new(),
+            };
+            Some((content(previous), content(current)))
+        }
+    };
+    let diff_skipped = contents.is_none();
+    let diff = if let Some((previous, current)) = contents {
+        // 大配置解析耗时较长，不在异步运行时中执行
+        tokio::task::spawn_blocking(move || -> Result<ProfileDiff> {
+            let old = serde_yaml::from_str::<Mapping>(&previous).unwrap_or_default();
+            let new = serde_yaml::from_str::<Mapping>(&current)?;
+            Ok(ProfileDiff::compute(&old, &new))
+        })
+        .await?
+        .inspect_err(|e| tracing::warn!("failed to compute diff of profile {uid}: {e:?}"))
+        .ok()
+    } else {
+        tracing::info!("profile {uid} is too large, skip computing the diff");
+        None
+    };
+    let update = ProfileUpdated {
+        uid: uid.to_string(),
+        updated_at: chrono::Utc::now().timestamp(),
+        diff,
+        diff_skipped,
+    };
+    log_err!(Handle::emit(PROFILE_UPDATED_EVENT, &update));
+    tokio::task::spawn_blocking(move || save_update(&update)).await?
+}
+
+pub fn last_diff(uid: &str) -> Option<ProfileUpdated> {
+    let _guard = STATE.lock();
+    load_state().remove(uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_diff() {
+        let old: Mapping = serde_yaml::from_str(
+            r#"
+proxies:
+  - { name: HK 01, type: ss, server: hk.example.com, port: 443 }
+  - { name: JP 01, type: ss, server: jp.example.com, port: 443 }
+  - { name: US 01, type: ss, server: us.example.com, port: 443 }
+  - { name: SG 01, type: ss, server: relay.example.com, port: 8001 }
+proxy-groups:
+  - { name: Proxy, type: select, proxies: [HK 01] }
+  - { name: Streaming, type: select, proxies: [HK 01] }
+rules:
+  - DOMAIN,a.com,Proxy
+  - DOMAIN-SUFFIX,b.com,Proxy
+  - MATCH,Proxy
+"#,
+        )
+        .unwrap();
+        let new: Mapping = serde_yaml::from_str(
+            r#"
+proxies:
+  - { name: HK 01 | 1x, type: ss, server: hk.example.com, port: 443 }
+  - { name: JP 01, type: ss, server: jp.example.com, port: "443" }
+  - { name: TW 01, type: ss, server: relay.example.com, port: 8001 }
+  - { name: KR 01, type: ss, server: kr.example.com, port: 443 }
+proxy-groups:
+  - { name: Proxy, type: select, proxies: [HK 01 | 1x] }
+  - { name: Auto, type: url-test, proxies: [HK 01 | 1x] }
+rules:
+  - DOMAIN,a.com,Proxy
+  - DOMAIN,c.com,Proxy
+  - DOMAIN,d.com,Proxy
+  - MATCH,Proxy
+"#,
+        )
+        .unwrap();
+        let diff = ProfileDiff::compute(&old, &new);
+        assert_eq!(
+            diff.renamed_nodes,
+            [NodeRename {
+                from: "HK 01".into(),
+                to: "HK 01 | 1x".into()
+            }]
+        );
+        // 同一地址但名称差异过大时不认为是改名
+        assert_eq!(diff.removed_nodes, ["US 01", "SG 01"]);
+        assert_eq!(diff.added_nodes, ["TW 01", "KR 01"]);
+        assert_eq!(diff.added_groups, ["Auto"]);
+        assert_eq!(diff.removed_groups, ["Streaming"]);
+        assert_eq!((diff.added_rules, diff.removed_rules), (2, 1));
+        assert_eq!(
+            diff.rule_delta,
+            BTreeMap::from([("DOMAIN".into(), 2), ("DOMAIN-SUFFIX".into(), -1)])
+        );
+
+        assert_eq!(ProfileDiff::compute(&new, &new), ProfileDiff::default());
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("HK 01", "HK 01"), 1.0);
+        assert!(similarity("HK 01", "HK 01 | 1x") >= RENAME_SIMILARITY);
+        assert!(similarity("HK 01", "JP 01") < RENAME_SIMILARITY);
+        assert!(similarity("香港 01", "香港 01 专线") >= RENAME_SIMILARITY);
+    }
+}
@@ -0,0 +1,308 @@
+//! 切换配置前评估对活跃连接的影响
+//! 核心支持热重载时可以保留已有连接（drain），失败时回退到重启核心
+use crate::{
+    config::{
+        Config,
+        nyanpasu::{ClashCore, ProfileSwitchGuard},
+    },
+    core::{
+        CoreManager, activation,
+        clash::{
+            api::{self, ConnectionItem, ConnectionsRes},
+            core_info::{CoreFeature, CoreFlavor, CoreInfo},
+        },
+        handle::Handle,
+    },
+    enhance::{self, TranslationWarning},
+    log_err,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::time::Duration;
+
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(2);
+const CORE_SWITCH_REQUIRED_EVENT: &str = "core-switch-required";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ConnSummary {
+    pub id: String,
+    /// 域名，缺失时为目标地址
+    pub host: String,
+    pub chains: Vec<String>,
+    pub bytes: u64,
+    pub age_secs: u64,
+}
+
+impl ConnSummary {
+    fn new(conn: &ConnectionItem, age_secs: u64) -> Self {
+        let metadata = &conn.metadata;
+        let host = if metadata.host.is_empty() {
+            format!("{}:{}", metadata.destination_ip, metadata.destination_port)
+        } else {
+            metadata.host.clone()
+        };
+        Self {
+            id: conn.id.clone(),
+            host,
+            chains: conn.chains.clone(),
+            bytes: conn.upload + conn.download,
+            age_secs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct SwitchImpact {
+    pub active_connections: usize,
+    pub total_transferring_bytes: u64,
+    /// 超过阈值的长连接，按传输量降序
+    pub long_lived: Vec<ConnSummary>,
+}
+
+impl SwitchImpact {
+    pub fn compute(
+        snapshot: &ConnectionsRes,
+        guard: &ProfileSwitchGuard,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut long_lived = snapshot
+            .connections
+            .iter()
+            .filter_map(|conn| {
+                // 无法解析建立时间时只按传输量判断
+                let age_secs = DateTime::parse_from_rfc3339(&conn.start)
+                    .map(|start| (now - start.with_timezone(&Utc)).num_seconds().max(0) as u64)
+                    .unwrap_or(0);
+                let bytes = conn.upload + conn.download;
+                (bytes >= guard.min_bytes || age_secs >= guard.min_age_secs)
+                    .then(|| ConnSummary::new(conn, age_secs))
+            })
+            .collect::<Vec<_>>();
+        long_lived.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+        Self {
+            active_connections: snapshot.connections.len(),
+            total_transferring_bytes: snapshot
+                .connections
+                .iter()
+                .map(|conn| conn.upload + conn.download)
+                .sum(),
+            long_lived,
+        }
+    }
+
+    pub fn requires_confirmation(&self) -> bool {
+        !self.long_lived.is_empty()
+    }
+}
+
+/// 按配置的阈值评估切换影响，未开启检查或核心不可用时返回 None
+pub async fn assess_switch_impact() -> Option<SwitchImpact> {
+    let guard = Config::verge().latest().profile_switch_guard?;
+    match tokio::time::timeout(SNAPSHOT_TIMEOUT, api::get_connections()).await {
+        Ok(Ok(snapshot)) => Some(SwitchImpact::compute(&snapshot, &guard, Utc::now())),
+        Ok(Err(e)) => {
+            tracing::debug!("failed to get connections snapshot: {e:?}");
+            None
+        }
+        Err(_) => {
+            tracing::debug!("timed out getting connections snapshot");
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchApplied {
+    /// 热重载，保留已有连接
+    Drained,
+    /// 常规的配置更新，按 `break_when_profile_change` 中断连接
+    Reloaded,
+    /// 热重载失败，已重启核心
+    Restarted,
+}
+
+/// 切换配置需要更换的核心
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct CoreSwitch {
+    pub from: ClashCore,
+    pub to: ClashCore,
+    /// 指定了核心的配置，恢复全局核心时为第一个当前配置
+    pub profile: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum SwitchOutcome {
+    NeedsConfirmation(SwitchImpact),
+    /// 需要更换正在运行的核心，由 `core_switch_confirm` 控制
+    NeedsCoreSwitchConfirmation(CoreSwitch),
+    /// 新配置为适配 clash-rs 需要改写或移除部分字段
+    NeedsCompatibilityConfirmation(Vec<TranslationWarning>),
+    Applied(SwitchApplied),
+}
+
+/// draft 中的配置需要的核心与正在运行的核心不同时返回
+pub fn pending_core_switch() -> Option<CoreSwitch> {
+    let from = CoreManager::global().running_core()?;
+    let to = Config::clash_core();
+    if from == to {
+        return None;
+    }
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let profile = profiles
+        .current_clash_core_source()
+        .map(|(uid, _)| uid)
+        .or(profiles.get_current().first())
+        .cloned()
+        .unwrap_or_default();
+    Some(CoreSwitch { from, to, profile })
+}
+
+/// 开启了 `core_switch_confirm` 且需要更换核心时返回
+pub fn core_switch_requiring_confirmation() -> Option<CoreSwitch> {
+    let confirm = Config::verge()
+        .latest()
+        .core_switch_confirm
+        .unwrap_or_default();
+    if !confirm {
+        return None;
+    }
+    pending_core_switch()
+}
+
+/// 按 draft 中的配置预先生成一次，返回适配 clash-rs 产生的警告
+/// 只有实际核心为 clash-rs 时才需要生成
+pub async fn compatibility_warnings() -> Vec<TranslationWarning> {
+    if CoreFlavor::detect(&Config::clash_core()).await != CoreFlavor::ClashRs {
+        return Vec::new();
+    }
+    let (.., output) = enhance::enhance().await;
+    output.compatibility_warnings
+}
+
+fn supports_hot_reload() -> bool {
+    let core = Config::clash_core();
+    CoreInfo::cached(&core).is_some_and(|info| info.supports(CoreFeature::HotReload))
+}
+
+/// 将 draft 中的配置应用到核心
+pub async fn apply_switch(drain: bool) -> Result<SwitchApplied> {
+    // 新配置指定了其他核心时只能重启
+    if let Some(switch) = pending_core_switch() {
+        tracing::info!(
+            "profile `{}` requires core `{}` instead of `{}`, restart core",
+            switch.profile,
+            switch.to,
+            switch.from
+        );
+        log_err!(Handle::emit(CORE_SWITCH_REQUIRED_EVENT, &switch));
+        return restart_core().await;
+    }
+    if !drain {
+        CoreManager::global().update_config().await?;
+        return Ok(SwitchApplied::Reloaded);
+    }
+    if !supports_hot_reload() {
+        tracing::info!("core does not support hot reload, restart core to switch profile");
+        return restart_core().await;
+    }
+    match CoreManager::global().update_config().await {
+        Ok(()) => Ok(SwitchApplied::Drained),
+        Err(e) => {
+            tracing::warn!("hot reload failed, fallback to restart core: {e:?}");
+            restart_core().await
+        }
+    }
+}
+
+/// 重新生成配置并重启核心
+pub async fn restart_core() -> Result<SwitchApplied> {
+    Config::generate().await?;
+    activation::stage("core restart", CoreManager::global().run_core()).await?;
+    Ok(SwitchApplied::Restarted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> ConnectionsRes {
+        serde_json::from_str(
+            r#"{
+                "downloadTotal": 0,
+                "uploadTotal": 0,
+                "connections": [
+                    {
+                        "id": "download",
+                        "metadata": {"network": "tcp", "host": "cdn.example.com", "destinationIP": "1.1.1.1", "destinationPort": "443"},
+                        "upload": 1024,
+                        "download": 52428800,
+                        "start": "2026-10-16T11:59:30Z",
+                        "chains": ["HK", "Proxy"],
+                        "rule": "Match"
+                    },
+                    {
+                        "id": "call",
+                        "metadata": {"network": "udp", "host": "", "destinationIP": "8.8.8.8", "destinationPort": "3478"},
+                        "upload": 2048,
+                        "download": 4096,
+                        "start": "2026-10-16T11:00:00Z",
+                        "chains": ["DIRECT"]
+                    },
+                    {
+                        "id": "short",
+                        "metadata": {"host": "example.com"},
+                        "upload": 10,
+                        "download": 20,
+                        "start": "2026-10-16T11:59:59Z"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-16T12:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_switch_impact_thresholds() {
+        let impact = SwitchImpact::compute(&snapshot(), &ProfileSwitchGuard::default(), now());
+        assert_eq!(impact.active_connections, 3);
+        assert_eq!(
+            impact.total_transferring_bytes,
+            52428800 + 1024 + 2048 + 4096 + 30
+        );
+        assert!(impact.requires_confirmation());
+        let ids = impact
+            .long_lived
+            .iter()
+            .map(|conn| conn.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["download", "call"]);
+        assert_eq!(impact.long_lived[0].age_secs, 30);
+        assert_eq!(impact.long_lived[1].host, "8.8.8.8:3478");
+        assert_eq!(impact.long_lived[1].age_secs, 3600);
+    }
+
+    #[test]
+    fn test_switch_impact_without_long_lived() {
+        let guard = ProfileSwitchGuard {
+            min_bytes: u64::MAX,
+            min_age_secs: u64::MAX,
+        };
+        let impact = SwitchImpact::compute(&snapshot(), &guard, now());
+        assert!(!impact.requires_confirmation());
+        assert_eq!(impact.active_connections, 3);
+
+        let empty: ConnectionsRes = serde_json::from_str(r#"{"connections": null}"#).unwrap();
+        let impact = SwitchImpact::compute(&empty, &ProfileSwitchGuard::default(), now());
+        assert_eq!(impact.active_connections, 0);
+        assert!(!impact.requires_confirmation());
+    }
+}
@@ -0,0 +1,321 @@
+//! 监听同步目录（Syncthing、Resilio 等）中的配置文件，变化时提示用户导入
+use crate::{
+    config::{
+        Config, ProfileMetaGetter,
+        profile::{
+            item::{LocalProfileBuilder, Profile, ProfileShared},
+            item_type::ProfileItemType,
+        },
+    },
+    core::{handle, profile_activation},
+    feat, log_err,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use notify_debouncer_full::{
+    DebounceEventResult, Debouncer, RecommendedCache, new_debouncer,
+    notify::{EventKind, RecommendedWatcher, RecursiveMode},
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+/// 同步工具通常分块写入，等待写入完成后再处理
+const SYNC_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// 两份配置的规则差异，同一规则出现多次时按次数计算
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClashConfigDiff {
+    pub added_rules: u32,
+    pub removed_rules: u32,
+}
+
+impl ClashConfigDiff {
+    pub fn compute(old: &Mapping, new: &Mapping) -> Self {
+        fn count(config: &Mapping) -> HashMap<Value, i64> {
+            let mut rules = HashMap::new();
+            for rule in config
+                .get("rules")
+                .and_then(Value::as_sequence)
+                .into_iter()
+                .flatten()
+            {
+                // 非字符串的规则按值比较，键的顺序不影响结果
+                let rule = match rule.as_str() {
+                    Some(rule) => Value::String(rule.trim().to_string()),
+                    None => rule.clone(),
+                };
+                *rules.entry(rule).or_insert(0) += 1;
+            }
+            rules
+        }
+
+        let mut delta = count(new);
+        for (rule, n) in count(old) {
+            *delta.entry(rule).or_insert(0) -= n;
+        }
+        let (mut added_rules, mut removed_rules) = (0, 0);
+        for n in delta.into_values() {
+            if n > 0 {
+                added_rules += n as u32;
+            } else {
+                removed_rules += n.unsigned_abs() as u32;
+            }
+        }
+        Self {
+            added_rules,
+            removed_rules,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProfileSyncAvailable {
+    pub filename: String,
+    pub added_rules: u32,
+    pub removed_rules: u32,
+}
+
+pub struct ProfileSyncManager {
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher, RecommendedCache>>>,
+    /// 等待用户确认的文件
+    pending: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl ProfileSyncManager {
+    pub fn global() -> &'static Self {
+        static MANAGER: OnceLock<ProfileSyncManager> = OnceLock::new();
+        MANAGER.get_or_init(|| Self {
+            debouncer: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 按 `profile_sync_dir` 重新开始监听，未设置或为空时停止
+    pub fn start(&self) -> Result<()> {
+        self.stop();
+        let Some(dir) = sync_dir(Config::verge().latest().profile_sync_dir.as_deref()) else {
+            return Ok(());
+        };
+        if !dir.is_dir() {
+            bail!("profile sync dir `{}` is not a directory", dir.display());
+        }
+        let mut debouncer = new_debouncer(SYNC_DEBOUNCE, None, |result: DebounceEventResult| {
+            let events = match result {
+                Ok(events) => events,
+                Err(errors) => {
+                    tracing::warn!("profile sync watcher error: {errors:?}");
+                    return;
+                }
+            };
+            let mut paths = events
+                .iter()
+                .filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+                .flat_map(|event| event.paths.iter())
+                .filter(|path| is_profile_file(path))
+                .cloned()
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths.dedup();
+            for path in paths {
+                tauri::async_runtime::spawn(async move {
+                    log_err!(
+                        ProfileSyncManager::global().on_file_changed(&path).await,
+                        "failed to check synced profile"
+                    );
+                });
+            }
+        })?;
+        debouncer.watch(&dir, RecursiveMode::NonRecursive)?;
+        tracing::info!("watching profile sync dir: {}", dir.display());
+        *self.debouncer.lock() = Some(debouncer);
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        self.debouncer.lock().take();
+        self.pending.lock().clear();
+    }
+
+    async fn on_file_changed(&self, path: &Path) -> Result<()> {
+        let filename = file_name(path)?;
+        let content = tokio::fs::read_to_string(path).await?;
+        let Ok(incoming) = serde_yaml::from_str::<Mapping>(&content) else {
+            tracing::warn!("skip synced profile `{filename}`: not a valid yaml mapping");
+            return Ok(());
+        };
+        let current = match find_synced_profile(path) {
+            Some(profile) => {
+                let existing = profile.read_file().unwrap_or_default();
+                // 只有键的顺序或格式不同时不提示
+                if existing == content
+                    || serde_yaml::from_str::<Mapping>(&existing).is_ok_and(|m| m == incoming)
+                {
+                    return Ok(());
+                }
+                existing
+            }
+            // 新文件与当前使用的配置比较
+            None => {
+                let profiles = Config::profiles();
+                let profiles = profiles.latest();
+                profiles
+                    .get_current()
+                    .first()
+                    .and_then(|uid| profiles.get_item(uid).ok())
+                    .and_then(|profile| profile.read_file().ok())
+                    .unwrap_or_default()
+            }
+        };
+        let current = serde_yaml::from_str::<Mapping>(&current).unwrap_or_default();
+        let diff = ClashConfigDiff::compute(&current, &incoming);
+        self.pending
+            .lock()
+            .insert(filename.clone(), path.to_path_buf());
+        handle::Handle::emit(
+            "profile-sync-available",
+            ProfileSyncAvailable {
+                filename,
+                added_rules: diff.added_rules,
+                removed_rules: diff.removed_rules,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// 导入用户确认的文件，同名的本地配置会被覆盖
+    pub async fn confirm(&self, filename: &str) -> Result<()> {
+        let path = self
+            .pending
+            .lock()
+            .remove(filename)
+            .ok_or_else(|| anyhow!("no pending sync for `{filename}`"))?;
+        let content = tokio::fs::read_to_string(&path).await?;
+        serde_yaml::from_str::<Mapping>(&content).context("synced profile is not valid yaml")?;
+
+        if let Some(profile) = find_synced_profile(&path) {
+            profile.save_file(content)?;
+            // 更新时间戳，正在使用时重新加载核心配置
+            feat::update_profile(profile.uid().to_string(), None).await?;
+        } else {
+            let mut shared = ProfileShared::get_default_builder(&ProfileItemType::Local);
+            shared.name(profile_name(&path)?);
+            let mut builder = LocalProfileBuilder::default();
+            builder.shared(shared);
+            let profile: Profile = builder
+                .build()
+                .context("failed to build local profile")?
+                .into();
+            profile.save_file(content)?;
+            profile_activation::modify_profiles(|profiles| profiles.append_item(profile)).await?;
+        }
+        handle::Handle::refresh_profiles();
+        Ok(())
+    }
+}
+
+/// 清空设置时前端传入空字符串，视为未设置
+fn sync_dir(dir: Option<&Path>) -> Option<PathBuf> {
+    dir.filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+}
+
+fn is_profile_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("invalid synced profile path: {}", path.display()))
+}
+
+fn profile_name(path: &Path) -> Result<String> {
+    path.file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("invalid synced profile path: {}", path.display()))
+}
+
+/// 同步的文件按文件名（不含扩展名）对应同名的本地配置
+fn find_synced_profile(path: &Path) -> Option<Profile> {
+    let name = profile_name(path).ok()?;
+    Config::profiles()
+        .latest()
+        .get_items()
+        .iter()
+        .find(|profile| profile.is_local() && profile.name() == name)
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clash_config_diff() {
+        let old: Mapping = serde_yaml::from_str(
+            "rules:\n  - DOMAIN,a.com,DIRECT\n  - DOMAIN,b.com,Proxy\n  - MATCH,Proxy\n",
+        )
+        .unwrap();
+        let new: Mapping = serde_yaml::from_str(
+            "rules:\n  - DOMAIN,a.com,DIRECT\n  - DOMAIN,c.com,Proxy\n  - DOMAIN,d.com,Proxy\n  - MATCH,Proxy\n",
+        )
+        .unwrap();
+        let diff = ClashConfigDiff::compute(&old, &new);
+        assert_eq!(
+            diff,
+            ClashConfigDiff {
+                added_rules: 2,
+                removed_rules: 1
+            }
+        );
+        assert_eq!(
+            ClashConfigDiff::compute(&Mapping::new(), &new),
+            ClashConfigDiff {
+                added_rules: 4,
+                removed_rules: 0
+            }
+        );
+        assert_eq!(
+            ClashConfigDiff::compute(&new, &new),
+            ClashConfigDiff::default()
+        );
+
+        let old: Mapping =
+            serde_yaml::from_str("rules:\n  - { type: DOMAIN, value: a.com, target: DIRECT }\n")
+                .unwrap();
+        let new: Mapping =
+            serde_yaml::from_str("rules:\n  - { target: DIRECT, value: a.com, type: DOMAIN }\n")
+                .unwrap();
+        assert_eq!(
+            ClashConfigDiff::compute(&old, &new),
+            ClashConfigDiff::default()
+        );
+    }
+
+    #[test]
+    fn test_empty_sync_dir_is_unset() {
+        assert_eq!(sync_dir(None), None);
+        assert_eq!(sync_dir(Some(Path::new(""))), None);
+        assert_eq!(
+            sync_dir(Some(Path::new("/sync"))),
+            Some(PathBuf::from("/sync"))
+        );
+    }
+
+    #[test]
+    fn test_is_profile_file() {
+        assert!(is_profile_file(Path::new("/sync/work.yaml")));
+        assert!(is_profile_file(Path::new("/sync/home.YML")));
+        assert!(!is_profile_file(Path::new("/sync/.stfolder")));
+        assert!(!is_profile_file(Path::new("/sync/work.yaml.tmp")));
+    }
+}
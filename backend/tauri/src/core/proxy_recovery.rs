@@ -0,0 +1,208 @@
+//! 崩溃后恢复系统代理
+//! 设置系统代理时记录标记，正常退出时清除；下次启动时标记仍在，
+//! 且系统代理仍指向已无人监听的端口，说明上次异常退出，自动关闭系统代理
+use crate::{
+    core::{handle::Handle, pac},
+    log_err,
+    utils::{dirs, net},
+};
+use anyhow::Result;
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::Path;
+use sysproxy::Sysproxy;
+
+const RECOVERED_EVENT: &str = "sysproxy-recovered";
+
+/// 由本应用写入的系统代理
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyMarker {
+    pub host: String,
+    pub port: u16,
+    /// PAC 模式写入的脚本地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pac_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProxyRecovery {
+    pub host: String,
+    pub port: u16,
+    /// 是否清除了 PAC 地址
+    pub pac: bool,
+}
+
+/// 最近一次写入的标记，守护任务会反复设置代理，相同时不再写文件
+static CURRENT: Lazy<Mutex<Option<ProxyMarker>>> = Lazy::new(|| Mutex::new(None));
+static LAST_RECOVERY: OnceCell<Option<ProxyRecovery>> = OnceCell::new();
+
+fn read_marker(path: &Path) -> Result<Option<ProxyMarker>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 记录系统代理已由本应用设置
+pub fn mark(marker: ProxyMarker) {
+    let mut current = CURRENT.lock();
+    if current.as_ref() == Some(&marker) {
+        return;
+    }
+    let result = dirs::sysproxy_marker_path()
+        .and_then(|path| Ok(std::fs::write(path, serde_json::to_vec(&marker)?)?));
+    match result {
+        Ok(()) => *current = Some(marker),
+        Err(e) => log::error!(target: "app", "failed to write system proxy marker: {e:?}"),
+    }
+}
+
+/// 标记中记录的 PAC 地址，本进程尚未写入标记时读取上次运行留下的文件
+pub fn marked_pac_url() -> Option<String> {
+    if let Some(marker) = CURRENT.lock().as_ref() {
+        return marker.pac_url.clone();
+    }
+    dirs::sysproxy_marker_path()
+        .and_then(|path| read_marker(&path))
+        .ok()
+        .flatten()
+        .and_then(|marker| marker.pac_url)
+}
+
+/// 系统代理已恢复，清除标记
+pub fn clear() {
+    CURRENT.lock().take();
+    let result = dirs::sysproxy_marker_path().and_then(|path| match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    });
+    log_err!(result, "failed to remove system proxy marker");
+}
+
+/// 系统代理仍指向标记中的端口，且该端口已无人监听
+fn is_stale(marker: &ProxyMarker, current: &Sysproxy, port_alive: impl Fn(u16) -> bool) -> bool {
+    current.enable
+        && current.host == marker.host
+        && current.port == marker.port
+        && !port_alive(marker.port)
+}
+
+fn pac_port(url: &str) -> Option<u16> {
+    let url = url::Url::parse(url).ok()?;
+    url.port_or_known_default()
+}
+
+fn recover(marker: &ProxyMarker) -> Result<Option<ProxyRecovery>> {
+    let port_alive = |port| !net::is_port_available(port);
+    let mut recovery = None;
+
+    let mut current = Sysproxy::get_system_proxy()?;
+    if is_stale(marker, &current, port_alive) {
+        current.enable = false;
+        current.set_system_proxy()?;
+        recovery = Some(ProxyRecovery {
+            host: marker.host.clone(),
+            port: marker.port,
+            pac: false,
+        });
+    }
+
+    // PAC 服务随应用退出，端口空闲说明脚本地址已失效，只在系统中仍是该地址时清除
+    if let Some(url) = &marker.pac_url
+        && pac_port(url).is_some_and(|port| !port_alive(port))
+        && pac::clear_auto_config_url(std::slice::from_ref(url))?
+    {
+        recovery
+            .get_or_insert_with(|| ProxyRecovery {
+                host: marker.host.clone(),
+                port: marker.port,
+                pac: false,
+            })
+            .pac = true;
+    }
+    Ok(recovery)
+}
+
+/// 启动时调用，需要在核心启动之前，否则无法判断端口是否仍在使用
+pub fn recover_on_launch() {
+    LAST_RECOVERY.get_or_init(|| {
+        let marker = match dirs::sysproxy_marker_path().and_then(|path| read_marker(&path)) {
+            Ok(Some(marker)) => marker,
+            Ok(None) => return None,
+            Err(e) => {
+                log::warn!(target: "app", "failed to read system proxy marker: {e:?}");
+                clear();
+                return None;
+            }
+        };
+        let recovery = match recover(&marker) {
+            Ok(recovery) => recovery,
+            Err(e) => {
+                log::error!(target: "app", "failed to recover system proxy: {e:?}");
+                return None;
+            }
+        };
+        clear();
+        if let Some(recovery) = &recovery {
+            log::warn!(
+                target: "app",
+                "system proxy {}:{} was left by a previous crash, reverted",
+                recovery.host,
+                recovery.port
+            );
+            log_err!(Handle::emit(RECOVERED_EVENT, recovery));
+        }
+        recovery
+    });
+}
+
+pub fn last_recovery() -> Option<ProxyRecovery> {
+    LAST_RECOVERY.get().cloned().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sysproxy(enable: bool, port: u16) -> Sysproxy {
+        Sysproxy {
+            enable,
+            host: "127.0.0.1".into(),
+            port,
+            bypass: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let marker = ProxyMarker {
+            host: "127.0.0.1".into(),
+            port: 7890,
+            pac_url: None,
+        };
+        assert!(is_stale(&marker, &sysproxy(true, 7890), |_| false));
+        // 核心仍在运行，例如服务模式启动的核心
+        assert!(!is_stale(&marker, &sysproxy(true, 7890), |_| true));
+        // 用户已改为其他代理
+        assert!(!is_stale(&marker, &sysproxy(true, 1080), |_| false));
+        assert!(!is_stale(&marker, &sysproxy(false, 7890), |_| false));
+    }
+
+    #[test]
+    fn test_marker_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("marker.json");
+        assert_eq!(read_marker(&path).unwrap(), None);
+        let marker = ProxyMarker {
+            host: "127.0.0.1".into(),
+            port: 7890,
+            pac_url: Some("http://127.0.0.1:33331/proxy.pac".into()),
+        };
+        std::fs::write(&path, serde_json::to_vec(&marker).unwrap()).unwrap();
+        assert_eq!(read_marker(&path).unwrap(), Some(marker.clone()));
+        assert_eq!(pac_port(marker.pac_url.as_deref().unwrap()), Some(33331));
+    }
+}
@@ -0,0 +1,319 @@
+//! Minimal executable header inspection, used to skip service binaries that
+//! were built for another machine before trying to run them.
+use std::{fs::File, io::Read, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Machine {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    Other(u32),
+}
+
+impl Machine {
+    /// Map a `std::env::consts::ARCH` value
+    pub fn from_arch(arch: &str) -> Machine {
+        match arch {
+            "x86" => Machine::X86,
+            "x86_64" => Machine::X86_64,
+            "arm" => Machine::Arm,
+            "aarch64" => Machine::Aarch64,
+            _ => Machine::Other(0),
+        }
+    }
+
+    /// The arch component used in target triples
+    pub fn triple_arch(self) -> Option<&'static str> {
+        match self {
+            Machine::X86 => Some("i686"),
+            Machine::X86_64 => Some("x86_64"),
+            Machine::Arm => Some("armv7"),
+            Machine::Aarch64 => Some("aarch64"),
+            Machine::Other(_) => None,
+        }
+    }
+
+    fn from_pe(machine: u16) -> Machine {
+        match machine {
+            0x014c => Machine::X86,
+            0x8664 => Machine::X86_64,
+            0x01c4 => Machine::Arm,
+            0xaa64 => Machine::Aarch64,
+            other => Machine::Other(other as u32),
+        }
+    }
+
+    fn from_elf(machine: u16) -> Machine {
+        match machine {
+            0x03 => Machine::X86,
+            0x3e => Machine::X86_64,
+            0x28 => Machine::Arm,
+            0xb7 => Machine::Aarch64,
+            other => Machine::Other(other as u32),
+        }
+    }
+
+    fn from_macho(cpu_type: u32) -> Machine {
+        match cpu_type {
+            0x0000_0007 => Machine::X86,
+            0x0100_0007 => Machine::X86_64,
+            0x0000_000c => Machine::Arm,
+            0x0100_000c => Machine::Aarch64,
+            other => Machine::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pe,
+    Elf,
+    MachO,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Header {
+    Binary {
+        format: Format,
+        machine: Machine,
+    },
+    /// Mach-O universal binary, the loader picks a slice
+    Universal,
+    /// `#!` script, runs wherever its interpreter does
+    Script,
+}
+
+fn u16_at(buf: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = buf.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn u32_at(buf: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+/// Parse the header from the first bytes of a file
+pub fn parse_header(buf: &[u8]) -> Option<Header> {
+    match buf.get(..4)? {
+        [b'M', b'Z', ..] => {
+            let pe = u32_at(buf, 0x3c, false)? as usize;
+            if buf.get(pe..pe + 4)? != b"PE\0\0" {
+                return None;
+            }
+            let machine = Machine::from_pe(u16_at(buf, pe + 4, false)?);
+            Some(Header::Binary {
+                format: Format::Pe,
+                machine,
+            })
+        }
+        [0x7f, b'E', b'L', b'F'] => {
+            let big_endian = *buf.get(5)? == 2;
+            let machine = Machine::from_elf(u16_at(buf, 18, big_endian)?);
+            Some(Header::Binary {
+                format: Format::Elf,
+                machine,
+            })
+        }
+        [0xcf, 0xfa, 0xed, 0xfe] | [0xce, 0xfa, 0xed, 0xfe] => {
+            let machine = Machine::from_macho(u32_at(buf, 4, false)?);
+            Some(Header::Binary {
+                format: Format::MachO,
+                machine,
+            })
+        }
+        [0xca, 0xfe, 0xba, 0xbe] => Some(Header::Universal),
+        [b'#', b'!', ..] => Some(Header::Script),
+        _ => None,
+    }
+}
+
+/// The real machine, not the one the app was compiled for.
+/// An x64 build running under emulation on Windows ARM64 reports ARM64 here.
+pub fn native_machine() -> Machine {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{GetCurrentProcess, IsWow64Process2};
+
+        let mut process = 0;
+        let mut native = 0;
+        // SAFETY: both out pointers are valid for the duration of the call
+        if unsafe { IsWow64Process2(GetCurrentProcess(), &mut process, &mut native) } != 0 {
+            return Machine::from_pe(native);
+        }
+    }
+    Machine::from_arch(std::env::consts::ARCH)
+}
+
+/// Machines whose binaries can run on `native`, in order of preference
+pub fn compatible_machines(native: Machine) -> Vec<Machine> {
+    let mut machines = vec![native];
+    match native {
+        // Windows 11 on ARM emulates both x64 and x86
+        Machine::Aarch64 if cfg!(windows) => {
+            machines.extend([Machine::X86_64, Machine::X86]);
+        }
+        // Rosetta 2
+        Machine::Aarch64 if cfg!(target_os = "macos") => machines.push(Machine::X86_64),
+        Machine::X86_64 if cfg!(windows) => machines.push(Machine::X86),
+        _ => {}
+    }
+    machines
+}
+
+fn expected_format() -> Option<Format> {
+    if cfg!(windows) {
+        Some(Format::Pe)
+    } else if cfg!(target_os = "macos") {
+        Some(Format::MachO)
+    } else if cfg!(unix) {
+        Some(Format::Elf)
+    } else {
+        None
+    }
+}
+
+/// Check whether the header describes a binary runnable on `native`.
+/// Returns the rejection reason otherwise.
+pub fn check_header(header: Option<Header>, native: Machine) -> Result<(), String> {
+    match header {
+        None => Err("unrecognized executable format".into()),
+        Some(Header::Universal) => Ok(()),
+        Some(Header::Script) if cfg!(unix) => Ok(()),
+        Some(Header::Script) => Err("scripts cannot be run as a service".into()),
+        Some(Header::Binary { format, machine }) => {
+            if expected_format().is_some_and(|expected| expected != format) {
+                return Err(format!("{format:?} binary cannot run on this platform"));
+            }
+            if !compatible_machines(native).contains(&machine) {
+                return Err(format!("built for {machine:?}, this machine is {native:?}"));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Check a file on disk against the native machine
+pub fn check_executable(path: &Path) -> Result<(), String> {
+    let mut buf = Vec::with_capacity(1024);
+    File::open(path)
+        .and_then(|file| file.take(1024).read_to_end(&mut buf))
+        .map_err(|e| format!("failed to read: {e}"))?;
+    // PE headers may sit past the first KiB in unusual layouts
+    if buf.starts_with(b"MZ")
+        && let Some(pe) = u32_at(&buf, 0x3c, false).map(|pe| pe as usize)
+        && pe + 6 > buf.len()
+    {
+        buf.clear();
+        File::open(path)
+            .and_then(|file| file.take(pe as u64 + 6).read_to_end(&mut buf))
+            .map_err(|e| format!("failed to read: {e}"))?;
+    }
+    check_header(parse_header(&buf), native_machine())
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// A PE file trimmed down to the fields `parse_header` reads
+    pub fn pe(machine: u16) -> Vec<u8> {
+        let mut buf = vec![0u8; 0x86];
+        buf[..2].copy_from_slice(b"MZ");
+        buf[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        buf[0x80..0x84].copy_from_slice(b"PE\0\0");
+        buf[0x84..0x86].copy_from_slice(&machine.to_le_bytes());
+        buf
+    }
+
+    pub fn elf(machine: u16) -> Vec<u8> {
+        let mut buf = vec![0u8; 20];
+        buf[..4].copy_from_slice(&[0x7f, b'E', b'L', b'F']);
+        buf[5] = 1;
+        buf[18..20].copy_from_slice(&machine.to_le_bytes());
+        buf
+    }
+
+    pub fn macho(cpu_type: u32) -> Vec<u8> {
+        let mut buf = vec![0xcf, 0xfa, 0xed, 0xfe];
+        buf.extend(cpu_type.to_le_bytes());
+        buf
+    }
+
+    /// A binary for the platform the tests run on
+    pub fn native_binary(machine: Machine) -> Vec<u8> {
+        let (pe_machine, elf_machine, macho_machine) = match machine {
+            Machine::X86 => (0x014c, 0x03, 0x0000_0007),
+            Machine::X86_64 => (0x8664, 0x3e, 0x0100_0007),
+            Machine::Arm => (0x01c4, 0x28, 0x0000_000c),
+            Machine::Aarch64 => (0xaa64, 0xb7, 0x0100_000c),
+            Machine::Other(_) => (0xffff, 0xffff, 0xffff),
+        };
+        if cfg!(windows) {
+            pe(pe_machine)
+        } else if cfg!(target_os = "macos") {
+            macho(macho_machine)
+        } else {
+            elf(elf_machine)
+        }
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header(&pe(0xaa64)),
+            Some(Header::Binary {
+                format: Format::Pe,
+                machine: Machine::Aarch64
+            })
+        );
+        assert_eq!(
+            parse_header(&elf(0x3e)),
+            Some(Header::Binary {
+                format: Format::Elf,
+                machine: Machine::X86_64
+            })
+        );
+        assert_eq!(
+            parse_header(&macho(0x0100_000c)),
+            Some(Header::Binary {
+                format: Format::MachO,
+                machine: Machine::Aarch64
+            })
+        );
+        assert_eq!(parse_header(b"#!/bin/sh\n"), Some(Header::Script));
+        assert_eq!(parse_header(b"not a binary"), None);
+        // truncated PE header
+        assert_eq!(parse_header(&pe(0x8664)[..0x82]), None);
+    }
+
+    #[test]
+    fn test_check_header_machine() {
+        let binary = |machine| parse_header(&native_binary(machine));
+        assert!(check_header(binary(Machine::X86_64), Machine::X86_64).is_ok());
+        let reason = check_header(binary(Machine::Aarch64), Machine::X86_64).unwrap_err();
+        assert!(reason.contains("built for Aarch64"), "{reason}");
+        // x64 binaries run under emulation on Windows ARM64 only
+        assert_eq!(
+            check_header(binary(Machine::X86_64), Machine::Aarch64).is_ok(),
+            cfg!(any(windows, target_os = "macos"))
+        );
+        assert!(check_header(None, Machine::X86_64).is_err());
+    }
+
+    #[test]
+    fn test_check_header_format() {
+        let foreign = if cfg!(windows) { elf(0x3e) } else { pe(0x8664) };
+        let reason = check_header(parse_header(&foreign), Machine::X86_64).unwrap_err();
+        assert!(reason.contains("cannot run on this platform"), "{reason}");
+    }
+}
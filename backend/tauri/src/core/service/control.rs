@@ -1,11 +1,29 @@
-use crate::utils::dirs::{app_config_dir, app_data_dir, app_install_dir};
+use crate::{
+    config::{Config, nyanpasu::ClashCore},
+    core::{clash::core_info::CoreInfo, updater},
+    utils::dirs::{
+        self, app_config_dir, app_config_dir_path, app_data_dir, app_data_dir_path, app_install_dir,
+    },
+};
 #[cfg(not(windows))]
 use runas::Command as RunasCommand;
-use std::ffi::OsString;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use nyanpasu_ipc::types::ServiceStatus;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use super::resolve_service_path;
 
@@ -15,6 +33,95 @@ use std::os::unix::process::ExitStatusExt;
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// 服务 IPC socket 的默认路径
+pub const DEFAULT_IPC_SOCKET_PATH: &str = "/run/nyanpasu_ipc.sock";
+
+/// unix socket 路径的长度上限（`sun_path`）
+const MAX_IPC_SOCKET_PATH_LEN: usize = 107;
+
+/// 用户设置的 IPC socket 路径，未设置时为 None
+fn custom_ipc_socket_path() -> Option<PathBuf> {
+    Config::verge().latest().ipc_socket_path.clone()
+}
+
+/// 服务 IPC socket 的路径，未设置时使用 `/run/nyanpasu_ipc.sock`
+pub fn ipc_socket_path() -> PathBuf {
+    custom_ipc_socket_path().unwrap_or_else(|| PathBuf::from(DEFAULT_IPC_SOCKET_PATH))
+}
+
+/// 连接服务 IPC 时使用的名称，设置了自定义 socket 路径时为该路径
+pub fn ipc_client_name() -> String {
+    match custom_ipc_socket_path() {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => nyanpasu_ipc::SERVICE_PLACEHOLDER.to_string(),
+    }
+}
+
+/// socket 路径会交给以 root 运行的命令，只允许常规的路径字符
+fn is_safe_socket_path_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '/' | '-')
+}
+
+/// 检查 socket 路径可用：绝对路径、字符安全、长度不超限、所在目录存在且可写
+pub fn validate_ipc_socket_path(path: &Path) -> anyhow::Result<()> {
+    if !path.is_absolute() {
+        anyhow::bail!("ipc socket path must be absolute: {}", path.display());
+    }
+    if !path
+        .to_str()
+        .is_some_and(|path| path.chars().all(is_safe_socket_path_char))
+    {
+        anyhow::bail!(
+            "ipc socket path may only contain letters, digits and `._/-`: {}",
+            path.display()
+        );
+    }
+    if path.as_os_str().len() > MAX_IPC_SOCKET_PATH_LEN {
+        anyhow::bail!(
+            "ipc socket path is longer than {MAX_IPC_SOCKET_PATH_LEN} bytes: {}",
+            path.display()
+        );
+    }
+    if path.is_dir() {
+        anyhow::bail!("ipc socket path is a directory: {}", path.display());
+    }
+    let Some(parent) = path.parent().filter(|parent| parent.is_dir()) else {
+        anyhow::bail!(
+            "parent directory of ipc socket path does not exist: {}",
+            path.display()
+        );
+    };
+    // socket 由以 root 运行的服务创建，只检查文件系统是否只读
+    #[cfg(unix)]
+    let readonly = nix::sys::statvfs::statvfs(parent)?
+        .flags()
+        .contains(nix::sys::statvfs::FsFlags::ST_RDONLY);
+    #[cfg(not(unix))]
+    let readonly = std::fs::metadata(parent)?.permissions().readonly();
+    if readonly {
+        anyhow::bail!(
+            "directory is on a read-only filesystem: {}",
+            parent.display()
+        );
+    }
+    Ok(())
+}
+
+/// 等待服务创建 socket 后，只允许 nyanpasu 组访问
+/// 路径作为位置参数传给 `sh -c`，不拼接进脚本
+#[cfg(all(unix, not(target_os = "macos")))]
+fn harden_ipc_socket_permissions(service: &Path, action: &str) -> Vec<OsString> {
+    const SCRIPT: &str = r#""$1" "$2"; for i in $(seq 1 20); do [ -S "$3" ] && break; sleep 0.1; done; if [ -S "$3" ]; then chown root:nyanpasu "$3" && chmod 660 "$3"; fi"#;
+    vec![
+        "-c".into(),
+        SCRIPT.into(),
+        "sh".into(),
+        service.into(),
+        action.into(),
+        ipc_socket_path().into(),
+    ]
+}
+
 #[cfg(all(unix, not(target_os = "macos")))]
 fn map_privilege_tool_not_found_error(e: std::io::Error) -> anyhow::Error {
     // Linux: missing privilege escalation tool (commonly pkexec from polkit)
@@ -64,7 +171,7 @@ fn is_service_already_running(status: &std::process::ExitStatus, output: &str) -
 }
 
 #[cfg(windows)]
-const WINDOWS_SERVICE_LABEL: &str = "moe.elaina.nyanpasu-service";
+pub const WINDOWS_SERVICE_LABEL: &str = "moe.elaina.nyanpasu-service";
 
 #[cfg(windows)]
 #[derive(Debug)]
@@ -212,6 +319,241 @@ fn windows_status_info_from_scm(status: ServiceStatus) -> nyanpasu_ipc::types::S
     }
 }
 
+/// 服务崩溃后 SCM 执行的恢复操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceRecoveryActionType {
+    None,
+    Restart,
+    Reboot,
+    RunCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub struct ServiceRecoveryAction {
+    pub action: ServiceRecoveryActionType,
+    pub delay_ms: u32,
+}
+
+/// 服务的故障恢复设置，`actions` 依次对应第一次、第二次与之后的失败
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub struct ServiceRecoveryConfig {
+    /// 没有再失败多久后重置失败计数（秒）
+    pub reset_period_secs: u32,
+    pub actions: Vec<ServiceRecoveryAction>,
+}
+
+impl ServiceRecoveryConfig {
+    /// 第一次失败 5 秒后重启，第二次 10 秒后，之后均为 30 秒后，一天后重置计数
+    pub fn restart_on_crash() -> Self {
+        Self {
+            reset_period_secs: 24 * 60 * 60,
+            actions: [5_000, 10_000, 30_000]
+                .into_iter()
+                .map(|delay_ms| ServiceRecoveryAction {
+                    action: ServiceRecoveryActionType::Restart,
+                    delay_ms,
+                })
+                .collect(),
+        }
+    }
+
+    /// 对应的 `sc.exe failure` 参数
+    #[cfg(any(windows, test))]
+    fn sc_failure_args(&self, service_name: &str) -> Vec<String> {
+        let actions = self
+            .actions
+            .iter()
+            .map(|action| {
+                let kind = match action.action {
+                    ServiceRecoveryActionType::None => "",
+                    ServiceRecoveryActionType::Restart => "restart",
+                    ServiceRecoveryActionType::Reboot => "reboot",
+                    ServiceRecoveryActionType::RunCommand => "run",
+                };
+                format!("{kind}/{}", action.delay_ms)
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        vec![
+            "failure".into(),
+            service_name.into(),
+            "reset=".into(),
+            self.reset_period_secs.to_string(),
+            "actions=".into(),
+            actions,
+        ]
+    }
+}
+
+#[cfg(windows)]
+mod scm {
+    use super::{ServiceRecoveryAction, ServiceRecoveryActionType, ServiceRecoveryConfig};
+    use anyhow::Context;
+    use std::{ffi::c_void, os::windows::ffi::OsStrExt, ptr};
+    use windows_sys::Win32::System::Services::{
+        ChangeServiceConfig2W, CloseServiceHandle, OpenSCManagerW, OpenServiceW,
+        QueryServiceConfig2W, SC_ACTION, SC_ACTION_NONE, SC_ACTION_REBOOT, SC_ACTION_RESTART,
+        SC_ACTION_RUN_COMMAND, SC_ACTION_TYPE, SC_HANDLE, SC_MANAGER_CONNECT,
+        SERVICE_CHANGE_CONFIG, SERVICE_CONFIG_FAILURE_ACTIONS, SERVICE_FAILURE_ACTIONSW,
+        SERVICE_QUERY_CONFIG, SERVICE_START,
+    };
+
+    struct ScHandle(SC_HANDLE);
+
+    impl Drop for ScHandle {
+        fn drop(&mut self) {
+            unsafe {
+                CloseServiceHandle(self.0);
+            }
+        }
+    }
+
+    /// 返回的管理器句柄需要比服务句柄活得更久
+    fn open_service(name: &str, access: u32) -> anyhow::Result<(ScHandle, ScHandle)> {
+        let manager = unsafe { OpenSCManagerW(ptr::null(), ptr::null(), SC_MANAGER_CONNECT) };
+        if manager.is_null() {
+            return Err(std::io::Error::last_os_error())
+                .context("failed to open the service control manager");
+        }
+        let manager = ScHandle(manager);
+        let wide_name = std::ffi::OsStr::new(name)
+            .encode_wide()
+            .chain(Some(0))
+            .collect::<Vec<u16>>();
+        let service = unsafe { OpenServiceW(manager.0, wide_name.as_ptr(), access) };
+        if service.is_null() {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("failed to open service `{name}`"));
+        }
+        Ok((manager, ScHandle(service)))
+    }
+
+    fn action_type(action: ServiceRecoveryActionType) -> SC_ACTION_TYPE {
+        match action {
+            ServiceRecoveryActionType::None => SC_ACTION_NONE,
+            ServiceRecoveryActionType::Restart => SC_ACTION_RESTART,
+            ServiceRecoveryActionType::Reboot => SC_ACTION_REBOOT,
+            ServiceRecoveryActionType::RunCommand => SC_ACTION_RUN_COMMAND,
+        }
+    }
+
+    fn action_from_raw(action: &SC_ACTION) -> ServiceRecoveryAction {
+        ServiceRecoveryAction {
+            action: match action.Type {
+                SC_ACTION_RESTART => ServiceRecoveryActionType::Restart,
+                SC_ACTION_REBOOT => ServiceRecoveryActionType::Reboot,
+                SC_ACTION_RUN_COMMAND => ServiceRecoveryActionType::RunCommand,
+                _ => ServiceRecoveryActionType::None,
+            },
+            delay_ms: action.Delay,
+        }
+    }
+
+    pub fn set_failure_actions(name: &str, config: &ServiceRecoveryConfig) -> anyhow::Result<()> {
+        // 重启操作要求句柄具有 SERVICE_START 权限
+        let (_manager, service) = open_service(name, SERVICE_CHANGE_CONFIG | SERVICE_START)?;
+        let mut actions = config
+            .actions
+            .iter()
+            .map(|action| SC_ACTION {
+                Type: action_type(action.action),
+                Delay: action.delay_ms,
+            })
+            .collect::<Vec<_>>();
+        let info = SERVICE_FAILURE_ACTIONSW {
+            dwResetPeriod: config.reset_period_secs,
+            lpRebootMsg: ptr::null_mut(),
+            lpCommand: ptr::null_mut(),
+            cActions: actions.len() as u32,
+            lpsaActions: actions.as_mut_ptr(),
+        };
+        let ok = unsafe {
+            ChangeServiceConfig2W(
+                service.0,
+                SERVICE_CONFIG_FAILURE_ACTIONS,
+                &info as *const _ as *const c_void,
+            )
+        };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("failed to set recovery actions of `{name}`"));
+        }
+        Ok(())
+    }
+
+    pub fn query_failure_actions(name: &str) -> anyhow::Result<ServiceRecoveryConfig> {
+        let (_manager, service) = open_service(name, SERVICE_QUERY_CONFIG)?;
+        let mut needed = 0u32;
+        unsafe {
+            QueryServiceConfig2W(
+                service.0,
+                SERVICE_CONFIG_FAILURE_ACTIONS,
+                ptr::null_mut(),
+                0,
+                &mut needed,
+            );
+        }
+        if needed == 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("failed to query recovery actions of `{name}`"));
+        }
+        // 以 u64 分配，保证结构体与其后的 SC_ACTION 数组对齐
+        let mut buf = vec![0u64; (needed as usize).div_ceil(8)];
+        let ok = unsafe {
+            QueryServiceConfig2W(
+                service.0,
+                SERVICE_CONFIG_FAILURE_ACTIONS,
+                buf.as_mut_ptr().cast(),
+                (buf.len() * 8) as u32,
+                &mut needed,
+            )
+        };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("failed to query recovery actions of `{name}`"));
+        }
+        let info = unsafe { &*(buf.as_ptr() as *const SERVICE_FAILURE_ACTIONSW) };
+        let actions = if info.lpsaActions.is_null() || info.cActions == 0 {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(info.lpsaActions, info.cActions as usize) }
+        };
+        Ok(ServiceRecoveryConfig {
+            reset_period_secs: info.dwResetPeriod,
+            actions: actions.iter().map(action_from_raw).collect(),
+        })
+    }
+}
+
+/// 让 SCM 在服务崩溃后自动重启服务
+/// 修改服务配置需要管理员权限，未提权时通过 UAC 执行 `sc.exe failure`
+#[cfg(windows)]
+pub fn configure_service_recovery(service_name: &str) -> anyhow::Result<()> {
+    let config = ServiceRecoveryConfig::restart_on_crash();
+    if is_elevated() {
+        return scm::set_failure_actions(service_name, &config);
+    }
+    let status = runas::Command::new("sc.exe")
+        .args(&config.sc_failure_args(service_name))
+        .gui(false)
+        .show(false)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!(
+            "failed to set recovery actions of `{service_name}`, exit code: {:?}",
+            status.code()
+        );
+    }
+    Ok(())
+}
+
+/// 读取服务当前的故障恢复设置
+#[cfg(windows)]
+pub fn get_service_recovery_config(service_name: &str) -> anyhow::Result<ServiceRecoveryConfig> {
+    scm::query_failure_actions(service_name)
+}
+
 #[cfg(windows)]
 pub async fn repair_windows_service_installation_if_needed() -> anyhow::Result<bool> {
     if !windows_service_registration_needs_repair()? {
@@ -246,7 +588,7 @@ pub async fn repair_windows_service_installation_if_needed() -> anyhow::Result<b
         );
     }
 
-    let install_args = get_service_install_args().await?;
+    let install_args = get_service_install_args(false).await?;
     let install_path = resolve_service_path();
     let (install_status, install_output) = tokio::task::spawn_blocking(
         move || -> anyhow::Result<(std::process::ExitStatus, String)> {
@@ -267,7 +609,8 @@ pub async fn repair_windows_service_installation_if_needed() -> anyhow::Result<b
     Ok(true)
 }
 
-pub async fn get_service_install_args() -> Result<Vec<OsString>, anyhow::Error> {
+/// `dry_run` 为 true 时只计算参数，不创建任何目录
+pub async fn get_service_install_args(dry_run: bool) -> Result<Vec<OsString>, anyhow::Error> {
     let user = {
         #[cfg(windows)]
         {
@@ -278,8 +621,11 @@ pub async fn get_service_install_args() -> Result<Vec<OsString>, anyhow::Error>
             whoami::username()
         }
     };
-    let data_dir = app_data_dir()?;
-    let config_dir = app_config_dir()?;
+    let (data_dir, config_dir) = if dry_run {
+        (app_data_dir_path()?, app_config_dir_path()?)
+    } else {
+        (app_data_dir()?, app_config_dir()?)
+    };
     let app_dir = app_install_dir()?;
 
     #[cfg(not(windows))]
@@ -294,6 +640,16 @@ pub async fn get_service_install_args() -> Result<Vec<OsString>, anyhow::Error>
         "--nyanpasu-app-dir".into(),
         format!("\"{}\"", app_dir.to_string_lossy()).into(),
     ];
+    #[cfg(not(windows))]
+    let args = match custom_ipc_socket_path() {
+        Some(socket) => {
+            let mut args = args;
+            args.push("--ipc-socket-path".into());
+            args.push(format!("\"{}\"", socket.to_string_lossy()).into());
+            args
+        }
+        None => args,
+    };
 
     #[cfg(windows)]
     let args: Vec<OsString> = vec![
@@ -311,7 +667,137 @@ pub async fn get_service_install_args() -> Result<Vec<OsString>, anyhow::Error>
     Ok(args)
 }
 
+/// 服务安装预览中的单个步骤
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct ServiceInstallStep {
+    pub description: String,
+    /// 将要执行的命令，仅用于展示
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+impl ServiceInstallStep {
+    fn new(description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            command: None,
+        }
+    }
+
+    fn with_command(mut self, command: String) -> Self {
+        self.command = Some(command);
+        self
+    }
+}
+
+/// 服务安装预览，描述 `install_service` 将要执行的操作
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct ServiceInstallPlan {
+    pub steps: Vec<ServiceInstallStep>,
+    pub estimated_duration_secs: u64,
+    pub requires_elevation: bool,
+    pub files_to_create: Vec<PathBuf>,
+    pub registry_keys_to_create: Vec<String>,
+}
+
+/// 当前进程是否已具有管理员权限
+fn is_elevated() -> bool {
+    #[cfg(windows)]
+    {
+        use deelevate::{PrivilegeLevel, Token};
+        Token::with_current_process()
+            .and_then(|token| token.privilege_level())
+            .is_ok_and(|level| !matches!(level, PrivilegeLevel::NotPrivileged))
+    }
+    #[cfg(unix)]
+    {
+        nix::unistd::geteuid().is_root()
+    }
+}
+
+/// 模拟服务安装，返回将要执行的操作而不产生任何副作用
+pub async fn dry_run_install_service() -> anyhow::Result<ServiceInstallPlan> {
+    dirs::ensure_not_portable("service mode")?;
+    let mut plan = ServiceInstallPlan::default();
+    if let Ok(info) = status().await
+        && !matches!(info.status, ServiceStatus::NotInstalled)
+    {
+        plan.steps.push(ServiceInstallStep::new(format!(
+            "Service is already installed ({:?}), nothing to do",
+            info.status
+        )));
+        return Ok(plan);
+    }
+
+    let service_path = resolve_service_path();
+    if !service_path.exists() {
+        anyhow::bail!(
+            "nyanpasu-service executable not found at: {}",
+            service_path.display()
+        );
+    }
+    plan.steps.push(ServiceInstallStep::new(format!(
+        "Use service executable at {}",
+        service_path.display()
+    )));
+
+    let args = get_service_install_args(true).await?;
+    for dir in [app_data_dir_path()?, app_config_dir_path()?] {
+        if !dir.exists() {
+            plan.files_to_create.push(dir);
+        }
+    }
+
+    plan.requires_elevation = !is_elevated();
+    if plan.requires_elevation {
+        let tool = if cfg!(windows) {
+            "UAC"
+        } else if cfg!(target_os = "macos") {
+            "the administrator password prompt"
+        } else {
+            "pkexec"
+        };
+        plan.steps.push(ServiceInstallStep::new(format!(
+            "Request administrator privileges via {tool}"
+        )));
+    }
+
+    let command = std::iter::once(service_path.as_os_str())
+        .chain(args.iter().map(|arg| arg.as_os_str()))
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    plan.steps.push(
+        ServiceInstallStep::new("Register nyanpasu-service as a system service")
+            .with_command(command),
+    );
+
+    #[cfg(windows)]
+    plan.registry_keys_to_create.push(format!(
+        r"HKLM\SYSTEM\CurrentControlSet\Services\{}",
+        super::SERVICE_NAME
+    ));
+    #[cfg(target_os = "macos")]
+    plan.files_to_create.push(PathBuf::from(format!(
+        "/Library/LaunchDaemons/{}.plist",
+        super::SERVICE_NAME
+    )));
+    #[cfg(all(unix, not(target_os = "macos")))]
+    plan.files_to_create.push(PathBuf::from(format!(
+        "/etc/systemd/system/{}.service",
+        super::SERVICE_NAME
+    )));
+
+    plan.steps.push(ServiceInstallStep::new(
+        "Start the service and wait until it responds",
+    ));
+    // Windows 需要轮询等待 SCM 完成注册
+    plan.estimated_duration_secs = if cfg!(windows) { 15 } else { 5 };
+    Ok(plan)
+}
+
 pub async fn install_service() -> anyhow::Result<()> {
+    dirs::ensure_not_portable("service mode")?;
     tracing::info!("🚀 Starting service installation process");
 
     #[cfg(windows)]
@@ -327,7 +813,7 @@ pub async fn install_service() -> anyhow::Result<()> {
         }
     }
 
-    let args = get_service_install_args().await?;
+    let args = get_service_install_args(false).await?;
     tracing::info!("🔧 Service install args prepared: {:?}", args);
 
     let service_path = resolve_service_path();
@@ -434,6 +920,16 @@ pub async fn install_service() -> anyhow::Result<()> {
                         "Service installation verified after {} seconds",
                         attempt + 1
                     );
+                    // 以管理员权限设置恢复操作，失败（例如拒绝 UAC）不影响安装结果
+                    let recovery = tokio::task::spawn_blocking(|| {
+                        configure_service_recovery(WINDOWS_SERVICE_LABEL)
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|res| res);
+                    if let Err(e) = recovery {
+                        tracing::warn!("failed to configure service recovery actions: {e:?}");
+                    }
                     break;
                 }
                 Ok(_) => {
@@ -632,50 +1128,47 @@ pub async fn start_service() -> anyhow::Result<()> {
         );
     }
 
-    let (child, output) = tokio::task::spawn_blocking(move || -> anyhow::Result<(std::process::ExitStatus, String)> {
-        #[cfg(not(target_os = "macos"))]
-        {
-            #[cfg(all(unix, not(target_os = "macos")))]
-            let status = {
-                let service = service_path.to_string_lossy();
-                let cmd = format!(
-                    "\"{}\" start; for i in $(seq 1 20); do [ -S /run/nyanpasu_ipc.sock ] && break; sleep 0.1; done; if [ -S /run/nyanpasu_ipc.sock ]; then chown root:nyanpasu /run/nyanpasu_ipc.sock && chmod 660 /run/nyanpasu_ipc.sock; fi",
-                    service
-                );
-                RunasCommand::new("/bin/sh")
-                    .arg("-c")
-                    .arg(cmd)
-                    .gui(false)
-                    .show(false)
-                    .status()
-                    .map(|status| (status, String::new()))
-                    .map_err(map_privilege_tool_not_found_error)
-            };
+    let (child, output) = tokio::task::spawn_blocking(
+        move || -> anyhow::Result<(std::process::ExitStatus, String)> {
+            #[cfg(not(target_os = "macos"))]
+            {
+                #[cfg(all(unix, not(target_os = "macos")))]
+                let status = {
+                    let args = harden_ipc_socket_permissions(&service_path, "start");
+                    RunasCommand::new("/bin/sh")
+                        .args(&args)
+                        .gui(false)
+                        .show(false)
+                        .status()
+                        .map(|status| (status, String::new()))
+                        .map_err(map_privilege_tool_not_found_error)
+                };
 
-            #[cfg(windows)]
-            let status = run_service_command(service_path.as_path(), &["start".into()]);
+                #[cfg(windows)]
+                let status = run_service_command(service_path.as_path(), &["start".into()]);
+
+                #[cfg(all(not(windows), not(all(unix, not(target_os = "macos")))))]
+                let status = {
+                    let mut cmd = RunasCommand::new(service_path.as_path());
+                    cmd.args(&["start"]);
+                    cmd.gui(false).show(false);
+                    cmd.status()
+                        .map(|status| (status, String::new()))
+                        .map_err(anyhow::Error::from)
+                };
 
-            #[cfg(all(not(windows), not(all(unix, not(target_os = "macos")))))]
-            let status = {
-                let mut cmd = RunasCommand::new(service_path.as_path());
-                cmd.args(&["start"]);
-                cmd.gui(false).show(false);
-                cmd.status()
-                    .map(|status| (status, String::new()))
+                status
+            }
+            #[cfg(target_os = "macos")]
+            {
+                use crate::utils::sudo::sudo;
+                const ARGS: &[&str] = &["start"];
+                sudo(service_path.to_string_lossy(), ARGS)
+                    .map(|()| (std::process::ExitStatus::from_raw(0), String::new()))
                     .map_err(anyhow::Error::from)
-            };
-
-            status
-        }
-        #[cfg(target_os = "macos")]
-        {
-            use crate::utils::sudo::sudo;
-            const ARGS: &[&str] = &["start"];
-            sudo(service_path.to_string_lossy(), ARGS)
-                .map(|()| (std::process::ExitStatus::from_raw(0), String::new()))
-                .map_err(anyhow::Error::from)
-        }
-    })
+            }
+        },
+    )
     .await??;
     if !child.success() {
         #[cfg(windows)]
@@ -744,6 +1237,18 @@ pub async fn start_service() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 服务是否由本次运行临时启动
+static STARTED_TRANSIENTLY: AtomicBool = AtomicBool::new(false);
+
+pub fn mark_started_transiently() {
+    STARTED_TRANSIENTLY.store(true, Ordering::Release);
+}
+
+/// 取出临时启动标记，退出时只停止一次
+pub fn take_started_transiently() -> bool {
+    STARTED_TRANSIENTLY.swap(false, Ordering::AcqRel)
+}
+
 pub async fn stop_service() -> anyhow::Result<()> {
     // 先检查服务状态，如果已经停止则直接返回成功
     match status().await {
@@ -824,56 +1329,53 @@ pub async fn stop_service() -> anyhow::Result<()> {
 
 pub async fn restart_service() -> anyhow::Result<()> {
     let service_path = resolve_service_path();
-    let (child, output) = tokio::task::spawn_blocking(move || -> anyhow::Result<(std::process::ExitStatus, String)> {
-        let service_path = service_path;
-        #[cfg(not(target_os = "macos"))]
-        {
-            #[cfg(all(unix, not(target_os = "macos")))]
-            let status = {
-                let service = service_path.to_string_lossy();
-                let cmd = format!(
-                    "\"{}\" restart; for i in $(seq 1 20); do [ -S /run/nyanpasu_ipc.sock ] && break; sleep 0.1; done; if [ -S /run/nyanpasu_ipc.sock ]; then chown root:nyanpasu /run/nyanpasu_ipc.sock && chmod 660 /run/nyanpasu_ipc.sock; fi",
-                    service
-                );
-                RunasCommand::new("/bin/sh")
-                    .arg("-c")
-                    .arg(cmd)
-                    .gui(false)
-                    .show(false)
-                    .status()
-                    .map(|status| (status, String::new()))
-                    .map_err(map_privilege_tool_not_found_error)
-            };
-
-            #[cfg(not(all(unix, not(target_os = "macos"))))]
-            let status = {
-                #[cfg(windows)]
-                {
-                    run_service_command(service_path.as_path(), &["restart".into()])
-                }
-                #[cfg(not(windows))]
-                {
-                    RunasCommand::new(service_path.as_path())
-                        .args(&["restart"])
+    let (child, output) = tokio::task::spawn_blocking(
+        move || -> anyhow::Result<(std::process::ExitStatus, String)> {
+            let service_path = service_path;
+            #[cfg(not(target_os = "macos"))]
+            {
+                #[cfg(all(unix, not(target_os = "macos")))]
+                let status = {
+                    let args = harden_ipc_socket_permissions(&service_path, "restart");
+                    RunasCommand::new("/bin/sh")
+                        .args(&args)
                         .gui(false)
                         .show(false)
                         .status()
                         .map(|status| (status, String::new()))
                         .map_err(map_privilege_tool_not_found_error)
-                }
-            };
+                };
 
-            status
-        }
-        #[cfg(target_os = "macos")]
-        {
-            use crate::utils::sudo::sudo;
-            const ARGS: &[&str] = &["restart"];
-            sudo(service_path.to_string_lossy(), ARGS)
-                .map(|()| (std::process::ExitStatus::from_raw(0), String::new()))
-                .map_err(anyhow::Error::from)
-        }
-    })
+                #[cfg(not(all(unix, not(target_os = "macos"))))]
+                let status = {
+                    #[cfg(windows)]
+                    {
+                        run_service_command(service_path.as_path(), &["restart".into()])
+                    }
+                    #[cfg(not(windows))]
+                    {
+                        RunasCommand::new(service_path.as_path())
+                            .args(&["restart"])
+                            .gui(false)
+                            .show(false)
+                            .status()
+                            .map(|status| (status, String::new()))
+                            .map_err(map_privilege_tool_not_found_error)
+                    }
+                };
+
+                status
+            }
+            #[cfg(target_os = "macos")]
+            {
+                use crate::utils::sudo::sudo;
+                const ARGS: &[&str] = &["restart"];
+                sudo(service_path.to_string_lossy(), ARGS)
+                    .map(|()| (std::process::ExitStatus::from_raw(0), String::new()))
+                    .map_err(anyhow::Error::from)
+            }
+        },
+    )
     .await??;
     if !child.success() {
         anyhow::bail!(
@@ -952,6 +1454,10 @@ pub async fn status<'a>() -> anyhow::Result<nyanpasu_ipc::types::StatusInfo<'a>>
 
     let mut cmd = tokio::process::Command::new(service_path.as_path());
     cmd.args(["status", "--json"]);
+    #[cfg(unix)]
+    if let Some(socket) = custom_ipc_socket_path() {
+        cmd.arg("--socket").arg(socket);
+    }
     #[cfg(windows)]
     cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
 
@@ -1093,3 +1599,322 @@ pub async fn status<'a>() -> anyhow::Result<nyanpasu_ipc::types::StatusInfo<'a>>
         }
     }
 }
+
+/// 核心 release 中的校验清单，sha256sum 格式，记录的是各附件（压缩包）的哈希
+const CHECKSUMS_MANIFEST: &str = "checksums.txt";
+const CHECKSUMS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum VerificationResult {
+    Verified {
+        sha256: String,
+    },
+    Failed {
+        expected: String,
+        actual: String,
+    },
+    /// 上游未发布校验清单，或清单中没有当前平台的条目，无法校验
+    Unavailable {
+        reason: String,
+    },
+}
+
+/// 最近一次核心校验是否失败，注册为 managed state
+#[derive(Debug, Clone, Default)]
+pub struct CoreVerificationState(Arc<AtomicBool>);
+
+impl CoreVerificationState {
+    pub fn core_verification_failed(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    fn set_failed(&self, failed: bool) {
+        self.0.store(failed, Ordering::Release);
+    }
+}
+
+static CORE_VERIFICATION: Lazy<CoreVerificationState> = Lazy::new(Default::default);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ChecksumsCache {
+    /// 以清单地址为键
+    #[serde(default)]
+    manifests: HashMap<String, CachedChecksums>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedChecksums {
+    /// unix 时间戳（秒）
+    fetched_at: u64,
+    /// 附件名称 -> 附件的 sha256
+    checksums: HashMap<String, String>,
+    /// 附件名称 -> 解压出的核心文件的 sha256，附件校验通过后记录
+    #[serde(default)]
+    binaries: HashMap<String, String>,
+}
+
+impl CachedChecksums {
+    fn is_fresh(&self, now: u64) -> bool {
+        now.saturating_sub(self.fetched_at) < CHECKSUMS_CACHE_TTL.as_secs()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// 注册校验状态，并在启动时校验当前核心
+pub fn setup_core_verification<R: tauri::Runtime, M: tauri::Manager<R>>(manager: &M) {
+    manager.manage(CORE_VERIFICATION.clone());
+    let core = Config::clash_core();
+    tauri::async_runtime::spawn(verify_core_if_enabled(core));
+}
+
+/// 核心文件所在路径，与更新时替换的位置一致
+pub fn core_binary_path(core: &ClashCore) -> anyhow::Result<PathBuf> {
+    let exe = tauri::utils::platform::current_exe()?;
+    let dir = exe
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("failed to get core dir"))?;
+    Ok(dir.join(format!("{core}{}", std::env::consts::EXE_SUFFIX)))
+}
+
+/// 首次运行和更新后调用，`skip_core_verification` 为真时跳过
+pub async fn verify_core_if_enabled(core: ClashCore) {
+    if Config::verge()
+        .latest()
+        .skip_core_verification
+        .unwrap_or(false)
+    {
+        return;
+    }
+    let path = match core_binary_path(&core) {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::warn!("failed to resolve core path: {e:?}");
+            return;
+        }
+    };
+    match verify_core_binary(core, &path).await {
+        Ok(VerificationResult::Failed { expected, actual }) => {
+            tracing::error!(
+                "core binary {} failed verification, expected {expected}, got {actual}",
+                path.display()
+            );
+        }
+        Ok(result) => tracing::debug!("core binary verification: {result:?}"),
+        Err(e) => tracing::warn!("failed to verify core binary: {e:?}"),
+    }
+}
+
+/// 按核心版本下载官方校验清单，校验对应的附件后与本地核心文件的 SHA-256 比较
+pub async fn verify_core_binary(
+    core: ClashCore,
+    path: &Path,
+) -> anyhow::Result<VerificationResult> {
+    // clash premium 的备份仓库不发布校验清单
+    if core == ClashCore::ClashPremium {
+        return Ok(VerificationResult::Unavailable {
+            reason: format!("{core} releases do not publish checksums"),
+        });
+    }
+    let version = match CoreInfo::cached(&core) {
+        Some(info) => info.version,
+        None => CoreInfo::detect(&core).await?.version,
+    };
+    let artifact = updater::release_artifact_name(&core, &version).await?;
+    let url = updater::release_asset_url(&core, &version, CHECKSUMS_MANIFEST).await?;
+
+    let cache_path = dirs::checksums_cache_path()?;
+    let mut cache = tokio::fs::read(&cache_path)
+        .await
+        .ok()
+        .and_then(|content| serde_json::from_slice::<ChecksumsCache>(&content).ok())
+        .unwrap_or_default();
+    let now = unix_now();
+    cache.manifests.retain(|_, cached| cached.is_fresh(now));
+    let mut cached = match cache.manifests.remove(&url) {
+        Some(cached) => cached,
+        None => CachedChecksums {
+            fetched_at: now,
+            checksums: fetch_checksums(&url).await?,
+            binaries: HashMap::new(),
+        },
+    };
+    let Some(archive_sha256) = cached.checksums.get(&artifact).cloned() else {
+        cache.manifests.insert(url.clone(), cached);
+        save_checksums_cache(&cache_path, &cache).await;
+        return Ok(VerificationResult::Unavailable {
+            reason: format!("no checksum for {artifact} in {url}"),
+        });
+    };
+    let expected = match cached.binaries.get(&artifact) {
+        Some(sha256) => sha256.clone(),
+        None => {
+            let sha256 =
+                verified_binary_sha256(&core, &version, &artifact, &archive_sha256).await?;
+            cached.binaries.insert(artifact.clone(), sha256.clone());
+            sha256
+        }
+    };
+    cache.manifests.insert(url, cached);
+    save_checksums_cache(&cache_path, &cache).await;
+
+    let actual = hex::encode(Sha256::digest(tokio::fs::read(path).await?));
+    let result = if actual == expected {
+        VerificationResult::Verified { sha256: actual }
+    } else {
+        VerificationResult::Failed { expected, actual }
+    };
+    CORE_VERIFICATION.set_failed(matches!(result, VerificationResult::Failed { .. }));
+    Ok(result)
+}
+
+async fn fetch_checksums(url: &str) -> anyhow::Result<HashMap<String, String>> {
+    let content = crate::utils::candy::get_reqwest_client()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(parse_checksums(&content))
+}
+
+async fn save_checksums_cache(path: &Path, cache: &ChecksumsCache) {
+    let result = match serde_json::to_vec_pretty(cache) {
+        Ok(content) => tokio::fs::write(path, content)
+            .await
+            .map_err(anyhow::Error::from),
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = result {
+        tracing::warn!("failed to write checksums cache: {e}");
+    }
+}
+
+/// 清单只记录附件的哈希，下载附件校验通过后取出其中的核心文件计算哈希
+async fn verified_binary_sha256(
+    core: &ClashCore,
+    version: &str,
+    artifact: &str,
+    archive_sha256: &str,
+) -> anyhow::Result<String> {
+    let url = updater::release_asset_url(core, version, artifact).await?;
+    let archive = crate::utils::candy::get_reqwest_client()?
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let actual = hex::encode(Sha256::digest(&archive));
+    if !actual.eq_ignore_ascii_case(archive_sha256) {
+        anyhow::bail!(
+            "downloaded {artifact} does not match the published checksum, expected {archive_sha256}, got {actual}"
+        );
+    }
+    let artifact = artifact.to_string();
+    let binary = tokio::task::spawn_blocking(move || {
+        updater::extract_core(&artifact, std::io::Cursor::new(archive))
+    })
+    .await??;
+    Ok(hex::encode(Sha256::digest(&binary)))
+}
+
+/// 解析 sha256sum 格式的清单，每行为 `<sha256>  <文件名>`，二进制模式的文件名带 `*` 前缀
+fn parse_checksums(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (sha256, name) = line.trim().split_once(char::is_whitespace)?;
+            let name = name.trim_start().trim_start_matches('*');
+            (sha256.len() == 64 && !name.is_empty())
+                .then(|| (name.to_string(), sha256.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checksums() {
+        let a = "a".repeat(64);
+        let b = "B".repeat(64);
+        let content = format!(
+            "{a}  mihomo-linux-amd64-v1.19.0.gz\n{b} *mihomo-windows-amd64-v1.19.0.zip\n\nnot a checksum line\n"
+        );
+        let checksums = parse_checksums(&content);
+        assert_eq!(checksums.len(), 2);
+        assert_eq!(checksums["mihomo-linux-amd64-v1.19.0.gz"], a);
+        assert_eq!(
+            checksums["mihomo-windows-amd64-v1.19.0.zip"],
+            b.to_ascii_lowercase()
+        );
+    }
+
+    #[test]
+    fn test_validate_ipc_socket_path() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(validate_ipc_socket_path(&dir.path().join("nyanpasu_ipc.sock")).is_ok());
+        assert!(validate_ipc_socket_path(Path::new("nyanpasu_ipc.sock")).is_err());
+        assert!(validate_ipc_socket_path(dir.path()).is_err());
+        assert!(validate_ipc_socket_path(&dir.path().join("missing/nyanpasu_ipc.sock")).is_err());
+        let long = dir.path().join("a".repeat(MAX_IPC_SOCKET_PATH_LEN));
+        assert!(validate_ipc_socket_path(&long).is_err());
+        for name in [
+            "a\"b.sock",
+            "$(id).sock",
+            "`id`.sock",
+            "a;b.sock",
+            "a b.sock",
+        ] {
+            assert!(
+                validate_ipc_socket_path(&dir.path().join(name)).is_err(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_checksums_cache_ttl() {
+        let cached = CachedChecksums {
+            fetched_at: 1_000,
+            checksums: HashMap::new(),
+            binaries: HashMap::new(),
+        };
+        assert!(cached.is_fresh(1_000 + CHECKSUMS_CACHE_TTL.as_secs() - 1));
+        assert!(!cached.is_fresh(1_000 + CHECKSUMS_CACHE_TTL.as_secs()));
+    }
+
+    #[test]
+    fn test_restart_on_crash_recovery_actions() {
+        let config = ServiceRecoveryConfig::restart_on_crash();
+        assert_eq!(
+            config
+                .actions
+                .iter()
+                .map(|a| (a.action, a.delay_ms))
+                .collect::<Vec<_>>(),
+            [5_000, 10_000, 30_000].map(|delay| (ServiceRecoveryActionType::Restart, delay))
+        );
+        assert_eq!(config.reset_period_secs, 86_400);
+        assert_eq!(
+            config.sc_failure_args("svc"),
+            [
+                "failure",
+                "svc",
+                "reset=",
+                "86400",
+                "actions=",
+                "restart/5000/restart/10000/restart/30000"
+            ]
+        );
+    }
+}
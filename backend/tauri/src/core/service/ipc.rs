@@ -1,10 +1,19 @@
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU8, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use atomic_enum::atomic_enum;
 
 use nyanpasu_ipc::types::ServiceStatus;
 use nyanpasu_utils::runtime::block_on;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use serde::Serialize;
+use tauri::Manager;
 use tracing::instrument;
 
 use crate::log_err;
@@ -28,6 +37,56 @@ pub(super) static KILL_FLAG: AtomicBool = AtomicBool::new(false);
 pub(super) static HEALTH_CHECK_RUNNING: AtomicBool = AtomicBool::new(false);
 static DISCONNECT_STREAK: AtomicU8 = AtomicU8::new(0);
 
+/// 健康检查的耗时统计
+#[derive(Debug, Clone, Default, Serialize, specta::Type)]
+pub struct IpcMetrics {
+    pub last_roundtrip_ms: Option<u64>,
+    pub avg_roundtrip_ms: f64,
+    pub max_roundtrip_ms: u64,
+    pub total_checks: u64,
+    pub failed_checks: u64,
+}
+
+impl IpcMetrics {
+    fn record_success(&mut self, roundtrip: Duration) {
+        let ms = roundtrip.as_millis() as u64;
+        let succeeded = self.total_checks - self.failed_checks;
+        self.avg_roundtrip_ms =
+            (self.avg_roundtrip_ms * succeeded as f64 + ms as f64) / (succeeded + 1) as f64;
+        self.last_roundtrip_ms = Some(ms);
+        self.max_roundtrip_ms = self.max_roundtrip_ms.max(ms);
+        self.total_checks += 1;
+    }
+
+    fn record_failure(&mut self) {
+        self.total_checks += 1;
+        self.failed_checks += 1;
+    }
+}
+
+pub type IpcMetricsState = Arc<Mutex<IpcMetrics>>;
+
+static IPC_METRICS: Lazy<IpcMetricsState> = Lazy::new(Default::default);
+
+/// 指标推送间隔
+const IPC_METRICS_EMIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 注册指标到 managed state，并定时推送 `ipc-metrics-updated` 事件
+pub fn setup_metrics<R: tauri::Runtime, M: Manager<R>>(manager: &M) {
+    manager.manage(IPC_METRICS.clone());
+    tauri::async_runtime::spawn(async {
+        let mut interval = tokio::time::interval(IPC_METRICS_EMIT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let snapshot = IPC_METRICS.lock().clone();
+            log_err!(crate::core::handle::Handle::emit(
+                "ipc-metrics-updated",
+                snapshot
+            ));
+        }
+    });
+}
+
 pub fn get_ipc_state() -> IpcState {
     IPC_STATE.load(Ordering::Relaxed)
 }
@@ -160,12 +219,22 @@ pub(super) fn spawn_health_check() {
 }
 
 #[instrument]
-async fn health_check() {
-    match super::control::status().await {
+pub(crate) async fn health_check() {
+    let started_at = Instant::now();
+    let result = super::control::status().await;
+    {
+        let mut metrics = IPC_METRICS.lock();
+        match &result {
+            Ok(_) => metrics.record_success(started_at.elapsed()),
+            Err(_) => metrics.record_failure(),
+        }
+    }
+    match result {
         Ok(info) => match info.status {
             ServiceStatus::Running => {
                 DISCONNECT_STREAK.store(0, Ordering::Release);
                 dispatch_connected();
+                crate::core::clash::watchdog::check_service_core().await;
             }
             ServiceStatus::Stopped | ServiceStatus::NotInstalled => {
                 let streak = DISCONNECT_STREAK.fetch_add(1, Ordering::AcqRel) + 1;
@@ -188,3 +257,21 @@ async fn health_check() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipc_metrics_average() {
+        let mut metrics = IpcMetrics::default();
+        metrics.record_success(Duration::from_millis(10));
+        metrics.record_failure();
+        metrics.record_success(Duration::from_millis(30));
+        assert_eq!(metrics.total_checks, 3);
+        assert_eq!(metrics.failed_checks, 1);
+        assert_eq!(metrics.last_roundtrip_ms, Some(30));
+        assert_eq!(metrics.max_roundtrip_ms, 30);
+        assert!((metrics.avg_roundtrip_ms - 20.0).abs() < f64::EPSILON);
+    }
+}
@@ -1,60 +1,199 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use nyanpasu_ipc::types::StatusInfo;
+use serde::Serialize;
 
 use crate::{config::Config, utils::dirs::app_install_dir};
 
+use binary::Machine;
+
+pub mod binary;
 pub mod control;
 pub mod ipc;
 
 const SERVICE_NAME: &str = "nyanpasu-service";
 const SERVICE_TARGET_TRIPLE: Option<&str> = option_env!("TAURI_ENV_TARGET_TRIPLE");
 
+/// The triple of the running build when `TAURI_ENV_TARGET_TRIPLE` was not set
+fn default_target_triple() -> Option<String> {
+    let arch = Machine::from_arch(std::env::consts::ARCH).triple_arch()?;
+    let rest = if cfg!(windows) {
+        "pc-windows-msvc"
+    } else if cfg!(target_os = "macos") {
+        "apple-darwin"
+    } else if cfg!(target_os = "linux") {
+        "unknown-linux-gnu"
+    } else {
+        return None;
+    };
+    Some(format!("{arch}-{rest}"))
+}
+
+/// Target triples a sidecar may be suffixed with, the compiled triple first.
+/// Repackaged builds may ship the gnu variant of a msvc build and vice versa,
+/// and builds for other machines can still run here via emulation.
+fn candidate_triples(built: &str, machines: &[Machine]) -> Vec<String> {
+    let (built_arch, rest) = built.split_once('-').unwrap_or((built, ""));
+    let mut rests = vec![rest.to_string()];
+    if let Some(prefix) = rest.strip_suffix("-msvc") {
+        rests.push(format!("{prefix}-gnu"));
+    } else if let Some(prefix) = rest.strip_suffix("-gnu") {
+        rests.push(format!("{prefix}-msvc"));
+    }
+    let mut arches = vec![built_arch];
+    arches.extend(machines.iter().filter_map(|m| m.triple_arch()));
+
+    let mut triples = Vec::new();
+    for arch in arches {
+        for rest in &rests {
+            let triple = format!("{arch}-{rest}");
+            if !triples.contains(&triple) {
+                triples.push(triple);
+            }
+        }
+    }
+    triples
+}
+
 fn service_file_names() -> Vec<String> {
     let mut names = Vec::new();
     names.push(format!("{}{}", SERVICE_NAME, std::env::consts::EXE_SUFFIX));
 
-    if let Some(triple) = SERVICE_TARGET_TRIPLE {
-        names.push(format!(
-            "{}-{}{}",
-            SERVICE_NAME,
-            triple,
-            std::env::consts::EXE_SUFFIX
-        ));
+    let built = SERVICE_TARGET_TRIPLE
+        .map(str::to_string)
+        .or_else(default_target_triple);
+    if let Some(built) = built {
+        let machines = binary::compatible_machines(binary::native_machine());
+        for triple in candidate_triples(&built, &machines) {
+            names.push(format!(
+                "{}-{}{}",
+                SERVICE_NAME,
+                triple,
+                std::env::consts::EXE_SUFFIX
+            ));
+        }
     }
 
     names
 }
 
-/// Get service executable path with improved resolution logic
-pub fn get_service_path() -> anyhow::Result<PathBuf> {
-    // Try multiple possible locations in order of preference
-    let candidates = get_service_path_candidates()?;
+#[derive(Debug, Clone, Serialize)]
+pub struct ServicePathCandidate {
+    pub path: PathBuf,
+    pub source: &'static str,
+    pub exists: bool,
+    /// Why an existing file was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<String>,
+}
+
+/// Every candidate checked while resolving the service executable
+#[derive(Debug, Clone, Serialize)]
+pub struct ServicePathReport {
+    pub candidates: Vec<ServicePathCandidate>,
+    /// Index into `candidates`
+    pub selected: Option<usize>,
+}
+
+impl ServicePathReport {
+    fn build(
+        candidates: Vec<(PathBuf, &'static str)>,
+        check: impl Fn(&Path) -> Result<(), String>,
+    ) -> Self {
+        let mut report = ServicePathReport {
+            candidates: Vec::with_capacity(candidates.len()),
+            selected: None,
+        };
+        for (path, source) in candidates {
+            let exists = path.exists();
+            let rejection = if exists { check(&path).err() } else { None };
+            let accepted = exists && rejection.is_none();
+            report.candidates.push(ServicePathCandidate {
+                path,
+                source,
+                exists,
+                rejection,
+            });
+            if accepted {
+                report.selected = Some(report.candidates.len() - 1);
+                break;
+            }
+        }
+        report
+    }
+
+    pub fn selected(&self) -> Option<&ServicePathCandidate> {
+        self.selected.map(|i| &self.candidates[i])
+    }
 
+    pub fn rejected(&self) -> impl Iterator<Item = &ServicePathCandidate> {
+        self.candidates.iter().filter(|c| c.rejection.is_some())
+    }
+}
+
+/// Check the candidate locations in order of preference
+pub fn service_path_report() -> anyhow::Result<ServicePathReport> {
+    let candidates = get_service_path_candidates()?;
     tracing::debug!(
         "🔍 Searching for nyanpasu-service executable in {} locations",
         candidates.len()
     );
-
-    for (i, path) in candidates.iter().enumerate() {
-        tracing::debug!("  {}: {:?} - exists: {}", i + 1, path, path.exists());
-        if path.exists() {
-            tracing::info!("✅ Found nyanpasu-service at: {:?}", path);
-            return Ok(path.clone());
+    let report = ServicePathReport::build(candidates, binary::check_executable);
+    for (i, candidate) in report.candidates.iter().enumerate() {
+        tracing::debug!(
+            "  {}: {:?} ({}) - exists: {}",
+            i + 1,
+            candidate.path,
+            candidate.source,
+            candidate.exists
+        );
+        if let Some(reason) = &candidate.rejection {
+            tracing::warn!(
+                "skip nyanpasu-service at {:?} ({}): {reason}",
+                candidate.path,
+                candidate.source
+            );
         }
     }
+    Ok(report)
+}
 
-    // If none found, return the most likely fallback
-    let app_path = app_install_dir()?;
-    let fallback_path = app_path.join(format!("{}{}", SERVICE_NAME, std::env::consts::EXE_SUFFIX));
-    tracing::warn!(
-        "❌ Service executable not found in any candidate location. Using fallback: {:?}",
-        fallback_path
-    );
-    Ok(fallback_path)
+/// Get service executable path with improved resolution logic
+pub fn get_service_path() -> anyhow::Result<PathBuf> {
+    locate_service_path().map(|(path, _)| path)
+}
+
+/// Same as `get_service_path`, also returns the source of the chosen path
+pub fn locate_service_path() -> anyhow::Result<(PathBuf, &'static str)> {
+    service_path_report()?.selected_or_fallback()
 }
 
-fn get_service_path_candidates() -> anyhow::Result<Vec<PathBuf>> {
+impl ServicePathReport {
+    /// The selected candidate, or the install dir path when none was accepted
+    pub fn selected_or_fallback(&self) -> anyhow::Result<(PathBuf, &'static str)> {
+        if let Some(candidate) = self.selected() {
+            tracing::info!(
+                "✅ Found nyanpasu-service at: {:?} ({})",
+                candidate.path,
+                candidate.source
+            );
+            return Ok((candidate.path.clone(), candidate.source));
+        }
+
+        // If none found, return the most likely fallback
+        let app_path = app_install_dir()?;
+        let fallback_path =
+            app_path.join(format!("{}{}", SERVICE_NAME, std::env::consts::EXE_SUFFIX));
+        tracing::warn!(
+            "❌ Service executable not found in any candidate location. Using fallback: {:?}",
+            fallback_path
+        );
+        Ok((fallback_path, "fallback"))
+    }
+}
+
+/// Candidate paths, each annotated with the source it comes from
+fn get_service_path_candidates() -> anyhow::Result<Vec<(PathBuf, &'static str)>> {
     use crate::utils::dirs::app_data_dir;
 
     let mut candidates = Vec::new();
@@ -63,33 +202,44 @@ fn get_service_path_candidates() -> anyhow::Result<Vec<PathBuf>> {
     // This is the version packaged with the app during build
     if let Ok(app_path) = app_install_dir() {
         for file_name in service_file_names() {
-            candidates.push(app_path.join(&file_name));
+            candidates.push((app_path.join(&file_name), "install dir"));
+        }
+    }
+
+    // 2. Try user binary directories (Unix), e.g. installed via `cargo install`
+    #[cfg(unix)]
+    if let Some(home) = std::env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        for (dir, source) in [(".local/bin", "XDG bin"), (".cargo/bin", "cargo bin")] {
+            for file_name in service_file_names() {
+                candidates.push((home.join(dir).join(&file_name), source));
+            }
         }
     }
 
-    // 2. Try app data directory (user-downloaded/updated files)
+    // 3. Try app data directory (user-downloaded/updated files)
     // This is where users can manually place newer versions of the service
     if let Ok(data_path) = app_data_dir() {
         for file_name in service_file_names() {
-            candidates.push(data_path.join(&file_name));
+            candidates.push((data_path.join(&file_name), "data dir"));
         }
     }
 
-    // 3. Try current exe directory (development/portable)
+    // 4. Try current exe directory (development/portable)
     if let Ok(current_exe) = std::env::current_exe() {
         if let Some(exe_dir) = current_exe.parent() {
             // Development: backend/target/debug/sidecar/
             for file_name in service_file_names() {
-                candidates.push(exe_dir.join("sidecar").join(&file_name));
+                candidates.push((exe_dir.join("sidecar").join(&file_name), "sidecar dir"));
             }
             // Production: same directory as exe
             for file_name in service_file_names() {
-                candidates.push(exe_dir.join(&file_name));
+                candidates.push((exe_dir.join(&file_name), "exe dir"));
             }
         }
     }
 
-    // 4. Try common installation paths (Windows)
+    // 5. Try common installation paths (Windows)
     #[cfg(windows)]
     {
         // Program Files installation path
@@ -98,12 +248,12 @@ fn get_service_path_candidates() -> anyhow::Result<Vec<PathBuf>> {
                 let program_files_path = PathBuf::from(&program_files)
                     .join("nyanpasu")
                     .join(&file_name);
-                candidates.push(program_files_path);
+                candidates.push((program_files_path, "program files"));
             }
         }
     }
 
-    // 5. Try installed service location (Windows)
+    // 6. Try installed service location (Windows)
     #[cfg(windows)]
     {
         if let Some(program_data) = std::env::var_os("PROGRAMDATA") {
@@ -112,11 +262,19 @@ fn get_service_path_candidates() -> anyhow::Result<Vec<PathBuf>> {
                     .join("nyanpasu-service")
                     .join("data")
                     .join(&file_name);
-                candidates.push(program_data_path);
+                candidates.push((program_data_path, "program data"));
             }
         }
     }
 
+    // 7. Try every directory in PATH, before falling back to the install dir
+    if let Some(path) = std::env::var_os("PATH") {
+        let file_name = format!("{}{}", SERVICE_NAME, std::env::consts::EXE_SUFFIX);
+        for dir in std::env::split_paths(&path).filter(|dir| !dir.as_os_str().is_empty()) {
+            candidates.push((dir.join(&file_name), "PATH"));
+        }
+    }
+
     Ok(candidates)
 }
 
@@ -157,3 +315,73 @@ pub async fn init_service() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{binary::tests::native_binary, *};
+
+    #[test]
+    fn test_candidate_triples() {
+        let triples = candidate_triples(
+            "x86_64-pc-windows-msvc",
+            &[Machine::Aarch64, Machine::X86_64, Machine::X86],
+        );
+        assert_eq!(
+            triples,
+            [
+                "x86_64-pc-windows-msvc",
+                "x86_64-pc-windows-gnu",
+                "aarch64-pc-windows-msvc",
+                "aarch64-pc-windows-gnu",
+                "i686-pc-windows-msvc",
+                "i686-pc-windows-gnu",
+            ]
+        );
+        assert_eq!(
+            candidate_triples("aarch64-apple-darwin", &[Machine::Aarch64]),
+            ["aarch64-apple-darwin"]
+        );
+    }
+
+    #[test]
+    fn test_report_skips_foreign_binaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let native = binary::native_machine();
+        let foreign = Machine::Other(0xffff);
+        let write = |name: &str, content: Vec<u8>| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        let candidates = vec![
+            (dir.path().join("missing"), "install dir"),
+            (write("foreign", native_binary(foreign)), "data dir"),
+            (write("garbage", b"not a binary".to_vec()), "sidecar dir"),
+            (write("native", native_binary(native)), "exe dir"),
+            (write("unchecked", native_binary(foreign)), "PATH"),
+        ];
+        let report = ServicePathReport::build(candidates, binary::check_executable);
+
+        assert_eq!(report.selected().unwrap().source, "exe dir");
+        // candidates after the selected one are not checked
+        assert_eq!(report.candidates.len(), 4);
+        assert!(!report.candidates[0].exists);
+        assert!(report.candidates[0].rejection.is_none());
+
+        let rejected = report
+            .rejected()
+            .map(|c| (c.source, c.rejection.clone().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(rejected.len(), 2);
+        assert_eq!(rejected[0].0, "data dir");
+        assert!(
+            rejected[0].1.contains("built for Other(65535)"),
+            "{}",
+            rejected[0].1
+        );
+        assert_eq!(
+            rejected[1],
+            ("sidecar dir", "unrecognized executable format".to_string())
+        );
+    }
+}
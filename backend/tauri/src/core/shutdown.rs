@@ -0,0 +1,94 @@
+//! 退出时的清理流程：停止核心、恢复系统代理、停止临时启动的服务
+//! 每个步骤都有超时，核心或服务卡死时也不会阻塞退出
+use crate::{
+    core::{
+        CoreManager, clash::ws::ClashConnectionsConnector, emergency, service::control,
+        sysopt::Sysopt,
+    },
+    log_err,
+};
+use anyhow::{Result, anyhow};
+use std::{future::Future, time::Duration};
+use tauri::Manager;
+
+/// 单个步骤的超时时间
+const STEP_TIMEOUT: Duration = Duration::from_secs(3);
+/// 需要用户授权的步骤留出输入密码的时间
+const ELEVATED_STEP_TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn with_timeout<T>(step: &str, fut: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(STEP_TIMEOUT, fut)
+        .await
+        .map_err(|_| anyhow!("{step} timed out after {STEP_TIMEOUT:?}"))?
+}
+
+/// 停止核心，超时或失败时直接结束核心进程，避免残留
+async fn stop_core() {
+    if let Err(e) = with_timeout("stopping core", CoreManager::global().stop_core()).await {
+        tracing::warn!("failed to stop core gracefully: {e:#}");
+        log_err!(emergency::kill_core_process().await);
+    }
+}
+
+async fn reset_sysproxy() -> Result<()> {
+    with_timeout("resetting system proxy", async {
+        tokio::task::spawn_blocking(|| Sysopt::global().reset_sysproxy()).await?
+    })
+    .await
+}
+
+/// 核心停止后活跃连接会被清空，需要在停止核心之前保存
+/// 安全模式或启动失败时没有注册连接订阅，跳过保存
+async fn save_connection_session() -> Result<()> {
+    let Some(connector) = crate::consts::app_handle().try_state::<ClashConnectionsConnector>()
+    else {
+        tracing::debug!("connections connector is not managed, skip saving connection session");
+        return Ok(());
+    };
+    let connector = connector.inner().clone();
+    with_timeout("saving connection session", connector.save_session()).await
+}
+
+/// 退出前调用，可重复调用
+pub async fn graceful_shutdown() {
+    tracing::info!("graceful shutdown started");
+    log_err!(save_connection_session().await);
+    // 先恢复系统代理，核心停止后代理端口不可用会导致断网
+    log_err!(reset_sysproxy().await);
+    stop_core().await;
+    // 严格路由规则不随进程退出失效，TUN 关闭后需要移除
+    match tokio::time::timeout(
+        ELEVATED_STEP_TIMEOUT,
+        crate::utils::platform::remove_strict_routing_on_exit(),
+    )
+    .await
+    {
+        Ok(result) => log_err!(result, "failed to remove strict routing rules"),
+        Err(_) => tracing::warn!("removing strict routing rules timed out"),
+    }
+    if control::take_started_transiently() {
+        log_err!(with_timeout("stopping service", control::stop_service()).await);
+    }
+    tracing::info!("graceful shutdown finished");
+}
+
+/// 等待 SIGTERM 或 SIGINT
+#[cfg(unix)]
+pub async fn wait_for_termination() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let (mut term, mut int) = match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(term), Ok(int)) => (term, int),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::warn!("failed to register termination signals: {e}");
+            return std::future::pending().await;
+        }
+    };
+    tokio::select! {
+        _ = term.recv() => {}
+        _ = int.recv() => {}
+    }
+}
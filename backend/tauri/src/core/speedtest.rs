@@ -0,0 +1,485 @@
+//! 通过当前代理（mixed port）测速
+//! 指定节点时会临时切换到 global 模式并固定 GLOBAL 选择，结束或切换失败后恢复原来的选择与模式
+use crate::{
+    config::Config,
+    core::{clash::api, handle::Handle},
+    log_err,
+    utils::config::{ClashMode, current_clash_mode},
+};
+use anyhow::{Context, Result, bail};
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use specta::Type;
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// 默认下载测速地址
+pub const DEFAULT_SPEEDTEST_ENDPOINTS: &[&str] = &[
+    "https://speed.cloudflare.com/__down?bytes=104857600",
+    "http://cachefly.cachefly.net/100mb.test",
+];
+/// 上传测速地址
+const UPLOAD_ENDPOINT: &str = "https://speed.cloudflare.com/__up";
+/// 默认流量上限 100MB，保护按量计费的订阅
+pub const DEFAULT_SPEEDTEST_BYTE_CAP: u64 = 100 * 1024 * 1024;
+const MAX_DURATION_SECS: u64 = 60;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+const HISTORY_CAPACITY: usize = 20;
+const PROGRESS_EVENT: &str = "speedtest-progress";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedtestDirection {
+    Download,
+    Upload,
+    Both,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeedtestProgress {
+    pub direction: SpeedtestDirection,
+    pub bytes: u64,
+    pub elapsed_ms: u64,
+    pub instant_mbps: f64,
+    pub avg_mbps: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeedtestResult {
+    /// unix 时间戳（毫秒）
+    pub started_at: i64,
+    pub node: Option<String>,
+    pub endpoint: Option<String>,
+    pub download_mbps: Option<f64>,
+    pub upload_mbps: Option<f64>,
+    pub total_bytes: u64,
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static HISTORY: Lazy<Mutex<VecDeque<SpeedtestResult>>> = Lazy::new(Default::default);
+
+/// 运行标记，保证同一时间只有一个测速
+struct RunningGuard;
+
+impl RunningGuard {
+    fn acquire() -> Result<Self> {
+        if RUNNING
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            bail!("a speedtest is already running");
+        }
+        CANCELLED.store(false, Ordering::Release);
+        Ok(Self)
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::Release);
+    }
+}
+
+/// 取消正在进行的测速
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::Release);
+}
+
+pub fn history() -> Vec<SpeedtestResult> {
+    HISTORY.lock().iter().cloned().collect()
+}
+
+/// 本次测速的流量上限与实际收发的字节数
+struct ByteBudget {
+    cap: u64,
+    used: AtomicU64,
+}
+
+impl ByteBudget {
+    fn new(cap: u64) -> Self {
+        Self {
+            cap,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    /// 记录已经收到的字节，达到上限时返回 false
+    fn record(&self, len: u64) -> bool {
+        let used = self.used.fetch_add(len, Ordering::AcqRel) + len;
+        used < self.cap
+    }
+
+    /// 发送前预留字节，超出上限时不预留并返回 false
+    fn reserve(&self, len: u64) -> bool {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(len).filter(|total| *total <= self.cap)
+            })
+            .is_ok()
+    }
+
+    fn used(&self) -> u64 {
+        self.used.load(Ordering::Acquire)
+    }
+}
+
+fn to_mbps(bytes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return 0.0;
+    }
+    bytes as f64 * 8.0 / secs / 1_000_000.0
+}
+
+/// 统计字节数并定时推送进度
+struct Meter {
+    direction: SpeedtestDirection,
+    started_at: Instant,
+    last_emit: Instant,
+    bytes_at_last_emit: u64,
+    bytes: u64,
+}
+
+impl Meter {
+    fn new(direction: SpeedtestDirection) -> Self {
+        let now = Instant::now();
+        Self {
+            direction,
+            started_at: now,
+            last_emit: now,
+            bytes_at_last_emit: 0,
+            bytes: 0,
+        }
+    }
+
+    fn add(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        let since_last = self.last_emit.elapsed();
+        if since_last >= PROGRESS_INTERVAL {
+            let progress = SpeedtestProgress {
+                direction: self.direction,
+                bytes: self.bytes,
+                elapsed_ms: self.started_at.elapsed().as_millis() as u64,
+                instant_mbps: to_mbps(self.bytes - self.bytes_at_last_emit, since_last),
+                avg_mbps: self.avg_mbps(),
+            };
+            log_err!(Handle::emit(PROGRESS_EVENT, progress));
+            self.last_emit = Instant::now();
+            self.bytes_at_last_emit = self.bytes;
+        }
+    }
+
+    fn avg_mbps(&self) -> f64 {
+        to_mbps(self.bytes, self.started_at.elapsed())
+    }
+}
+
+/// 测速期间被临时修改的选择，用于恢复
+struct PinnedSelection {
+    mode: ClashMode,
+    global_now: Option<String>,
+}
+
+async fn pin_node(node: &str) -> Result<PinnedSelection> {
+    let proxies = api::get_proxies().await?;
+    let global = proxies
+        .proxies
+        .get("GLOBAL")
+        .context("GLOBAL selector is missing")?;
+    if !global
+        .all
+        .as_ref()
+        .is_some_and(|all| all.iter().any(|n| n == node))
+    {
+        bail!("node `{node}` is not selectable in GLOBAL");
+    }
+    let pinned = PinnedSelection {
+        mode: current_clash_mode().await,
+        global_now: global.now.clone(),
+    };
+    // 仅修改运行时状态，不写入配置文件
+    let mut mapping = Mapping::new();
+    mapping.insert("mode".into(), ClashMode::Global.as_str().into());
+    api::patch_configs(&mapping).await?;
+    // 切换节点失败时模式已经改变，同样需要恢复
+    if let Err(e) = api::update_proxy("GLOBAL", node).await {
+        restore_selection(&pinned).await;
+        return Err(e);
+    }
+    Ok(pinned)
+}
+
+/// 先恢复 GLOBAL 的选择，再恢复模式，避免恢复期间流量经过测速节点
+async fn restore_selection(pinned: &PinnedSelection) {
+    if let Some(now) = &pinned.global_now {
+        log_err!(
+            api::update_proxy("GLOBAL", now).await,
+            "failed to restore the GLOBAL selection after speedtest"
+        );
+    }
+    log_err!(
+        api::patch_configs(&restore_mode_patch(&pinned.mode)).await,
+        "failed to restore the clash mode after speedtest"
+    );
+}
+
+fn restore_mode_patch(mode: &ClashMode) -> Mapping {
+    let mut mapping = Mapping::new();
+    mapping.insert("mode".into(), mode.as_str().into());
+    mapping
+}
+
+fn build_client() -> Result<reqwest::Client> {
+    let port = crate::core::sysopt::proxy_port();
+    let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{port}"))?;
+    Ok(reqwest::ClientBuilder::new()
+        .proxy(proxy)
+        .connect_timeout(Duration::from_secs(10))
+        .build()?)
+}
+
+/// 选择首字节最快的测速地址，探测时收到的数据同样计入流量
+async fn select_endpoint(
+    client: &reqwest::Client,
+    endpoints: &[String],
+    budget: &ByteBudget,
+) -> Result<String> {
+    let mut best: Option<(Duration, &String)> = None;
+    for endpoint in endpoints {
+        let started_at = Instant::now();
+        let resp = tokio::time::timeout(
+            Duration::from_secs(5),
+            client
+                .get(endpoint)
+                .header(reqwest::header::RANGE, "bytes=0-1023")
+                .send(),
+        )
+        .await;
+        // 仅测量首字节时间，只读取第一个分块，其余随 drop 丢弃
+        if let Ok(Ok(mut resp)) = resp
+            && resp.status().is_success()
+        {
+            let ttfb = started_at.elapsed();
+            if let Ok(Ok(Some(chunk))) =
+                tokio::time::timeout(Duration::from_secs(5), resp.chunk()).await
+            {
+                budget.record(chunk.len() as u64);
+            }
+            if best.is_none_or(|(d, _)| ttfb < d) {
+                best = Some((ttfb, endpoint));
+            }
+        }
+    }
+    best.map(|(_, e)| e.clone())
+        .context("no speedtest endpoint is reachable")
+}
+
+async fn run_download(
+    client: &reqwest::Client,
+    endpoint: &str,
+    deadline: Duration,
+    budget: &ByteBudget,
+) -> Result<f64> {
+    let mut meter = Meter::new(SpeedtestDirection::Download);
+    let resp = client.get(endpoint).send().await?.error_for_status()?;
+    let mut stream = resp.bytes_stream();
+    while meter.started_at.elapsed() < deadline && !CANCELLED.load(Ordering::Acquire) {
+        let remaining = deadline.saturating_sub(meter.started_at.elapsed());
+        let Ok(chunk) = tokio::time::timeout(remaining, stream.next()).await else {
+            break;
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        // 已经收到的分块无论是否超出上限都计入
+        let len = chunk?.len() as u64;
+        meter.add(len);
+        if !budget.record(len) {
+            tracing::info!("speedtest byte cap reached");
+            break;
+        }
+    }
+    Ok(meter.avg_mbps())
+}
+
+async fn run_upload(
+    client: &reqwest::Client,
+    deadline: Duration,
+    budget: Arc<ByteBudget>,
+) -> Result<f64> {
+    let meter = Arc::new(Mutex::new(Meter::new(SpeedtestDirection::Upload)));
+    let chunk = bytes::Bytes::from(vec![0u8; UPLOAD_CHUNK_SIZE]);
+    let stream_meter = meter.clone();
+    let stream = futures::stream::unfold((), move |_| {
+        let chunk = chunk.clone();
+        let meter = stream_meter.clone();
+        let budget = budget.clone();
+        async move {
+            let mut meter = meter.lock();
+            if meter.started_at.elapsed() >= deadline
+                || CANCELLED.load(Ordering::Acquire)
+                || !budget.reserve(chunk.len() as u64)
+            {
+                return None;
+            }
+            meter.add(chunk.len() as u64);
+            Some((Ok::<_, std::io::Error>(chunk), ()))
+        }
+    });
+    client
+        .post(UPLOAD_ENDPOINT)
+        .body(reqwest::Body::wrap_stream(stream))
+        .send()
+        .await?
+        .error_for_status()?;
+    let avg = meter.lock().avg_mbps();
+    Ok(avg)
+}
+
+/// 执行一次测速，并写入历史
+pub async fn speedtest(
+    node: Option<String>,
+    duration_secs: u64,
+    direction: SpeedtestDirection,
+) -> Result<SpeedtestResult> {
+    let _guard = RunningGuard::acquire()?;
+    let (endpoints, byte_cap) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.speedtest_endpoints.clone().unwrap_or_else(|| {
+                DEFAULT_SPEEDTEST_ENDPOINTS
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect()
+            }),
+            verge
+                .speedtest_byte_cap
+                .unwrap_or(DEFAULT_SPEEDTEST_BYTE_CAP),
+        )
+    };
+    let deadline = Duration::from_secs(duration_secs.clamp(1, MAX_DURATION_SECS));
+    let budget = Arc::new(ByteBudget::new(byte_cap));
+    let mut result = SpeedtestResult {
+        started_at: chrono::Utc::now().timestamp_millis(),
+        node: node.clone(),
+        endpoint: None,
+        download_mbps: None,
+        upload_mbps: None,
+        total_bytes: 0,
+        cancelled: false,
+        error: None,
+    };
+
+    let pinned = match node.as_deref() {
+        Some(node) => Some(pin_node(node).await?),
+        None => None,
+    };
+    let outcome = async {
+        let client = build_client()?;
+        if matches!(
+            direction,
+            SpeedtestDirection::Download | SpeedtestDirection::Both
+        ) {
+            let endpoint = select_endpoint(&client, &endpoints, &budget).await?;
+            result.download_mbps = Some(run_download(&client, &endpoint, deadline, &budget).await?);
+            result.endpoint = Some(endpoint);
+        }
+        if matches!(
+            direction,
+            SpeedtestDirection::Upload | SpeedtestDirection::Both
+        ) && !CANCELLED.load(Ordering::Acquire)
+        {
+            result.upload_mbps = Some(run_upload(&client, deadline, budget.clone()).await?);
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    if let Some(pinned) = &pinned {
+        restore_selection(pinned).await;
+    }
+
+    result.total_bytes = budget.used();
+    result.cancelled = CANCELLED.load(Ordering::Acquire);
+    if let Err(e) = outcome {
+        result.error = Some(format!("{e:#}"));
+    }
+    {
+        let mut history = HISTORY.lock();
+        if history.len() >= HISTORY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back(result.clone());
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+
+    const PAYLOAD_SIZE: usize = 4 * 1024 * 1024;
+
+    async fn serve() -> String {
+        let app = Router::new().route("/down", get(|| async { vec![0u8; PAYLOAD_SIZE] }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}/down")
+    }
+
+    #[test]
+    fn test_byte_budget() {
+        let budget = ByteBudget::new(100);
+        assert!(budget.reserve(60));
+        // 超出上限时不预留
+        assert!(!budget.reserve(60));
+        assert_eq!(budget.used(), 60);
+        // 已收到的字节即使超出上限也要计入
+        assert!(!budget.record(50));
+        assert_eq!(budget.used(), 110);
+    }
+
+    #[test]
+    fn test_restore_mode_patch() {
+        let patch = restore_mode_patch(&ClashMode::Rule);
+        assert_eq!(patch.len(), 1);
+        assert_eq!(patch.get("mode").and_then(|v| v.as_str()), Some("rule"));
+    }
+
+    #[tokio::test]
+    async fn test_download_counts_received_bytes() {
+        let url = serve().await;
+        let client = reqwest::Client::new();
+
+        let budget = ByteBudget::new(u64::MAX);
+        run_download(&client, &url, Duration::from_secs(10), &budget)
+            .await
+            .unwrap();
+        assert_eq!(budget.used(), PAYLOAD_SIZE as u64);
+
+        let cap = 64 * 1024;
+        let budget = ByteBudget::new(cap);
+        run_download(&client, &url, Duration::from_secs(10), &budget)
+            .await
+            .unwrap();
+        let used = budget.used();
+        assert!(used >= cap && used < PAYLOAD_SIZE as u64, "{used}");
+    }
+}
@@ -1,4 +1,15 @@
-use crate::{config::Config, log_err};
+use crate::{
+    config::{Config, nyanpasu::SystemProxyMode},
+    core::{
+        pac::{self, PacServer},
+        proxy_recovery::{self, ProxyMarker},
+    },
+    log_err,
+    utils::{
+        config::{BypassManager, GlobalExcludeList},
+        net::ClashMixedPortRangeScanner,
+    },
+};
 use anyhow::{Result, anyhow};
 use auto_launch::{AutoLaunch, AutoLaunchBuilder};
 use once_cell::sync::OnceCell;
@@ -53,6 +64,24 @@ fn get_autostart_requirements(desktop_env: &str) -> (bool, Vec<String>) {
     }
 }
 
+/// 系统代理使用的端口
+pub(crate) fn proxy_port() -> u16 {
+    let port = Config::verge()
+        .latest()
+        .verge_mixed_port
+        .unwrap_or(Config::clash().latest().get_mixed_port());
+    ClashMixedPortRangeScanner::substituted(port).unwrap_or(port)
+}
+
+pub(crate) fn default_bypass() -> Vec<String> {
+    BypassManager::split(DEFAULT_BYPASS)
+}
+
+/// 配置的绕过列表，未设置时使用默认值，并合并全局排除的域名
+pub(crate) fn effective_bypass(bypass: Option<Vec<String>>) -> Vec<String> {
+    GlobalExcludeList::merge_into_bypass(bypass.unwrap_or_else(default_bypass))
+}
+
 impl Sysopt {
     pub fn global() -> &'static Sysopt {
         static SYSOPT: OnceCell<Sysopt> = OnceCell::new();
@@ -65,8 +94,65 @@ impl Sysopt {
         })
     }
 
+    /// 按配置开启或关闭 PAC 模式，端口或绕过列表变化后同样需要调用以重新生成脚本
+    pub fn update_pac(&self) -> Result<()> {
+        let (enable, mode, pac_port, bypass) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.enable_system_proxy.unwrap_or(false),
+                verge.system_proxy_mode.unwrap_or_default(),
+                verge.pac_server_port,
+                verge.system_proxy_bypass.clone(),
+            )
+        };
+        if !enable || mode != SystemProxyMode::Pac {
+            return self.disable_pac();
+        }
+        // 从静态代理切换过来时先关闭静态代理，两者同时开启时静态代理优先
+        let mut current = Sysproxy::get_system_proxy()?;
+        if current.enable {
+            current.enable = false;
+            current.set_system_proxy()?;
+            log::info!(target: "app", "static system proxy disabled for pac mode");
+        }
+        let bypass = effective_bypass(bypass);
+        let server = PacServer::global();
+        server.set_content(pac::generate_pac("127.0.0.1", proxy_port(), &bypass));
+        let url = server.start(pac_port)?;
+        pac::set_auto_config_url(&url)?;
+        proxy_recovery::mark(ProxyMarker {
+            host: "127.0.0.1".into(),
+            port: proxy_port(),
+            pac_url: Some(url.clone()),
+        });
+        log::info!(target: "app", "pac system proxy set to {url}");
+        Ok(())
+    }
+
+    /// 停止 PAC 服务并清除系统中由本应用写入的 PAC 地址
+    /// 只有地址与本次运行或标记中记录的地址一致时才清除，其他来源的 PAC 设置保持不变
+    pub fn disable_pac(&self) -> Result<()> {
+        let server = PacServer::global();
+        let owned: Vec<String> = server
+            .url()
+            .into_iter()
+            .chain(proxy_recovery::marked_pac_url())
+            .collect();
+        let was_running = server.stop();
+        if !owned.is_empty() && pac::clear_auto_config_url(&owned)? {
+            log::info!(target: "app", "pac url removed from system settings");
+        }
+        if was_running {
+            proxy_recovery::clear();
+            log::info!(target: "app", "pac system proxy cleared");
+        }
+        Ok(())
+    }
+
     /// reset the sysproxy
     pub fn reset_sysproxy(&self) -> Result<()> {
+        log_err!(self.disable_pac());
         let mut cur_sysproxy = self.cur_sysproxy.lock();
         let mut old_sysproxy = self.old_sysproxy.lock();
 
@@ -93,10 +179,28 @@ impl Sysopt {
         } else {
             log::info!(target: "app", "reset proxy with no action");
         }
+        proxy_recovery::clear();
 
         Ok(())
     }
 
+    /// disable the system proxy right away, without restoring the original one
+    /// the recorded proxies are dropped so that exiting won't turn it on again
+    pub fn disable_sysproxy_now(&self) -> Result<()> {
+        self.cur_sysproxy.lock().take();
+        self.old_sysproxy.lock().take();
+        let pac = self.disable_pac();
+
+        let mut current = Sysproxy::get_system_proxy()?;
+        if current.enable {
+            current.enable = false;
+            current.set_system_proxy()?;
+            log::info!(target: "app", "system proxy disabled by emergency reset");
+        }
+        proxy_recovery::clear();
+        pac
+    }
+
     /// init the auto launch
     pub fn init_launch(&self) -> Result<()> {
         let enable = { Config::verge().latest().enable_auto_launch };
@@ -104,6 +208,14 @@ impl Sysopt {
 
         log::info!(target: "app", "Initializing auto-launch with enable={}", enable);
 
+        // 便携模式下不写入系统的启动项
+        if crate::utils::dirs::get_portable_flag() {
+            if enable {
+                log::warn!(target: "app", "auto launch is not supported in portable mode, skipped");
+            }
+            return Ok(());
+        }
+
         let app_exe = current_exe()?;
         let app_exe = dunce::canonicalize(app_exe)?;
         log::debug!(target: "app", "Resolved app executable path: {:?}", app_exe);
@@ -270,13 +382,14 @@ impl Sysopt {
             loop {
                 sleep(Duration::from_secs(wait_secs)).await;
 
-                let (enable, guard, guard_interval, bypass) = {
+                let (enable, guard, guard_interval, mode, bypass) = {
                     let verge = Config::verge();
                     let verge = verge.latest();
                     (
                         verge.enable_system_proxy.unwrap_or(false),
                         verge.enable_proxy_guard.unwrap_or(false),
                         verge.proxy_guard_interval.unwrap_or(10),
+                        verge.system_proxy_mode.unwrap_or_default(),
                         verge.system_proxy_bypass.clone(),
                     )
                 };
@@ -291,21 +404,29 @@ impl Sysopt {
 
                 log::debug!(target: "app", "try to guard the system proxy");
 
-                let port = {
-                    Config::verge()
-                        .latest()
-                        .verge_mixed_port
-                        .unwrap_or(Config::clash().data().get_mixed_port())
-                };
+                // PAC 模式下守护的是 PAC 地址
+                if mode == SystemProxyMode::Pac {
+                    log_err!(Sysopt::global().update_pac());
+                    continue;
+                }
+
+                let port = proxy_port();
 
                 let sysproxy = Sysproxy {
                     enable: true,
                     host: "127.0.0.1".into(),
                     port,
-                    bypass: bypass.unwrap_or(DEFAULT_BYPASS.into()),
+                    bypass: BypassManager::join(&effective_bypass(bypass)),
                 };
 
-                log_err!(sysproxy.set_system_proxy());
+                match sysproxy.set_system_proxy() {
+                    Ok(()) => proxy_recovery::mark(ProxyMarker {
+                        host: sysproxy.host,
+                        port,
+                        pac_url: None,
+                    }),
+                    Err(e) => log::error!(target: "app", "{e:#?}"),
+                }
             }
 
             let mut state = guard_state.lock().await;
@@ -1,6 +1,7 @@
 mod events_rotate;
 mod logger;
 mod profiles;
+mod provider_refresh;
 
 use super::{
     task::{Task, TaskManager},
@@ -30,9 +31,12 @@ impl JobsManager {
     }
 
     pub fn setup(&mut self) -> anyhow::Result<()> {
-        let jobs: Vec<Box<dyn JobExt + Send + Sync>> = vec![Box::new(
-            events_rotate::EventsRotateJob::new(self.task_manager.read().get_inner_task_storage()),
-        )];
+        let jobs: Vec<Box<dyn JobExt + Send + Sync>> = vec![
+            Box::new(events_rotate::EventsRotateJob::new(
+                self.task_manager.read().get_inner_task_storage(),
+            )),
+            Box::new(provider_refresh::ProviderRefreshJob),
+        ];
         for job in jobs {
             let task = job.setup();
             if let Some(task) = task {
@@ -4,7 +4,8 @@ use super::super::{
 };
 use crate::{
     config::{Config, ProfileMetaGetter},
-    feat,
+    core::profile_diff,
+    feat, log_err,
 };
 use anyhow::Result;
 use async_trait::async_trait;
@@ -31,8 +32,15 @@ impl ProfileUpdater {
 impl AsyncJobExecutor for ProfileUpdater {
     async fn execute(&self) -> Result<()> {
         log::info!(target: "app", "running timer task `{}`", self.0);
+        let previous = profile_diff::snapshot(&self.0).await;
         match feat::update_profile(self.0.clone(), None).await {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                log_err!(
+                    profile_diff::record_update(&self.0, previous).await,
+                    "failed to record profile diff"
+                );
+                Ok(())
+            }
             Err(err) => {
                 log::error!(target: "app", "failed to update profile: {err:?}");
                 Err(err)
@@ -0,0 +1,38 @@
+use super::JobExt;
+use crate::core::{
+    clash::provider_refresh,
+    tasks::{
+        executor::{AsyncJobExecutor, TaskExecutor},
+        task::{Task, TaskID, TaskSchedule},
+    },
+};
+use std::time::Duration;
+
+const PROVIDER_REFRESH_TASK_NAME: &str = "Proxy Provider Refresh";
+const PROVIDER_REFRESH_TASK_ID: TaskID = 1;
+
+#[derive(Clone)]
+pub struct ProviderRefreshJob;
+
+#[async_trait::async_trait]
+impl AsyncJobExecutor for ProviderRefreshJob {
+    async fn execute(&self) -> anyhow::Result<()> {
+        provider_refresh::refresh_due_providers().await
+    }
+}
+
+impl JobExt for ProviderRefreshJob {
+    fn name(&self) -> &'static str {
+        PROVIDER_REFRESH_TASK_NAME
+    }
+
+    fn setup(&self) -> Option<Task> {
+        Some(Task {
+            id: PROVIDER_REFRESH_TASK_ID,
+            name: PROVIDER_REFRESH_TASK_NAME.to_string(),
+            schedule: TaskSchedule::Interval(Duration::from_secs(60)),
+            executor: TaskExecutor::Async(Box::new(self.clone())),
+            ..Default::default()
+        })
+    }
+}
@@ -3,7 +3,7 @@ use std::borrow::Cow;
 use crate::{
     config::{Config, nyanpasu::ClashCore},
     feat, ipc, log_err,
-    utils::{help, resolve},
+    utils::{config::ClashMode, help, resolve},
 };
 use anyhow::Result;
 use once_cell::sync::Lazy;
@@ -17,6 +17,7 @@ use tauri::{
 use tracing_attributes::instrument;
 
 pub mod proxies;
+pub mod rates;
 use self::proxies::SystemTrayMenuProxiesExt;
 
 #[cfg(target_os = "linux")]
@@ -168,6 +169,7 @@ impl Tray {
         menu = menu
             .separator()
             .check("tun_mode", t!("tray.tun_mode"))
+            .text("emergency_reset", t!("tray.emergency_reset"))
             .separator()
             .text("copy_env_sh", t!("tray.copy_env.sh"))
             .text("copy_env_cmd", t!("tray.copy_env.cmd"))
@@ -319,36 +321,41 @@ impl Tray {
         };
         let tray_id = get_tray_id();
         tracing::debug!("updating tray part: {}", tray_id);
+        #[cfg_attr(target_os = "macos", allow(unused_variables))]
         let tray = app_handle
             .tray_by_id(tray_id.as_ref())
             .expect("tray not found");
         let state = app_handle.state::<TrayState<R>>();
         let menu = state.menu.lock();
 
-        let _ = menu
-            .get("rule_mode")
-            .and_then(|item| item.as_check_menuitem()?.set_checked(mode == "rule").ok());
-        let _ = menu
-            .get("global_mode")
-            .and_then(|item| item.as_check_menuitem()?.set_checked(mode == "global").ok());
-        let _ = menu
-            .get("direct_mode")
-            .and_then(|item| item.as_check_menuitem()?.set_checked(mode == "direct").ok());
+        let _ = menu.get("rule_mode").and_then(|item| {
+            item.as_check_menuitem()?
+                .set_checked(mode == ClashMode::Rule)
+                .ok()
+        });
+        let _ = menu.get("global_mode").and_then(|item| {
+            item.as_check_menuitem()?
+                .set_checked(mode == ClashMode::Global)
+                .ok()
+        });
+        let _ = menu.get("direct_mode").and_then(|item| {
+            item.as_check_menuitem()?
+                .set_checked(mode == ClashMode::Direct)
+                .ok()
+        });
         if core == ClashCore::ClashPremium {
-            let _ = menu
-                .get("script_mode")
-                .and_then(|item| item.as_check_menuitem()?.set_checked(mode == "script").ok());
+            let _ = menu.get("script_mode").and_then(|item| {
+                item.as_check_menuitem()?
+                    .set_checked(mode == ClashMode::Script)
+                    .ok()
+            });
         }
 
-        #[allow(unused_variables)]
-        let (tun_mode, enable_tray_text) = {
-            let verge = Config::verge();
-            let verge = verge.latest();
-            (
-                *verge.enable_tun_mode.as_ref().unwrap_or(&false),
-                *verge.enable_tray_text.as_ref().unwrap_or(&false),
-            )
-        };
+        let tun_mode = *Config::verge()
+            .latest()
+            .enable_tun_mode
+            .as_ref()
+            .unwrap_or(&false);
 
         #[cfg(any(target_os = "windows", target_os = "linux"))]
         {
@@ -369,29 +376,41 @@ impl Tray {
             .get("tun_mode")
             .and_then(|item| item.as_check_menuitem()?.set_checked(tun_mode).ok());
 
-        let switch_map = {
-            let mut map = std::collections::HashMap::new();
-            map.insert(true, t!("tray.proxy_action.on"));
-            map.insert(false, t!("tray.proxy_action.off"));
-            map
+        Tray::update_tooltip(app_handle)
+    }
+
+    /// 更新提示文字（Linux 上为标题），包含 TUN 状态与实时速率
+    pub fn update_tooltip<R: Runtime>(app_handle: &AppHandle<R>) -> Result<()> {
+        let Some(tray) = app_handle.tray_by_id(get_tray_id().as_ref()) else {
+            return Ok(());
         };
+        #[allow(unused_variables)]
+        let (tun_mode, enable_tray_text) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                *verge.enable_tun_mode.as_ref().unwrap_or(&false),
+                *verge.enable_tray_text.as_ref().unwrap_or(&false),
+            )
+        };
+        let tun_text = format!(
+            "{}: {}",
+            t!("tray.tun_mode"),
+            if tun_mode {
+                t!("tray.proxy_action.on")
+            } else {
+                t!("tray.proxy_action.off")
+            }
+        );
 
         #[cfg(not(target_os = "linux"))]
         {
-            let _ = tray.set_tooltip(Some(&format!(
-                "{}: {}",
-                t!("tray.tun_mode"),
-                switch_map[&tun_mode]
-            )));
+            let _ = tray.set_tooltip(Some(&format!("{tun_text}\n{}", rates::rate_text())));
         }
         #[cfg(target_os = "linux")]
         {
             if enable_tray_text {
-                let _ = tray.set_title(Some(&format!(
-                    "{}: {}",
-                    t!("tray.tun_mode"),
-                    switch_map[&tun_mode]
-                )));
+                let _ = tray.set_title(Some(&format!("{tun_text} | {}", rates::rate_text())));
             } else {
                 let _ = tray.set_title::<&str>(None);
             }
@@ -406,11 +425,12 @@ impl Tray {
         match id {
             mode @ ("rule_mode" | "global_mode" | "direct_mode" | "script_mode") => {
                 let mode = &mode[0..mode.len() - 5];
-                feat::change_clash_mode(mode.into());
+                feat::change_clash_mode(ClashMode::from(mode));
             }
 
             "open_window" => resolve::create_window(app_handle),
             "tun_mode" => feat::toggle_tun_mode(),
+            "emergency_reset" => feat::emergency_reset(),
             "copy_env_sh" => feat::copy_clash_env(app_handle, "sh"),
             #[cfg(target_os = "windows")]
             "copy_env_cmd" => feat::copy_clash_env(app_handle, "cmd"),
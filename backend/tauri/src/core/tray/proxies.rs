@@ -1,6 +1,7 @@
 use crate::{
     config::{Config, nyanpasu::ProxiesSelectorMode},
     core::{
+        activity::ActivityManager,
         clash::proxies::{Proxies, ProxiesGuard, ProxiesGuardExt},
         handle::Handle,
     },
@@ -14,6 +15,12 @@ use tracing_attributes::instrument;
 #[instrument]
 async fn loop_task() {
     loop {
+        let Some(poll_interval) = ActivityManager::global().profile().proxies_poll_interval()
+        else {
+            // 暂停时不轮询，恢复后由 ActivityManager 主动刷新
+            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+            continue;
+        };
         match ProxiesGuard::global().update().await {
             Ok(_) => {
                 debug!("update proxies success");
@@ -33,7 +40,7 @@ async fn loop_task() {
             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
             continue;
         }
-        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await; // TODO: add a config to control the interval
+        tokio::time::sleep(poll_interval).await;
     }
 }
 
@@ -0,0 +1,93 @@
+//! 托盘中显示实时上下行速率，约每秒刷新一次
+//! 核心停止（connections ws 断开）后显示空闲状态，避免停留在过期的数值
+use super::Tray;
+use crate::{
+    core::clash::ws::{ClashConnectionsConnectorEvent, ClashConnectionsConnectorState},
+    log_err,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rust_i18n::t;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Runtime};
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+
+/// 略小于 ws 推送间隔，避免因抖动跳过一次更新
+const UPDATE_INTERVAL: Duration = Duration::from_millis(900);
+
+/// 当前速率文本，None 表示空闲
+static RATE_TEXT: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn rate_text() -> String {
+    RATE_TEXT
+        .lock()
+        .clone()
+        .unwrap_or_else(|| t!("tray.traffic_idle").to_string())
+}
+
+fn format_rate(bytes_per_sec: u64) -> String {
+    const UNITS: [&str; 4] = ["B/s", "KB/s", "MB/s", "GB/s"];
+    let mut value = bytes_per_sec as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes_per_sec} {}", UNITS[0])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn format_rates(upload: u64, download: u64) -> String {
+    format!("↑ {} ↓ {}", format_rate(upload), format_rate(download))
+}
+
+pub fn setup<R: Runtime>(
+    app_handle: AppHandle<R>,
+    mut rx: Receiver<ClashConnectionsConnectorEvent>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_update: Option<Instant> = None;
+        loop {
+            match rx.recv().await {
+                Ok(ClashConnectionsConnectorEvent::Update(info)) => {
+                    if last_update.is_some_and(|at| at.elapsed() < UPDATE_INTERVAL) {
+                        continue;
+                    }
+                    last_update = Some(Instant::now());
+                    *RATE_TEXT.lock() = Some(format_rates(info.upload_speed, info.download_speed));
+                }
+                Ok(ClashConnectionsConnectorEvent::StateChanged(
+                    ClashConnectionsConnectorState::Disconnected,
+                )) => {
+                    last_update = None;
+                    if RATE_TEXT.lock().take().is_none() {
+                        continue;
+                    }
+                }
+                Ok(ClashConnectionsConnectorEvent::StateChanged(_)) => continue,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+            let handle = app_handle.clone();
+            log_err!(app_handle.run_on_main_thread(move || {
+                log_err!(Tray::update_tooltip(&handle));
+            }));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_rates() {
+        assert_eq!(format_rate(0), "0 B/s");
+        assert_eq!(format_rate(1023), "1023 B/s");
+        assert_eq!(format_rate(1536), "1.5 KB/s");
+        assert_eq!(format_rates(1_258_291, 8_808_038), "↑ 1.2 MB/s ↓ 8.4 MB/s");
+    }
+}
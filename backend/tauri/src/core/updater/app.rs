@@ -0,0 +1,347 @@
+//! 检查应用自身的更新：读取 GitHub 上最新的 release，只报告结果，下载见 `app_download`
+use super::UpdaterManager;
+use crate::utils::{
+    candy::{get_reqwest_client, parse_gh_url},
+    dirs::APP_VERSION,
+};
+use parking_lot::Mutex;
+use reqwest::{StatusCode, header};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+const LATEST_RELEASE_API: &str =
+    "https://api.github.com/repos/Alfred1109/clashnyanpasu/releases/latest";
+const DEFAULT_MIRROR: &str = "https://github.com";
+
+/// 最近一次检查得到的更新信息，下载时只使用这里的地址与校验值
+static LAST_CHECKED: Mutex<Option<UpdateInfo>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct UpdateInfo {
+    pub current: String,
+    pub latest: String,
+    pub available: bool,
+    /// 当前平台安装包的下载地址，已按镜像改写；release 中没有匹配的安装包时为空
+    pub url: Option<String>,
+    pub asset_name: Option<String>,
+    /// GitHub 记录的安装包 sha256
+    pub sha256: Option<String>,
+    /// release 中附带的 `{asset}.sha256` 校验文件
+    pub checksum_url: Option<String>,
+    /// release 中附带的 `{asset}.sig` minisign 签名
+    pub signature_url: Option<String>,
+    /// release 页面
+    pub release_url: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error, Serialize, Type)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum UpdateCheckError {
+    /// GitHub API 的请求次数已用完，值为限额重置的 unix 时间戳
+    #[error("GitHub API rate limit exceeded")]
+    RateLimited(Option<i64>),
+    #[error("failed to reach the release feed: {0}")]
+    Network(String),
+    #[error("release feed responded with status {0}")]
+    Status(u16),
+    #[error("invalid release: {0}")]
+    InvalidRelease(String),
+}
+
+impl From<reqwest::Error> for UpdateCheckError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            UpdateCheckError::InvalidRelease(e.to_string())
+        } else {
+            UpdateCheckError::Network(e.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    body: Option<String>,
+    #[serde(default)]
+    assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+    /// 形如 `sha256:<hex>`
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+fn parse_version(version: &str) -> Result<Version, UpdateCheckError> {
+    Version::parse(version.trim().trim_start_matches('v'))
+        .map_err(|e| UpdateCheckError::InvalidRelease(format!("invalid version `{version}`: {e}")))
+}
+
+/// 各平台可用的安装包后缀，越靠前越优先
+fn platform_extensions(os: &str) -> &'static [&'static str] {
+    match os {
+        "windows" => &["-setup.exe", ".exe", ".msi"],
+        "macos" => &[".dmg"],
+        "linux" => &[".AppImage", ".deb", ".rpm"],
+        _ => &[],
+    }
+}
+
+/// 安装包名中表示架构的写法
+fn arch_aliases(arch: &str) -> &'static [&'static str] {
+    match arch {
+        "x86_64" => &["x64", "x86_64", "amd64"],
+        "aarch64" => &["aarch64", "arm64"],
+        "x86" => &["x86", "i686", "i386"],
+        _ => &[],
+    }
+}
+
+/// 选出与平台和架构匹配的安装包
+fn select_asset<'a>(assets: &'a [ReleaseAsset], os: &str, arch: &str) -> Option<&'a ReleaseAsset> {
+    let aliases = arch_aliases(arch);
+    platform_extensions(os).iter().find_map(|ext| {
+        assets.iter().find(|asset| {
+            asset.name.ends_with(ext)
+                && asset
+                    .name
+                    .split(['_', '-', '.'])
+                    .any(|part| aliases.contains(&part))
+        })
+    })
+}
+
+fn rate_limit_reset(headers: &header::HeaderMap) -> Option<i64> {
+    headers
+        .get("x-ratelimit-reset")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+fn is_rate_limited(status: StatusCode, headers: &header::HeaderMap) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && headers
+                .get("x-ratelimit-remaining")
+                .is_some_and(|v| v.as_bytes() == b"0"))
+}
+
+fn build_update_info(
+    current: &str,
+    release: Release,
+    mirror: &str,
+) -> Result<UpdateInfo, UpdateCheckError> {
+    let current_version = parse_version(current)?;
+    let latest_version = parse_version(&release.tag_name)?;
+    let mirrored = |url: &str| parse_gh_url(mirror, url).unwrap_or_else(|_| url.to_string());
+    let asset = select_asset(
+        &release.assets,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    );
+    let sibling_url = |suffix: &str| {
+        asset.and_then(|asset| {
+            let name = format!("{}{suffix}", asset.name);
+            release
+                .assets
+                .iter()
+                .find(|a| a.name == name)
+                .map(|a| mirrored(&a.browser_download_url))
+        })
+    };
+    let checksum_url = sibling_url(".sha256");
+    let signature_url = sibling_url(".sig");
+    Ok(UpdateInfo {
+        current: current_version.to_string(),
+        latest: latest_version.to_string(),
+        available: latest_version > current_version,
+        url: asset.map(|asset| mirrored(&asset.browser_download_url)),
+        asset_name: asset.map(|asset| asset.name.clone()),
+        sha256: asset
+            .and_then(|asset| asset.digest.as_deref())
+            .and_then(|digest| digest.strip_prefix("sha256:"))
+            .map(str::to_ascii_lowercase),
+        checksum_url,
+        signature_url,
+        release_url: release.html_url,
+        notes: release.body.filter(|notes| !notes.trim().is_empty()),
+    })
+}
+
+async fn fetch_latest_release() -> Result<Release, UpdateCheckError> {
+    let client = get_reqwest_client().map_err(|e| UpdateCheckError::Network(format!("{e:#}")))?;
+    let resp = client
+        .get(LATEST_RELEASE_API)
+        .header(header::USER_AGENT, format!("clash-nyanpasu/v{APP_VERSION}"))
+        .header(header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await?;
+    let status = resp.status();
+    if is_rate_limited(status, resp.headers()) {
+        return Err(UpdateCheckError::RateLimited(rate_limit_reset(
+            resp.headers(),
+        )));
+    }
+    if !status.is_success() {
+        return Err(UpdateCheckError::Status(status.as_u16()));
+    }
+    Ok(resp.json::<Release>().await?)
+}
+
+/// 有可下载的更新时才测速
+fn needs_download(current: &str, release: &Release) -> Result<bool, UpdateCheckError> {
+    Ok(parse_version(&release.tag_name)? > parse_version(current)?
+        && select_asset(
+            &release.assets,
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+        )
+        .is_some())
+}
+
+/// 查询最新的 release 并与当前版本比较，有更新时下载地址使用测速选出的镜像
+pub async fn check_for_update() -> Result<UpdateInfo, UpdateCheckError> {
+    let release = fetch_latest_release().await?;
+    let needs_download = needs_download(APP_VERSION, &release)?;
+    let mirror = {
+        let manager = UpdaterManager::global().read().await;
+        if needs_download && let Err(e) = manager.mirror_speed_test().await {
+            log::warn!(target: "app", "mirror speed test failed: {e:?}");
+        }
+        manager
+            .get_mirror()
+            .unwrap_or_else(|| DEFAULT_MIRROR.to_string())
+    };
+    let info = build_update_info(APP_VERSION, release, &mirror)?;
+    *LAST_CHECKED.lock() = Some(info.clone());
+    Ok(info)
+}
+
+/// 返回后端检查得到的 `version` 版本的更新信息，没有缓存时重新检查
+pub(super) async fn checked_update(version: &str) -> Result<UpdateInfo, UpdateCheckError> {
+    let cached = LAST_CHECKED.lock().clone();
+    let info = match cached {
+        Some(info) if info.latest == version => info,
+        _ => check_for_update().await?,
+    };
+    if info.latest != version {
+        return Err(UpdateCheckError::InvalidRelease(format!(
+            "version {version} is no longer the latest release ({})",
+            info.latest
+        )));
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(name: &str) -> ReleaseAsset {
+        ReleaseAsset {
+            name: name.to_string(),
+            browser_download_url: format!(
+                "https://github.com/Alfred1109/clashnyanpasu/releases/download/v3.1.0/{name}"
+            ),
+            digest: Some(format!("sha256:{}", "AB".repeat(32))),
+        }
+    }
+
+    fn assets() -> Vec<ReleaseAsset> {
+        [
+            "Clash.Nyanpasu_3.1.0_x64-setup.exe",
+            "Clash.Nyanpasu_3.1.0_x64-setup.exe.sig",
+            "Clash.Nyanpasu_3.1.0_arm64-setup.exe",
+            "Clash.Nyanpasu_3.1.0_x64.dmg",
+            "Clash.Nyanpasu_3.1.0_aarch64.dmg",
+            "Clash.Nyanpasu_3.1.0_amd64.deb",
+            "Clash.Nyanpasu_3.1.0_amd64.AppImage",
+            "Clash.Nyanpasu_3.1.0_arm64.deb",
+            "update.json",
+        ]
+        .into_iter()
+        .map(asset)
+        .collect()
+    }
+
+    #[test]
+    fn test_select_asset() {
+        let assets = assets();
+        let name = |os, arch| select_asset(&assets, os, arch).map(|a| a.name.as_str());
+        assert_eq!(
+            name("windows", "x86_64"),
+            Some("Clash.Nyanpasu_3.1.0_x64-setup.exe")
+        );
+        assert_eq!(
+            name("windows", "aarch64"),
+            Some("Clash.Nyanpasu_3.1.0_arm64-setup.exe")
+        );
+        assert_eq!(
+            name("macos", "aarch64"),
+            Some("Clash.Nyanpasu_3.1.0_aarch64.dmg")
+        );
+        assert_eq!(
+            name("linux", "x86_64"),
+            Some("Clash.Nyanpasu_3.1.0_amd64.AppImage")
+        );
+        assert_eq!(
+            name("linux", "aarch64"),
+            Some("Clash.Nyanpasu_3.1.0_arm64.deb")
+        );
+        assert_eq!(name("freebsd", "x86_64"), None);
+    }
+
+    #[test]
+    fn test_build_update_info() {
+        let release = || Release {
+            tag_name: "v3.1.0".to_string(),
+            html_url: "https://github.com/Alfred1109/clashnyanpasu/releases/tag/v3.1.0".to_string(),
+            body: Some("- fixes".to_string()),
+            assets: assets(),
+        };
+        let info = build_update_info("3.0.4", release(), "https://mirror.example").unwrap();
+        assert!(info.available);
+        assert_eq!(info.latest, "3.1.0");
+        assert_eq!(info.notes.as_deref(), Some("- fixes"));
+        if let Some(url) = info.url {
+            assert!(url.starts_with("https://mirror.example/"), "{url}");
+            assert_eq!(info.sha256, Some("ab".repeat(32)));
+        }
+        if cfg!(all(windows, target_arch = "x86_64")) {
+            assert!(info.signature_url.unwrap().ends_with("_x64-setup.exe.sig"));
+        }
+
+        let info = build_update_info("3.1.0", release(), DEFAULT_MIRROR).unwrap();
+        assert!(!info.available);
+        // 已是最新版本时不测速
+        assert!(!needs_download("3.1.0", &release()).unwrap());
+        assert_eq!(
+            needs_download("3.0.4", &release()).unwrap(),
+            select_asset(&assets(), std::env::consts::OS, std::env::consts::ARCH).is_some()
+        );
+        assert!(matches!(
+            build_update_info("unknown", release(), DEFAULT_MIRROR),
+            Err(UpdateCheckError::InvalidRelease(_))
+        ));
+    }
+
+    #[test]
+    fn test_rate_limited() {
+        let mut headers = header::HeaderMap::new();
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, &headers));
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1700000000".parse().unwrap());
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, &headers));
+        assert_eq!(rate_limit_reset(&headers), Some(1700000000));
+        assert!(is_rate_limited(
+            StatusCode::TOO_MANY_REQUESTS,
+            &header::HeaderMap::new()
+        ));
+    }
+}
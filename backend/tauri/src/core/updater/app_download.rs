@@ -0,0 +1,417 @@
+//! 由用户手动触发的安装包下载：断点续传到下载目录，校验 minisign 签名与 sha256，只返回本地路径，不会自动安装
+//! 下载地址与校验值只取自后端检查得到的 release，签名与 sha256 都没有时拒绝下载，避免把未经校验的安装包交给用户
+//! 发布时不生成更新包签名，.dmg 也从来没有签名，此时只校验 release 中发布的 sha256
+use super::app::{UpdateInfo, checked_update};
+use crate::{
+    core::handle::Handle,
+    utils::{candy::get_reqwest_client, dirs},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures::StreamExt;
+use minisign_verify::{PublicKey, Signature};
+use reqwest::{StatusCode, header};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use specta::Type;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+pub const DOWNLOAD_PROGRESS_EVENT: &str = "update-download-progress";
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, thiserror::Error, Serialize, Type)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum UpdateDownloadError {
+    #[error("no installer available for this platform")]
+    NoAsset,
+    /// release 中既没有安装包的签名也没有 sha256
+    #[error("the installer has no signature or checksum to verify against")]
+    Unverified,
+    #[error("checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("signature verification failed: {0}")]
+    InvalidSignature(String),
+    #[error("failed to check the release: {0}")]
+    Release(String),
+    #[error("download failed: {0}")]
+    Network(String),
+    #[error("failed to write the installer: {0}")]
+    Io(String),
+}
+
+impl From<reqwest::Error> for UpdateDownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        UpdateDownloadError::Network(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for UpdateDownloadError {
+    fn from(e: std::io::Error) -> Self {
+        UpdateDownloadError::Io(e.to_string())
+    }
+}
+
+impl From<super::app::UpdateCheckError> for UpdateDownloadError {
+    fn from(e: super::app::UpdateCheckError) -> Self {
+        UpdateDownloadError::Release(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    /// 服务器没有返回长度时为空
+    pub total: Option<u64>,
+}
+
+/// 取校验文件中的第一个 sha256，兼容 `sha256sum` 的 `<hex>  <name>` 格式
+fn parse_checksum(content: &str) -> Option<String> {
+    content
+        .split_whitespace()
+        .find(|token| token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_ascii_lowercase)
+}
+
+/// release 中的 sha256，没有签名时是唯一的校验
+async fn expected_checksum(
+    client: &reqwest::Client,
+    info: &UpdateInfo,
+) -> Result<Option<String>, UpdateDownloadError> {
+    if let Some(sha256) = info.sha256.as_deref().and_then(parse_checksum) {
+        return Ok(Some(sha256));
+    }
+    let Some(url) = &info.checksum_url else {
+        return Ok(None);
+    };
+    let resp = client.get(url).send().await?.error_for_status()?;
+    Ok(parse_checksum(&resp.text().await?))
+}
+
+/// `tauri.conf.json` 中更新插件的公钥，与 tauri 一样是 base64 编码后的 minisign 公钥文件
+fn updater_pubkey() -> Result<PublicKey, UpdateDownloadError> {
+    use tauri::Manager;
+    let app_handle = Handle::global().app_handle.lock().clone();
+    let encoded = app_handle
+        .as_ref()
+        .and_then(|app| {
+            app.config()
+                .plugins
+                .0
+                .get("updater")?
+                .get("pubkey")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .ok_or_else(|| UpdateDownloadError::InvalidSignature("no updater pubkey".into()))?;
+    decode_pubkey(&encoded)
+}
+
+fn decode_base64_text(encoded: &str) -> Result<String, UpdateDownloadError> {
+    let decoded = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| UpdateDownloadError::InvalidSignature(e.to_string()))?;
+    String::from_utf8(decoded).map_err(|e| UpdateDownloadError::InvalidSignature(e.to_string()))
+}
+
+fn decode_pubkey(encoded: &str) -> Result<PublicKey, UpdateDownloadError> {
+    PublicKey::decode(&decode_base64_text(encoded)?)
+        .map_err(|e| UpdateDownloadError::InvalidSignature(e.to_string()))
+}
+
+/// `.sig` 文件同样是 base64 编码后的 minisign 签名文件
+fn decode_signature(encoded: &str) -> Result<Signature, UpdateDownloadError> {
+    Signature::decode(&decode_base64_text(encoded)?)
+        .map_err(|e| UpdateDownloadError::InvalidSignature(e.to_string()))
+}
+
+/// release 中安装包的签名与更新插件的公钥，没有签名时返回 None
+async fn fetch_signature(
+    client: &reqwest::Client,
+    info: &UpdateInfo,
+) -> Result<Option<(PublicKey, Signature)>, UpdateDownloadError> {
+    let Some(url) = &info.signature_url else {
+        return Ok(None);
+    };
+    let pubkey = updater_pubkey()?;
+    let resp = client.get(url).send().await?.error_for_status()?;
+    Ok(Some((pubkey, decode_signature(&resp.text().await?)?)))
+}
+
+/// 签名与 sha256 至少要有一项
+fn ensure_verifiable<S>(
+    signature: &Option<S>,
+    checksum: &Option<String>,
+) -> Result<(), UpdateDownloadError> {
+    if signature.is_none() && checksum.is_none() {
+        return Err(UpdateDownloadError::Unverified);
+    }
+    Ok(())
+}
+
+async fn verify_signature(
+    path: &Path,
+    pubkey: &PublicKey,
+    signature: &Signature,
+) -> Result<(), UpdateDownloadError> {
+    let data = fs::read(path).await?;
+    pubkey
+        .verify(&data, signature, true)
+        .map_err(|e| UpdateDownloadError::InvalidSignature(e.to_string()))
+}
+
+async fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn download_dir() -> Result<PathBuf, UpdateDownloadError> {
+    match ::dirs::download_dir() {
+        Some(dir) => Ok(dir),
+        None => Ok(dirs::cache_dir()
+            .map_err(|e| UpdateDownloadError::Io(format!("{e:#}")))?
+            .join("updates")),
+    }
+}
+
+/// 下载到 `{dest}.part`，已有的部分通过 Range 续传，校验通过后才改名为 `dest`
+async fn download_verified(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    expected: Option<&str>,
+    on_progress: impl Fn(DownloadProgress),
+) -> Result<(), UpdateDownloadError> {
+    let part = dest.with_file_name(format!(
+        "{}.part",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let existing = match fs::metadata(&part).await {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    };
+
+    let mut req = client.get(url);
+    if existing > 0 {
+        req = req.header(header::RANGE, format!("bytes={existing}-"));
+    }
+    let resp = req.send().await?;
+    let status = resp.status();
+    let (mut file, mut downloaded) = match status {
+        StatusCode::PARTIAL_CONTENT => (
+            fs::OpenOptions::new().append(true).open(&part).await?,
+            existing,
+        ),
+        // 已有部分超出了文件长度，删除后重新下载
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            fs::remove_file(&part).await?;
+            return Box::pin(download_verified(client, url, dest, expected, on_progress)).await;
+        }
+        status if status.is_success() => (fs::File::create(&part).await?, 0),
+        status => {
+            return Err(UpdateDownloadError::Network(format!(
+                "server responded with status {status}"
+            )));
+        }
+    };
+    let total = resp.content_length().map(|len| len + downloaded);
+
+    let mut stream = resp.bytes_stream();
+    let mut last_emit = Instant::now();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            last_emit = Instant::now();
+            on_progress(DownloadProgress { downloaded, total });
+        }
+    }
+    file.flush().await?;
+    drop(file);
+    on_progress(DownloadProgress { downloaded, total });
+
+    if let Some(expected) = expected {
+        let actual = sha256_file(&part).await?;
+        if actual != expected {
+            let _ = fs::remove_file(&part).await;
+            return Err(UpdateDownloadError::ChecksumMismatch {
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+    }
+    fs::rename(&part, dest).await?;
+    Ok(())
+}
+
+/// 下载 `check_for_update` 找到的 `version` 版本安装包，进度通过 `update-download-progress` 事件推送
+/// 返回校验通过的本地路径，由用户自行运行
+pub async fn download_update(version: String) -> Result<PathBuf, UpdateDownloadError> {
+    let info = checked_update(&version).await?;
+    let (Some(url), Some(name)) = (&info.url, &info.asset_name) else {
+        return Err(UpdateDownloadError::NoAsset);
+    };
+    let client =
+        get_reqwest_client().map_err(|e| UpdateDownloadError::Network(format!("{e:#}")))?;
+    let signature = fetch_signature(&client, &info).await?;
+    let expected = expected_checksum(&client, &info).await?;
+    ensure_verifiable(&signature, &expected)?;
+
+    let dir = download_dir()?;
+    fs::create_dir_all(&dir).await?;
+    // 安装包名来自 release，只取文件名部分
+    let file_name = Path::new(name)
+        .file_name()
+        .ok_or(UpdateDownloadError::NoAsset)?;
+    let dest = dir.join(file_name);
+    if fs::try_exists(&dest).await?
+        && match &expected {
+            Some(expected) => sha256_file(&dest).await? == *expected,
+            None => true,
+        }
+        && match &signature {
+            Some((pubkey, signature)) => verify_signature(&dest, pubkey, signature).await.is_ok(),
+            None => true,
+        }
+    {
+        return Ok(dest);
+    }
+
+    download_verified(&client, url, &dest, expected.as_deref(), |progress| {
+        if let Err(e) = Handle::emit(DOWNLOAD_PROGRESS_EVENT, progress) {
+            log::debug!(target: "app", "failed to emit update download progress: {e:?}");
+        }
+    })
+    .await?;
+    if let Some((pubkey, signature)) = &signature
+        && let Err(e) = verify_signature(&dest, pubkey, signature).await
+    {
+        let _ = fs::remove_file(&dest).await;
+        return Err(e);
+    }
+    log::info!(target: "app", "update {} downloaded to {}", info.latest, dest.display());
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::HeaderMap, response::IntoResponse, routing::get};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    const PAYLOAD: &[u8] = b"clash-nyanpasu installer payload";
+
+    /// 支持 `bytes=N-` 的简易文件服务器
+    async fn serve(ranges: Arc<AtomicUsize>) -> String {
+        let app = Router::new().route(
+            "/installer",
+            get(move |headers: HeaderMap| {
+                let ranges = ranges.clone();
+                async move {
+                    let start = headers
+                        .get(header::RANGE)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.strip_prefix("bytes="))
+                        .and_then(|v| v.trim_end_matches('-').parse::<usize>().ok());
+                    match start {
+                        Some(start) => {
+                            ranges.fetch_add(1, Ordering::SeqCst);
+                            (StatusCode::PARTIAL_CONTENT, PAYLOAD[start..].to_vec()).into_response()
+                        }
+                        None => PAYLOAD.to_vec().into_response(),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}/installer")
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let hash = "AB".repeat(32);
+        assert_eq!(parse_checksum(&hash), Some("ab".repeat(32)));
+        assert_eq!(
+            parse_checksum(&format!("{hash}  Clash.Nyanpasu_3.1.0_x64-setup.exe\n")),
+            Some("ab".repeat(32))
+        );
+        assert_eq!(parse_checksum("not a checksum"), None);
+        assert_eq!(parse_checksum(&"zz".repeat(32)), None);
+    }
+
+    #[test]
+    fn test_checksum_is_enough_without_signature() {
+        let checksum = Some("ab".repeat(32));
+        assert!(ensure_verifiable(&None::<()>, &checksum).is_ok());
+        assert!(ensure_verifiable(&Some(()), &None).is_ok());
+        assert!(matches!(
+            ensure_verifiable(&None::<()>, &None),
+            Err(UpdateDownloadError::Unverified)
+        ));
+    }
+
+    #[test]
+    fn test_decode_updater_keys() {
+        let conf: serde_json::Value =
+            serde_json::from_str(include_str!("../../../tauri.conf.json")).unwrap();
+        let pubkey = conf["plugins"]["updater"]["pubkey"].as_str().unwrap();
+        assert!(decode_pubkey(pubkey).is_ok());
+        assert!(decode_pubkey("not base64").is_err());
+        assert!(matches!(
+            decode_signature(&STANDARD.encode("untrusted comment: x\nnot a signature\n")),
+            Err(UpdateDownloadError::InvalidSignature(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resume_and_verify() {
+        let ranges = Arc::new(AtomicUsize::new(0));
+        let url = serve(ranges.clone()).await;
+        let client = reqwest::Client::new();
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("installer.exe");
+        let expected = hex::encode(Sha256::digest(PAYLOAD));
+
+        // 已经下载了一部分
+        std::fs::write(dir.path().join("installer.exe.part"), &PAYLOAD[..10]).unwrap();
+        let last = parking_lot::Mutex::new(None);
+        download_verified(&client, &url, &dest, Some(&expected), |p| {
+            *last.lock() = Some(p.downloaded)
+        })
+        .await
+        .unwrap();
+        assert_eq!(ranges.load(Ordering::SeqCst), 1);
+        assert_eq!(*last.lock(), Some(PAYLOAD.len() as u64));
+        assert_eq!(std::fs::read(&dest).unwrap(), PAYLOAD);
+        assert!(!dir.path().join("installer.exe.part").exists());
+
+        // 校验失败时不保留文件
+        let other = dir.path().join("other.exe");
+        let err = download_verified(&client, &url, &other, Some(&"00".repeat(32)), |_| {})
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UpdateDownloadError::ChecksumMismatch { .. }));
+        assert!(!other.exists());
+        assert!(!dir.path().join("other.exe.part").exists());
+    }
+}
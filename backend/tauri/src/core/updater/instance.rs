@@ -1,7 +1,7 @@
 use super::shared::{self, CoreTypeMeta};
 use crate::{
     config::nyanpasu::ClashCore,
-    core::CoreManager,
+    core::{CoreManager, clash::core_info::CoreVersionInfo},
     utils::downloader::{DownloadStatus, Downloader, DownloaderBuilder, DownloaderState},
 };
 use anyhow::anyhow;
@@ -158,45 +158,11 @@ impl Updater {
         self.dispatch_state(UpdaterState::Decompressing);
         let path = self.temp_dir.path().join(&self.artifact);
         tracing::debug!("decompressing file: {:?}", path);
-        let mut tmp_file = std::fs::File::open(path)?;
+        let tmp_file = std::fs::File::open(path)?;
         tracing::debug!("file size: {}", tmp_file.metadata()?.len());
         let artifact = self.artifact.clone();
-        let buff = tokio::task::spawn_blocking(move || {
-            let mut buff = Vec::<u8>::new();
-            match artifact {
-                fname if fname.ends_with(".gz") => {
-                    tracing::debug!("decompressing gz file");
-                    let mut decoder = flate2::read::GzDecoder::new(&mut tmp_file);
-                    std::io::copy(&mut decoder, &mut buff)?;
-                }
-                fname if fname.ends_with(".zip") => {
-                    tracing::debug!("decompressing zip file");
-                    let mut archive = zip::ZipArchive::new(tmp_file)?;
-                    let len = archive.len();
-                    for i in 0..len {
-                        let mut file = archive.by_index(i)?;
-                        let file_name = file.name();
-                        tracing::debug!("Filename: {}", file.name());
-                        // TODO: 在 enum 做点魔法
-                        if file_name.contains("mihomo") || file_name.contains("clash") {
-                            tracing::debug!("extract file: {}", file_name);
-                            tracing::debug!("extract file size: {}", file.size());
-                            std::io::copy(&mut file, &mut buff)?;
-                            break;
-                        }
-                        if i == len - 1 {
-                            anyhow::bail!("failed to find core file in a zip archive");
-                        }
-                    }
-                }
-                _ => {
-                    tracing::debug!("directly copying file");
-                    std::io::copy(&mut tmp_file, &mut buff)?;
-                }
-            };
-            Ok::<_, anyhow::Error>(buff)
-        })
-        .await??;
+        let buff = tokio::task::spawn_blocking(move || shared::extract_core(&artifact, tmp_file))
+            .await??;
         let tmp_core = self.temp_dir.path().join(format!(
             "{}{}",
             self.core_type,
@@ -214,10 +180,7 @@ impl Updater {
 
     async fn replace_core(&self) -> anyhow::Result<()> {
         self.dispatch_state(UpdaterState::Replacing);
-        let current_core = crate::config::Config::verge()
-            .latest()
-            .clash_core
-            .unwrap_or_default();
+        let current_core = crate::config::Config::clash_core();
         tracing::debug!("current core: {}", current_core);
         if current_core == self.core_type {
             tracing::debug!("stopping core to replace");
@@ -279,10 +242,18 @@ impl Updater {
             }
         };
 
+        match CoreVersionInfo::probe(self.core_type).await {
+            Ok(info) => tracing::info!("core {} updated to {}", self.core_type, info.version),
+            Err(e) => tracing::warn!("failed to read version of the updated core: {e:?}"),
+        }
+
         if current_core == self.core_type {
             self.dispatch_state(UpdaterState::Restarting);
             CoreManager::global().run_core().await?;
         }
+        tokio::spawn(crate::core::service::control::verify_core_if_enabled(
+            self.core_type,
+        ));
 
         Ok(())
     }
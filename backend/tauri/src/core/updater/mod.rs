@@ -10,10 +10,13 @@ use crate::{
 use anyhow::{Result, anyhow};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use shared::{CoreTypeMeta, get_arch};
+use shared::CoreTypeMeta;
+pub(crate) use shared::{extract_core, get_arch};
 use specta::Type;
 use tokio::{join, sync::RwLock};
 
+pub mod app;
+pub mod app_download;
 mod instance;
 mod shared;
 
@@ -117,6 +120,62 @@ impl ManifestVersion {
             )),
         }
     }
+
+    /// 指定版本在当前平台的附件名称
+    fn artifact_name(&self, core_type: &ClashCore, version: &str) -> Option<String> {
+        let arch = get_arch().ok()?;
+        let template = match core_type {
+            ClashCore::ClashPremium => &self.arch_template.clash_premium,
+            ClashCore::Mihomo => &self.arch_template.mihomo,
+            ClashCore::MihomoAlpha => &self.arch_template.mihomo_alpha,
+        };
+        Some(template.get(arch)?.replace("{}", version))
+    }
+}
+
+/// 核心自身报告的版本转为 release 使用的版本号，mihomo 的 tag 带 `v` 前缀
+fn release_version(core: &ClashCore, version: &str) -> String {
+    match core {
+        ClashCore::Mihomo if !version.starts_with('v') => format!("v{version}"),
+        _ => version.to_string(),
+    }
+}
+
+/// 核心官方 release 中附件的下载地址，`version` 为核心自身报告的版本
+pub async fn release_asset_url(core: &ClashCore, version: &str, asset: &str) -> Result<String> {
+    let version = release_version(core, version);
+    let tag = match core {
+        ClashCore::ClashPremium => CoreTypeMeta::ClashPremium(version),
+        ClashCore::Mihomo => CoreTypeMeta::Mihomo(version),
+        ClashCore::MihomoAlpha => CoreTypeMeta::MihomoAlpha,
+    };
+    let mut url = url::Url::parse("https://github.com")?;
+    url.set_path(&shared::get_download_path(tag, asset));
+    let mirror = UpdaterManager::global()
+        .read()
+        .await
+        .get_mirror()
+        .unwrap_or_else(|| "https://github.com".to_string());
+    parse_gh_url(&mirror, url.as_str())
+}
+
+/// 核心官方 release 中当前平台的附件名称，版本清单尚未加载时先拉取
+pub async fn release_artifact_name(core: &ClashCore, version: &str) -> Result<String> {
+    let version = release_version(core, version);
+    if let Some(name) = UpdaterManager::global()
+        .read()
+        .await
+        .manifest_version
+        .artifact_name(core, &version)
+    {
+        return Ok(name);
+    }
+    let mut manager = UpdaterManager::global().write().await;
+    manager.fetch_latest().await?;
+    manager
+        .manifest_version
+        .artifact_name(core, &version)
+        .ok_or_else(|| anyhow!("no release artifact of {core} for this platform"))
 }
 
 impl UpdaterManager {
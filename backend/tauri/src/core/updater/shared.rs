@@ -1,4 +1,4 @@
-pub(super) fn get_arch() -> anyhow::Result<&'static str> {
+pub(crate) fn get_arch() -> anyhow::Result<&'static str> {
     let env = {
         let arch = std::env::consts::ARCH;
         let os = std::env::consts::OS;
@@ -24,6 +24,46 @@ pub(super) fn get_arch() -> anyhow::Result<&'static str> {
     }
 }
 
+/// 从下载的附件中取出核心文件，附件可能是 gz、zip 或未压缩的文件
+pub(crate) fn extract_core<R: std::io::Read + std::io::Seek>(
+    artifact: &str,
+    mut reader: R,
+) -> anyhow::Result<Vec<u8>> {
+    let mut buff = Vec::<u8>::new();
+    match artifact {
+        fname if fname.ends_with(".gz") => {
+            tracing::debug!("decompressing gz file");
+            let mut decoder = flate2::read::GzDecoder::new(&mut reader);
+            std::io::copy(&mut decoder, &mut buff)?;
+        }
+        fname if fname.ends_with(".zip") => {
+            tracing::debug!("decompressing zip file");
+            let mut archive = zip::ZipArchive::new(reader)?;
+            let len = archive.len();
+            for i in 0..len {
+                let mut file = archive.by_index(i)?;
+                let file_name = file.name();
+                tracing::debug!("Filename: {}", file.name());
+                // TODO: 在 enum 做点魔法
+                if file_name.contains("mihomo") || file_name.contains("clash") {
+                    tracing::debug!("extract file: {}", file_name);
+                    tracing::debug!("extract file size: {}", file.size());
+                    std::io::copy(&mut file, &mut buff)?;
+                    break;
+                }
+                if i == len - 1 {
+                    anyhow::bail!("failed to find core file in a zip archive");
+                }
+            }
+        }
+        _ => {
+            tracing::debug!("directly copying file");
+            std::io::copy(&mut reader, &mut buff)?;
+        }
+    };
+    Ok(buff)
+}
+
 #[allow(dead_code)]
 pub(super) enum CoreTypeMeta {
     ClashPremium(String),
@@ -27,6 +27,12 @@ pub struct PostProcessingOutput {
     pub global: IndexMap<ProfileUid, Logs>,
     /// 根据配置进行的分析建议
     pub advice: Logs,
+    /// 最终配置中节点定义的检查结果
+    #[serde(default)]
+    pub proxy_validation: Vec<super::ProxyValidationError>,
+    /// 转换为 clash-rs 配置时移除或改写的字段
+    #[serde(default)]
+    pub compatibility_warnings: Vec<super::TranslationWarning>,
     // TODO: 增加 Meta 信息
 }
 
@@ -0,0 +1,356 @@
+//! 内置的订阅转换，将节点分享链接列表转换为完整的 clash 配置
+//! 替代外部 subconverter，避免把订阅地址交给第三方
+mod outbound;
+mod shadowsocks;
+mod template;
+mod trojan;
+mod vless;
+mod vmess;
+
+pub use outbound::*;
+pub use template::{ConversionTemplate, builtin_template_names};
+
+use anyhow::{Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::collections::HashSet;
+use url::Url;
+
+#[derive(Debug)]
+pub struct Conversion {
+    pub config: Mapping,
+    /// 被跳过的行及原因
+    pub warnings: Vec<String>,
+}
+
+/// 转换原始订阅内容，无法识别的行会被跳过并记录警告
+pub fn convert_subscription(raw: &str, template: &ConversionTemplate) -> Result<Conversion> {
+    let (outbounds, warnings) = parse_node_list(raw);
+    if outbounds.is_empty() {
+        bail!("no supported nodes found in the subscription");
+    }
+    let proxies = outbounds
+        .iter()
+        .map(|outbound| Value::Mapping(outbound.to_mapping()))
+        .collect::<Vec<_>>();
+    let names = outbounds
+        .iter()
+        .map(|outbound| outbound.name().to_string())
+        .collect::<Vec<_>>();
+    let config = template.render(proxies, &names)?;
+    Ok(Conversion { config, warnings })
+}
+
+/// 解析节点列表，支持逐行的分享链接或整体 base64 编码
+pub fn parse_node_list(raw: &str) -> (Vec<Outbound>, Vec<String>) {
+    let raw = raw.trim().trim_start_matches('\u{feff}');
+    let content = if raw.contains("://") {
+        raw.to_string()
+    } else {
+        let compact = raw.split_whitespace().collect::<String>();
+        decode_base64(&compact).unwrap_or_else(|| raw.to_string())
+    };
+
+    let mut outbounds = Vec::new();
+    let mut warnings = Vec::new();
+    let mut names = HashSet::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse_share_link(line) {
+            Ok(mut outbound) => {
+                let name = unique_name(&mut names, outbound.name());
+                outbound.set_name(name);
+                outbounds.push(outbound);
+            }
+            Err(reason) => warnings.push(format!("line {}: {reason}", index + 1)),
+        }
+    }
+    (outbounds, warnings)
+}
+
+pub fn parse_share_link(link: &str) -> Result<Outbound, String> {
+    let scheme = link
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_ascii_lowercase())
+        .ok_or_else(|| "not a share link".to_string())?;
+    match scheme.as_str() {
+        "ss" => shadowsocks::parse(link).map(Outbound::Shadowsocks),
+        "vmess" => vmess::parse(link).map(Outbound::Vmess),
+        "trojan" => trojan::parse(link).map(Outbound::Trojan),
+        "vless" => vless::parse(link).map(Outbound::Vless),
+        other => Err(format!("unsupported protocol `{other}`")),
+    }
+}
+
+/// 单个分享链接的解析结果
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProxyNode {
+    pub name: String,
+    /// clash 的节点类型，如 `ss`、`vmess`
+    pub protocol: String,
+    pub server: String,
+    pub port: u16,
+    /// 写入配置的 clash proxy
+    pub proxy: serde_json::Value,
+}
+
+/// 错误信息带上协议以便定位
+fn parse_share_link_with_scheme(link: &str) -> Result<Outbound> {
+    parse_share_link(link).map_err(|reason| match link.split_once("://") {
+        Some((scheme, _)) => anyhow!("{}: {reason}", scheme.to_ascii_lowercase()),
+        None => anyhow!("{reason}"),
+    })
+}
+
+/// 解析单个分享链接
+pub fn parse_proxy_link(link: &str) -> Result<ProxyNode> {
+    let outbound = parse_share_link_with_scheme(link.trim())?;
+    let proxy = outbound.to_mapping();
+    let get_str = |key: &str| {
+        proxy
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    Ok(ProxyNode {
+        name: outbound.name().to_string(),
+        protocol: get_str("type"),
+        server: get_str("server"),
+        port: proxy
+            .get("port")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default() as u16,
+        proxy: serde_json::to_value(&proxy)?,
+    })
+}
+
+/// 批量导入时单个链接的结果
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProxyLinkReport {
+    pub link: String,
+    /// 去重后写入配置的节点名称，解析失败时为空
+    pub name: Option<String>,
+    pub error: Option<String>,
+}
+
+/// 逐个解析分享链接，重名的节点追加序号
+pub fn parse_proxy_links(links: &[String]) -> (Vec<Outbound>, Vec<ProxyLinkReport>) {
+    let mut names = HashSet::new();
+    let mut outbounds = Vec::new();
+    let reports = links
+        .iter()
+        .map(|link| match parse_share_link_with_scheme(link.trim()) {
+            Ok(mut outbound) => {
+                let name = unique_name(&mut names, outbound.name());
+                outbound.set_name(name.clone());
+                outbounds.push(outbound);
+                ProxyLinkReport {
+                    link: link.clone(),
+                    name: Some(name),
+                    error: None,
+                }
+            }
+            Err(e) => ProxyLinkReport {
+                link: link.clone(),
+                name: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+    (outbounds, reports)
+}
+
+/// 只包含 `proxies` 的配置
+pub fn proxies_config(outbounds: &[Outbound]) -> Mapping {
+    let proxies = outbounds
+        .iter()
+        .map(|outbound| Value::Mapping(outbound.to_mapping()))
+        .collect::<Vec<_>>();
+    let mut config = Mapping::new();
+    config.insert("proxies".into(), proxies.into());
+    config
+}
+
+/// 节点名称重复时追加序号
+fn unique_name(names: &mut HashSet<String>, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut index = 2;
+    while !names.insert(candidate.clone()) {
+        candidate = format!("{name} {index}");
+        index += 1;
+    }
+    candidate
+}
+
+/// 兼容标准与 URL safe 字符集，以及是否带填充
+fn decode_base64(input: &str) -> Option<String> {
+    let input = input.trim().trim_end_matches('=');
+    [
+        general_purpose::STANDARD_NO_PAD,
+        general_purpose::URL_SAFE_NO_PAD,
+    ]
+    .iter()
+    .find_map(|engine| engine.decode(input).ok())
+    .and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+fn percent_decode(input: &str) -> String {
+    percent_encoding::percent_decode_str(input)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// 解析 `scheme://userinfo@host:port?query#name` 形式的链接
+struct ShareLink {
+    userinfo: String,
+    password: Option<String>,
+    server: String,
+    port: u16,
+    query: Vec<(String, String)>,
+    name: Option<String>,
+}
+
+impl ShareLink {
+    fn parse(link: &str) -> Result<Self, String> {
+        let url = Url::parse(link).map_err(|e| format!("invalid link: {e}"))?;
+        let server = url
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or("missing server")?
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_string();
+        let port = url.port().ok_or("missing port")?;
+        Ok(Self {
+            userinfo: percent_decode(url.username()),
+            password: url.password().map(percent_decode),
+            server,
+            port,
+            query: url.query_pairs().into_owned().collect(),
+            name: url
+                .fragment()
+                .map(percent_decode)
+                .filter(|name| !name.is_empty()),
+        })
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .filter(|v| !v.is_empty())
+    }
+
+    fn get_bool(&self, key: &str) -> bool {
+        self.get(key)
+            .is_some_and(|v| matches!(v.as_str(), "1" | "true"))
+    }
+
+    fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}:{}", self.server, self.port))
+    }
+
+    fn alpn(&self) -> Vec<String> {
+        self.get("alpn")
+            .map(|alpn| alpn.split(',').map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_base64_node_list_with_unknown_protocols() {
+        let lines = [
+            "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ=@example.com:8388#HK%2001",
+            "hysteria2://secret@example.com:443#Unknown",
+            "trojan://password@example.com:443#HK%2001",
+            "not a link",
+        ]
+        .join("\n");
+        let blob = general_purpose::STANDARD.encode(lines);
+        let (outbounds, warnings) = parse_node_list(&blob);
+        assert_eq!(outbounds.len(), 2);
+        assert_eq!(outbounds[0].name(), "HK 01");
+        assert_eq!(outbounds[1].name(), "HK 01 2");
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("line 2: unsupported protocol `hysteria2`"));
+    }
+
+    #[test]
+    fn test_parse_proxy_link() {
+        let node = parse_proxy_link(" ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ=@example.com:8388#HK%2001 ")
+            .unwrap();
+        assert_eq!(
+            (
+                node.name.as_str(),
+                node.protocol.as_str(),
+                node.server.as_str(),
+                node.port
+            ),
+            ("HK 01", "ss", "example.com", 8388)
+        );
+        assert_eq!(node.proxy["cipher"], "aes-256-gcm");
+
+        let err = parse_proxy_link("vmess://not-base64!").unwrap_err();
+        assert_eq!(err.to_string(), "vmess: invalid base64 content");
+        let vmess = general_purpose::STANDARD.encode(r#"{"add":"example.com","port":443}"#);
+        let err = parse_proxy_link(&format!("vmess://{vmess}")).unwrap_err();
+        assert_eq!(err.to_string(), "vmess: missing uuid");
+        let err = parse_proxy_link("hysteria2://secret@example.com:443").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "hysteria2: unsupported protocol `hysteria2`"
+        );
+    }
+
+    #[test]
+    fn test_parse_proxy_links() {
+        let links = [
+            "trojan://password@example.com:443#JP",
+            "vmess://not-base64!",
+            "trojan://password@example.org:443#JP",
+        ]
+        .map(String::from);
+        let (outbounds, reports) = parse_proxy_links(&links);
+        assert_eq!(outbounds.len(), 2);
+        assert_eq!(reports[0].name.as_deref(), Some("JP"));
+        assert_eq!(
+            reports[1].error.as_deref(),
+            Some("vmess: invalid base64 content")
+        );
+        assert_eq!(reports[2].name.as_deref(), Some("JP 2"));
+
+        let config = proxies_config(&outbounds);
+        let proxies = config["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies[1]["name"].as_str(), Some("JP 2"));
+    }
+
+    #[test]
+    fn test_convert_with_builtin_template() {
+        let raw = "trojan://password@example.com:443?sni=example.com#JP";
+        let conversion = convert_subscription(raw, &ConversionTemplate::default()).unwrap();
+        let config = conversion.config;
+        assert_eq!(config["proxies"].as_sequence().unwrap().len(), 1);
+        let groups = config["proxy-groups"].as_sequence().unwrap();
+        assert!(groups.iter().all(|group| {
+            group["proxies"]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .all(|name| name.as_str() != Some(template::NODES_PLACEHOLDER))
+        }));
+        assert!(config["rules"].as_sequence().is_some_and(|r| !r.is_empty()));
+        assert!(convert_subscription("unknown", &ConversionTemplate::default()).is_err());
+    }
+}
@@ -0,0 +1,321 @@
+//! 解析后的节点结构，统一转换为 clash 的 proxy 配置
+use serde_yaml::{Mapping, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outbound {
+    Shadowsocks(ShadowsocksOutbound),
+    Vmess(VmessOutbound),
+    Trojan(TrojanOutbound),
+    Vless(VlessOutbound),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowsocksOutbound {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    pub cipher: String,
+    pub password: String,
+    pub plugin: Option<ShadowsocksPlugin>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShadowsocksPlugin {
+    Obfs {
+        mode: String,
+        host: Option<String>,
+    },
+    V2ray {
+        mode: String,
+        host: Option<String>,
+        path: Option<String>,
+        tls: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmessOutbound {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    pub uuid: String,
+    pub alter_id: u32,
+    pub cipher: String,
+    pub tls: Option<Tls>,
+    pub transport: Transport,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrojanOutbound {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    pub password: String,
+    pub tls: Tls,
+    pub transport: Transport,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VlessOutbound {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    pub uuid: String,
+    pub flow: Option<String>,
+    pub tls: Option<Tls>,
+    pub transport: Transport,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tls {
+    pub sni: Option<String>,
+    pub skip_cert_verify: bool,
+    pub alpn: Vec<String>,
+    pub fingerprint: Option<String>,
+    pub reality: Option<Reality>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reality {
+    pub public_key: String,
+    pub short_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Transport {
+    #[default]
+    Tcp,
+    Http {
+        host: Option<String>,
+        path: Option<String>,
+    },
+    Ws {
+        host: Option<String>,
+        path: Option<String>,
+    },
+    H2 {
+        host: Option<String>,
+        path: Option<String>,
+    },
+    Grpc {
+        service_name: Option<String>,
+    },
+}
+
+impl Transport {
+    /// 按 v2ray 分享链接中的 `type`/`net` 字段构造
+    pub fn from_share_link(
+        network: Option<&str>,
+        host: Option<String>,
+        path: Option<String>,
+        service_name: Option<String>,
+    ) -> Result<Self, String> {
+        Ok(match network.unwrap_or("tcp") {
+            "" | "tcp" | "raw" => Transport::Tcp,
+            "http" => Transport::Http { host, path },
+            "ws" | "websocket" => Transport::Ws { host, path },
+            "h2" => Transport::H2 { host, path },
+            "grpc" => Transport::Grpc {
+                service_name: service_name.or(path),
+            },
+            other => return Err(format!("unsupported transport `{other}`")),
+        })
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty())
+}
+
+macro_rules! insert {
+    ($map: expr, $key: expr, $val: expr) => {
+        $map.insert(Value::from($key), Value::from($val));
+    };
+}
+
+impl Outbound {
+    pub fn name(&self) -> &str {
+        match self {
+            Outbound::Shadowsocks(o) => &o.name,
+            Outbound::Vmess(o) => &o.name,
+            Outbound::Trojan(o) => &o.name,
+            Outbound::Vless(o) => &o.name,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        match self {
+            Outbound::Shadowsocks(o) => o.name = name,
+            Outbound::Vmess(o) => o.name = name,
+            Outbound::Trojan(o) => o.name = name,
+            Outbound::Vless(o) => o.name = name,
+        }
+    }
+
+    pub fn to_mapping(&self) -> Mapping {
+        let mut map = Mapping::new();
+        match self {
+            Outbound::Shadowsocks(o) => {
+                insert!(map, "name", o.name.as_str());
+                insert!(map, "type", "ss");
+                insert!(map, "server", o.server.as_str());
+                insert!(map, "port", o.port);
+                insert!(map, "cipher", o.cipher.as_str());
+                insert!(map, "password", o.password.as_str());
+                insert!(map, "udp", true);
+                if let Some(plugin) = &o.plugin {
+                    write_ss_plugin(&mut map, plugin);
+                }
+            }
+            Outbound::Vmess(o) => {
+                insert!(map, "name", o.name.as_str());
+                insert!(map, "type", "vmess");
+                insert!(map, "server", o.server.as_str());
+                insert!(map, "port", o.port);
+                insert!(map, "uuid", o.uuid.as_str());
+                insert!(map, "alterId", o.alter_id);
+                insert!(map, "cipher", o.cipher.as_str());
+                insert!(map, "udp", true);
+                if let Some(tls) = &o.tls {
+                    insert!(map, "tls", true);
+                    write_tls(&mut map, tls, "servername");
+                }
+                write_transport(&mut map, &o.transport);
+            }
+            Outbound::Trojan(o) => {
+                insert!(map, "name", o.name.as_str());
+                insert!(map, "type", "trojan");
+                insert!(map, "server", o.server.as_str());
+                insert!(map, "port", o.port);
+                insert!(map, "password", o.password.as_str());
+                insert!(map, "udp", true);
+                write_tls(&mut map, &o.tls, "sni");
+                write_transport(&mut map, &o.transport);
+            }
+            Outbound::Vless(o) => {
+                insert!(map, "name", o.name.as_str());
+                insert!(map, "type", "vless");
+                insert!(map, "server", o.server.as_str());
+                insert!(map, "port", o.port);
+                insert!(map, "uuid", o.uuid.as_str());
+                insert!(map, "udp", true);
+                if let Some(flow) = &o.flow {
+                    insert!(map, "flow", flow.as_str());
+                }
+                if let Some(tls) = &o.tls {
+                    insert!(map, "tls", true);
+                    write_tls(&mut map, tls, "servername");
+                }
+                write_transport(&mut map, &o.transport);
+            }
+        }
+        map
+    }
+}
+
+fn write_ss_plugin(map: &mut Mapping, plugin: &ShadowsocksPlugin) {
+    let mut opts = Mapping::new();
+    match plugin {
+        ShadowsocksPlugin::Obfs { mode, host } => {
+            insert!(map, "plugin", "obfs");
+            insert!(opts, "mode", mode.as_str());
+            if let Some(host) = host {
+                insert!(opts, "host", host.as_str());
+            }
+        }
+        ShadowsocksPlugin::V2ray {
+            mode,
+            host,
+            path,
+            tls,
+        } => {
+            insert!(map, "plugin", "v2ray-plugin");
+            insert!(opts, "mode", mode.as_str());
+            insert!(opts, "tls", *tls);
+            if let Some(host) = host {
+                insert!(opts, "host", host.as_str());
+            }
+            if let Some(path) = path {
+                insert!(opts, "path", path.as_str());
+            }
+        }
+    }
+    insert!(map, "plugin-opts", opts);
+}
+
+/// vmess/vless 使用 `servername`，trojan 使用 `sni`
+fn write_tls(map: &mut Mapping, tls: &Tls, sni_key: &str) {
+    if let Some(sni) = &tls.sni {
+        insert!(map, sni_key, sni.as_str());
+    }
+    if tls.skip_cert_verify {
+        insert!(map, "skip-cert-verify", true);
+    }
+    if !tls.alpn.is_empty() {
+        insert!(map, "alpn", tls.alpn.clone());
+    }
+    if let Some(fingerprint) = &tls.fingerprint {
+        insert!(map, "client-fingerprint", fingerprint.as_str());
+    }
+    if let Some(reality) = &tls.reality {
+        let mut opts = Mapping::new();
+        insert!(opts, "public-key", reality.public_key.as_str());
+        if let Some(short_id) = &reality.short_id {
+            insert!(opts, "short-id", short_id.as_str());
+        }
+        insert!(map, "reality-opts", opts);
+    }
+}
+
+fn write_transport(map: &mut Mapping, transport: &Transport) {
+    let mut opts = Mapping::new();
+    let (network, opts_key) = match transport {
+        Transport::Tcp => return,
+        Transport::Http { host, path } => {
+            insert!(opts, "method", "GET");
+            insert!(
+                opts,
+                "path",
+                vec![non_empty(path.clone()).unwrap_or_else(|| "/".into())]
+            );
+            if let Some(host) = non_empty(host.clone()) {
+                let mut headers = Mapping::new();
+                insert!(headers, "Host", vec![host]);
+                insert!(opts, "headers", headers);
+            }
+            ("http", "http-opts")
+        }
+        Transport::Ws { host, path } => {
+            insert!(
+                opts,
+                "path",
+                non_empty(path.clone()).unwrap_or_else(|| "/".into())
+            );
+            if let Some(host) = non_empty(host.clone()) {
+                let mut headers = Mapping::new();
+                insert!(headers, "Host", host);
+                insert!(opts, "headers", headers);
+            }
+            ("ws", "ws-opts")
+        }
+        Transport::H2 { host, path } => {
+            insert!(
+                opts,
+                "path",
+                non_empty(path.clone()).unwrap_or_else(|| "/".into())
+            );
+            if let Some(host) = non_empty(host.clone()) {
+                insert!(opts, "host", vec![host]);
+            }
+            ("h2", "h2-opts")
+        }
+        Transport::Grpc { service_name } => {
+            if let Some(service_name) = non_empty(service_name.clone()) {
+                insert!(opts, "grpc-service-name", service_name);
+            }
+            ("grpc", "grpc-opts")
+        }
+    };
+    insert!(map, "network", network);
+    insert!(map, opts_key, opts);
+}
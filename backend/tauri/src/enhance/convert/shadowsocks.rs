@@ -0,0 +1,121 @@
+//! `ss://` 链接，支持 SIP002 与旧版整体 base64 格式
+use super::{ShadowsocksOutbound, ShadowsocksPlugin, ShareLink, decode_base64};
+use base64::{Engine, engine::general_purpose};
+
+pub fn parse(link: &str) -> Result<ShadowsocksOutbound, String> {
+    let link = normalize_legacy(link)?;
+    let share = ShareLink::parse(&link)?;
+    let (cipher, password) = match &share.password {
+        Some(password) => (share.userinfo.clone(), password.clone()),
+        None => decode_base64(&share.userinfo)
+            .and_then(|userinfo| {
+                userinfo
+                    .split_once(':')
+                    .map(|(cipher, password)| (cipher.to_string(), password.to_string()))
+            })
+            .ok_or("invalid userinfo")?,
+    };
+    if cipher.is_empty() {
+        return Err("missing cipher".into());
+    }
+    let plugin = share.get("plugin").map(|p| parse_plugin(&p)).transpose()?;
+    Ok(ShadowsocksOutbound {
+        name: share.name(),
+        server: share.server,
+        port: share.port,
+        cipher,
+        password,
+        plugin,
+    })
+}
+
+/// 旧版格式 `ss://base64(method:password@host:port)#name` 转为 SIP002
+fn normalize_legacy(link: &str) -> Result<String, String> {
+    let body = &link[link.find("://").map(|i| i + 3).unwrap_or(0)..];
+    let (body, fragment) = match body.split_once('#') {
+        Some((body, fragment)) => (body, Some(fragment)),
+        None => (body, None),
+    };
+    if body.contains('@') {
+        return Ok(link.to_string());
+    }
+    let decoded = decode_base64(body.trim_end_matches('/')).ok_or("invalid base64 content")?;
+    let (userinfo, address) = decoded.rsplit_once('@').ok_or("missing server")?;
+    let userinfo = general_purpose::URL_SAFE_NO_PAD.encode(userinfo);
+    Ok(match fragment {
+        Some(fragment) => format!("ss://{userinfo}@{address}#{fragment}"),
+        None => format!("ss://{userinfo}@{address}"),
+    })
+}
+
+/// `obfs-local;obfs=http;obfs-host=example.com`
+fn parse_plugin(plugin: &str) -> Result<ShadowsocksPlugin, String> {
+    let mut parts = plugin.split(';');
+    let name = parts.next().unwrap_or_default();
+    let opts = parts
+        .map(|part| part.split_once('=').unwrap_or((part, "")))
+        .collect::<Vec<_>>();
+    let get = |key: &str| {
+        opts.iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.to_string())
+            .filter(|v| !v.is_empty())
+    };
+    match name {
+        "obfs-local" | "simple-obfs" => Ok(ShadowsocksPlugin::Obfs {
+            mode: get("obfs").unwrap_or_else(|| "http".into()),
+            host: get("obfs-host"),
+        }),
+        "v2ray-plugin" => Ok(ShadowsocksPlugin::V2ray {
+            mode: get("mode").unwrap_or_else(|| "websocket".into()),
+            host: get("host"),
+            path: get("path"),
+            tls: opts.iter().any(|(k, _)| *k == "tls"),
+        }),
+        other => Err(format!("unsupported shadowsocks plugin `{other}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sip002() {
+        // SIP002 文档中的示例
+        let ss = parse(
+            "ss://YWVzLTEyOC1nY206dGVzdA@192.168.100.1:8888/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dexample.com#Example2",
+        )
+        .unwrap();
+        assert_eq!(ss.name, "Example2");
+        assert_eq!(ss.server, "192.168.100.1");
+        assert_eq!(ss.port, 8888);
+        assert_eq!(ss.cipher, "aes-128-gcm");
+        assert_eq!(ss.password, "test");
+        assert_eq!(
+            ss.plugin,
+            Some(ShadowsocksPlugin::Obfs {
+                mode: "http".into(),
+                host: Some("example.com".into()),
+            })
+        );
+
+        let ss = parse(
+            "ss://2022-blake3-aes-256-gcm:YctPZ6U7xPPcU%2Bgp3u%2B0tx%2FtRizJN9K8y%2BuKlW2qjlI%3D@192.168.100.1:8888#Example3",
+        )
+        .unwrap();
+        assert_eq!(ss.cipher, "2022-blake3-aes-256-gcm");
+        assert_eq!(ss.password, "YctPZ6U7xPPcU+gp3u+0tx/tRizJN9K8y+uKlW2qjlI=");
+    }
+
+    #[test]
+    fn test_parse_legacy() {
+        // base64("bf-cfb:test@192.168.100.1:8888")
+        let ss = parse("ss://YmYtY2ZiOnRlc3RAMTkyLjE2OC4xMDAuMTo4ODg4#example-server").unwrap();
+        assert_eq!(ss.name, "example-server");
+        assert_eq!(ss.cipher, "bf-cfb");
+        assert_eq!(ss.password, "test");
+        assert_eq!(ss.server, "192.168.100.1");
+        assert_eq!(ss.port, 8888);
+    }
+}
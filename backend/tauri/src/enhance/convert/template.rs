@@ -0,0 +1,141 @@
+//! 转换使用的规则模板
+//! 模板是普通的 clash 配置片段，代理组中的 `$nodes` 会被替换为全部节点
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+pub const NODES_PLACEHOLDER: &str = "$nodes";
+
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("default", include_str!("./templates/default.yaml")),
+    ("minimal", include_str!("./templates/minimal.yaml")),
+];
+
+pub fn builtin_template_names() -> Vec<String> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ConversionTemplate {
+    /// 内置模板名称
+    Builtin(String),
+    /// 用户提供的模板内容
+    Custom(String),
+}
+
+impl Default for ConversionTemplate {
+    fn default() -> Self {
+        Self::Builtin("default".into())
+    }
+}
+
+impl ConversionTemplate {
+    fn load(&self) -> Result<Mapping> {
+        let content = match self {
+            ConversionTemplate::Builtin(name) => BUILTIN_TEMPLATES
+                .iter()
+                .find(|(builtin, _)| *builtin == name.as_str())
+                .map(|(_, content)| *content)
+                .with_context(|| format!("builtin template `{name}` not found"))?,
+            ConversionTemplate::Custom(content) => content.as_str(),
+        };
+        let template = serde_yaml::from_str::<Option<Mapping>>(content)
+            .context("failed to parse conversion template")?;
+        Ok(template.unwrap_or_default())
+    }
+
+    /// 生成完整配置，模板中未定义代理组或规则时使用默认值
+    pub fn render(&self, proxies: Vec<Value>, names: &[String]) -> Result<Mapping> {
+        let mut config = self.load()?;
+        config.insert("proxies".into(), Value::Sequence(proxies));
+
+        let nodes = names.iter().cloned().map(Value::from).collect::<Vec<_>>();
+        let mut groups = config
+            .remove("proxy-groups")
+            .and_then(|groups| match groups {
+                Value::Sequence(groups) => Some(groups),
+                _ => None,
+            })
+            .unwrap_or_default();
+        if groups.is_empty() {
+            let mut group = Mapping::new();
+            group.insert("name".into(), "Proxy".into());
+            group.insert("type".into(), "select".into());
+            group.insert("proxies".into(), NODES_PLACEHOLDER.into());
+            groups.push(Value::Mapping(group));
+        }
+        for group in groups.iter_mut().filter_map(Value::as_mapping_mut) {
+            let members = match group.get("proxies") {
+                Some(Value::Sequence(members)) => members.clone(),
+                Some(member @ Value::String(_)) => vec![member.clone()],
+                _ => vec![],
+            };
+            let mut expanded = Vec::with_capacity(members.len());
+            for member in members {
+                if member.as_str() == Some(NODES_PLACEHOLDER) {
+                    expanded.extend(nodes.iter().cloned());
+                } else {
+                    expanded.push(member);
+                }
+            }
+            // 不使用 provider 的空组会导致核心启动失败
+            if expanded.is_empty() && !group.contains_key("use") {
+                expanded.push("DIRECT".into());
+            }
+            group.insert("proxies".into(), Value::Sequence(expanded));
+        }
+        let first_group = groups
+            .first()
+            .and_then(|group| group.get("name"))
+            .and_then(Value::as_str)
+            .unwrap_or("DIRECT")
+            .to_string();
+        config.insert("proxy-groups".into(), Value::Sequence(groups));
+
+        if !config
+            .get("rules")
+            .and_then(Value::as_sequence)
+            .is_some_and(|rules| !rules.is_empty())
+        {
+            config.insert(
+                "rules".into(),
+                Value::Sequence(vec![format!("MATCH,{first_group}").into()]),
+            );
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_templates_are_valid() {
+        for name in builtin_template_names() {
+            let config = ConversionTemplate::Builtin(name)
+                .render(vec![], &["A".into(), "B".into()])
+                .unwrap();
+            assert!(config.contains_key("proxy-groups"));
+        }
+        assert!(
+            ConversionTemplate::Builtin("missing".into())
+                .render(vec![], &[])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_custom_template_defaults() {
+        let config = ConversionTemplate::Custom("dns:\n  enable: true\n".into())
+            .render(vec![], &["A".into()])
+            .unwrap();
+        assert_eq!(config["proxy-groups"][0]["proxies"][0].as_str(), Some("A"));
+        assert_eq!(config["rules"][0].as_str(), Some("MATCH,Proxy"));
+        assert_eq!(config["dns"]["enable"].as_bool(), Some(true));
+    }
+}
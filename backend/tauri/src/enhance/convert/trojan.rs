@@ -0,0 +1,56 @@
+//! `trojan://password@host:port?sni=...&type=ws#name`
+use super::{ShareLink, Tls, Transport, TrojanOutbound};
+
+pub fn parse(link: &str) -> Result<TrojanOutbound, String> {
+    let share = ShareLink::parse(link)?;
+    if share.userinfo.is_empty() {
+        return Err("missing password".into());
+    }
+    let transport = Transport::from_share_link(
+        share.get("type").as_deref(),
+        share.get("host"),
+        share.get("path"),
+        share.get("serviceName"),
+    )?;
+    let tls = Tls {
+        sni: share.get("sni").or_else(|| share.get("peer")),
+        skip_cert_verify: share.get_bool("allowInsecure") || share.get_bool("insecure"),
+        alpn: share.alpn(),
+        fingerprint: share.get("fp"),
+        reality: None,
+    };
+    Ok(TrojanOutbound {
+        name: share.name(),
+        server: share.server.clone(),
+        port: share.port,
+        password: share.userinfo.clone(),
+        tls,
+        transport,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trojan() {
+        let trojan = parse(
+            "trojan://f0b2a4c6@example.com:443?security=tls&sni=cdn.example.com&type=ws&host=cdn.example.com&path=%2Fws&allowInsecure=1#Trojan%20WS",
+        )
+        .unwrap();
+        assert_eq!(trojan.name, "Trojan WS");
+        assert_eq!(trojan.password, "f0b2a4c6");
+        assert_eq!(trojan.port, 443);
+        assert_eq!(trojan.tls.sni.as_deref(), Some("cdn.example.com"));
+        assert!(trojan.tls.skip_cert_verify);
+        assert_eq!(
+            trojan.transport,
+            Transport::Ws {
+                host: Some("cdn.example.com".into()),
+                path: Some("/ws".into()),
+            }
+        );
+        assert!(parse("trojan://example.com:443").is_err());
+    }
+}
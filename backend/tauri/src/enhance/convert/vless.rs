@@ -0,0 +1,90 @@
+//! `vless://uuid@host:port?security=reality&pbk=...&type=grpc#name`
+use super::{Reality, ShareLink, Tls, Transport, VlessOutbound};
+
+pub fn parse(link: &str) -> Result<VlessOutbound, String> {
+    let share = ShareLink::parse(link)?;
+    if share.userinfo.is_empty() {
+        return Err("missing uuid".into());
+    }
+    if share
+        .get("encryption")
+        .is_some_and(|encryption| encryption != "none")
+    {
+        return Err("unsupported vless encryption".into());
+    }
+    let transport = Transport::from_share_link(
+        share.get("type").as_deref(),
+        share.get("host"),
+        share.get("path"),
+        share.get("serviceName"),
+    )?;
+    let tls = match share.get("security").as_deref() {
+        None | Some("none") => None,
+        Some(security @ ("tls" | "xtls" | "reality")) => Some(Tls {
+            sni: share.get("sni"),
+            skip_cert_verify: share.get_bool("allowInsecure"),
+            alpn: share.alpn(),
+            fingerprint: share.get("fp"),
+            reality: match security {
+                "reality" => Some(Reality {
+                    public_key: share.get("pbk").ok_or("missing reality public key")?,
+                    short_id: share.get("sid"),
+                }),
+                _ => None,
+            },
+        }),
+        Some(other) => return Err(format!("unsupported security `{other}`")),
+    };
+    Ok(VlessOutbound {
+        name: share.name(),
+        server: share.server.clone(),
+        port: share.port,
+        uuid: share.userinfo.clone(),
+        flow: share.get("flow"),
+        tls,
+        transport,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vless_reality() {
+        let vless = parse(
+            "vless://b831381d-6324-4d53-ad4f-8cda48b30811@example.com:443?encryption=none&flow=xtls-rprx-vision&security=reality&sni=www.microsoft.com&fp=chrome&pbk=SbVKOEMjK0sIlbwg4akyBg5mL5KZwwB-ed4eEE7YnRc&sid=6ba85179e30d4fc2&type=tcp#VLESS%20Reality",
+        )
+        .unwrap();
+        assert_eq!(vless.name, "VLESS Reality");
+        assert_eq!(vless.uuid, "b831381d-6324-4d53-ad4f-8cda48b30811");
+        assert_eq!(vless.flow.as_deref(), Some("xtls-rprx-vision"));
+        assert_eq!(vless.transport, Transport::Tcp);
+        let tls = vless.tls.unwrap();
+        assert_eq!(tls.sni.as_deref(), Some("www.microsoft.com"));
+        assert_eq!(tls.fingerprint.as_deref(), Some("chrome"));
+        assert_eq!(
+            tls.reality,
+            Some(Reality {
+                public_key: "SbVKOEMjK0sIlbwg4akyBg5mL5KZwwB-ed4eEE7YnRc".into(),
+                short_id: Some("6ba85179e30d4fc2".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_vless_grpc() {
+        let vless = parse(
+            "vless://b831381d-6324-4d53-ad4f-8cda48b30811@example.com:443?security=tls&type=grpc&serviceName=grpc-svc#gRPC",
+        )
+        .unwrap();
+        assert_eq!(
+            vless.transport,
+            Transport::Grpc {
+                service_name: Some("grpc-svc".into())
+            }
+        );
+        assert!(vless.tls.is_some_and(|tls| tls.reality.is_none()));
+        assert!(parse("vless://uuid@example.com:443?type=kcp").is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! `vmess://base64(json)`，即 v2rayN 的分享格式
+use super::{Tls, Transport, VmessOutbound, decode_base64};
+use serde_json::Value;
+
+/// v2rayN 导出的字段可能是字符串也可能是数字
+fn get_str(json: &Value, key: &str) -> Option<String> {
+    match json.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+pub fn parse(link: &str) -> Result<VmessOutbound, String> {
+    let body = &link[link.find("://").map(|i| i + 3).unwrap_or(0)..];
+    let body = body.split('#').next().unwrap_or_default();
+    let json = decode_base64(body).ok_or("invalid base64 content")?;
+    let json: Value = serde_json::from_str(&json).map_err(|e| format!("invalid json: {e}"))?;
+
+    let server = get_str(&json, "add").ok_or("missing server")?;
+    let port = get_str(&json, "port")
+        .and_then(|port| port.parse::<u16>().ok())
+        .ok_or("invalid port")?;
+    let uuid = get_str(&json, "id").ok_or("missing uuid")?;
+    let alter_id = get_str(&json, "aid")
+        .and_then(|aid| aid.parse().ok())
+        .unwrap_or(0);
+
+    let network = get_str(&json, "net");
+    let host = get_str(&json, "host");
+    let path = get_str(&json, "path");
+    // tcp 伪装为 http 时 `type` 为 http
+    let http_header = get_str(&json, "type").is_some_and(|t| t == "http");
+    let network = match network {
+        None if http_header => Some("http".to_string()),
+        Some(net) if net == "tcp" && http_header => Some("http".to_string()),
+        net => net,
+    };
+    let transport = Transport::from_share_link(network.as_deref(), host.clone(), path, None)?;
+
+    let tls = get_str(&json, "tls")
+        .filter(|tls| tls == "tls")
+        .map(|_| Tls {
+            sni: get_str(&json, "sni").or(host),
+            skip_cert_verify: get_str(&json, "allowInsecure").is_some_and(|v| v == "1"),
+            alpn: get_str(&json, "alpn")
+                .map(|alpn| alpn.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            fingerprint: get_str(&json, "fp"),
+            reality: None,
+        });
+
+    Ok(VmessOutbound {
+        name: get_str(&json, "ps").unwrap_or_else(|| format!("{server}:{port}")),
+        server,
+        port,
+        uuid,
+        alter_id,
+        cipher: get_str(&json, "scy").unwrap_or_else(|| "auto".into()),
+        tls,
+        transport,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{Engine, engine::general_purpose};
+
+    fn encode(json: &str) -> String {
+        format!("vmess://{}", general_purpose::STANDARD.encode(json))
+    }
+
+    #[test]
+    fn test_parse_vmess_ws_tls() {
+        let vmess = parse(&encode(
+            r#"{"v":"2","ps":"VMess WS","add":"example.com","port":"443","id":"a3482e88-686a-4a58-8126-99c9df64b7bf","aid":"0","scy":"auto","net":"ws","type":"none","host":"cdn.example.com","path":"/ray","tls":"tls","sni":""}"#,
+        ))
+        .unwrap();
+        assert_eq!(vmess.name, "VMess WS");
+        assert_eq!(vmess.port, 443);
+        assert_eq!(vmess.alter_id, 0);
+        assert_eq!(
+            vmess.transport,
+            Transport::Ws {
+                host: Some("cdn.example.com".into()),
+                path: Some("/ray".into()),
+            }
+        );
+        // sni 为空时回退到 host
+        assert_eq!(
+            vmess.tls.and_then(|tls| tls.sni).as_deref(),
+            Some("cdn.example.com")
+        );
+    }
+
+    #[test]
+    fn test_parse_vmess_numeric_fields() {
+        let vmess = parse(&encode(
+            r#"{"ps":"tcp","add":"1.2.3.4","port":10086,"id":"a3482e88-686a-4a58-8126-99c9df64b7bf","aid":64,"net":"tcp","type":"http","path":"/"}"#,
+        ))
+        .unwrap();
+        assert_eq!(vmess.port, 10086);
+        assert_eq!(vmess.alter_id, 64);
+        assert_eq!(vmess.cipher, "auto");
+        assert!(vmess.tls.is_none());
+        assert!(matches!(vmess.transport, Transport::Http { .. }));
+        assert!(parse("vmess://not-base64").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! 补全 fake-ip DNS 配置，开启 TUN 或单独开启 DNS 增强时执行
+use crate::{config::nyanpasu::ClashCore, core::clash::core_info::CoreFlavor};
+use anyhow::{Result, anyhow, bail};
+use serde_yaml::{Mapping, Value};
+use std::net::IpAddr;
+use url::{Host, Url};
+
+pub const DEFAULT_NAMESERVERS: [&str; 3] = ["114.114.114.114", "223.5.5.5", "8.8.8.8"];
+
+/// 开启 IPv6 时追加的 DNS 服务器
+const IPV6_NAMESERVERS: [&str; 2] = ["2400:3200::1", "2001:4860:4860::8888"];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DnsOptions<'a> {
+    pub enable_ipv6: bool,
+    /// 用户设置的上游，设置后替换配置中的 `nameserver`
+    pub nameservers: Option<&'a [String]>,
+}
+
+/// 解析后的 DNS 上游，如 `8.8.8.8`、`tls://1.1.1.1`、`https://dns.google/dns-query`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsUpstream {
+    pub scheme: String,
+    /// `dhcp://` 为网卡名称，`system://` 为空
+    pub host: String,
+    pub port: u16,
+    /// DoH 请求的完整地址
+    pub url: Option<Url>,
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "udp" | "tcp" => Some(53),
+        "tls" | "quic" => Some(853),
+        "https" => Some(443),
+        "dhcp" | "system" => Some(0),
+        _ => None,
+    }
+}
+
+impl DnsUpstream {
+    pub fn parse(server: &str) -> Result<Self> {
+        // mihomo 支持用 `#` 指定出站的策略
+        let server = server.split_once('#').map_or(server, |(s, _)| s).trim();
+        if let Ok(ip) = server.parse::<IpAddr>() {
+            return Ok(Self {
+                scheme: "udp".into(),
+                host: ip.to_string(),
+                port: 53,
+                url: None,
+            });
+        }
+        let with_scheme = if server.contains("://") {
+            server.to_string()
+        } else {
+            format!("udp://{server}")
+        };
+        let url = Url::parse(&with_scheme).map_err(|e| anyhow!("`{server}`: {e}"))?;
+        let scheme = url.scheme().to_string();
+        let Some(port) = default_port(&scheme) else {
+            bail!("`{server}`: unknown scheme `{scheme}`");
+        };
+        let host = match url.host() {
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            Some(host) => host.to_string(),
+            None => String::new(),
+        };
+        if host.is_empty() && scheme != "system" {
+            bail!("`{server}`: missing host");
+        }
+        Ok(Self {
+            port: url.port().unwrap_or(port),
+            url: (scheme == "https").then_some(url),
+            scheme,
+            host,
+        })
+    }
+}
+
+/// 检查上游的格式以及当前核心是否支持其协议
+pub fn validate_nameservers(servers: &[String], core: &ClashCore) -> Result<()> {
+    let schemes = CoreFlavor::of(core).dns_schemes();
+    let errors = servers
+        .iter()
+        .filter_map(|server| match DnsUpstream::parse(server) {
+            Ok(upstream) if !schemes.contains(&upstream.scheme.as_str()) => Some(format!(
+                "`{server}`: {core} does not support `{}` upstreams",
+                upstream.scheme
+            )),
+            Ok(_) => None,
+            Err(e) => Some(e.to_string()),
+        })
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        bail!("invalid dns nameservers: {}", errors.join("; "));
+    }
+    Ok(())
+}
+
+/// 键不存在时才写入
+fn append(map: &mut Mapping, key: &str, value: impl Into<Value>) {
+    map.entry(key.into()).or_insert_with(|| value.into());
+}
+
+/// 向列表追加一项，保留已有的项
+fn push_unique(map: &mut Mapping, key: &str, item: &str) {
+    let entry = map
+        .entry(key.into())
+        .or_insert_with(|| Value::Sequence(Vec::new()));
+    if !entry.is_sequence() {
+        *entry = Value::Sequence(Vec::new());
+    }
+    if let Some(items) = entry.as_sequence_mut()
+        && !items.iter().any(|v| v.as_str() == Some(item))
+    {
+        items.push(Value::from(item));
+    }
+}
+
+/// 关闭 IPv6 时覆盖订阅中的 `dns.ipv6`，不依赖 DNS 增强是否执行
+pub fn use_dns_ipv6(mut config: Mapping, enable_ipv6: bool) -> Mapping {
+    if !enable_ipv6 && let Some(dns) = config.get_mut("dns").and_then(|v| v.as_mapping_mut()) {
+        dns.insert("ipv6".into(), false.into());
+    }
+    config
+}
+
+pub fn use_dns(mut config: Mapping, opts: &DnsOptions) -> Mapping {
+    let mut dns = config
+        .get("dns")
+        .and_then(|v| v.as_mapping())
+        .cloned()
+        .unwrap_or_default();
+
+    dns.insert("enable".into(), true.into());
+
+    append(&mut dns, "enhanced-mode", "fake-ip");
+    append(&mut dns, "fake-ip-range", "198.18.0.1/16");
+    if let Some(servers) = opts.nameservers.filter(|servers| !servers.is_empty()) {
+        dns.insert("nameserver".into(), servers.to_vec().into());
+    }
+    append(&mut dns, "nameserver", DEFAULT_NAMESERVERS.to_vec());
+    append(&mut dns, "fallback", Vec::<&str>::new());
+
+    // 部分网络环境中 IPv6 会导致连接异常，默认关闭
+    dns.insert("ipv6".into(), opts.enable_ipv6.into());
+    if opts.enable_ipv6 {
+        append(&mut dns, "fake-ip-range6", "2001:db8::/32");
+        // 用户设置的上游不做改动
+        if opts.nameservers.is_none()
+            && let Some(nameservers) = dns.get_mut("nameserver").and_then(|v| v.as_sequence_mut())
+        {
+            for server in IPV6_NAMESERVERS {
+                if !nameservers.iter().any(|v| v.as_str() == Some(server)) {
+                    nameservers.push(Value::from(server));
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    append(
+        &mut dns,
+        "fake-ip-filter",
+        vec![
+            "dns.msftncsi.com",
+            "www.msftncsi.com",
+            "www.msftconnecttest.com",
+        ],
+    );
+    if opts.enable_ipv6 {
+        push_unique(&mut dns, "fake-ip-filter", "AAAA");
+    }
+    config.insert("dns".into(), dns.into());
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dns(config: &Mapping) -> &Mapping {
+        config.get("dns").and_then(|v| v.as_mapping()).unwrap()
+    }
+
+    #[test]
+    fn test_ipv6_disabled() {
+        let config = use_dns(Mapping::new(), &DnsOptions::default());
+        let dns = dns(&config);
+        assert_eq!(dns.get("enable"), Some(&Value::from(true)));
+        assert_eq!(dns.get("ipv6"), Some(&Value::from(false)));
+        assert!(!dns.contains_key("fake-ip-range6"));
+        assert!(
+            !dns.get("fake-ip-filter")
+                .and_then(|v| v.as_sequence())
+                .is_some_and(|filter| filter.contains(&Value::from("AAAA")))
+        );
+        let nameservers = dns.get("nameserver").and_then(|v| v.as_sequence()).unwrap();
+        assert_eq!(nameservers.len(), 3);
+    }
+
+    #[test]
+    fn test_ipv6_enabled() {
+        let config: Mapping = serde_yaml::from_str(
+            "dns:\n  enhanced-mode: redir-host\n  nameserver: [223.5.5.5, 2400:3200::1]\n  fake-ip-filter: ['*.lan']",
+        )
+        .unwrap();
+        let config = use_dns(
+            config,
+            &DnsOptions {
+                enable_ipv6: true,
+                ..Default::default()
+            },
+        );
+        let dns = dns(&config);
+        assert_eq!(dns.get("ipv6"), Some(&Value::from(true)));
+        // 已有的设置不会被覆盖
+        assert_eq!(dns.get("enhanced-mode"), Some(&Value::from("redir-host")));
+        assert_eq!(
+            dns.get("fake-ip-range6"),
+            Some(&Value::from("2001:db8::/32"))
+        );
+        assert_eq!(
+            dns.get("fake-ip-filter"),
+            Some(&Value::from(vec!["*.lan", "AAAA"]))
+        );
+        let nameservers = dns.get("nameserver").and_then(|v| v.as_sequence()).unwrap();
+        assert_eq!(
+            nameservers,
+            &["223.5.5.5", "2400:3200::1", "2001:4860:4860::8888"]
+                .map(Value::from)
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn test_custom_nameservers() {
+        let servers = vec!["https://dns.google/dns-query".to_string()];
+        let config = use_dns(
+            serde_yaml::from_str("dns:\n  nameserver: [223.5.5.5]").unwrap(),
+            &DnsOptions {
+                enable_ipv6: true,
+                nameservers: Some(&servers),
+            },
+        );
+        assert_eq!(
+            dns(&config).get("nameserver"),
+            Some(&Value::from(servers.clone()))
+        );
+    }
+
+    #[test]
+    fn test_dns_ipv6_without_enhancement() {
+        let config: Mapping = serde_yaml::from_str("dns:\n  ipv6: true").unwrap();
+        let disabled = use_dns_ipv6(config.clone(), false);
+        assert_eq!(dns(&disabled).get("ipv6"), Some(&Value::from(false)));
+        assert_eq!(use_dns_ipv6(config.clone(), true), config);
+        assert!(!use_dns_ipv6(Mapping::new(), false).contains_key("dns"));
+    }
+
+    #[test]
+    fn test_parse_upstream() {
+        let upstream = DnsUpstream::parse("2400:3200::1").unwrap();
+        assert_eq!((upstream.scheme.as_str(), upstream.port), ("udp", 53));
+        let upstream = DnsUpstream::parse("tls://[2001:db8::1]#Proxy").unwrap();
+        assert_eq!(
+            (
+                upstream.scheme.as_str(),
+                upstream.host.as_str(),
+                upstream.port
+            ),
+            ("tls", "2001:db8::1", 853)
+        );
+        let upstream = DnsUpstream::parse("https://dns.google:8443/dns-query").unwrap();
+        assert_eq!(upstream.port, 8443);
+        assert!(upstream.url.is_some());
+        assert_eq!(
+            DnsUpstream::parse("quic://dns.adguard.com").unwrap().port,
+            853
+        );
+        assert!(DnsUpstream::parse("ftp://1.1.1.1").is_err());
+        assert!(DnsUpstream::parse("tls://").is_err());
+    }
+
+    #[test]
+    fn test_validate_nameservers_per_core() {
+        let servers = vec![
+            "223.5.5.5".to_string(),
+            "quic://dns.adguard.com".to_string(),
+        ];
+        assert!(validate_nameservers(&servers, &ClashCore::Mihomo).is_ok());
+        let err = validate_nameservers(&servers, &ClashCore::ClashPremium)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("clash does not support `quic`"), "{err}");
+        assert_eq!(
+            CoreFlavor::ClashRs.dns_schemes(),
+            ["udp", "tcp", "tls", "https"]
+        );
+    }
+}
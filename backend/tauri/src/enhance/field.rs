@@ -1,5 +1,4 @@
 use serde_yaml::{Mapping, Value};
-use std::collections::HashSet;
 
 pub const HANDLE_FIELDS: [&str; 9] = [
     "mode",
@@ -21,7 +20,7 @@ pub const DEFAULT_FIELDS: [&str; 5] = [
     "rule-providers",
 ];
 
-pub const OTHERS_FIELDS: [&str; 31] = [
+pub const OTHERS_FIELDS: [&str; 33] = [
     "dns",
     "tun",
     "ebpf",
@@ -51,7 +50,9 @@ pub const OTHERS_FIELDS: [&str; 31] = [
     "enable-process",            // meta
     "find-process-mode",         // meta
     "skip-auth-prefixes",        // meta
+    "lan-allowed-ips",           // meta
     "external-controller-tls",   // meta
+    "external-controller-unix",  // meta
     "global-client-fingerprint", // meta
 ];
 
@@ -72,7 +73,8 @@ pub fn use_whitelist_fields_filter(config: Mapping, _filter: &[String], enable:
     if enable { config } else { config }
 }
 
-pub fn use_sort(config: Mapping, enable_filter: bool) -> Mapping {
+/// 按字段顺序重排，移动而不复制字段的值，避免大配置的规则被复制一遍
+pub fn use_sort(mut config: Mapping, enable_filter: bool) -> Mapping {
     let mut ret = Mapping::new();
 
     HANDLE_FIELDS
@@ -81,26 +83,14 @@ pub fn use_sort(config: Mapping, enable_filter: bool) -> Mapping {
         .chain(DEFAULT_FIELDS)
         .for_each(|key| {
             let key = Value::from(key);
-            if let Some(value) = config.get(&key) {
-                ret.insert(key, value.clone());
+            if let Some(value) = config.remove(&key) {
+                ret.insert(key, value);
             }
         });
 
+    // 剩余的都是不支持的字段
     if !enable_filter {
-        let supported_keys: HashSet<&str> = HANDLE_FIELDS
-            .into_iter()
-            .chain(OTHERS_FIELDS)
-            .chain(DEFAULT_FIELDS)
-            .collect();
-
-        let config_keys: HashSet<&str> = config.keys().filter_map(|e| e.as_str()).collect();
-
-        config_keys.difference(&supported_keys).for_each(|&key| {
-            let key = Value::from(key);
-            if let Some(value) = config.get(&key) {
-                ret.insert(key, value.clone());
-            }
-        });
+        ret.extend(config);
     }
 
     ret
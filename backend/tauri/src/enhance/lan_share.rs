@@ -0,0 +1,119 @@
+use serde_yaml::{Mapping, Value};
+
+use crate::config::nyanpasu::{ClashCore, LanShareConfig};
+
+/// 本机访问无需认证也不受地址段限制
+const LOOPBACK_CIDRS: [&str; 2] = ["127.0.0.0/8", "::1/128"];
+
+/// 按局域网共享设置覆盖 `allow-lan` 等字段，未设置时保持原样
+#[tracing_attributes::instrument(skip(config))]
+pub fn use_lan_share(
+    mut config: Mapping,
+    lan_share: Option<&LanShareConfig>,
+    core: ClashCore,
+) -> Mapping {
+    let Some(lan_share) = lan_share else {
+        return config;
+    };
+    config.insert("allow-lan".into(), lan_share.enabled.into());
+    if !lan_share.enabled {
+        return config;
+    }
+    let is_mihomo = matches!(core, ClashCore::Mihomo | ClashCore::MihomoAlpha);
+
+    config.insert(
+        "bind-address".into(),
+        lan_share.bind_address.as_bind_address().into(),
+    );
+
+    if !lan_share.allowed_cidrs.is_empty() {
+        if is_mihomo {
+            let allowed = lan_share
+                .allowed_cidrs
+                .iter()
+                .map(String::as_str)
+                .chain(LOOPBACK_CIDRS)
+                .map(Value::from)
+                .collect::<Vec<_>>();
+            config.insert("lan-allowed-ips".into(), allowed.into());
+        } else {
+            tracing::warn!("{core} does not support `lan-allowed-ips`, ignore allowed cidrs");
+        }
+    }
+
+    if let Some(credentials) = lan_share.credentials() {
+        config.insert("authentication".into(), vec![credentials].into());
+        if is_mihomo {
+            // 保留订阅或用户已有的免认证地址段
+            let mut prefixes = config
+                .get("skip-auth-prefixes")
+                .and_then(Value::as_sequence)
+                .cloned()
+                .unwrap_or_default();
+            for cidr in LOOPBACK_CIDRS {
+                if !prefixes.iter().any(|v| v.as_str() == Some(cidr)) {
+                    prefixes.push(cidr.into());
+                }
+            }
+            config.insert("skip-auth-prefixes".into(), prefixes.into());
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::nyanpasu::LanBindAddress;
+
+    #[test]
+    fn test_use_lan_share() {
+        let mut config = Mapping::new();
+        config.insert("allow-lan".into(), true.into());
+        assert_eq!(
+            use_lan_share(config.clone(), None, ClashCore::Mihomo),
+            config
+        );
+
+        let lan_share = LanShareConfig {
+            enabled: true,
+            bind_address: LanBindAddress::Specific("192.168.1.2".parse().unwrap()),
+            allowed_cidrs: vec!["192.168.1.0/24".into()],
+            require_auth: true,
+            username: "phone".into(),
+            password: "secret".into(),
+        };
+        let result = use_lan_share(Mapping::new(), Some(&lan_share), ClashCore::Mihomo);
+        let expected: Mapping = serde_yaml::from_str(
+            r#"
+allow-lan: true
+bind-address: 192.168.1.2
+lan-allowed-ips: [192.168.1.0/24, 127.0.0.0/8, "::1/128"]
+authentication: ["phone:secret"]
+skip-auth-prefixes: [127.0.0.0/8, "::1/128"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(result, expected);
+
+        let mut existing = Mapping::new();
+        existing.insert(
+            "skip-auth-prefixes".into(),
+            vec!["10.0.0.0/8", "127.0.0.0/8"].into(),
+        );
+        let result = use_lan_share(existing, Some(&lan_share), ClashCore::Mihomo);
+        assert_eq!(
+            result.get("skip-auth-prefixes"),
+            Some(&Value::from(vec!["10.0.0.0/8", "127.0.0.0/8", "::1/128"]))
+        );
+
+        let result = use_lan_share(Mapping::new(), Some(&lan_share), ClashCore::ClashPremium);
+        assert!(!result.contains_key("lan-allowed-ips"));
+        assert!(!result.contains_key("skip-auth-prefixes"));
+
+        let disabled = LanShareConfig::default();
+        let result = use_lan_share(config, Some(&disabled), ClashCore::Mihomo);
+        assert_eq!(result.get("allow-lan"), Some(&Value::from(false)));
+        assert!(!result.contains_key("bind-address"));
+    }
+}
@@ -1,25 +1,53 @@
-use super::{Logs, LogsExt, runner::ProcessOutput};
+use super::{Logs, LogsExt, field::HANDLE_FIELDS, runner::ProcessOutput};
 use mlua::LuaSerdeExt;
 use serde::de::DeserializeOwned;
 use serde_yaml::{Mapping, Value};
 use tracing_attributes::instrument;
 
-// Override recursive, and if the value is sequence, it should be append to the end.
+// Override recursive, sequences and other values are replaced.
 fn override_recursive(config: &mut Mapping, key: &Value, data: Value) {
-    if let Some(value) = config.get_mut(key) {
-        if value.is_mapping() {
-            let value = value.as_mapping_mut().unwrap();
-            let data = data.as_mapping().unwrap();
-            for (k, v) in data.iter() {
-                override_recursive(value, k, v.clone());
+    deep_merge(config, Mapping::from_iter([(key.clone(), data)]), false);
+}
+
+/// 映射递归合并，其余类型的值直接覆盖
+/// `directives` 为真时序列默认追加到末尾，由同级的 `$replace`、`$prepend` 指令改为替换或插入到开头，
+/// 否则序列同样直接覆盖
+fn deep_merge(base: &mut Mapping, overlay: Mapping, directives: bool) {
+    let (replace, prepend) = if directives {
+        (
+            directive_keys(&overlay, REPLACE_DIRECTIVE),
+            directive_keys(&overlay, PREPEND_DIRECTIVE),
+        )
+    } else {
+        Default::default()
+    };
+    for (key, value) in overlay {
+        if directives && matches!(key.as_str(), Some(REPLACE_DIRECTIVE | PREPEND_DIRECTIVE)) {
+            continue;
+        }
+        let Some(target) = base.get_mut(&key) else {
+            tracing::trace!("insert key: {:#?}", key);
+            base.insert(key, value);
+            continue;
+        };
+        match (target, value) {
+            (Value::Mapping(target), Value::Mapping(value)) => {
+                deep_merge(target, value, directives);
+            }
+            (Value::Sequence(target), Value::Sequence(value))
+                if directives && !replace.contains(&key) =>
+            {
+                if prepend.contains(&key) {
+                    target.splice(0..0, value);
+                } else {
+                    target.extend(value);
+                }
+            }
+            (target, value) => {
+                tracing::trace!("override key: {:#?}", key);
+                *target = value;
             }
-        } else {
-            tracing::trace!("override key: {:#?}", key);
-            *value = data;
         }
-    } else {
-        tracing::trace!("insert key: {:#?}", key);
-        config.insert(key.clone(), data);
     }
 }
 
@@ -317,6 +345,39 @@ pub fn use_merge(merge: &Mapping, mut config: Mapping) -> ProcessOutput {
     (Ok(config), logs)
 }
 
+/// overlay 中的指令键，值为同级中需要整体替换的序列字段
+pub(super) const REPLACE_DIRECTIVE: &str = "$replace";
+/// overlay 中的指令键，值为同级中需要插入到开头的序列字段，如 `rules`
+pub(super) const PREPEND_DIRECTIVE: &str = "$prepend";
+
+fn directive_keys(overlay: &Mapping, directive: &str) -> Vec<Value> {
+    overlay
+        .get(directive)
+        .and_then(|v| v.as_sequence())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// 将 overlay 深度合并到 base 中，序列默认追加到末尾，见 `deep_merge`
+pub fn merge_config(mut base: Mapping, overlay: Mapping) -> Mapping {
+    deep_merge(&mut base, overlay, true);
+    base
+}
+
+/// 去掉 overlay 中由应用管理的字段（端口、secret、控制器地址等），返回被去掉的键
+pub fn strip_guarded_fields(overlay: &mut Mapping) -> Vec<String> {
+    let mut stripped = Vec::new();
+    overlay.retain(|key, _| {
+        let key = key.as_str().unwrap_or_default();
+        let guarded = HANDLE_FIELDS.contains(&key) || key.starts_with("external-controller");
+        if guarded {
+            stripped.push(key.to_string());
+        }
+        !guarded
+    });
+    stripped
+}
+
 mod tests {
     #[allow(unused_imports)]
     use pretty_assertions::{assert_eq, assert_ne};
@@ -344,6 +405,105 @@ mod tests {
         assert!(field.is_none(), "a.b.c.2 should not be found");
     }
 
+    #[test]
+    fn test_merge_config_nested_map() {
+        let base = r"
+        dns:
+          enable: false
+          nameserver:
+            - 223.5.5.5
+          fallback-filter:
+            geoip: true
+        mode: rule
+        ";
+        let overlay = r"
+        dns:
+          enable: true
+          fallback-filter:
+            geoip-code: CN
+        log-level: debug
+        ";
+        let expected = r"
+        dns:
+          enable: true
+          nameserver:
+            - 223.5.5.5
+          fallback-filter:
+            geoip: true
+            geoip-code: CN
+        mode: rule
+        log-level: debug
+        ";
+        let base = serde_yaml::from_str::<super::Mapping>(base).unwrap();
+        let overlay = serde_yaml::from_str::<super::Mapping>(overlay).unwrap();
+        let expected = serde_yaml::from_str::<super::Mapping>(expected).unwrap();
+        assert_eq!(super::merge_config(base, overlay), expected);
+    }
+
+    #[test]
+    fn test_merge_config_sequences() {
+        let base = r"
+        proxies:
+          - a
+        rules:
+          - MATCH,DIRECT
+        dns:
+          nameserver:
+            - 223.5.5.5
+        ";
+        let overlay = r"
+        $prepend: [rules]
+        proxies:
+          - b
+        rules:
+          - DOMAIN-SUFFIX,example.com,Proxy
+        dns:
+          $replace: [nameserver]
+          nameserver:
+            - 8.8.8.8
+        ";
+        let expected = r"
+        proxies:
+          - a
+          - b
+        rules:
+          - DOMAIN-SUFFIX,example.com,Proxy
+          - MATCH,DIRECT
+        dns:
+          nameserver:
+            - 8.8.8.8
+        ";
+        let base = serde_yaml::from_str::<super::Mapping>(base).unwrap();
+        let overlay = serde_yaml::from_str::<super::Mapping>(overlay).unwrap();
+        let expected = serde_yaml::from_str::<super::Mapping>(expected).unwrap();
+        assert_eq!(super::merge_config(base, overlay), expected);
+    }
+
+    #[test]
+    fn test_strip_guarded_fields() {
+        let overlay = r"
+        secret: leaked
+        external-controller: 0.0.0.0:9090
+        external-controller-tls: 0.0.0.0:9443
+        mixed-port: 1080
+        rules:
+          - MATCH,DIRECT
+        ";
+        let mut overlay = serde_yaml::from_str::<super::Mapping>(overlay).unwrap();
+        let stripped = super::strip_guarded_fields(&mut overlay);
+        assert_eq!(
+            stripped,
+            [
+                "secret",
+                "external-controller",
+                "external-controller-tls",
+                "mixed-port"
+            ]
+        );
+        assert_eq!(overlay.len(), 1);
+        assert!(overlay.contains_key("rules"));
+    }
+
     #[test]
     fn test_merge_append() {
         let merge = r"
@@ -1,40 +1,95 @@
 mod chain;
+pub mod convert;
+mod dns;
 mod field;
+mod lan_share;
 mod merge;
+mod pipeline;
+mod plugin;
+mod rule_inject;
 mod script;
 mod tun;
 mod utils;
+mod validate;
 
 pub use self::chain::ScriptType;
-use self::{chain::*, field::*, merge::*, script::*, tun::*};
-use crate::config::{Config, ProfileMetaGetter, nyanpasu::ClashCore};
+use self::{chain::*, field::*, lan_share::*, merge::*, pipeline::EnhanceContext};
+use crate::{
+    config::{Config, ProfileMetaGetter, Profiles},
+    core::{activation, clash::core_info::CoreFlavor},
+};
 pub use chain::PostProcessingOutput;
+pub use dns::{DEFAULT_NAMESERVERS, DnsUpstream, validate_nameservers};
 use futures::future::join_all;
 use indexmap::IndexMap;
+pub use pipeline::{EnhanceChain, EnhanceStep, EnhanceStepKind};
+pub use plugin::{PluginInfo, list_core_plugins, load_core_plugins, set_plugin_enabled};
+pub use rule_inject::{RuleInjection, RulePosition};
 use serde_yaml::{Mapping, Value};
 use std::collections::HashSet;
+pub use tun::{TranslationAction, TranslationWarning};
 pub use utils::{Logs, LogsExt};
 use utils::{merge_profiles, process_chain};
+pub use validate::{
+    ProxyValidationError, ValidationSeverity, use_proxy_validation, validate_proxy_definitions,
+};
+
+/// 超过该大小（MB）的配置在解析时记录警告
+const DEFAULT_LARGE_PROFILE_WARN_MB: u64 = 20;
 
 /// Enhance mode
 /// 返回最终配置、该配置包含的键、和script执行的结果
 pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
     // config.yaml 的配置
-    let clash_config = { Config::clash().latest().0.clone() };
-
-    let (clash_core, enable_tun, enable_builtin, enable_filter) = {
+    let mut clash_config = { Config::clash().latest().0.clone() };
+    // 端口替换只作用于运行时配置，不写回 clash 配置
+    if let Some(port) = clash_config.get("mixed-port").and_then(|v| v.as_u64())
+        && let Ok(port) = u16::try_from(port)
+        && let Some(actual) = crate::utils::net::ClashMixedPortRangeScanner::substituted(port)
+    {
+        clash_config.insert("mixed-port".into(), actual.into());
+    }
+    let overlay = read_merge_overlay();
+
+    let clash_core = Config::clash_core();
+    let (
+        enable_tun,
+        enable_ipv6,
+        enable_dns,
+        dns_nameservers,
+        enable_builtin,
+        enable_filter,
+        lan_share,
+        enhance_chain,
+        rule_injection,
+        global_excludes,
+        large_profile_warn_size,
+        core_extra_args,
+    ) = {
         let verge = Config::verge();
         let verge = verge.latest();
         (
-            verge.clash_core,
             verge.enable_tun_mode.unwrap_or(false),
+            verge.enable_ipv6.unwrap_or(false),
+            verge.enable_dns_enhance.unwrap_or(false),
+            verge.dns_nameservers.clone(),
             verge.enable_builtin_enhanced.unwrap_or(true),
             verge.enable_clash_fields.unwrap_or(true),
+            verge.lan_share.clone(),
+            verge.enhance_chain.clone().unwrap_or_default(),
+            verge.rule_injection.clone(),
+            verge.global_exclude_list.clone().unwrap_or_default(),
+            verge
+                .large_profile_warn_mb
+                .unwrap_or(DEFAULT_LARGE_PROFILE_WARN_MB)
+                * 1024
+                * 1024,
+            verge.clash_core_extra_args.clone().unwrap_or_default(),
         )
     };
 
     // 从profiles里拿东西
-    let (profiles, profile_chain, global_chain, valid) = {
+    let (current_files, profile_chain, global_chain, valid) = {
         let profiles = Config::profiles();
         let profiles = profiles.latest();
 
@@ -60,20 +115,26 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
             })
             .collect::<IndexMap<_, _>>();
 
-        let current_mappings = profiles
-            .current_mappings()
-            .unwrap_or_default()
-            .into_iter()
-            .map(|(k, v)| (k.to_string(), v))
-            .collect::<IndexMap<_, _>>();
+        let current_files = profiles.current_files();
 
         let global_chain = utils::convert_uids_to_scripts(&profiles, &profiles.chain);
 
         let valid = profiles.valid.clone();
 
-        (current_mappings, profile_chain_mapping, global_chain, valid)
+        (current_files, profile_chain_mapping, global_chain, valid)
     };
 
+    // 大配置解析耗时较长，不在异步运行时中执行
+    let timer = activation::StageTimer::start();
+    let profiles = async {
+        let files = current_files?;
+        tokio::task::spawn_blocking(move || Profiles::read_mappings(files, large_profile_warn_size))
+            .await?
+    }
+    .await;
+    timer.finish("parse", profiles.as_ref().err());
+    let profiles = profiles.unwrap_or_default();
+
     let mut postprocessing_output = PostProcessingOutput::default();
 
     let valid = use_valid_fields(&valid);
@@ -105,48 +166,47 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
     let mut exists_keys = use_keys(&config);
     config = use_whitelist_fields_filter(config, &valid, enable_filter);
 
-    // 合并默认的config
-    clash_config
-        .iter()
-        // only guarded fields should be overwritten
-        .filter(|(k, _)| HANDLE_FIELDS.contains(&k.as_str().unwrap_or_default()))
-        .for_each(|(key, value)| {
-            config.insert(key.to_owned(), value.clone());
-        });
-
     let clash_fields = use_clash_fields();
 
-    // 内建脚本最后跑
-    if enable_builtin {
-        let mut script_runner = RunnerManager::new();
-        for item in ChainItem::builtin()
-            .into_iter()
-            .filter(|(s, _)| s.contains(*clash_core.as_ref().unwrap_or(&ClashCore::default())))
-            .map(|(_, c)| c)
-        {
-            log::debug!(target: "app", "run builtin script {}", item.uid);
-
-            if let ChainTypeWrapper::Script(script) = item.data {
-                let (res, _) = script_runner
-                    .process_script(&script, config.to_owned())
-                    .await;
-                match res {
-                    Ok(res_config) => {
-                        config = res_config;
-                    }
-                    Err(err) => {
-                        log::error!(target: "app", "builtin script error `{err:?}`");
-                    }
-                }
-            }
-        }
-    }
+    // 配置的核心名称可能指向 clash-rs 二进制，切换核心后的第一份配置也要按实际类型生成
+    let flavor = CoreFlavor::detect(&clash_core).await;
+
+    // 合并默认的 config、内建脚本、TUN 等步骤按用户设置的顺序执行
+    let timer = activation::StageTimer::start();
+    let ctx = EnhanceContext {
+        clash_config: &clash_config,
+        overlay: overlay.as_ref(),
+        rule_injection: rule_injection.as_ref(),
+        clash_core,
+        enable_tun,
+        enable_ipv6,
+        enable_dns,
+        dns_nameservers: dns_nameservers.as_deref(),
+        enable_builtin,
+    };
+    config = enhance_chain.apply(config, &ctx).await;
+    config = rule_inject::use_global_excludes(config, &global_excludes);
+    config = plugin::use_core_plugins(config, clash_core);
 
     config = use_whitelist_fields_filter(config, &clash_fields, enable_filter);
-    config = use_tun(config, enable_tun);
+    config = use_lan_share(config, lan_share.as_ref(), clash_core);
     config = use_include_all_proxy_groups(config);
+    if flavor == CoreFlavor::ClashRs {
+        let (translated, warnings) = tun::translate_config_for_clash_rs(config);
+        config = translated;
+        postprocessing_output.compatibility_warnings = warnings;
+    }
+    postprocessing_output.proxy_validation = use_proxy_validation(&config, clash_core);
     config = use_cache(config);
     config = use_sort(config, enable_filter);
+    // 核心的附加参数与命令行参数一样覆盖配置，不受字段过滤影响，未通过校验时由启动核心时报错
+    if !core_extra_args.is_empty() {
+        match crate::core::clash::core_args::validate_core_args(&core_extra_args) {
+            Ok(args) => crate::core::clash::core_args::apply_core_args(&mut config, &args),
+            Err(e) => log::error!(target: "app", "{e:?}"),
+        }
+    }
+    timer.finish("builtin", None::<String>);
 
     let logs = Vec::new(); // Simplified - no advice in extreme cleanup version
     postprocessing_output.advice = logs;
@@ -158,6 +218,21 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
     (config, exists_keys, postprocessing_output)
 }
 
+/// 读取用户的合并配置，文件不存在时返回 None
+fn read_merge_overlay() -> Option<Mapping> {
+    let path = crate::utils::dirs::merge_overlay_path().ok()?;
+    if !path.exists() {
+        return None;
+    }
+    match crate::utils::help::read_merge_mapping(&path) {
+        Ok(overlay) => Some(overlay),
+        Err(e) => {
+            log::error!(target: "app", "failed to read merge overlay: {e:?}");
+            None
+        }
+    }
+}
+
 /// Process proxy groups with include-all field
 fn use_include_all_proxy_groups(mut config: Mapping) -> Mapping {
     // Collect all proxy names from proxies and proxy-providers first (before mutable borrow)
@@ -0,0 +1,288 @@
+//! 可调整顺序、可单独关闭的增强步骤
+//! 字段过滤、局域网共享等必须的处理不在其中，始终在固定位置执行
+//! Merge 与 Tun 负责 secret、端口、控制器与 TUN 设置，始终启用，分别固定在最前与最后
+use super::{
+    chain::{ChainItem, ChainTypeWrapper},
+    dns::{DnsOptions, use_dns, use_dns_ipv6},
+    field::HANDLE_FIELDS,
+    merge::{merge_config, strip_guarded_fields},
+    rule_inject::{RuleInjection, inject_rules},
+    script::RunnerManager,
+    tun::use_tun,
+};
+use crate::config::nyanpasu::ClashCore;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use specta::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum EnhanceStepKind {
+    /// 用 `config.yaml` 中受管理的字段覆盖配置
+    Merge,
+    /// 用户的合并配置（`merge-overlay.yaml`），深度合并到订阅配置上
+    Overlay,
+    /// 插入用户的规则
+    Rules,
+    /// 内建脚本
+    Script,
+    Tun,
+    /// 补全 DNS 配置，开启 TUN 或 DNS 增强时执行
+    Dns,
+}
+
+impl EnhanceStepKind {
+    /// 用户可以调整顺序与开关的步骤
+    const USER: [EnhanceStepKind; 4] = [Self::Overlay, Self::Rules, Self::Script, Self::Dns];
+
+    /// 不能关闭或调整位置的步骤，链中出现时被忽略
+    pub fn is_mandatory(self) -> bool {
+        matches!(self, Self::Merge | Self::Tun)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct EnhanceStep {
+    pub kind: EnhanceStepKind,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct EnhanceChain {
+    pub steps: Vec<EnhanceStep>,
+}
+
+impl Default for EnhanceChain {
+    fn default() -> Self {
+        Self {
+            steps: EnhanceStepKind::USER
+                .into_iter()
+                .map(|kind| EnhanceStep {
+                    kind,
+                    enabled: true,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 执行步骤所需的设置
+pub struct EnhanceContext<'a> {
+    pub clash_config: &'a Mapping,
+    pub overlay: Option<&'a Mapping>,
+    pub rule_injection: Option<&'a RuleInjection>,
+    pub clash_core: ClashCore,
+    pub enable_tun: bool,
+    pub enable_ipv6: bool,
+    /// 未开启 TUN 时也补全 DNS 配置
+    pub enable_dns: bool,
+    pub dns_nameservers: Option<&'a [String]>,
+    pub enable_builtin: bool,
+}
+
+impl EnhanceChain {
+    /// 去掉重复与固定的步骤，缺少的步骤按默认顺序启用并追加到末尾
+    pub fn normalize(mut self) -> Self {
+        let mut seen = Vec::with_capacity(self.steps.len());
+        self.steps.retain(|step| {
+            let keep = !step.kind.is_mandatory() && !seen.contains(&step.kind);
+            seen.push(step.kind);
+            keep
+        });
+        for kind in EnhanceStepKind::USER {
+            if !seen.contains(&kind) {
+                self.steps.push(EnhanceStep {
+                    kind,
+                    enabled: true,
+                });
+            }
+        }
+        self
+    }
+
+    pub async fn apply(&self, mut config: Mapping, ctx: &EnhanceContext<'_>) -> Mapping {
+        config = use_merge(config, ctx.clash_config);
+        for step in self
+            .steps
+            .iter()
+            .filter(|step| step.enabled && !step.kind.is_mandatory())
+        {
+            config = match step.kind {
+                EnhanceStepKind::Merge | EnhanceStepKind::Tun => config,
+                EnhanceStepKind::Overlay => match ctx.overlay {
+                    Some(overlay) => {
+                        // 受管理的字段由 Merge 步骤写入，不允许 overlay 覆盖
+                        let mut overlay = overlay.clone();
+                        let stripped = strip_guarded_fields(&mut overlay);
+                        if !stripped.is_empty() {
+                            log::warn!(target: "app", "merge overlay can not override managed fields, ignored: {stripped:?}");
+                        }
+                        merge_config(config, overlay)
+                    }
+                    None => config,
+                },
+                EnhanceStepKind::Rules => match ctx.rule_injection {
+                    Some(injection) => {
+                        inject_rules(config, injection.rules.clone(), injection.position)
+                    }
+                    None => config,
+                },
+                EnhanceStepKind::Script if ctx.enable_builtin => {
+                    use_builtin_scripts(config, ctx.clash_core).await
+                }
+                EnhanceStepKind::Script => config,
+                EnhanceStepKind::Dns if ctx.enable_tun || ctx.enable_dns => use_dns(
+                    config,
+                    &DnsOptions {
+                        enable_ipv6: ctx.enable_ipv6,
+                        nameservers: ctx.dns_nameservers,
+                    },
+                ),
+                EnhanceStepKind::Dns => config,
+            };
+        }
+        // DNS 步骤未执行或被关闭时也要遵循 IPv6 开关
+        let config = use_dns_ipv6(config, ctx.enable_ipv6);
+        use_tun(config, ctx.enable_tun, ctx.enable_ipv6, ctx.clash_core)
+    }
+}
+
+/// 只覆盖受管理的字段
+fn use_merge(mut config: Mapping, clash_config: &Mapping) -> Mapping {
+    clash_config
+        .iter()
+        .filter(|(k, _)| HANDLE_FIELDS.contains(&k.as_str().unwrap_or_default()))
+        .for_each(|(key, value)| {
+            config.insert(key.to_owned(), value.clone());
+        });
+    config
+}
+
+async fn use_builtin_scripts(mut config: Mapping, clash_core: ClashCore) -> Mapping {
+    let mut script_runner = RunnerManager::new();
+    for item in ChainItem::builtin()
+        .into_iter()
+        .filter(|(s, _)| s.contains(clash_core))
+        .map(|(_, c)| c)
+    {
+        log::debug!(target: "app", "run builtin script {}", item.uid);
+
+        if let ChainTypeWrapper::Script(script) = item.data {
+            let (res, _) = script_runner
+                .process_script(&script, config.to_owned())
+                .await;
+            match res {
+                Ok(res_config) => {
+                    config = res_config;
+                }
+                Err(err) => {
+                    log::error!(target: "app", "builtin script error `{err:?}`");
+                }
+            }
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(kind: EnhanceStepKind, enabled: bool) -> EnhanceStep {
+        EnhanceStep { kind, enabled }
+    }
+
+    #[test]
+    fn test_normalize_chain() {
+        let chain = EnhanceChain {
+            steps: vec![
+                step(EnhanceStepKind::Dns, false),
+                step(EnhanceStepKind::Tun, false),
+                step(EnhanceStepKind::Merge, false),
+                step(EnhanceStepKind::Rules, true),
+                step(EnhanceStepKind::Dns, true),
+            ],
+        }
+        .normalize();
+        // 固定的步骤不会出现在链中
+        assert_eq!(
+            chain.steps,
+            [
+                step(EnhanceStepKind::Dns, false),
+                step(EnhanceStepKind::Rules, true),
+                step(EnhanceStepKind::Overlay, true),
+                step(EnhanceStepKind::Script, true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_respects_order_and_toggles() {
+        let clash_config: Mapping = serde_yaml::from_str("mode: rule\nlog-level: info").unwrap();
+        let ctx = EnhanceContext {
+            clash_config: &clash_config,
+            overlay: None,
+            rule_injection: None,
+            clash_core: ClashCore::Mihomo,
+            enable_tun: false,
+            enable_ipv6: false,
+            enable_dns: false,
+            dns_nameservers: None,
+            enable_builtin: false,
+        };
+        let config: Mapping = serde_yaml::from_str("mode: global\nproxies: []").unwrap();
+
+        let chain = EnhanceChain::default();
+        let output = chain.apply(config.clone(), &ctx).await;
+        assert_eq!(output.get("mode").and_then(|v| v.as_str()), Some("rule"));
+        assert!(output.contains_key("tun"));
+        assert!(!output.contains_key("dns"));
+
+        // 关闭 Merge 与 Tun 不生效
+        let chain = EnhanceChain {
+            steps: vec![
+                step(EnhanceStepKind::Merge, false),
+                step(EnhanceStepKind::Tun, false),
+            ],
+        };
+        let output = chain.apply(config.clone(), &ctx).await;
+        assert_eq!(output.get("mode").and_then(|v| v.as_str()), Some("rule"));
+        assert!(output.contains_key("tun"));
+
+        // DNS 增强不依赖 TUN
+        let dns_ctx = EnhanceContext {
+            enable_dns: true,
+            ..ctx
+        };
+        let output = EnhanceChain::default()
+            .apply(config.clone(), &dns_ctx)
+            .await;
+        assert!(output.contains_key("dns"));
+        assert_eq!(
+            output["tun"].get("enable").and_then(|v| v.as_bool()),
+            Some(false)
+        );
+
+        let overlay: Mapping = serde_yaml::from_str("proxies: [a]\nmode: direct").unwrap();
+        let ctx = EnhanceContext {
+            overlay: Some(&overlay),
+            ..ctx
+        };
+        let chain = EnhanceChain {
+            steps: vec![
+                step(EnhanceStepKind::Merge, true),
+                step(EnhanceStepKind::Overlay, true),
+            ],
+        };
+        let output = chain.apply(config, &ctx).await;
+        // overlay 不能覆盖受管理的字段
+        assert_eq!(output.get("mode").and_then(|v| v.as_str()), Some("rule"));
+        assert_eq!(
+            output
+                .get("proxies")
+                .and_then(|v| v.as_sequence())
+                .map(Vec::len),
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! 按核心加载的增强插件，位于 `{config_dir}/plugins/{core}/*.yaml`
+//! 每个插件是一段配置片段，在增强步骤之后深度合并到配置中，合并规则与合并配置相同
+//! 停用的插件改名为 `*.yaml.disabled`，不需要额外保存状态
+use super::{
+    field::{DEFAULT_FIELDS, HANDLE_FIELDS, OTHERS_FIELDS},
+    merge::{PREPEND_DIRECTIVE, REPLACE_DIRECTIVE, merge_config},
+    validate::{ValidationSeverity, validate_proxy_definitions},
+};
+use crate::{config::nyanpasu::ClashCore, core::handle::Handle, log_err, utils::dirs};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::path::{Path, PathBuf};
+
+const PLUGIN_EXT: &str = "yaml";
+const DISABLED_SUFFIX: &str = ".disabled";
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct PluginInfo {
+    /// 不含扩展名的文件名
+    pub name: String,
+    pub core: ClashCore,
+    pub path: PathBuf,
+    pub enabled: bool,
+    /// 读取或校验失败的原因，失败的插件不会被合并
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PluginLoaded {
+    name: String,
+    core: String,
+}
+
+fn core_dir(base: &Path, core: ClashCore) -> PathBuf {
+    base.join(core.to_string())
+}
+
+/// 插件名只能是单个文件名，防止访问目录外的文件
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\'])
+        || Path::new(name).components().count() != 1
+    {
+        bail!("invalid plugin name `{name}`");
+    }
+    Ok(())
+}
+
+fn plugin_path(base: &Path, core: ClashCore, name: &str, enabled: bool) -> PathBuf {
+    let file = match enabled {
+        true => format!("{name}.{PLUGIN_EXT}"),
+        false => format!("{name}.{PLUGIN_EXT}{DISABLED_SUFFIX}"),
+    };
+    core_dir(base, core).join(file)
+}
+
+/// 只允许核心认识的顶层字段，端口、secret 等由 nyanpasu 管理的字段不能被插件覆盖
+fn validate_fragment(fragment: &Mapping, core: ClashCore) -> Result<()> {
+    for key in fragment.keys() {
+        let Some(key) = key.as_str() else {
+            bail!("top-level keys should be strings");
+        };
+        if matches!(key, REPLACE_DIRECTIVE | PREPEND_DIRECTIVE) {
+            continue;
+        }
+        if HANDLE_FIELDS.contains(&key) {
+            bail!("`{key}` is managed by nyanpasu and cannot be set by plugins");
+        }
+        if !DEFAULT_FIELDS.contains(&key) && !OTHERS_FIELDS.contains(&key) {
+            bail!("`{key}` is not a known field");
+        }
+    }
+    if let Some(proxies) = fragment.get("proxies") {
+        let Some(proxies) = proxies.as_sequence() else {
+            bail!("`proxies` should be a sequence");
+        };
+        if let Some(e) = validate_proxy_definitions(proxies, core)
+            .into_iter()
+            .find(|e| e.severity == ValidationSeverity::Error)
+        {
+            bail!(
+                "proxy `{}`{}: {}",
+                e.proxy_name,
+                e.field.map(|f| format!(" field `{f}`")).unwrap_or_default(),
+                e.error
+            );
+        }
+    }
+    Ok(())
+}
+
+fn read_fragment(path: &Path, core: ClashCore) -> Result<Mapping> {
+    let content = std::fs::read_to_string(path)?;
+    let fragment = match serde_yaml::from_str::<Value>(&content)? {
+        Value::Mapping(fragment) => fragment,
+        Value::Null => Mapping::new(),
+        _ => bail!("plugin should be a mapping"),
+    };
+    validate_fragment(&fragment, core)?;
+    Ok(fragment)
+}
+
+fn list_in(base: &Path, core: ClashCore) -> Result<Vec<PluginInfo>> {
+    let dir = core_dir(base, core);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut plugins = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let (stem, enabled) = match file_name.strip_suffix(DISABLED_SUFFIX) {
+            Some(stem) => (stem, false),
+            None => (file_name, true),
+        };
+        let Some(name) = stem.strip_suffix(&format!(".{PLUGIN_EXT}")) else {
+            continue;
+        };
+        if check_name(name).is_err() || !path.is_file() {
+            continue;
+        }
+        let error = read_fragment(&path, core).err().map(|e| format!("{e:#}"));
+        plugins.push(PluginInfo {
+            name: name.to_string(),
+            core,
+            path,
+            enabled,
+            error,
+        });
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(plugins)
+}
+
+/// 按名称顺序读取启用的插件，无效的插件记录错误后跳过
+fn load_in(base: &Path, core: ClashCore) -> Result<Vec<(String, Mapping)>> {
+    let mut fragments = Vec::new();
+    for plugin in list_in(base, core)? {
+        if !plugin.enabled {
+            continue;
+        }
+        match read_fragment(&plugin.path, core) {
+            Ok(fragment) => fragments.push((plugin.name, fragment)),
+            Err(e) => {
+                log::error!(target: "app", "skip invalid plugin `{}`: {e:?}", plugin.path.display())
+            }
+        }
+    }
+    Ok(fragments)
+}
+
+fn set_enabled_in(base: &Path, core: ClashCore, name: &str, enabled: bool) -> Result<()> {
+    check_name(name)?;
+    let target = plugin_path(base, core, name, enabled);
+    if target.exists() {
+        return Ok(());
+    }
+    let source = plugin_path(base, core, name, !enabled);
+    if !source.exists() {
+        bail!("plugin `{name}` for {core} not found");
+    }
+    std::fs::rename(&source, &target)
+        .with_context(|| format!("failed to rename {}", source.display()))
+}
+
+pub fn list_core_plugins(core: ClashCore) -> Result<Vec<PluginInfo>> {
+    list_in(&dirs::core_plugins_dir()?, core)
+}
+
+/// 读取并校验核心启用的插件，每加载一个插件推送 `plugin-loaded` 事件
+pub fn load_core_plugins(core: ClashCore) -> Result<Vec<Mapping>> {
+    let fragments = load_in(&dirs::core_plugins_dir()?, core)?;
+    Ok(fragments
+        .into_iter()
+        .map(|(name, fragment)| {
+            log_err!(Handle::emit(
+                "plugin-loaded",
+                PluginLoaded {
+                    name,
+                    core: core.to_string(),
+                }
+            ));
+            fragment
+        })
+        .collect())
+}
+
+pub fn set_plugin_enabled(core: ClashCore, name: &str, enabled: bool) -> Result<()> {
+    set_enabled_in(&dirs::core_plugins_dir()?, core, name, enabled)
+}
+
+pub fn use_core_plugins(config: Mapping, core: ClashCore) -> Mapping {
+    match load_core_plugins(core) {
+        Ok(fragments) => fragments.into_iter().fold(config, merge_config),
+        Err(e) => {
+            log::error!(target: "app", "failed to load plugins for {core}: {e:?}");
+            config
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(base: &Path, core: ClashCore, file: &str, content: &str) {
+        let dir = core_dir(base, core);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(file), content).unwrap();
+    }
+
+    #[test]
+    fn test_load_plugins() {
+        let base = tempfile::tempdir().unwrap();
+        let base = base.path();
+        write(
+            base,
+            ClashCore::Mihomo,
+            "b-sniffer.yaml",
+            "sniffer:\n  enable: true\n",
+        );
+        write(
+            base,
+            ClashCore::Mihomo,
+            "a-rules.yaml",
+            "rules: [MATCH,DIRECT]\n",
+        );
+        write(base, ClashCore::Mihomo, "port.yaml", "mixed-port: 1234\n");
+        write(
+            base,
+            ClashCore::Mihomo,
+            "off.yaml.disabled",
+            "tun:\n  enable: true\n",
+        );
+        write(base, ClashCore::Mihomo, "notes.txt", "ignored");
+        write(base, ClashCore::ClashPremium, "other.yaml", "hosts: {}\n");
+
+        let plugins = list_in(base, ClashCore::Mihomo).unwrap();
+        assert_eq!(
+            plugins
+                .iter()
+                .map(|p| (p.name.as_str(), p.enabled, p.error.is_some()))
+                .collect::<Vec<_>>(),
+            [
+                ("a-rules", true, false),
+                ("b-sniffer", true, false),
+                ("off", false, false),
+                ("port", true, true),
+            ]
+        );
+
+        let fragments = load_in(base, ClashCore::Mihomo).unwrap();
+        assert_eq!(
+            fragments
+                .iter()
+                .map(|(n, _)| n.as_str())
+                .collect::<Vec<_>>(),
+            ["a-rules", "b-sniffer"]
+        );
+        let config: Mapping = serde_yaml::from_str("rules: [DOMAIN,a.com,DIRECT]").unwrap();
+        let config = fragments
+            .into_iter()
+            .map(|(_, f)| f)
+            .fold(config, merge_config);
+        assert_eq!(config.get("rules").unwrap().as_sequence().unwrap().len(), 2);
+        assert_eq!(config.get("sniffer").unwrap()["enable"], Value::Bool(true));
+    }
+
+    #[test]
+    fn test_validate_fragment() {
+        let fragment = |s: &str| serde_yaml::from_str::<Mapping>(s).unwrap();
+        assert!(validate_fragment(&fragment("dns: {enable: true}"), ClashCore::Mihomo).is_ok());
+        assert!(validate_fragment(&fragment("secret: x"), ClashCore::Mihomo).is_err());
+        assert!(validate_fragment(&fragment("unknown-key: 1"), ClashCore::Mihomo).is_err());
+        assert!(
+            validate_fragment(
+                &fragment("proxies: [{name: a, type: unknown, server: s, port: 1}]"),
+                ClashCore::Mihomo
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_toggle_plugin() {
+        let base = tempfile::tempdir().unwrap();
+        let base = base.path();
+        write(base, ClashCore::Mihomo, "dns.yaml", "dns: {}\n");
+
+        set_enabled_in(base, ClashCore::Mihomo, "dns", false).unwrap();
+        assert!(plugin_path(base, ClashCore::Mihomo, "dns", false).exists());
+        assert!(load_in(base, ClashCore::Mihomo).unwrap().is_empty());
+        // 重复操作不报错
+        set_enabled_in(base, ClashCore::Mihomo, "dns", false).unwrap();
+        set_enabled_in(base, ClashCore::Mihomo, "dns", true).unwrap();
+        assert_eq!(load_in(base, ClashCore::Mihomo).unwrap().len(), 1);
+
+        assert!(set_enabled_in(base, ClashCore::Mihomo, "missing", true).is_err());
+        assert!(set_enabled_in(base, ClashCore::Mihomo, "../dns", true).is_err());
+    }
+}
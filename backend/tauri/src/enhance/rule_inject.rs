@@ -0,0 +1,240 @@
+//! 不借助脚本，将用户的规则插入到合并后配置的 `rules` 中
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::collections::HashSet;
+
+/// 只有类型与策略两段的规则
+const FINAL_RULE_TYPES: &[&str] = &["MATCH", "FINAL"];
+
+/// 逻辑规则的条件中含有逗号，不检查段数
+const LOGIC_RULE_TYPES: &[&str] = &["AND", "OR", "NOT"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum RulePosition {
+    /// 插入到订阅的规则之前
+    #[default]
+    Before,
+    /// 插入到订阅的规则之后，兜底规则（`MATCH`）之前
+    After,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct RuleInjection {
+    pub rules: Vec<String>,
+    #[serde(default)]
+    pub position: RulePosition,
+}
+
+impl RuleInjection {
+    pub fn validate(&self) -> Result<()> {
+        let errors = self
+            .rules
+            .iter()
+            .filter_map(|rule| validate_rule(rule).err())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            bail!("invalid rules: {}", errors.join("; "));
+        }
+        Ok(())
+    }
+}
+
+/// 去掉逗号两侧的空白，用于比较与写入
+fn normalize_rule(rule: &str) -> String {
+    rule.split(',').map(str::trim).collect::<Vec<_>>().join(",")
+}
+
+/// 粗略检查规则是否符合 `TYPE,value,target`
+pub fn validate_rule(rule: &str) -> Result<()> {
+    let rule = normalize_rule(rule);
+    let parts = rule.split(',').collect::<Vec<_>>();
+    let rule_type = parts[0];
+    if rule_type.is_empty()
+        || !rule_type
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-')
+    {
+        bail!("`{rule}`: invalid rule type");
+    }
+    let valid = if FINAL_RULE_TYPES.contains(&rule_type) {
+        parts.len() == 2
+    } else if LOGIC_RULE_TYPES.contains(&rule_type) {
+        parts.len() >= 3 && parts[1].starts_with('(')
+    } else {
+        // 部分规则可追加 `no-resolve` 等参数
+        parts.len() >= 3
+    };
+    if !valid || parts.iter().any(|part| part.is_empty()) {
+        bail!("`{rule}`: expected `TYPE,value,target`");
+    }
+    Ok(())
+}
+
+fn is_final_rule(rule: &str) -> bool {
+    rule.split(',')
+        .next()
+        .is_some_and(|rule_type| FINAL_RULE_TYPES.contains(&rule_type.trim()))
+}
+
+/// 插入规则并去重，重复的规则保留靠前的一条，不合法的规则会被跳过
+pub fn inject_rules(mut config: Mapping, rules: Vec<String>, position: RulePosition) -> Mapping {
+    let injected = rules
+        .iter()
+        .filter(|rule| match validate_rule(rule) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!(target: "app", "skip injected rule {e}");
+                false
+            }
+        })
+        .map(|rule| Value::from(normalize_rule(rule)))
+        .collect::<Vec<_>>();
+    if injected.is_empty() {
+        return config;
+    }
+    let mut existing = config
+        .get("rules")
+        .and_then(|rules| rules.as_sequence())
+        .cloned()
+        .unwrap_or_default();
+    let merged = match position {
+        RulePosition::Before => injected.into_iter().chain(existing).collect::<Vec<_>>(),
+        RulePosition::After => {
+            let index = existing
+                .iter()
+                .position(|rule| rule.as_str().is_some_and(is_final_rule))
+                .unwrap_or(existing.len());
+            existing.splice(index..index, injected);
+            existing
+        }
+    };
+    let mut seen = HashSet::new();
+    let rules = merged
+        .into_iter()
+        .filter(|rule| match rule.as_str() {
+            Some(rule) => seen.insert(normalize_rule(rule)),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    config.insert("rules".into(), Value::Sequence(rules));
+    config
+}
+
+/// 全局排除的域名直连，插入到最前面以优先于其他规则
+pub fn use_global_excludes(config: Mapping, excludes: &[String]) -> Mapping {
+    let rules = excludes
+        .iter()
+        .map(|domain| format!("DOMAIN-SUFFIX,{domain},DIRECT"))
+        .collect();
+    inject_rules(config, rules, RulePosition::Before)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Mapping {
+        serde_yaml::from_str(
+            r"
+            rules:
+              - DOMAIN-SUFFIX,google.com,Proxy
+              - GEOIP,CN,DIRECT
+              - MATCH,Proxy
+            ",
+        )
+        .unwrap()
+    }
+
+    fn rules(config: &Mapping) -> Vec<&str> {
+        config["rules"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|rule| rule.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_global_excludes_take_precedence() {
+        let config = inject_rules(
+            config(),
+            vec!["DOMAIN,example.com,Proxy".into()],
+            RulePosition::Before,
+        );
+        let config = use_global_excludes(config, &["example.com".into(), "google.com".into()]);
+        assert_eq!(
+            rules(&config)[..3],
+            [
+                "DOMAIN-SUFFIX,example.com,DIRECT",
+                "DOMAIN-SUFFIX,google.com,DIRECT",
+                "DOMAIN,example.com,Proxy",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_rule() {
+        assert!(validate_rule("DOMAIN-SUFFIX,example.com,DIRECT").is_ok());
+        assert!(validate_rule("IP-CIDR, 10.0.0.0/8 , DIRECT, no-resolve").is_ok());
+        assert!(validate_rule("MATCH,Proxy").is_ok());
+        assert!(validate_rule("AND,((NETWORK,UDP),(DST-PORT,443)),REJECT").is_ok());
+        assert!(validate_rule("DOMAIN,example.com").is_err());
+        assert!(validate_rule("domain,example.com,DIRECT").is_err());
+        assert!(validate_rule("DOMAIN,,DIRECT").is_err());
+        assert!(validate_rule("MATCH,Proxy,DIRECT").is_err());
+    }
+
+    #[test]
+    fn test_inject_before() {
+        let config = inject_rules(
+            config(),
+            vec![
+                "DOMAIN,example.com,DIRECT".into(),
+                "GEOIP, CN, DIRECT".into(),
+                "invalid".into(),
+            ],
+            RulePosition::Before,
+        );
+        assert_eq!(
+            rules(&config),
+            [
+                "DOMAIN,example.com,DIRECT",
+                "GEOIP,CN,DIRECT",
+                "DOMAIN-SUFFIX,google.com,Proxy",
+                "MATCH,Proxy",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inject_after() {
+        let config = inject_rules(
+            config(),
+            vec![
+                "DOMAIN,example.com,DIRECT".into(),
+                "DOMAIN-SUFFIX,google.com,Proxy".into(),
+            ],
+            RulePosition::After,
+        );
+        assert_eq!(
+            rules(&config),
+            [
+                "DOMAIN-SUFFIX,google.com,Proxy",
+                "GEOIP,CN,DIRECT",
+                "DOMAIN,example.com,DIRECT",
+                "MATCH,Proxy",
+            ]
+        );
+
+        let config = inject_rules(
+            Mapping::new(),
+            vec!["DOMAIN,example.com,DIRECT".into()],
+            RulePosition::After,
+        );
+        assert_eq!(rules(&config), ["DOMAIN,example.com,DIRECT"]);
+    }
+}
@@ -1,8 +1,13 @@
+use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
+use specta::Type;
 
-use crate::config::{
-    Config,
-    nyanpasu::{ClashCore, TunStack},
+use crate::{
+    config::{
+        Config,
+        nyanpasu::{ClashCore, TunStack},
+    },
+    core::clash::core_info::{CoreFeature, CoreFlavor, CoreInfo},
 };
 
 macro_rules! revise {
@@ -23,7 +28,7 @@ macro_rules! append {
 }
 
 #[tracing_attributes::instrument(skip(config))]
-pub fn use_tun(mut config: Mapping, enable: bool) -> Mapping {
+pub fn use_tun(mut config: Mapping, enable: bool, enable_ipv6: bool, core: ClashCore) -> Mapping {
     let tun_key = Value::from("tun");
     let tun_val = config.get(&tun_key);
     tracing::debug!("tun_val: {:?}, enable: {}", tun_val, enable);
@@ -36,6 +41,7 @@ pub fn use_tun(mut config: Mapping, enable: bool) -> Mapping {
 
     // Always set enable field to prevent parsing errors
     revise!(tun_val, "enable", enable);
+    revise!(tun_val, "ipv6", enable_ipv6);
 
     if !enable {
         // For disabled TUN, still provide minimal valid config
@@ -44,14 +50,6 @@ pub fn use_tun(mut config: Mapping, enable: bool) -> Mapping {
     }
 
     // TUN is enabled, configure for supported cores
-    let core = {
-        *Config::verge()
-            .latest()
-            .clash_core
-            .as_ref()
-            .unwrap_or(&ClashCore::default())
-    };
-
     let mut tun_stack = {
         *Config::verge()
             .latest()
@@ -59,48 +57,320 @@ pub fn use_tun(mut config: Mapping, enable: bool) -> Mapping {
             .as_ref()
             .unwrap_or(&TunStack::default())
     };
-    if core == ClashCore::ClashPremium && tun_stack == TunStack::Mixed {
+    // 优先使用探测到的核心功能，尚未探测时按配置的核心判断
+    let mixed_stack_supported = CoreInfo::cached(&core)
+        .map(|info| info.supports(CoreFeature::TunMixedStack))
+        .unwrap_or(core != ClashCore::ClashPremium);
+    if tun_stack == TunStack::Mixed && !mixed_stack_supported {
         tun_stack = TunStack::Gvisor;
     }
-    append!(tun_val, "stack", AsRef::<str>::as_ref(&tun_stack));
+    if !ClashRsCompatibilityLayer::applies_to(&core) {
+        append!(tun_val, "stack", AsRef::<str>::as_ref(&tun_stack));
+    }
     append!(tun_val, "dns-hijack", vec!["any:53"]);
     revise!(tun_val, "auto-route", true);
     append!(tun_val, "auto-detect-interface", true);
 
     revise!(config, "tun", tun_val);
-    use_dns_for_tun(config)
+
+    // 严格路由依赖核心标记自身的出站流量，否则会形成回环
+    #[cfg(target_os = "linux")]
+    if Config::verge().latest().strict_routing.unwrap_or(false) {
+        revise!(
+            config,
+            "routing-mark",
+            crate::utils::platform::strict_routing::ROUTE_MARK
+        );
+    }
+    config
 }
 
-fn use_dns_for_tun(mut config: Mapping) -> Mapping {
-    let dns_key = Value::from("dns");
-    let dns_val = config.get(&dns_key);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationAction {
+    Removed,
+    Renamed,
+    ValueTransformed,
+}
 
-    let mut dns_val = dns_val.map_or(Mapping::new(), |val| {
-        val.as_mapping().cloned().unwrap_or(Mapping::new())
-    });
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct TranslationWarning {
+    /// 字段路径，如 `proxies[HK 01].reality-opts`
+    pub field: String,
+    pub action: TranslationAction,
+    pub suggestion: Option<String>,
+}
 
-    // 开启tun将同时开启dns
-    revise!(dns_val, "enable", true);
-
-    append!(dns_val, "enhanced-mode", "fake-ip");
-    append!(dns_val, "fake-ip-range", "198.18.0.1/16");
-    append!(
-        dns_val,
-        "nameserver",
-        vec!["114.114.114.114", "223.5.5.5", "8.8.8.8"]
-    );
-    append!(dns_val, "fallback", vec![] as Vec<&str>);
-
-    #[cfg(target_os = "windows")]
-    append!(
-        dns_val,
-        "fake-ip-filter",
-        vec![
-            "dns.msftncsi.com",
-            "www.msftncsi.com",
-            "www.msftconnecttest.com"
-        ]
-    );
-    revise!(config, "dns", dns_val);
-    config
+/// clash-rs 不支持的顶层字段
+const META_ONLY_KEYS: &[&str] = &[
+    "geodata-mode",
+    "geodata-loader",
+    "geox-url",
+    "geo-auto-update",
+    "geo-update-interval",
+    "sniffer",
+    "find-process-mode",
+    "global-client-fingerprint",
+    "unified-delay",
+    "tcp-concurrent",
+    "keep-alive-interval",
+];
+
+/// clash-rs 不支持的节点字段及提示
+const META_ONLY_PROXY_KEYS: &[(&str, &str)] = &[
+    (
+        "client-fingerprint",
+        "clash-rs does not support uTLS fingerprints",
+    ),
+    ("smux", "clash-rs does not support sing-mux"),
+];
+
+/// 将 mihomo 特有的字段转换为 clash-rs 的写法，无法转换的字段移除
+/// 配置的核心名称可能指向 clash-rs 二进制，按探测到的核心类型判断是否需要转换
+pub struct ClashRsCompatibilityLayer {
+    warnings: Vec<TranslationWarning>,
+}
+
+impl ClashRsCompatibilityLayer {
+    pub fn applies_to(core: &ClashCore) -> bool {
+        CoreFlavor::of(core) == CoreFlavor::ClashRs
+    }
+
+    fn warn(&mut self, field: String, action: TranslationAction, suggestion: Option<&str>) {
+        tracing::warn!("clash-rs compatibility: {action:?} `{field}`");
+        self.warnings.push(TranslationWarning {
+            field,
+            action,
+            suggestion: suggestion.map(str::to_string),
+        });
+    }
+
+    /// 返回 false 时节点无法在 clash-rs 中使用，需要移除
+    fn translate_proxy(&mut self, proxy: &mut Mapping) -> bool {
+        let name = proxy
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        // 去掉 REALITY 后节点会以普通 TLS 连接，直接移除节点
+        if proxy.contains_key("reality-opts") {
+            self.warn(
+                format!("proxies[{name}]"),
+                TranslationAction::Removed,
+                Some("clash-rs does not support REALITY, switch to mihomo to use this node"),
+            );
+            return false;
+        }
+        for (key, suggestion) in META_ONLY_PROXY_KEYS {
+            if proxy.remove(*key).is_some() {
+                self.warn(
+                    format!("proxies[{name}].{key}"),
+                    TranslationAction::Removed,
+                    Some(suggestion),
+                );
+            }
+        }
+        // mihomo 的 `shadow-tls-opts` 对应 clash-rs 的 shadow-tls 插件
+        if let Some(opts) = proxy.remove("shadow-tls-opts") {
+            if proxy.contains_key("plugin") {
+                self.warn(
+                    format!("proxies[{name}].shadow-tls-opts"),
+                    TranslationAction::Removed,
+                    Some("the node already uses another plugin"),
+                );
+            } else {
+                proxy.insert("plugin".into(), "shadow-tls".into());
+                proxy.insert("plugin-opts".into(), opts);
+                self.warn(
+                    format!("proxies[{name}].shadow-tls-opts"),
+                    TranslationAction::Renamed,
+                    Some("moved to `plugin: shadow-tls` with `plugin-opts`"),
+                );
+            }
+        }
+        true
+    }
+
+    /// 从策略组中去掉已移除的节点
+    fn remove_from_group(&mut self, group: &mut Mapping, removed: &[String]) {
+        let name = group
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let Some(proxies) = group.get_mut("proxies").and_then(Value::as_sequence_mut) else {
+            return;
+        };
+        proxies.retain(|proxy| {
+            let Some(proxy) = proxy
+                .as_str()
+                .filter(|proxy| removed.iter().any(|r| r == proxy))
+            else {
+                return true;
+            };
+            self.warn(
+                format!("proxy-groups[{name}].proxies[{proxy}]"),
+                TranslationAction::Removed,
+                None,
+            );
+            false
+        });
+    }
+
+    fn translate_tun(&mut self, tun: &mut Mapping) {
+        // clash-rs 使用 `device-id: dev://{name}` 指定网卡
+        if let Some(device) = tun.remove("device") {
+            let device = device.as_str().unwrap_or_default();
+            if !device.is_empty() {
+                tun.insert("device-id".into(), format!("dev://{device}").into());
+            }
+            self.warn(
+                "tun.device".to_string(),
+                TranslationAction::ValueTransformed,
+                Some("converted to `device-id`"),
+            );
+        }
+        if tun.remove("stack").is_some() {
+            self.warn(
+                "tun.stack".to_string(),
+                TranslationAction::Removed,
+                Some("clash-rs does not support choosing the TUN stack"),
+            );
+        }
+    }
+
+    pub fn translate(mut config: Mapping) -> (Mapping, Vec<TranslationWarning>) {
+        let mut layer = Self {
+            warnings: Vec::new(),
+        };
+        for key in META_ONLY_KEYS {
+            if config.remove(*key).is_some() {
+                layer.warn(key.to_string(), TranslationAction::Removed, None);
+            }
+        }
+        let mut removed = Vec::new();
+        if let Some(proxies) = config.get_mut("proxies").and_then(Value::as_sequence_mut) {
+            proxies.retain_mut(|proxy| match proxy.as_mapping_mut() {
+                Some(proxy) if !layer.translate_proxy(proxy) => {
+                    removed.extend(
+                        proxy
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .map(str::to_string),
+                    );
+                    false
+                }
+                _ => true,
+            });
+        }
+        if !removed.is_empty()
+            && let Some(groups) = config
+                .get_mut("proxy-groups")
+                .and_then(Value::as_sequence_mut)
+        {
+            for group in groups.iter_mut().filter_map(Value::as_mapping_mut) {
+                layer.remove_from_group(group, &removed);
+            }
+        }
+        if let Some(tun) = config.get_mut("tun").and_then(Value::as_mapping_mut) {
+            layer.translate_tun(tun);
+        }
+        (config, layer.warnings)
+    }
+}
+
+pub fn translate_config_for_clash_rs(config: Mapping) -> (Mapping, Vec<TranslationWarning>) {
+    ClashRsCompatibilityLayer::translate(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_config_for_clash_rs() {
+        let config: Mapping = serde_yaml::from_str(
+            r#"
+mixed-port: 7890
+sniffer:
+  enable: true
+tun:
+  enable: true
+  stack: mixed
+  device: Meta
+proxies:
+  - name: reality
+    type: vless
+    server: a.example
+    client-fingerprint: chrome
+    reality-opts:
+      public-key: xxx
+  - name: stls
+    type: ss
+    server: b.example
+    shadow-tls-opts:
+      host: cloud.tencent.com
+      password: pass
+  - name: plain
+    type: ss
+    server: c.example
+proxy-groups:
+  - name: select
+    type: select
+    proxies: [reality, stls, plain]
+"#,
+        )
+        .unwrap();
+        let (config, warnings) = translate_config_for_clash_rs(config);
+        assert!(!config.contains_key("sniffer"));
+        assert_eq!(config.get("mixed-port"), Some(&Value::from(7890)));
+
+        // REALITY 节点连同策略组中的引用一起移除
+        let proxies = config["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[0]["plugin"], Value::from("shadow-tls"));
+        assert_eq!(proxies[0]["plugin-opts"]["password"], Value::from("pass"));
+        assert_eq!(proxies[1].as_mapping().unwrap().len(), 3);
+        assert_eq!(
+            config["proxy-groups"][0]["proxies"],
+            Value::from(vec!["stls", "plain"])
+        );
+
+        let tun = config["tun"].as_mapping().unwrap();
+        assert_eq!(tun.get("device-id"), Some(&Value::from("dev://Meta")));
+        assert!(!tun.contains_key("stack"));
+
+        let fields = warnings
+            .iter()
+            .map(|w| (w.field.as_str(), w.action))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("sniffer", TranslationAction::Removed),
+                ("proxies[reality]", TranslationAction::Removed),
+                ("proxies[stls].shadow-tls-opts", TranslationAction::Renamed),
+                (
+                    "proxy-groups[select].proxies[reality]",
+                    TranslationAction::Removed
+                ),
+                ("tun.device", TranslationAction::ValueTransformed),
+                ("tun.stack", TranslationAction::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ipv6_disabled() {
+        let config = use_tun(Mapping::new(), false, false, ClashCore::Mihomo);
+        let tun = config.get("tun").and_then(|v| v.as_mapping()).unwrap();
+        assert_eq!(tun.get("ipv6"), Some(&Value::from(false)));
+    }
+
+    #[test]
+    fn test_ipv6_enabled() {
+        let config = use_tun(Mapping::new(), false, true, ClashCore::Mihomo);
+        let tun = config.get("tun").and_then(|v| v.as_mapping()).unwrap();
+        assert_eq!(tun.get("ipv6"), Some(&Value::from(true)));
+    }
 }
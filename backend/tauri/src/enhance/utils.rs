@@ -1,8 +1,11 @@
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use serde_yaml::Mapping;
+use serde_yaml::{Mapping, Value};
 
-use crate::config::profile::{item_type::ProfileUid, profiles::Profiles};
+use crate::{
+    config::profile::{item_type::ProfileUid, profiles::Profiles},
+    core::activation::StageTimer,
+};
 
 use super::{ChainItem, ChainTypeWrapper, RunnerManager, use_merge};
 use parking_lot::Mutex;
@@ -73,15 +76,17 @@ pub fn merge_profiles<T: Borrow<String>>(mappings: IndexMap<T, Mapping>) -> Mapp
     mappings
         .into_iter()
         .enumerate()
-        .fold(Mapping::new(), |mut acc, (idx, (_key, value))| {
+        .fold(Mapping::new(), |mut acc, (idx, (_key, mut value))| {
             // full extend the first one, others just extend proxies
             // TODO: custom merge logic
             // TODO: add meta info
             if idx == 0 {
                 acc.extend(value);
-            } else {
-                let proxies = value.get("proxies").unwrap().as_sequence().unwrap().clone();
-                let acc_proxies = acc.get_mut("proxies").unwrap().as_sequence_mut().unwrap();
+            } else if let Some(Value::Sequence(proxies)) = value.remove("proxies")
+                && let Some(acc_proxies) = acc
+                    .get_mut("proxies")
+                    .and_then(|proxies| proxies.as_sequence_mut())
+            {
                 acc_proxies.extend(proxies);
             }
             acc
@@ -97,10 +102,12 @@ pub async fn process_chain(
 
     let mut script_runner = RunnerManager::new();
     for item in nodes.iter() {
+        let timer = StageTimer::start();
+        let mut error = None;
         match &item.data {
             ChainTypeWrapper::Merge(merge) => {
                 let mut logs = vec![];
-                let (res, process_logs) = use_merge(merge, config.clone());
+                let (res, process_logs) = use_merge(merge, std::mem::take(&mut config));
                 config = res.unwrap();
                 logs.extend(process_logs);
                 result_map.insert(item.uid.to_string(), logs);
@@ -115,12 +122,16 @@ pub async fn process_chain(
                     Ok(res_config) => {
                         config = res_config;
                     }
-                    Err(err) => logs.error(err.to_string()),
+                    Err(err) => {
+                        error = Some(err.to_string());
+                        logs.error(err.to_string());
+                    }
                 }
                 // TODO: 这里添加对 field 的检查，触发 WARN 日记。此外，需要对 Merge 的结果进行检查？
                 result_map.insert(item.uid.to_string(), logs);
             }
         }
+        timer.finish(format!("chain:{}", item.uid), error);
     }
 
     (config, result_map)
@@ -0,0 +1,283 @@
+//! 按核心的 schema 检查 `proxies` 中每个节点的定义
+//! schema 只使用 `required`、`properties`、`type`、`enum`、`minimum`、`maximum`，
+//! 按 `type` 区分的字段放在 `x-types` 中；与核心一致，标量之间允许弱类型转换
+//! 因此不使用通用的 JSON Schema 校验器：它会拒绝 `port: "443"` 这类核心可以加载的写法，
+//! 也无法按字段区分 `x-severity`
+use crate::config::nyanpasu::ClashCore;
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use specta::Type;
+
+static CLASH_SCHEMA: &[u8] = include_bytes!("schemas/clash.json");
+static MIHOMO_SCHEMA: &[u8] = include_bytes!("schemas/mihomo.json");
+static MIHOMO_ALPHA_SCHEMA: &[u8] = include_bytes!("schemas/mihomo-alpha.json");
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationSeverity {
+    /// 核心无法加载，阻止启动
+    #[default]
+    Error,
+    /// 核心可以加载，但节点可能不可用
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct ProxyValidationError {
+    /// 缺少名称时为节点的序号
+    pub proxy_name: String,
+    /// 针对整个节点时为空
+    pub field: Option<String>,
+    pub error: String,
+    pub severity: ValidationSeverity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FieldType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+#[derive(Debug, Deserialize)]
+struct FieldSchema {
+    #[serde(rename = "type")]
+    ty: Option<FieldType>,
+    #[serde(rename = "enum")]
+    values: Option<Vec<serde_json::Value>>,
+    minimum: Option<i64>,
+    maximum: Option<i64>,
+    #[serde(default, rename = "x-severity")]
+    severity: ValidationSeverity,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProxySchema {
+    #[serde(default)]
+    required: Vec<String>,
+    #[serde(default)]
+    properties: IndexMap<String, FieldSchema>,
+    #[serde(default, rename = "x-types")]
+    types: IndexMap<String, ProxySchema>,
+}
+
+fn parse_schema(schema: &[u8]) -> ProxySchema {
+    serde_json::from_slice(schema).expect("invalid embedded proxy schema")
+}
+
+fn schema_of(core: ClashCore) -> &'static ProxySchema {
+    static CLASH: Lazy<ProxySchema> = Lazy::new(|| parse_schema(CLASH_SCHEMA));
+    static MIHOMO: Lazy<ProxySchema> = Lazy::new(|| parse_schema(MIHOMO_SCHEMA));
+    static MIHOMO_ALPHA: Lazy<ProxySchema> = Lazy::new(|| parse_schema(MIHOMO_ALPHA_SCHEMA));
+    match core {
+        ClashCore::ClashPremium => &CLASH,
+        ClashCore::Mihomo => &MIHOMO,
+        ClashCore::MihomoAlpha => &MIHOMO_ALPHA,
+    }
+}
+
+fn scalar_as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn matches_type(value: &Value, ty: FieldType) -> bool {
+    match ty {
+        FieldType::String => matches!(value, Value::String(_) | Value::Number(_) | Value::Bool(_)),
+        FieldType::Integer => scalar_as_i64(value).is_some(),
+        FieldType::Number => match value {
+            Value::Number(_) => true,
+            Value::String(s) => s.trim().parse::<f64>().is_ok(),
+            _ => false,
+        },
+        FieldType::Boolean => match value {
+            Value::Bool(_) => true,
+            Value::String(s) => matches!(s.as_str(), "true" | "false"),
+            _ => false,
+        },
+        FieldType::Array => value.is_sequence(),
+        FieldType::Object => value.is_mapping(),
+    }
+}
+
+fn enum_contains(values: &[serde_json::Value], value: &Value) -> bool {
+    values.iter().any(|candidate| match candidate {
+        serde_json::Value::String(s) => value.as_str() == Some(s.as_str()),
+        serde_json::Value::Number(n) => n.as_i64().is_some() && scalar_as_i64(value) == n.as_i64(),
+        serde_json::Value::Bool(b) => value.as_bool() == Some(*b),
+        _ => false,
+    })
+}
+
+/// 返回字段的错误信息
+fn check_field(schema: &FieldSchema, value: &Value) -> Option<String> {
+    if let Some(ty) = schema.ty
+        && !matches_type(value, ty)
+    {
+        return Some(format!("expected {ty:?}").to_lowercase());
+    }
+    if let Some(values) = &schema.values
+        && !enum_contains(values, value)
+    {
+        let values = values
+            .iter()
+            .map(|v| v.to_string().trim_matches('"').to_string())
+            .collect::<Vec<_>>();
+        return Some(format!("should be one of: {}", values.join(", ")));
+    }
+    if let Some(n) = scalar_as_i64(value) {
+        if let Some(min) = schema.minimum
+            && n < min
+        {
+            return Some(format!("should be at least {min}"));
+        }
+        if let Some(max) = schema.maximum
+            && n > max
+        {
+            return Some(format!("should be at most {max}"));
+        }
+    }
+    None
+}
+
+fn check_schema(
+    schema: &ProxySchema,
+    proxy: &serde_yaml::Mapping,
+    proxy_name: &str,
+    errors: &mut Vec<ProxyValidationError>,
+) {
+    for field in &schema.required {
+        if proxy.get(field.as_str()).is_none_or(Value::is_null) {
+            errors.push(ProxyValidationError {
+                proxy_name: proxy_name.to_string(),
+                field: Some(field.clone()),
+                error: "missing required field".into(),
+                severity: ValidationSeverity::Error,
+            });
+        }
+    }
+    for (field, field_schema) in &schema.properties {
+        if let Some(value) = proxy.get(field.as_str()).filter(|v| !v.is_null())
+            && let Some(error) = check_field(field_schema, value)
+        {
+            errors.push(ProxyValidationError {
+                proxy_name: proxy_name.to_string(),
+                field: Some(field.clone()),
+                error,
+                severity: field_schema.severity,
+            });
+        }
+    }
+}
+
+/// 检查节点定义是否能被指定核心加载
+pub fn validate_proxy_definitions(proxies: &[Value], core: ClashCore) -> Vec<ProxyValidationError> {
+    let schema = schema_of(core);
+    let mut errors = Vec::new();
+    for (index, proxy) in proxies.iter().enumerate() {
+        let Some(proxy) = proxy.as_mapping() else {
+            errors.push(ProxyValidationError {
+                proxy_name: format!("#{index}"),
+                field: None,
+                error: "proxy should be a mapping".into(),
+                severity: ValidationSeverity::Error,
+            });
+            continue;
+        };
+        let proxy_name = proxy
+            .get("name")
+            .and_then(|name| name.as_str())
+            .map_or_else(|| format!("#{index}"), str::to_string);
+        check_schema(schema, proxy, &proxy_name, &mut errors);
+        let Some(ty) = proxy.get("type").and_then(|ty| ty.as_str()) else {
+            continue;
+        };
+        match schema.types.get(ty) {
+            Some(type_schema) => check_schema(type_schema, proxy, &proxy_name, &mut errors),
+            // schema 可能落后于核心，未收录的类型不阻止加载
+            None => errors.push(ProxyValidationError {
+                proxy_name,
+                field: Some("type".into()),
+                error: format!("`{ty}` is not supported by {core}"),
+                severity: ValidationSeverity::Warning,
+            }),
+        }
+    }
+    errors
+}
+
+/// 配置中的 `proxies`
+pub fn use_proxy_validation(
+    config: &serde_yaml::Mapping,
+    core: ClashCore,
+) -> Vec<ProxyValidationError> {
+    config
+        .get("proxies")
+        .and_then(|proxies| proxies.as_sequence())
+        .map(|proxies| validate_proxy_definitions(proxies, core))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies(yaml: &str) -> Vec<Value> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_embedded_schemas() {
+        for core in [
+            ClashCore::ClashPremium,
+            ClashCore::Mihomo,
+            ClashCore::MihomoAlpha,
+        ] {
+            let schema = schema_of(core);
+            assert!(schema.types.contains_key("ss"), "{core}");
+        }
+    }
+
+    #[test]
+    fn test_validate_proxy_definitions() {
+        let proxies = proxies(
+            r#"
+- { name: ok, type: ss, server: a.com, port: "443", cipher: aes-128-gcm, password: 123456 }
+- { name: no-password, type: trojan, server: a.com, port: 443 }
+- { name: bad-port, type: socks5, server: a.com, port: 70000 }
+- { name: odd-cipher, type: ss, server: a.com, port: 443, cipher: foo, password: p }
+- { name: reality, type: vless, server: a.com, port: 443, uuid: u }
+- not-a-mapping
+"#,
+        );
+        let errors = validate_proxy_definitions(&proxies, ClashCore::ClashPremium);
+        let summary = errors
+            .iter()
+            .map(|e| (e.proxy_name.as_str(), e.field.as_deref(), e.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("no-password", Some("password"), ValidationSeverity::Error),
+                ("bad-port", Some("port"), ValidationSeverity::Error),
+                ("odd-cipher", Some("cipher"), ValidationSeverity::Warning),
+                ("reality", Some("type"), ValidationSeverity::Warning),
+                ("#5", None, ValidationSeverity::Error),
+            ]
+        );
+        assert!(errors[3].error.contains("not supported by clash"));
+
+        // mihomo 支持 vless
+        let errors = validate_proxy_definitions(&proxies[4..5], ClashCore::Mihomo);
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+}
@@ -0,0 +1,11 @@
+//! 前端发出的事件，转换为对后台模块的调用
+//! 窗口重建时会再次调用 [`mount_handlers`]，监听只注册一次
+pub mod widget;
+
+use std::sync::Once;
+use tauri::{AppHandle, Runtime};
+
+pub fn mount_handlers<R: Runtime>(app: &AppHandle<R>) {
+    static MOUNTED: Once = Once::new();
+    MOUNTED.call_once(|| widget::mount(app));
+}
@@ -0,0 +1,178 @@
+//! 浮窗的生命周期事件，处理后通过 `widget://state` 推送浮窗的当前状态
+//! 重复的请求不会重启浮窗，例如浮窗已在运行时再次启动只会重新推送状态
+use crate::{
+    log_err,
+    widget::{WidgetManager, WidgetState},
+};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use nyanpasu_egui::widget::{StatisticWidgetVariant, WidgetTheme};
+use serde::Deserialize;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Listener, Manager, Runtime};
+
+pub const START_EVENT: &str = "widget://start";
+pub const STOP_EVENT: &str = "widget://stop";
+pub const SET_THEME_EVENT: &str = "widget://set-theme";
+pub const VARIANT_CHANGED_EVENT: &str = "widget://variant-changed";
+pub const STATE_EVENT: &str = "widget://state";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WidgetRequest {
+    Start(StatisticWidgetVariant),
+    Stop,
+    SetTheme(WidgetTheme),
+    /// 只切换运行中的浮窗
+    ChangeVariant(StatisticWidgetVariant),
+}
+
+#[derive(Deserialize)]
+struct VariantPayload {
+    variant: StatisticWidgetVariant,
+}
+
+#[derive(Deserialize)]
+struct ThemePayload {
+    theme: WidgetTheme,
+}
+
+impl WidgetRequest {
+    fn parse(event: &str, payload: &str) -> Result<Self> {
+        let variant =
+            || Ok::<_, anyhow::Error>(serde_json::from_str::<VariantPayload>(payload)?.variant);
+        Ok(match event {
+            START_EVENT => Self::Start(variant()?),
+            STOP_EVENT => Self::Stop,
+            SET_THEME_EVENT => Self::SetTheme(serde_json::from_str::<ThemePayload>(payload)?.theme),
+            VARIANT_CHANGED_EVENT => Self::ChangeVariant(variant()?),
+            _ => bail!("unknown widget event `{event}`"),
+        })
+    }
+}
+
+#[async_trait]
+pub trait WidgetSupervisor: Send + Sync + 'static {
+    async fn handle(&self, request: WidgetRequest) -> Result<()>;
+    async fn state(&self) -> WidgetState;
+}
+
+/// 每次请求时从 managed state 中取出浮窗管理器
+/// 管理器在销毁时会停止浮窗，因此不持有它的副本
+struct ManagedWidgetSupervisor<R: Runtime>(AppHandle<R>);
+
+#[async_trait]
+impl<R: Runtime> WidgetSupervisor for ManagedWidgetSupervisor<R> {
+    async fn handle(&self, request: WidgetRequest) -> Result<()> {
+        let Some(manager) = self.0.try_state::<WidgetManager>() else {
+            bail!("widget manager is not initialized");
+        };
+        match request {
+            WidgetRequest::Start(variant) => manager.ensure_started(variant).await,
+            WidgetRequest::Stop => manager.stop().await,
+            WidgetRequest::SetTheme(theme) => manager.set_theme(theme).await,
+            WidgetRequest::ChangeVariant(variant) => manager.change_variant(variant).await,
+        }
+    }
+
+    async fn state(&self) -> WidgetState {
+        match self.0.try_state::<WidgetManager>() {
+            Some(manager) => manager.state().await,
+            None => WidgetState::default(),
+        }
+    }
+}
+
+/// 注册浮窗事件，请求按收到的顺序依次处理
+pub fn listen<R: Runtime, S: WidgetSupervisor>(app: &AppHandle<R>, supervisor: Arc<S>) {
+    let queue = Arc::new(tokio::sync::Mutex::new(()));
+    for event in [
+        START_EVENT,
+        STOP_EVENT,
+        SET_THEME_EVENT,
+        VARIANT_CHANGED_EVENT,
+    ] {
+        let app_handle = app.clone();
+        let supervisor = supervisor.clone();
+        let queue = queue.clone();
+        app.listen(event, move |e| {
+            let request = WidgetRequest::parse(event, e.payload());
+            let app_handle = app_handle.clone();
+            let supervisor = supervisor.clone();
+            let queue = queue.clone();
+            tauri::async_runtime::spawn(async move {
+                let _guard = queue.lock().await;
+                let error = match request {
+                    Ok(request) => supervisor.handle(request).await.err(),
+                    Err(e) => Some(e),
+                };
+                if let Some(e) = &error {
+                    tracing::error!("failed to handle widget event `{event}`: {e:?}");
+                }
+                let mut state = supervisor.state().await;
+                state.error = error.map(|e| e.to_string());
+                log_err!(app_handle.emit(STATE_EVENT, state));
+            });
+        });
+    }
+}
+
+pub(super) fn mount<R: Runtime>(app: &AppHandle<R>) {
+    listen(app, Arc::new(ManagedWidgetSupervisor(app.clone())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    struct MockSupervisor(mpsc::Sender<WidgetRequest>);
+
+    #[async_trait]
+    impl WidgetSupervisor for MockSupervisor {
+        async fn handle(&self, request: WidgetRequest) -> Result<()> {
+            self.0.send(request)?;
+            Ok(())
+        }
+
+        async fn state(&self) -> WidgetState {
+            WidgetState::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            WidgetRequest::parse(START_EVENT, r#"{"variant":"small"}"#).unwrap(),
+            WidgetRequest::Start(StatisticWidgetVariant::Small)
+        );
+        assert_eq!(
+            WidgetRequest::parse(STOP_EVENT, "null").unwrap(),
+            WidgetRequest::Stop
+        );
+        assert_eq!(
+            WidgetRequest::parse(SET_THEME_EVENT, r#"{"theme":"light"}"#).unwrap(),
+            WidgetRequest::SetTheme(WidgetTheme::Light)
+        );
+        assert!(WidgetRequest::parse(START_EVENT, r#"{"variant":"huge"}"#).is_err());
+    }
+
+    #[test]
+    fn test_start_event_reaches_supervisor() {
+        let app = tauri::test::mock_app();
+        let (tx, rx) = mpsc::channel();
+        listen(app.handle(), Arc::new(MockSupervisor(tx)));
+        let (state_tx, state_rx) = mpsc::channel();
+        app.listen(STATE_EVENT, move |e| {
+            let _ = state_tx.send(e.payload().to_string());
+        });
+
+        app.emit(START_EVENT, serde_json::json!({ "variant": "large" }))
+            .unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            WidgetRequest::Start(StatisticWidgetVariant::Large)
+        );
+        let state = state_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(state.contains(r#""running":false"#), "{state}");
+    }
+}
@@ -20,11 +20,17 @@ use crate::{
     },
     core::{service::ipc::get_ipc_state, *},
     log_err,
-    utils::{self, help::get_clash_external_port, resolve},
+    utils::{
+        self,
+        config::{ClashMode, current_clash_mode},
+        help::get_clash_external_port,
+        resolve,
+    },
 };
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 use handle::Message;
 use nyanpasu_ipc::api::status::CoreState;
+use once_cell::sync::Lazy;
 use serde_yaml::{Mapping, Value};
 use tauri::{AppHandle, Manager};
 use tauri_plugin_clipboard_manager::ClipboardExt;
@@ -57,26 +63,21 @@ pub fn restart_clash_core() {
     });
 }
 
+// 紧急恢复网络，供托盘调用
+pub fn emergency_reset() {
+    tauri::async_runtime::spawn(async {
+        let report = emergency::emergency_reset().await;
+        if !report.success {
+            log::error!(target: "app", "emergency reset partially failed: {report:?}");
+        }
+    });
+}
+
 // 切换模式 rule/global/direct/script mode
-pub fn change_clash_mode(mode: String) {
-    let mut mapping = Mapping::new();
-    mapping.insert(Value::from("mode"), mode.clone().into());
+pub fn change_clash_mode(mode: ClashMode) {
     let (tx, rx) = tokio::sync::oneshot::channel();
     tauri::async_runtime::spawn(async move {
-        log::debug!(target: "app", "change clash mode to {mode}");
-
-        match clash::api::patch_configs(&mapping).await {
-            Ok(_) => {
-                // 更新配置
-                Config::clash().data().patch_config(mapping);
-
-                if Config::clash().data().save_config().is_ok() {
-                    handle::Handle::refresh_clash();
-                    log_err!(handle::Handle::update_systray_part());
-                }
-            }
-            Err(err) => log::error!(target: "app", "{err:?}"),
-        }
+        log_err!(set_clash_mode(mode).await);
         if tx.send(()).is_err() {
             log::error!(target: "app::change_clash_mode", "failed to send tx");
         }
@@ -93,6 +94,44 @@ pub fn change_clash_mode(mode: String) {
     });
 }
 
+/// 同时更新控制器与本地配置中的模式，保存失败时回滚控制器
+pub async fn set_clash_mode(mode: ClashMode) -> Result<()> {
+    let core = Config::clash_core();
+    if !mode.is_supported_by(core) {
+        bail!("clash mode `{mode}` is not supported by core `{core}`");
+    }
+    log::debug!(target: "app", "change clash mode to {mode}");
+
+    let previous = current_clash_mode().await;
+    let mut mapping = Mapping::new();
+    mapping.insert(Value::from("mode"), mode.as_str().into());
+    clash::api::patch_configs(&mapping)
+        .await
+        .with_context(|| format!("core rejected clash mode `{mode}`"))?;
+    // 部分核心对不支持的模式会静默忽略，以核心实际生效的模式为准
+    let applied = clash::api::get_configs()
+        .await?
+        .get("mode")
+        .and_then(|v| v.as_str())
+        .map(ClashMode::from);
+    if applied.as_ref().is_some_and(|applied| *applied != mode) {
+        bail!("core rejected clash mode `{mode}`");
+    }
+
+    Config::clash().data().patch_config(mapping);
+    if let Err(err) = Config::clash().data().save_config() {
+        let mut rollback = Mapping::new();
+        rollback.insert(Value::from("mode"), previous.as_str().into());
+        Config::clash().data().patch_config(rollback.clone());
+        log_err!(clash::api::patch_configs(&rollback).await);
+        return Err(err);
+    }
+
+    handle::Handle::refresh_clash();
+    log_err!(handle::Handle::emit("clash-mode-changed", mode));
+    Ok(())
+}
+
 // 切换tun模式 - 使用新的权限管理系统
 pub fn toggle_tun_mode() {
     tauri::async_runtime::spawn(async move {
@@ -139,6 +178,25 @@ pub fn disable_tun_mode() {
     });
 }
 
+// 切换系统代理
+pub fn toggle_system_proxy() {
+    tauri::async_runtime::spawn(async {
+        let enable = Config::verge()
+            .latest()
+            .enable_system_proxy
+            .unwrap_or(false);
+        match patch_verge(IVerge {
+            enable_system_proxy: Some(!enable),
+            ..IVerge::default()
+        })
+        .await
+        {
+            Ok(_) => handle::Handle::refresh_verge(),
+            Err(err) => log::error!(target: "app", "{err:?}"),
+        }
+    });
+}
+
 /// 修改clash的配置
 pub async fn patch_clash(patch: Mapping) -> Result<()> {
     Config::clash().draft().patch_config(patch.clone());
@@ -193,6 +251,10 @@ pub async fn patch_clash(patch: Mapping) -> Result<()> {
         }
 
         // System proxy functionality removed, only TUN mode remains
+        // PAC 脚本中的端口需要跟随 mixed-port
+        if mixed_port.is_some() {
+            log_err!(sysopt::Sysopt::global().update_pac());
+        }
 
         if patch.get("mode").is_some() {
             crate::feat::update_proxies_buff(None);
@@ -216,20 +278,248 @@ pub async fn patch_clash(patch: Mapping) -> Result<()> {
     }
 }
 
+/// 生成新的 external-controller secret，重启核心后生效
+pub async fn rotate_controller_secret() -> Result<String> {
+    let secret = uuid::Uuid::new_v4().to_string().to_lowercase();
+    let mut patch = Mapping::new();
+    patch.insert("secret".into(), secret.clone().into());
+    patch_clash(patch).await?;
+
+    // 连接流仍使用旧 secret，需要重新连接
+    let ws_connector = crate::consts::app_handle()
+        .state::<clash::ws::ClashConnectionsConnector>()
+        .inner()
+        .clone();
+    log_err!(ws_connector.restart().await);
+    Ok(secret)
+}
+
+/// verge 配置只有一份 draft，并发修改时一方的 discard 会丢掉另一方的修改，
+/// 所以所有修改都需要串行执行
+static VERGE_PATCH_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+const VERGE_SAVE_RETRIES: usize = 3;
+
 /// 修改verge的配置
 /// 一般都是一个个的修改
 pub async fn patch_verge(patch: IVerge) -> Result<()> {
+    update_verge(move |_| Ok(patch)).await
+}
+
+/// 切换核心，失败时 verge 的修改被丢弃，核心已停止时用原来的核心重新启动
+pub async fn change_clash_core(clash_core: crate::config::nyanpasu::ClashCore) -> Result<()> {
+    let res = patch_verge(IVerge {
+        clash_core: Some(clash_core),
+        ..IVerge::default()
+    })
+    .await;
+    if res.is_err() {
+        let (state, ..) = CoreManager::global().status().await;
+        if !matches!(state.as_ref(), CoreState::Running) {
+            log_err!(CoreManager::global().run_core().await);
+        }
+    }
+    res
+}
+
+/// 在锁内根据当前配置生成修改，读取后再修改的场景需要通过这里，避免并发时丢失修改
+/// 修改可能触发特权操作，先进入特权操作队列再获取修改锁，与切换 TUN 的顺序一致
+pub async fn update_verge(build: impl FnOnce(&IVerge) -> Result<IVerge> + Send) -> Result<()> {
+    privilege::manager::PrivilegeManager::global()
+        .with_queue(commit_verge_patch(
+            &Config::verge(),
+            |current| validate_verge_patch(build(current)?),
+            |patch, previous| apply_verge_patch(patch, previous.enable_tun_mode.unwrap_or(false)),
+            |verge| verge.save_file(),
+        ))
+        .await
+}
+
+/// 校验修改，并将需要规范化的字段替换为规范化后的值
+fn validate_verge_patch(mut patch: IVerge) -> Result<IVerge> {
     // Validate theme_color if it's being updated
     if let Some(ref theme_color) = patch.theme_color {
         if !theme_color.is_empty() && !crate::config::nyanpasu::is_hex_color(theme_color) {
             anyhow::bail!("Invalid theme color: {}", theme_color);
         }
     }
+    if let Some(ref bypass) = patch.system_proxy_bypass {
+        utils::config::BypassManager::validate(bypass)?;
+    }
+    if let Some(excludes) = patch.global_exclude_list.take() {
+        patch.global_exclude_list =
+            Some(utils::config::GlobalExcludeList::normalize_list(&excludes)?);
+    }
+    if let Some(ref lan_share) = patch.lan_share {
+        lan_share.validate()?;
+    }
+    // 关闭遥测时丢弃匿名 id，重新开启时生成新的
+    if let Some(telemetry) = patch.telemetry.as_mut()
+        && !telemetry.enabled
+    {
+        telemetry.user_id.clear();
+    }
+    if let Some(ref injection) = patch.rule_injection {
+        injection.validate()?;
+    }
+    if let Some(ref servers) = patch.dns_nameservers {
+        let profile_core = Config::profiles().latest().current_clash_core();
+        let core = crate::config::nyanpasu::ClashCore::resolve(
+            profile_core,
+            patch.clash_core.or(Config::verge().latest().clash_core),
+        );
+        crate::enhance::validate_nameservers(servers, &core)?;
+    }
+    if let Some(ref thresholds) = patch.polling_thresholds {
+        thresholds.validate()?;
+    }
+    if patch.enable_auto_launch == Some(true) {
+        utils::dirs::ensure_not_portable("auto launch")?;
+    }
+    if patch.enable_service_mode == Some(true) {
+        utils::dirs::ensure_not_portable("service mode")?;
+    }
+    if let Some(ref socket) = patch.ipc_socket_path {
+        crate::core::service::control::validate_ipc_socket_path(socket)?;
+    }
+    if let Some(ref args) = patch.clash_core_extra_args {
+        crate::core::clash::core_args::validate_core_args(args)?;
+    }
+    Ok(patch)
+}
 
+/// 在锁内生成修改并写入 draft，执行副作用，成功时应用并保存，失败时丢弃
+/// `build` 与 `run` 接收修改前已持久化的配置
+async fn commit_verge_patch<F, Fut>(
+    verge: &Draft<IVerge>,
+    build: impl FnOnce(&IVerge) -> Result<IVerge>,
+    run: F,
+    save: impl Fn(&IVerge) -> Result<()>,
+) -> Result<()>
+where
+    F: FnOnce(IVerge, IVerge) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let _guard = VERGE_PATCH_LOCK.lock().await;
     // Capture the persisted state before we write to the draft copy. `latest()`
     // reflects the draft value, which would hide whether TUN actually changed.
-    let previous_tun_mode = Config::verge().data().enable_tun_mode.unwrap_or(false);
-    Config::verge().draft().patch_config(patch.clone());
+    let previous = verge.data().clone();
+    let patch = build(&previous)?;
+    verge.draft().patch_config(patch.clone());
+
+    match run(patch, previous).await {
+        Ok(()) => {
+            verge.apply();
+            save_with_retry(|| save(&verge.data())).await
+        }
+        Err(err) => {
+            verge.discard();
+            Err(err)
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct SettingsReset {
+    /// 本次重置的备份目录
+    pub backup_dir: String,
+    pub keep_profiles: bool,
+}
+
+/// 备份当前的设置，不保留配置时同时备份配置列表
+fn backup_settings(keep_profiles: bool) -> Result<std::path::PathBuf> {
+    let dir = utils::dirs::settings_backup_dir()?.join(format!(
+        "reset-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::create_dir_all(&dir)?;
+    let mut files = vec![utils::dirs::nyanpasu_config_path()?];
+    if !keep_profiles {
+        files.push(utils::dirs::profiles_path()?);
+    }
+    for file in files.into_iter().filter(|file| file.exists()) {
+        let name = file.file_name().context("invalid config path")?;
+        std::fs::copy(&file, dir.join(name))
+            .with_context(|| format!("failed to backup {}", file.display()))?;
+    }
+    Ok(dir)
+}
+
+/// 备份后恢复默认设置并重新加载，服务模式会被关闭，核心改为直接运行
+/// keep_profiles 为假时同时清空配置列表，配置文件本身保留在配置目录中
+pub async fn reset_settings(keep_profiles: bool) -> Result<SettingsReset> {
+    let backup_dir = backup_settings(keep_profiles)?;
+    tracing::info!("settings backed up to {}", backup_dir.display());
+
+    let verge = Config::verge();
+    {
+        let _exclusive = profile_activation::global().exclusive().await;
+        // 与 update_verge 一样先进入特权操作队列，再获取修改锁
+        privilege::manager::PrivilegeManager::global()
+            .with_queue(async {
+                let _guard = VERGE_PATCH_LOCK.lock().await;
+                let previous = verge.data().clone();
+                let template = IVerge::template();
+                *verge.draft() = template.clone();
+                if !keep_profiles {
+                    *Config::profiles().draft() = Profiles::default();
+                }
+                // 服务模式在下面统一处理，这里只应用其余的副作用
+                let patch = IVerge {
+                    enable_service_mode: None,
+                    ..template
+                };
+                let res = async {
+                    apply_verge_patch(patch, previous.enable_tun_mode.unwrap_or(false)).await?;
+                    sysopt::Sysopt::global().reset_sysproxy()?;
+                    // 新的设置中服务模式已关闭，重启后核心不再由服务托管
+                    Config::generate().await?;
+                    CoreManager::global().run_core().await
+                };
+                if let Err(err) = res.await {
+                    verge.discard();
+                    Config::profiles().discard();
+                    return Err(err.context("failed to reset settings"));
+                }
+                verge.apply();
+                save_with_retry(|| verge.data().save_file()).await?;
+                if !keep_profiles {
+                    Config::profiles().apply();
+                    Config::profiles().data().save_file()?;
+                }
+                Ok::<(), anyhow::Error>(())
+            })
+            .await?;
+    }
+
+    handle::Handle::refresh_verge();
+    handle::Handle::refresh_profiles();
+    handle::Handle::refresh_clash();
+    let reset = SettingsReset {
+        backup_dir: backup_dir.to_string_lossy().to_string(),
+        keep_profiles,
+    };
+    log_err!(handle::Handle::emit("settings-reset", &reset));
+    Ok(reset)
+}
+
+/// 配置文件可能被其他进程（杀毒软件、同步盘等）短暂占用，写入失败时稍后重试
+async fn save_with_retry(save: impl Fn() -> Result<()>) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        match save() {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < VERGE_SAVE_RETRIES => {
+                log::warn!(target: "app", "failed to save verge config (attempt {attempt}): {err:?}");
+                tokio::time::sleep(std::time::Duration::from_millis(100 * attempt as u64)).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+async fn apply_verge_patch(patch: IVerge, previous_tun_mode: bool) -> Result<()> {
     let tun_mode = patch.enable_tun_mode;
     let auto_launch = patch.enable_auto_launch;
     let language = patch.language;
@@ -239,6 +529,18 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
     let enable_tray_text = patch.enable_tray_text;
     let network_statistic_widget = patch.network_statistic_widget;
     let res = || async move {
+        // 证书无效时在这里报错，而不是等到下载时
+        if let Some(paths) = &patch.custom_ca_certs {
+            utils::tls::reload_custom_ca(paths)?;
+        }
+
+        if let Some(enabled) = patch.enable_connection_log {
+            crate::core::clash::connection_log::ConnectionLogger::global().set_enabled(enabled);
+        }
+        if let Some(telemetry) = &patch.telemetry {
+            utils::telemetry::sync_enabled(telemetry.enabled);
+        }
+
         let service_mode = patch.enable_service_mode;
         let ipc_state = get_ipc_state();
         if service_mode.is_some() && ipc_state.is_connected() {
@@ -255,8 +557,7 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             {
                 use crate::utils::dirs::check_core_permission;
-                let current_core = Config::verge().data().clash_core.unwrap_or_default();
-                let current_core: nyanpasu_utils::core::CoreType = (&current_core).into();
+                let current_core: nyanpasu_utils::core::CoreType = (&Config::clash_core()).into();
                 let service_state = crate::core::service::ipc::get_ipc_state();
                 if !service_state.is_connected() && check_core_permission(&current_core).inspect_err(|e| {
                     log::error!(target: "app", "clash core is not granted the necessary permissions, grant it: {e:?}");
@@ -289,10 +590,65 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
             }
         }
 
+        // 核心支持热重载时不会断开已连接的局域网设备
+        if patch.lan_share.is_some() && tun_mode.is_none() {
+            log::debug!(target: "app", "apply lan share config");
+            crate::core::profile_switch::apply_switch(true).await?;
+            handle::Handle::refresh_clash();
+        }
+
+        if (patch.enhance_chain.is_some()
+            || patch.enable_ipv6.is_some()
+            || patch.enable_dns_enhance.is_some()
+            || patch.dns_nameservers.is_some()
+            || patch.rule_injection.is_some()
+            || patch.global_exclude_list.is_some())
+            && tun_mode.is_none()
+        {
+            log::debug!(target: "app", "enhance chain changed, update core config");
+            update_core_config().await?;
+        }
+
+        if patch.clash_core.is_some() {
+            CoreManager::global().change_core().await?;
+        } else if patch.clash_core_extra_args.is_some() || patch.allow_remote_api.is_some() {
+            log::debug!(target: "app", "core args changed, restart core");
+            Config::generate().await?;
+            CoreManager::global().run_core().await?;
+        }
+
+        if patch.profile_sync_dir.is_some() {
+            crate::core::profile_sync::ProfileSyncManager::global().start()?;
+        }
+
+        // 未开启严格路由时切换 TUN 不需要请求权限
+        if patch.strict_routing.is_some()
+            || (tun_mode.is_some() && Config::verge().latest().strict_routing.unwrap_or(false))
+        {
+            utils::platform::sync_strict_routing().await?;
+        }
+
         if auto_launch.is_some() {
             sysopt::Sysopt::global().update_launch()?;
         }
 
+        if patch.enable_system_proxy == Some(true)
+            || patch.system_proxy_bypass.is_some()
+            || patch.global_exclude_list.is_some()
+        {
+            utils::config::BypassManager::sync_to_system()?;
+        }
+
+        if patch.enable_system_proxy.is_some()
+            || patch.system_proxy_mode.is_some()
+            || patch.system_proxy_bypass.is_some()
+            || patch.global_exclude_list.is_some()
+            || patch.verge_mixed_port.is_some()
+            || patch.pac_server_port.is_some()
+        {
+            sysopt::Sysopt::global().update_pac()?;
+        }
+
         if let Some(true) = patch.enable_proxy_guard {
             sysopt::Sysopt::global().guard_proxy();
         }
@@ -301,6 +657,14 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
             hotkey::Hotkey::global().update(hotkeys)?;
         }
 
+        if let Some(true) = patch.auto_restart_on_network_change {
+            crate::utils::net::NetworkChangeDetector::global().start()?;
+        }
+
+        if patch.node_metadata_patterns.is_some() {
+            log_err!(clash::node_meta::reparse_node_metadata().await);
+        }
+
         if language.is_some() {
             rust_i18n::set_locale(language.unwrap().as_str());
             handle::Handle::update_systray()?;
@@ -336,17 +700,7 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
         <Result<()>>::Ok(())
     };
 
-    match res().await {
-        Ok(()) => {
-            Config::verge().apply();
-            Config::verge().data().save_file()?;
-            Ok(())
-        }
-        Err(err) => {
-            Config::verge().discard();
-            Err(err)
-        }
-    }
+    res().await
 }
 
 fn should_restart_core_for_tun_change(
@@ -358,20 +712,57 @@ fn should_restart_core_for_tun_change(
     needs_permission_grant || is_core_stopped || previous_tun_mode != desired_tun_mode
 }
 
+/// 依次更新订阅，可以只更新某个文件夹内的订阅，出错时继续更新其余的订阅
+pub async fn update_all_profiles(folder: Option<&str>) -> Result<()> {
+    let uids = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        profiles
+            .uids_in_folder(folder)
+            .into_iter()
+            .filter(|uid| profiles.get_item(uid).is_ok_and(|item| item.is_remote()))
+            .collect::<Vec<_>>()
+    };
+    let mut errors = vec![];
+    for uid in uids {
+        if let Err(err) = update_profile(&uid, None).await {
+            log::error!(target: "app", "failed to update profile `{uid}`: {err:?}");
+            errors.push(format!("{uid}: {err}"));
+        }
+    }
+    if !errors.is_empty() {
+        bail!("failed to update profiles: {}", errors.join("; "));
+    }
+    Ok(())
+}
+
 /// 更新某个profile
 /// 如果更新当前配置就激活配置
 pub async fn update_profile<T: Borrow<String>>(
     uid: T,
     opts: Option<RemoteProfileOptionsBuilder>,
+) -> Result<()> {
+    activation::track("update", update_profile_inner(uid, opts)).await
+}
+
+async fn update_profile_inner<T: Borrow<String>>(
+    uid: T,
+    opts: Option<RemoteProfileOptionsBuilder>,
 ) -> Result<()> {
     let uid = uid.borrow();
     let profile_item = Config::profiles().latest().get_item(uid)?.clone();
-    let is_remote = profile_item.is_remote();
-
-    let should_update = if is_remote {
-        let mut item = profile_item.as_remote().unwrap().clone();
+    let remote = match profile_item.as_remote() {
+        Some(item) => {
+            let mut item = item.clone();
+            activation::stage("download", item.subscribe(opts)).await?;
+            Some(item)
+        }
+        None => None,
+    };
+    // 下载完成后再与配置激活互斥，避免激活失败时丢弃这里写入的 draft
+    let _exclusive = profile_activation::global().exclusive().await;
 
-        item.subscribe(opts).await?;
+    let should_update = if let Some(item) = remote {
         let committer = Config::profiles().auto_commit();
         let mut profiles = committer.draft();
         profiles.replace_item(uid, item.into())?;
@@ -417,6 +808,28 @@ pub async fn update_profile<T: Borrow<String>>(
     Ok(())
 }
 
+/// 保存用户的合并配置并重新生成配置，内容为空时删除该文件
+pub async fn save_merge_overlay(content: String) -> Result<()> {
+    let path = utils::dirs::merge_overlay_path()?;
+    if content.trim().is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+    } else {
+        let mut value: Value = serde_yaml::from_str(&content).context("invalid merge overlay")?;
+        value.apply_merge().context("invalid merge overlay")?;
+        if !value.is_mapping() {
+            bail!("merge overlay must be a yaml mapping");
+        }
+        let dir = path.parent().context("invalid merge overlay path")?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        std::io::Write::write_all(&mut file, content.as_bytes())?;
+        file.as_file().sync_all()?;
+        file.persist(&path)?;
+    }
+    update_core_config().await
+}
+
 /// 更新配置
 async fn update_core_config() -> Result<()> {
     match CoreManager::global().update_config().await {
@@ -434,7 +847,7 @@ async fn update_core_config() -> Result<()> {
 
 /// copy env variable
 pub fn copy_clash_env(app_handle: &AppHandle, option: &str) {
-    let port = { Config::verge().latest().verge_mixed_port.unwrap_or(7890) };
+    let port = crate::core::sysopt::proxy_port();
     let http_proxy = format!("http://127.0.0.1:{port}");
     let socks5_proxy = format!("socks5://127.0.0.1:{port}");
 
@@ -487,7 +900,12 @@ pub fn update_proxies_buff(rx: Option<tokio::sync::oneshot::Receiver<()>>) {
 
 #[cfg(test)]
 mod tests {
-    use super::should_restart_core_for_tun_change;
+    use super::{commit_verge_patch, should_restart_core_for_tun_change};
+    use crate::config::{Draft, IVerge};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
 
     #[test]
     fn tun_change_requires_restart_even_if_core_is_running() {
@@ -518,4 +936,89 @@ mod tests {
             false, true, false, false
         ));
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_verge_patches_all_land() {
+        let verge = Draft::from(IVerge::default());
+        let saved = Arc::new(AtomicUsize::new(0));
+        let patches = vec![
+            IVerge {
+                enable_tun_mode: Some(true),
+                ..IVerge::default()
+            },
+            IVerge {
+                enable_service_mode: Some(true),
+                ..IVerge::default()
+            },
+            IVerge {
+                enable_auto_launch: Some(true),
+                ..IVerge::default()
+            },
+            IVerge {
+                language: Some("en".into()),
+                ..IVerge::default()
+            },
+        ];
+        let tasks = patches.into_iter().enumerate().map(|(index, patch)| {
+            let verge = verge.clone();
+            let saved = saved.clone();
+            tokio::spawn(async move {
+                commit_verge_patch(
+                    &verge,
+                    |_| Ok(patch),
+                    |_, _| async move {
+                        // 让出执行权，模拟修改过程中的异步操作
+                        for _ in 0..index + 1 {
+                            tokio::task::yield_now().await;
+                        }
+                        Ok(())
+                    },
+                    |_| {
+                        saved.fetch_add(1, Ordering::SeqCst);
+                        Ok(())
+                    },
+                )
+                .await
+            })
+        });
+        // 失败的修改不应影响其他修改
+        let failed = commit_verge_patch(
+            &verge,
+            |_| {
+                Ok(IVerge {
+                    enable_random_port: Some(true),
+                    ..IVerge::default()
+                })
+            },
+            |_, _| async { anyhow::bail!("side effect failed") },
+            |_| Ok(()),
+        );
+        let (results, failed) = tokio::join!(futures::future::join_all(tasks), failed);
+        assert!(failed.is_err());
+        for result in results {
+            result.unwrap().unwrap();
+        }
+
+        let data = verge.data();
+        assert_eq!(data.enable_tun_mode, Some(true));
+        assert_eq!(data.enable_service_mode, Some(true));
+        assert_eq!(data.enable_auto_launch, Some(true));
+        assert_eq!(data.language.as_deref(), Some("en"));
+        assert_eq!(data.enable_random_port, None);
+        assert_eq!(saved.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn verge_save_is_retried() {
+        let attempts = AtomicUsize::new(0);
+        super::save_with_retry(|| {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                anyhow::bail!("file is locked");
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
 }
@@ -4,12 +4,13 @@ use crate::{
         logger::Logger, storage::Storage, tasks::jobs::ProfilesJobGuard,
         updater::ManifestVersionLatest, *,
     },
-    enhance::PostProcessingOutput,
+    enhance::{PostProcessingOutput, ProxyValidationError},
     feat,
     utils::{
-        candy,
         collect::EnvInfo,
-        dirs, help,
+        config::{BypassManager, GlobalExcludeList},
+        deep_link, dirs, help,
+        redact::{Redactor, redact_config},
         resolve::{self, save_window_state},
     },
 };
@@ -107,18 +108,42 @@ pub fn get_profiles() -> Result<Profiles> {
     Ok(Profiles::new())
 }
 
-#[cfg(target_os = "windows")]
 #[tauri::command]
 #[specta::specta]
 pub fn is_portable() -> Result<bool> {
     Ok(crate::utils::dirs::get_portable_flag())
 }
 
-#[cfg(not(target_os = "windows"))]
+/// 返回样本经过日志脱敏后的内容，用于确认脱敏规则
 #[tauri::command]
 #[specta::specta]
-pub fn is_portable() -> Result<bool> {
-    Ok(false)
+pub fn log_redaction_test(sample: String) -> Result<String> {
+    Ok(crate::utils::redact::LogRedactor::global()
+        .redact(&sample)
+        .into_owned())
+}
+
+/// 从 `preferred` 开始查找可用的端口，找不到时原样返回
+#[tauri::command]
+#[specta::specta]
+pub fn suggest_available_port(preferred: u16) -> Result<u16> {
+    Ok(crate::utils::net::ClashMixedPortRangeScanner::default()
+        .suggest(preferred)
+        .unwrap_or(preferred))
+}
+
+/// 是否以 `--safe-mode` 启动
+#[tauri::command]
+#[specta::specta]
+pub fn is_safe_mode() -> Result<bool> {
+    Ok(crate::consts::is_safe_mode())
+}
+
+/// 实际使用的各个目录，以及是否处于便携模式
+#[tauri::command]
+#[specta::specta]
+pub fn runtime_paths() -> Result<crate::utils::dirs::RuntimePaths> {
+    Ok((crate::utils::dirs::runtime_paths())?)
 }
 
 #[tauri::command]
@@ -149,21 +174,18 @@ pub async fn import_profile(url: String, option: Option<RemoteProfileOptionsBuil
 
     tracing::info!("配置文件构建成功，UID: {}", profile.uid());
 
-    // 根据是否为 Some(uid) 来判断是否要激活配置
-    let profile_id = {
-        if Config::profiles().draft().current.is_empty() {
-            Some(profile.uid().to_string())
-        } else {
-            None
-        }
-    };
+    tracing::info!("准备保存配置到列表");
 
-    tracing::info!("准备保存配置到列表，profile_id: {:?}", profile_id);
-
-    {
-        let committer = Config::profiles().auto_commit();
-        (committer.draft().append_item(profile.into()))?;
-    }
+    // 根据是否为 Some(uid) 来判断是否要激活配置
+    let profile_id = (profile_activation::modify_profiles(|profiles| {
+        let profile_id = profiles
+            .current
+            .is_empty()
+            .then(|| profile.uid().to_string());
+        profiles.append_item(profile.into())?;
+        Ok(profile_id)
+    })
+    .await)?;
 
     tracing::info!("配置已添加到列表");
 
@@ -220,22 +242,15 @@ pub async fn create_profile(item: ProfileBuilder, file_data: Option<String>) ->
         profile.save_file(file_data)?;
     }
 
-    // 根据是否为 Some(uid) 来判断是否要激活配置
-    let profile_id = {
-        if (profile.is_local() || profile.is_remote())
-            && Config::profiles().draft().current.is_empty()
-        {
-            Some(profile.uid().to_string())
-        } else {
-            None
-        }
-    };
-
-    // Save the profile
-    {
-        let committer = Config::profiles().auto_commit();
-        committer.draft().append_item(profile)?;
-    };
+    // 根据是否为 Some(uid) 来判断是否要激活配置，同时保存配置
+    let profile_id = (profile_activation::modify_profiles(|profiles| {
+        let profile_id = ((profile.is_local() || profile.is_remote())
+            && profiles.current.is_empty())
+        .then(|| profile.uid().to_string());
+        profiles.append_item(profile)?;
+        Ok(profile_id)
+    })
+    .await)?;
     // Activate the newly created profile if no current profile exists
     if let Some(profile_id) = profile_id {
         let mut builder = ProfilesBuilder::default();
@@ -249,16 +264,70 @@ pub async fn create_profile(item: ProfileBuilder, file_data: Option<String>) ->
 #[tauri::command]
 #[specta::specta]
 pub async fn reorder_profile(active_id: String, over_id: String) -> Result {
-    let committer = Config::profiles().auto_commit();
-    (committer.draft().reorder(active_id, over_id))?;
+    (profile_activation::modify_profiles(|profiles| profiles.reorder(active_id, over_id)).await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn reorder_profiles_by_list(list: Vec<String>) -> Result {
+    (profile_activation::modify_profiles(|profiles| profiles.reorder_by_list(&list)).await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn create_profile_folder(name: String) -> Result {
+    (profile_activation::modify_profiles(|profiles| profiles.create_folder(&name)).await)?;
+    Ok(())
+}
+
+/// `folder` 为空时移回根目录
+#[tauri::command]
+#[specta::specta]
+pub async fn move_profile_to_folder(uid: String, folder: Option<String>) -> Result {
+    (profile_activation::modify_profiles(|profiles| profiles.move_to_folder(&uid, folder)).await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn rename_folder(old: String, new: String) -> Result {
+    (profile_activation::modify_profiles(|profiles| profiles.rename_folder(&old, &new)).await)?;
+    Ok(())
+}
+
+/// 文件夹不为空时，需要 `move_to_root` 将成员移回根目录，否则拒绝删除
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_profile_folder(name: String, move_to_root: bool) -> Result {
+    (profile_activation::modify_profiles(|profiles| profiles.delete_folder(&name, move_to_root))
+        .await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn list_profiles_grouped() -> Result<profile::folder::ProfileTree> {
+    Ok(Config::profiles().latest().grouped())
+}
+
+/// 设置文件夹内的顺序，`folder` 为空时为根目录
+#[tauri::command]
+#[specta::specta]
+pub async fn set_profile_order(folder: Option<String>, uids: Vec<String>) -> Result {
+    (profile_activation::modify_profiles(|profiles| {
+        profiles.set_folder_order(folder.as_deref(), &uids)
+    })
+    .await)?;
     Ok(())
 }
 
+/// 更新全部订阅，或只更新某个文件夹内的订阅
 #[tauri::command]
 #[specta::specta]
-pub fn reorder_profiles_by_list(list: Vec<String>) -> Result {
-    let committer = Config::profiles().auto_commit();
-    (committer.draft().reorder_by_list(&list))?;
+pub async fn update_all_profiles(folder: Option<String>) -> Result {
+    (feat::update_all_profiles(folder.as_deref()).await)?;
     Ok(())
 }
 
@@ -269,9 +338,18 @@ pub async fn update_profile(uid: String, option: Option<RemoteProfileOptionsBuil
     Ok(())
 }
 
+/// 最近一次定时更新时配置的变化
+#[tauri::command]
+#[specta::specta]
+pub fn profile_last_diff(uid: String) -> Result<Option<crate::core::profile_diff::ProfileUpdated>> {
+    Ok(crate::core::profile_diff::last_diff(&uid))
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn delete_profile(uid: String) -> Result {
+    // 删除当前配置后需要重载核心，期间同样不能与激活交错
+    let _exclusive = profile_activation::global().exclusive().await;
     let should_update = tokio::task::spawn_blocking(move || {
         #[allow(clippy::let_and_return)] // a bug in clippy
         nyanpasu_utils::runtime::block_on_current_thread(async move {
@@ -295,9 +373,10 @@ pub async fn delete_profile(uid: String) -> Result {
 #[tauri::command]
 #[specta::specta]
 pub async fn patch_profiles_config(profiles: ProfilesBuilder) -> Result {
+    let _exclusive = profile_activation::global().exclusive().await;
     Config::profiles().draft().apply(profiles);
 
-    match CoreManager::global().update_config().await {
+    match activation::track("switch", CoreManager::global().update_config()).await {
         Ok(_) => {
             handle::Handle::refresh_clash();
             handle::Handle::refresh_profiles();
@@ -317,15 +396,94 @@ pub async fn patch_profiles_config(profiles: ProfilesBuilder) -> Result {
     }
 }
 
+/// 切换配置前检查活跃连接，未确认时返回受影响的连接
+/// drain 为真时尽量通过热重载保留已有连接
+#[tauri::command]
+#[specta::specta]
+pub async fn switch_profiles(
+    profiles: ProfilesBuilder,
+    confirm: bool,
+    drain: bool,
+) -> Result<profile_switch::SwitchOutcome> {
+    use profile_switch::{SwitchApplied, SwitchOutcome};
+
+    if !confirm
+        && let Some(impact) = profile_switch::assess_switch_impact().await
+        && impact.requires_confirmation()
+    {
+        return Ok(SwitchOutcome::NeedsConfirmation(impact));
+    }
+
+    let _exclusive = profile_activation::global().exclusive().await;
+    Config::profiles().draft().apply(profiles);
+    if !confirm && let Some(switch) = profile_switch::core_switch_requiring_confirmation() {
+        Config::profiles().discard();
+        return Ok(SwitchOutcome::NeedsCoreSwitchConfirmation(switch));
+    }
+    if !confirm {
+        let warnings = profile_switch::compatibility_warnings().await;
+        if !warnings.is_empty() {
+            Config::profiles().discard();
+            return Ok(SwitchOutcome::NeedsCompatibilityConfirmation(warnings));
+        }
+    }
+    match activation::track("switch", profile_switch::apply_switch(drain)).await {
+        Ok(applied) => {
+            handle::Handle::refresh_clash();
+            handle::Handle::refresh_profiles();
+            Config::profiles().apply();
+            (Config::profiles().data().save_file())?;
+
+            if applied != SwitchApplied::Drained {
+                let _ = crate::core::connection_interruption::ConnectionInterruptionService::on_profile_change().await;
+            }
+            Ok(SwitchOutcome::Applied(applied))
+        }
+        Err(err) => {
+            Config::profiles().discard();
+            log::error!(target: "app", "{err:?}");
+            Err(IpcError::from(err))
+        }
+    }
+}
+
+/// 将配置排队激活，进度通过 `profile-activation` 事件按 ticket 推送
+#[tauri::command]
+#[specta::specta]
+pub fn activate_profile(uid: String) -> Result<profile_activation::ActivationTicket> {
+    Ok(profile_activation::global().activate(uid))
+}
+
+/// 正在执行与排队中的激活请求
+#[tauri::command]
+#[specta::specta]
+pub fn current_activation() -> Result<profile_activation::CurrentActivation> {
+    Ok(profile_activation::global().current())
+}
+
 /// update profile by uid
 #[tauri::command]
 #[specta::specta]
 pub async fn patch_profile(app_handle: AppHandle, uid: String, profile: ProfileBuilder) -> Result {
     tracing::debug!("patch profile: {uid} with {profile:?}");
+    let _exclusive = profile_activation::global().exclusive().await;
+    let core_before = Config::clash_core();
+    if let Err(err) = Config::profiles().draft().patch_item(uid.clone(), profile) {
+        Config::profiles().discard();
+        return Err(err.into());
+    }
+    // 更换核心需要确认时不在这里切换，由 set_profile_core_preference 确认
+    if Config::clash_core() != core_before
+        && let Some(switch) = profile_switch::core_switch_requiring_confirmation()
     {
-        let committer = Config::profiles().auto_commit();
-        (committer.draft().patch_item(uid.clone(), profile))?;
+        Config::profiles().discard();
+        crate::log_err!(Config::profiles().data().save_file());
+        return Err(IpcError::Custom(format!(
+            "profile `{}` requires switching the core from {} to {}, confirm it with set_profile_core_preference",
+            switch.profile, switch.from, switch.to
+        )));
     }
+    Config::profiles().apply();
     {
         let profiles_jobs = app_handle.state::<ProfilesJobGuard>();
         profiles_jobs.write().refresh();
@@ -352,7 +510,8 @@ pub async fn patch_profile(app_handle: AppHandle, uid: String, profile: ProfileB
         }
     };
     if need_update {
-        match CoreManager::global().update_config().await {
+        // 修改了当前配置的核心时需要重启
+        match profile_switch::apply_switch(false).await {
             Ok(_) => {
                 handle::Handle::refresh_clash();
             }
@@ -364,6 +523,107 @@ pub async fn patch_profile(app_handle: AppHandle, uid: String, profile: ProfileB
     Ok(())
 }
 
+/// 设置配置使用的核心，为空时跟随全局设置，修改当前配置时立即切换核心
+/// 开启了 `core_switch_confirm` 且未确认时不做修改，返回需要更换的核心
+#[tauri::command]
+#[specta::specta]
+pub async fn set_profile_core_preference(
+    uid: String,
+    core: Option<nyanpasu::ClashCore>,
+    confirm: bool,
+) -> Result<Option<profile_switch::CoreSwitch>> {
+    let _exclusive = profile_activation::global().exclusive().await;
+    let outcome: anyhow::Result<Option<profile_switch::CoreSwitch>> = async {
+        let is_current = {
+            let mut profiles = Config::profiles().draft();
+            let mut item = profiles.get_item(&uid)?.clone();
+            item.set_clash_core(core);
+            profiles.replace_item(&uid, item)?;
+            profiles.get_current().contains(&uid)
+        };
+        if !is_current {
+            return Ok(None);
+        }
+        if !confirm && let Some(switch) = profile_switch::core_switch_requiring_confirmation() {
+            return Ok(Some(switch));
+        }
+        activation::track("switch", profile_switch::apply_switch(false)).await?;
+        handle::Handle::refresh_clash();
+        Ok(None)
+    }
+    .await;
+    match outcome {
+        Ok(None) => {
+            Config::profiles().apply();
+        }
+        // 未确认或切换失败时恢复原来的设置
+        Ok(Some(_)) | Err(_) => {
+            Config::profiles().discard();
+            crate::log_err!(Config::profiles().data().save_file());
+        }
+    }
+    handle::Handle::refresh_profiles();
+    Ok((outcome)?)
+}
+
+/// 解析单个节点分享链接，用于添加前校验
+#[tauri::command]
+#[specta::specta]
+pub fn parse_proxy_link(link: String) -> Result<crate::enhance::convert::ProxyNode> {
+    Ok((crate::enhance::convert::parse_proxy_link(&link))?)
+}
+
+#[derive(Debug, serde::Serialize, specta::Type)]
+pub struct ProxyLinksImport {
+    /// 新建的本地配置，没有可用的节点时不创建
+    pub uid: Option<String>,
+    pub results: Vec<crate::enhance::convert::ProxyLinkReport>,
+}
+
+/// 将多个分享链接导入为一个新的本地配置
+#[tauri::command]
+#[specta::specta]
+pub async fn import_proxy_links(
+    links: Vec<String>,
+    profile_name: String,
+) -> Result<ProxyLinksImport> {
+    use crate::enhance::convert;
+    use profile::item::{LocalProfileBuilder, ProfileShared};
+
+    let profile_name = profile_name.trim();
+    if profile_name.is_empty() {
+        return Err(IpcError::Custom("profile name is empty".to_string()));
+    }
+    let (outbounds, results) = convert::parse_proxy_links(&links);
+    if outbounds.is_empty() {
+        return Ok(ProxyLinksImport { uid: None, results });
+    }
+
+    let mut shared = ProfileShared::get_default_builder(&ProfileItemType::Local);
+    shared.name(profile_name.to_string());
+    let mut builder = LocalProfileBuilder::default();
+    builder.shared(shared);
+    let profile: Profile = builder
+        .build()
+        .context("failed to build local profile")?
+        .into();
+    profile.save_file(serde_yaml::to_string(&convert::proxies_config(&outbounds))?)?;
+    let uid = profile.uid().to_string();
+    (profile_activation::modify_profiles(|profiles| profiles.append_item(profile)).await)?;
+    handle::Handle::refresh_profiles();
+    Ok(ProxyLinksImport {
+        uid: Some(uid),
+        results,
+    })
+}
+
+/// 列出订阅转换的内置模板
+#[tauri::command]
+#[specta::specta]
+pub fn list_conversion_templates() -> Result<Vec<String>> {
+    Ok(crate::enhance::convert::builtin_template_names())
+}
+
 #[tauri::command]
 #[specta::specta]
 pub fn view_profile(app_handle: tauri::AppHandle, uid: String) -> Result {
@@ -421,6 +681,30 @@ pub fn get_clash_info() -> Result<ClashInfo> {
     Ok(Config::clash().latest().get_client_info())
 }
 
+/// 当前连接核心 API 使用的地址
+#[tauri::command]
+#[specta::specta]
+pub fn get_controller_endpoint() -> Result<crate::config::ControllerEndpoint> {
+    Ok(crate::config::ControllerEndpoint::current())
+}
+
+/// 按当前的 secret 与时间（unix 秒）签名，请求中的 `X-Nyanpasu-Timestamp` 需使用返回的时间
+#[tauri::command]
+#[specta::specta]
+pub fn generate_api_request_signature(
+    method: String,
+    path: String,
+) -> Result<crate::core::clash::signing::RequestSignature> {
+    Ok((crate::core::clash::signing::current_signature(&method, &path))?)
+}
+
+/// 当前生效的核心 API 地址，已包含配置文件中的覆盖
+#[tauri::command]
+#[specta::specta]
+pub fn get_controller_url() -> Result<String> {
+    Ok((crate::config::clash_controller_url())?.to_string())
+}
+
 /// get the runtime config
 #[tauri::command]
 #[specta::specta]
@@ -428,7 +712,7 @@ pub fn get_runtime_config() -> Result<Option<serde_json::Value>> {
     let config = Config::runtime().latest().config.clone();
     match config {
         Some(cfg) => {
-            let yaml_value = serde_yaml::to_value(cfg)?;
+            let yaml_value = serde_yaml::to_value(redact_config(cfg))?;
             let json_value = serde_json::to_value(&yaml_value)?;
             Ok(Some(json_value))
         }
@@ -445,11 +729,26 @@ pub fn get_runtime_yaml() -> Result<String> {
     let mapping = (config
         .ok_or(anyhow::anyhow!("failed to parse config to yaml file"))
         .and_then(|config| {
-            serde_yaml::to_string(config).context("failed to convert config to yaml")
+            serde_yaml::to_string(&redact_config(config.clone()))
+                .context("failed to convert config to yaml")
         }))?;
     Ok(mapping)
 }
 
+/// 预览服务模式启动时使用的精简配置
+#[tauri::command]
+#[specta::specta]
+pub fn preview_minimal_config() -> Result<String> {
+    let runtime = Config::runtime();
+    let runtime = runtime.latest();
+    let config = runtime
+        .config
+        .as_ref()
+        .ok_or(anyhow::anyhow!("runtime config is not generated yet"))?;
+    let minimal = crate::config::generate_minimal_service_config(config);
+    Ok(serde_yaml::to_string(&redact_config(minimal))?)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub fn get_runtime_exists() -> Result<Vec<String>> {
@@ -462,6 +761,21 @@ pub fn get_postprocessing_output() -> Result<PostProcessingOutput> {
     Ok(Config::runtime().latest().postprocessing_output.clone())
 }
 
+/// 切换配置前按当前核心检查其中的节点定义，不经过脚本与合并
+#[tauri::command]
+#[specta::specta]
+pub fn validate_profile_proxies(uid: String) -> Result<Vec<ProxyValidationError>> {
+    let (raw, profile_core) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let item = profiles.get_item(&uid)?;
+        ((item.read_file())?, item.clash_core())
+    };
+    let config = (serde_yaml::from_str::<Mapping>(&raw))?;
+    let core = nyanpasu::ClashCore::resolve(profile_core, Config::verge().latest().clash_core);
+    Ok(crate::enhance::use_proxy_validation(&config, core))
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn get_core_status<'n>() -> Result<(Cow<'n, CoreState>, i64, RunType)> {
@@ -480,6 +794,13 @@ pub async fn get_ipsb_asn() -> Result<serde_json::Value> {
     Ok(crate::utils::net::get_ipsb_asn().await?)
 }
 
+/// 系统是否支持 IPv6，供开启 IPv6 前检查
+#[tauri::command]
+#[specta::specta]
+pub fn check_ipv6_connectivity() -> Result<bool> {
+    Ok(crate::utils::net::ipv6_supported())
+}
+
 /// patch clash runtime config
 #[tauri::command]
 #[specta::specta]
@@ -517,6 +838,48 @@ pub async fn patch_verge_config(payload: IVerge) -> Result {
     Ok(())
 }
 
+/// 当前的增强步骤，未设置时为默认顺序
+#[tauri::command]
+#[specta::specta]
+pub fn get_enhance_chain() -> Result<crate::enhance::EnhanceChain> {
+    Ok(Config::verge()
+        .latest()
+        .enhance_chain
+        .clone()
+        .unwrap_or_default())
+}
+
+/// 调整增强步骤的顺序与开关，并重新生成配置
+#[tauri::command]
+#[specta::specta]
+pub async fn set_enhance_chain(chain: crate::enhance::EnhanceChain) -> Result {
+    let patch = IVerge {
+        enhance_chain: Some(chain.normalize()),
+        ..IVerge::default()
+    };
+    (feat::patch_verge(patch).await)?;
+    Ok(())
+}
+
+/// 用户的合并配置原文，文件不存在时为空
+#[tauri::command]
+#[specta::specta]
+pub fn get_merge_overlay() -> Result<String> {
+    let path = (crate::utils::dirs::merge_overlay_path())?;
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    Ok((std::fs::read_to_string(path))?)
+}
+
+/// 保存用户的合并配置并重新生成配置，端口、secret 等受管理的字段会被忽略
+#[tauri::command]
+#[specta::specta]
+pub async fn set_merge_overlay(content: String) -> Result {
+    (feat::save_merge_overlay(content).await)?;
+    Ok(())
+}
+
 /// toggle tun mode with service dependency
 #[tauri::command]
 #[specta::specta]
@@ -531,6 +894,8 @@ pub async fn toggle_tun_mode() -> Result<crate::core::privilege::PrivilegedOpera
     let operation = PrivilegedOperation::SetTunMode {
         enable: !current_enable,
     };
+    crate::core::privilege::validation::guard("toggle_tun_mode", Some(&operation))
+        .map_err(|e| IpcError::Custom(e.to_string()))?;
 
     let result = PrivilegeManager::global()
         .execute_operation(operation)
@@ -547,7 +912,8 @@ pub async fn toggle_tun_mode() -> Result<crate::core::privilege::PrivilegedOpera
 #[tauri::command]
 #[specta::specta]
 pub async fn change_clash_core(clash_core: Option<nyanpasu::ClashCore>) -> Result {
-    (CoreManager::global().change_core(clash_core).await)?;
+    let clash_core = clash_core.context("clash core is null")?;
+    (feat::change_clash_core(clash_core).await)?;
     Ok(())
 }
 
@@ -559,6 +925,13 @@ pub async fn restart_sidecar() -> Result {
     Ok(())
 }
 
+/// 紧急恢复：关闭系统代理与 TUN 并刷新 DNS，返回各步骤结果
+#[tauri::command]
+#[specta::specta]
+pub async fn emergency_reset() -> Result<crate::core::emergency::EmergencyResetReport> {
+    Ok(crate::core::emergency::emergency_reset().await)
+}
+
 /// get the system proxy
 /// server field is the combination of host and port
 #[tauri::command]
@@ -577,70 +950,226 @@ pub fn get_sys_proxy() -> Result<GetSysProxyResponse> {
     })
 }
 
-/// 检查TUN模式权限
+/// 读取系统当前的代理绕过列表
 #[tauri::command]
 #[specta::specta]
-pub async fn check_tun_permission() -> Result<bool> {
-    #[cfg(any(target_os = "macos", target_os = "linux"))]
-    {
-        let current_core = Config::verge().data().clash_core.unwrap_or_default();
-        let current_core: nyanpasu_utils::core::CoreType = (&current_core).into();
-        let service_state = crate::core::service::ipc::get_ipc_state();
+pub fn get_system_bypass_list() -> Result<Vec<String>> {
+    Ok((BypassManager::get_system_bypass_list())?)
+}
 
-        if service_state.is_connected() {
-            // 服务模式下不需要权限检查
-            return Ok(true);
-        }
+/// 替换绕过列表并保存到配置，系统代理开启时才写入系统
+#[tauri::command]
+#[specta::specta]
+pub async fn set_system_bypass_list(entries: Vec<String>) -> Result {
+    BypassManager::validate(&entries)?;
+    (feat::patch_verge(IVerge {
+        system_proxy_bypass: Some(entries),
+        ..IVerge::default()
+    })
+    .await)?;
+    Ok(())
+}
 
-        match crate::utils::dirs::check_core_permission(&current_core) {
-            Ok(has_permission) => Ok(has_permission),
-            Err(e) => {
-                log::error!(target: "app", "Failed to check core permission: {e:?}");
-                Ok(false)
-            }
-        }
-    }
-    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-    {
-        // Windows下通过服务模式或管理员权限处理
-        Ok(true)
-    }
+#[tauri::command]
+#[specta::specta]
+pub async fn add_bypass_entry(entry: String) -> Result<Vec<String>> {
+    Ok((BypassManager::add_bypass_entry(entry).await)?)
 }
 
-/// 授予TUN模式权限
 #[tauri::command]
 #[specta::specta]
-pub async fn grant_tun_permission() -> Result<()> {
-    #[cfg(any(target_os = "macos", target_os = "linux"))]
-    {
-        let current_core = Config::verge().data().clash_core.unwrap_or_default();
-        let current_core: nyanpasu_utils::core::CoreType = (&current_core).into();
+pub async fn remove_bypass_entry(entry: String) -> Result<Vec<String>> {
+    Ok((BypassManager::remove_bypass_entry(entry).await)?)
+}
 
-        match crate::core::manager::grant_permission(&current_core) {
-            Ok(()) => {
-                log::info!(target: "app", "Successfully granted TUN permission to core");
-                Ok(())
-            }
-            Err(e) => {
-                log::error!(target: "app", "Failed to grant TUN permission: {e:?}");
-                Err(IpcError::Custom(format!(
-                    "Failed to grant permission: {}",
-                    e
-                )))
-            }
-        }
-    }
-    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-    {
-        // Windows下不需要此操作
-        Ok(())
-    }
+/// 添加同时从系统代理与核心规则中排除的域名
+#[tauri::command]
+#[specta::specta]
+pub async fn add_global_exclude(domain: String) -> Result<Vec<String>> {
+    Ok((GlobalExcludeList::add(domain).await)?)
 }
 
-/// 检查服务模式权限
 #[tauri::command]
 #[specta::specta]
-pub async fn check_service_permission() -> Result<bool> {
+pub async fn remove_global_exclude(domain: String) -> Result<Vec<String>> {
+    Ok((GlobalExcludeList::remove(domain).await)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn list_global_excludes() -> Result<Vec<String>> {
+    Ok(GlobalExcludeList::entries())
+}
+
+/// 从每行一个域名的文本文件导入，与现有列表合并
+#[tauri::command]
+#[specta::specta]
+pub async fn import_global_excludes_from_file(path: PathBuf) -> Result<Vec<String>> {
+    Ok((GlobalExcludeList::import_from_file(&path).await)?)
+}
+
+/// 校验核心文件与官方 release 的 SHA-256 是否一致
+#[tauri::command]
+#[specta::specta]
+pub async fn verify_core_binary(
+    core: nyanpasu::ClashCore,
+) -> Result<crate::core::service::control::VerificationResult> {
+    use crate::core::service::control;
+    let path = (control::core_binary_path(&core))?;
+    Ok((control::verify_core_binary(core, &path).await)?)
+}
+
+/// 模拟一次系统传入的 deep link，与真实链接走相同的校验与分发流程
+#[tauri::command]
+#[specta::specta]
+pub async fn simulate_deep_link(app_handle: AppHandle, url: String) -> Result<String> {
+    use crate::utils::deep_link::{DeepLinkSource, handle_deep_link};
+    let url = (tauri::async_runtime::spawn_blocking(move || {
+        handle_deep_link(&app_handle, &url, DeepLinkSource::Simulated)
+    })
+    .await
+    .context("deep link task panicked")??);
+    Ok(url.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_strict_routing(enabled: bool) -> Result {
+    Ok((feat::patch_verge(IVerge {
+        strict_routing: Some(enabled),
+        ..IVerge::default()
+    })
+    .await)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn check_split_tunnel_routes()
+-> Result<Vec<crate::utils::platform::strict_routing::SplitTunnelRoute>> {
+    Ok(
+        (tokio::task::spawn_blocking(crate::utils::platform::check_split_tunnel_routes)
+            .await
+            .context("route check task panicked"))?,
+    )
+}
+
+/// 导入同步目录中已提示的配置文件
+#[tauri::command]
+#[specta::specta]
+pub async fn confirm_profile_sync(filename: String) -> StdResult<(), String> {
+    profile_sync::ProfileSyncManager::global()
+        .confirm(&filename)
+        .await
+        .map_err(|e| format!("{e:#}"))
+}
+
+/// 局域网共享的连接地址、二维码内容与安全提示
+#[tauri::command]
+#[specta::specta]
+pub async fn lan_share_info() -> Result<lan_share::LanShareInfo> {
+    Ok(
+        (tauri::async_runtime::spawn_blocking(lan_share::lan_share_info)
+            .await
+            .context("lan share info task panicked")?),
+    )
+}
+
+/// 前端开始监听 `deep-link` 事件后调用，发送启动期间收到的链接
+#[tauri::command]
+#[specta::specta]
+pub fn mark_frontend_ready(app_handle: AppHandle) -> Result {
+    Ok((deep_link::mark_frontend_ready(&app_handle))?)
+}
+
+/// 修复 deep link 的 scheme 注册，返回是否写入了注册信息
+#[tauri::command]
+#[specta::specta]
+pub fn register_deep_link_scheme() -> StdResult<bool, deep_link::DeepLinkError> {
+    deep_link::register_scheme()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn unregister_deep_link_scheme() -> StdResult<bool, deep_link::DeepLinkError> {
+    deep_link::unregister_scheme()
+}
+
+/// 向系统确认 scheme 是否已注册到当前程序
+#[tauri::command]
+#[specta::specta]
+pub fn verify_deep_link_scheme(scheme: String) -> StdResult<bool, deep_link::DeepLinkError> {
+    deep_link::verify_scheme(&scheme)
+}
+
+/// 最近一次配置激活的各阶段耗时
+#[tauri::command]
+#[specta::specta]
+pub fn last_activation_report() -> Result<Option<activation::ActivationReport>> {
+    Ok(activation::last_report())
+}
+
+/// 检查TUN模式权限
+#[tauri::command]
+#[specta::specta]
+pub async fn check_tun_permission() -> Result<bool> {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        let current_core: nyanpasu_utils::core::CoreType = (&Config::clash_core()).into();
+        let service_state = crate::core::service::ipc::get_ipc_state();
+
+        if service_state.is_connected() {
+            // 服务模式下不需要权限检查
+            return Ok(true);
+        }
+
+        match crate::utils::dirs::check_core_permission(&current_core) {
+            Ok(has_permission) => Ok(has_permission),
+            Err(e) => {
+                log::error!(target: "app", "Failed to check core permission: {e:?}");
+                Ok(false)
+            }
+        }
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        // Windows下通过服务模式或管理员权限处理
+        Ok(true)
+    }
+}
+
+/// 授予TUN模式权限
+#[tauri::command]
+#[specta::specta]
+pub async fn grant_tun_permission() -> Result<()> {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        let current_core: nyanpasu_utils::core::CoreType = (&Config::clash_core()).into();
+
+        match crate::core::manager::grant_permission(&current_core) {
+            Ok(()) => {
+                log::info!(target: "app", "Successfully granted TUN permission to core");
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(target: "app", "Failed to grant TUN permission: {e:?}");
+                Err(IpcError::Custom(format!(
+                    "Failed to grant permission: {}",
+                    e
+                )))
+            }
+        }
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        // Windows下不需要此操作
+        Ok(())
+    }
+}
+
+/// 检查服务模式权限
+#[tauri::command]
+#[specta::specta]
+pub async fn check_service_permission() -> Result<bool> {
     let service_state = crate::core::service::ipc::get_ipc_state();
     if service_state.is_connected() {
         return Ok(true);
@@ -766,7 +1295,12 @@ pub async fn grant_autostart_permission() -> Result<()> {
 #[tauri::command]
 #[specta::specta]
 pub fn get_clash_logs() -> Result<VecDeque<String>> {
-    Ok(Logger::global().get_log())
+    let redactor = Redactor::current();
+    Ok(Logger::global()
+        .get_log()
+        .into_iter()
+        .map(|line| redactor.redact_text(&line))
+        .collect())
 }
 
 #[tauri::command]
@@ -851,6 +1385,22 @@ pub async fn get_core_version(
     }
 }
 
+/// 获取当前核心的实际类型、版本与支持的功能
+#[tauri::command]
+#[specta::specta]
+pub async fn get_core_info() -> Result<crate::core::clash::core_info::CoreInfo> {
+    let core = Config::clash_core();
+    Ok((crate::core::clash::core_info::CoreInfo::detect(&core).await)?)
+}
+
+/// 当前核心二进制的版本与构建信息
+#[tauri::command]
+#[specta::specta]
+pub async fn core_version_info() -> Result<crate::core::clash::core_info::CoreVersionInfo> {
+    let core = Config::clash_core();
+    Ok((crate::core::clash::core_info::CoreVersionInfo::probe(core).await)?)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn collect_logs(app_handle: AppHandle) -> Result {
@@ -864,7 +1414,7 @@ pub async fn collect_logs(app_handle: AppHandle) -> Result {
         .save_file(|file_path| match file_path {
             Some(path) if path.as_path().is_some() => {
                 debug!("{path:#?}");
-                match candy::collect_logs(path.as_path().unwrap()) {
+                match diagnostics::export_logs(path.as_path().unwrap()) {
                     Ok(_) => (),
                     Err(err) => {
                         log::error!(target: "app", "{err:?}");
@@ -898,6 +1448,23 @@ pub async fn inspect_updater(updater_id: usize) -> Result<updater::UpdaterSummar
     Ok(updater)
 }
 
+/// 检查应用更新，只返回结果，不会下载
+#[tauri::command]
+#[specta::specta]
+pub async fn check_for_update()
+-> StdResult<updater::app::UpdateInfo, updater::app::UpdateCheckError> {
+    updater::app::check_for_update().await
+}
+
+/// 下载并校验 `check_for_update` 找到的 `version` 版本安装包，返回本地路径，不会自动安装
+#[tauri::command]
+#[specta::specta]
+pub async fn download_update(
+    version: String,
+) -> StdResult<PathBuf, updater::app_download::UpdateDownloadError> {
+    updater::app_download::download_update(version).await
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn clash_api_get_proxy_delay(
@@ -910,6 +1477,28 @@ pub async fn clash_api_get_proxy_delay(
     }
 }
 
+/// 并发测量节点延迟，使用默认的测试地址与并发数
+#[tauri::command]
+#[specta::specta]
+pub async fn run_proxy_benchmark(
+    proxy: String,
+    duration_secs: u32,
+) -> StdResult<clash::bench::BenchmarkResult, String> {
+    if duration_secs == 0 {
+        return Err("duration should be greater than 0".to_string());
+    }
+    clash::api::get_proxy(proxy.clone())
+        .await
+        .map_err(|e| format!("proxy `{proxy}` is not available: {e:#}"))?;
+    Ok(clash::bench::benchmark_proxy(
+        proxy,
+        Vec::new(),
+        clash::bench::DEFAULT_CONCURRENCY,
+        duration_secs,
+    )
+    .await)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn get_proxies() -> Result<crate::core::clash::proxies::Proxies> {
@@ -937,6 +1526,81 @@ pub async fn mutate_proxies() -> Result<crate::core::clash::proxies::Proxies> {
     Ok(ProxiesGuard::global().read().inner().clone())
 }
 
+/// 修改标签或置顶后刷新代理快照，核心未运行时忽略
+async fn refresh_proxy_tags() {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
+    if let Err(e) = ProxiesGuard::global().update().await {
+        tracing::debug!("skip refreshing proxies after tag change: {e:?}");
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn tag_proxy(proxy_name: String, tag: String) -> Result {
+    use crate::core::clash::proxies::ProxyTagSystem;
+    (ProxyTagSystem::global().tag(&proxy_name, &tag))?;
+    refresh_proxy_tags().await;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn untag_proxy(proxy_name: String, tag: String) -> Result {
+    use crate::core::clash::proxies::ProxyTagSystem;
+    (ProxyTagSystem::global().untag(&proxy_name, &tag))?;
+    refresh_proxy_tags().await;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_proxy_note(proxy_name: String, note: String) -> Result {
+    use crate::core::clash::proxies::ProxyTagSystem;
+    (ProxyTagSystem::global().set_note(&proxy_name, &note))?;
+    refresh_proxy_tags().await;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn search_proxies(
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<crate::core::clash::proxy_search::ProxySearchMatch>> {
+    use crate::core::clash::proxy_search;
+    Ok(proxy_search::search_index().search(&query, limit.unwrap_or(50)))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn pin_proxy(name: String) -> Result {
+    (crate::core::clash::proxy_search::pin_proxy(&name))?;
+    refresh_proxy_tags().await;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn unpin_proxy(name: String) -> Result {
+    (crate::core::clash::proxy_search::unpin_proxy(&name))?;
+    refresh_proxy_tags().await;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_proxies_by_tag(tag: String) -> Result<Vec<String>> {
+    use crate::core::clash::proxies::ProxyTagSystem;
+    Ok(ProxyTagSystem::global().proxies_by_tag(&tag))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn list_all_tags() -> Result<Vec<String>> {
+    use crate::core::clash::proxies::ProxyTagSystem;
+    Ok(ProxyTagSystem::global().all_tags())
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn select_proxy(group: String, name: String) -> Result<()> {
@@ -950,6 +1614,41 @@ pub async fn select_proxy(group: String, name: String) -> Result<()> {
     Ok(())
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn set_group_selection(group: String, proxy: String, persist: Option<bool>) -> Result {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
+    ProxiesGuard::global()
+        .set_group_selection(&group, &proxy, persist.unwrap_or(true))
+        .await
+        .map_err(anyhow::Error::from)?;
+
+    let _ = crate::core::connection_interruption::ConnectionInterruptionService::on_proxy_change()
+        .await;
+
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_proxy_providers() -> Result<Vec<crate::core::clash::api::ProviderInfo>> {
+    Ok((crate::core::clash::api::get_proxy_providers().await)?)
+}
+
+/// 代理集合及其刷新间隔、最后一次主动刷新的时间
+#[tauri::command]
+#[specta::specta]
+pub async fn list_proxy_providers() -> Result<Vec<crate::core::clash::api::ProviderInfo>> {
+    Ok((crate::core::clash::provider_refresh::list_proxy_providers().await)?)
+}
+
+/// 立即刷新代理集合，返回刷新后的节点数量
+#[tauri::command]
+#[specta::specta]
+pub async fn force_refresh_proxy_provider(name: String) -> Result<usize> {
+    Ok((crate::core::clash::provider_refresh::force_refresh(&name).await)?)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn update_proxy_provider(name: String) -> Result<()> {
@@ -962,6 +1661,21 @@ pub async fn update_proxy_provider(name: String) -> Result<()> {
     Ok(())
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn refresh_rule_provider(
+    name: String,
+) -> Result<crate::core::clash::api::RuleProviderRefreshResult> {
+    Ok((crate::core::clash::api::refresh_rule_provider(name).await)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn refresh_all_rule_providers()
+-> Result<Vec<crate::core::clash::api::RuleProviderRefreshResult>> {
+    Ok((crate::core::clash::api::refresh_all_rule_providers().await)?)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub fn collect_envs<'a>() -> Result<EnvInfo<'a>> {
@@ -1040,6 +1754,23 @@ pub async fn set_custom_app_dir(app_handle: tauri::AppHandle, path: String) -> R
     Ok(())
 }
 
+/// 确认后备份并恢复默认设置，用户取消时返回 None
+#[tauri::command]
+#[specta::specta]
+pub async fn reset_settings(keep_profiles: bool) -> Result<Option<feat::SettingsReset>> {
+    let backup_dir = (dirs::settings_backup_dir())?;
+    let confirmed = (tauri::async_runtime::spawn_blocking(move || {
+        let msg = rust_i18n::t!("dialog.reset_settings", path = backup_dir.to_string_lossy());
+        crate::utils::dialog::migrate_dialog(&msg)
+    })
+    .await
+    .context("failed to show the confirmation dialog"))?;
+    if !confirmed {
+        return Ok(None);
+    }
+    Ok(Some((feat::reset_settings(keep_profiles).await)?))
+}
+
 #[tauri::command]
 #[specta::specta]
 pub fn restart_application(app_handle: tauri::AppHandle) -> Result {
@@ -1106,10 +1837,101 @@ pub mod uwp {
     }
 }
 
+#[tauri::command]
+#[specta::specta]
+pub fn list_core_plugins(core: nyanpasu::ClashCore) -> Result<Vec<crate::enhance::PluginInfo>> {
+    Ok((crate::enhance::list_core_plugins(core))?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn enable_plugin(core: nyanpasu::ClashCore, name: String) -> Result {
+    (crate::enhance::set_plugin_enabled(core, &name, true))?;
+    reload_plugins().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn disable_plugin(core: nyanpasu::ClashCore, name: String) -> Result {
+    (crate::enhance::set_plugin_enabled(core, &name, false))?;
+    reload_plugins().await
+}
+
+/// 重新生成配置，使插件目录中的改动生效
+#[tauri::command]
+#[specta::specta]
+pub async fn reload_plugins() -> Result {
+    let _exclusive = profile_activation::global().exclusive().await;
+    (CoreManager::global().update_config().await)?;
+    handle::Handle::refresh_clash();
+    Ok(())
+}
+
+/// 读取服务在 SCM 中的故障恢复设置
+#[cfg(windows)]
+#[tauri::command]
+#[specta::specta]
+pub fn get_service_recovery_config() -> Result<crate::core::service::control::ServiceRecoveryConfig>
+{
+    use crate::core::service::control;
+    Ok((control::get_service_recovery_config(control::WINDOWS_SERVICE_LABEL))?)
+}
+
+#[cfg(not(windows))]
+#[tauri::command]
+#[specta::specta]
+pub fn get_service_recovery_config() -> Result<crate::core::service::control::ServiceRecoveryConfig>
+{
+    Err(IpcError::Custom(
+        "service recovery actions are only available on Windows".to_string(),
+    ))
+}
+
+/// 为已安装的服务设置崩溃后自动重启
+#[cfg(windows)]
+#[tauri::command]
+#[specta::specta]
+pub fn configure_service_recovery() -> Result {
+    use crate::core::service::control;
+    (control::configure_service_recovery(control::WINDOWS_SERVICE_LABEL))?;
+    Ok(())
+}
+
+#[cfg(not(windows))]
+#[tauri::command]
+#[specta::specta]
+pub fn configure_service_recovery() -> Result {
+    Err(IpcError::Custom(
+        "service recovery actions are only available on Windows".to_string(),
+    ))
+}
+
+/// 预览服务安装将执行的操作，不会实际安装
+#[tauri::command]
+#[specta::specta]
+pub async fn dry_run_service_install() -> Result<crate::core::service::control::ServiceInstallPlan>
+{
+    Ok((crate::core::service::control::dry_run_install_service().await)?)
+}
+
+/// 直接查询设置中的 DNS 上游，返回可用性与延迟
+#[tauri::command]
+#[specta::specta]
+pub async fn probe_dns_upstreams() -> Result<Vec<crate::core::dns_probe::DnsProbeResult>> {
+    Ok((crate::core::dns_probe::probe_dns_upstreams().await)?)
+}
+
+/// 检查服务 IPC socket 路径是否可用
+#[tauri::command]
+#[specta::specta]
+pub fn validate_ipc_socket_path(path: PathBuf) -> Result {
+    Ok((crate::core::service::control::validate_ipc_socket_path(&path))?)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn get_service_install_prompt() -> Result<String> {
-    let args = (crate::core::service::control::get_service_install_args().await)?
+    let args = (crate::core::service::control::get_service_install_args(true).await)?
         .into_iter()
         .map(|arg| arg.to_string_lossy().to_string())
         .collect::<Vec<_>>()
@@ -1161,6 +1983,386 @@ pub async fn get_clash_ws_connections_state(
     Ok(ws_connector.state())
 }
 
+/// 统计最近 limit 条连接使用的策略与规则
+#[tauri::command]
+#[specta::specta]
+pub fn analyze_traffic_policies(
+    app_handle: AppHandle,
+    limit: usize,
+) -> Result<crate::core::clash::traffic_policy::PolicyAnalysis> {
+    use crate::core::clash::traffic_policy;
+    let ws_connector = app_handle.state::<crate::core::clash::ws::ClashConnectionsConnector>();
+    let connections = ws_connector.recent_connections(limit);
+    Ok(traffic_policy::analyze_traffic_policies(
+        &connections,
+        limit,
+    ))
+}
+
+/// 根据连接历史找出从未命中的规则
+#[tauri::command]
+#[specta::specta]
+pub fn suggest_rule_optimizations(
+    app_handle: AppHandle,
+) -> Result<Vec<crate::core::clash::traffic_policy::RuleOptimizationSuggestion>> {
+    use crate::core::clash::traffic_policy;
+    let ws_connector = app_handle.state::<crate::core::clash::ws::ClashConnectionsConnector>();
+    let connections = ws_connector.recent_connections(usize::MAX);
+    let rules = traffic_policy::runtime_rules(Config::runtime().latest().config.as_ref());
+    Ok(traffic_policy::suggest_rule_optimizations(
+        &rules,
+        &connections,
+    ))
+}
+
+/// 按指定方式汇总活跃连接，之后通过 `connections-groups-updated` 事件推送
+#[tauri::command]
+#[specta::specta]
+pub fn get_connection_groups(
+    app_handle: AppHandle,
+    by: crate::core::clash::ws::GroupingKey,
+) -> Result<Vec<crate::core::clash::ws::ConnectionGroup>> {
+    let ws_connector = app_handle.state::<crate::core::clash::ws::ClashConnectionsConnector>();
+    Ok(ws_connector.connection_groups(by))
+}
+
+/// 扫描配置与数据目录，按可清理的分类统计
+#[tauri::command]
+#[specta::specta]
+pub async fn data_dir_report() -> Result<crate::core::data_dir::DataDirReport> {
+    Ok(
+        (tokio::task::spawn_blocking(crate::core::data_dir::data_dir_report)
+            .await
+            .context("data dir scan task panicked"))??,
+    )
+}
+
+/// 清理所选分类，`dry_run` 时只返回将被删除的条目
+#[tauri::command]
+#[specta::specta]
+pub async fn data_dir_cleanup(
+    categories: Vec<crate::core::data_dir::CleanupCategory>,
+    dry_run: bool,
+) -> Result<Vec<crate::core::data_dir::CleanupResult>> {
+    Ok((crate::core::data_dir::data_dir_cleanup(categories, dry_run).await)?)
+}
+
+/// 本次启动时因上次崩溃而关闭的系统代理，没有恢复时为空
+#[tauri::command]
+#[specta::specta]
+pub fn get_sysproxy_recovery() -> Result<Option<crate::core::proxy_recovery::ProxyRecovery>> {
+    Ok(crate::core::proxy_recovery::last_recovery())
+}
+
+/// 上一次运行结束时的活跃连接，没有记录时为空
+#[tauri::command]
+#[specta::specta]
+pub fn get_last_connection_session()
+-> Result<Option<crate::core::clash::connection_session::ConnectionSession>> {
+    Ok(crate::core::clash::connection_session::last_session())
+}
+
+/// 导出连接日志中关闭时间在范围内的记录，返回导出的条数
+#[tauri::command]
+#[specta::specta]
+pub async fn export_connections(
+    range: clash::connection_log::TimeRange,
+    format: clash::connection_log::ExportFormat,
+    dest: String,
+) -> Result<u64> {
+    Ok(tokio::task::spawn_blocking(move || {
+        clash::connection_log::ConnectionLogger::global().export(
+            &range,
+            format,
+            std::path::Path::new(&dest),
+        )
+    })
+    .await
+    .context("export task panicked")??)
+}
+
+/// 删除关闭时间早于 `before` 的连接日志，返回删除的条数
+#[tauri::command]
+#[specta::specta]
+pub async fn purge_connection_history(before: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+    Ok(tokio::task::spawn_blocking(move || {
+        clash::connection_log::ConnectionLogger::global().purge(before)
+    })
+    .await
+    .context("purge task panicked")??)
+}
+
+/// 上次崩溃遗留、仍在运行的核心进程
+#[tauri::command]
+#[specta::specta]
+pub async fn orphaned_cores() -> Result<Vec<clash::orphan::OrphanedCore>> {
+    Ok(tokio::task::spawn_blocking(clash::orphan::orphaned_cores)
+        .await
+        .context("failed to scan processes")?)
+}
+
+/// 结束遗留的核心进程，返回未能结束的进程
+#[tauri::command]
+#[specta::specta]
+pub async fn kill_orphaned_cores() -> Result<Vec<clash::orphan::OrphanedCore>> {
+    Ok(tokio::task::spawn_blocking(|| {
+        let table = clash::orphan::SystemProcessTable;
+        clash::orphan::kill_orphaned_cores(&table, clash::orphan::orphaned_cores_with(&table))
+    })
+    .await
+    .context("failed to scan processes")?)
+}
+
+/// 开启或关闭匿名遥测，首次开启时生成匿名 id
+#[tauri::command]
+#[specta::specta]
+pub async fn set_telemetry_enabled(enabled: bool) -> Result {
+    let mut telemetry = Config::verge()
+        .latest()
+        .telemetry
+        .clone()
+        .unwrap_or_default();
+    if enabled && telemetry.user_id.is_empty() {
+        telemetry.user_id = crate::utils::telemetry::new_user_id();
+    }
+    telemetry.enabled = enabled;
+    Ok((feat::patch_verge(IVerge {
+        telemetry: Some(telemetry),
+        ..IVerge::default()
+    })
+    .await)?)
+}
+
+/// 匿名 id，只在开启时生成，关闭后清除，未开启遥测时为空字符串
+#[tauri::command]
+#[specta::specta]
+pub fn get_telemetry_id() -> Result<String> {
+    Ok(Config::verge()
+        .latest()
+        .telemetry
+        .as_ref()
+        .filter(|telemetry| telemetry.enabled)
+        .map(|telemetry| telemetry.user_id.clone())
+        .unwrap_or_default())
+}
+
+/// 开启遥测后将要发送的内容
+#[tauri::command]
+#[specta::specta]
+pub fn preview_telemetry_payload() -> Result<String> {
+    let payload = crate::utils::telemetry::build_payload()?;
+    Ok(serde_json::to_string_pretty(&payload).context("failed to serialize payload")?)
+}
+
+/// 设置网络统计浮窗是否置顶
+#[tauri::command]
+#[specta::specta]
+pub async fn set_widget_always_on_top(
+    app_handle: AppHandle,
+    variant: nyanpasu_egui::widget::StatisticWidgetVariant,
+    enabled: bool,
+) -> Result {
+    let widget_manager = app_handle.state::<crate::widget::WidgetManager>();
+    (widget_manager.set_always_on_top(variant, enabled).await)?;
+    Ok(())
+}
+
+/// 单条连接的详情，刚关闭的连接在约 60 秒内仍可查询
+#[tauri::command]
+#[specta::specta]
+pub async fn connection_details(
+    app_handle: AppHandle,
+    id: String,
+) -> Result<crate::core::clash::connection_details::ConnectionDetails> {
+    let cached = app_handle
+        .state::<crate::core::clash::ws::ClashConnectionsConnector>()
+        .find_connection(&id);
+    Ok(crate::core::clash::connection_details::connection_details(&id, cached).await?)
+}
+
+/// 测量到 external-controller 的延迟（毫秒），不可达时为空
+#[tauri::command]
+#[specta::specta]
+pub async fn controller_ping() -> Result<Option<u64>> {
+    Ok(crate::core::clash::api::controller_ping()
+        .await
+        .map(|latency| latency.as_millis() as u64))
+}
+
+/// 当前的轮询频率档位，变化时推送 `polling-profile-changed` 事件
+#[tauri::command]
+#[specta::specta]
+pub fn get_polling_profile() -> Result<crate::core::activity::PollingProfile> {
+    Ok(crate::core::activity::ActivityManager::global().profile())
+}
+
+/// 获取 external-controller 的 secret
+#[tauri::command]
+#[specta::specta]
+pub fn get_controller_secret() -> Result<Option<String>> {
+    Ok(crate::config::ClashController::current().secret)
+}
+
+/// 生成新的 secret 并重启核心，返回新的 secret
+#[tauri::command]
+#[specta::specta]
+pub async fn rotate_controller_secret() -> Result<String> {
+    Ok((feat::rotate_controller_secret().await)?)
+}
+
+/// 获取核心当前实际运行的模式
+#[tauri::command]
+#[specta::specta]
+pub async fn get_clash_mode() -> Result<crate::utils::config::ClashMode> {
+    Ok(crate::utils::config::current_clash_mode().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_clash_mode(mode: crate::utils::config::ClashMode) -> Result {
+    (feat::set_clash_mode(mode).await)?;
+    feat::update_proxies_buff(None);
+    let _ =
+        crate::core::connection_interruption::ConnectionInterruptionService::on_mode_change().await;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_ipc_metrics(app_handle: AppHandle) -> Result<crate::core::service::ipc::IpcMetrics> {
+    let metrics = app_handle.state::<crate::core::service::ipc::IpcMetricsState>();
+    Ok(metrics.lock().clone())
+}
+
+/// 探测服务器发送的证书链，便于找到需要信任的 CA
+#[tauri::command]
+#[specta::specta]
+pub async fn probe_tls(url: String) -> Result<crate::utils::tls::TlsProbe> {
+    let url = url::Url::parse(&url).context("invalid url")?;
+    Ok((crate::utils::tls::probe_tls(&url).await)?)
+}
+
+/// 导出诊断包到 `dest`，其中的 secret 与订阅凭据已脱敏
+#[tauri::command]
+#[specta::specta]
+pub async fn export_diagnostics(app_handle: AppHandle, dest: PathBuf) -> Result {
+    let metrics = app_handle
+        .state::<crate::core::service::ipc::IpcMetricsState>()
+        .lock()
+        .clone();
+    (crate::core::diagnostics::export_diagnostics(&dest, metrics).await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn start_network_monitor() -> Result {
+    (crate::utils::net::NetworkChangeDetector::global().start())?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn stop_network_monitor() -> Result {
+    crate::utils::net::NetworkChangeDetector::global().stop();
+    Ok(())
+}
+
+/// 获取最近一段时间的流量曲线
+#[tauri::command]
+#[specta::specta]
+pub fn traffic_recent(
+    window_secs: u64,
+    resolution: crate::core::clash::statistics::TrafficResolution,
+) -> Result<crate::core::clash::statistics::TrafficSeries> {
+    use crate::core::clash::statistics::TrafficHistory;
+    let history = TrafficHistory::global().lock();
+    Ok(history.recent(TrafficHistory::now(), window_secs, resolution))
+}
+
+/// 获取经过鉴权代理的外部 UI 地址
+#[tauri::command]
+#[specta::specta]
+pub fn get_dashboard_url(app_handle: AppHandle) -> Result<String> {
+    let proxy = app_handle
+        .try_state::<crate::core::clash::api::ClashExtUIProxy>()
+        .context("external ui proxy is not running")?;
+    Ok(proxy.dashboard_url())
+}
+
 // Updater block
 // NOTE: 自动更新功能现在由 tauri-plugin-updater 直接处理
 // 旧的 UpdateWrapper 和 check_update 已移除，前端应使用 tauri-plugin-updater 的 API
+
+/// 通过 mixed port 测速，指定节点时测速结束后恢复原选择
+#[tauri::command]
+#[specta::specta]
+pub async fn speedtest(
+    node: Option<String>,
+    duration_secs: u64,
+    direction: crate::core::speedtest::SpeedtestDirection,
+) -> Result<crate::core::speedtest::SpeedtestResult> {
+    Ok((crate::core::speedtest::speedtest(node, duration_secs, direction).await)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn cancel_speedtest() -> Result {
+    crate::core::speedtest::cancel();
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn speedtest_history() -> Result<Vec<crate::core::speedtest::SpeedtestResult>> {
+    Ok(crate::core::speedtest::history())
+}
+
+/// 设置代理组的智能选择策略（当前 profile）
+#[tauri::command]
+#[specta::specta]
+pub async fn set_group_policy(
+    group: String,
+    policy: crate::core::clash::policy::GroupPolicy,
+) -> Result {
+    use crate::core::clash::policy::GroupPolicyManager;
+    (GroupPolicyManager::global().set(group, policy).await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_group_policy(group: String) -> Result {
+    use crate::core::clash::policy::GroupPolicyManager;
+    (GroupPolicyManager::global().remove(&group).await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn list_group_policies() -> Result<crate::core::clash::policy::GroupPolicies> {
+    use crate::core::clash::policy::GroupPolicyManager;
+    Ok(GroupPolicyManager::global().list())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_group_policy_decisions() -> Result<Vec<crate::core::clash::policy::PolicyDecision>> {
+    use crate::core::clash::policy::GroupPolicyManager;
+    Ok(GroupPolicyManager::global().decisions())
+}
+
+/// 当前平台是否已注册系统休眠唤醒通知
+#[tauri::command]
+#[specta::specta]
+pub fn is_system_sleep_supported() -> Result<bool> {
+    Ok(crate::setup::SleepWakeHandler::global().is_supported())
+}
+
+/// 重新解析节点名称中的地区、倍率等信息
+#[tauri::command]
+#[specta::specta]
+pub async fn reparse_node_metadata() -> Result {
+    (crate::core::clash::node_meta::reparse_node_metadata().await)?;
+    Ok(())
+}
@@ -10,6 +10,7 @@ mod config;
 mod consts;
 mod core;
 mod enhance;
+mod event_handler;
 mod feat;
 mod ipc;
 mod server;
@@ -53,9 +54,8 @@ use crate::{
 use anyhow::Context;
 #[cfg(debug_assertions)]
 use specta_typescript::{BigIntExportBehavior, Typescript};
-use tauri::Emitter;
 use tauri_specta::collect_commands;
-use utils::resolve::{is_window_opened, reset_window_open_counter};
+use utils::resolve::reset_window_open_counter;
 
 rust_i18n::i18n!("../../locales");
 
@@ -166,6 +166,7 @@ pub fn run() -> std::io::Result<()> {
         }
 
         crate::log_err!(init::init_config());
+        utils::telemetry::init();
 
         // Panic Hook to show a panic dialog and save logs
         std::panic::set_hook(Box::new(move |panic_info| {
@@ -197,6 +198,14 @@ pub fn run() -> std::io::Result<()> {
                 panic.note = note,
                 "A panic occurred",
             );
+            utils::telemetry::AppCrashReporter::record_panic(
+                payload,
+                location.as_deref(),
+                &backtrace
+                    .as_ref()
+                    .map(|backtrace| backtrace.to_string())
+                    .unwrap_or_default(),
+            );
 
             // This is a workaround for the upstream issue: https://github.com/tauri-apps/tauri/issues/10546
             if let Some(s) = payload.as_ref()
@@ -234,6 +243,14 @@ pub fn run() -> std::io::Result<()> {
     let specta_builder = tauri_specta::Builder::<tauri::Wry>::new().commands(collect_commands![
         // common
         ipc::get_sys_proxy,
+        ipc::get_system_bypass_list,
+        ipc::set_system_bypass_list,
+        ipc::add_bypass_entry,
+        ipc::remove_bypass_entry,
+        ipc::add_global_exclude,
+        ipc::remove_global_exclude,
+        ipc::list_global_excludes,
+        ipc::import_global_excludes_from_file,
         ipc::open_app_config_dir,
         ipc::open_app_data_dir,
         ipc::open_logs_dir,
@@ -241,27 +258,54 @@ pub fn run() -> std::io::Result<()> {
         ipc::open_core_dir,
         // cmds::kill_sidecar,
         ipc::restart_sidecar,
+        ipc::emergency_reset,
         // clash
         ipc::get_clash_info,
+        ipc::get_controller_endpoint,
+        ipc::get_controller_url,
+        ipc::generate_api_request_signature,
         ipc::get_clash_logs,
         ipc::patch_clash_config,
         ipc::change_clash_core,
+        ipc::get_clash_mode,
+        ipc::controller_ping,
+        ipc::get_polling_profile,
+        ipc::get_controller_secret,
+        ipc::rotate_controller_secret,
+        ipc::set_clash_mode,
         ipc::get_runtime_config,
         ipc::get_runtime_yaml,
+        ipc::preview_minimal_config,
         ipc::get_runtime_exists,
         ipc::get_postprocessing_output,
+        ipc::validate_profile_proxies,
         ipc::clash_api_get_proxy_delay,
+        ipc::run_proxy_benchmark,
         ipc::uwp::invoke_uwp_tool,
         // updater
         ipc::fetch_latest_core_versions,
         ipc::update_core,
         ipc::inspect_updater,
+        ipc::check_for_update,
+        ipc::download_update,
         ipc::get_core_version,
+        ipc::core_version_info,
+        ipc::get_core_info,
+        ipc::verify_core_binary,
+        ipc::simulate_deep_link,
+        ipc::mark_frontend_ready,
+        ipc::register_deep_link_scheme,
+        ipc::unregister_deep_link_scheme,
+        ipc::verify_deep_link_scheme,
         // utils
         ipc::collect_logs,
         // verge
         ipc::get_verge_config,
         ipc::patch_verge_config,
+        ipc::get_enhance_chain,
+        ipc::set_enhance_chain,
+        ipc::get_merge_overlay,
+        ipc::set_merge_overlay,
         ipc::toggle_tun_mode,
         ipc::check_tun_permission,
         ipc::grant_tun_permission,
@@ -276,13 +320,33 @@ pub fn run() -> std::io::Result<()> {
         ipc::get_profiles,
         ipc::enhance_profiles,
         ipc::patch_profiles_config,
+        ipc::switch_profiles,
+        ipc::activate_profile,
+        ipc::current_activation,
+        ipc::last_activation_report,
+        ipc::lan_share_info,
+        ipc::set_strict_routing,
+        ipc::check_split_tunnel_routes,
+        ipc::confirm_profile_sync,
         ipc::view_profile,
+        ipc::list_conversion_templates,
+        ipc::parse_proxy_link,
+        ipc::import_proxy_links,
         ipc::patch_profile,
+        ipc::set_profile_core_preference,
         ipc::create_profile,
         ipc::import_profile,
         ipc::reorder_profile,
         ipc::reorder_profiles_by_list,
+        ipc::create_profile_folder,
+        ipc::move_profile_to_folder,
+        ipc::rename_folder,
+        ipc::delete_profile_folder,
+        ipc::list_profiles_grouped,
+        ipc::set_profile_order,
+        ipc::update_all_profiles,
         ipc::update_profile,
+        ipc::profile_last_diff,
         ipc::delete_profile,
         ipc::read_profile_file,
         ipc::save_profile_file,
@@ -305,16 +369,36 @@ pub fn run() -> std::io::Result<()> {
         crate::core::privilege::ipc_commands::get_privilege_status,
         crate::core::privilege::ipc_commands::get_current_privilege_mode,
         crate::core::privilege::ipc_commands::execute_privilege_operation,
+        crate::core::privilege::ipc_commands::batch_privilege_operation,
         crate::core::privilege::ipc_commands::precheck_privilege_operation,
         crate::core::privilege::ipc_commands::get_privilege_recommendations,
         crate::core::privilege::ipc_commands::auto_setup_service_mode,
         crate::core::privilege::ipc_commands::check_service_mode_availability,
         crate::core::privilege::ipc_commands::test_privilege_system,
         ipc::is_portable,
+        ipc::is_safe_mode,
+        ipc::suggest_available_port,
+        ipc::log_redaction_test,
+        ipc::runtime_paths,
         ipc::get_proxies,
         ipc::select_proxy,
+        ipc::tag_proxy,
+        ipc::untag_proxy,
+        ipc::set_proxy_note,
+        ipc::get_proxies_by_tag,
+        ipc::search_proxies,
+        ipc::pin_proxy,
+        ipc::unpin_proxy,
+        ipc::list_all_tags,
+        ipc::set_group_selection,
+        ipc::get_proxy_providers,
+        ipc::list_proxy_providers,
+        ipc::force_refresh_proxy_provider,
         ipc::update_proxy_provider,
+        ipc::refresh_rule_provider,
+        ipc::refresh_all_rule_providers,
         ipc::restart_application,
+        ipc::reset_settings,
         ipc::collect_envs,
         ipc::get_server_port,
         ipc::set_tray_icon,
@@ -322,9 +406,22 @@ pub fn run() -> std::io::Result<()> {
         ipc::get_core_status,
         ipc::url_delay_test,
         ipc::get_ipsb_asn,
+        ipc::check_ipv6_connectivity,
         ipc::open_that,
         ipc::is_appimage,
         ipc::get_service_install_prompt,
+        ipc::dry_run_service_install,
+        ipc::get_service_recovery_config,
+        ipc::configure_service_recovery,
+        ipc::list_core_plugins,
+        ipc::enable_plugin,
+        ipc::disable_plugin,
+        ipc::reload_plugins,
+        ipc::validate_ipc_socket_path,
+        ipc::probe_dns_upstreams,
+        ipc::get_ipc_metrics,
+        ipc::export_diagnostics,
+        ipc::probe_tls,
         ipc::cleanup_processes,
         ipc::get_storage_item,
         ipc::set_storage_item,
@@ -333,6 +430,35 @@ pub fn run() -> std::io::Result<()> {
         ipc::get_core_dir,
         // clash layer
         ipc::get_clash_ws_connections_state,
+        ipc::analyze_traffic_policies,
+        ipc::suggest_rule_optimizations,
+        ipc::connection_details,
+        ipc::get_connection_groups,
+        ipc::get_last_connection_session,
+        ipc::get_sysproxy_recovery,
+        ipc::data_dir_report,
+        ipc::data_dir_cleanup,
+        ipc::export_connections,
+        ipc::purge_connection_history,
+        ipc::orphaned_cores,
+        ipc::kill_orphaned_cores,
+        ipc::set_telemetry_enabled,
+        ipc::get_telemetry_id,
+        ipc::preview_telemetry_payload,
+        ipc::set_widget_always_on_top,
+        ipc::get_dashboard_url,
+        ipc::traffic_recent,
+        ipc::start_network_monitor,
+        ipc::stop_network_monitor,
+        ipc::speedtest,
+        ipc::cancel_speedtest,
+        ipc::speedtest_history,
+        ipc::set_group_policy,
+        ipc::remove_group_policy,
+        ipc::list_group_policies,
+        ipc::get_group_policy_decisions,
+        ipc::is_system_sleep_supported,
+        ipc::reparse_node_metadata,
         // updater layer
     ]);
 
@@ -435,34 +561,26 @@ pub fn run() -> std::io::Result<()> {
             #[cfg(not(target_os = "macos"))]
             if let Some(url) = custom_scheme {
                 log::info!(target: "app", "started with schema");
-                resolve::create_window(&handle.clone());
-                while !is_window_opened() {
-                    log::info!(target: "app", "waiting for window open");
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                }
-                Handle::global()
-                    .app_handle
-                    .lock()
-                    .as_ref()
-                    .unwrap()
-                    .emit("scheme-request-received", url.clone())
-                    .unwrap();
+                let _ = utils::deep_link::handle_deep_link(
+                    &handle,
+                    url.as_str(),
+                    utils::deep_link::DeepLinkSource::LaunchArgs,
+                );
             }
             // This operation should terminate the app if app is called by custom scheme and this instance is not the primary instance
             // Disable deep-link registration in debug builds to prevent crashes during development
             #[cfg(not(debug_assertions))]
             {
-                if let Err(e) =
-                    tauri_plugin_deep_link::register(&["clash-nyanpasu", "clash"], move |request| {
-                        log::info!(target: "app", "scheme request received: {:?}", &request);
-                        resolve::create_window(&handle.clone()); // create window if not exists
-                        while !is_window_opened() {
-                            log::info!(target: "app", "waiting for window open");
-                            std::thread::sleep(std::time::Duration::from_millis(100));
-                        }
-                        handle.emit("scheme-request-received", request).unwrap();
-                    })
-                {
+                if let Err(e) = tauri_plugin_deep_link::register(
+                    &utils::deep_link::DEEP_LINK_SCHEMES,
+                    move |request| {
+                        let _ = utils::deep_link::handle_deep_link(
+                            &handle,
+                            &request,
+                            utils::deep_link::DeepLinkSource::Os,
+                        );
+                    },
+                ) {
                     log::warn!(target: "app", "Failed to register deep link: {}", e);
                 }
             }
@@ -505,8 +623,17 @@ pub fn run() -> std::io::Result<()> {
             tauri::WindowEvent::Destroyed => {
                 log::debug!(target: "app", "window destroyed");
                 reset_window_open_counter();
+                crate::core::activity::ActivityManager::global().set_visible(false);
+            }
+            tauri::WindowEvent::Focused(true) => {
+                crate::core::activity::ActivityManager::global().set_visible(true);
+            }
+            // 失去焦点、最小化或还原时按窗口的实际状态更新
+            tauri::WindowEvent::Focused(false) => {
+                crate::core::activity::ActivityManager::global().sync_window(app_handle);
             }
             tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) => {
+                crate::core::activity::ActivityManager::global().sync_window(app_handle);
                 log::debug!(target: "app", "window moved or resized");
                 std::thread::sleep(std::time::Duration::from_nanos(1));
                 let _ = resolve::save_window_state(app_handle, false);
@@ -1,6 +1,14 @@
 //! Setup logic for the app
 #[cfg(target_os = "windows")]
 use anyhow::Context;
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(_app: &M) -> Result<(), anyhow::Error> {
     #[cfg(target_os = "windows")]
@@ -12,8 +20,239 @@ pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(_app: &M) -> Result<(), an
         })
         .context("Failed to setup the shutdown hook")?;
     }
+    #[cfg(unix)]
+    {
+        let app_handle = _app.app_handle().clone();
+        tauri::async_runtime::spawn(async move {
+            crate::core::shutdown::wait_for_termination().await;
+            tracing::info!("Termination signal received, exiting app...");
+            app_handle.exit(0);
+        });
+    }
+
+    if let Err(e) = SleepWakeHandler::global().register() {
+        tracing::warn!("Failed to register sleep/wake notifications: {e:#}");
+    }
 
     // FIXME: this is a background setup, so be careful use this state in ipc.
     // crate::logging::setup(app).context("Failed to setup logging")?;
     Ok(())
 }
+
+/// 唤醒后等待网络恢复的时间
+const WAKE_SETTLE_DELAY: Duration = Duration::from_secs(3);
+/// 部分平台一次唤醒会发出多个通知，时间窗口内只处理一次
+const WAKE_DEBOUNCE: Duration = Duration::from_secs(10);
+
+/// 系统休眠唤醒处理
+/// 唤醒后网络接口会重新配置，需检查服务状态并可选重启核心
+pub struct SleepWakeHandler {
+    supported: AtomicBool,
+    last_wake: Mutex<Option<Instant>>,
+}
+
+impl SleepWakeHandler {
+    pub fn global() -> &'static SleepWakeHandler {
+        static HANDLER: OnceLock<SleepWakeHandler> = OnceLock::new();
+        HANDLER.get_or_init(|| SleepWakeHandler {
+            supported: AtomicBool::new(false),
+            last_wake: Mutex::new(None),
+        })
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.supported.load(Ordering::Acquire) && platform::is_listening()
+    }
+
+    fn register(&self) -> anyhow::Result<()> {
+        platform::watch()?;
+        self.supported.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// 由各平台的监听回调调用
+    pub fn notify_wake(&self) {
+        {
+            let mut last_wake = self.last_wake.lock();
+            if last_wake.is_some_and(|at| at.elapsed() < WAKE_DEBOUNCE) {
+                return;
+            }
+            *last_wake = Some(Instant::now());
+        }
+        tracing::info!("system woke up from sleep");
+        tauri::async_runtime::spawn(async {
+            tokio::time::sleep(WAKE_SETTLE_DELAY).await;
+            let (service_mode, restart_core) = {
+                let verge = crate::config::Config::verge();
+                let verge = verge.latest();
+                (
+                    verge.enable_service_mode.unwrap_or(false),
+                    verge.restart_core_on_wake.unwrap_or(false),
+                )
+            };
+            if service_mode {
+                crate::core::service::ipc::health_check().await;
+            }
+            crate::log_err!(crate::core::handle::Handle::emit("system-wake", ()));
+            if restart_core {
+                crate::log_err!(
+                    crate::core::CoreManager::global().run_core().await,
+                    "failed to restart core after system wake"
+                );
+            }
+        });
+    }
+}
+
+mod platform {
+    #[cfg(target_os = "linux")]
+    pub fn watch() -> anyhow::Result<()> {
+        use anyhow::Context;
+        use zbus::blocking::{Connection, Proxy};
+
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("sleep-wake-monitor".into())
+            .spawn(move || {
+                let signals = Connection::system()
+                    .and_then(|connection| {
+                        Proxy::new(
+                            &connection,
+                            "org.freedesktop.login1",
+                            "/org/freedesktop/login1",
+                            "org.freedesktop.login1.Manager",
+                        )
+                    })
+                    .and_then(|proxy| proxy.receive_signal("PrepareForSleep"));
+                let signals = match signals {
+                    Ok(signals) => {
+                        let _ = ready_tx.send(Ok(()));
+                        signals
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(anyhow::Error::from(e)));
+                        return;
+                    }
+                };
+                // PrepareForSleep(true) 表示即将休眠，false 表示已唤醒
+                for msg in signals {
+                    match msg.body().deserialize::<bool>() {
+                        Ok(false) => super::SleepWakeHandler::global().notify_wake(),
+                        Ok(true) => tracing::debug!("system is going to sleep"),
+                        Err(e) => tracing::warn!("invalid PrepareForSleep signal: {e}"),
+                    }
+                }
+            })?;
+        ready_rx
+            .recv()
+            .context("sleep/wake monitor thread exited unexpectedly")?
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn watch() -> anyhow::Result<()> {
+        use anyhow::Context;
+        use core_foundation::{
+            base::TCFType,
+            runloop::{CFRunLoop, CFRunLoopSource, CFRunLoopSourceRef, kCFRunLoopCommonModes},
+        };
+        use std::{
+            ffi::c_void,
+            sync::atomic::{AtomicU32, Ordering},
+        };
+
+        type IONotificationPortRef = *mut c_void;
+        const K_IO_MESSAGE_CAN_SYSTEM_SLEEP: u32 = 0xe000_0270;
+        const K_IO_MESSAGE_SYSTEM_WILL_SLEEP: u32 = 0xe000_0280;
+        const K_IO_MESSAGE_SYSTEM_HAS_POWERED_ON: u32 = 0xe000_0300;
+
+        #[link(name = "IOKit", kind = "framework")]
+        unsafe extern "C" {
+            fn IORegisterForSystemPower(
+                refcon: *mut c_void,
+                the_port_ref: *mut IONotificationPortRef,
+                callback: extern "C" fn(*mut c_void, u32, u32, *mut c_void),
+                notifier: *mut u32,
+            ) -> u32;
+            fn IONotificationPortGetRunLoopSource(
+                notify: IONotificationPortRef,
+            ) -> CFRunLoopSourceRef;
+            fn IOAllowPowerChange(kernel_port: u32, notification_id: isize) -> i32;
+        }
+
+        // IOPMrootDomain 的连接，休眠前需通过它确认
+        static ROOT_PORT: AtomicU32 = AtomicU32::new(0);
+
+        extern "C" fn callback(
+            _refcon: *mut c_void,
+            _service: u32,
+            message_type: u32,
+            argument: *mut c_void,
+        ) {
+            match message_type {
+                K_IO_MESSAGE_CAN_SYSTEM_SLEEP | K_IO_MESSAGE_SYSTEM_WILL_SLEEP => unsafe {
+                    IOAllowPowerChange(ROOT_PORT.load(Ordering::Acquire), argument as isize);
+                },
+                K_IO_MESSAGE_SYSTEM_HAS_POWERED_ON => {
+                    super::SleepWakeHandler::global().notify_wake()
+                }
+                _ => {}
+            }
+        }
+
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("sleep-wake-monitor".into())
+            .spawn(move || {
+                let mut port: IONotificationPortRef = std::ptr::null_mut();
+                let mut notifier = 0u32;
+                let root_port = unsafe {
+                    IORegisterForSystemPower(
+                        std::ptr::null_mut(),
+                        &mut port,
+                        callback,
+                        &mut notifier,
+                    )
+                };
+                if root_port == 0 {
+                    let _ = ready_tx.send(Err(anyhow::anyhow!("IORegisterForSystemPower failed")));
+                    return;
+                }
+                ROOT_PORT.store(root_port, Ordering::Release);
+                let source = unsafe {
+                    CFRunLoopSource::wrap_under_get_rule(IONotificationPortGetRunLoopSource(port))
+                };
+                CFRunLoop::get_current().add_source(&source, unsafe { kCFRunLoopCommonModes });
+                let _ = ready_tx.send(Ok(()));
+                CFRunLoop::run_current();
+            })?;
+        ready_rx
+            .recv()
+            .context("sleep/wake monitor thread exited unexpectedly")?
+    }
+
+    /// Windows 由关机钩子的隐藏窗口接收 WM_POWERBROADCAST
+    #[cfg(windows)]
+    pub fn watch() -> anyhow::Result<()> {
+        if !is_listening() {
+            anyhow::bail!("the shutdown hook window is not running");
+        }
+        Ok(())
+    }
+
+    /// 隐藏窗口的消息循环退出后不再能收到唤醒通知
+    #[cfg(windows)]
+    pub fn is_listening() -> bool {
+        crate::shutdown_hook::is_listening()
+    }
+
+    /// 监听线程注册成功后一直运行
+    #[cfg(not(windows))]
+    pub fn is_listening() -> bool {
+        true
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    pub fn watch() -> anyhow::Result<()> {
+        anyhow::bail!("sleep/wake notifications are not supported on this platform")
+    }
+}
@@ -2,6 +2,7 @@
 
 use atomic_enum::atomic_enum;
 use once_cell::sync::OnceCell;
+use std::sync::atomic::{AtomicBool, Ordering};
 use windows_core::{Error, w};
 use windows_sys::Win32::{
     Foundation::{HWND, LPARAM, WPARAM},
@@ -10,13 +11,16 @@ use windows_sys::Win32::{
         Shutdown::{ShutdownBlockReasonCreate, ShutdownBlockReasonDestroy},
     },
     UI::WindowsAndMessaging::{
-        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, MSG, PostMessageW,
-        RegisterClassExW, TranslateMessage, WM_CLOSE, WM_ENDSESSION, WM_QUERYENDSESSION,
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, MSG,
+        PBT_APMRESUMEAUTOMATIC, PBT_APMRESUMESUSPEND, PostMessageW, RegisterClassExW,
+        TranslateMessage, WM_CLOSE, WM_ENDSESSION, WM_POWERBROADCAST, WM_QUERYENDSESSION,
         WNDCLASSEXW, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW,
     },
 };
 
 static SHUTDOWN_HOOK_INSTANCE: OnceCell<std::sync::mpsc::Sender<()>> = OnceCell::new();
+/// Whether the hidden window is pumping messages, including WM_POWERBROADCAST
+static LISTENING: AtomicBool = AtomicBool::new(false);
 
 #[atomic_enum]
 #[derive(PartialEq, Eq)]
@@ -93,10 +97,21 @@ unsafe extern "system" fn callback(hwnd: HWND, msg: u32, wparam: WPARAM, lparam:
             }
             0
         }
+        WM_POWERBROADCAST
+            if matches!(wparam as u32, PBT_APMRESUMEAUTOMATIC | PBT_APMRESUMESUSPEND) =>
+        {
+            crate::setup::SleepWakeHandler::global().notify_wake();
+            1
+        }
         _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
     }
 }
 
+/// Whether power broadcast (sleep/wake) messages are currently being received
+pub fn is_listening() -> bool {
+    LISTENING.load(Ordering::Acquire)
+}
+
 /// Only called on tauri cleanup thread finished
 pub fn set_ready_for_shutdown() {
     SHUTDOWN_STATE.store(
@@ -165,7 +180,9 @@ fn setup_shutdown_hook_inner(
         hwnd: hidden_window,
     };
 
+    LISTENING.store(true, Ordering::Release);
     if let Err(e) = initd_tx.send(()) {
+        LISTENING.store(false, Ordering::Release);
         anyhow::bail!("Failed to send initd signal: {e}");
     }
 
@@ -185,6 +202,7 @@ fn setup_shutdown_hook_inner(
             }
         }
     }
+    LISTENING.store(false, Ordering::Release);
 
     Ok(())
 }
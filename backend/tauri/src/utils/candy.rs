@@ -1,11 +1,12 @@
 // Simplified candy module - keeping only essential functions
+use super::tls::CustomCaExt;
 use anyhow::Result;
 use reqwest::Client;
 
 pub const INTERNAL_MIRRORS: &[&str] = &["https://github.com"];
 
 pub fn get_reqwest_client() -> Result<Client> {
-    Ok(Client::new())
+    Ok(Client::builder().with_custom_ca().build()?)
 }
 
 pub fn parse_gh_url(mirror: &str, url: &str) -> Result<String> {
@@ -30,8 +31,3 @@ impl ReqwestSpeedTestExt for Client {
         Ok(results)
     }
 }
-
-pub fn collect_logs(_path: &std::path::Path) -> Result<String> {
-    // Simplified log collection - just return empty string
-    Ok("Logs collection simplified in extreme cleanup version".to_string())
-}
@@ -3,7 +3,11 @@ use std::{borrow::Cow, collections::HashMap};
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
-use crate::consts::{BUILD_INFO, BuildInfo};
+use crate::{
+    config::Config,
+    consts::{BUILD_INFO, BuildInfo},
+    core::clash::core_info::CoreVersionInfo,
+};
 use humansize::{BINARY, SizeFormatter};
 use nyanpasu_utils::core::{ClashCoreType, CoreType};
 use serde::Serialize;
@@ -24,6 +28,8 @@ pub struct EnvInfo<'a> {
     pub os: Cow<'a, str>,
     pub arch: Cow<'a, str>,
     pub core: CoreInfo<'a>,
+    /// 当前使用的核心
+    pub active_core: Option<CoreVersionInfo>,
     pub device: DeviceInfo<'a>,
     pub build_info: Cow<'a, BuildInfo>,
     // TODO: add service info
@@ -94,6 +100,9 @@ pub fn collect_envs<'a>() -> Result<EnvInfo<'a>, std::io::Error> {
         ),
         arch: Cow::Owned(System::cpu_arch()),
         core,
+        active_core: CoreVersionInfo::probe_blocking(Config::clash_core())
+            .inspect_err(|e| log::warn!(target: "app", "failed to probe core version: {e:?}"))
+            .ok(),
         device,
         build_info: Cow::Borrowed(&BUILD_INFO),
     })
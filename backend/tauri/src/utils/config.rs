@@ -1,13 +1,430 @@
-use crate::config::Config;
+use crate::config::{Config, nyanpasu::ClashCore};
+use anyhow::{Context, Result, bail};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::Value;
+use std::fmt;
+use sysproxy::Sysproxy;
 
-pub fn get_current_clash_mode() -> String {
+/// clash 的运行模式
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ClashMode {
+    #[default]
+    Rule,
+    Global,
+    Direct,
+    /// 仅 Clash Premium 支持
+    Script,
+    /// 未知核心的自定义模式，原样保留
+    Other(String),
+}
+
+impl ClashMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ClashMode::Rule => "rule",
+            ClashMode::Global => "global",
+            ClashMode::Direct => "direct",
+            ClashMode::Script => "script",
+            ClashMode::Other(mode) => mode,
+        }
+    }
+
+    /// 核心是否支持该模式
+    pub fn is_supported_by(&self, core: ClashCore) -> bool {
+        match self {
+            ClashMode::Rule | ClashMode::Global | ClashMode::Direct => true,
+            ClashMode::Script => core == ClashCore::ClashPremium,
+            ClashMode::Other(_) => false,
+        }
+    }
+}
+
+/// 不区分大小写
+impl From<&str> for ClashMode {
+    fn from(mode: &str) -> Self {
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "rule" => ClashMode::Rule,
+            "global" => ClashMode::Global,
+            "direct" => ClashMode::Direct,
+            "script" => ClashMode::Script,
+            _ => ClashMode::Other(mode.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ClashMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ClashMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ClashMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mode = String::deserialize(deserializer)?;
+        Ok(ClashMode::from(mode.as_str()))
+    }
+}
+
+impl specta::Type for ClashMode {
+    fn inline(
+        _type_map: &mut specta::TypeMap,
+        _generics: specta::Generics,
+    ) -> specta::datatype::DataType {
+        specta::datatype::DataType::Primitive(specta::datatype::PrimitiveType::String)
+    }
+}
+
+/// 读取配置中的模式，不访问核心
+pub fn get_current_clash_mode() -> ClashMode {
     Config::clash()
         .latest()
         .0
         .get("mode")
-        .map(|val| val.as_str().unwrap_or("rule"))
-        .unwrap_or("rule")
-        .to_owned()
+        .and_then(|val| val.as_str())
+        .map(ClashMode::from)
+        .unwrap_or_default()
+}
+
+/// 优先读取核心实际运行的模式（可能被外部修改过），核心不可用时回退到配置
+pub async fn current_clash_mode() -> ClashMode {
+    let configs = tokio::time::timeout(
+        std::time::Duration::from_secs(2),
+        crate::core::clash::api::get_configs(),
+    )
+    .await;
+    match configs {
+        Ok(Ok(configs)) => {
+            if let Some(mode) = configs.get("mode").and_then(|val| val.as_str()) {
+                return ClashMode::from(mode);
+            }
+        }
+        Ok(Err(e)) => tracing::debug!("failed to read clash mode from core: {e:#}"),
+        Err(_) => tracing::debug!("timed out reading clash mode from core"),
+    }
+    get_current_clash_mode()
+}
+
+/// `IVerge` 配置文件的 schema 版本
+pub struct ConfigVersion;
+
+impl ConfigVersion {
+    /// 当前 schema 版本，每次有破坏性的字段变更时都需要递增，并在
+    /// `migrate_verge_config` 中补充对应的迁移步骤
+    pub const CURRENT: u32 = 1;
+
+    /// 读取配置中记录的版本，缺失视为 0，超出 u32 范围时报错
+    pub fn of(config: &Value) -> Result<u32> {
+        match config.get("config_version").and_then(Value::as_u64) {
+            Some(v) => u32::try_from(v).with_context(|| format!("invalid config version {v}")),
+            None => Ok(0),
+        }
+    }
+}
+
+/// 将旧版本的 `IVerge` 配置逐步升级到 `ConfigVersion::CURRENT`
+pub fn migrate_verge_config(mut config: Value, from_version: u32) -> Result<Value> {
+    if from_version > ConfigVersion::CURRENT {
+        bail!(
+            "config version {from_version} is newer than the supported version {}",
+            ConfigVersion::CURRENT
+        );
+    }
+    if !config.is_mapping() {
+        bail!("verge config is not a mapping");
+    }
+    let mut version = from_version;
+    while version < ConfigVersion::CURRENT {
+        config = match version {
+            0 => migrate_v0_to_v1(config),
+            _ => unreachable!("missing migration step for config version {version}"),
+        };
+        version += 1;
+    }
+    if let Some(map) = config.as_mapping_mut() {
+        map.insert("config_version".into(), Value::from(ConfigVersion::CURRENT));
+    }
+    Ok(config)
+}
+
+/// v0 -> v1: `service_mode` 重命名为 `enable_service_mode`
+fn migrate_v0_to_v1(mut config: Value) -> Value {
+    let map = config.as_mapping_mut().unwrap();
+    if let Some(value) = map.remove("service_mode")
+        && !map.contains_key("enable_service_mode")
+    {
+        map.insert("enable_service_mode".into(), value);
+    }
+    config
+}
+
+/// 系统代理绕过列表，`IVerge::system_proxy_bypass` 为唯一来源，开启系统代理时同步到系统
+/// 系统设置的读写通过 sysproxy 完成（macOS 使用 networksetup，Windows 使用注册表）
+pub struct BypassManager;
+
+#[cfg(target_os = "windows")]
+const BYPASS_SEPARATOR: &str = ";";
+#[cfg(not(target_os = "windows"))]
+const BYPASS_SEPARATOR: &str = ",";
+
+/// 域名（可带 `*.` 通配前缀）、`localhost`、`<local>`、IPv4（可带通配或 CIDR 前缀）及 IPv6（可带 CIDR 前缀）
+static BYPASS_ENTRY_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?x)^(?:
+            <local>
+            | (?:\*\.)?(?:[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?\.)*[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?
+            | (?:(?:25[0-5]|2[0-4]\d|1?\d?\d|\*)\.){1,3}(?:25[0-5]|2[0-4]\d|1?\d?\d|\*)(?:/(?:3[0-2]|[12]?\d))?
+            | [0-9A-Fa-f:]*:[0-9A-Fa-f:]*(?:/(?:12[0-8]|1[01]\d|\d?\d))?
+        )$",
+    )
+    .unwrap()
+});
+
+impl BypassManager {
+    pub fn is_valid_entry(entry: &str) -> bool {
+        BYPASS_ENTRY_REGEX.is_match(entry)
+    }
+
+    pub fn validate(entries: &[String]) -> Result<()> {
+        if let Some(entry) = entries.iter().find(|entry| !Self::is_valid_entry(entry)) {
+            bail!("invalid bypass entry: `{entry}`");
+        }
+        Ok(())
+    }
+
+    /// 拆分系统或旧配置中的绕过字符串，兼容 `,` 与 `;` 分隔
+    pub fn split(bypass: &str) -> Vec<String> {
+        bypass
+            .split([',', ';'])
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn join(entries: &[String]) -> String {
+        entries.join(BYPASS_SEPARATOR)
+    }
+
+    /// 当前配置的绕过列表，未设置时为 `None`，此时使用系统默认值
+    pub fn configured_bypass() -> Option<String> {
+        Config::verge()
+            .latest()
+            .system_proxy_bypass
+            .as_deref()
+            .map(Self::join)
+    }
+
+    /// 从系统读取当前的绕过列表
+    pub fn get_system_bypass_list() -> Result<Vec<String>> {
+        let sysproxy = Sysproxy::get_system_proxy()?;
+        Ok(Self::split(&sysproxy.bypass))
+    }
+
+    /// 直接写入系统设置，不修改配置，只能在系统代理开启时调用
+    fn set_system_bypass_list(entries: &[String]) -> Result<()> {
+        Self::validate(entries)?;
+        let mut sysproxy = Sysproxy::get_system_proxy()?;
+        sysproxy.bypass = Self::join(entries);
+        sysproxy.set_system_proxy()?;
+        Ok(())
+    }
+
+    /// 系统代理开启时，将配置中的绕过列表同步到系统，与系统代理守护使用同一份列表
+    pub fn sync_to_system() -> Result<()> {
+        let (enabled, entries) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.enable_system_proxy.unwrap_or(false),
+                verge.system_proxy_bypass.clone(),
+            )
+        };
+        if !enabled {
+            return Ok(());
+        }
+        Self::set_system_bypass_list(&crate::core::sysopt::effective_bypass(entries))
+    }
+
+    /// 修改配置中的绕过列表，并在系统代理开启时同步
+    /// 未设置时从默认列表开始修改，不读取系统中的值，避免把排除的域名写进配置
+    async fn update_entries(update: impl FnOnce(&mut Vec<String>) + Send) -> Result<Vec<String>> {
+        crate::feat::update_verge(|verge| {
+            let mut entries = verge
+                .system_proxy_bypass
+                .clone()
+                .unwrap_or_else(crate::core::sysopt::default_bypass);
+            update(&mut entries);
+            Ok(crate::config::IVerge {
+                system_proxy_bypass: Some(entries),
+                ..Default::default()
+            })
+        })
+        .await?;
+        Ok(Config::verge()
+            .latest()
+            .system_proxy_bypass
+            .clone()
+            .unwrap_or_default())
+    }
+
+    pub async fn add_bypass_entry(entry: String) -> Result<Vec<String>> {
+        let entry = entry.trim().to_string();
+        if !Self::is_valid_entry(&entry) {
+            bail!("invalid bypass entry: `{entry}`");
+        }
+        Self::update_entries(|entries| {
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        })
+        .await
+    }
+
+    pub async fn remove_bypass_entry(entry: String) -> Result<Vec<String>> {
+        let entry = entry.trim();
+        Self::update_entries(|entries| entries.retain(|item| item != entry)).await
+    }
+}
+
+/// 同时作用于系统代理绕过列表与核心规则的排除域名
+pub struct GlobalExcludeList;
+
+impl GlobalExcludeList {
+    /// 去掉空白与 `*.`、`.` 前缀并转为小写，只接受域名
+    pub fn normalize(domain: &str) -> Result<String> {
+        let domain = domain.trim().to_ascii_lowercase();
+        let domain = domain
+            .strip_prefix("*.")
+            .or_else(|| domain.strip_prefix('.'))
+            .unwrap_or(&domain);
+        if domain.contains(['*', '/', ':', '<'])
+            || domain.parse::<std::net::IpAddr>().is_ok()
+            || !BypassManager::is_valid_entry(domain)
+        {
+            bail!("invalid domain: `{domain}`");
+        }
+        Ok(domain.to_string())
+    }
+
+    /// 规范化并去重
+    pub fn normalize_list(domains: &[String]) -> Result<Vec<String>> {
+        let mut normalized = Vec::with_capacity(domains.len());
+        for domain in domains {
+            let domain = Self::normalize(domain)?;
+            if !normalized.contains(&domain) {
+                normalized.push(domain);
+            }
+        }
+        Ok(normalized)
+    }
+
+    pub fn entries() -> Vec<String> {
+        Config::verge()
+            .latest()
+            .global_exclude_list
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// 将排除的域名及其子域名追加到绕过列表，已存在的条目不重复添加
+    pub fn merge_into_bypass(mut bypass: Vec<String>) -> Vec<String> {
+        for domain in Self::entries() {
+            for entry in [format!("*.{domain}"), domain] {
+                if !bypass.contains(&entry) {
+                    bypass.push(entry);
+                }
+            }
+        }
+        bypass
+    }
+
+    async fn update(update: impl FnOnce(&mut Vec<String>) + Send) -> Result<Vec<String>> {
+        crate::feat::update_verge(|verge| {
+            let mut domains = verge.global_exclude_list.clone().unwrap_or_default();
+            update(&mut domains);
+            Ok(crate::config::IVerge {
+                global_exclude_list: Some(domains),
+                ..Default::default()
+            })
+        })
+        .await?;
+        Ok(Self::entries())
+    }
+
+    pub async fn add(domain: String) -> Result<Vec<String>> {
+        let domain = Self::normalize(&domain)?;
+        Self::update(|domains| {
+            if !domains.contains(&domain) {
+                domains.push(domain);
+            }
+        })
+        .await
+    }
+
+    pub async fn remove(domain: String) -> Result<Vec<String>> {
+        let domain = Self::normalize(&domain)?;
+        Self::update(|domains| domains.retain(|item| *item != domain)).await
+    }
+
+    /// 每行一个域名，忽略空行与 `#` 开头的注释
+    fn parse(content: &str) -> Result<Vec<String>> {
+        let mut domains = Vec::new();
+        let mut invalid = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match Self::normalize(line) {
+                Ok(domain) if !domains.contains(&domain) => domains.push(domain),
+                Ok(_) => {}
+                Err(_) => invalid.push(line),
+            }
+        }
+        if !invalid.is_empty() {
+            bail!("invalid domains: {}", invalid.join(", "));
+        }
+        Ok(domains)
+    }
+
+    /// 从文件导入并与现有列表合并
+    pub async fn import_from_file(path: &std::path::Path) -> Result<Vec<String>> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let imported = Self::parse(&content)?;
+        Self::update(|domains| {
+            for domain in imported {
+                if !domains.contains(&domain) {
+                    domains.push(domain);
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// 兼容旧配置中以字符串保存的绕过列表
+pub fn deserialize_bypass_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BypassList {
+        Joined(String),
+        List(Vec<String>),
+    }
+    Ok(
+        Option::<BypassList>::deserialize(deserializer)?.map(|bypass| match bypass {
+            BypassList::Joined(bypass) => BypassManager::split(&bypass),
+            BypassList::List(entries) => entries,
+        }),
+    )
 }
 
 // Minimal trait to fix compilation - simplified in extreme cleanup
@@ -20,3 +437,146 @@ impl NyanpasuReqwestProxyExt for reqwest::ClientBuilder {
         self // No proxy configuration in extreme cleanup version
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clash_mode_parse_case_insensitive() {
+        assert_eq!(ClashMode::from("Rule"), ClashMode::Rule);
+        assert_eq!(ClashMode::from("GLOBAL"), ClashMode::Global);
+        assert_eq!(ClashMode::from("direct"), ClashMode::Direct);
+        assert_eq!(ClashMode::from("Script"), ClashMode::Script);
+        assert_eq!(
+            ClashMode::from("Whitelist"),
+            ClashMode::Other("Whitelist".to_string())
+        );
+        let mode: ClashMode = serde_yaml::from_str("Global").unwrap();
+        assert_eq!(mode, ClashMode::Global);
+        assert_eq!(serde_yaml::to_string(&mode).unwrap().trim(), "global");
+    }
+
+    #[test]
+    fn test_clash_mode_script_requires_premium() {
+        assert!(ClashMode::Script.is_supported_by(ClashCore::ClashPremium));
+        assert!(!ClashMode::Script.is_supported_by(ClashCore::Mihomo));
+        assert!(!ClashMode::Script.is_supported_by(ClashCore::MihomoAlpha));
+        assert!(ClashMode::Rule.is_supported_by(ClashCore::Mihomo));
+    }
+
+    #[test]
+    fn test_migrate_v0_to_v1_renames_service_mode() {
+        let config: Value = serde_yaml::from_str("service_mode: true\nlanguage: en\n").unwrap();
+        let migrated = migrate_verge_config(config, 0).unwrap();
+        assert_eq!(
+            migrated.get("enable_service_mode"),
+            Some(&Value::Bool(true))
+        );
+        assert!(migrated.get("service_mode").is_none());
+        assert_eq!(
+            ConfigVersion::of(&migrated).unwrap(),
+            ConfigVersion::CURRENT
+        );
+    }
+
+    #[test]
+    fn test_migrate_v0_to_v1_keeps_existing_field() {
+        let config: Value =
+            serde_yaml::from_str("service_mode: true\nenable_service_mode: false\n").unwrap();
+        let migrated = migrate_verge_config(config, 0).unwrap();
+        assert_eq!(
+            migrated.get("enable_service_mode"),
+            Some(&Value::Bool(false))
+        );
+        assert!(migrated.get("service_mode").is_none());
+    }
+
+    #[test]
+    fn test_bypass_entry_validation() {
+        for entry in [
+            "localhost",
+            "<local>",
+            "*.example.com",
+            "192.168.0.0/16",
+            "127.*",
+            "172.16.*.*",
+            "::1",
+            "fd00::/8",
+        ] {
+            assert!(BypassManager::is_valid_entry(entry), "{entry}");
+        }
+        for entry in [
+            "",
+            "exa mple.com",
+            "http://example.com",
+            "10.0.0.0/33",
+            "-bad.com",
+        ] {
+            assert!(!BypassManager::is_valid_entry(entry), "{entry}");
+        }
+    }
+
+    #[test]
+    fn test_global_exclude_normalize() {
+        assert_eq!(
+            GlobalExcludeList::normalize(" *.Example.com ").unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            GlobalExcludeList::normalize(".example.com").unwrap(),
+            "example.com"
+        );
+        for domain in [
+            "",
+            "192.168.1.1",
+            "::1",
+            "<local>",
+            "a.*.com",
+            "example.com/path",
+        ] {
+            assert!(GlobalExcludeList::normalize(domain).is_err(), "{domain}");
+        }
+        let domains =
+            GlobalExcludeList::parse("# comment\nexample.com\n\n*.example.com\nfoo.org\n").unwrap();
+        assert_eq!(domains, ["example.com", "foo.org"]);
+        assert!(GlobalExcludeList::parse("example.com\n10.0.0.1\n").is_err());
+        let domains = [" *.Example.com", "example.com", "Foo.org"].map(String::from);
+        assert_eq!(
+            GlobalExcludeList::normalize_list(&domains).unwrap(),
+            ["example.com", "foo.org"]
+        );
+    }
+
+    #[test]
+    fn test_bypass_list_accepts_legacy_string() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            #[serde(default, deserialize_with = "deserialize_bypass_list")]
+            bypass: Option<Vec<String>>,
+        }
+        let legacy: Wrapper = serde_yaml::from_str("bypass: localhost;127.*, <local>").unwrap();
+        assert_eq!(
+            legacy.bypass,
+            Some(vec!["localhost".into(), "127.*".into(), "<local>".into()])
+        );
+        let list: Wrapper = serde_yaml::from_str("bypass:\n  - localhost\n").unwrap();
+        assert_eq!(list.bypass, Some(vec!["localhost".into()]));
+        let missing: Wrapper = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(missing.bypass, None);
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_version() {
+        let config: Value = serde_yaml::from_str("language: en\n").unwrap();
+        assert!(migrate_verge_config(config, ConfigVersion::CURRENT + 1).is_err());
+    }
+
+    #[test]
+    fn test_config_version_out_of_range() {
+        let config: Value = serde_yaml::from_str("config_version: 4294967297\n").unwrap();
+        assert!(ConfigVersion::of(&config).is_err());
+        let config: Value = serde_yaml::from_str("language: en\n").unwrap();
+        assert_eq!(ConfigVersion::of(&config).unwrap(), 0);
+    }
+}
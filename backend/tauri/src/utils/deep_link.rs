@@ -0,0 +1,218 @@
+//! 自定义 scheme（`clash-nyanpasu://`、`clash://`）链接的校验与分发
+//! 系统传入的链接与 `simulate_deep_link` 走同一条路径
+//! 前端就绪前收到的链接先缓存，调用 `mark_frontend_ready` 后再发送
+use crate::utils::resolve::{create_window, is_window_opened};
+use anyhow::{Result, bail};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use specta::Type;
+use std::{collections::VecDeque, fmt};
+use tauri::{AppHandle, Emitter};
+use url::Url;
+
+pub const DEEP_LINK_SCHEMES: [&str; 2] = ["clash-nyanpasu", "clash"];
+
+/// 缓存的链接上限，超出时丢弃最早的
+const MAX_PENDING_DEEP_LINKS: usize = 16;
+
+#[derive(Default)]
+struct DeepLinkQueue {
+    frontend_ready: bool,
+    pending: VecDeque<String>,
+}
+
+impl DeepLinkQueue {
+    /// 前端未就绪时缓存链接，返回是否已缓存
+    fn enqueue(&mut self, url: &str) -> bool {
+        if self.frontend_ready {
+            return false;
+        }
+        if self.pending.len() == MAX_PENDING_DEEP_LINKS {
+            let dropped = self.pending.pop_front();
+            log::warn!(target: "app", "deep link queue is full, drop {dropped:?}");
+        }
+        self.pending.push_back(url.to_string());
+        true
+    }
+
+    fn mark_ready(&mut self) -> VecDeque<String> {
+        self.frontend_ready = true;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+static DEEP_LINK_QUEUE: Lazy<Mutex<DeepLinkQueue>> = Lazy::new(Default::default);
+
+/// 前端 `scheme-provider` 能处理的操作
+const DEEP_LINK_ACTIONS: [&str; 2] = ["install-config", "subscribe-remote-profile"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeepLinkSource {
+    /// 启动参数
+    LaunchArgs,
+    /// 运行中由系统转发
+    Os,
+    /// 应用内模拟
+    Simulated,
+}
+
+impl fmt::Display for DeepLinkSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeepLinkSource::LaunchArgs => "launch args",
+            DeepLinkSource::Os => "os",
+            DeepLinkSource::Simulated => "simulated",
+        })
+    }
+}
+
+pub fn log_deep_link_received(url: &str, source: DeepLinkSource) {
+    log::info!(target: "app", "deep link received from {source}: {url}");
+}
+
+/// 与前端一致，兼容 `scheme://action` 与 `scheme:///action` 两种写法
+fn deep_link_action(url: &Url) -> String {
+    let action = format!("{}{}", url.host_str().unwrap_or_default(), url.path());
+    action.trim_matches('/').to_string()
+}
+
+pub fn validate_deep_link_url(url: &str) -> Result<Url> {
+    let url = Url::parse(url.trim())?;
+    if !DEEP_LINK_SCHEMES.contains(&url.scheme()) {
+        bail!("unsupported scheme `{}`", url.scheme());
+    }
+    let action = deep_link_action(&url);
+    if !DEEP_LINK_ACTIONS.contains(&action.as_str()) {
+        bail!("unsupported deep link action `{action}`");
+    }
+    let Some((_, subscription)) = url.query_pairs().find(|(key, _)| key == "url") else {
+        bail!("missing subscription url");
+    };
+    let subscription = Url::parse(&subscription)?;
+    if !matches!(subscription.scheme(), "http" | "https") {
+        bail!("subscription url must be http or https");
+    }
+    Ok(url)
+}
+
+/// 校验后交给前端处理，窗口未打开时先创建窗口
+/// 创建窗口可能阻塞，不要在异步运行时中直接调用
+pub fn handle_deep_link(app_handle: &AppHandle, url: &str, source: DeepLinkSource) -> Result<Url> {
+    log_deep_link_received(url, source);
+    let url = validate_deep_link_url(url)
+        .inspect_err(|e| log::warn!(target: "app", "rejected deep link: {e}"))?;
+    let queued = {
+        let mut queue = DEEP_LINK_QUEUE.lock();
+        // 新窗口需要重新等待前端就绪
+        if !is_window_opened() {
+            queue.frontend_ready = false;
+        }
+        queue.enqueue(url.as_str())
+    };
+    create_window(app_handle);
+    if !queued {
+        app_handle.emit("deep-link", url.as_str())?;
+    }
+    Ok(url)
+}
+
+/// 前端开始监听后调用，发送缓存的链接
+pub fn mark_frontend_ready(app_handle: &AppHandle) -> Result<()> {
+    let pending = DEEP_LINK_QUEUE.lock().mark_ready();
+    for url in pending {
+        app_handle.emit("deep-link", url)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error, Serialize, Type)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum DeepLinkError {
+    /// 未初始化标识符，调试构建中不会启用 deep link
+    #[error("deep link is not initialized")]
+    NotPrepared,
+    #[error("failed to access scheme registration: {0}")]
+    Io(String),
+}
+
+impl From<std::io::Error> for DeepLinkError {
+    fn from(e: std::io::Error) -> Self {
+        DeepLinkError::Io(e.to_string())
+    }
+}
+
+fn ensure_prepared() -> Result<(), DeepLinkError> {
+    if tauri_plugin_deep_link::id_already_set() {
+        Ok(())
+    } else {
+        Err(DeepLinkError::NotPrepared)
+    }
+}
+
+/// 将 scheme 注册到当前程序，已注册时不做修改，返回是否写入了注册信息
+pub fn register_scheme() -> Result<bool, DeepLinkError> {
+    ensure_prepared()?;
+    if tauri_plugin_deep_link::is_registered(&DEEP_LINK_SCHEMES)? {
+        return Ok(false);
+    }
+    tauri_plugin_deep_link::register_scheme(&DEEP_LINK_SCHEMES)?;
+    log::info!(target: "app", "deep link schemes registered");
+    Ok(true)
+}
+
+/// 移除 scheme 注册，未注册时不做修改，返回是否移除了注册信息
+pub fn unregister_scheme() -> Result<bool, DeepLinkError> {
+    ensure_prepared()?;
+    if !tauri_plugin_deep_link::is_registered(&DEEP_LINK_SCHEMES)? {
+        return Ok(false);
+    }
+    tauri_plugin_deep_link::unregister(&DEEP_LINK_SCHEMES)?;
+    log::info!(target: "app", "deep link schemes unregistered");
+    Ok(true)
+}
+
+/// 向系统确认 scheme 是否指向当前程序
+pub fn verify_scheme(scheme: &str) -> Result<bool, DeepLinkError> {
+    Ok(tauri_plugin_deep_link::verify_scheme_registered(scheme)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_deep_link_url() {
+        for url in [
+            "clash-nyanpasu://install-config?url=https%3A%2F%2Fexample.com%2Fsub&name=test",
+            "clash:///install-config/?url=https://example.com/sub",
+            "clash://subscribe-remote-profile?url=http%3A%2F%2Fexample.com",
+        ] {
+            assert!(validate_deep_link_url(url).is_ok(), "{url}");
+        }
+        for url in [
+            "not a url",
+            "https://install-config?url=https://example.com",
+            "clash://uninstall?url=https://example.com",
+            "clash://install-config",
+            "clash://install-config?url=file%3A%2F%2F%2Fetc%2Fpasswd",
+        ] {
+            assert!(validate_deep_link_url(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_deep_link_queue() {
+        let mut queue = DeepLinkQueue::default();
+        for i in 0..MAX_PENDING_DEEP_LINKS + 2 {
+            assert!(queue.enqueue(&format!(
+                "clash://install-config?url=https://example.com/{i}"
+            )));
+        }
+        let pending = queue.mark_ready();
+        assert_eq!(pending.len(), MAX_PENDING_DEEP_LINKS);
+        assert!(pending[0].ends_with("/2"));
+        assert!(!queue.enqueue("clash://install-config?url=https://example.com"));
+        assert!(queue.mark_ready().is_empty());
+    }
+}
@@ -3,7 +3,13 @@ use anyhow::Result;
 use fs_err as fs;
 use nyanpasu_utils::dirs::{suggest_config_dir, suggest_data_dir};
 use once_cell::sync::Lazy;
-use std::{borrow::Cow, path::PathBuf};
+use serde::Serialize;
+use specta::Type;
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
 use tauri::{Env, utils::platform::resource_dir};
 
 #[cfg(not(feature = "verge-dev"))]
@@ -34,54 +40,128 @@ pub const CLASH_CFG_GUARD_OVERRIDES: &str = "clash-guard-overrides.yaml";
 pub const NYANPASU_CONFIG: &str = "nyanpasu-config.yaml";
 pub const PROFILE_YAML: &str = "profiles.yaml";
 pub const STORAGE_DB: &str = "storage.db";
+pub const MERGE_OVERLAY: &str = "merge-overlay.yaml";
 
 pub static APP_VERSION: &str = env!("NYANPASU_VERSION");
 
-#[cfg(target_os = "windows")]
-pub fn get_portable_flag() -> bool {
-    *crate::consts::IS_PORTABLE
+/// exe 旁存在该文件时以便携模式运行
+pub const PORTABLE_FLAG: &str = "portable.flag";
+pub const PORTABLE_ARG: &str = "--portable";
+/// 便携模式下 WebView 的数据目录，位于数据目录内
+const WEBVIEW_DIR: &str = "webview";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortableMode {
+    Disabled,
+    /// 旧版 Windows 便携包，标记为 `.config/PORTABLE`，沿用原有的目录结构
+    Legacy,
+    /// 所有数据放在 exe 旁的 `data/` 中
+    Enabled,
 }
 
-pub fn app_config_dir() -> Result<PathBuf> {
-    let path: Option<PathBuf> = {
-        #[cfg(target_os = "windows")]
-        {
-            if get_portable_flag() {
-                let app_dir = app_install_dir()?;
-                Some(app_dir.join(".config").join(APP_NAME))
-            } else if let Ok(Some(path)) = super::winreg::get_app_dir() {
-                Some(path)
-            } else {
-                None
-            }
-        }
-        #[cfg(not(target_os = "windows"))]
-        {
-            None
+pub fn detect_portable_mode(
+    install_dir: &Path,
+    mut args: impl Iterator<Item = OsString>,
+) -> PortableMode {
+    if install_dir.join(PORTABLE_FLAG).exists() || args.any(|arg| arg == PORTABLE_ARG) {
+        PortableMode::Enabled
+    } else if cfg!(windows) && install_dir.join(".config/PORTABLE").exists() {
+        PortableMode::Legacy
+    } else {
+        PortableMode::Disabled
+    }
+}
+
+/// 便携模式下的配置与数据目录
+fn portable_dirs(install_dir: &Path, mode: PortableMode) -> Option<(PathBuf, PathBuf)> {
+    match mode {
+        PortableMode::Disabled => None,
+        PortableMode::Legacy => Some((
+            install_dir.join(".config").join(APP_NAME),
+            install_dir.join(".data").join(APP_NAME),
+        )),
+        PortableMode::Enabled => {
+            let root = install_dir.join("data");
+            Some((root.join("config"), root.join("data")))
         }
-    };
+    }
+}
 
-    match path {
-        Some(path) => Ok(path),
-        None => suggest_config_dir(&APP_DIR_PLACEHOLDER)
-            .ok_or(anyhow::anyhow!("failed to get the app config dir")),
+fn current_portable_dirs() -> Result<Option<(PathBuf, PathBuf)>> {
+    match *crate::consts::PORTABLE_MODE {
+        PortableMode::Disabled => Ok(None),
+        mode => Ok(portable_dirs(&app_install_dir()?, mode)),
     }
-    .and_then(|dir| {
+}
+
+/// 便携模式下 WebView2 的数据目录，非便携模式使用系统默认位置
+pub fn webview_data_dir() -> Result<Option<PathBuf>> {
+    Ok(current_portable_dirs()?.map(|(_, data_dir)| data_dir.join(WEBVIEW_DIR)))
+}
+
+pub fn get_portable_flag() -> bool {
+    *crate::consts::PORTABLE_MODE != PortableMode::Disabled
+}
+
+/// 便携模式下不支持注册服务、开机自启等需要写入系统位置的功能
+pub fn ensure_not_portable(feature: &str) -> Result<()> {
+    if get_portable_flag() {
+        anyhow::bail!("{feature} is not supported in portable mode");
+    }
+    Ok(())
+}
+
+/// 实际使用的各个目录
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct RuntimePaths {
+    pub portable: bool,
+    pub install_dir: PathBuf,
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub profiles_dir: PathBuf,
+    pub logs_dir: PathBuf,
+    pub cache_dir: PathBuf,
+}
+
+impl RuntimePaths {
+    fn resolve(install_dir: PathBuf, mode: PortableMode) -> Result<Self> {
+        let (config_dir, data_dir) = match portable_dirs(&install_dir, mode) {
+            Some(dirs) => dirs,
+            None => (app_config_dir_path()?, app_data_dir_path()?),
+        };
+        Ok(Self {
+            portable: mode != PortableMode::Disabled,
+            profiles_dir: config_dir.join("profiles"),
+            logs_dir: data_dir.join("logs"),
+            cache_dir: data_dir.join("cache"),
+            install_dir,
+            config_dir,
+            data_dir,
+        })
+    }
+}
+
+/// 不会创建目录
+pub fn runtime_paths() -> Result<RuntimePaths> {
+    RuntimePaths::resolve(app_install_dir()?, *crate::consts::PORTABLE_MODE)
+}
+
+pub fn app_config_dir() -> Result<PathBuf> {
+    app_config_dir_path().and_then(|dir| {
         create_dir_all(&dir)?;
         Ok(dir)
     })
 }
 
-pub fn app_data_dir() -> Result<PathBuf> {
+/// 与 `app_config_dir` 相同，但不会创建目录
+pub fn app_config_dir_path() -> Result<PathBuf> {
+    if let Some((config_dir, _)) = current_portable_dirs()? {
+        return Ok(config_dir);
+    }
     let path: Option<PathBuf> = {
         #[cfg(target_os = "windows")]
         {
-            if get_portable_flag() {
-                let app_dir = app_install_dir()?;
-                Some(app_dir.join(".data").join(APP_NAME))
-            } else {
-                None
-            }
+            super::winreg::get_app_dir().ok().flatten()
         }
         #[cfg(not(target_os = "windows"))]
         {
@@ -91,15 +171,26 @@ pub fn app_data_dir() -> Result<PathBuf> {
 
     match path {
         Some(path) => Ok(path),
-        None => suggest_data_dir(&APP_DIR_PLACEHOLDER)
-            .ok_or(anyhow::anyhow!("failed to get the app data dir")),
+        None => suggest_config_dir(&APP_DIR_PLACEHOLDER)
+            .ok_or(anyhow::anyhow!("failed to get the app config dir")),
     }
-    .and_then(|dir| {
+}
+
+pub fn app_data_dir() -> Result<PathBuf> {
+    app_data_dir_path().and_then(|dir| {
         create_dir_all(&dir)?;
         Ok(dir)
     })
 }
 
+/// 与 `app_data_dir` 相同，但不会创建目录
+pub fn app_data_dir_path() -> Result<PathBuf> {
+    if let Some((_, data_dir)) = current_portable_dirs()? {
+        return Ok(data_dir);
+    }
+    suggest_data_dir(&APP_DIR_PLACEHOLDER).ok_or(anyhow::anyhow!("failed to get the app data dir"))
+}
+
 /// get the verge app home dir
 #[deprecated(
     since = "1.6.0",
@@ -205,10 +296,49 @@ pub fn profiles_path() -> Result<PathBuf> {
     Ok(app_config_dir()?.join(PROFILE_YAML))
 }
 
+/// 重置设置前的备份，每次重置使用一个带时间戳的子目录
+pub fn settings_backup_dir() -> Result<PathBuf> {
+    Ok(app_config_dir()?.join("backups"))
+}
+
+pub fn merge_overlay_path() -> Result<PathBuf> {
+    Ok(app_config_dir()?.join(MERGE_OVERLAY))
+}
+
+/// 按核心分目录存放的增强插件
+pub fn core_plugins_dir() -> Result<PathBuf> {
+    Ok(app_config_dir()?.join("plugins"))
+}
+
 pub fn storage_path() -> Result<PathBuf> {
     Ok(app_data_dir()?.join(STORAGE_DB))
 }
 
+pub fn proxy_tags_path() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join("proxy_tags.json"))
+}
+
+pub fn provider_refresh_state_path() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join("provider_refresh_state.json"))
+}
+
+pub fn profile_diff_state_path() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join("profile_diffs.json"))
+}
+
+pub fn checksums_cache_path() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join("checksums_cache.json"))
+}
+
+pub fn sysproxy_marker_path() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join("sysproxy_marker.json"))
+}
+
+/// 已写入严格路由规则的网卡，正常退出时删除
+pub fn strict_routing_marker_path() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join("strict_routing_marker"))
+}
+
 pub fn clash_pid_path() -> Result<PathBuf> {
     Ok(app_data_dir()?.join("clash.pid"))
 }
@@ -354,7 +484,68 @@ pub fn check_core_permission(core: &nyanpasu_utils::core::CoreType) -> anyhow::R
     Ok(false)
 }
 
+#[cfg(test)]
 mod test {
+    use super::*;
+
+    #[test]
+    fn test_portable_paths_stay_next_to_exe() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let install_dir = install_dir.path();
+        assert_eq!(
+            detect_portable_mode(install_dir, std::iter::empty()),
+            PortableMode::Disabled
+        );
+        assert_eq!(
+            detect_portable_mode(install_dir, ["app".into(), PORTABLE_ARG.into()].into_iter()),
+            PortableMode::Enabled
+        );
+        std::fs::write(install_dir.join(PORTABLE_FLAG), "").unwrap();
+        let mode = detect_portable_mode(install_dir, std::iter::empty());
+        assert_eq!(mode, PortableMode::Enabled);
+
+        let paths = RuntimePaths::resolve(install_dir.to_path_buf(), mode).unwrap();
+        assert!(paths.portable);
+        assert_eq!(
+            Some((paths.config_dir.clone(), paths.data_dir.clone())),
+            portable_dirs(install_dir, mode)
+        );
+        for path in [
+            &paths.config_dir,
+            &paths.data_dir,
+            &paths.profiles_dir,
+            &paths.logs_dir,
+            &paths.cache_dir,
+        ] {
+            assert!(path.starts_with(install_dir.join("data")), "{path:?}");
+        }
+
+        let paths = RuntimePaths::resolve(install_dir.to_path_buf(), PortableMode::Legacy).unwrap();
+        assert!(paths.data_dir.starts_with(install_dir));
+    }
+
+    #[test]
+    fn test_runtime_paths_match_current_portable_dirs() {
+        let paths = runtime_paths().unwrap();
+        match current_portable_dirs().unwrap() {
+            Some((config_dir, data_dir)) => {
+                assert!(paths.portable);
+                assert_eq!(
+                    (&paths.config_dir, &paths.data_dir),
+                    (&config_dir, &data_dir)
+                );
+                assert_eq!(
+                    webview_data_dir().unwrap(),
+                    Some(data_dir.join(WEBVIEW_DIR))
+                );
+            }
+            None => {
+                assert!(!paths.portable);
+                assert_eq!(webview_data_dir().unwrap(), None);
+            }
+        }
+    }
+
     #[test]
     #[ignore]
     fn test_dir_placeholder() {
@@ -1,10 +1,18 @@
 #[cfg(target_os = "macos")]
 pub mod macos {
-    use objc2_app_kit::{NSApplication, NSApplicationActivationPolicy};
-    use objc2_foundation::MainThreadMarker;
-    use std::cell::Cell;
+    use objc2::{
+        MainThreadOnly, define_class, ffi, msg_send,
+        rc::Retained,
+        runtime::{AnyObject, Imp, Sel},
+        sel,
+    };
+    use objc2_app_kit::{NSApplication, NSApplicationActivationPolicy, NSMenu, NSMenuItem};
+    use objc2_foundation::{MainThreadMarker, NSObject, NSObjectProtocol, NSString};
+    use rust_i18n::t;
+    use std::cell::{Cell, RefCell};
     thread_local! {
         static MARK: Cell<MainThreadMarker> = Cell::new(MainThreadMarker::new().unwrap());
+        static DOCK_MENU: RefCell<Option<(Retained<NSMenu>, Retained<DockMenuTarget>)>> = const { RefCell::new(None) };
     }
 
     pub fn show_dock_icon() {
@@ -19,4 +27,96 @@ pub mod macos {
         let app = NSApplication::sharedApplication(MARK.get());
         app.setActivationPolicy(NSApplicationActivationPolicy::Accessory);
     }
+
+    define_class! {
+        #[unsafe(super(NSObject))]
+        #[name = "NyanpasuDockMenuTarget"]
+        #[thread_kind = MainThreadOnly]
+        struct DockMenuTarget;
+
+        unsafe impl NSObjectProtocol for DockMenuTarget {}
+
+        impl DockMenuTarget {
+            #[unsafe(method(toggleTun:))]
+            fn toggle_tun(&self, _sender: Option<&AnyObject>) {
+                crate::feat::toggle_tun_mode();
+            }
+
+            #[unsafe(method(toggleSystemProxy:))]
+            fn toggle_system_proxy(&self, _sender: Option<&AnyObject>) {
+                crate::feat::toggle_system_proxy();
+            }
+
+            #[unsafe(method(restartCore:))]
+            fn restart_core(&self, _sender: Option<&AnyObject>) {
+                crate::feat::restart_clash_core();
+            }
+        }
+    }
+
+    /// `-[NSApplicationDelegate applicationDockMenu:]`
+    unsafe extern "C-unwind" fn application_dock_menu(
+        _this: *mut AnyObject,
+        _cmd: Sel,
+        _sender: *mut AnyObject,
+    ) -> *mut NSMenu {
+        DOCK_MENU.with_borrow(|menu| {
+            menu.as_ref().map_or(std::ptr::null_mut(), |(menu, _)| {
+                Retained::as_ptr(menu).cast_mut()
+            })
+        })
+    }
+
+    /// tao 持有应用的 delegate，通过给 delegate 的类添加 `applicationDockMenu:` 提供菜单
+    pub fn setup_dock_menu() {
+        let mtm = MARK.get();
+        let target: Retained<DockMenuTarget> =
+            unsafe { msg_send![DockMenuTarget::alloc(mtm), init] };
+        let menu = NSMenu::new(mtm);
+        for (title, action) in [
+            (t!("tray.tun_mode"), sel!(toggleTun:)),
+            (t!("tray.system_proxy"), sel!(toggleSystemProxy:)),
+            (t!("tray.more.restart_clash"), sel!(restartCore:)),
+        ] {
+            let item = unsafe {
+                NSMenuItem::initWithTitle_action_keyEquivalent(
+                    NSMenuItem::alloc(mtm),
+                    &NSString::from_str(&title),
+                    Some(action),
+                    &NSString::new(),
+                )
+            };
+            unsafe { item.setTarget(Some(&target)) };
+            menu.addItem(&item);
+        }
+
+        let app = NSApplication::sharedApplication(mtm);
+        let Some(delegate) = (unsafe { app.delegate() }) else {
+            tracing::warn!("application delegate is not set, skip dock menu");
+            return;
+        };
+        let delegate: &AnyObject = delegate.as_ref();
+        let added = unsafe {
+            ffi::class_addMethod(
+                delegate.class() as *const _ as *mut _,
+                sel!(applicationDockMenu:),
+                std::mem::transmute::<
+                    unsafe extern "C-unwind" fn(*mut AnyObject, Sel, *mut AnyObject) -> *mut NSMenu,
+                    Imp,
+                >(application_dock_menu),
+                c"@@:@".as_ptr(),
+            )
+        };
+        if !added.as_bool() {
+            tracing::warn!("application delegate already provides a dock menu");
+        }
+        DOCK_MENU.set(Some((menu, target)));
+    }
+}
+
+/// 设置 Dock 图标的右键菜单，非 macOS 为空操作
+#[allow(unused_variables)]
+pub fn setup_dock_menu(app_handle: &tauri::AppHandle) {
+    #[cfg(target_os = "macos")]
+    crate::log_err!(app_handle.run_on_main_thread(macos::setup_dock_menu));
 }
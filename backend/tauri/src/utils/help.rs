@@ -5,6 +5,7 @@ use serde::{Serialize, de::DeserializeOwned};
 use serde_yaml::{Mapping, Value};
 use std::{
     fs,
+    io::{BufWriter, Write},
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -50,22 +51,30 @@ pub fn read_merge_mapping(path: &PathBuf) -> Result<Mapping> {
 
 /// save the data to the file
 /// can set `prefix` string to add some comments
+/// 直接序列化到同目录的临时文件，落盘后再替换原文件，写入中断时原文件保持不变
 pub fn save_yaml<T: Serialize, P: AsRef<Path>>(
     path: P,
     data: &T,
     prefix: Option<&str>,
 ) -> Result<()> {
     let path = path.as_ref();
-    let data_str = serde_yaml::to_string(data)?;
-
-    let yaml_str = match prefix {
-        Some(prefix) => format!("{prefix}\n\n{data_str}"),
-        None => data_str,
-    };
-
     let path_str = path.as_os_str().to_string_lossy().to_string();
-    fs::write(path, yaml_str.as_bytes())
-        .with_context(|| format!("failed to save file \"{path_str}\""))
+    let write = || -> Result<()> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut writer = BufWriter::new(tempfile::NamedTempFile::new_in(dir)?);
+        if let Some(prefix) = prefix {
+            write!(writer, "{prefix}\n\n")?;
+        }
+        serde_yaml::to_writer(&mut writer, data)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.as_file().sync_all()?;
+        file.persist(path)?;
+        Ok(())
+    };
+    write().with_context(|| format!("failed to save file \"{path_str}\""))
 }
 
 const ALPHABET: [char; 62] = [
@@ -177,13 +186,12 @@ pub fn get_clash_external_port(
 #[instrument(skip(app_handle))]
 pub fn cleanup_processes(app_handle: &AppHandle) {
     let _ = super::resolve::save_window_state(app_handle, true);
-    super::resolve::resolve_reset();
     let widget_manager = app_handle.state::<crate::widget::WidgetManager>();
-    let _ = nyanpasu_utils::runtime::block_on(async {
+    nyanpasu_utils::runtime::block_on(async {
         if let Err(e) = widget_manager.stop().await {
             log::error!("failed to stop widget manager: {e:?}");
         };
-        crate::core::CoreManager::global().stop_core().await
+        crate::core::shutdown::graceful_shutdown().await
     });
     #[cfg(windows)]
     crate::shutdown_hook::set_ready_for_shutdown();
@@ -279,3 +287,23 @@ fn test_parse_value() {
     assert_eq!(parse_str::<usize>(test_1, "expire1"), None);
     assert_eq!(parse_str::<usize>(test_2, "attachment"), None);
 }
+
+#[test]
+fn test_save_yaml_keeps_original_on_failure() {
+    struct Broken;
+    impl Serialize for Broken {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("broken"))
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    save_yaml(&path, &Mapping::from_iter([("a".into(), 1.into())]), None).unwrap();
+    let original = fs::read_to_string(&path).unwrap();
+
+    assert!(save_yaml(&path, &Broken, Some("# test")).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    // 临时文件不会残留
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
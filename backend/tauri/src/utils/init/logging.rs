@@ -1,10 +1,14 @@
-use crate::{Config, config, utils::dirs};
+use crate::{
+    Config, config,
+    utils::{dirs, redact::LogRedactor},
+};
 use anyhow::{Result, anyhow, bail};
 use parking_lot::Mutex;
 #[cfg(debug_assertions)]
 use std::io::IsTerminal;
 use std::{
     fs,
+    io::{self, Write},
     sync::{
         OnceLock,
         mpsc::{self, Sender},
@@ -17,7 +21,12 @@ use tracing_appender::{
     rolling::Rotation,
 };
 use tracing_log::log_tracer;
-use tracing_subscriber::{EnvFilter, filter, fmt, layer::SubscriberExt, reload};
+use tracing_subscriber::{
+    EnvFilter, filter,
+    fmt::{self, MakeWriter},
+    layer::SubscriberExt,
+    reload,
+};
 
 use super::nyanpasu::LoggingLevel;
 
@@ -42,6 +51,34 @@ pub fn refresh_logger(signal: ReloadSignal) -> Result<()> {
     }
 }
 
+/// 写入前对每条日志脱敏，fmt 层每个事件只调用一次 `write`
+pub struct RedactingWriter<M>(M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(line) => {
+                self.0
+                    .write_all(LogRedactor::global().redact(line).as_bytes())?;
+                Ok(buf.len())
+            }
+            Err(_) => self.0.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
 fn get_file_appender(max_files: usize) -> Result<(NonBlocking, WorkerGuard)> {
     let log_dir = dirs::app_logs_dir().unwrap();
     let file_appender = tracing_appender::rolling::Builder::new()
@@ -75,7 +112,7 @@ pub fn init() -> Result<()> {
     let (file_layer, file_handle) = reload::Layer::new(
         fmt::layer()
             .json()
-            .with_writer(appender)
+            .with_writer(RedactingWriter(appender))
             .with_current_span(true)
             .with_line_number(true)
             .with_file(true),
@@ -115,7 +152,9 @@ pub fn init() -> Result<()> {
                     }
                 };
                 _guard = guard;
-                if let Err(e) = file_handle.modify(|layer| *layer.writer_mut() = appender) {
+                if let Err(e) =
+                    file_handle.modify(|layer| *layer.writer_mut() = RedactingWriter(appender))
+                {
                     error!("failed to modify file appender: {}", e);
                 }
             }
@@ -130,7 +169,7 @@ pub fn init() -> Result<()> {
         .with_target(false)
         .with_file(true)
         .with_line_number(true)
-        .with_writer(std::io::stdout);
+        .with_writer(RedactingWriter(std::io::stdout));
 
     let subscriber = tracing_subscriber::registry().with(filter).with(file_layer);
     #[cfg(debug_assertions)]
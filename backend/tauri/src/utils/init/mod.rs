@@ -205,6 +205,10 @@ pub fn init_resources() -> Result<()> {
 #[instrument]
 pub fn init_service() -> Result<()> {
     use nyanpasu_utils::runtime::block_on;
+    if crate::consts::is_safe_mode() {
+        tracing::info!("safe mode, skip service initialization");
+        return Ok(());
+    }
     tracing::debug!("init services");
     block_on(async move {
         let enable_service = {
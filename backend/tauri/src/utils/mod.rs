@@ -1,10 +1,13 @@
 pub mod candy;
 pub mod config;
+pub mod deep_link;
 pub mod dialog;
 pub mod dirs;
 pub mod help;
 pub mod init;
+pub mod redact;
 pub mod resolve;
+pub mod tls;
 // mod winhelp;
 pub mod downloader;
 #[cfg(windows)]
@@ -18,3 +21,4 @@ pub mod open;
 pub mod dock;
 pub mod platform;
 pub mod sudo;
+pub mod telemetry;
@@ -32,3 +32,631 @@ pub async fn get_ipsb_asn() -> anyhow::Result<serde_json::Value> {
     let data: serde_json::Value = response.json().await?;
     Ok(data)
 }
+
+/// 回环地址与所有地址都能绑定时才视为可用，Windows 下两者可以同时被不同进程绑定
+pub fn is_port_available(port: u16) -> bool {
+    use std::net::{Ipv4Addr, TcpListener};
+    port != 0
+        && TcpListener::bind((Ipv4Addr::LOCALHOST, port)).is_ok()
+        && TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).is_ok()
+}
+
+/// 先尝试 `preferred`，再按顺序扫描 `scan_range`
+pub fn find_available_port(preferred: u16, scan_range: std::ops::Range<u16>) -> Option<u16> {
+    std::iter::once(preferred)
+        .chain(scan_range)
+        .find(|port| is_port_available(*port))
+}
+
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct PortSubstituted {
+    pub original: u16,
+    pub actual: u16,
+}
+
+/// 混合端口被占用时，在其后的端口中查找可用端口
+/// 替换只作用于本次运行的核心配置，不写入设置，由用户决定是否保存
+pub struct ClashMixedPortRangeScanner {
+    /// 向后扫描的端口数
+    pub width: u16,
+}
+
+impl Default for ClashMixedPortRangeScanner {
+    fn default() -> Self {
+        Self { width: 100 }
+    }
+}
+
+static MIXED_PORT_SUBSTITUTION: parking_lot::Mutex<Option<PortSubstituted>> =
+    parking_lot::Mutex::new(None);
+
+impl ClashMixedPortRangeScanner {
+    pub fn scan_range(&self, preferred: u16) -> std::ops::Range<u16> {
+        let start = preferred.saturating_add(1);
+        start..start.saturating_add(self.width)
+    }
+
+    pub fn suggest(&self, preferred: u16) -> Option<u16> {
+        find_available_port(preferred, self.scan_range(preferred))
+    }
+
+    /// 端口被占用时返回替换后的端口，并推送 `port-substituted` 事件
+    pub fn substitute(&self, port: u16) -> Option<u16> {
+        if is_port_available(port) {
+            *MIXED_PORT_SUBSTITUTION.lock() = None;
+            return None;
+        }
+        let actual = find_available_port(port, self.scan_range(port))?;
+        log::warn!(target: "app", "mixed port {port} is in use, use {actual} instead");
+        let substituted = PortSubstituted {
+            original: port,
+            actual,
+        };
+        *MIXED_PORT_SUBSTITUTION.lock() = Some(substituted.clone());
+        crate::log_err!(crate::core::handle::Handle::emit(
+            "port-substituted",
+            substituted
+        ));
+        Some(actual)
+    }
+
+    /// 设置中的端口在本次运行中被替换成的端口
+    pub fn substituted(original: u16) -> Option<u16> {
+        MIXED_PORT_SUBSTITUTION
+            .lock()
+            .as_ref()
+            .filter(|substituted| substituted.original == original)
+            .map(|substituted| substituted.actual)
+    }
+}
+
+/// 网络接口变化事件
+#[derive(Debug, Clone, Default, serde::Serialize, specta::Type)]
+pub struct NetworkInterfaceChanged {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl NetworkInterfaceChanged {
+    fn diff(
+        previous: &std::collections::BTreeSet<String>,
+        current: &std::collections::BTreeSet<String>,
+    ) -> Self {
+        Self {
+            added: current.difference(previous).cloned().collect(),
+            removed: previous.difference(current).cloned().collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// 监听网络接口变化，主接口变化时可自动重启核心
+pub struct NetworkChangeDetector {
+    task: parking_lot::Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+/// 接口变化后等待网络稳定的时间，期间的新事件会重新计时
+const NETWORK_STABILIZE_DELAY: Duration = Duration::from_secs(2);
+/// 两次自动重启核心的最小间隔
+const AUTO_RESTART_COOLDOWN: Duration = Duration::from_secs(30);
+/// 容器、虚拟机与代理软件创建的虚拟网卡，它们的变化不视为网络切换
+const VIRTUAL_INTERFACE_PREFIXES: &[&str] = &[
+    "tun",
+    "utun",
+    "tap",
+    "wintun",
+    "docker",
+    "br-",
+    "veth",
+    "virbr",
+    "vmnet",
+    "vboxnet",
+    "vethernet",
+    "meta",
+    "mihomo",
+    "clash",
+];
+
+/// 核心自己的 TUN 网卡与常见的虚拟网卡
+fn is_virtual_interface(name: &str, tun_device: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name == tun_device.to_ascii_lowercase()
+        || VIRTUAL_INTERFACE_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// 等待事件停止 `quiet` 时长，合并连续的事件，通知关闭时返回 false
+async fn debounce(rx: &mut tokio::sync::broadcast::Receiver<()>, quiet: Duration) -> bool {
+    use tokio::sync::broadcast::error::RecvError;
+    loop {
+        match tokio::time::timeout(quiet, rx.recv()).await {
+            Err(_) => return true,
+            Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => {}
+            Ok(Err(RecvError::Closed)) => return false,
+        }
+    }
+}
+
+impl NetworkChangeDetector {
+    pub fn global() -> &'static Self {
+        static DETECTOR: std::sync::OnceLock<NetworkChangeDetector> = std::sync::OnceLock::new();
+        DETECTOR.get_or_init(|| Self {
+            task: parking_lot::Mutex::new(None),
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task.lock().as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    pub fn start(&self) -> anyhow::Result<()> {
+        let mut task = self.task.lock();
+        if task.as_ref().is_some_and(|t| !t.is_finished()) {
+            return Ok(());
+        }
+        let mut rx = platform::subscribe()?;
+        *task = Some(tauri::async_runtime::spawn(async move {
+            let mut snapshot = active_interfaces();
+            let mut last_restart: Option<std::time::Instant> = None;
+            loop {
+                match rx.recv().await {
+                    Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+                if !debounce(&mut rx, NETWORK_STABILIZE_DELAY).await {
+                    break;
+                }
+                let current = active_interfaces();
+                let changed = NetworkInterfaceChanged::diff(&snapshot, &current);
+                snapshot = current;
+                if changed.is_empty() {
+                    continue;
+                }
+                tracing::info!("network interfaces changed: {changed:?}");
+                crate::log_err!(crate::core::handle::Handle::emit(
+                    "network-interface-changed",
+                    changed
+                ));
+                let auto_restart = crate::config::Config::verge()
+                    .latest()
+                    .auto_restart_on_network_change
+                    .unwrap_or(false);
+                if !auto_restart {
+                    continue;
+                }
+                if last_restart.is_some_and(|at| at.elapsed() < AUTO_RESTART_COOLDOWN) {
+                    tracing::info!(
+                        "core was restarted recently, skip restarting on network change"
+                    );
+                    continue;
+                }
+                last_restart = Some(std::time::Instant::now());
+                crate::log_err!(
+                    crate::core::CoreManager::global().run_core().await,
+                    "failed to restart core after network change"
+                );
+                // 重启本身引起的接口变化不再触发重启
+                if !debounce(&mut rx, NETWORK_STABILIZE_DELAY).await {
+                    break;
+                }
+                snapshot = active_interfaces();
+            }
+        }));
+        Ok(())
+    }
+
+    /// 停止处理任务与系统的变化通知
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().take() {
+            task.abort();
+        }
+        platform::stop();
+    }
+}
+
+/// 当前拥有非回环地址的接口，不含虚拟网卡
+fn active_interfaces() -> std::collections::BTreeSet<String> {
+    let tun_device = super::platform::tun_device();
+    let networks = sysinfo::Networks::new_with_refreshed_list();
+    networks
+        .iter()
+        .filter(|(name, _)| !is_virtual_interface(name, &tun_device))
+        .filter(|(_, data)| {
+            data.ip_networks()
+                .iter()
+                .any(|ip| !ip.addr.is_loopback() && !ip.addr.is_unspecified())
+        })
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// 本机可供局域网访问的 IPv4 地址，不含回环、链路本地地址以及 TUN、容器等虚拟网卡的地址
+pub fn lan_ipv4_addrs() -> Vec<std::net::Ipv4Addr> {
+    let tun_device = super::platform::tun_device();
+    let networks = sysinfo::Networks::new_with_refreshed_list();
+    let mut addrs = networks
+        .iter()
+        .filter(|(name, _)| !is_virtual_interface(name, &tun_device))
+        .flat_map(|(_, data)| data.ip_networks().iter())
+        .filter_map(|ip| match ip.addr {
+            std::net::IpAddr::V4(addr)
+                if !addr.is_loopback() && !addr.is_link_local() && !addr.is_unspecified() =>
+            {
+                Some(addr)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    addrs.sort();
+    addrs.dedup();
+    addrs
+}
+
+/// 能否绑定 IPv6 回环地址，用于判断系统是否启用了 IPv6 协议栈
+pub fn ipv6_supported() -> bool {
+    std::net::UdpSocket::bind("[::1]:0").is_ok()
+}
+
+/// 当前连接的网络是否为公用网络，无法判断时返回 None
+/// Windows 读取网络类别，Linux 读取 NetworkManager 连接所属的防火墙区域
+pub fn is_public_network() -> Option<bool> {
+    network_category::is_public()
+}
+
+mod network_category {
+    #[cfg(windows)]
+    pub fn is_public() -> Option<bool> {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+        let output = std::process::Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                "(Get-NetConnectionProfile).NetworkCategory",
+            ])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let categories = stdout
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        if !output.status.success() || categories.is_empty() {
+            return None;
+        }
+        Some(categories.contains(&"Public"))
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn is_public() -> Option<bool> {
+        use std::process::Command;
+
+        fn run(program: &str, args: &[&str]) -> Option<String> {
+            let output = Command::new(program).args(args).output().ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+
+        let connections = run(
+            "nmcli",
+            &["-t", "-f", "NAME,TYPE", "connection", "show", "--active"],
+        )?;
+        let mut zones = Vec::new();
+        for line in connections.lines() {
+            // `-t` 模式下名称中的 `:` 会被转义为 `\:`
+            let Some((name, kind)) = line.rsplit_once(':') else {
+                continue;
+            };
+            if matches!(kind, "loopback" | "bridge" | "tun") {
+                continue;
+            }
+            let name = name.replace("\\:", ":");
+            zones.push(run(
+                "nmcli",
+                &["-g", "connection.zone", "connection", "show", "id", &name],
+            )?);
+        }
+        if zones.is_empty() {
+            return None;
+        }
+        // 未指定区域的连接使用 firewalld 的默认区域
+        let default_zone = zones
+            .iter()
+            .any(String::is_empty)
+            .then(|| run("firewall-cmd", &["--get-default-zone"]))
+            .flatten();
+        Some(zones.iter().any(|zone| {
+            let zone = if zone.is_empty() {
+                default_zone.as_deref().unwrap_or_default()
+            } else {
+                zone.as_str()
+            };
+            zone == "public"
+        }))
+    }
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    pub fn is_public() -> Option<bool> {
+        None
+    }
+}
+
+/// 各平台的接口变化通知，只负责发出信号，具体变化由 `active_interfaces` 计算
+mod platform {
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tokio::sync::broadcast;
+
+    static NOTIFIER: Mutex<Option<broadcast::Sender<()>>> = Mutex::new(None);
+    /// 每次停止时递增，监听线程发现代数变化后退出
+    static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+    fn notify() {
+        if let Some(tx) = NOTIFIER.lock().as_ref() {
+            let _ = tx.send(());
+        }
+    }
+
+    fn is_current(generation: u64) -> bool {
+        GENERATION.load(Ordering::Acquire) == generation
+    }
+
+    /// 监听成功启动后才记录通知通道，之后的调用只是订阅
+    pub fn subscribe() -> anyhow::Result<broadcast::Receiver<()>> {
+        let mut notifier = NOTIFIER.lock();
+        if let Some(tx) = notifier.as_ref() {
+            return Ok(tx.subscribe());
+        }
+        let (tx, rx) = broadcast::channel(16);
+        watch(GENERATION.load(Ordering::Acquire))?;
+        *notifier = Some(tx);
+        Ok(rx)
+    }
+
+    /// 停止监听，订阅者会收到通道关闭
+    pub fn stop() {
+        if NOTIFIER.lock().take().is_none() {
+            return;
+        }
+        GENERATION.fetch_add(1, Ordering::AcqRel);
+        unwatch();
+    }
+
+    /// 监听线程通过代数自行退出
+    #[cfg(not(windows))]
+    fn unwatch() {}
+
+    #[cfg(target_os = "linux")]
+    fn watch(generation: u64) -> anyhow::Result<()> {
+        use nix::sys::{
+            socket::{
+                AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType, bind, recv,
+                setsockopt, socket, sockopt::ReceiveTimeout,
+            },
+            time::TimeVal,
+        };
+        use std::os::fd::AsRawFd;
+
+        const RTMGRP_LINK: u32 = 0x1;
+        const RTMGRP_IPV4_IFADDR: u32 = 0x10;
+        const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+
+        let fd = socket(
+            AddressFamily::Netlink,
+            SockType::Raw,
+            SockFlag::SOCK_CLOEXEC,
+            SockProtocol::NetlinkRoute,
+        )?;
+        bind(
+            fd.as_raw_fd(),
+            &NetlinkAddr::new(0, RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR),
+        )?;
+        // 定期醒来检查是否已停止
+        setsockopt(&fd, ReceiveTimeout, &TimeVal::new(1, 0))?;
+        std::thread::Builder::new()
+            .name("network-monitor".into())
+            .spawn(move || {
+                let mut buf = [0u8; 8192];
+                while is_current(generation) {
+                    match recv(fd.as_raw_fd(), &mut buf, MsgFlags::empty()) {
+                        Ok(_) => notify(),
+                        Err(nix::errno::Errno::EINTR | nix::errno::Errno::EAGAIN) => continue,
+                        Err(e) => {
+                            tracing::error!("rtnetlink socket error: {e}");
+                            break;
+                        }
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn watch(generation: u64) -> anyhow::Result<()> {
+        use anyhow::Context;
+        use core_foundation::{
+            array::CFArray,
+            runloop::{CFRunLoop, kCFRunLoopCommonModes, kCFRunLoopDefaultMode},
+            string::CFString,
+        };
+        use system_configuration::dynamic_store::{
+            SCDynamicStore, SCDynamicStoreBuilder, SCDynamicStoreCallBackContext,
+        };
+
+        fn callback(_store: SCDynamicStore, _changed: CFArray<CFString>, _info: &mut ()) {
+            notify();
+        }
+
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("network-monitor".into())
+            .spawn(move || {
+                let store = SCDynamicStoreBuilder::new("clash-nyanpasu-network-monitor")
+                    .callback_context(SCDynamicStoreCallBackContext {
+                        callout: callback,
+                        info: (),
+                    })
+                    .build();
+                let Some(store) = store else {
+                    let _ = ready_tx.send(Err(anyhow::anyhow!("failed to create SCDynamicStore")));
+                    return;
+                };
+                let keys = CFArray::<CFString>::from_CFTypes(&[
+                    CFString::from_static_string("State:/Network/Global/IPv4"),
+                    CFString::from_static_string("State:/Network/Global/IPv6"),
+                ]);
+                let patterns = CFArray::<CFString>::from_CFTypes(&[CFString::from_static_string(
+                    "State:/Network/Interface/.*/IPv.",
+                )]);
+                if !store.set_notification_keys(&keys, &patterns) {
+                    let _ = ready_tx.send(Err(anyhow::anyhow!("failed to set notification keys")));
+                    return;
+                }
+                let source = store.create_run_loop_source();
+                CFRunLoop::get_current().add_source(&source, unsafe { kCFRunLoopCommonModes });
+                let _ = ready_tx.send(Ok(()));
+                // 定期返回检查是否已停止
+                while is_current(generation) {
+                    CFRunLoop::run_in_mode(
+                        unsafe { kCFRunLoopDefaultMode },
+                        std::time::Duration::from_secs(1),
+                        false,
+                    );
+                }
+            })?;
+        ready_rx
+            .recv()
+            .context("network monitor thread exited unexpectedly")?
+    }
+
+    /// 注册的通知句柄，停止时取消注册
+    #[cfg(windows)]
+    static NOTIFY_HANDLE: Mutex<Option<usize>> = Mutex::new(None);
+
+    #[cfg(windows)]
+    fn unwatch() {
+        use windows_sys::Win32::NetworkManagement::IpHelper::CancelMibChangeNotify2;
+        if let Some(handle) = NOTIFY_HANDLE.lock().take() {
+            unsafe { CancelMibChangeNotify2(handle as _) };
+        }
+    }
+
+    #[cfg(windows)]
+    fn watch(_generation: u64) -> anyhow::Result<()> {
+        use windows_sys::Win32::{
+            Foundation::{HANDLE, NO_ERROR},
+            NetworkManagement::IpHelper::{
+                MIB_IPINTERFACE_ROW, MIB_NOTIFICATION_TYPE, NotifyIpInterfaceChange,
+            },
+            Networking::WinSock::AF_UNSPEC,
+        };
+
+        unsafe extern "system" fn callback(
+            _context: *const core::ffi::c_void,
+            _row: *const MIB_IPINTERFACE_ROW,
+            _notification_type: MIB_NOTIFICATION_TYPE,
+        ) {
+            notify();
+        }
+
+        let mut handle: HANDLE = std::ptr::null_mut();
+        let ret = unsafe {
+            NotifyIpInterfaceChange(
+                AF_UNSPEC,
+                Some(callback),
+                std::ptr::null(),
+                false,
+                &mut handle,
+            )
+        };
+        if ret != NO_ERROR {
+            anyhow::bail!("NotifyIpInterfaceChange failed: {ret}");
+        }
+        *NOTIFY_HANDLE.lock() = Some(handle as usize);
+        Ok(())
+    }
+
+    /// 其他平台退化为轮询
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    fn watch(generation: u64) -> anyhow::Result<()> {
+        std::thread::Builder::new()
+            .name("network-monitor".into())
+            .spawn(move || {
+                while is_current(generation) {
+                    std::thread::sleep(std::time::Duration::from_secs(10));
+                    notify();
+                }
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpListener};
+
+    #[test]
+    fn test_find_available_port() {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let busy = listener.local_addr().unwrap().port();
+        assert!(!is_port_available(busy));
+
+        let scanner = ClashMixedPortRangeScanner { width: 20 };
+        let port = scanner.suggest(busy).unwrap();
+        assert!(scanner.scan_range(busy).contains(&port));
+        assert_eq!(find_available_port(port, 0..0), Some(port));
+        assert_eq!(find_available_port(busy, 0..0), None);
+        assert_eq!(scanner.scan_range(u16::MAX), u16::MAX..u16::MAX);
+    }
+
+    #[test]
+    fn test_virtual_interfaces_are_ignored() {
+        for name in [
+            "Meta",
+            "utun4",
+            "tun0",
+            "docker0",
+            "br-1a2b",
+            "veth12ab",
+            "vEthernet (WSL)",
+        ] {
+            assert!(is_virtual_interface(name, "Mihomo"), "{name}");
+        }
+        assert!(is_virtual_interface("custom-tun", "custom-tun"));
+        for name in ["eth0", "en0", "wlan0", "Wi-Fi", "Ethernet"] {
+            assert!(!is_virtual_interface(name, "Meta"), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_debounce_merges_bursts() {
+        let (tx, mut rx) = tokio::sync::broadcast::channel(16);
+        let quiet = Duration::from_millis(100);
+        let sender = tokio::spawn(async move {
+            for _ in 0..5 {
+                tx.send(()).unwrap();
+                tokio::time::sleep(Duration::from_millis(30)).await;
+            }
+            tokio::time::sleep(quiet * 3).await;
+            tx
+        });
+        let started = std::time::Instant::now();
+        assert!(debounce(&mut rx, quiet).await);
+        // 连续事件期间不会提前返回
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert!(rx.try_recv().is_err());
+
+        drop(sender.await.unwrap());
+        assert!(!debounce(&mut rx, quiet).await);
+    }
+}
@@ -1,5 +1,13 @@
 // Platform-specific utilities consolidated from various modules
 
+use crate::{
+    core::privilege::{PrivilegedOperation, manager::PrivilegeManager, validation},
+    log_err,
+    utils::dirs,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+
 #[cfg(target_os = "windows")]
 pub mod windows {
     // Future Windows-specific utilities can be added here
@@ -9,3 +17,477 @@ pub mod windows {
 pub mod linux {
     // Future Linux-specific utilities can be added here
 }
+
+/// 运行时配置中的 TUN 网卡名称，未设置时为核心的默认值
+pub(crate) fn tun_device() -> String {
+    crate::config::Config::runtime()
+        .latest()
+        .config
+        .as_ref()
+        .and_then(|config| config.get("tun"))
+        .and_then(|tun| tun.get("device"))
+        .and_then(|device| device.as_str())
+        .map_or_else(
+            || strict_routing::DEFAULT_TUN_DEVICE.to_string(),
+            str::to_string,
+        )
+}
+
+/// 已写入严格路由规则的网卡，退出时据此移除规则
+static APPLIED_STRICT_ROUTING: Mutex<Option<String>> = Mutex::new(None);
+
+/// 开启 TUN 与严格路由时写入规则，否则移除
+pub async fn sync_strict_routing() -> Result<()> {
+    let enabled = {
+        let verge = crate::config::Config::verge();
+        let verge = verge.latest();
+        verge.strict_routing.unwrap_or(false) && verge.enable_tun_mode.unwrap_or(false)
+    };
+    // 网卡名称来自运行时配置，订阅可以修改
+    let tun_device = tun_device();
+    validation::validate_device_name(&tun_device)?;
+    if enabled {
+        // 先写标记，写入规则时崩溃同样能在下次启动时清理
+        write_strict_routing_marker(Some(&tun_device));
+        execute_strict_routing(PrivilegedOperation::ApplyStrictRouting {
+            tun_device: tun_device.clone(),
+        })
+        .await?;
+        *APPLIED_STRICT_ROUTING.lock() = Some(tun_device);
+    } else {
+        execute_strict_routing(PrivilegedOperation::RemoveStrictRouting { tun_device }).await?;
+        *APPLIED_STRICT_ROUTING.lock() = None;
+        write_strict_routing_marker(None);
+    }
+    Ok(())
+}
+
+/// 退出时移除本次写入的严格路由规则，未写入时不请求权限
+pub async fn remove_strict_routing_on_exit() -> Result<()> {
+    let Some(tun_device) = APPLIED_STRICT_ROUTING.lock().take() else {
+        return Ok(());
+    };
+    execute_strict_routing(PrivilegedOperation::RemoveStrictRouting { tun_device }).await?;
+    write_strict_routing_marker(None);
+    Ok(())
+}
+
+/// 记录已写入规则的网卡，传入 `None` 时删除标记
+fn write_strict_routing_marker(tun_device: Option<&str>) {
+    let result = dirs::strict_routing_marker_path().and_then(|path| match tun_device {
+        Some(tun_device) => Ok(std::fs::write(path, tun_device)?),
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+    });
+    log_err!(result, "failed to update strict routing marker");
+}
+
+/// 启动时调用，标记仍在说明上次异常退出，移除残留的规则
+/// 否则 macOS 的 pf 规则会一直拦截当前用户的所有连接
+pub async fn recover_strict_routing() -> Result<()> {
+    let tun_device = match std::fs::read_to_string(dirs::strict_routing_marker_path()?) {
+        Ok(tun_device) => tun_device.trim().to_string(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let tun_device = if validation::validate_device_name(&tun_device).is_ok() {
+        tun_device
+    } else {
+        strict_routing::DEFAULT_TUN_DEVICE.to_string()
+    };
+    tracing::warn!(
+        "strict routing rules on `{tun_device}` were left by a previous crash, removing"
+    );
+    execute_strict_routing(PrivilegedOperation::RemoveStrictRouting { tun_device }).await?;
+    write_strict_routing_marker(None);
+    Ok(())
+}
+
+async fn execute_strict_routing(operation: PrivilegedOperation) -> Result<()> {
+    let result = PrivilegeManager::global()
+        .execute_operation(operation)
+        .await?;
+    if !result.success {
+        anyhow::bail!(
+            "failed to update strict routing rules: {}",
+            result.message.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// 检查绕过 TUN 的路由，如其他 VPN 客户端、Docker 或虚拟机网卡添加的路由
+pub fn check_split_tunnel_routes() -> Vec<strict_routing::SplitTunnelRoute> {
+    let tun_device = tun_device();
+    match strict_routing::list_routes() {
+        Ok(routes) => routes
+            .into_iter()
+            .filter(|route| strict_routing::is_bypass_interface(&route.interface, &tun_device))
+            .collect(),
+        Err(e) => {
+            tracing::warn!("failed to list routes: {e:?}");
+            Vec::new()
+        }
+    }
+}
+
+/// 把所有未标记的流量导向 TUN，避免其他网卡上的路由绕过代理
+/// Linux 使用策略路由，核心的出站流量通过 `routing-mark` 排除；macOS 使用 pf；Windows 调整网卡优先级
+/// 网卡名称只作为命令参数传入，不拼接进脚本
+pub mod strict_routing {
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    use crate::core::privilege::validation::validate_device_name;
+    use anyhow::Result;
+    use serde::Serialize;
+    use specta::Type;
+    use std::process::Command;
+
+    /// 核心出站流量的标记，与配置中的 `routing-mark` 一致
+    pub const ROUTE_MARK: u32 = 1;
+    #[cfg(any(target_os = "linux", test))]
+    const ROUTE_TABLE: u32 = 100;
+
+    #[cfg(target_os = "macos")]
+    pub const DEFAULT_TUN_DEVICE: &str = "utun";
+    #[cfg(not(target_os = "macos"))]
+    pub const DEFAULT_TUN_DEVICE: &str = "Meta";
+
+    /// 常见的 VPN、容器与虚拟机网卡名称前缀
+    const BYPASS_INTERFACE_PREFIXES: [&str; 14] = [
+        "tun",
+        "tap",
+        "wg",
+        "ppp",
+        "utun",
+        "ipsec",
+        "docker",
+        "br-",
+        "virbr",
+        "vboxnet",
+        "vmnet",
+        "zt",
+        "tailscale",
+        "vethernet",
+    ];
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Type)]
+    pub struct SplitTunnelRoute {
+        pub destination: String,
+        pub gateway: Option<String>,
+        pub interface: String,
+    }
+
+    pub fn is_bypass_interface(interface: &str, tun_device: &str) -> bool {
+        let interface = interface.to_lowercase();
+        // macOS 的 TUN 名称为 utunN，按前缀排除
+        if interface.starts_with(&tun_device.to_lowercase()) {
+            return false;
+        }
+        BYPASS_INTERFACE_PREFIXES
+            .iter()
+            .any(|prefix| interface.starts_with(prefix))
+            || interface.contains("vpn")
+    }
+
+    /// 解析 `ip route show` 的输出
+    #[cfg(any(target_os = "linux", test))]
+    fn parse_ip_routes(output: &str) -> Vec<SplitTunnelRoute> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let tokens = line.split_whitespace().collect::<Vec<_>>();
+                let destination = *tokens.first()?;
+                if matches!(
+                    destination,
+                    "local" | "broadcast" | "multicast" | "unreachable" | "blackhole" | "prohibit"
+                ) {
+                    return None;
+                }
+                let value_of = |key: &str| {
+                    tokens
+                        .iter()
+                        .position(|token| *token == key)
+                        .and_then(|i| tokens.get(i + 1))
+                        .map(|value| value.to_string())
+                };
+                Some(SplitTunnelRoute {
+                    destination: destination.to_string(),
+                    gateway: value_of("via"),
+                    interface: value_of("dev")?,
+                })
+            })
+            .collect()
+    }
+
+    /// 解析 `netstat -rn` 的输出
+    #[cfg(any(target_os = "macos", test))]
+    fn parse_netstat_routes(output: &str) -> Vec<SplitTunnelRoute> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let tokens = line.split_whitespace().collect::<Vec<_>>();
+                if tokens.len() < 4 || tokens[0] == "Destination" {
+                    return None;
+                }
+                Some(SplitTunnelRoute {
+                    destination: tokens[0].to_string(),
+                    gateway: Some(tokens[1].to_string()).filter(|g| !g.starts_with("link#")),
+                    interface: tokens[3].to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// 解析 `Get-NetRoute` 按 `目标 下一跳 网卡` 输出的每一行，网卡名可能包含空格
+    #[cfg(any(target_os = "windows", test))]
+    fn parse_net_routes(output: &str) -> Vec<SplitTunnelRoute> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut tokens = line.trim().splitn(3, ' ');
+                let destination = tokens.next()?.to_string();
+                let gateway = tokens.next()?;
+                let interface = tokens.next()?.trim().to_string();
+                Some(SplitTunnelRoute {
+                    destination,
+                    gateway: Some(gateway.to_string()).filter(|g| g != "0.0.0.0" && g != "::"),
+                    interface,
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    fn command_output(program: &str, args: &[&str]) -> Result<String> {
+        let output = Command::new(program).args(args).output()?;
+        if !output.status.success() {
+            anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn list_routes() -> Result<Vec<SplitTunnelRoute>> {
+        let mut routes = parse_ip_routes(&command_output("ip", &["route", "show"])?);
+        routes.extend(parse_ip_routes(&command_output(
+            "ip",
+            &["-6", "route", "show"],
+        )?));
+        Ok(routes)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn list_routes() -> Result<Vec<SplitTunnelRoute>> {
+        Ok(parse_netstat_routes(&command_output("netstat", &["-rn"])?))
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn list_routes() -> Result<Vec<SplitTunnelRoute>> {
+        Ok(parse_net_routes(&command_output(
+            "powershell",
+            &[
+                "-NoProfile",
+                "-Command",
+                "Get-NetRoute | ForEach-Object { \"$($_.DestinationPrefix) $($_.NextHop) $($_.InterfaceAlias)\" }",
+            ],
+        )?))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    pub fn list_routes() -> Result<Vec<SplitTunnelRoute>> {
+        Ok(Vec::new())
+    }
+
+    /// 先删除旧规则，保证重复执行时不会叠加，`$1` 为网卡名称
+    /// TUN 没有 IPv6 地址时 IPv6 流量直接不可达，避免从其他网卡绕出
+    #[cfg(any(target_os = "linux", test))]
+    fn ip_rules_script() -> String {
+        format!(
+            "{remove}; ip rule add not fwmark {ROUTE_MARK} table {ROUTE_TABLE} && ip route add default dev \"$1\" table {ROUTE_TABLE} && ip -6 rule add not fwmark {ROUTE_MARK} table {ROUTE_TABLE} && (ip -6 route add default dev \"$1\" table {ROUTE_TABLE} 2>/dev/null || ip -6 route add unreachable default table {ROUTE_TABLE})",
+            remove = remove_ip_rules_script()
+        )
+    }
+
+    #[cfg(any(target_os = "linux", test))]
+    fn remove_ip_rules_script() -> String {
+        ["ip", "ip -6"]
+            .map(|ip| {
+                format!(
+                    "({ip} rule del not fwmark {ROUTE_MARK} table {ROUTE_TABLE} 2>/dev/null || true) && ({ip} route flush table {ROUTE_TABLE} 2>/dev/null || true)"
+                )
+            })
+            .join(" && ")
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    const PF_ANCHOR: &str = "com.apple/nyanpasu-strict-routing";
+
+    /// 核心以 root 运行，只放行 root 的出站连接
+    /// 未指定地址族的规则同时作用于 IPv4 与 IPv6，`%s` 为网卡名称
+    #[cfg(any(target_os = "macos", test))]
+    fn pf_rules_template() -> &'static str {
+        "pass out quick on lo0 all\npass out quick on %s all\nblock return out quick proto { tcp udp } all user != 0\n"
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn apply_rules(tun_device: &str) -> Result<()> {
+        validate_device_name(tun_device)?;
+        super::run_elevated(&ip_rules_script(), &[tun_device])
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn remove_rules(_tun_device: &str) -> Result<()> {
+        super::run_elevated(&remove_ip_rules_script(), &[])
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn apply_rules(tun_device: &str) -> Result<()> {
+        validate_device_name(tun_device)?;
+        super::run_elevated(
+            &format!(
+                "printf '{}' \"$1\" | pfctl -a {PF_ANCHOR} -f - && (pfctl -E || true)",
+                pf_rules_template()
+            ),
+            &[tun_device],
+        )
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn remove_rules(_tun_device: &str) -> Result<()> {
+        super::run_elevated(&format!("pfctl -a {PF_ANCHOR} -F rules"), &[])
+    }
+
+    /// 网卡名称通过环境变量传给 PowerShell
+    #[cfg(target_os = "windows")]
+    fn set_interface(tun_device: &str, script: &str) -> Result<()> {
+        let output = Command::new("powershell")
+            .env("NYANPASU_TUN_DEVICE", tun_device)
+            .args(["-NoProfile", "-Command", script])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(())
+    }
+
+    /// 把 TUN 网卡的跃点数设为最小，使其路由优先于其他网卡，IPv4 与 IPv6 都会设置
+    #[cfg(target_os = "windows")]
+    pub fn apply_rules(tun_device: &str) -> Result<()> {
+        validate_device_name(tun_device)?;
+        set_interface(
+            tun_device,
+            "Set-NetIPInterface -InterfaceAlias $env:NYANPASU_TUN_DEVICE -InterfaceMetric 1",
+        )
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn remove_rules(tun_device: &str) -> Result<()> {
+        validate_device_name(tun_device)?;
+        set_interface(
+            tun_device,
+            "Set-NetIPInterface -InterfaceAlias $env:NYANPASU_TUN_DEVICE -AutomaticMetric Enabled -ErrorAction SilentlyContinue",
+        )
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    pub fn apply_rules(_tun_device: &str) -> Result<()> {
+        anyhow::bail!("strict routing is not supported on this platform")
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    pub fn remove_rules(_tun_device: &str) -> Result<()> {
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_routes() {
+            let routes = parse_ip_routes(
+                "default via 192.168.1.1 dev eth0 proto dhcp metric 100\n\
+                 10.8.0.0/24 via 10.8.0.1 dev tun0 proto static\n\
+                 172.17.0.0/16 dev docker0 proto kernel scope link src 172.17.0.1 linkdown\n\
+                 local 127.0.0.1 dev lo table local\n",
+            );
+            assert_eq!(routes.len(), 3);
+            assert_eq!(routes[1].gateway.as_deref(), Some("10.8.0.1"));
+            let bypass = routes
+                .iter()
+                .filter(|route| is_bypass_interface(&route.interface, "Meta"))
+                .map(|route| route.interface.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(bypass, ["tun0", "docker0"]);
+
+            let routes = parse_netstat_routes(
+                "Routing tables\n\nInternet:\nDestination        Gateway            Flags           Netif Expire\n\
+                 default            192.168.1.1        UGScg             en0\n\
+                 10.8/24            link#18            UCS             utun5\n",
+            );
+            assert_eq!(routes.len(), 2);
+            assert_eq!(routes[1].gateway, None);
+            assert!(!is_bypass_interface("utun5", "utun"));
+            assert!(is_bypass_interface("utun5", "utun9"));
+
+            let routes = parse_net_routes(
+                "0.0.0.0/0 192.168.1.1 Wi-Fi\n10.0.0.0/8 0.0.0.0 vEthernet (WSL)\n",
+            );
+            assert_eq!(routes[1].interface, "vEthernet (WSL)");
+            assert!(is_bypass_interface(&routes[1].interface, "Meta"));
+        }
+
+        #[test]
+        fn test_strict_routing_rules() {
+            let script = ip_rules_script();
+            assert!(script.contains(
+                "ip rule add not fwmark 1 table 100 && ip route add default dev \"$1\" table 100"
+            ));
+            assert!(script.contains("ip -6 rule add not fwmark 1 table 100"));
+            assert!(remove_ip_rules_script().contains("ip -6 route flush table 100"));
+            assert!(pf_rules_template().contains("pass out quick on %s all\n"));
+        }
+    }
+}
+
+/// 以 root 运行 `sh -c script sh args...`，参数不经过脚本拼接
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn run_elevated(script: &str, args: &[&str]) -> Result<()> {
+    use std::process::Command;
+
+    #[cfg(target_os = "macos")]
+    let output = {
+        // AppleScript 字符串字面量，`quoted form of` 再转为 shell 的单引号参数
+        let literal = |s: &str| {
+            format!(
+                "quoted form of \"{}\"",
+                s.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        };
+        let mut command = format!("\"sh -c \" & {} & \" sh\"", literal(script));
+        for arg in args {
+            command.push_str(&format!(" & \" \" & {}", literal(arg)));
+        }
+        let command = format!("do shell script {command} with administrator privileges");
+        Command::new("osascript").args(["-e", &command]).output()?
+    };
+
+    #[cfg(target_os = "linux")]
+    let output = {
+        let sudo = match which::which("pkexec") {
+            Ok(_) => "pkexec",
+            Err(_) => "sudo",
+        };
+        Command::new(sudo)
+            .args(["sh", "-c", script, "sh"])
+            .args(args)
+            .output()?
+    };
+
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
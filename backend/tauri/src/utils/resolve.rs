@@ -5,7 +5,7 @@ use crate::{
     },
     core::{storage::Storage, tray::proxies, *},
     log_err, trace_err,
-    utils::init,
+    utils::{init, telemetry::StartupTimingProfiler},
 };
 use anyhow::Result;
 use semver::Version;
@@ -14,7 +14,7 @@ use std::{
     net::TcpListener,
     sync::atomic::{AtomicU16, Ordering},
 };
-use tauri::{App, AppHandle, Emitter, Listener, Manager, async_runtime::block_on};
+use tauri::{App, AppHandle, Emitter, Listener, Manager};
 use tauri_plugin_shell::ShellExt;
 static OPEN_WINDOWS_COUNTER: AtomicU16 = AtomicU16::new(0);
 
@@ -109,9 +109,19 @@ pub fn resolve_setup(app: &mut App) {
 
     handle::Handle::global().init(app.app_handle().clone());
     crate::consts::setup_app_handle(app.app_handle().clone());
+    crate::utils::dock::setup_dock_menu(app.app_handle());
 
     log_err!(init::init_resources());
+    log_err!(crate::utils::tls::init_custom_ca());
+    // 需要在核心启动前检查，核心启动后端口会重新被占用
+    crate::core::proxy_recovery::recover_on_launch();
+    tauri::async_runtime::spawn(async {
+        log_err!(crate::utils::platform::recover_strict_routing().await);
+    });
+    crate::core::service::ipc::setup_metrics(app);
+    crate::core::service::control::setup_core_verification(app);
     log_err!(init::init_service());
+    StartupTimingProfiler::global().mark("service");
 
     // 处理随机端口
     let enable_random_port = Config::verge().latest().enable_random_port.unwrap_or(false);
@@ -161,12 +171,14 @@ pub fn resolve_setup(app: &mut App) {
     // 启动核心
     log::trace!("init config");
     log_err!(Config::init_config());
+    StartupTimingProfiler::global().mark("config");
 
     log::trace!("init storage");
     log_err!(crate::core::storage::setup(app));
 
     log::trace!("launch core");
     log_err!(CoreManager::global().init());
+    StartupTimingProfiler::global().mark("core");
 
     log::trace!("init clash connection connector");
     log_err!(crate::core::clash::setup(app));
@@ -179,11 +191,23 @@ pub fn resolve_setup(app: &mut App) {
         })
         .await
     }));
+    crate::event_handler::mount_handlers(app.app_handle());
 
-    log::trace!("init privilege management system");
-    log_err!(tauri::async_runtime::block_on(async {
-        crate::core::privilege::operations::initialize_privilege_system().await
-    }));
+    log::trace!("init tray traffic rates");
+    tray::rates::setup(app.app_handle().clone(), {
+        let manager = app.state::<crate::core::clash::ws::ClashConnectionsConnector>();
+        manager.subscribe()
+    });
+
+    let safe_mode = crate::consts::is_safe_mode();
+    if safe_mode {
+        log::warn!(target: "app", "running in safe mode, core, service, privilege system, system proxy and jobs are not started");
+    } else {
+        log::trace!("init privilege management system");
+        log_err!(tauri::async_runtime::block_on(async {
+            crate::core::privilege::operations::initialize_privilege_system().await
+        }));
+    }
 
     #[cfg(any(windows, target_os = "linux"))]
     log::trace!("init system tray");
@@ -201,40 +225,72 @@ pub fn resolve_setup(app: &mut App) {
         }));
     });
     log_err!(app.emit("update_systray", ()));
+    // 模式切换后刷新托盘勾选状态
+    let app_handle = app.app_handle().clone();
+    app.listen("clash-mode-changed", move |_| {
+        let app_handle_clone = app_handle.clone();
+        log_err!(app_handle.run_on_main_thread(move || {
+            log_err!(tray::Tray::update_part(&app_handle_clone));
+        }));
+    });
 
     let silent_start = { Config::verge().data().enable_silent_start };
     if !silent_start.unwrap_or(false) {
         create_window(app.app_handle());
     }
+    StartupTimingProfiler::global().mark("window");
 
     log_err!(sysopt::Sysopt::global().init_launch());
-    // System proxy functionality removed, only TUN mode remains
+    // 核心没有启动，代理端口不可用，不设置系统代理
+    if !safe_mode {
+        // System proxy functionality removed, only TUN mode remains
+        log_err!(sysopt::Sysopt::global().update_pac());
+    }
 
     log_err!(handle::Handle::update_systray_part());
     log_err!(hotkey::Hotkey::global().init(app.app_handle().clone()));
 
-    // setup jobs
-    log::trace!("setup jobs");
-    {
-        let storage = app.state::<Storage>();
-        let storage = (*storage).clone();
-        log_err!(crate::core::tasks::setup(app, storage));
+    // 定时任务、网络监控与策略组调度都依赖核心
+    if !safe_mode {
+        log::trace!("setup jobs");
+        {
+            let storage = app.state::<Storage>();
+            let storage = (*storage).clone();
+            log_err!(crate::core::tasks::setup(app, storage));
+        }
+
+        if Config::verge()
+            .latest()
+            .auto_restart_on_network_change
+            .unwrap_or(false)
+        {
+            log::trace!("start network monitor");
+            log_err!(crate::utils::net::NetworkChangeDetector::global().start());
+        }
+
+        crate::core::clash::policy::GroupPolicyManager::global().start();
     }
+    crate::core::activity::ActivityManager::global().start();
+    log_err!(crate::core::profile_sync::ProfileSyncManager::global().start());
 
     // test job
     proxies::setup_proxies();
     crate::core::storage::register_web_storage_listener(app.app_handle());
-}
 
-/// reset system proxy
-pub fn resolve_reset() {
-    log_err!(sysopt::Sysopt::global().reset_sysproxy());
-    log_err!(block_on(CoreManager::global().stop_core()));
+    StartupTimingProfiler::global().mark("setup");
+    tauri::async_runtime::spawn(async {
+        log_err!(
+            crate::utils::telemetry::flush().await,
+            "failed to send telemetry"
+        );
+    });
 }
 
 /// create main window
 #[tracing_attributes::instrument(skip(app_handle))]
 pub fn create_window(app_handle: &AppHandle) {
+    crate::core::activity::ActivityManager::global().set_visible(true);
+    crate::event_handler::mount_handlers(app_handle);
     if let Some(window) = app_handle.get_webview_window("main") {
         tracing::debug!("main window is already opened, try to show it");
         trace_err!(window.unminimize(), "set win unminimize");
@@ -262,6 +318,13 @@ pub fn create_window(app_handle: &AppHandle) {
     .always_on_top(always_on_top)
     .min_inner_size(400.0, 600.0);
 
+    // 便携模式下 WebView 的数据也放在 exe 旁，不写入 AppData
+    match crate::utils::dirs::webview_data_dir() {
+        Ok(Some(dir)) => builder = builder.data_directory(dir),
+        Ok(None) => {}
+        Err(e) => log::error!(target: "app", "failed to resolve webview data dir: {e:?}"),
+    }
+
     let win_state = &Config::verge().latest().window_size_state.clone();
     match win_state {
         Some(_) => {
@@ -0,0 +1,331 @@
+//! 匿名遥测，需要用户在设置中主动开启
+//! 崩溃报告只包含崩溃位置的文件名、payload 与调用栈各帧的哈希；启动耗时只上报所在的区间
+//! 崩溃时无法联网，报告先写入 `{data_dir}/telemetry`，下次启动后再发送
+use crate::{config::Config, utils::dirs};
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+const PENDING_DIR: &str = "telemetry";
+/// 启动耗时区间的上限（毫秒），超过最后一个值的记为 `u64::MAX`
+const TIMING_BUCKETS_MS: &[u64] = &[100, 250, 500, 1000, 2500, 5000, 10000];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    /// 随机生成的匿名 id，与设备和用户无关
+    pub user_id: String,
+    /// 上报地址，未设置时不会发送
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+/// 崩溃时读取配置可能再次 panic，开关状态另存一份
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn settings() -> TelemetrySettings {
+    Config::verge()
+        .latest()
+        .telemetry
+        .clone()
+        .unwrap_or_default()
+}
+
+/// 启动时以及设置变化后同步开关状态，关闭时删除未发送的报告
+/// 匿名 id 随同一次设置修改在 `feat::patch_verge` 中清除
+pub fn sync_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Release);
+    if !enabled {
+        crate::log_err!(clear_pending_reports());
+    }
+}
+
+/// 在启动最开始调用，同时作为启动耗时的起点
+pub fn init() {
+    StartupTimingProfiler::global();
+    sync_enabled(settings().enabled);
+}
+
+pub fn new_user_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+fn sha256_hex(data: impl AsRef<[u8]>) -> String {
+    hex::encode(Sha256::digest(data.as_ref()))
+}
+
+/// 路径只以哈希形式上报，避免泄露用户名等信息
+pub fn hash_path_for_telemetry(path: &Path) -> String {
+    sha256_hex(path.to_string_lossy().as_bytes())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct CrashReport {
+    /// 崩溃位置的文件名与行号，不含目录
+    pub location: Option<String>,
+    pub payload_hash: Option<String>,
+    /// 调用栈每一帧符号名的哈希前 16 位
+    pub backtrace_hashes: Vec<String>,
+    pub app_version: String,
+    pub timestamp: i64,
+}
+
+/// 只取 `N: symbol` 形式的行，`at /path/to/file.rs` 形式的源码路径不参与计算
+fn backtrace_hashes(backtrace: &str) -> Vec<String> {
+    backtrace
+        .lines()
+        .filter_map(|line| {
+            let (index, symbol) = line.trim().split_once(": ")?;
+            index.parse::<usize>().ok()?;
+            Some(sha256_hex(symbol.trim())[..16].to_string())
+        })
+        .collect()
+}
+
+/// `file:line:column` 只保留文件名，兼容 Windows 路径
+fn scrub_location(location: &str) -> String {
+    let (file, position) = match location.rsplitn(3, ':').collect::<Vec<_>>()[..] {
+        [column, line, file] => (file, format!(":{line}:{column}")),
+        _ => (location, String::new()),
+    };
+    let file = file.rsplit(['/', '\\']).next().unwrap_or_default();
+    format!("{file}{position}")
+}
+
+pub struct AppCrashReporter;
+
+impl AppCrashReporter {
+    pub fn build_report(
+        payload: Option<&str>,
+        location: Option<&str>,
+        backtrace: &str,
+    ) -> CrashReport {
+        CrashReport {
+            location: location.map(scrub_location),
+            payload_hash: payload.map(sha256_hex),
+            backtrace_hashes: backtrace_hashes(backtrace),
+            app_version: dirs::APP_VERSION.to_string(),
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// 在 panic hook 中调用，未开启遥测时什么都不做
+    pub fn record_panic(payload: Option<&str>, location: Option<&str>, backtrace: &str) {
+        if !ENABLED.load(Ordering::Acquire) {
+            return;
+        }
+        let report = Self::build_report(payload, location, backtrace);
+        if let Err(e) = save_pending_report(&report) {
+            log::error!(target: "app", "failed to save crash report: {e:?}");
+        }
+    }
+}
+
+fn pending_dir() -> Result<PathBuf> {
+    Ok(dirs::app_data_dir()?.join(PENDING_DIR))
+}
+
+fn save_pending_report(report: &CrashReport) -> Result<()> {
+    let dir = pending_dir()?;
+    fs::create_dir_all(&dir)?;
+    // 同一秒内的多次崩溃不能互相覆盖
+    let path = dir.join(format!(
+        "crash-{}-{}.json",
+        report.timestamp,
+        uuid::Uuid::new_v4().simple()
+    ));
+    fs::write(&path, serde_json::to_vec(report)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn pending_reports() -> Result<Vec<(PathBuf, CrashReport)>> {
+    let dir = pending_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut reports = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        match fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice(&bytes)?))
+        {
+            Ok(report) => reports.push((path, report)),
+            Err(e) => {
+                log::warn!(target: "app", "drop invalid crash report {}: {e:?}", path.display());
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+fn clear_pending_reports() -> Result<()> {
+    let dir = pending_dir()?;
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct PhaseTiming {
+    pub phase: String,
+    /// 耗时所在区间的上限（毫秒）
+    pub bucket_ms: u64,
+}
+
+fn timing_bucket(duration: Duration) -> u64 {
+    let ms = duration.as_millis() as u64;
+    TIMING_BUCKETS_MS
+        .iter()
+        .copied()
+        .find(|bucket| ms <= *bucket)
+        .unwrap_or(u64::MAX)
+}
+
+/// 记录启动各阶段的耗时
+pub struct StartupTimingProfiler {
+    last: Mutex<Instant>,
+    phases: Mutex<Vec<PhaseTiming>>,
+}
+
+impl StartupTimingProfiler {
+    pub fn global() -> &'static StartupTimingProfiler {
+        static PROFILER: Lazy<StartupTimingProfiler> = Lazy::new(|| StartupTimingProfiler {
+            last: Mutex::new(Instant::now()),
+            phases: Mutex::new(Vec::new()),
+        });
+        &PROFILER
+    }
+
+    /// 记录从上一次调用到现在的耗时
+    pub fn mark(&self, phase: &str) {
+        let elapsed = {
+            let mut last = self.last.lock();
+            let elapsed = last.elapsed();
+            *last = Instant::now();
+            elapsed
+        };
+        self.phases.lock().push(PhaseTiming {
+            phase: phase.to_string(),
+            bucket_ms: timing_bucket(elapsed),
+        });
+    }
+
+    pub fn histogram(&self) -> Vec<PhaseTiming> {
+        self.phases.lock().clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TelemetryPayload {
+    pub user_id: String,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub crash_reports: Vec<CrashReport>,
+    pub startup: Vec<PhaseTiming>,
+}
+
+fn payload(crash_reports: Vec<CrashReport>) -> TelemetryPayload {
+    TelemetryPayload {
+        user_id: settings().user_id,
+        app_version: dirs::APP_VERSION.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        crash_reports,
+        startup: StartupTimingProfiler::global().histogram(),
+    }
+}
+
+/// 将要发送的内容，供用户预览
+pub fn build_payload() -> Result<TelemetryPayload> {
+    let reports = pending_reports()?;
+    Ok(payload(
+        reports.into_iter().map(|(_, report)| report).collect(),
+    ))
+}
+
+/// 启动完成后调用，发送成功后删除已上报的崩溃报告
+pub async fn flush() -> Result<()> {
+    let settings = settings();
+    let Some(endpoint) = settings.endpoint.filter(|_| settings.enabled) else {
+        return Ok(());
+    };
+    let (paths, reports): (Vec<_>, Vec<_>) = pending_reports()?.into_iter().unzip();
+    crate::utils::candy::get_reqwest_client()?
+        .post(&endpoint)
+        .json(&payload(reports))
+        .send()
+        .await?
+        .error_for_status()?;
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKTRACE: &str = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/abc/library/std/src/backtrace.rs:312:13
+   1: clash_nyanpasu::run::{{closure}}
+             at /home/alice/clash-nyanpasu/backend/tauri/src/lib.rs:181:33
+   2: main";
+
+    #[test]
+    fn test_crash_report_is_scrubbed() {
+        let report = AppCrashReporter::build_report(
+            Some("failed to open /home/alice/.config/clash-nyanpasu/config.yaml"),
+            Some("/home/alice/clash-nyanpasu/backend/tauri/src/lib.rs:181:33"),
+            BACKTRACE,
+        );
+        assert_eq!(report.location.as_deref(), Some("lib.rs:181:33"));
+        assert_eq!(
+            scrub_location(r"C:\Users\alice\src\main.rs:3:5"),
+            "main.rs:3:5"
+        );
+        assert_eq!(report.backtrace_hashes.len(), 3);
+        assert!(report.backtrace_hashes.iter().all(|hash| hash.len() == 16));
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(!json.contains("alice"), "{json}");
+        assert!(!json.contains("config.yaml"), "{json}");
+    }
+
+    #[test]
+    fn test_backtrace_hashes_ignore_paths() {
+        let other = BACKTRACE.replace("/home/alice", "/Users/bob");
+        assert_eq!(backtrace_hashes(BACKTRACE), backtrace_hashes(&other));
+    }
+
+    #[test]
+    fn test_hash_path() {
+        let hash = hash_path_for_telemetry(Path::new("/home/alice/.config"));
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains("alice"));
+        assert_eq!(
+            hash,
+            hash_path_for_telemetry(Path::new("/home/alice/.config"))
+        );
+    }
+
+    #[test]
+    fn test_timing_bucket() {
+        assert_eq!(timing_bucket(Duration::from_millis(40)), 100);
+        assert_eq!(timing_bucket(Duration::from_millis(1000)), 1000);
+        assert_eq!(timing_bucket(Duration::from_millis(1001)), 2500);
+        assert_eq!(timing_bucket(Duration::from_secs(30)), u64::MAX);
+    }
+}
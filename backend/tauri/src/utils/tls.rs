@@ -0,0 +1,383 @@
+//! 自定义 CA 与证书指纹校验，用于订阅与更新下载
+//! 企业代理常用自签根证书做 TLS 中间人，把它们加入信任即可，不需要关闭证书校验
+use crate::config::Config;
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use reqwest::{Certificate, ClientBuilder, Response, tls::TlsInfo};
+use rustls::{
+    DigitallySignedStruct, SignatureScheme,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use specta::Type;
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use url::Url;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+static CUSTOM_CA: Lazy<RwLock<Vec<Certificate>>> = Lazy::new(Default::default);
+
+/// 读取 PEM 文件中的全部证书，文件无效或没有证书时报错
+pub fn load_custom_ca(paths: &[PathBuf]) -> Result<Vec<Certificate>> {
+    let mut certs = Vec::new();
+    for path in paths {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("failed to read CA certificate {}", path.display()))?;
+        let bundle = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("invalid PEM certificate {}", path.display()))?;
+        if bundle.is_empty() {
+            bail!("no certificate found in {}", path.display());
+        }
+        certs.extend(bundle);
+    }
+    Ok(certs)
+}
+
+/// 重新加载自定义 CA，失败时保留原有的证书，供应用配置时校验
+pub fn reload_custom_ca(paths: &[PathBuf]) -> Result<()> {
+    let certs = load_custom_ca(paths)?;
+    *CUSTOM_CA.write() = certs;
+    Ok(())
+}
+
+/// 启动时加载配置中的自定义 CA
+pub fn init_custom_ca() -> Result<()> {
+    let paths = Config::verge()
+        .latest()
+        .custom_ca_certs
+        .clone()
+        .unwrap_or_default();
+    reload_custom_ca(&paths)
+}
+
+pub trait CustomCaExt {
+    /// 信任配置中的自定义 CA
+    fn with_custom_ca(self) -> Self;
+}
+
+impl CustomCaExt for ClientBuilder {
+    fn with_custom_ca(self) -> Self {
+        CUSTOM_CA
+            .read()
+            .iter()
+            .cloned()
+            .fold(self, |builder, cert| builder.add_root_certificate(cert))
+    }
+}
+
+/// 解析 hex 指纹，允许冒号或空格分隔
+pub fn parse_fingerprint(pin: &str) -> Result<[u8; 32]> {
+    let hex = pin
+        .chars()
+        .filter(|c| !matches!(c, ':' | ' '))
+        .collect::<String>();
+    hex::decode(&hex)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("invalid SHA-256 fingerprint `{pin}`"))
+}
+
+pub fn fingerprint(der: &[u8]) -> String {
+    hex::encode(Sha256::digest(der))
+}
+
+/// 检查响应的叶子证书指纹，没有 TLS 信息时同样视为不匹配
+/// 客户端需要开启 `tls_info`
+pub fn verify_pin(resp: &Response, pin: &str) -> Result<()> {
+    let expected = parse_fingerprint(pin)?;
+    let Some(leaf) = resp
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+    else {
+        bail!("no TLS certificate presented by {}", resp.url());
+    };
+    let actual = Sha256::digest(leaf);
+    if actual.as_slice() != expected {
+        bail!(
+            "certificate fingerprint mismatch for {}: expected {}, got {}",
+            resp.url(),
+            hex::encode(expected),
+            hex::encode(actual)
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct PresentedCertificate {
+    /// DER 的 SHA-256，可直接用作 `pin_sha256`
+    pub sha256: String,
+    pub pem: String,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct TlsProbe {
+    pub host: String,
+    pub port: u16,
+    /// 服务器发送的证书链，第一个为叶子证书
+    pub chain: Vec<PresentedCertificate>,
+    /// 使用系统与自定义 CA 校验失败的原因，为空表示证书受信任
+    pub trust_error: Option<String>,
+}
+
+/// 只记录证书链，不做任何校验，仅用于探测
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn to_pem(der: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der);
+    let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+        pem.push('\n');
+    }
+    pem.push_str("-----END CERTIFICATE-----\n");
+    pem
+}
+
+/// 直连服务器完成握手，返回其发送的证书链
+async fn presented_chain(host: &str, port: u16) -> Result<Vec<PresentedCertificate>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(host.to_string())?;
+    let connect = async {
+        let stream = tokio::net::TcpStream::connect((host, port)).await?;
+        tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await
+    };
+    let stream = tokio::time::timeout(PROBE_TIMEOUT, connect)
+        .await
+        .context("TLS handshake timed out")??;
+    let (_, conn) = stream.get_ref();
+    Ok(conn
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .map(|cert| PresentedCertificate {
+            sha256: fingerprint(cert),
+            pem: to_pem(cert),
+        })
+        .collect())
+}
+
+/// 探测服务器发送的证书链，并检查当前的信任配置能否通过校验
+pub async fn probe_tls(url: &Url) -> Result<TlsProbe> {
+    if url.scheme() != "https" {
+        bail!("`{url}` is not an https url");
+    }
+    let host = match url.host() {
+        Some(url::Host::Domain(domain)) => domain.to_string(),
+        Some(url::Host::Ipv4(ip)) => ip.to_string(),
+        Some(url::Host::Ipv6(ip)) => ip.to_string(),
+        None => bail!("`{url}` has no host"),
+    };
+    let port = url.port_or_known_default().unwrap_or(443);
+    let chain = presented_chain(&host, port).await?;
+    let trust_error = ClientBuilder::new()
+        .use_rustls_tls()
+        .no_proxy()
+        .with_custom_ca()
+        .timeout(PROBE_TIMEOUT)
+        .build()?
+        .head(url.as_str())
+        .send()
+        .await
+        .err()
+        .map(|e| format!("{:#}", anyhow::Error::from(e)));
+    Ok(TlsProbe {
+        host,
+        port,
+        chain,
+        trust_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::{PrivateKeyDer, pem::PemObject};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    const CA_PEM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls/ca.pem");
+    const SERVER_PEM: &[u8] = include_bytes!("../../tests/tls/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("../../tests/tls/server.key");
+
+    /// 使用测试 CA 签发的证书，对任何请求返回 `ok`
+    async fn serve_tls() -> u16 {
+        let certs = CertificateDer::pem_slice_iter(SERVER_PEM)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut buf = Vec::new();
+                    let mut chunk = [0; 1024];
+                    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut chunk).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                        }
+                    }
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                        )
+                        .await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        port
+    }
+
+    fn leaf_fingerprint() -> String {
+        let leaf = CertificateDer::pem_slice_iter(SERVER_PEM)
+            .next()
+            .unwrap()
+            .unwrap();
+        fingerprint(&leaf)
+    }
+
+    fn client(certs: Vec<Certificate>) -> reqwest::Client {
+        certs
+            .into_iter()
+            .fold(
+                ClientBuilder::new()
+                    .use_rustls_tls()
+                    .no_proxy()
+                    .tls_info(true),
+                |builder, cert| builder.add_root_certificate(cert),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_load_custom_ca() {
+        assert_eq!(load_custom_ca(&[PathBuf::from(CA_PEM)]).unwrap().len(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let invalid = dir.path().join("invalid.pem");
+        std::fs::write(&invalid, "not a certificate").unwrap();
+        assert!(load_custom_ca(&[invalid]).is_err());
+        assert!(load_custom_ca(&[dir.path().join("missing.pem")]).is_err());
+    }
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let hex = "e73eb076b97b97d2f5457b7bcd16497eb31b3020968abcf67bbf9ad39ba16953";
+        let colons = "E7:3E:B0:76:B9:7B:97:D2:F5:45:7B:7B:CD:16:49:7E:B3:1B:30:20:96:8A:BC:F6:7B:BF:9A:D3:9B:A1:69:53";
+        assert_eq!(
+            parse_fingerprint(hex).unwrap(),
+            parse_fingerprint(colons).unwrap()
+        );
+        assert!(parse_fingerprint("e73eb076").is_err());
+        assert!(parse_fingerprint("not hex").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_ca_and_pin() {
+        let port = serve_tls().await;
+        let url = format!("https://localhost:{port}/");
+
+        assert!(client(vec![]).get(&url).send().await.is_err());
+
+        let ca = load_custom_ca(&[PathBuf::from(CA_PEM)]).unwrap();
+        let resp = client(ca).get(&url).send().await.unwrap();
+        verify_pin(&resp, &leaf_fingerprint()).unwrap();
+        assert!(verify_pin(&resp, &"00".repeat(32)).is_err());
+        assert_eq!(resp.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_presented_chain() {
+        let port = serve_tls().await;
+        let chain = presented_chain("127.0.0.1", port).await.unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].sha256, leaf_fingerprint());
+        assert!(chain[0].pem.starts_with("-----BEGIN CERTIFICATE-----\n"));
+        let parsed = CertificateDer::pem_slice_iter(chain[0].pem.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(fingerprint(&parsed), chain[0].sha256);
+    }
+}
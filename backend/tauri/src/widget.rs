@@ -1,13 +1,23 @@
 use crate::config::{Config, nyanpasu::NetworkStatisticWidgetConfig};
 
-use super::core::clash::ws::ClashConnectionsConnectorEvent;
+use super::core::clash::{
+    statistics::{TrafficHistory, TrafficResolution},
+    ws::ClashConnectionsConnectorEvent,
+};
 
 use anyhow::Context;
 use nyanpasu_egui::{
-    ipc::{IpcSender, Message, StatisticMessage, create_ipc_server},
-    widget::StatisticWidgetVariant,
+    ipc::{IpcSender, Message, StatisticHistoryMessage, StatisticMessage, create_ipc_server},
+    widget::{StatisticWidgetVariant, WidgetPreferences, WidgetTheme},
+};
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64},
+    },
 };
-use std::sync::{Arc, atomic::AtomicBool};
 use tauri::{Manager, Runtime, utils::platform::current_exe};
 use tokio::{
     process::Child,
@@ -17,22 +27,48 @@ use tokio::{
     },
 };
 
+/// 推送给浮窗的历史曲线时长
+const WIDGET_HISTORY_WINDOW_SECS: u64 = 10 * 60;
+
 #[derive(Clone)]
 pub struct WidgetManager {
     instance: Arc<Mutex<Option<WidgetManagerInstance>>>,
     listener_initd: Arc<AtomicBool>,
+    last_history_frame: Arc<AtomicU64>,
+    /// 前端推送的主题，浮窗重启后重新应用
+    theme: Arc<parking_lot::Mutex<Option<WidgetTheme>>>,
+}
+
+/// 浮窗的当前状态，通过 `widget://state` 推送给前端
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, specta::Type)]
+pub struct WidgetState {
+    pub running: bool,
+    pub variant: Option<StatisticWidgetVariant>,
+    pub theme: Option<WidgetTheme>,
+    /// 最近一次请求失败的原因
+    pub error: Option<String>,
 }
 
 struct WidgetManagerInstance {
+    variant: StatisticWidgetVariant,
     tx: IpcSender<Message>,
     process: Child,
 }
 
+/// 浮窗的窗口状态文件，置顶等偏好保存在同目录下
+fn widget_state_path(variant: StatisticWidgetVariant) -> anyhow::Result<PathBuf> {
+    Ok(crate::utils::dirs::app_data_dir()
+        .context("Failed to get app data dir")?
+        .join(format!("widget_{variant}.state")))
+}
+
 impl WidgetManager {
     pub fn new() -> Self {
         Self {
             instance: Arc::new(Mutex::new(None)),
             listener_initd: Arc::new(AtomicBool::new(false)),
+            last_history_frame: Arc::new(AtomicU64::new(0)),
+            theme: Arc::new(parking_lot::Mutex::new(None)),
         }
     }
 
@@ -74,6 +110,11 @@ impl WidgetManager {
                 .as_mut()
                 .is_some_and(|instance| instance.is_alive())
         {
+            // 只有大号浮窗绘制历史曲线
+            let history = match instance.as_ref().map(|instance| instance.variant) {
+                Some(StatisticWidgetVariant::Large) => self.take_history_frame(),
+                _ => None,
+            };
             tokio::task::spawn_blocking(move || {
                 let instance = instance.as_ref().unwrap();
                 // we only care about the update event now
@@ -85,6 +126,11 @@ impl WidgetManager {
                         upload_speed: info.upload_speed,
                     }))
                     .context("Failed to send event to widget")?;
+                if let Some(history) = history {
+                    instance
+                        .send_message(Message::UpdateStatisticHistory(history))
+                        .context("Failed to send history to widget")?;
+                }
                 Ok::<(), anyhow::Error>(())
             })
             .await
@@ -93,6 +139,40 @@ impl WidgetManager {
         Ok(())
     }
 
+    /// 每个降采样周期最多推送一次历史曲线
+    fn take_history_frame(&self) -> Option<StatisticHistoryMessage> {
+        let now = TrafficHistory::now();
+        let last = self
+            .last_history_frame
+            .load(std::sync::atomic::Ordering::Acquire);
+        let resolution = TrafficResolution::TenSeconds;
+        if now.saturating_sub(last) < resolution.secs() {
+            return None;
+        }
+        self.last_history_frame
+            .store(now, std::sync::atomic::Ordering::Release);
+        let series =
+            TrafficHistory::global()
+                .lock()
+                .recent(now, WIDGET_HISTORY_WINDOW_SECS, resolution);
+        let (download, upload) = series
+            .points
+            .iter()
+            .map(|p| {
+                if p.gap {
+                    (None, None)
+                } else {
+                    (Some(p.download_avg), Some(p.upload_avg))
+                }
+            })
+            .unzip();
+        Some(StatisticHistoryMessage {
+            resolution_secs: series.resolution_secs,
+            download,
+            upload,
+        })
+    }
+
     pub async fn start(&self, widget: StatisticWidgetVariant) -> anyhow::Result<()> {
         if (self.instance.lock().await).is_some() {
             log::info!("Widget already running, stopping it first...");
@@ -105,9 +185,7 @@ impl WidgetManager {
         // spawn a process to run the widget
         let variant = format!("{widget}");
         tracing::debug!("Spawning widget process for {}...", variant);
-        let widget_win_state_path = crate::utils::dirs::app_data_dir()
-            .context("Failed to get app data dir")?
-            .join(format!("widget_{variant}.state"));
+        let widget_win_state_path = widget_state_path(widget)?;
         let mut child = tokio::process::Command::new(current_exe)
             .arg("statistic-widget")
             .arg(variant)
@@ -137,10 +215,72 @@ impl WidgetManager {
                 }
             }
         };
-        instance.replace(WidgetManagerInstance { tx, process: child });
+        let instance = instance.insert(WidgetManagerInstance {
+            variant: widget,
+            tx,
+            process: child,
+        });
+        // 新的浮窗立即拿到历史曲线
+        self.last_history_frame
+            .store(0, std::sync::atomic::Ordering::Release);
+        if let Some(theme) = *self.theme.lock() {
+            instance.send_message(Message::SetTheme(theme))?;
+        }
         Ok(())
     }
 
+    /// 同一浮窗已在运行时不做任何事，否则启动或切换到该浮窗
+    pub async fn ensure_started(&self, widget: StatisticWidgetVariant) -> anyhow::Result<()> {
+        if self.running_variant().await == Some(widget) {
+            return Ok(());
+        }
+        self.start(widget).await
+    }
+
+    /// 运行中的浮窗切换到其他样式，未运行时不做任何事
+    pub async fn change_variant(&self, widget: StatisticWidgetVariant) -> anyhow::Result<()> {
+        match self.running_variant().await {
+            Some(running) if running != widget => self.start(widget).await,
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn set_theme(&self, theme: WidgetTheme) -> anyhow::Result<()> {
+        *self.theme.lock() = Some(theme);
+        let mut instance = self.instance.clone().lock_owned().await;
+        if instance
+            .as_mut()
+            .is_some_and(|instance| instance.is_alive())
+        {
+            return tokio::task::spawn_blocking(move || {
+                instance
+                    .as_ref()
+                    .unwrap()
+                    .send_message(Message::SetTheme(theme))
+            })
+            .await
+            .context("Failed to send message to widget")?;
+        }
+        Ok(())
+    }
+
+    async fn running_variant(&self) -> Option<StatisticWidgetVariant> {
+        let mut instance = self.instance.lock().await;
+        instance
+            .as_mut()
+            .and_then(|instance| instance.is_alive().then_some(instance.variant))
+    }
+
+    pub async fn state(&self) -> WidgetState {
+        let variant = self.running_variant().await;
+        WidgetState {
+            running: variant.is_some(),
+            variant,
+            theme: *self.theme.lock(),
+            error: None,
+        }
+    }
+
     pub async fn stop(&self) -> anyhow::Result<()> {
         let Some(mut instance) = self.instance.lock().await.take() else {
             tracing::debug!("Widget instance is not exists, skipping...");
@@ -175,6 +315,32 @@ impl WidgetManager {
         Ok(())
     }
 
+    /// 运行中的浮窗直接切换，否则只保存偏好，下次启动时生效
+    pub async fn set_always_on_top(
+        &self,
+        variant: StatisticWidgetVariant,
+        enabled: bool,
+    ) -> anyhow::Result<()> {
+        let mut instance = self.instance.clone().lock_owned().await;
+        if instance
+            .as_mut()
+            .is_some_and(|instance| instance.variant == variant && instance.is_alive())
+        {
+            return tokio::task::spawn_blocking(move || {
+                instance
+                    .as_ref()
+                    .unwrap()
+                    .send_message(Message::SetAlwaysOnTop(enabled))
+            })
+            .await
+            .context("Failed to send message to widget")?;
+        }
+        let path = WidgetPreferences::path_of(&widget_state_path(variant)?);
+        let mut prefs = WidgetPreferences::load(&path);
+        prefs.always_on_top = Some(enabled);
+        prefs.save(&path)
+    }
+
     pub async fn is_running(&self) -> bool {
         let mut instance = self.instance.lock().await;
         instance
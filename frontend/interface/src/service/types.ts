@@ -17,7 +17,7 @@ export interface VergeConfig {
   verge_mixed_port?: number
   enable_proxy_guard?: boolean
   proxy_guard_interval?: number
-  system_proxy_bypass?: string
+  system_proxy_bypass?: string[]
   web_ui_list?: string[]
   hotkeys?: string[]
   theme_setting?: {
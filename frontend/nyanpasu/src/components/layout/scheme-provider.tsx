@@ -12,7 +12,7 @@ const SchemeProvider = () => {
     if (!isInTauri) return
 
     const run = async () => {
-      unlistenRef.current = await listen('scheme-request-received', (req) => {
+      unlistenRef.current = await listen('deep-link', (req) => {
         const message: string = req.payload as string
 
         const url = new URL(message)
@@ -44,6 +44,9 @@ const SchemeProvider = () => {
             })
         }
       })
+      // flush links received before the listener was registered
+      const { invoke } = await import('@tauri-apps/api/core')
+      await invoke('mark_frontend_ready')
     }
     run()
     return () => {